@@ -0,0 +1,652 @@
+//! Analyse musicale d'une piste : tempo, tonalité et énergie
+//!
+//! Le signal est ramené en mono à 22050 Hz et analysé par trames de 2048
+//! échantillons (pas de 512, ~23 ms) :
+//! - tempo : flux spectral positif comme fonction d'onsets, autocorrélée ;
+//!   chaque BPM candidat est noté sur ses quatre premiers multiples de période
+//!   et pondéré par un prior perceptif centré sur 120 BPM ;
+//! - tonalité : chromagramme cumulé corrélé aux profils de Krumhansl-Kessler
+//!   des 24 tonalités majeures et mineures ;
+//! - énergie : RMS, centroïde et platitude spectrale, densité d'onsets.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::codecs::SymphoniaDecoder;
+use crate::error::AppError;
+use crate::streaming::segmenter::StreamResampler;
+
+/// Fréquence d'échantillonnage de l'analyse
+const ANALYSIS_SAMPLE_RATE: u32 = 22050;
+const FRAME_SIZE: usize = 2048;
+const HOP_SIZE: usize = 512;
+/// Durée maximale analysée (s)
+const MAX_ANALYSIS_SECS: f32 = 600.0;
+/// Plage de tempo recherchée (BPM)
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+/// Centre et largeur (en octaves) du prior de tempo
+const PRIOR_BPM: f32 = 120.0;
+const PRIOR_OCTAVES: f32 = 1.0;
+/// Multiples de la période pris en compte pour noter un tempo
+const TEMPO_HARMONICS: usize = 4;
+/// Plage de fréquences utilisée pour le chromagramme (Hz)
+const CHROMA_MIN_FREQUENCY: f32 = 55.0;
+const CHROMA_MAX_FREQUENCY: f32 = 2000.0;
+/// Compression logarithmique des magnitudes pour le flux spectral
+const FLUX_COMPRESSION: f32 = 1000.0;
+
+/// Profils de Krumhansl-Kessler, à partir de la tonique
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+const PITCH_NAMES: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+/// Mode d'une tonalité
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyMode {
+    Major,
+    Minor,
+}
+
+/// Tonalité : tonique (classe de hauteur, 0 = Do) et mode, sérialisée `"A minor"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct MusicalKey {
+    pub tonic: u8,
+    pub mode: KeyMode,
+}
+
+impl MusicalKey {
+    /// Position sur la roue de Camelot : `(1..=12, 'A' mineur | 'B' majeur)`
+    pub fn camelot(&self) -> (u8, char) {
+        match self.mode {
+            KeyMode::Major => (Self::camelot_number(self.tonic), 'B'),
+            // Même numéro que la relative majeure
+            KeyMode::Minor => (Self::camelot_number((self.tonic + 3) % 12), 'A'),
+        }
+    }
+
+    /// Notation Camelot (`8A`, `11B`...) utilisée par les DJ
+    pub fn camelot_code(&self) -> String {
+        let (number, letter) = self.camelot();
+        format!("{}{}", number, letter)
+    }
+
+    /// Mixable sans dissonance : même case, case voisine ou relative
+    pub fn is_harmonic_with(&self, other: &MusicalKey) -> bool {
+        let (a, letter_a) = self.camelot();
+        let (b, letter_b) = other.camelot();
+        let distance = (i16::from(a) - i16::from(b)).rem_euclid(12);
+        if letter_a == letter_b {
+            distance <= 1 || distance == 11
+        } else {
+            distance == 0
+        }
+    }
+
+    /// Le Do majeur est en 8B ; chaque quinte ajoute une case
+    fn camelot_number(tonic: u8) -> u8 {
+        ((u16::from(tonic) * 7 + 7) % 12) as u8 + 1
+    }
+
+    fn from_camelot(number: u8, letter: char) -> Option<Self> {
+        if !(1..=12).contains(&number) {
+            return None;
+        }
+        let major_tonic = (0..12u8).find(|&tonic| Self::camelot_number(tonic) == number)?;
+        match letter.to_ascii_uppercase() {
+            'B' => Some(Self { tonic: major_tonic, mode: KeyMode::Major }),
+            'A' => Some(Self { tonic: (major_tonic + 9) % 12, mode: KeyMode::Minor }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MusicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            KeyMode::Major => "major",
+            KeyMode::Minor => "minor",
+        };
+        write!(f, "{} {}", PITCH_NAMES[usize::from(self.tonic % 12)], mode)
+    }
+}
+
+impl FromStr for MusicalKey {
+    type Err = AppError;
+
+    /// Accepte la notation Camelot (`8A`), `A minor`, `Am`, `F#`, `Bb major`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AppError::ValidationError(format!("Tonalité invalide: {}", s));
+        let s = s.trim();
+
+        if let Some(letter) = s.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+            if let Ok(number) = s[..s.len() - 1].parse::<u8>() {
+                return Self::from_camelot(number, letter).ok_or_else(invalid);
+            }
+        }
+
+        let (note, mode) = match s.split_once(char::is_whitespace) {
+            Some((note, mode)) => match mode.trim().to_ascii_lowercase().as_str() {
+                "major" | "maj" => (note, KeyMode::Major),
+                "minor" | "min" => (note, KeyMode::Minor),
+                _ => return Err(invalid()),
+            },
+            None => match s.strip_suffix('m') {
+                Some(note) => (note, KeyMode::Minor),
+                None => (s, KeyMode::Major),
+            },
+        };
+
+        let mut chars = note.chars();
+        let natural = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => return Err(invalid()),
+        };
+        let tonic = match chars.as_str() {
+            "" => natural,
+            "#" | "♯" => natural + 1,
+            "b" | "♭" => natural + 11,
+            _ => return Err(invalid()),
+        };
+        Ok(Self { tonic: tonic % 12, mode })
+    }
+}
+
+impl From<MusicalKey> for String {
+    fn from(key: MusicalKey) -> Self {
+        key.to_string()
+    }
+}
+
+impl TryFrom<String> for MusicalKey {
+    type Error = AppError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Résultat de l'analyse musicale d'une piste
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackAnalysis {
+    /// Tempo (BPM), absent pour un signal sans pulsation
+    pub bpm: Option<f32>,
+    /// Régularité de la pulsation (0.0 - 1.0)
+    pub bpm_confidence: f32,
+    pub key: Option<MusicalKey>,
+    /// Corrélation avec le profil de la tonalité retenue (0.0 - 1.0)
+    pub key_confidence: f32,
+    /// Intensité perçue (0.0 - 1.0)
+    pub energy: f32,
+    /// Aptitude à la danse : pulsation régulière à un tempo dansant (0.0 - 1.0)
+    pub danceability: f32,
+    /// Niveau RMS moyen (dBFS)
+    pub loudness_db: f32,
+    pub spectral_centroid_hz: f32,
+    /// Platitude spectrale moyenne : 0 tonal, 1 bruit blanc
+    pub spectral_flatness: f32,
+    /// Onsets par seconde
+    pub onset_rate: f32,
+    pub duration_secs: f32,
+}
+
+/// Analyse incrémentale d'un signal entrelacé
+pub struct TrackAnalyzer {
+    channels: usize,
+    resampler: Option<StreamResampler>,
+    pending: Vec<f32>,
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    /// Classe de hauteur des bins FFT retenus pour le chromagramme
+    chroma_bins: Vec<(usize, usize)>,
+    previous_spectrum: Vec<f32>,
+    /// Flux spectral de chaque trame
+    onsets: Vec<f32>,
+    chroma: [f64; 12],
+    energy_sum: f64,
+    centroid_sum: f64,
+    flatness_sum: f64,
+    /// Trames non silencieuses, pour les moyennes spectrales
+    voiced_frames: usize,
+    max_frames: usize,
+}
+
+impl fmt::Debug for TrackAnalyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrackAnalyzer")
+            .field("channels", &self.channels)
+            .field("frames", &self.onsets.len())
+            .finish()
+    }
+}
+
+impl TrackAnalyzer {
+    pub fn new(sample_rate: u32, channels: usize) -> Result<Self, AppError> {
+        let channels = channels.max(1);
+        let resampler = if sample_rate == ANALYSIS_SAMPLE_RATE {
+            None
+        } else {
+            Some(StreamResampler::new(sample_rate, ANALYSIS_SAMPLE_RATE, 1)?)
+        };
+
+        // Fenêtre de Hann
+        let window = (0..FRAME_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME_SIZE as f32).cos())
+            .collect();
+
+        let chroma_bins = (1..=FRAME_SIZE / 2)
+            .filter_map(|bin| {
+                let frequency = bin as f32 * ANALYSIS_SAMPLE_RATE as f32 / FRAME_SIZE as f32;
+                if !(CHROMA_MIN_FREQUENCY..=CHROMA_MAX_FREQUENCY).contains(&frequency) {
+                    return None;
+                }
+                let midi = 69.0 + 12.0 * (frequency / 440.0).log2();
+                Some((bin, (midi.round() as i32).rem_euclid(12) as usize))
+            })
+            .collect();
+
+        Ok(Self {
+            channels,
+            resampler,
+            pending: Vec::new(),
+            window,
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            chroma_bins,
+            previous_spectrum: vec![0.0; FRAME_SIZE / 2 + 1],
+            onsets: Vec::new(),
+            chroma: [0.0; 12],
+            energy_sum: 0.0,
+            centroid_sum: 0.0,
+            flatness_sum: 0.0,
+            voiced_frames: 0,
+            max_frames: (MAX_ANALYSIS_SECS * ANALYSIS_SAMPLE_RATE as f32) as usize / HOP_SIZE,
+        })
+    }
+
+    /// Vrai une fois la durée maximale analysée atteinte
+    pub fn is_full(&self) -> bool {
+        self.onsets.len() >= self.max_frames
+    }
+
+    /// Ajoute des échantillons entrelacés
+    pub fn add_samples(&mut self, samples: &[f32]) -> Result<(), AppError> {
+        if self.is_full() {
+            return Ok(());
+        }
+        let mono: Vec<f32> = samples
+            .chunks_exact(self.channels)
+            .map(|frame| frame.iter().sum::<f32>() / self.channels as f32)
+            .collect();
+        let resampled = match &mut self.resampler {
+            Some(resampler) => resampler.process(&mono)?,
+            None => mono,
+        };
+        self.pending.extend_from_slice(&resampled);
+        self.consume_frames();
+        Ok(())
+    }
+
+    /// Termine l'analyse
+    pub fn finalize(mut self) -> Result<TrackAnalysis, AppError> {
+        if let Some(resampler) = &mut self.resampler {
+            let tail = resampler.flush()?;
+            self.pending.extend_from_slice(&tail);
+        }
+        // Dernière trame complétée par du silence
+        if !self.pending.is_empty() && !self.is_full() {
+            self.pending.resize(self.pending.len().max(FRAME_SIZE), 0.0);
+            self.consume_frames();
+        }
+
+        let frame_rate = ANALYSIS_SAMPLE_RATE as f32 / HOP_SIZE as f32;
+        let frames = self.onsets.len();
+        let duration_secs = frames as f32 / frame_rate;
+        let envelope = onset_envelope(&self.onsets, frame_rate);
+        let (bpm, bpm_confidence) = estimate_tempo(&envelope, frame_rate);
+        let (key, key_confidence) = estimate_key(&self.chroma);
+
+        let mean_energy = if frames > 0 { self.energy_sum / frames as f64 } else { 0.0 };
+        let loudness_db = (10.0 * mean_energy.max(1e-10).log10()) as f32;
+        let (spectral_centroid_hz, spectral_flatness) = if self.voiced_frames > 0 {
+            (
+                (self.centroid_sum / self.voiced_frames as f64) as f32,
+                (self.flatness_sum / self.voiced_frames as f64) as f32,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let onset_rate = if duration_secs > 0.0 { count_peaks(&envelope) as f32 / duration_secs } else { 0.0 };
+
+        // Intensité : niveau (-40 → 0, -6 dBFS → 1), brillance et densité d'attaques
+        let level = ((loudness_db + 40.0) / 34.0).clamp(0.0, 1.0);
+        let brightness = (spectral_centroid_hz / 4000.0).clamp(0.0, 1.0);
+        let density = (onset_rate / 8.0).clamp(0.0, 1.0);
+        let energy = 0.5 * level + 0.3 * brightness + 0.2 * density;
+
+        let danceability = match bpm {
+            Some(bpm) => {
+                let suitability = (-0.5 * ((bpm / PRIOR_BPM).log2() / 0.5).powi(2)).exp();
+                (0.6 * bpm_confidence + 0.4 * suitability).clamp(0.0, 1.0)
+            }
+            None => 0.0,
+        };
+
+        Ok(TrackAnalysis {
+            bpm,
+            bpm_confidence,
+            key,
+            key_confidence,
+            energy,
+            danceability,
+            loudness_db,
+            spectral_centroid_hz,
+            spectral_flatness,
+            onset_rate,
+            duration_secs,
+        })
+    }
+
+    fn consume_frames(&mut self) {
+        let mut start = 0;
+        while start + FRAME_SIZE <= self.pending.len() && !self.is_full() {
+            let frame: Vec<f32> = self.pending[start..start + FRAME_SIZE].to_vec();
+            self.analyze_frame(&frame);
+            start += HOP_SIZE;
+        }
+        if self.is_full() {
+            self.pending.clear();
+        } else {
+            self.pending.drain(..start);
+        }
+    }
+
+    fn analyze_frame(&mut self, frame: &[f32]) {
+        let energy = frame.iter().map(|s| f64::from(s * s)).sum::<f64>() / FRAME_SIZE as f64;
+        self.energy_sum += energy;
+
+        let mut buffer: Vec<Complex<f32>> = frame
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        let magnitudes: Vec<f32> = buffer[..=FRAME_SIZE / 2].iter().map(|c| c.norm()).collect();
+
+        // Flux spectral positif sur magnitudes compressées
+        let mut flux = 0.0;
+        for (magnitude, previous) in magnitudes.iter().zip(self.previous_spectrum.iter_mut()) {
+            let compressed = (1.0 + FLUX_COMPRESSION * magnitude).ln();
+            flux += (compressed - *previous).max(0.0);
+            *previous = compressed;
+        }
+        self.onsets.push(flux);
+
+        for &(bin, pitch_class) in &self.chroma_bins {
+            self.chroma[pitch_class] += f64::from(magnitudes[bin]);
+        }
+
+        let total: f64 = magnitudes[1..].iter().map(|&m| f64::from(m)).sum();
+        if energy > 1e-8 && total > 0.0 {
+            let bin_hz = f64::from(ANALYSIS_SAMPLE_RATE) / FRAME_SIZE as f64;
+            let centroid = magnitudes[1..]
+                .iter()
+                .enumerate()
+                .map(|(i, &m)| (i + 1) as f64 * bin_hz * f64::from(m))
+                .sum::<f64>() / total;
+            let power: Vec<f64> = magnitudes[1..].iter().map(|&m| f64::from(m * m) + 1e-12).collect();
+            let geometric = (power.iter().map(|p| p.ln()).sum::<f64>() / power.len() as f64).exp();
+            let arithmetic = power.iter().sum::<f64>() / power.len() as f64;
+            self.centroid_sum += centroid;
+            self.flatness_sum += geometric / arithmetic;
+            self.voiced_frames += 1;
+        }
+    }
+}
+
+/// Décode un fichier et l'analyse (bloquant)
+pub fn analyze_file(path: &Path) -> Result<TrackAnalysis, AppError> {
+    let mut decoder = SymphoniaDecoder::open(path)?;
+    let channels = usize::from(decoder.channels());
+    let mut analyzer = TrackAnalyzer::new(decoder.sample_rate(), channels)?;
+    while let Some(chunk) = decoder.next_chunk()? {
+        if usize::from(chunk.channels) != channels {
+            return Err(AppError::DecodingError {
+                message: "Format audio variable en cours de piste".to_string(),
+            });
+        }
+        analyzer.add_samples(&chunk.samples)?;
+        if analyzer.is_full() {
+            break;
+        }
+    }
+    analyzer.finalize()
+}
+
+/// Analyse un signal entrelacé déjà décodé
+pub fn analyze_samples(samples: &[f32], sample_rate: u32, channels: usize) -> Result<TrackAnalysis, AppError> {
+    let mut analyzer = TrackAnalyzer::new(sample_rate, channels)?;
+    analyzer.add_samples(samples)?;
+    analyzer.finalize()
+}
+
+/// Fonction d'onsets centrée sur sa moyenne locale (~1 s) et redressée
+fn onset_envelope(flux: &[f32], frame_rate: f32) -> Vec<f32> {
+    let half = (frame_rate / 2.0) as usize;
+    let mut prefix = vec![0.0f64; flux.len() + 1];
+    for (i, value) in flux.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + f64::from(*value);
+    }
+    (0..flux.len())
+        .map(|i| {
+            let (start, end) = (i.saturating_sub(half), (i + half + 1).min(flux.len()));
+            let mean = (prefix[end] - prefix[start]) / (end - start) as f64;
+            (flux[i] - mean as f32).max(0.0)
+        })
+        .collect()
+}
+
+/// Tempo le plus probable et régularité de la pulsation
+fn estimate_tempo(envelope: &[f32], frame_rate: f32) -> (Option<f32>, f32) {
+    let max_lag = ((60.0 * frame_rate / MIN_BPM) * TEMPO_HARMONICS as f32).ceil() as usize + 1;
+    if envelope.len() <= max_lag * 2 {
+        return (None, 0.0);
+    }
+
+    let autocorrelation: Vec<f32> = (0..=max_lag)
+        .map(|lag| {
+            let sum: f64 = envelope[lag..]
+                .iter()
+                .zip(envelope)
+                .map(|(a, b)| f64::from(a * b))
+                .sum();
+            (sum / (envelope.len() - lag) as f64) as f32
+        })
+        .collect();
+    if autocorrelation[0] <= f32::EPSILON {
+        return (None, 0.0);
+    }
+    let at = |lag: f32| {
+        let index = lag.floor() as usize;
+        let fraction = lag - index as f32;
+        autocorrelation[index] * (1.0 - fraction) + autocorrelation[index + 1] * fraction
+    };
+
+    let mut best: Option<(f32, f32, f32)> = None;
+    let steps = ((MAX_BPM - MIN_BPM) * 10.0) as usize;
+    for step in 0..=steps {
+        let bpm = MIN_BPM + step as f32 / 10.0;
+        let period = 60.0 * frame_rate / bpm;
+        let score: f32 = (1..=TEMPO_HARMONICS).map(|k| at(period * k as f32)).sum::<f32>() / TEMPO_HARMONICS as f32;
+        let prior = (-0.5 * ((bpm / PRIOR_BPM).log2() / PRIOR_OCTAVES).powi(2)).exp();
+        if best.is_none_or(|(_, _, weighted)| score * prior > weighted) {
+            best = Some((bpm, score, score * prior));
+        }
+    }
+
+    match best {
+        Some((bpm, score, _)) if score > 0.0 => (Some(bpm), (score / autocorrelation[0]).clamp(0.0, 1.0)),
+        _ => (None, 0.0),
+    }
+}
+
+/// Tonalité dont le profil est le mieux corrélé au chromagramme
+fn estimate_key(chroma: &[f64; 12]) -> (Option<MusicalKey>, f32) {
+    if chroma.iter().sum::<f64>() <= f64::EPSILON {
+        return (None, 0.0);
+    }
+    let chroma: Vec<f32> = chroma.iter().map(|&v| v as f32).collect();
+
+    let mut best: Option<(MusicalKey, f32)> = None;
+    for tonic in 0..12u8 {
+        for (mode, profile) in [(KeyMode::Major, &MAJOR_PROFILE), (KeyMode::Minor, &MINOR_PROFILE)] {
+            let rotated: Vec<f32> = (0..12).map(|pc| profile[(pc + 12 - usize::from(tonic)) % 12]).collect();
+            let correlation = pearson(&chroma, &rotated);
+            if best.is_none_or(|(_, current)| correlation > current) {
+                best = Some((MusicalKey { tonic, mode }, correlation));
+            }
+        }
+    }
+    match best {
+        Some((key, correlation)) => (Some(key), correlation.clamp(0.0, 1.0)),
+        None => (None, 0.0),
+    }
+}
+
+fn pearson(a: &[f32], b: &[f32]) -> f32 {
+    let n = a.len() as f32;
+    let (mean_a, mean_b) = (a.iter().sum::<f32>() / n, b.iter().sum::<f32>() / n);
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        variance_a += (x - mean_a).powi(2);
+        variance_b += (y - mean_b).powi(2);
+    }
+    if variance_a <= 0.0 || variance_b <= 0.0 {
+        return 0.0;
+    }
+    covariance / (variance_a * variance_b).sqrt()
+}
+
+/// Maxima locaux de la fonction d'onsets au-dessus de moyenne + écart-type
+fn count_peaks(envelope: &[f32]) -> usize {
+    if envelope.len() < 3 {
+        return 0;
+    }
+    let mean = envelope.iter().sum::<f32>() / envelope.len() as f32;
+    let deviation = (envelope.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / envelope.len() as f32).sqrt();
+    let threshold = mean + deviation;
+    envelope
+        .windows(3)
+        .filter(|w| w[1] > threshold && w[1] > w[0] && w[1] >= w[2])
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn midi_frequency(note: i32) -> f32 {
+        440.0 * 2f32.powf((note - 69) as f32 / 12.0)
+    }
+
+    /// Batterie minimale : un coup bref (sinus amorti) à chaque temps
+    fn click_track(bpm: f32, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        let frames = (sample_rate as f32 * seconds) as usize;
+        let beat = 60.0 / bpm;
+        (0..frames)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                let since_beat = t % beat;
+                (2.0 * std::f32::consts::PI * 1000.0 * since_beat).sin() * (-since_beat * 60.0).exp() * 0.8
+            })
+            .collect()
+    }
+
+    /// Accords (notes MIDI) de 2 s chacun, avec deux harmoniques
+    fn progression(chords: &[[i32; 3]], sample_rate: u32) -> Vec<f32> {
+        let chord_frames = 2 * sample_rate as usize;
+        let mut signal = Vec::with_capacity(chords.len() * chord_frames);
+        for chord in chords {
+            for i in 0..chord_frames {
+                let t = i as f32 / sample_rate as f32;
+                let sample: f32 = chord
+                    .iter()
+                    .map(|&note| {
+                        let f = midi_frequency(note);
+                        (1..=3)
+                            .map(|h| (2.0 * std::f32::consts::PI * f * h as f32 * t).sin() / h as f32)
+                            .sum::<f32>()
+                    })
+                    .sum();
+                signal.push(sample * 0.1);
+            }
+        }
+        signal
+    }
+
+    #[test]
+    fn test_tempo_of_click_tracks() {
+        for bpm in [95.0, 128.0, 140.0] {
+            let analysis = analyze_samples(&click_track(bpm, 44100, 30.0), 44100, 1).unwrap();
+            let detected = analysis.bpm.unwrap();
+            assert!((detected - bpm).abs() < 1.0, "{} détecté pour {}", detected, bpm);
+            assert!(analysis.bpm_confidence > 0.3, "{:?}", analysis);
+            assert!(analysis.danceability > 0.5, "{:?}", analysis);
+        }
+    }
+
+    #[test]
+    fn test_key_of_chord_progressions() {
+        // Do majeur : C - F - G - C
+        let c_major = progression(&[[60, 64, 67], [65, 69, 72], [67, 71, 74], [60, 64, 67]], 22050);
+        let analysis = analyze_samples(&c_major, 22050, 1).unwrap();
+        assert_eq!(analysis.key.unwrap().to_string(), "C major");
+
+        // La mineur : Am - Dm - E - Am
+        let a_minor = progression(&[[57, 60, 64], [62, 65, 69], [64, 68, 71], [57, 60, 64]], 22050);
+        let analysis = analyze_samples(&a_minor, 22050, 1).unwrap();
+        assert_eq!(analysis.key.unwrap().to_string(), "A minor");
+        assert!(analysis.key_confidence > 0.5);
+    }
+
+    #[test]
+    fn test_camelot_notation() {
+        let a_minor: MusicalKey = "Am".parse().unwrap();
+        assert_eq!(a_minor.camelot_code(), "8A");
+        assert_eq!("8A".parse::<MusicalKey>().unwrap(), a_minor);
+        assert_eq!("C major".parse::<MusicalKey>().unwrap().camelot_code(), "8B");
+        assert_eq!("F#".parse::<MusicalKey>().unwrap().camelot_code(), "2B");
+        assert_eq!("Bb minor".parse::<MusicalKey>().unwrap().camelot_code(), "3A");
+        for code in (1..=12).flat_map(|n| [format!("{}A", n), format!("{}B", n)]) {
+            assert_eq!(code.parse::<MusicalKey>().unwrap().camelot_code(), code);
+        }
+
+        assert!(a_minor.is_harmonic_with(&"C".parse().unwrap()));
+        assert!(a_minor.is_harmonic_with(&"Em".parse().unwrap()));
+        assert!(a_minor.is_harmonic_with(&"Dm".parse().unwrap()));
+        assert!(!a_minor.is_harmonic_with(&"F#m".parse().unwrap()));
+        assert!("1B".parse::<MusicalKey>().unwrap().is_harmonic_with(&"12B".parse().unwrap()));
+        assert!("13A".parse::<MusicalKey>().is_err());
+    }
+
+    #[test]
+    fn test_silence_has_no_tempo_or_key() {
+        let analysis = analyze_samples(&vec![0.0; 22050 * 10], 22050, 1).unwrap();
+        assert_eq!(analysis.bpm, None);
+        assert_eq!(analysis.key, None);
+        assert!(analysis.energy < 0.05);
+    }
+}
@@ -5,7 +5,12 @@ use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 use serde::{Deserialize, Serialize};
 use crate::Config;
-use tracing::{debug, info, error};
+use crate::audio::loudness::{self, LoudnessNormalization, TrackLoudness};
+use crate::audio::tags::{self, TrackTags};
+use crate::codecs::flac::{FlacEncoderImpl, STREAMINFO_SIZE};
+use crate::codecs::{AudioEncoder, CodecFactory, CodecQuality, EncoderConfig, LatencyMode};
+use crate::streaming::segmenter::{decode_file, remix_channels, resample};
+use tracing::{debug, info, error, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressionProfile {
@@ -29,6 +34,13 @@ pub enum AudioCodec {
     WAV,
 }
 
+/// Codec du profil « high » : AAC si l'encodeur libfdk-aac est compilé
+/// (fonctionnalité `fdk-aac`), MP3 sinon
+#[cfg(feature = "fdk-aac")]
+const HIGH_PROFILE_CODEC: AudioCodec = AudioCodec::AAC;
+#[cfg(not(feature = "fdk-aac"))]
+const HIGH_PROFILE_CODEC: AudioCodec = AudioCodec::MP3;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressionJob {
     pub id: String,
@@ -44,6 +56,15 @@ pub struct CompressionJob {
     pub original_size_bytes: u64,
     pub compressed_size_bytes: Option<u64>,
     pub compression_ratio: Option<f32>,
+    /// Normalisation appliquée avant encodage
+    #[serde(default)]
+    pub normalization: Option<LoudnessNormalization>,
+    /// Loudness connue de la source (mesurée à l'encodage sinon)
+    #[serde(default)]
+    pub loudness: Option<TrackLoudness>,
+    /// Tags écrits dans le fichier produit (pochettes comprises, d'où l'exclusion)
+    #[serde(skip)]
+    pub tags: Option<TrackTags>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -72,6 +93,14 @@ pub struct CompressionRequest {
     pub target_quality: String,
     pub preserve_metadata: bool,
     pub async_processing: bool,
+    #[serde(default)]
+    pub normalization: Option<LoudnessNormalization>,
+    #[serde(default)]
+    pub loudness: Option<TrackLoudness>,
+    /// Tags de la plateforme (`TrackMetadata`) ; à défaut, ceux de la source
+    /// sont recopiés quand `preserve_metadata` est demandé
+    #[serde(default)]
+    pub tags: Option<TrackTags>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,7 +138,7 @@ impl CompressionEngine {
 
         profiles.insert("high".to_string(), CompressionProfile {
             name: "High Quality".to_string(),
-            codec: AudioCodec::AAC,
+            codec: HIGH_PROFILE_CODEC,
             bitrate_kbps: 320,
             sample_rate: 44100,
             channels: 2,
@@ -155,7 +184,7 @@ impl CompressionEngine {
             name: "Podcast/Voice".to_string(),
             codec: AudioCodec::OPUS,
             bitrate_kbps: 64,
-            sample_rate: 22050,
+            sample_rate: 24000, // Opus n'accepte pas 22050 Hz
             channels: 1, // Mono pour la voix
             quality_factor: 0.8,
             compression_level: 5,
@@ -219,6 +248,11 @@ impl CompressionEngine {
         let profile = self.profiles.get(&request.target_quality)
             .ok_or_else(|| CompressionError::InvalidProfile(request.target_quality.clone()))?;
 
+        #[cfg(not(feature = "fdk-aac"))]
+        if profile.codec == AudioCodec::AAC {
+            return Err(CompressionError::UnsupportedFormat("aac (compilé sans la fonctionnalité `fdk-aac`)".to_string()));
+        }
+
         let input_path = PathBuf::from(&self.config.audio_dir).join(&request.input_file);
         if !input_path.exists() {
             return Err(CompressionError::FileNotFound(request.input_file));
@@ -238,6 +272,22 @@ impl CompressionEngine {
             .map_err(|e| CompressionError::IoError(e.to_string()))?
             .len();
 
+        let tags = match request.tags {
+            Some(tags) => Some(tags),
+            None if request.preserve_metadata => {
+                let path = input_path.clone();
+                match tokio::task::spawn_blocking(move || tags::read_tags(&path)).await {
+                    Ok(Ok(tags)) => Some(tags).filter(|tags| !tags.is_empty()),
+                    Ok(Err(e)) => {
+                        warn!("Tags de {:?} illisibles, rendu sans tags: {}", input_path, e);
+                        None
+                    }
+                    Err(e) => return Err(CompressionError::CompressionFailed(e.to_string())),
+                }
+            }
+            None => None,
+        };
+
         let job = CompressionJob {
             id: job_id.clone(),
             input_path,
@@ -252,6 +302,9 @@ impl CompressionEngine {
             original_size_bytes: original_size,
             compressed_size_bytes: None,
             compression_ratio: None,
+            normalization: request.normalization,
+            loudness: request.loudness,
+            tags,
         };
 
         // Ajouter le job à la liste active
@@ -380,7 +433,7 @@ impl CompressionEngine {
             tokio::time::sleep(Duration::from_millis(100)).await;
         }
 
-        // Exécuter la compression réelle (simulation)
+        // Exécuter la compression réelle
         let compression_result = self.perform_actual_compression(job).await;
 
         job.completed_at = Some(SystemTime::now());
@@ -402,21 +455,25 @@ impl CompressionEngine {
     }
 
     async fn perform_actual_compression(&self, job: &CompressionJob) -> Result<u64, CompressionError> {
-        // Dans une implémentation réelle, ici on utiliserait FFmpeg ou une autre bibliothèque
-        // Pour la démo, on simule la compression
-        
         debug!("Compression de {:?} vers {:?} avec le profil {:?}", 
                job.input_path, job.output_path, job.profile.name);
 
-        // Simuler la création du fichier compressé
-        let simulated_compressed_size = (job.original_size_bytes as f32 * 
-            (1.0 - job.profile.target_size_reduction)) as u64;
+        let input_path = job.input_path.clone();
+        let profile = job.profile.clone();
+        let normalization = job.normalization.map(|n| (n, job.loudness));
+        let encoded = tokio::task::spawn_blocking(move || encode_file(&input_path, &profile, normalization))
+            .await
+            .map_err(|e| CompressionError::CompressionFailed(e.to_string()))??;
 
-        // Créer un fichier de simulation
-        tokio::fs::write(&job.output_path, b"compressed_audio_data_simulation").await
+        let encoded = match &job.tags {
+            Some(job_tags) => tag_rendition(encoded, job_tags).await?,
+            None => encoded,
+        };
+
+        tokio::fs::write(&job.output_path, &encoded).await
             .map_err(|e| CompressionError::IoError(e.to_string()))?;
 
-        Ok(simulated_compressed_size)
+        Ok(encoded.len() as u64)
     }
 
     fn generate_output_filename(&self, input_filename: &str, profile: &CompressionProfile) -> String {
@@ -424,7 +481,7 @@ impl CompressionEngine {
         let stem = input_path.file_stem().unwrap_or_default().to_string_lossy();
         let extension = match profile.codec {
             AudioCodec::MP3 => "mp3",
-            AudioCodec::AAC => "aac",
+            AudioCodec::AAC => "m4a",
             AudioCodec::OGG => "ogg",
             AudioCodec::OPUS => "opus",
             AudioCodec::FLAC => "flac",
@@ -533,6 +590,121 @@ impl Clone for CompressionEngine {
     }
 }
 
+/// Écrit les tags dans un rendu. Tous les conteneurs produits par le moteur
+/// sont réécrivables : un échec fait échouer le job plutôt que de livrer un
+/// rendu sans ses tags.
+async fn tag_rendition(encoded: Vec<u8>, job_tags: &TrackTags) -> Result<Vec<u8>, CompressionError> {
+    tags::write::tag_bytes(encoded, job_tags)
+        .await
+        .map_err(|e| CompressionError::CompressionFailed(format!("Écriture des tags du rendu impossible: {}", e)))
+}
+
+/// Décode `input`, l'adapte au profil puis l'encode intégralement dans le
+/// conteneur du codec (bloquant) :
+/// - MP3 : flux de trames MPEG ;
+/// - AAC : fichier M4A (MP4 non fragmenté) ;
+/// - OPUS, OGG : Opus dans Ogg, faute d'encodeur Vorbis ;
+/// - FLAC : fichier FLAC, STREAMINFO complet ;
+/// - WAV : PCM 16 bits.
+fn encode_file(
+    input: &Path,
+    profile: &CompressionProfile,
+    normalization: Option<(LoudnessNormalization, Option<TrackLoudness>)>,
+) -> Result<Vec<u8>, CompressionError> {
+    let failed = |e: crate::error::AppError| CompressionError::CompressionFailed(e.to_string());
+
+    let pcm = decode_file(input).map_err(failed)?;
+    let channels = profile.channels.max(1);
+    let samples = remix_channels(&pcm.samples, pcm.channels, channels as usize);
+    let mut samples = resample(&samples, channels as usize, pcm.sample_rate, profile.sample_rate).map_err(failed)?;
+
+    if let Some((normalization, known)) = normalization {
+        let measured = known.unwrap_or_else(|| {
+            loudness::measure_samples(&pcm.samples, pcm.sample_rate, pcm.channels)
+        });
+        loudness::apply_gain(&mut samples, normalization.gain_db(&measured));
+    }
+
+    let config = EncoderConfig {
+        bitrate: profile.bitrate_kbps * 1000,
+        sample_rate: profile.sample_rate,
+        channels,
+        quality: CodecQuality::Custom(profile.quality_factor.clamp(0.0, 1.0)),
+        latency_mode: LatencyMode::High,
+        enable_vbr: false,
+        complexity: (profile.compression_level + 1).min(10),
+    };
+    let unsupported = |e: crate::error::AppError| CompressionError::UnsupportedFormat(e.to_string());
+
+    match profile.codec {
+        AudioCodec::MP3 => {
+            let mut encoder = CodecFactory::create_encoder("mp3", config).map_err(unsupported)?;
+            encode_samples(encoder.as_mut(), &samples, profile)
+        }
+        AudioCodec::OPUS | AudioCodec::OGG => {
+            let mut encoder = CodecFactory::create_encoder("opus", config).map_err(unsupported)?;
+            encode_samples(encoder.as_mut(), &samples, profile)
+        }
+        AudioCodec::FLAC => {
+            let mut encoder = FlacEncoderImpl::new(config).map_err(unsupported)?;
+            let mut encoded = encode_samples(&mut encoder, &samples, profile)?;
+            // Durée, tailles de trames et MD5 ne sont connus qu'en fin d'encodage
+            encoded[8..8 + STREAMINFO_SIZE].copy_from_slice(&encoder.stream_info());
+            Ok(encoded)
+        }
+        AudioCodec::WAV => {
+            let spec = hound::WavSpec {
+                channels: u16::from(channels),
+                sample_rate: profile.sample_rate,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let wav_error = |e: hound::Error| CompressionError::CompressionFailed(e.to_string());
+            let mut output = std::io::Cursor::new(Vec::new());
+            let mut writer = hound::WavWriter::new(&mut output, spec).map_err(wav_error)?;
+            for &sample in &samples {
+                writer.write_sample((sample.clamp(-1.0, 1.0) * 32767.0).round() as i16).map_err(wav_error)?;
+            }
+            writer.finalize().map_err(wav_error)?;
+            Ok(output.into_inner())
+        }
+        #[cfg(feature = "fdk-aac")]
+        AudioCodec::AAC => {
+            use crate::streaming::fmp4::{self, Fmp4Track};
+            use crate::streaming::segmenter::{create_segment_encoder, split_frames};
+
+            let (bitrate, sample_rate) = (config.bitrate, config.sample_rate);
+            let (mut encoder, codec) = create_segment_encoder("aac", config).map_err(unsupported)?;
+            let adts = encode_samples(encoder.as_mut(), &samples, profile)?;
+            let (frames, _) = split_frames(&codec, &adts);
+            let track = Fmp4Track {
+                track_id: 1,
+                timescale: sample_rate,
+                sample_rate,
+                channels: u16::from(channels),
+                codec,
+                avg_bitrate: bitrate,
+                max_bitrate: bitrate,
+            };
+            Ok(fmp4::progressive_file(&track, &frames))
+        }
+        #[cfg(not(feature = "fdk-aac"))]
+        AudioCodec::AAC => Err(CompressionError::UnsupportedFormat("aac (compilé sans la fonctionnalité `fdk-aac`)".to_string())),
+    }
+}
+
+/// Encode des échantillons entrelacés par blocs d'une seconde
+fn encode_samples(encoder: &mut dyn AudioEncoder, samples: &[f32], profile: &CompressionProfile) -> Result<Vec<u8>, CompressionError> {
+    let failed = |e: crate::error::AppError| CompressionError::CompressionFailed(e.to_string());
+    let channels = profile.channels.max(1);
+    let mut encoded = Vec::new();
+    for chunk in samples.chunks(profile.sample_rate as usize * channels as usize) {
+        encoded.extend_from_slice(&encoder.encode(chunk, profile.sample_rate, channels).map_err(failed)?);
+    }
+    encoded.extend_from_slice(&encoder.finalize().map_err(failed)?);
+    Ok(encoded)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CompressionError {
     #[error("Profile de compression invalide: {0}")]
@@ -555,4 +727,94 @@ pub enum CompressionError {
     
     #[error("Format audio non supporté: {0}")]
     UnsupportedFormat(String),
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::tags::TagFormat;
+
+    /// Source WAV stéréo d'une seconde
+    fn write_source(dir: &Path) -> PathBuf {
+        let path = dir.join("source.wav");
+        let spec = hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..44100 {
+            let sample = ((2.0 * std::f32::consts::PI * 440.0 * i as f32 / 44100.0).sin() * 16000.0) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(-sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    fn job(engine: &CompressionEngine, profile: &str, input: &Path, tags: &TrackTags) -> CompressionJob {
+        let profile = engine.profiles[profile].clone();
+        let output_name = engine.generate_output_filename("source.wav", &profile);
+        CompressionJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            input_path: input.to_path_buf(),
+            output_path: input.with_file_name(output_name),
+            profile,
+            status: JobStatus::Pending,
+            progress: 0.0,
+            created_at: SystemTime::now(),
+            started_at: None,
+            completed_at: None,
+            error_message: None,
+            original_size_bytes: 0,
+            compressed_size_bytes: None,
+            compression_ratio: None,
+            normalization: None,
+            loudness: None,
+            tags: Some(tags.clone()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_renditions_are_tagged_containers() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = write_source(dir.path());
+        let mut engine = CompressionEngine::new(Arc::new(Config::from_env().unwrap()));
+        engine.add_custom_profile("archive".to_string(), CompressionProfile {
+            name: "Archive".to_string(),
+            codec: AudioCodec::WAV,
+            bitrate_kbps: 1411,
+            sample_rate: 44100,
+            channels: 2,
+            quality_factor: 1.0,
+            compression_level: 0,
+            target_size_reduction: 0.0,
+        }).await;
+        let tags = TrackTags {
+            title: Some("Harbour Lights".into()),
+            artists: vec!["Eve".into()],
+            isrc: Some("FRZ032400002".into()),
+            ..Default::default()
+        };
+
+        let renditions = [
+            ("ultra_high", TagFormat::Flac),
+            ("medium", TagFormat::Id3),
+            ("mobile", TagFormat::Ogg),
+            ("archive", TagFormat::Wav),
+        ]
+        .into_iter()
+        .chain(cfg!(feature = "fdk-aac").then_some(("high", TagFormat::Mp4)));
+        for (profile, format) in renditions {
+            let job = job(&engine, profile, &input, &tags);
+            let size = engine.perform_actual_compression(&job).await.unwrap();
+            assert_eq!(std::fs::metadata(&job.output_path).unwrap().len(), size);
+            assert_eq!(TagFormat::detect_file(&job.output_path).unwrap(), Some(format), "{}", profile);
+
+            let read = tags::read_tags(&job.output_path).unwrap();
+            assert_eq!(read.title, tags.title, "{}", profile);
+            assert_eq!(read.artists, tags.artists, "{}", profile);
+            assert_eq!(read.isrc, tags.isrc, "{}", profile);
+
+            let pcm = decode_file(&job.output_path).unwrap();
+            let seconds = pcm.frames() as f32 / pcm.sample_rate as f32;
+            assert!((seconds - 1.0).abs() < 0.1, "{}: {} s", profile, seconds);
+        }
+    }
+}
@@ -0,0 +1,616 @@
+//! Empreintes acoustiques dans l'esprit de Chromaprint
+//!
+//! Le signal est ramené en mono à 11025 Hz puis découpé en trames de 4096
+//! échantillons (recouvrement de 2/3) dont on extrait un chromagramme à 12
+//! bandes entre 28 Hz et 3520 Hz. Seize classifieurs de type Haar, appliqués
+//! à une fenêtre glissante de 16 trames de l'image chroma, produisent chacun
+//! 2 bits en code de Gray : une sous-empreinte de 32 bits toutes les ~124 ms.
+//! Deux empreintes alignées se comparent par leur taux d'erreur binaire.
+//!
+//! `FingerprintIndex` est un index inversé valeur → (piste, position). Une
+//! recherche vote pour les décalages entre l'extrait et chaque piste puis
+//! vérifie les meilleurs candidats bit à bit : un extrait est retrouvé à
+//! l'intérieur d'une piste plus longue, et une piste à l'intérieur d'un mix.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::codecs::SymphoniaDecoder;
+use crate::error::AppError;
+use crate::streaming::segmenter::StreamResampler;
+
+/// Fréquence d'échantillonnage de l'analyse
+pub const FINGERPRINT_SAMPLE_RATE: u32 = 11025;
+/// Taille des trames FFT
+const FRAME_SIZE: usize = 4096;
+/// Pas entre deux trames (recouvrement de 2/3)
+const FRAME_HOP: usize = FRAME_SIZE / 3;
+/// Durée couverte par une sous-empreinte (s)
+pub const ITEM_DURATION_SECS: f32 = FRAME_HOP as f32 / FINGERPRINT_SAMPLE_RATE as f32;
+/// Durée maximale analysée (s)
+pub const MAX_FINGERPRINT_SECS: f32 = 1800.0;
+/// Bandes de fréquences retenues pour le chromagramme (Hz)
+const MIN_FREQUENCY: f32 = 28.0;
+const MAX_FREQUENCY: f32 = 3520.0;
+/// Fréquence de référence des octaves (La0)
+const REFERENCE_FREQUENCY: f32 = 27.5;
+const CHROMA_BANDS: usize = 12;
+/// Lissage temporel du chromagramme
+const CHROMA_FILTER: [f32; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+/// Largeur maximale (en trames) des classifieurs
+const MAX_FILTER_WIDTH: usize = 16;
+/// Taille minimale d'un segment commun, en sous-empreintes (~5 s)
+const MIN_SEGMENT_ITEMS: usize = 40;
+/// Taux d'erreur binaire maximal d'une fenêtre considérée comme concordante
+const MAX_SEGMENT_BIT_ERROR_RATE: f32 = 0.35;
+/// Valeurs trop fréquentes (silence, bruit stationnaire) ignorées au vote
+const MAX_POSTINGS_PER_VALUE: usize = 4096;
+/// Votes minimaux pour vérifier un décalage candidat
+const MIN_CANDIDATE_VOTES: u32 = 2;
+/// Décalages vérifiés par piste
+const MAX_CANDIDATES_PER_TRACK: usize = 4;
+
+/// Filtre de Haar appliqué à l'image chroma et ses seuils de quantification
+#[derive(Debug, Clone, Copy)]
+struct Classifier {
+    /// 0 : aire ; 1 : haut/bas ; 2 : avant/après ; 3 : diagonales ;
+    /// 4 : centre/bords en chroma ; 5 : centre/bords dans le temps
+    kind: u8,
+    /// Première bande chroma
+    y: usize,
+    /// Nombre de bandes chroma
+    height: usize,
+    /// Nombre de trames
+    width: usize,
+    thresholds: [f32; 3],
+}
+
+const fn classifier(kind: u8, y: usize, height: usize, width: usize, thresholds: [f32; 3]) -> Classifier {
+    Classifier { kind, y, height, width, thresholds }
+}
+
+/// Classifieurs de la configuration par défaut de Chromaprint
+const CLASSIFIERS: [Classifier; 16] = [
+    classifier(0, 4, 3, 15, [1.98215, 2.35817, 2.63523]),
+    classifier(4, 4, 6, 15, [-1.03809, -0.651211, -0.282167]),
+    classifier(1, 0, 4, 16, [-0.298702, 0.119262, 0.558497]),
+    classifier(3, 8, 2, 12, [-0.105439, 0.0153946, 0.135898]),
+    classifier(3, 4, 4, 8, [-0.142891, 0.0258736, 0.200632]),
+    classifier(4, 0, 3, 5, [-0.826319, -0.590612, -0.368214]),
+    classifier(1, 2, 2, 9, [-0.557409, -0.233035, 0.0534525]),
+    classifier(2, 7, 3, 4, [-0.0646826, 0.00620476, 0.0784847]),
+    classifier(2, 6, 2, 16, [-0.192387, -0.029699, 0.215855]),
+    classifier(2, 1, 3, 2, [-0.0397818, -0.00568076, 0.0292026]),
+    classifier(5, 10, 1, 15, [-0.53823, -0.369934, -0.190235]),
+    classifier(3, 6, 2, 10, [-0.124877, 0.0296483, 0.139239]),
+    classifier(2, 1, 1, 14, [-0.101475, 0.0225617, 0.231971]),
+    classifier(3, 5, 6, 4, [-0.0799915, -0.00729616, 0.063262]),
+    classifier(1, 9, 2, 12, [-0.272556, 0.019424, 0.302559]),
+    classifier(3, 4, 2, 14, [-0.164292, -0.0321188, 0.0846339]),
+];
+
+/// Empreinte acoustique d'une piste
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioFingerprint {
+    /// Une sous-empreinte de 32 bits par pas de `ITEM_DURATION_SECS`
+    pub sub_fingerprints: Vec<u32>,
+}
+
+impl AudioFingerprint {
+    /// Durée couverte par l'empreinte (s)
+    pub fn duration_secs(&self) -> f32 {
+        self.sub_fingerprints.len() as f32 * ITEM_DURATION_SECS
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sub_fingerprints.is_empty()
+    }
+}
+
+/// Calcul incrémental d'une empreinte à partir d'un signal entrelacé
+pub struct Fingerprinter {
+    channels: usize,
+    resampler: Option<StreamResampler>,
+    /// Signal mono à 11025 Hz en attente d'une trame complète
+    pending: Vec<f32>,
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    /// Bande chroma de chaque bin FFT retenu
+    bins: Vec<(usize, usize)>,
+    chroma: Vec<[f32; CHROMA_BANDS]>,
+    max_frames: usize,
+}
+
+impl fmt::Debug for Fingerprinter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Fingerprinter")
+            .field("channels", &self.channels)
+            .field("frames", &self.chroma.len())
+            .finish()
+    }
+}
+
+impl Fingerprinter {
+    pub fn new(sample_rate: u32, channels: usize) -> Result<Self, AppError> {
+        let channels = channels.max(1);
+        let resampler = if sample_rate == FINGERPRINT_SAMPLE_RATE {
+            None
+        } else {
+            Some(StreamResampler::new(sample_rate, FINGERPRINT_SAMPLE_RATE, 1)?)
+        };
+
+        // Fenêtre de Hamming
+        let window = (0..FRAME_SIZE)
+            .map(|i| 0.54 - 0.46 * (2.0 * std::f32::consts::PI * i as f32 / (FRAME_SIZE - 1) as f32).cos())
+            .collect();
+
+        let bins = (1..FRAME_SIZE / 2)
+            .filter_map(|bin| {
+                let frequency = bin as f32 * FINGERPRINT_SAMPLE_RATE as f32 / FRAME_SIZE as f32;
+                if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency) {
+                    return None;
+                }
+                let octave = (frequency / REFERENCE_FREQUENCY).log2();
+                let band = ((octave - octave.floor()) * CHROMA_BANDS as f32) as usize;
+                Some((bin, band.min(CHROMA_BANDS - 1)))
+            })
+            .collect();
+
+        Ok(Self {
+            channels,
+            resampler,
+            pending: Vec::new(),
+            window,
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            bins,
+            chroma: Vec::new(),
+            max_frames: (MAX_FINGERPRINT_SECS / ITEM_DURATION_SECS) as usize,
+        })
+    }
+
+    /// Vrai une fois la durée maximale analysée atteinte
+    pub fn is_full(&self) -> bool {
+        self.chroma.len() >= self.max_frames
+    }
+
+    /// Ajoute des échantillons entrelacés
+    pub fn add_samples(&mut self, samples: &[f32]) -> Result<(), AppError> {
+        if self.is_full() {
+            return Ok(());
+        }
+        let mono: Vec<f32> = samples
+            .chunks_exact(self.channels)
+            .map(|frame| frame.iter().sum::<f32>() / self.channels as f32)
+            .collect();
+        let resampled = match &mut self.resampler {
+            Some(resampler) => resampler.process(&mono)?,
+            None => mono,
+        };
+        self.pending.extend_from_slice(&resampled);
+        self.consume_frames();
+        Ok(())
+    }
+
+    /// Termine l'analyse et calcule les sous-empreintes
+    pub fn finalize(mut self) -> Result<AudioFingerprint, AppError> {
+        if let Some(resampler) = &mut self.resampler {
+            let tail = resampler.flush()?;
+            self.pending.extend_from_slice(&tail);
+            self.consume_frames();
+        }
+        Ok(AudioFingerprint {
+            sub_fingerprints: sub_fingerprints(&self.chroma),
+        })
+    }
+
+    fn consume_frames(&mut self) {
+        let mut start = 0;
+        while start + FRAME_SIZE <= self.pending.len() && !self.is_full() {
+            let chroma = self.frame_chroma(&self.pending[start..start + FRAME_SIZE]);
+            self.chroma.push(chroma);
+            start += FRAME_HOP;
+        }
+        if self.is_full() {
+            self.pending.clear();
+        } else {
+            self.pending.drain(..start);
+        }
+    }
+
+    /// Énergie de chaque classe de hauteur dans une trame
+    fn frame_chroma(&self, frame: &[f32]) -> [f32; CHROMA_BANDS] {
+        let mut buffer: Vec<Complex<f32>> = frame
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+
+        let mut chroma = [0.0; CHROMA_BANDS];
+        for &(bin, band) in &self.bins {
+            chroma[band] += buffer[bin].norm_sqr();
+        }
+        chroma
+    }
+}
+
+/// Décode un fichier et calcule son empreinte (bloquant)
+pub fn analyze_file(path: &Path) -> Result<AudioFingerprint, AppError> {
+    let mut decoder = SymphoniaDecoder::open(path)?;
+    let channels = usize::from(decoder.channels());
+    let mut fingerprinter = Fingerprinter::new(decoder.sample_rate(), channels)?;
+    while let Some(chunk) = decoder.next_chunk()? {
+        if usize::from(chunk.channels) != channels {
+            return Err(AppError::DecodingError {
+                message: "Format audio variable en cours de piste".to_string(),
+            });
+        }
+        fingerprinter.add_samples(&chunk.samples)?;
+        if fingerprinter.is_full() {
+            break;
+        }
+    }
+    fingerprinter.finalize()
+}
+
+/// Empreinte d'un signal entrelacé déjà décodé
+pub fn fingerprint_samples(samples: &[f32], sample_rate: u32, channels: usize) -> Result<AudioFingerprint, AppError> {
+    let mut fingerprinter = Fingerprinter::new(sample_rate, channels)?;
+    fingerprinter.add_samples(samples)?;
+    fingerprinter.finalize()
+}
+
+/// Lisse, normalise puis classe le chromagramme
+fn sub_fingerprints(chroma: &[[f32; CHROMA_BANDS]]) -> Vec<u32> {
+    if chroma.len() < CHROMA_FILTER.len() {
+        return Vec::new();
+    }
+
+    let image: Vec<[f32; CHROMA_BANDS]> = chroma
+        .windows(CHROMA_FILTER.len())
+        .map(|frames| {
+            let mut row = [0.0; CHROMA_BANDS];
+            for (frame, coefficient) in frames.iter().zip(CHROMA_FILTER) {
+                for (value, energy) in row.iter_mut().zip(frame) {
+                    *value += coefficient * energy;
+                }
+            }
+            let norm = row.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if norm < 0.01 {
+                [0.0; CHROMA_BANDS]
+            } else {
+                row.map(|v| v / norm)
+            }
+        })
+        .collect();
+
+    if image.len() < MAX_FILTER_WIDTH {
+        return Vec::new();
+    }
+
+    let integral = IntegralImage::new(&image);
+    (0..=image.len() - MAX_FILTER_WIDTH)
+        .map(|x| {
+            CLASSIFIERS.iter().enumerate().fold(0u32, |bits, (i, classifier)| {
+                bits | (classifier.classify(&integral, x) << (2 * i))
+            })
+        })
+        .collect()
+}
+
+/// Image intégrale (temps × chroma) pour les sommes sur rectangle
+struct IntegralImage {
+    sums: Vec<[f64; CHROMA_BANDS + 1]>,
+}
+
+impl IntegralImage {
+    fn new(image: &[[f32; CHROMA_BANDS]]) -> Self {
+        let mut sums = vec![[0.0; CHROMA_BANDS + 1]; image.len() + 1];
+        for (t, row) in image.iter().enumerate() {
+            for (c, value) in row.iter().enumerate() {
+                sums[t + 1][c + 1] = f64::from(*value) + sums[t][c + 1] + sums[t + 1][c] - sums[t][c];
+            }
+        }
+        Self { sums }
+    }
+
+    /// Somme des trames `x1..x2` et des bandes `y1..y2`
+    fn area(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> f64 {
+        self.sums[x2][y2] - self.sums[x1][y2] - self.sums[x2][y1] + self.sums[x1][y1]
+    }
+}
+
+impl Classifier {
+    /// Valeur quantifiée sur 2 bits (code de Gray) du filtre à la trame `x`
+    fn classify(&self, image: &IntegralImage, x: usize) -> u32 {
+        let value = self.apply(image, x) as f32;
+        let level = self.thresholds.iter().take_while(|&&threshold| value >= threshold).count();
+        [0, 1, 3, 2][level]
+    }
+
+    fn apply(&self, image: &IntegralImage, x: usize) -> f64 {
+        let (y, w, h) = (self.y, self.width, self.height);
+        let area = |x1, y1, x2, y2| image.area(x1, y1, x2, y2);
+        let (a, b) = match self.kind {
+            0 => (area(x, y, x + w, y + h), 0.0),
+            1 => {
+                let h2 = h / 2;
+                (area(x, y + h2, x + w, y + h), area(x, y, x + w, y + h2))
+            }
+            2 => {
+                let w2 = w / 2;
+                (area(x + w2, y, x + w, y + h), area(x, y, x + w2, y + h))
+            }
+            3 => {
+                let (w2, h2) = (w / 2, h / 2);
+                (
+                    area(x, y + h2, x + w2, y + h) + area(x + w2, y, x + w, y + h2),
+                    area(x, y, x + w2, y + h2) + area(x + w2, y + h2, x + w, y + h),
+                )
+            }
+            4 => {
+                let h3 = h / 3;
+                (
+                    area(x, y, x + w, y + h3) + area(x, y + 2 * h3, x + w, y + h),
+                    area(x, y + h3, x + w, y + 2 * h3),
+                )
+            }
+            _ => {
+                let w3 = w / 3;
+                (
+                    area(x, y, x + w3, y + h) + area(x + 2 * w3, y, x + w, y + h),
+                    area(x + w3, y, x + 2 * w3, y + h),
+                )
+            }
+        };
+        (1.0 + a).ln() - (1.0 + b).ln()
+    }
+}
+
+/// Segment commun entre un extrait et une piste indexée
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FingerprintMatch {
+    pub track_id: String,
+    /// `1 - taux d'erreur binaire` sur le segment commun (0.5 pour deux signaux sans rapport)
+    pub similarity: f32,
+    /// Début du segment dans l'extrait recherché (s)
+    pub query_offset_secs: f32,
+    /// Début du segment dans la piste indexée (s)
+    pub track_offset_secs: f32,
+    /// Durée du segment commun (s)
+    pub duration_secs: f32,
+}
+
+/// Index inversé des sous-empreintes de pistes
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintIndex {
+    tracks: Vec<Option<(String, AudioFingerprint)>>,
+    slots: HashMap<String, u32>,
+    /// Valeur de sous-empreinte → (piste, position)
+    postings: HashMap<u32, Vec<(u32, u32)>>,
+}
+
+impl FingerprintIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn get(&self, track_id: &str) -> Option<&AudioFingerprint> {
+        let slot = *self.slots.get(track_id)?;
+        self.tracks[slot as usize].as_ref().map(|(_, fingerprint)| fingerprint)
+    }
+
+    /// Pistes indexées et leurs empreintes
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AudioFingerprint)> {
+        self.tracks.iter().flatten().map(|(id, fingerprint)| (id.as_str(), fingerprint))
+    }
+
+    /// Indexe une piste, en remplaçant son ancienne empreinte
+    pub fn insert(&mut self, track_id: String, fingerprint: AudioFingerprint) {
+        self.remove(&track_id);
+        let slot = self.tracks.len() as u32;
+        for (position, value) in fingerprint.sub_fingerprints.iter().enumerate() {
+            self.postings.entry(*value).or_default().push((slot, position as u32));
+        }
+        self.slots.insert(track_id.clone(), slot);
+        self.tracks.push(Some((track_id, fingerprint)));
+    }
+
+    pub fn remove(&mut self, track_id: &str) -> Option<AudioFingerprint> {
+        let slot = self.slots.remove(track_id)?;
+        let (_, fingerprint) = self.tracks[slot as usize].take()?;
+        for value in &fingerprint.sub_fingerprints {
+            if let Some(postings) = self.postings.get_mut(value) {
+                postings.retain(|(s, _)| *s != slot);
+                if postings.is_empty() {
+                    self.postings.remove(value);
+                }
+            }
+        }
+        Some(fingerprint)
+    }
+
+    /// Pistes partageant un segment d'au moins ~5 s avec `query`, de la plus
+    /// ressemblante à la moins ressemblante
+    pub fn search(&self, query: &AudioFingerprint, min_similarity: f32) -> Vec<FingerprintMatch> {
+        // Vote pour les décalages (position dans la piste - position dans l'extrait)
+        let mut votes: HashMap<(u32, i64), u32> = HashMap::new();
+        for (position, value) in query.sub_fingerprints.iter().enumerate() {
+            let Some(postings) = self.postings.get(value) else { continue };
+            if postings.len() > MAX_POSTINGS_PER_VALUE {
+                continue;
+            }
+            for &(slot, track_position) in postings {
+                *votes.entry((slot, i64::from(track_position) - position as i64)).or_default() += 1;
+            }
+        }
+
+        let mut candidates: Vec<((u32, i64), u32)> = votes
+            .into_iter()
+            .filter(|(_, count)| *count >= MIN_CANDIDATE_VOTES)
+            .collect();
+        candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut checked: HashMap<u32, usize> = HashMap::new();
+        let mut best: HashMap<u32, FingerprintMatch> = HashMap::new();
+        for ((slot, offset), _) in candidates {
+            let attempts = checked.entry(slot).or_default();
+            if *attempts >= MAX_CANDIDATES_PER_TRACK {
+                continue;
+            }
+            *attempts += 1;
+
+            let Some((track_id, fingerprint)) = &self.tracks[slot as usize] else { continue };
+            let Some(segment) = align(&query.sub_fingerprints, &fingerprint.sub_fingerprints, offset) else { continue };
+            if segment.similarity < min_similarity {
+                continue;
+            }
+            let better = best.get(&slot).is_none_or(|current| {
+                (segment.duration_secs, segment.similarity) > (current.duration_secs, current.similarity)
+            });
+            if better {
+                best.insert(slot, FingerprintMatch { track_id: track_id.clone(), ..segment });
+            }
+        }
+
+        let mut matches: Vec<FingerprintMatch> = best.into_values().collect();
+        matches.sort_by(|a, b| b.similarity.total_cmp(&a.similarity).then(a.track_id.cmp(&b.track_id)));
+        matches
+    }
+}
+
+/// Plus long segment concordant entre deux empreintes décalées de `offset`
+fn align(query: &[u32], track: &[u32], offset: i64) -> Option<FingerprintMatch> {
+    let query_start = (-offset).max(0) as usize;
+    let query_end = (track.len() as i64 - offset).min(query.len() as i64);
+    if query_end - (query_start as i64) < MIN_SEGMENT_ITEMS as i64 {
+        return None;
+    }
+    let errors: Vec<u32> = (query_start..query_end as usize)
+        .map(|i| (query[i] ^ track[(i as i64 + offset) as usize]).count_ones())
+        .collect();
+
+    // Fenêtres de MIN_SEGMENT_ITEMS sous le seuil d'erreur, fusionnées en segments
+    let max_window_errors = (MAX_SEGMENT_BIT_ERROR_RATE * 32.0 * MIN_SEGMENT_ITEMS as f32) as u32;
+    let mut window_errors: u32 = errors[..MIN_SEGMENT_ITEMS].iter().sum();
+    let mut best: Option<(usize, usize)> = None;
+    let mut run_start = None;
+    for start in 0..=errors.len() - MIN_SEGMENT_ITEMS {
+        if start > 0 {
+            window_errors = window_errors - errors[start - 1] + errors[start + MIN_SEGMENT_ITEMS - 1];
+        }
+        let good = window_errors <= max_window_errors;
+        match (good, run_start) {
+            (true, None) => run_start = Some(start),
+            (false, Some(first)) => {
+                let segment = (first, start - 1 + MIN_SEGMENT_ITEMS);
+                if best.is_none_or(|(s, e)| segment.1 - segment.0 > e - s) {
+                    best = Some(segment);
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(first) = run_start {
+        let segment = (first, errors.len());
+        if best.is_none_or(|(s, e)| segment.1 - segment.0 > e - s) {
+            best = Some(segment);
+        }
+    }
+
+    let (start, end) = best?;
+    let bit_errors: u32 = errors[start..end].iter().sum();
+    let similarity = 1.0 - bit_errors as f32 / (32 * (end - start)) as f32;
+    Some(FingerprintMatch {
+        track_id: String::new(),
+        similarity,
+        query_offset_secs: (query_start + start) as f32 * ITEM_DURATION_SECS,
+        track_offset_secs: ((query_start + start) as i64 + offset) as f32 * ITEM_DURATION_SECS,
+        duration_secs: (end - start) as f32 * ITEM_DURATION_SECS,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Suite d'accords pseudo-aléatoires (un toutes les 0.5 s) avec un léger bruit
+    fn music(seed: u64, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) as u32
+        };
+        let chord_frames = sample_rate as usize / 2;
+        let frames = (sample_rate as f32 * seconds) as usize;
+        let mut signal = Vec::with_capacity(frames);
+        let mut notes = [0.0f32; 3];
+        for i in 0..frames {
+            if i % chord_frames == 0 {
+                for note in notes.iter_mut() {
+                    *note = 110.0 * 2f32.powf((next() % 36) as f32 / 12.0);
+                }
+            }
+            let t = i as f32 / sample_rate as f32;
+            let tone: f32 = notes.iter().map(|f| (2.0 * std::f32::consts::PI * f * t).sin()).sum();
+            signal.push(tone * 0.2);
+        }
+        signal
+    }
+
+    #[test]
+    fn test_excerpt_found_inside_longer_track() {
+        let original = music(1, 22050, 60.0);
+        let other = music(2, 22050, 60.0);
+
+        let mut index = FingerprintIndex::new();
+        index.insert("original".to_string(), fingerprint_samples(&original, 22050, 1).unwrap());
+        index.insert("other".to_string(), fingerprint_samples(&other, 22050, 1).unwrap());
+
+        // Extrait de 15 s à partir de 20 s, atténué, bruité et rééchantillonné
+        let mut noise = 12345u32;
+        let excerpt: Vec<f32> = original[20 * 22050..35 * 22050]
+            .iter()
+            .map(|s| {
+                noise = noise.wrapping_mul(1103515245).wrapping_add(12345);
+                s * 0.5 + ((noise >> 16) as f32 / 65536.0 - 0.5) * 0.02
+            })
+            .collect();
+        let excerpt = crate::streaming::segmenter::resample(&excerpt, 1, 22050, 44100).unwrap();
+        let query = fingerprint_samples(&excerpt, 44100, 1).unwrap();
+
+        let matches = index.search(&query, 0.75);
+        assert_eq!(matches.len(), 1, "{:?}", matches);
+        assert_eq!(matches[0].track_id, "original");
+        assert!(matches[0].similarity > 0.85, "{:?}", matches[0]);
+        assert!((matches[0].track_offset_secs - matches[0].query_offset_secs - 20.0).abs() < 0.5, "{:?}", matches[0]);
+        assert!(matches[0].duration_secs > 10.0);
+    }
+
+    #[test]
+    fn test_unrelated_audio_does_not_match() {
+        let mut index = FingerprintIndex::new();
+        index.insert("a".to_string(), fingerprint_samples(&music(3, 11025, 40.0), 11025, 1).unwrap());
+
+        let query = fingerprint_samples(&music(4, 11025, 40.0), 11025, 1).unwrap();
+        assert!(index.search(&query, 0.6).is_empty());
+
+        index.remove("a");
+        assert!(index.is_empty());
+        assert!(index.postings.is_empty());
+    }
+}
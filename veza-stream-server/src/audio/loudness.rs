@@ -0,0 +1,524 @@
+//! Mesure de loudness EBU R128 (ITU-R BS.1770-4) et normalisation
+//!
+//! `LoudnessMeter` calcule le loudness intégré (LUFS), le loudness range (LU)
+//! et le true peak (dBTP) d'un signal entrelacé. Les résultats sont stockés
+//! dans `TrackMetadata` à l'upload ; `LoudnessNormalization` en déduit le gain
+//! piste ou album à appliquer à la lecture et au transcodage.
+
+use std::f64::consts::PI;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::codecs::SymphoniaDecoder;
+use crate::error::AppError;
+
+/// Seuil de gating absolu (LUFS)
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Gate relatif du loudness intégré (LU sous la moyenne)
+const INTEGRATED_RELATIVE_GATE_LU: f64 = -10.0;
+/// Gate relatif du loudness range (LU sous la moyenne)
+const RANGE_RELATIVE_GATE_LU: f64 = -20.0;
+/// Nombre de sous-blocs de 100 ms d'un bloc momentary (400 ms)
+const MOMENTARY_SUBBLOCKS: usize = 4;
+/// Nombre de sous-blocs de 100 ms d'un bloc short-term (3 s)
+const SHORT_TERM_SUBBLOCKS: usize = 30;
+/// Pas des blocs short-term utilisés pour le LRA, en sous-blocs (1 s)
+const SHORT_TERM_HOP_SUBBLOCKS: usize = 10;
+/// Coefficients par phase du filtre d'interpolation du true peak
+const TRUE_PEAK_TAPS_PER_PHASE: usize = 12;
+
+/// Résultat de l'analyse d'une piste
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrackLoudness {
+    /// Loudness intégré (LUFS), `-70.0` ou moins pour un silence
+    pub integrated_lufs: f32,
+    /// Loudness range (LU)
+    pub loudness_range_lu: f32,
+    /// True peak (dBTP), mesuré sur signal suréchantillonné
+    pub true_peak_dbtp: f32,
+    /// Pic échantillon (dBFS)
+    pub sample_peak_dbfs: f32,
+    /// Durée analysée en secondes
+    pub duration_secs: f32,
+    /// Loudness intégré de l'album, une fois tout l'album analysé
+    #[serde(default)]
+    pub album_lufs: Option<f32>,
+    /// True peak de l'album (dBTP)
+    #[serde(default)]
+    pub album_true_peak_dbtp: Option<f32>,
+}
+
+/// Gain appliqué à la lecture ou au transcodage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum GainMode {
+    /// Aucun gain
+    Off,
+    /// Chaque piste est ramenée à la cible
+    #[default]
+    Track,
+    /// Gain commun à l'album, qui préserve les écarts voulus entre pistes ;
+    /// retombe sur le gain piste si l'album n'a pas été analysé
+    Album,
+}
+
+/// Paramètres de normalisation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessNormalization {
+    pub mode: GainMode,
+    /// Loudness cible (LUFS)
+    pub target_lufs: f32,
+    /// Plafond de true peak après gain (dBTP)
+    pub max_true_peak_dbtp: f32,
+}
+
+impl Default for LoudnessNormalization {
+    fn default() -> Self {
+        Self {
+            mode: GainMode::Track,
+            target_lufs: -14.0,
+            max_true_peak_dbtp: -1.0,
+        }
+    }
+}
+
+impl LoudnessNormalization {
+    /// Gain en dB pour une piste, limité pour que le true peak reste sous le plafond
+    pub fn gain_db(&self, loudness: &TrackLoudness) -> f32 {
+        let (lufs, peak) = match self.mode {
+            GainMode::Off => return 0.0,
+            GainMode::Track => (loudness.integrated_lufs, loudness.true_peak_dbtp),
+            GainMode::Album => (
+                loudness.album_lufs.unwrap_or(loudness.integrated_lufs),
+                loudness.album_true_peak_dbtp.unwrap_or(loudness.true_peak_dbtp),
+            ),
+        };
+
+        // Un silence n'a pas de loudness mesurable : on ne l'amplifie pas
+        if lufs <= ABSOLUTE_GATE_LUFS as f32 {
+            return 0.0;
+        }
+
+        (self.target_lufs - lufs).min(self.max_true_peak_dbtp - peak)
+    }
+}
+
+/// Applique un gain en dB à des échantillons
+pub fn apply_gain(samples: &mut [f32], gain_db: f32) {
+    if gain_db == 0.0 {
+        return;
+    }
+    let factor = 10f32.powf(gain_db / 20.0);
+    for sample in samples.iter_mut() {
+        *sample *= factor;
+    }
+}
+
+/// Loudness d'un album à partir de ses pistes : moyenne énergétique
+/// pondérée par la durée et true peak maximal. Renseigne `album_lufs` et
+/// `album_true_peak_dbtp` de chaque piste et renvoie `(lufs, dBTP)`.
+pub fn compute_album_loudness(tracks: &mut [TrackLoudness]) -> Option<(f32, f32)> {
+    let mut energy = 0.0f64;
+    let mut duration = 0.0f64;
+    for track in tracks.iter().filter(|t| t.integrated_lufs > ABSOLUTE_GATE_LUFS as f32) {
+        energy += f64::from(track.duration_secs) * lufs_to_energy(f64::from(track.integrated_lufs));
+        duration += f64::from(track.duration_secs);
+    }
+    if duration <= 0.0 {
+        return None;
+    }
+
+    let album_lufs = energy_to_lufs(energy / duration) as f32;
+    let album_peak = tracks.iter().map(|t| t.true_peak_dbtp).fold(f32::NEG_INFINITY, f32::max);
+    for track in tracks.iter_mut() {
+        track.album_lufs = Some(album_lufs);
+        track.album_true_peak_dbtp = Some(album_peak);
+    }
+    Some((album_lufs, album_peak))
+}
+
+/// Décode un fichier et mesure sa loudness (bloquant)
+pub fn analyze_file(path: &Path) -> Result<TrackLoudness, AppError> {
+    let mut decoder = SymphoniaDecoder::open(path)?;
+    let mut meter = LoudnessMeter::new(decoder.sample_rate(), usize::from(decoder.channels()));
+    while let Some(chunk) = decoder.next_chunk()? {
+        if chunk.sample_rate != meter.sample_rate || usize::from(chunk.channels) != meter.channels {
+            return Err(AppError::DecodingError {
+                message: "Format audio variable en cours de piste".to_string(),
+            });
+        }
+        meter.add_samples(&chunk.samples);
+    }
+    Ok(meter.finalize())
+}
+
+/// Mesure la loudness d'un signal entrelacé déjà décodé
+pub fn measure_samples(samples: &[f32], sample_rate: u32, channels: usize) -> TrackLoudness {
+    let mut meter = LoudnessMeter::new(sample_rate, channels);
+    meter.add_samples(samples);
+    meter.finalize()
+}
+
+/// Biquad en forme directe I
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    /// Filtre de pré-emphase (plateau haut) de BS.1770, recalculé pour `sample_rate`
+    fn high_shelf(sample_rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain_db = 3.999843853973347;
+        let q = 0.7071752369554196;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        }
+    }
+
+    /// Filtre passe-haut RLB de BS.1770, recalculé pour `sample_rate`
+    fn high_pass(sample_rate: f64) -> Self {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+
+        Self {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        }
+    }
+}
+
+/// État d'un biquad pour un canal
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl BiquadState {
+    fn process(&mut self, filter: &Biquad, input: f64) -> f64 {
+        let output = filter.b[0] * input + filter.b[1] * self.x[0] + filter.b[2] * self.x[1]
+            - filter.a[0] * self.y[0]
+            - filter.a[1] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// Mesureur EBU R128 incrémental
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    channels: usize,
+    channel_weights: Vec<f64>,
+    shelf: Biquad,
+    high_pass: Biquad,
+    filter_states: Vec<(BiquadState, BiquadState)>,
+    /// Frames par sous-bloc de 100 ms
+    subblock_frames: usize,
+    subblock_energy: f64,
+    subblock_position: usize,
+    /// Énergie pondérée de chaque sous-bloc de 100 ms terminé
+    subblocks: Vec<f64>,
+    oversampling: usize,
+    /// Filtre polyphase d'interpolation, une ligne par phase
+    interpolation: Vec<Vec<f64>>,
+    /// Historique d'entrée par canal pour l'interpolation
+    history: Vec<Vec<f64>>,
+    true_peak: f64,
+    sample_peak: f64,
+    frames: u64,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let rate = f64::from(sample_rate.max(1));
+        let channels = channels.max(1);
+
+        // Pondérations BS.1770 : surround arrière à +1.5 dB, LFE exclu (ordre 5.1 L R C LFE Ls Rs)
+        let channel_weights = (0..channels)
+            .map(|channel| match (channels, channel) {
+                (6, 3) => 0.0,
+                (6, 4) | (6, 5) => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+
+        let oversampling = match sample_rate {
+            0..=95_999 => 4,
+            96_000..=191_999 => 2,
+            _ => 1,
+        };
+
+        Self {
+            sample_rate,
+            channels,
+            channel_weights,
+            shelf: Biquad::high_shelf(rate),
+            high_pass: Biquad::high_pass(rate),
+            filter_states: vec![Default::default(); channels],
+            subblock_frames: ((rate / 10.0).round() as usize).max(1),
+            subblock_energy: 0.0,
+            subblock_position: 0,
+            subblocks: Vec::new(),
+            oversampling,
+            interpolation: interpolation_filter(oversampling),
+            history: vec![vec![0.0; TRUE_PEAK_TAPS_PER_PHASE]; channels],
+            true_peak: 0.0,
+            sample_peak: 0.0,
+            frames: 0,
+        }
+    }
+
+    /// Ajoute des échantillons entrelacés
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            let mut energy = 0.0;
+            for (channel, &sample) in frame.iter().enumerate() {
+                let sample = f64::from(sample);
+                self.track_peaks(channel, sample);
+
+                let (shelf_state, high_pass_state) = &mut self.filter_states[channel];
+                let weighted = high_pass_state.process(&self.high_pass, shelf_state.process(&self.shelf, sample));
+                energy += self.channel_weights[channel] * weighted * weighted;
+            }
+
+            self.subblock_energy += energy;
+            self.subblock_position += 1;
+            self.frames += 1;
+            if self.subblock_position == self.subblock_frames {
+                self.subblocks.push(self.subblock_energy / self.subblock_frames as f64);
+                self.subblock_energy = 0.0;
+                self.subblock_position = 0;
+            }
+        }
+    }
+
+    fn track_peaks(&mut self, channel: usize, sample: f64) {
+        self.sample_peak = self.sample_peak.max(sample.abs());
+
+        let history = &mut self.history[channel];
+        history.rotate_right(1);
+        history[0] = sample;
+
+        if self.oversampling == 1 {
+            self.true_peak = self.true_peak.max(sample.abs());
+            return;
+        }
+        for phase in &self.interpolation {
+            let value: f64 = phase.iter().zip(history.iter()).map(|(c, x)| c * x).sum();
+            self.true_peak = self.true_peak.max(value.abs());
+        }
+    }
+
+    /// Loudness momentary (400 ms) de chaque bloc, pas de 100 ms
+    fn block_energies(&self, subblocks_per_block: usize, hop: usize) -> Vec<f64> {
+        if self.subblocks.len() < subblocks_per_block {
+            return Vec::new();
+        }
+        (0..=self.subblocks.len() - subblocks_per_block)
+            .step_by(hop)
+            .map(|start| self.subblocks[start..start + subblocks_per_block].iter().sum::<f64>() / subblocks_per_block as f64)
+            .collect()
+    }
+
+    /// Loudness intégré avec gating absolu puis relatif (LUFS)
+    pub fn integrated_loudness(&self) -> f64 {
+        let blocks = self.block_energies(MOMENTARY_SUBBLOCKS, 1);
+        gated_mean(&blocks, INTEGRATED_RELATIVE_GATE_LU)
+            .map(energy_to_lufs)
+            .unwrap_or(f64::NEG_INFINITY)
+    }
+
+    /// Loudness range (EBU Tech 3342) : écart entre les percentiles 10 et 95
+    /// du loudness short-term, après gating
+    pub fn loudness_range(&self) -> f64 {
+        let blocks = self.block_energies(SHORT_TERM_SUBBLOCKS, SHORT_TERM_HOP_SUBBLOCKS);
+        let Some(mean) = gated_mean(&blocks, 0.0) else {
+            return 0.0;
+        };
+        let relative_gate = energy_to_lufs(mean) + RANGE_RELATIVE_GATE_LU;
+
+        let mut loudness: Vec<f64> = blocks
+            .into_iter()
+            .map(energy_to_lufs)
+            .filter(|&l| l > ABSOLUTE_GATE_LUFS && l > relative_gate)
+            .collect();
+        if loudness.is_empty() {
+            return 0.0;
+        }
+        loudness.sort_by(|a, b| a.total_cmp(b));
+
+        let percentile = |p: f64| loudness[((loudness.len() - 1) as f64 * p).round() as usize];
+        percentile(0.95) - percentile(0.10)
+    }
+
+    /// True peak (dBTP)
+    pub fn true_peak_dbtp(&self) -> f64 {
+        amplitude_to_db(self.true_peak.max(self.sample_peak))
+    }
+
+    /// Termine la mesure
+    pub fn finalize(&self) -> TrackLoudness {
+        TrackLoudness {
+            integrated_lufs: self.integrated_loudness().max(-144.0) as f32,
+            loudness_range_lu: self.loudness_range() as f32,
+            true_peak_dbtp: self.true_peak_dbtp().max(-144.0) as f32,
+            sample_peak_dbfs: amplitude_to_db(self.sample_peak).max(-144.0) as f32,
+            duration_secs: self.frames as f32 / self.sample_rate.max(1) as f32,
+            album_lufs: None,
+            album_true_peak_dbtp: None,
+        }
+    }
+}
+
+/// Moyenne des énergies au-dessus du gate absolu puis du gate relatif
+/// (`relative_gate_lu` sous la première moyenne ; 0 pour ne pas l'appliquer)
+fn gated_mean(energies: &[f64], relative_gate_lu: f64) -> Option<f64> {
+    let absolute = lufs_to_energy(ABSOLUTE_GATE_LUFS);
+    let mean_above = |threshold: f64| {
+        let gated: Vec<f64> = energies.iter().copied().filter(|&e| e > threshold).collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+    };
+
+    let mean = mean_above(absolute)?;
+    if relative_gate_lu == 0.0 {
+        return Some(mean);
+    }
+    let relative = lufs_to_energy(energy_to_lufs(mean) + relative_gate_lu);
+    mean_above(absolute.max(relative))
+}
+
+fn energy_to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+fn lufs_to_energy(lufs: f64) -> f64 {
+    10f64.powf((lufs + 0.691) / 10.0)
+}
+
+fn amplitude_to_db(amplitude: f64) -> f64 {
+    20.0 * amplitude.log10()
+}
+
+/// Sinc fenêtré (Hann) découpé en `factor` phases de `TRUE_PEAK_TAPS_PER_PHASE` coefficients
+fn interpolation_filter(factor: usize) -> Vec<Vec<f64>> {
+    let taps = factor * TRUE_PEAK_TAPS_PER_PHASE;
+    let center = (taps - 1) as f64 / 2.0;
+
+    (0..factor)
+        .map(|phase| {
+            (0..TRUE_PEAK_TAPS_PER_PHASE)
+                .map(|k| {
+                    let n = (k * factor + phase) as f64;
+                    let t = (n - center) / factor as f64;
+                    let sinc = if t.abs() < 1e-9 { 1.0 } else { (PI * t).sin() / (PI * t) };
+                    let window = 0.5 - 0.5 * (2.0 * PI * (n + 0.5) / taps as f64).cos();
+                    sinc * window
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(sample_rate: u32, channels: usize, frequency: f32, amplitude: f32, seconds: f32) -> Vec<f32> {
+        let frames = (sample_rate as f32 * seconds) as usize;
+        (0..frames * channels)
+            .map(|i| {
+                let t = (i / channels) as f32 / sample_rate as f32;
+                (t * frequency * 2.0 * std::f32::consts::PI).sin() * amplitude
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reference_tone_integrated_loudness() {
+        // EBU Tech 3341 : sinus 1 kHz à -20 dBFS sur les deux canaux → -20 LUFS
+        let amplitude = 10f32.powf(-20.0 / 20.0);
+        for sample_rate in [44100, 48000] {
+            let mut meter = LoudnessMeter::new(sample_rate, 2);
+            meter.add_samples(&sine(sample_rate, 2, 1000.0, amplitude, 20.0));
+            let loudness = meter.finalize();
+
+            assert!((loudness.integrated_lufs + 20.0).abs() < 0.1, "{:?}", loudness);
+            assert!(loudness.loudness_range_lu < 0.1);
+            assert!((loudness.sample_peak_dbfs + 20.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_loudness_range_of_two_levels() {
+        // Tech 3342 : 20 s à -20 dBFS puis 20 s à -30 dBFS → LRA de 10 LU
+        let mut signal = sine(48000, 2, 1000.0, 10f32.powf(-20.0 / 20.0), 20.0);
+        signal.extend(sine(48000, 2, 1000.0, 10f32.powf(-30.0 / 20.0), 20.0));
+
+        let mut meter = LoudnessMeter::new(48000, 2);
+        meter.add_samples(&signal);
+        assert!((meter.loudness_range() - 10.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_true_peak_exceeds_sample_peak() {
+        // Sinus à fs/4 déphasé de 45° : les échantillons tombent à ±0.707 de la crête
+        let samples: Vec<f32> = (0..48000)
+            .map(|i| if i % 4 < 2 { std::f32::consts::FRAC_1_SQRT_2 } else { -std::f32::consts::FRAC_1_SQRT_2 })
+            .collect();
+        let mut meter = LoudnessMeter::new(48000, 1);
+        meter.add_samples(&samples);
+        let loudness = meter.finalize();
+
+        assert!((loudness.sample_peak_dbfs + 3.01).abs() < 0.05);
+        assert!(loudness.true_peak_dbtp > -0.5, "{:?}", loudness);
+    }
+
+    #[test]
+    fn test_gain_respects_true_peak_ceiling() {
+        let mut tracks = [
+            TrackLoudness {
+                integrated_lufs: -20.0,
+                loudness_range_lu: 5.0,
+                true_peak_dbtp: -6.0,
+                sample_peak_dbfs: -6.0,
+                duration_secs: 180.0,
+                album_lufs: None,
+                album_true_peak_dbtp: None,
+            },
+            TrackLoudness {
+                integrated_lufs: -10.0,
+                loudness_range_lu: 3.0,
+                true_peak_dbtp: -0.5,
+                sample_peak_dbfs: -0.5,
+                duration_secs: 180.0,
+                album_lufs: None,
+                album_true_peak_dbtp: None,
+            },
+        ];
+
+        let track = LoudnessNormalization::default();
+        assert_eq!(track.gain_db(&tracks[0]), 5.0);
+        assert_eq!(track.gain_db(&tracks[1]), -4.0);
+
+        let (album_lufs, album_peak) = compute_album_loudness(&mut tracks).unwrap();
+        assert!((album_lufs + 12.6).abs() < 0.1);
+        assert_eq!(album_peak, -0.5);
+
+        let album = LoudnessNormalization { mode: GainMode::Album, ..Default::default() };
+        assert_eq!(album.gain_db(&tracks[0]), album.gain_db(&tracks[1]));
+    }
+}
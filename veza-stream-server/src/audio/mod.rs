@@ -7,6 +7,11 @@ pub mod effects;
 pub mod realtime;
 pub mod compression;
 pub mod processing;
+pub mod loudness;
+pub mod fingerprint;
+pub mod analysis;
+pub mod spectrogram;
+pub mod tags;
 
 
 pub use realtime::*;
@@ -9,7 +9,12 @@ use tokio::sync::RwLock;
 // Imports simplifiés pour éviter les erreurs de compilation
 use serde::{Serialize, Deserialize};
 use tracing::{info, debug, warn};
+use crate::audio::spectrogram::{self, SpectrogramConfig};
+use crate::audio::tags::{self, TrackTags};
+use crate::codecs::decoder::SymphoniaDecoder;
+use crate::codecs::AudioDecoder;
 use crate::config::Config;
+use crate::error::AppError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioMetadata {
@@ -74,6 +79,43 @@ impl AudioQuality {
     }
 }
 
+/// Lit durée, format et tags d'un fichier ; le titre retombe sur le nom du
+/// fichier quand les tags n'en donnent pas
+fn read_audio_metadata(file_path: &Path) -> Result<AudioMetadata, AppError> {
+    let decoder = SymphoniaDecoder::open(file_path)?;
+    let info = decoder.info();
+    let file_metadata = std::fs::metadata(file_path)?;
+    let tags = tags::read_tags(file_path).unwrap_or_else(|e| {
+        warn!("Tags illisibles dans {}: {}", file_path.display(), e);
+        TrackTags::default()
+    });
+
+    let duration_seconds = decoder.duration().map_or(0.0, |duration| duration.as_secs_f64());
+    let audio_size = file_metadata.len().saturating_sub(tags.pictures.iter().map(|p| p.data.len() as u64).sum());
+    let bitrate_kbps = (duration_seconds > 0.0)
+        .then(|| (audio_size as f64 * 8.0 / duration_seconds / 1000.0).round() as u32);
+
+    Ok(AudioMetadata {
+        duration_seconds,
+        sample_rate: info.sample_rate,
+        channels: u32::from(info.channels),
+        bitrate_kbps,
+        codec: info.codec_name.to_uppercase(),
+        title: tags.title.clone().or_else(|| {
+            file_path.file_stem()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_string())
+        }),
+        artist: tags.artist(),
+        album: tags.album.clone(),
+        year: tags.year,
+        genre: tags.genre.clone(),
+        artwork_available: tags.cover().is_some(),
+        file_size: file_metadata.len(),
+        last_modified: file_metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioProcessor {
     _config: Arc<Config>,
@@ -107,26 +149,9 @@ impl AudioProcessor {
     }
 
     async fn extract_metadata_from_file(&self, file_path: &Path) -> Result<AudioMetadata, Box<dyn std::error::Error + Send + Sync>> {
-        // Implémentation basique - dans un vrai projet, on utiliserait symphonia
-        let file_metadata = std::fs::metadata(file_path)?;
-        
-        Ok(AudioMetadata {
-            duration_seconds: 180.0, // Valeur par défaut
-            sample_rate: 44100,
-            channels: 2,
-            bitrate_kbps: Some(320),
-            codec: "MP3".to_string(),
-            title: file_path.file_stem()
-                .and_then(|s| s.to_str())
-                .map(|s| s.to_string()),
-            artist: None,
-            album: None,
-            year: None,
-            genre: None,
-            artwork_available: false,
-            file_size: file_metadata.len(),
-            last_modified: file_metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
-        })
+        let path = file_path.to_path_buf();
+        let metadata = tokio::task::spawn_blocking(move || read_audio_metadata(&path)).await??;
+        Ok(metadata)
     }
 
     /// Génère les données de waveform pour la visualisation
@@ -164,15 +189,29 @@ impl AudioProcessor {
         })
     }
 
-    /// Analyse spectrale pour obtenir les fréquences dominantes
-    pub async fn analyze_spectrum(&self, _file_path: &Path, fft_size: usize) -> Result<Vec<f32>, Box<dyn std::error::Error + Send + Sync>> {
-        // Implémentation basique - génère des données de test
-        let spectrum: Vec<f32> = (0..fft_size/2)
-            .map(|i| {
-                let freq = i as f32 / (fft_size as f32 / 2.0);
-                (freq * 10.0).sin().abs() * (1.0 - freq).max(0.0)
-            })
+    /// Analyse spectrale pour obtenir les fréquences dominantes : amplitude
+    /// moyenne des `fft_size / 2` bins de 0 à Nyquist, normalisée sur le bin
+    /// le plus fort
+    pub async fn analyze_spectrum(&self, file_path: &Path, fft_size: usize) -> Result<Vec<f32>, Box<dyn std::error::Error + Send + Sync>> {
+        let config = SpectrogramConfig {
+            fft_size,
+            hop_size: fft_size / 2,
+            bands: fft_size / 2,
+            ..SpectrogramConfig::default()
+        };
+        let path = file_path.to_path_buf();
+        let spectrogram = tokio::task::spawn_blocking(move || spectrogram::analyze_file(&path, config)).await??;
+
+        let amplitudes: Vec<f32> = spectrogram.average_spectrum()
+            .into_iter()
+            .map(|level| 10f32.powf(level / 20.0))
             .collect();
+        let loudest = amplitudes.iter().copied().fold(0.0f32, f32::max);
+        let spectrum = if loudest > 0.0 {
+            amplitudes.into_iter().map(|amplitude| amplitude / loudest).collect()
+        } else {
+            amplitudes
+        };
         
         Ok(spectrum)
     }
@@ -320,6 +359,38 @@ impl Default for AudioProcessor {
                     ffmpeg_path: None,
                     quality_profiles: vec![],
                 },
+                storage: crate::config::StorageConfig {
+                    backend: crate::config::StorageBackend::Local,
+                    local_path: "./audio/uploads".to_string(),
+                    public_url_base: None,
+                    deduplicate: false,
+                    dedup_index_path: "./audio/dedup-index.json".to_string(),
+                    gc_grace: Duration::from_secs(86400),
+                    s3: crate::config::S3StorageConfig {
+                        endpoint: "https://s3.amazonaws.com".to_string(),
+                        region: "us-east-1".to_string(),
+                        bucket: String::new(),
+                        access_key_id: String::new(),
+                        secret_access_key: String::new(),
+                        path_style: true,
+                        prefix: String::new(),
+                        presign_expiry: Duration::from_secs(3600),
+                        multipart_part_size: 8 * 1024 * 1024,
+                    },
+                },
+                live: crate::config::LiveConfig {
+                    icecast_port: 0,
+                    rtmp_port: 0,
+                    source_idle_timeout: Duration::from_secs(10),
+                    hls_bitrates: vec![128],
+                    icy_bitrate: 128,
+                    icy_metaint: 16000,
+                    webrtc_udp_port: 0,
+                    webrtc_public_ips: vec![],
+                    webrtc_opus_bitrate: 128,
+                    ws_audio_bitrate: 128,
+                    ws_audio_max_latency: Duration::from_secs(2),
+                },
                 environment: crate::config::Environment::Development,
             }
         });
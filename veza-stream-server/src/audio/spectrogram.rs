@@ -0,0 +1,472 @@
+//! Spectrogramme : transformée de Fourier à court terme d'une piste
+//!
+//! Le signal est ramené en mono à sa fréquence d'origine (la coupure haute
+//! d'un faux fichier sans perte doit rester visible), fenêtré puis analysé
+//! par trames de `fft_size` échantillons espacées de `hop_size`. La puissance
+//! des bins est regroupée en bandes régulières sur une échelle linéaire,
+//! logarithmique ou mel, et exprimée en dBFS (une sinusoïde pleine échelle
+//! donne 0 dB).
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::codecs::SymphoniaDecoder;
+use crate::error::AppError;
+
+/// Plancher des niveaux (dBFS)
+pub const MIN_DB: f32 = -120.0;
+/// Tailles de FFT acceptées
+const MIN_FFT_SIZE: usize = 256;
+const MAX_FFT_SIZE: usize = 32768;
+const MAX_BANDS: usize = 4096;
+/// Recouvrement maximal des trames : `hop_size` d'au moins `fft_size / 8`,
+/// pour borner le nombre de FFT par seconde de signal
+const MAX_OVERLAP: usize = 8;
+/// Nombre maximal de valeurs conservées (trames × bandes, ~64 Mo)
+const MAX_CELLS: usize = 16 * 1024 * 1024;
+/// Fréquence basse par défaut de l'échelle logarithmique (Hz)
+const DEFAULT_LOG_MIN_FREQUENCY: f32 = 20.0;
+
+/// Fenêtre d'apodisation appliquée à chaque trame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowFunction {
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+    Nuttall,
+    Rectangular,
+}
+
+/// Répartition des bandes de fréquence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrequencyScale {
+    #[default]
+    Linear,
+    Log,
+    Mel,
+}
+
+/// Paramètres de l'analyse
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpectrogramConfig {
+    /// Taille de la FFT (puissance de deux)
+    pub fft_size: usize,
+    /// Pas entre deux trames, en échantillons
+    pub hop_size: usize,
+    pub window: WindowFunction,
+    pub scale: FrequencyScale,
+    /// Nombre de bandes de fréquence en sortie
+    pub bands: usize,
+    /// Fréquence basse (Hz), 0 par défaut (20 Hz en échelle logarithmique)
+    pub min_frequency: Option<f32>,
+    /// Fréquence haute (Hz), Nyquist par défaut
+    pub max_frequency: Option<f32>,
+}
+
+/// Spectrogramme calculé
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spectrogram {
+    pub sample_rate: u32,
+    pub fft_size: usize,
+    pub hop_size: usize,
+    pub window: WindowFunction,
+    pub scale: FrequencyScale,
+    /// Fréquence centrale de chaque bande (Hz), par ordre croissant
+    pub frequencies: Vec<f32>,
+    /// Durée entre deux trames (ms)
+    pub time_resolution_ms: f32,
+    /// Niveau de chaque bande par trame (dBFS, plancher `MIN_DB`)
+    pub frames: Vec<Vec<f32>>,
+    /// Vrai si la piste dépassait la taille maximale et a été tronquée
+    pub truncated: bool,
+}
+
+/// Analyse incrémentale d'un signal entrelacé
+pub struct SpectrogramAnalyzer {
+    config: SpectrogramConfig,
+    sample_rate: u32,
+    channels: usize,
+    window: Vec<f32>,
+    /// Facteur ramenant la puissance d'une sinusoïde pleine échelle à 1
+    power_scale: f32,
+    fft: Arc<dyn Fft<f32>>,
+    /// Bins (et poids) contribuant à chaque bande
+    band_bins: Vec<Vec<(usize, f32)>>,
+    frequencies: Vec<f32>,
+    pending: VecDeque<f32>,
+    frames: Vec<Vec<f32>>,
+    max_frames: usize,
+    truncated: bool,
+}
+
+impl fmt::Debug for SpectrogramAnalyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpectrogramAnalyzer")
+            .field("config", &self.config)
+            .field("sample_rate", &self.sample_rate)
+            .field("frames", &self.frames.len())
+            .finish()
+    }
+}
+
+impl Default for SpectrogramConfig {
+    fn default() -> Self {
+        Self {
+            fft_size: 2048,
+            hop_size: 1024,
+            window: WindowFunction::Hann,
+            scale: FrequencyScale::Linear,
+            bands: 256,
+            min_frequency: None,
+            max_frequency: None,
+        }
+    }
+}
+
+impl WindowFunction {
+    /// Coefficients de la fenêtre (périodique pour les fenêtres cosinus)
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        // apodize produit des fenêtres symétriques : on calcule `size + 1`
+        // points et on retire le dernier pour une fenêtre périodique
+        let periodic = |iter: apodize::CosineWindowIter| iter.take(size).map(|w| w as f32).collect();
+        match self {
+            Self::Hann => periodic(apodize::hanning_iter(size + 1)),
+            Self::Hamming => periodic(apodize::hamming_iter(size + 1)),
+            Self::Blackman => periodic(apodize::blackman_iter(size + 1)),
+            Self::Nuttall => periodic(apodize::nuttall_iter(size + 1)),
+            Self::Rectangular => vec![1.0; size],
+        }
+    }
+}
+
+impl FrequencyScale {
+    fn to_scale(self, frequency: f32) -> f32 {
+        match self {
+            Self::Linear => frequency,
+            Self::Log => frequency.ln(),
+            Self::Mel => 2595.0 * (1.0 + frequency / 700.0).log10(),
+        }
+    }
+
+    fn to_frequency(self, value: f32) -> f32 {
+        match self {
+            Self::Linear => value,
+            Self::Log => value.exp(),
+            Self::Mel => 700.0 * (10f32.powf(value / 2595.0) - 1.0),
+        }
+    }
+}
+
+impl SpectrogramConfig {
+    /// Vérifie les paramètres et renvoie la plage de fréquences effective
+    pub fn validate(&self, sample_rate: u32) -> Result<(f32, f32), AppError> {
+        let invalid = |message: String| Err(AppError::ValidationError(message));
+        if !self.fft_size.is_power_of_two() || !(MIN_FFT_SIZE..=MAX_FFT_SIZE).contains(&self.fft_size) {
+            return invalid(format!(
+                "fft_size doit être une puissance de deux entre {} et {}",
+                MIN_FFT_SIZE, MAX_FFT_SIZE
+            ));
+        }
+        if self.hop_size < self.fft_size / MAX_OVERLAP || self.hop_size > self.fft_size {
+            return invalid(format!("hop_size doit être compris entre fft_size/{} et fft_size", MAX_OVERLAP));
+        }
+        if self.bands == 0 || self.bands > MAX_BANDS {
+            return invalid(format!("bands doit être compris entre 1 et {}", MAX_BANDS));
+        }
+
+        let nyquist = sample_rate as f32 / 2.0;
+        let default_min = if self.scale == FrequencyScale::Log { DEFAULT_LOG_MIN_FREQUENCY } else { 0.0 };
+        let min = self.min_frequency.unwrap_or(default_min);
+        let max = self.max_frequency.unwrap_or(nyquist).min(nyquist);
+        if min.is_nan() || min < 0.0 || min >= max || (self.scale == FrequencyScale::Log && min <= 0.0) {
+            return invalid(format!("Plage de fréquences invalide: {} - {} Hz", min, max));
+        }
+        Ok((min, max))
+    }
+}
+
+impl SpectrogramAnalyzer {
+    pub fn new(config: SpectrogramConfig, sample_rate: u32, channels: usize) -> Result<Self, AppError> {
+        let (min_frequency, max_frequency) = config.validate(sample_rate)?;
+        let window = config.window.coefficients(config.fft_size);
+        let window_sum: f32 = window.iter().sum();
+        let bin_width = sample_rate as f32 / config.fft_size as f32;
+        let bin_count = config.fft_size / 2 + 1;
+
+        // Limites des bandes, régulières sur l'échelle choisie
+        let (low, high) = (config.scale.to_scale(min_frequency), config.scale.to_scale(max_frequency));
+        let edges: Vec<f32> = (0..=config.bands)
+            .map(|i| config.scale.to_frequency(low + (high - low) * i as f32 / config.bands as f32))
+            .collect();
+
+        let mut band_bins = Vec::with_capacity(config.bands);
+        let mut frequencies = Vec::with_capacity(config.bands);
+        for band in 0..config.bands {
+            let (lo, hi) = (edges[band], edges[band + 1]);
+            let center = config.scale.to_frequency((config.scale.to_scale(lo) + config.scale.to_scale(hi)) / 2.0);
+            frequencies.push(center);
+
+            let first = (lo / bin_width).ceil() as usize;
+            let last = ((hi / bin_width).ceil() as usize).min(bin_count);
+            let bins: Vec<(usize, f32)> = if first < last {
+                // Moyenne des bins de la bande
+                let weight = 1.0 / (last - first) as f32;
+                (first..last).map(|bin| (bin, weight)).collect()
+            } else {
+                // Bande plus étroite qu'un bin : interpolation au centre
+                let position = (center / bin_width).min((bin_count - 1) as f32);
+                let below = position.floor() as usize;
+                let fraction = position - below as f32;
+                if below + 1 < bin_count && fraction > 0.0 {
+                    vec![(below, 1.0 - fraction), (below + 1, fraction)]
+                } else {
+                    vec![(below, 1.0)]
+                }
+            };
+            band_bins.push(bins);
+        }
+
+        Ok(Self {
+            sample_rate,
+            channels: channels.max(1),
+            window,
+            power_scale: (2.0 / window_sum).powi(2),
+            fft: FftPlanner::new().plan_fft_forward(config.fft_size),
+            band_bins,
+            frequencies,
+            pending: VecDeque::new(),
+            frames: Vec::new(),
+            max_frames: (MAX_CELLS / config.bands).max(1),
+            truncated: false,
+            config,
+        })
+    }
+
+    /// Vrai une fois la taille maximale atteinte
+    pub fn is_full(&self) -> bool {
+        self.truncated
+    }
+
+    /// Ajoute des échantillons entrelacés
+    pub fn add_samples(&mut self, samples: &[f32]) {
+        if self.truncated {
+            return;
+        }
+        self.pending.extend(
+            samples
+                .chunks_exact(self.channels)
+                .map(|frame| frame.iter().sum::<f32>() / self.channels as f32),
+        );
+
+        while self.pending.len() >= self.config.fft_size && !self.truncated {
+            self.analyze_frame();
+            self.pending.drain(..self.config.hop_size);
+        }
+    }
+
+    /// Termine l'analyse (la dernière trame incomplète est complétée de zéros)
+    pub fn finalize(mut self) -> Spectrogram {
+        // Seuls les `fft_size - hop_size` premiers échantillons restants ont
+        // déjà été couverts par la trame précédente
+        let tail_unanalyzed = self.frames.is_empty() || self.pending.len() > self.config.fft_size - self.config.hop_size;
+        if !self.truncated && !self.pending.is_empty() && tail_unanalyzed {
+            self.pending.resize(self.config.fft_size, 0.0);
+            self.analyze_frame();
+        }
+
+        Spectrogram {
+            sample_rate: self.sample_rate,
+            fft_size: self.config.fft_size,
+            hop_size: self.config.hop_size,
+            window: self.config.window,
+            scale: self.config.scale,
+            frequencies: self.frequencies,
+            time_resolution_ms: self.config.hop_size as f32 * 1000.0 / self.sample_rate as f32,
+            frames: self.frames,
+            truncated: self.truncated,
+        }
+    }
+
+    fn analyze_frame(&mut self) {
+        if self.frames.len() >= self.max_frames {
+            self.truncated = true;
+            return;
+        }
+        let mut buffer: Vec<Complex<f32>> = self.pending
+            .range(..self.config.fft_size)
+            .zip(&self.window)
+            .map(|(sample, weight)| Complex::new(sample * weight, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+
+        let frame = self.band_bins
+            .iter()
+            .map(|bins| {
+                let power: f32 = bins.iter().map(|&(bin, weight)| buffer[bin].norm_sqr() * weight).sum();
+                (10.0 * (power * self.power_scale).max(1e-30).log10()).max(MIN_DB)
+            })
+            .collect();
+        self.frames.push(frame);
+    }
+}
+
+impl Spectrogram {
+    /// Nombre de trames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.frames.len() as f32 * self.time_resolution_ms / 1000.0
+    }
+
+    /// Niveau moyen de chaque bande sur toute la piste (dBFS)
+    pub fn average_spectrum(&self) -> Vec<f32> {
+        if self.frames.is_empty() {
+            return vec![MIN_DB; self.frequencies.len()];
+        }
+        (0..self.frequencies.len())
+            .map(|band| {
+                let power: f64 = self.frames.iter().map(|frame| 10f64.powf(f64::from(frame[band]) / 10.0)).sum();
+                ((10.0 * (power / self.frames.len() as f64).log10()) as f32).max(MIN_DB)
+            })
+            .collect()
+    }
+
+    /// Trames `[start, end)` regroupées en au plus `columns` colonnes (niveau
+    /// maximal de chaque bande sur les trames regroupées)
+    pub fn columns(&self, start: usize, end: usize, columns: usize) -> Vec<Vec<f32>> {
+        let end = end.min(self.frames.len());
+        if start >= end || columns == 0 {
+            return Vec::new();
+        }
+        let frames = &self.frames[start..end];
+        if frames.len() <= columns {
+            return frames.to_vec();
+        }
+        (0..columns)
+            .map(|column| {
+                let from = column * frames.len() / columns;
+                let to = ((column + 1) * frames.len() / columns).max(from + 1);
+                let mut merged = frames[from].clone();
+                for frame in &frames[from + 1..to] {
+                    for (value, level) in merged.iter_mut().zip(frame) {
+                        *value = value.max(*level);
+                    }
+                }
+                merged
+            })
+            .collect()
+    }
+}
+
+/// Décode un fichier et calcule son spectrogramme (bloquant)
+pub fn analyze_file(path: &Path, config: SpectrogramConfig) -> Result<Spectrogram, AppError> {
+    let mut decoder = SymphoniaDecoder::open(path)?;
+    let channels = usize::from(decoder.channels());
+    let mut analyzer = SpectrogramAnalyzer::new(config, decoder.sample_rate(), channels)?;
+    while let Some(chunk) = decoder.next_chunk()? {
+        if usize::from(chunk.channels) != channels {
+            return Err(AppError::DecodingError {
+                message: "Format audio variable en cours de piste".to_string(),
+            });
+        }
+        analyzer.add_samples(&chunk.samples);
+        if analyzer.is_full() {
+            break;
+        }
+    }
+    Ok(analyzer.finalize())
+}
+
+/// Spectrogramme d'un signal entrelacé déjà décodé
+pub fn analyze_samples(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: usize,
+    config: SpectrogramConfig,
+) -> Result<Spectrogram, AppError> {
+    let mut analyzer = SpectrogramAnalyzer::new(config, sample_rate, channels)?;
+    analyzer.add_samples(samples);
+    Ok(analyzer.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, sample_rate: u32, secs: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * secs) as usize)
+            .map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    fn loudest_band(spectrum: &[f32]) -> usize {
+        spectrum.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap().0
+    }
+
+    #[test]
+    fn test_sine_lands_in_expected_band_on_each_scale() {
+        let samples = sine(1000.0, 44100, 2.0);
+        for scale in [FrequencyScale::Linear, FrequencyScale::Log, FrequencyScale::Mel] {
+            let config = SpectrogramConfig { scale, bands: 128, ..SpectrogramConfig::default() };
+            let spectrogram = analyze_samples(&samples, 44100, 1, config).unwrap();
+            // 85 trames complètes, puis une dernière complétée de zéros
+            assert_eq!(spectrogram.len(), 86);
+
+            let spectrum = spectrogram.average_spectrum();
+            let band = loudest_band(&spectrum);
+            let ratio = spectrogram.frequencies[band] / 1000.0;
+            assert!((0.8..1.25).contains(&ratio), "{:?}: {} Hz", scale, spectrogram.frequencies[band]);
+            // Sinusoïde pleine échelle ≈ 0 dBFS une fois la puissance de la bande moyennée
+            assert!(spectrum[band] > -12.0 && spectrum[band] < 1.0, "{:?}: {} dB", scale, spectrum[band]);
+            // Bandes éloignées au plancher de la fenêtre
+            assert!(spectrum[spectrum.len() - 1] < -80.0);
+        }
+    }
+
+    #[test]
+    fn test_lowpassed_content_shows_cutoff() {
+        // Bruit blanc puis seulement des graves : les bandes hautes s'effondrent
+        let mut state = 1u32;
+        let noise: Vec<f32> = (0..44100)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+            })
+            .collect();
+        let config = SpectrogramConfig { window: WindowFunction::Blackman, bands: 64, ..SpectrogramConfig::default() };
+        let spectrogram = analyze_samples(&noise, 44100, 1, config.clone()).unwrap();
+        let spectrum = spectrogram.average_spectrum();
+        assert!((spectrum[60] - spectrum[5]).abs() < 6.0);
+
+        let low = sine(200.0, 44100, 1.0);
+        let spectrogram = analyze_samples(&low, 44100, 1, config).unwrap();
+        assert!(spectrogram.average_spectrum()[60] < -100.0);
+
+        let columns = spectrogram.columns(0, spectrogram.len(), 10);
+        assert_eq!(columns.len(), 10);
+        assert_eq!(columns[0].len(), 64);
+
+        let invalid = SpectrogramConfig { fft_size: 1000, ..SpectrogramConfig::default() };
+        assert!(analyze_samples(&low, 44100, 1, invalid).is_err());
+        let invalid = SpectrogramConfig { hop_size: 255, ..SpectrogramConfig::default() };
+        assert!(analyze_samples(&low, 44100, 1, invalid).is_err());
+        let densest = SpectrogramConfig { hop_size: 256, ..SpectrogramConfig::default() };
+        assert!(analyze_samples(&low, 44100, 1, densest).is_ok());
+        let invalid = SpectrogramConfig { scale: FrequencyScale::Log, min_frequency: Some(0.0), ..SpectrogramConfig::default() };
+        assert!(analyze_samples(&low, 44100, 1, invalid).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Tags ID3 (v1 et v2) des fichiers MP3, et chunks `id3 ` des WAV et AIFF
+
+use std::path::Path;
+
+use id3::frame::{Comment, ExtendedText, Lyrics, Picture, PictureType, Timestamp, UniqueFileIdentifier};
+use id3::{Frame, Tag, TagLike, Version};
+
+use super::{non_empty, parse_bpm, parse_number_pair, parse_year, EmbeddedPicture, TrackTags};
+use crate::error::AppError;
+
+/// Propriétaire UFID des identifiants d'enregistrement MusicBrainz
+const MUSICBRAINZ_UFID_OWNER: &str = "http://musicbrainz.org";
+/// Description TXXX de la licence
+const LICENSE_DESCRIPTION: &str = "LICENSE";
+
+pub fn read_mp3(path: &Path) -> Result<TrackTags, AppError> {
+    convert(id3::v1v2::read_from_path(path))
+}
+
+/// Chunk `id3 ` d'un WAV ou d'un AIFF (le conteneur est détecté par `id3`)
+pub fn read_chunk(path: &Path) -> Result<TrackTags, AppError> {
+    convert(Tag::read_from_path(path))
+}
+
+fn convert(result: id3::Result<Tag>) -> Result<TrackTags, AppError> {
+    match result {
+        Ok(tag) => Ok(from_tag(&tag)),
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => Ok(TrackTags::default()),
+        // Tag partiellement lisible : on garde ce qui a pu être décodé
+        Err(id3::Error { partial_tag: Some(tag), .. }) => Ok(from_tag(&tag)),
+        Err(e) => Err(AppError::DecodingError { message: format!("Tag ID3 illisible: {}", e) }),
+    }
+}
+
+/// Convertit un tag ID3 en `TrackTags`
+pub fn from_tag(tag: &Tag) -> TrackTags {
+    let text = |id: &str| tag.get(id).and_then(|frame| frame.content().text()).and_then(non_empty);
+    let link = |id: &str| tag.get(id).and_then(|frame| frame.content().link()).and_then(non_empty);
+
+    let mut tags = TrackTags {
+        title: tag.title().and_then(non_empty),
+        artists: tag
+            .artists()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(non_empty)
+            .collect(),
+        album: tag.album().and_then(non_empty),
+        album_artist: tag.album_artist().and_then(non_empty),
+        genre: tag.genre_parsed().as_deref().and_then(non_empty),
+        year: tag
+            .date_recorded()
+            .map(|date| date.year as u32)
+            .or_else(|| tag.year().map(|year| year as u32))
+            .or_else(|| text("TDRL").as_deref().and_then(parse_year)),
+        track_number: tag.track(),
+        track_total: tag.total_tracks(),
+        disc_number: tag.disc(),
+        disc_total: tag.total_discs(),
+        composer: text("TCOM"),
+        isrc: text("TSRC"),
+        bpm: text("TBPM").as_deref().and_then(parse_bpm),
+        key: text("TKEY"),
+        lyrics: tag.lyrics().find_map(|lyrics| non_empty(&lyrics.text)),
+        label: text("TPUB"),
+        copyright: text("TCOP"),
+        comment: tag
+            .comments()
+            .filter(|comment| comment.description.is_empty())
+            .find_map(|comment| non_empty(&comment.text)),
+        mbid: tag
+            .unique_file_identifiers()
+            .find(|ufid| ufid.owner_identifier == MUSICBRAINZ_UFID_OWNER)
+            .and_then(|ufid| non_empty(&String::from_utf8_lossy(&ufid.identifier))),
+        license: link("WCOP"),
+        url: link("WOAF"),
+        pictures: tag
+            .pictures()
+            .map(|picture| EmbeddedPicture {
+                picture_type: picture.picture_type.into(),
+                mime_type: picture.mime_type.clone(),
+                description: picture.description.clone(),
+                data: picture.data.clone(),
+            })
+            .collect(),
+        custom: Default::default(),
+    };
+
+    // Les numéros mal formés (« 03 of 12 ») sont relus au mieux
+    if tags.track_number.is_none() {
+        (tags.track_number, tags.track_total) = text("TRCK").as_deref().map(parse_number_pair).unwrap_or_default();
+    }
+    for extended in tag.extended_texts() {
+        if let Some(value) = non_empty(&extended.value) {
+            match extended.description.to_ascii_lowercase().as_str() {
+                "musicbrainz track id" if tags.mbid.is_none() => tags.mbid = Some(value),
+                "isrc" if tags.isrc.is_none() => tags.isrc = Some(value),
+                "license" => tags.license = Some(value),
+                _ => {
+                    tags.custom.insert(extended.description.clone(), value);
+                }
+            }
+        }
+    }
+    tags
+}
+
+/// Construit un tag ID3v2.4 : artistes multiples séparés par des nuls,
+/// licence en TXXX (et WCOP si c'est une URL), page de la piste en WOAF
+pub fn build_tag(tags: &TrackTags) -> Tag {
+    let mut tag = Tag::with_version(Version::Id3v24);
+    let texts = [
+        ("TIT2", &tags.title),
+        ("TALB", &tags.album),
+        ("TPE2", &tags.album_artist),
+        ("TCON", &tags.genre),
+        ("TCOM", &tags.composer),
+        ("TSRC", &tags.isrc),
+        ("TKEY", &tags.key),
+        ("TPUB", &tags.label),
+        ("TCOP", &tags.copyright),
+    ];
+    for (id, value) in texts {
+        if let Some(value) = value {
+            tag.set_text(id, value.as_str());
+        }
+    }
+    if !tags.artists.is_empty() {
+        tag.set_text_values("TPE1", tags.artists.iter().map(String::as_str));
+    }
+    if let Some(year) = tags.year {
+        tag.set_date_recorded(Timestamp { year: year as i32, month: None, day: None, hour: None, minute: None, second: None });
+    }
+    if let Some(pair) = format_pair(tags.track_number, tags.track_total) {
+        tag.set_text("TRCK", pair);
+    }
+    if let Some(pair) = format_pair(tags.disc_number, tags.disc_total) {
+        tag.set_text("TPOS", pair);
+    }
+    if let Some(bpm) = tags.bpm {
+        // TBPM est un entier
+        tag.set_text("TBPM", (bpm.round() as u32).to_string());
+    }
+    if let Some(lyrics) = &tags.lyrics {
+        tag.add_frame(Lyrics { lang: "und".to_string(), description: String::new(), text: lyrics.clone() });
+    }
+    if let Some(comment) = &tags.comment {
+        tag.add_frame(Comment { lang: "und".to_string(), description: String::new(), text: comment.clone() });
+    }
+    if let Some(mbid) = &tags.mbid {
+        tag.add_frame(UniqueFileIdentifier {
+            owner_identifier: MUSICBRAINZ_UFID_OWNER.to_string(),
+            identifier: mbid.as_bytes().to_vec(),
+        });
+    }
+    if let Some(license) = &tags.license {
+        tag.add_frame(ExtendedText { description: LICENSE_DESCRIPTION.to_string(), value: license.clone() });
+        if license.starts_with("http://") || license.starts_with("https://") {
+            tag.add_frame(Frame::link("WCOP", license.as_str()));
+        }
+    }
+    if let Some(url) = &tags.url {
+        tag.add_frame(Frame::link("WOAF", url.as_str()));
+    }
+    let mut custom: Vec<_> = tags.custom.iter().collect();
+    custom.sort();
+    for (description, value) in custom {
+        tag.add_frame(ExtendedText { description: description.clone(), value: value.clone() });
+    }
+    for picture in &tags.pictures {
+        tag.add_frame(Picture {
+            mime_type: picture.mime_type.clone(),
+            picture_type: picture_type(picture.picture_type),
+            description: picture.description.clone(),
+            data: picture.data.clone(),
+        });
+    }
+    tag
+}
+
+/// Tag ID3v2.4 sérialisé, prêt à précéder le flux audio
+pub fn encode(tags: &TrackTags) -> Result<Vec<u8>, AppError> {
+    let mut encoded = Vec::new();
+    build_tag(tags)
+        .write_to(&mut encoded, Version::Id3v24)
+        .map_err(|e| AppError::EncodingError { message: format!("Tag ID3 impossible à écrire: {}", e) })?;
+    Ok(encoded)
+}
+
+/// Taille d'un tag ID3v2 (en-tête et pied compris) d'après ses 10 premiers octets
+pub fn tag_size(header: &[u8]) -> Option<u64> {
+    if header.len() < 10 || &header[..3] != b"ID3" || header[6..10].iter().any(|b| b & 0x80 != 0) {
+        return None;
+    }
+    let size = header[6..10].iter().fold(0u64, |acc, &b| (acc << 7) | u64::from(b));
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Some(10 + size + footer)
+}
+
+fn format_pair(number: Option<u32>, total: Option<u32>) -> Option<String> {
+    match (number, total) {
+        (Some(number), Some(total)) => Some(format!("{}/{}", number, total)),
+        (Some(number), None) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn picture_type(code: u8) -> PictureType {
+    const TYPES: [PictureType; 21] = [
+        PictureType::Other,
+        PictureType::Icon,
+        PictureType::OtherIcon,
+        PictureType::CoverFront,
+        PictureType::CoverBack,
+        PictureType::Leaflet,
+        PictureType::Media,
+        PictureType::LeadArtist,
+        PictureType::Artist,
+        PictureType::Conductor,
+        PictureType::Band,
+        PictureType::Composer,
+        PictureType::Lyricist,
+        PictureType::RecordingLocation,
+        PictureType::DuringRecording,
+        PictureType::DuringPerformance,
+        PictureType::ScreenCapture,
+        PictureType::BrightFish,
+        PictureType::Illustration,
+        PictureType::BandLogo,
+        PictureType::PublisherLogo,
+    ];
+    TYPES.get(usize::from(code)).copied().unwrap_or(PictureType::Undefined(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maps_id3_frames() {
+        let mut tag = Tag::new();
+        tag.set_title("Night Drive");
+        tag.set_text_values("TPE1", ["Alice", "Bob"]);
+        tag.set_album("Neon");
+        tag.set_genre("(13)");
+        tag.set_text("TDRC", "2021-06-01");
+        tag.set_text("TRCK", "4/10");
+        tag.set_text("TCOM", "Carol");
+        tag.set_text("TSRC", "FRZ031900001");
+        tag.set_text("TBPM", "124");
+        tag.add_frame(Lyrics { lang: "fra".into(), description: String::new(), text: "La la".into() });
+        tag.add_frame(Comment { lang: "eng".into(), description: String::new(), text: "Mastered".into() });
+        tag.add_frame(ExtendedText { description: "MOOD".into(), value: "Dark".into() });
+        tag.add_frame(UniqueFileIdentifier {
+            owner_identifier: MUSICBRAINZ_UFID_OWNER.into(),
+            identifier: b"5b11f4ce-a62d-471e-81fc-a69a8278c7da".to_vec(),
+        });
+        tag.add_frame(Picture {
+            mime_type: "image/jpeg".into(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: vec![0xFF, 0xD8, 0xFF],
+        });
+
+        let tags = from_tag(&tag);
+        assert_eq!(tags.title.as_deref(), Some("Night Drive"));
+        assert_eq!(tags.artists, vec!["Alice", "Bob"]);
+        assert_eq!(tags.genre.as_deref(), Some("Pop"));
+        assert_eq!(tags.year, Some(2021));
+        assert_eq!((tags.track_number, tags.track_total), (Some(4), Some(10)));
+        assert_eq!(tags.composer.as_deref(), Some("Carol"));
+        assert_eq!(tags.isrc.as_deref(), Some("FRZ031900001"));
+        assert_eq!(tags.bpm, Some(124.0));
+        assert_eq!(tags.lyrics.as_deref(), Some("La la"));
+        assert_eq!(tags.comment.as_deref(), Some("Mastered"));
+        assert_eq!(tags.mbid.as_deref(), Some("5b11f4ce-a62d-471e-81fc-a69a8278c7da"));
+        assert_eq!(tags.custom.get("MOOD").map(String::as_str), Some("Dark"));
+        assert_eq!(tags.cover().unwrap().data, vec![0xFF, 0xD8, 0xFF]);
+    }
+}
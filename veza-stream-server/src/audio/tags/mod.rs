@@ -0,0 +1,206 @@
+//! Lecture des tags embarqués dans les fichiers audio
+//!
+//! Chaque conteneur a son format : ID3v2 (MP3, WAV, AIFF), commentaires
+//! Vorbis (FLAC, Ogg Vorbis, Opus) et atomes `ilst` (MP4/M4A). Ils sont tous
+//! ramenés à `TrackTags`, qui alimente `TrackMetadata` à l'upload et sert
+//! à réécrire les tags des fichiers livrés (voir `write`).
+
+pub mod id3v2;
+pub mod mp4;
+pub mod vorbis;
+pub mod write;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+
+/// Type d'image « couverture avant » (numérotation commune ID3 APIC / FLAC)
+pub const PICTURE_FRONT_COVER: u8 = 3;
+
+/// Conteneur de tags d'un fichier audio, détecté sur ses premiers octets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagFormat {
+    Id3,
+    Flac,
+    Ogg,
+    Wav,
+    Aiff,
+    Mp4,
+}
+
+impl TagFormat {
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        match header {
+            [b'I', b'D', b'3', ..] => Some(Self::Id3),
+            [b'f', b'L', b'a', b'C', ..] => Some(Self::Flac),
+            [b'O', b'g', b'g', b'S', ..] => Some(Self::Ogg),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(Self::Wav),
+            [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', _, ..] => Some(Self::Aiff),
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(Self::Mp4),
+            // Trame MPEG sans tag ID3v2 : un éventuel ID3v1 est en fin de fichier
+            [0xFF, second, ..] if second & 0xE0 == 0xE0 => Some(Self::Id3),
+            _ => None,
+        }
+    }
+
+    pub fn detect_file(path: &Path) -> Result<Option<Self>, AppError> {
+        let mut header = [0u8; 12];
+        let mut file = File::open(path)?;
+        let mut read = 0;
+        while read < header.len() {
+            match file.read(&mut header[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(Self::detect(&header[..read]))
+    }
+}
+
+/// Image embarquée (pochette, photo d'artiste…)
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddedPicture {
+    /// Type APIC/FLAC (3 = couverture avant)
+    pub picture_type: u8,
+    pub mime_type: String,
+    pub description: String,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl std::fmt::Debug for EmbeddedPicture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmbeddedPicture")
+            .field("picture_type", &self.picture_type)
+            .field("mime_type", &self.mime_type)
+            .field("description", &self.description)
+            .field("size", &self.data.len())
+            .finish()
+    }
+}
+
+/// Tags d'un fichier, indépendamment de son conteneur
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub composer: Option<String>,
+    pub isrc: Option<String>,
+    pub bpm: Option<f32>,
+    pub key: Option<String>,
+    pub lyrics: Option<String>,
+    pub label: Option<String>,
+    pub copyright: Option<String>,
+    pub comment: Option<String>,
+    /// Identifiant MusicBrainz de l'enregistrement
+    pub mbid: Option<String>,
+    /// Licence de diffusion (nom ou URL)
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Page de la piste sur la plateforme
+    #[serde(default)]
+    pub url: Option<String>,
+    pub pictures: Vec<EmbeddedPicture>,
+    /// Champs sans équivalent ci-dessus (TXXX, commentaires Vorbis, atomes `----`)
+    pub custom: HashMap<String, String>,
+}
+
+impl TrackTags {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Pochette à utiliser : la couverture avant, sinon la première image
+    pub fn cover(&self) -> Option<&EmbeddedPicture> {
+        self.pictures
+            .iter()
+            .find(|picture| picture.picture_type == PICTURE_FRONT_COVER)
+            .or_else(|| self.pictures.first())
+    }
+
+    /// Artistes joints pour l'affichage
+    pub fn artist(&self) -> Option<String> {
+        (!self.artists.is_empty()).then(|| self.artists.join(", "))
+    }
+}
+
+/// Lit les tags d'un fichier ; un fichier sans tags donne un `TrackTags` vide
+pub fn read_tags(path: &Path) -> Result<TrackTags, AppError> {
+    let tags = match TagFormat::detect_file(path)? {
+        Some(TagFormat::Id3) => id3v2::read_mp3(path)?,
+        Some(TagFormat::Wav | TagFormat::Aiff) => id3v2::read_chunk(path)?,
+        Some(TagFormat::Flac) => vorbis::read_flac(path)?,
+        Some(TagFormat::Ogg) => vorbis::read_ogg(path)?,
+        Some(TagFormat::Mp4) => mp4::read(path)?,
+        None => TrackTags::default(),
+    };
+    Ok(tags)
+}
+
+/// Année en tête d'une date (`2019`, `2019-04-12`…)
+pub(crate) fn parse_year(value: &str) -> Option<u32> {
+    let digits: String = value.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    (digits.len() == 4).then(|| digits.parse().ok()).flatten()
+}
+
+/// Numéro et total au format `3/12`
+pub(crate) fn parse_number_pair(value: &str) -> (Option<u32>, Option<u32>) {
+    let mut parts = value.splitn(2, '/');
+    let number = parts.next().and_then(|n| n.trim().parse().ok());
+    let total = parts.next().and_then(|n| n.trim().parse().ok());
+    (number, total)
+}
+
+/// Tempo décimal, éventuellement écrit avec une virgule
+pub(crate) fn parse_bpm(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .replace(',', ".")
+        .parse::<f32>()
+        .ok()
+        .filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+}
+
+/// Texte non vide, sans espaces ni terminateurs superflus
+pub(crate) fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_field_parsing() {
+        assert_eq!(TagFormat::detect(b"ID3\x04\x00"), Some(TagFormat::Id3));
+        assert_eq!(TagFormat::detect(&[0xFF, 0xFB, 0x90, 0x00]), Some(TagFormat::Id3));
+        assert_eq!(TagFormat::detect(b"fLaC\x00\x00"), Some(TagFormat::Flac));
+        assert_eq!(TagFormat::detect(b"RIFF\x24\x00\x00\x00WAVEfmt "), Some(TagFormat::Wav));
+        assert_eq!(TagFormat::detect(b"FORM\x00\x00\x00\x00AIFC"), Some(TagFormat::Aiff));
+        assert_eq!(TagFormat::detect(b"\x00\x00\x00\x20ftypM4A "), Some(TagFormat::Mp4));
+        assert_eq!(TagFormat::detect(b"garbage"), None);
+
+        assert_eq!(parse_year("2019-04-12"), Some(2019));
+        assert_eq!(parse_year("19"), None);
+        assert_eq!(parse_number_pair("3/12"), (Some(3), Some(12)));
+        assert_eq!(parse_number_pair("7"), (Some(7), None));
+        assert_eq!(parse_bpm("127,5"), Some(127.5));
+        assert_eq!(parse_bpm("0"), None);
+        assert_eq!(non_empty(" \0"), None);
+    }
+}
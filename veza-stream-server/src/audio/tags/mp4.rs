@@ -0,0 +1,483 @@
+//! Tags iTunes des fichiers MP4/M4A (`moov/udta/meta/ilst`)
+//!
+//! Chaque élément de `ilst` est un atome nommé d'après le champ (`©nam`,
+//! `trkn`…) contenant un ou plusieurs atomes `data` ; les champs libres
+//! `----` portent en plus un espace de noms (`mean`) et un nom (`name`).
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::{non_empty, parse_bpm, parse_year, EmbeddedPicture, TrackTags, PICTURE_FRONT_COVER};
+use crate::error::AppError;
+
+/// Taille maximale de l'atome `moov` chargé en mémoire
+pub(crate) const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Types de valeur des atomes `data`
+const DATA_UTF8: u32 = 1;
+const DATA_JPEG: u32 = 13;
+const DATA_PNG: u32 = 14;
+const DATA_BMP: u32 = 27;
+
+fn invalid(message: &str) -> AppError {
+    AppError::InvalidData { message: format!("MP4: {}", message) }
+}
+
+/// Atome : type et contenu
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Atom<'a> {
+    pub kind: [u8; 4],
+    pub payload: &'a [u8],
+}
+
+/// Itère sur les atomes successifs d'un tampon
+pub(crate) fn atoms(data: &[u8]) -> impl Iterator<Item = Atom<'_>> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let header = data.get(offset..offset + 8)?;
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (header_size, size) = match size {
+            0 => (8, (data.len() - offset) as u64),
+            1 => {
+                let large = data.get(offset + 8..offset + 16)?;
+                (16, u64::from_be_bytes(large.try_into().ok()?))
+            }
+            size => (8, size),
+        };
+        let end = offset.checked_add(usize::try_from(size).ok()?)?;
+        if size < header_size || end > data.len() {
+            return None;
+        }
+        let atom = Atom { kind, payload: &data[offset + header_size as usize..end] };
+        offset = end;
+        Some(atom)
+    })
+}
+
+pub(crate) fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<Atom<'a>> {
+    atoms(data).find(|atom| &atom.kind == kind)
+}
+
+/// Enfants de `meta` : l'en-tête version/drapeaux est absent chez certains
+/// encodeurs QuickTime, auquel cas `hdlr` suit directement
+pub(crate) fn meta_children(meta: &[u8]) -> &[u8] {
+    if meta.get(4..8) == Some(b"hdlr") {
+        meta
+    } else {
+        meta.get(4..).unwrap_or_default()
+    }
+}
+
+/// Charge l'atome `moov` (en-tête compris) d'un fichier
+pub(crate) fn read_moov(file: &mut File) -> Result<Option<(u64, Vec<u8>)>, AppError> {
+    let length = file.metadata()?.len();
+    let mut position = 0u64;
+    while position + 8 <= length {
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8])?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        if size == 1 {
+            file.read_exact(&mut header[8..])?;
+            size = u64::from_be_bytes(header[8..16].try_into().expect("8 octets"));
+        } else if size == 0 {
+            size = length - position;
+        }
+        if size < 8 {
+            return Err(invalid("atome de taille invalide"));
+        }
+        if &header[4..8] == b"moov" {
+            if size > MAX_MOOV_SIZE || position + size > length {
+                return Err(invalid("atome moov trop volumineux ou tronqué"));
+            }
+            let mut moov = vec![0u8; size as usize];
+            file.seek(SeekFrom::Start(position))?;
+            file.read_exact(&mut moov)?;
+            return Ok(Some((position, moov)));
+        }
+        position += size;
+    }
+    Ok(None)
+}
+
+pub fn read(path: &Path) -> Result<TrackTags, AppError> {
+    let mut file = File::open(path)?;
+    let Some((_, moov)) = read_moov(&mut file)? else {
+        return Ok(TrackTags::default());
+    };
+    let ilst = atoms(&moov)
+        .next()
+        .and_then(|moov| child(moov.payload, b"udta"))
+        .and_then(|udta| child(udta.payload, b"meta"))
+        .and_then(|meta| child(meta_children(meta.payload), b"ilst"));
+    Ok(ilst.map(|ilst| parse_ilst(ilst.payload)).unwrap_or_default())
+}
+
+/// Valeurs `data` d'un élément : type et contenu
+fn data_values(item: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    atoms(item)
+        .filter(|atom| &atom.kind == b"data" && atom.payload.len() >= 8)
+        .map(|atom| {
+            let kind = u32::from_be_bytes([0, atom.payload[1], atom.payload[2], atom.payload[3]]);
+            (kind, &atom.payload[8..])
+        })
+}
+
+fn text_values(item: &[u8]) -> Vec<String> {
+    data_values(item)
+        .filter_map(|(_, value)| non_empty(&String::from_utf8_lossy(value)))
+        .collect()
+}
+
+/// Entier big-endian de 1 à 8 octets
+fn integer(value: &[u8]) -> Option<u64> {
+    (1..=8)
+        .contains(&value.len())
+        .then(|| value.iter().fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte)))
+}
+
+/// Paire numéro/total de `trkn` et `disk`
+fn number_pair(item: &[u8]) -> (Option<u32>, Option<u32>) {
+    data_values(item)
+        .next()
+        .filter(|(_, value)| value.len() >= 6)
+        .map(|(_, value)| {
+            let number = u32::from(u16::from_be_bytes([value[2], value[3]]));
+            let total = u32::from(u16::from_be_bytes([value[4], value[5]]));
+            ((number > 0).then_some(number), (total > 0).then_some(total))
+        })
+        .unwrap_or_default()
+}
+
+/// Convertit le contenu d'un atome `ilst`
+pub fn parse_ilst(ilst: &[u8]) -> TrackTags {
+    let mut tags = TrackTags::default();
+    for item in atoms(ilst) {
+        let first = || text_values(item.payload).into_iter().next();
+        match &item.kind {
+            b"\xa9nam" => tags.title = first(),
+            b"\xa9ART" => tags.artists = text_values(item.payload),
+            b"aART" => tags.album_artist = first(),
+            b"\xa9alb" => tags.album = first(),
+            b"\xa9gen" => tags.genre = first(),
+            b"\xa9day" => tags.year = first().as_deref().and_then(parse_year),
+            b"trkn" => (tags.track_number, tags.track_total) = number_pair(item.payload),
+            b"disk" => (tags.disc_number, tags.disc_total) = number_pair(item.payload),
+            b"\xa9wrt" => tags.composer = first(),
+            b"tmpo" => {
+                tags.bpm = data_values(item.payload)
+                    .next()
+                    .and_then(|(_, value)| integer(value))
+                    .filter(|bpm| *bpm > 0)
+                    .map(|bpm| bpm as f32);
+            }
+            b"\xa9lyr" => tags.lyrics = first(),
+            b"\xa9cmt" => tags.comment = first(),
+            b"cprt" => tags.copyright = first(),
+            b"covr" => {
+                for (kind, value) in data_values(item.payload) {
+                    let mime_type = match kind {
+                        DATA_JPEG => "image/jpeg",
+                        DATA_PNG => "image/png",
+                        DATA_BMP => "image/bmp",
+                        _ => crate::utils::image::ImageFormat::sniff(value)
+                            .map_or("application/octet-stream", |format| format.mime_type()),
+                    };
+                    tags.pictures.push(EmbeddedPicture {
+                        picture_type: PICTURE_FRONT_COVER,
+                        mime_type: mime_type.to_string(),
+                        description: String::new(),
+                        data: value.to_vec(),
+                    });
+                }
+            }
+            b"----" => {
+                let name = child(item.payload, b"name")
+                    .and_then(|name| name.payload.get(4..))
+                    .map(|name| String::from_utf8_lossy(name).into_owned());
+                let value = data_values(item.payload)
+                    .find(|(kind, _)| *kind == DATA_UTF8)
+                    .and_then(|(_, value)| non_empty(&String::from_utf8_lossy(value)));
+                let (Some(name), Some(value)) = (name, value) else {
+                    continue;
+                };
+                match name.to_ascii_uppercase().as_str() {
+                    "ISRC" => tags.isrc = Some(value),
+                    "LABEL" | "PUBLISHER" => tags.label = Some(value),
+                    "INITIALKEY" | "KEY" => tags.key = Some(value),
+                    "MUSICBRAINZ TRACK ID" => tags.mbid = Some(value),
+                    "LICENSE" => tags.license = Some(value),
+                    "URL" => tags.url = Some(value),
+                    "BPM" if tags.bpm.is_none() => tags.bpm = parse_bpm(&value),
+                    _ => {
+                        tags.custom.insert(name, value);
+                    }
+                }
+            }
+            kind => {
+                if let Some(value) = first() {
+                    // Le préfixe 0xA9 est le « © » de Latin-1
+                    tags.custom.insert(kind.iter().map(|&byte| char::from(byte)).collect(), value);
+                }
+            }
+        }
+    }
+    tags
+}
+
+fn build_atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut atom = Vec::with_capacity(payload.len() + 16);
+    match u32::try_from(payload.len() + 8) {
+        Ok(size) => atom.extend(size.to_be_bytes()),
+        Err(_) => {
+            atom.extend(1u32.to_be_bytes());
+            atom.extend(kind);
+            atom.extend(((payload.len() + 16) as u64).to_be_bytes());
+            atom.extend(payload);
+            return atom;
+        }
+    }
+    atom.extend(kind);
+    atom.extend(payload);
+    atom
+}
+
+fn build_data(kind: u32, value: &[u8]) -> Vec<u8> {
+    let mut payload = kind.to_be_bytes().to_vec();
+    payload.extend([0; 4]);
+    payload.extend(value);
+    build_atom(b"data", &payload)
+}
+
+fn build_freeform(name: &str, value: &str) -> Vec<u8> {
+    let mut payload = build_atom(b"mean", &[&[0u8; 4][..], b"com.apple.iTunes"].concat());
+    payload.extend(build_atom(b"name", &[&[0u8; 4][..], name.as_bytes()].concat()));
+    payload.extend(build_data(DATA_UTF8, value.as_bytes()));
+    build_atom(b"----", &payload)
+}
+
+/// Construit le contenu d'un atome `ilst`
+pub fn build_ilst(tags: &TrackTags) -> Vec<u8> {
+    let mut ilst = Vec::new();
+    let mut text = |kind: &[u8; 4], value: Option<String>| {
+        if let Some(value) = value {
+            ilst.extend(build_atom(kind, &build_data(DATA_UTF8, value.as_bytes())));
+        }
+    };
+    text(b"\xa9nam", tags.title.clone());
+    text(b"\xa9ART", tags.artist());
+    text(b"aART", tags.album_artist.clone());
+    text(b"\xa9alb", tags.album.clone());
+    text(b"\xa9gen", tags.genre.clone());
+    text(b"\xa9day", tags.year.map(|year| year.to_string()));
+    text(b"\xa9wrt", tags.composer.clone());
+    text(b"\xa9lyr", tags.lyrics.clone());
+    text(b"\xa9cmt", tags.comment.clone());
+    text(b"cprt", tags.copyright.clone());
+
+    // `trkn` sur 8 octets, `disk` sur 6 : 0, numéro, total (et 0)
+    let pair = |kind: &[u8; 4], number: Option<u32>, total: Option<u32>, size: usize| {
+        number.map(|number| {
+            let mut value = vec![0u8; size];
+            value[2..4].copy_from_slice(&(number.min(u32::from(u16::MAX)) as u16).to_be_bytes());
+            value[4..6].copy_from_slice(&(total.unwrap_or(0).min(u32::from(u16::MAX)) as u16).to_be_bytes());
+            build_atom(kind, &build_data(0, &value))
+        })
+    };
+    ilst.extend(pair(b"trkn", tags.track_number, tags.track_total, 8).unwrap_or_default());
+    ilst.extend(pair(b"disk", tags.disc_number, tags.disc_total, 6).unwrap_or_default());
+    if let Some(bpm) = tags.bpm {
+        // Entier signé sur 2 octets
+        let bpm = bpm.round().clamp(1.0, f32::from(i16::MAX)) as u16;
+        ilst.extend(build_atom(b"tmpo", &build_data(21, &bpm.to_be_bytes())));
+    }
+    let covers: Vec<u8> = tags
+        .pictures
+        .iter()
+        .filter_map(|picture| {
+            let kind = match crate::utils::image::ImageFormat::sniff(&picture.data)? {
+                crate::utils::image::ImageFormat::Jpeg => DATA_JPEG,
+                crate::utils::image::ImageFormat::Png => DATA_PNG,
+                crate::utils::image::ImageFormat::Bmp => DATA_BMP,
+                // iTunes ne lit pas les autres formats
+                _ => return None,
+            };
+            Some(build_data(kind, &picture.data))
+        })
+        .flatten()
+        .collect();
+    if !covers.is_empty() {
+        ilst.extend(build_atom(b"covr", &covers));
+    }
+
+    let freeform = [
+        ("ISRC", &tags.isrc),
+        ("LABEL", &tags.label),
+        ("initialkey", &tags.key),
+        ("MusicBrainz Track Id", &tags.mbid),
+        ("LICENSE", &tags.license),
+        ("URL", &tags.url),
+    ];
+    for (name, value) in freeform {
+        if let Some(value) = value {
+            ilst.extend(build_freeform(name, value));
+        }
+    }
+    let mut custom: Vec<_> = tags.custom.iter().filter(|(name, _)| name.is_ascii()).collect();
+    custom.sort();
+    for (name, value) in custom {
+        ilst.extend(build_freeform(name, value));
+    }
+    ilst
+}
+
+/// Réécrit `moov` (en-tête compris, situé à `position` dans le fichier) avec
+/// un nouvel `ilst`. Quand `moov` précède `mdat`, les offsets `stco`/`co64`
+/// qui pointent après lui sont décalés de l'écart de taille.
+pub fn rewrite_moov(moov: &[u8], tags: &TrackTags, position: u64) -> Result<Vec<u8>, AppError> {
+    let root = atoms(moov).next().filter(|atom| &atom.kind == b"moov").ok_or_else(|| invalid("moov attendu"))?;
+
+    let mut udta = Vec::new();
+    let old_udta = child(root.payload, b"udta");
+    let old_meta = old_udta.and_then(|udta| child(udta.payload, b"meta"));
+    // Autres enfants de udta (chapitres, métadonnées QuickTime…) conservés
+    for atom in old_udta.into_iter().flat_map(|udta| atoms(udta.payload)) {
+        if &atom.kind != b"meta" {
+            udta.extend(build_atom(&atom.kind, atom.payload));
+        }
+    }
+    let mut meta = vec![0u8; 4];
+    let old_children = old_meta.map(|meta| meta_children(meta.payload)).unwrap_or_default();
+    if child(old_children, b"hdlr").is_none() {
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend(b"mdirappl");
+        hdlr.extend([0u8; 9]);
+        meta.extend(build_atom(b"hdlr", &hdlr));
+    }
+    for atom in atoms(old_children) {
+        // L'ancien `free` de remplissage ne correspond plus à rien
+        if &atom.kind != b"ilst" && &atom.kind != b"free" {
+            meta.extend(build_atom(&atom.kind, atom.payload));
+        }
+    }
+    meta.extend(build_atom(b"ilst", &build_ilst(tags)));
+    udta.extend(build_atom(b"meta", &meta));
+
+    let mut children = Vec::with_capacity(root.payload.len() + udta.len());
+    for atom in atoms(root.payload) {
+        if &atom.kind != b"udta" {
+            children.extend(build_atom(&atom.kind, atom.payload));
+        }
+    }
+    children.extend(build_atom(b"udta", &udta));
+    let mut rewritten = build_atom(b"moov", &children);
+
+    let delta = rewritten.len() as i64 - moov.len() as i64;
+    if delta != 0 {
+        shift_chunk_offsets(&mut rewritten, position, delta)?;
+    }
+    Ok(rewritten)
+}
+
+/// Décale les offsets de chunks ≥ `after` de `delta` dans toutes les pistes
+fn shift_chunk_offsets(moov: &mut [u8], after: u64, delta: i64) -> Result<(), AppError> {
+    // Positions (dans `moov`) des tables à corriger
+    let mut tables = Vec::new();
+    collect_tables(moov, 0, &mut tables);
+    for (start, end, wide) in tables {
+        let table = &mut moov[start..end];
+        if table.len() < 8 {
+            return Err(invalid("table d'offsets tronquée"));
+        }
+        let count = u32::from_be_bytes([table[4], table[5], table[6], table[7]]) as usize;
+        let width = if wide { 8 } else { 4 };
+        if 8 + count * width > table.len() {
+            return Err(invalid("table d'offsets tronquée"));
+        }
+        for entry in table[8..8 + count * width].chunks_exact_mut(width) {
+            let offset = entry.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+            if offset < after {
+                continue;
+            }
+            let shifted = offset.checked_add_signed(delta).ok_or_else(|| invalid("offset de chunk invalide"))?;
+            if wide {
+                entry.copy_from_slice(&shifted.to_be_bytes());
+            } else {
+                let shifted = u32::try_from(shifted).map_err(|_| invalid("offset stco hors limites"))?;
+                entry.copy_from_slice(&shifted.to_be_bytes());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn collect_tables(data: &[u8], base: usize, tables: &mut Vec<(usize, usize, bool)>) {
+    for atom in atoms(data) {
+        // Les pointeurs viennent du même tampon : la différence donne la position
+        let start = base + (atom.payload.as_ptr() as usize - data.as_ptr() as usize);
+        match &atom.kind {
+            b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" => collect_tables(atom.payload, start, tables),
+            b"stco" => tables.push((start, start + atom.payload.len(), false)),
+            b"co64" => tables.push((start, start + atom.payload.len(), true)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut atom = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend(kind);
+        atom.extend(payload);
+        atom
+    }
+
+    fn data(kind: u32, value: &[u8]) -> Vec<u8> {
+        let mut payload = kind.to_be_bytes().to_vec();
+        payload.extend([0; 4]);
+        payload.extend(value);
+        atom(b"data", &payload)
+    }
+
+    #[test]
+    fn test_reads_ilst_from_file() {
+        let mut freeform = atom(b"mean", b"\0\0\0\0com.apple.iTunes");
+        freeform.extend(atom(b"name", b"\0\0\0\0ISRC"));
+        freeform.extend(data(DATA_UTF8, b"GBAYE0601498"));
+
+        let ilst = [
+            atom(b"\xa9nam", &data(DATA_UTF8, b"Harbour Lights")),
+            atom(b"\xa9ART", &data(DATA_UTF8, b"Eve")),
+            atom(b"\xa9day", &data(DATA_UTF8, b"2012-01-01T00:00:00Z")),
+            atom(b"trkn", &data(0, &[0, 0, 0, 5, 0, 11, 0, 0])),
+            atom(b"tmpo", &data(21, &[0, 120])),
+            atom(b"covr", &data(DATA_PNG, b"\x89PNG")),
+            atom(b"----", &freeform),
+            atom(b"\xa9too", &data(DATA_UTF8, b"Lavf60")),
+        ]
+        .concat();
+        let mut meta = vec![0; 4];
+        meta.extend(atom(b"hdlr", &[0; 25]));
+        meta.extend(atom(b"ilst", &ilst));
+        let moov = atom(b"moov", &atom(b"udta", &atom(b"meta", &meta)));
+
+        let path = std::env::temp_dir().join(format!("mp4-tags-{}.m4a", uuid::Uuid::new_v4()));
+        std::fs::write(&path, [atom(b"ftyp", b"M4A \0\0\0\0"), atom(b"mdat", &[0; 32]), moov].concat()).unwrap();
+        let tags = read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tags.title.as_deref(), Some("Harbour Lights"));
+        assert_eq!(tags.artists, vec!["Eve"]);
+        assert_eq!(tags.year, Some(2012));
+        assert_eq!((tags.track_number, tags.track_total), (Some(5), Some(11)));
+        assert_eq!(tags.bpm, Some(120.0));
+        assert_eq!(tags.isrc.as_deref(), Some("GBAYE0601498"));
+        assert_eq!(tags.cover().unwrap().mime_type, "image/png");
+        assert_eq!(tags.custom.get("©too").map(String::as_str), Some("Lavf60"));
+    }
+}
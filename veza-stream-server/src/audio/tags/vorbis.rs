@@ -0,0 +1,298 @@
+//! Commentaires Vorbis des fichiers FLAC, Ogg Vorbis et Opus
+//!
+//! Les pochettes sont des blocs PICTURE en FLAC et des champs
+//! `METADATA_BLOCK_PICTURE` (le même bloc, en base64) dans Ogg.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use base64::Engine as _;
+use ogg::PacketReader;
+
+use super::{non_empty, parse_bpm, parse_number_pair, parse_year, EmbeddedPicture, TrackTags};
+use crate::error::AppError;
+
+/// Champ contenant une image FLAC encodée en base64
+pub const PICTURE_FIELD: &str = "METADATA_BLOCK_PICTURE";
+
+pub fn read_flac(path: &Path) -> Result<TrackTags, AppError> {
+    let tag = metaflac::Tag::read_from_path(path)
+        .map_err(|e| AppError::DecodingError { message: format!("Métadonnées FLAC illisibles: {}", e) })?;
+    let comments = tag
+        .vorbis_comments()
+        .map(|block| {
+            block
+                .comments
+                .iter()
+                .flat_map(|(key, values)| values.iter().map(move |value| (key.clone(), value.clone())))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut tags = from_comments(comments);
+    tags.pictures.extend(tag.pictures().map(from_flac_picture));
+    Ok(tags)
+}
+
+/// Lit les commentaires du premier flux Vorbis ou Opus d'un fichier Ogg
+pub fn read_ogg(path: &Path) -> Result<TrackTags, AppError> {
+    let mut reader = PacketReader::new(BufReader::new(File::open(path)?));
+    let ogg_error = |e: ogg::OggReadError| AppError::DecodingError { message: format!("Flux Ogg illisible: {}", e) };
+
+    let mut serial = None;
+    while let Some(packet) = reader.read_packet().map_err(ogg_error)? {
+        match serial {
+            None if packet.data.starts_with(b"\x01vorbis") || packet.data.starts_with(b"OpusHead") => {
+                serial = Some(packet.stream_serial());
+            }
+            Some(serial) if packet.stream_serial() == serial => {
+                let block = if let Some(block) = packet.data.strip_prefix(b"\x03vorbis") {
+                    block
+                } else if let Some(block) = packet.data.strip_prefix(b"OpusTags") {
+                    block
+                } else {
+                    return Ok(TrackTags::default());
+                };
+                return Ok(from_comments(parse_comment_block(block)?));
+            }
+            _ => {}
+        }
+    }
+    Ok(TrackTags::default())
+}
+
+/// Décode un bloc de commentaires (vendeur, nombre, puis `CLÉ=valeur`)
+pub fn parse_comment_block(block: &[u8]) -> Result<Vec<(String, String)>, AppError> {
+    let truncated = || AppError::InvalidData { message: "Commentaires Vorbis tronqués".to_string() };
+    let mut position = 0;
+    let next_length = |position: &mut usize| -> Result<usize, AppError> {
+        let bytes = block.get(*position..*position + 4).ok_or_else(truncated)?;
+        *position += 4;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    };
+
+    let vendor_length = next_length(&mut position)?;
+    position += vendor_length;
+    let count = next_length(&mut position)?;
+    let mut comments = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        let length = next_length(&mut position)?;
+        let field = block.get(position..position + length).ok_or_else(truncated)?;
+        position += length;
+        let field = String::from_utf8_lossy(field);
+        if let Some((key, value)) = field.split_once('=') {
+            comments.push((key.to_string(), value.to_string()));
+        }
+    }
+    Ok(comments)
+}
+
+fn from_flac_picture(picture: &metaflac::block::Picture) -> EmbeddedPicture {
+    EmbeddedPicture {
+        picture_type: picture.picture_type as u8,
+        mime_type: picture.mime_type.clone(),
+        description: picture.description.clone(),
+        data: picture.data.clone(),
+    }
+}
+
+/// Convertit des paires `CLÉ=valeur` (clés insensibles à la casse)
+pub fn from_comments(comments: Vec<(String, String)>) -> TrackTags {
+    let mut tags = TrackTags::default();
+    for (key, value) in comments {
+        let key = key.to_ascii_uppercase();
+        if key == PICTURE_FIELD {
+            let picture = base64::engine::general_purpose::STANDARD
+                .decode(value.trim())
+                .ok()
+                .and_then(|bytes| metaflac::block::Picture::from_bytes(&bytes).ok());
+            tags.pictures.extend(picture.as_ref().map(from_flac_picture));
+            continue;
+        }
+        let Some(value) = non_empty(&value) else {
+            continue;
+        };
+        let set = |field: &mut Option<String>| {
+            field.get_or_insert(value.clone());
+        };
+        match key.as_str() {
+            "TITLE" => set(&mut tags.title),
+            "ARTIST" | "ARTISTS" => {
+                if !tags.artists.contains(&value) {
+                    tags.artists.push(value);
+                }
+            }
+            "ALBUM" => set(&mut tags.album),
+            "ALBUMARTIST" | "ALBUM ARTIST" => set(&mut tags.album_artist),
+            "GENRE" => set(&mut tags.genre),
+            "DATE" | "YEAR" | "ORIGINALDATE" => {
+                tags.year = tags.year.or_else(|| parse_year(&value));
+            }
+            "TRACKNUMBER" => {
+                let (number, total) = parse_number_pair(&value);
+                tags.track_number = tags.track_number.or(number);
+                tags.track_total = tags.track_total.or(total);
+            }
+            "TRACKTOTAL" | "TOTALTRACKS" => tags.track_total = value.parse().ok().or(tags.track_total),
+            "DISCNUMBER" => {
+                let (number, total) = parse_number_pair(&value);
+                tags.disc_number = tags.disc_number.or(number);
+                tags.disc_total = tags.disc_total.or(total);
+            }
+            "DISCTOTAL" | "TOTALDISCS" => tags.disc_total = value.parse().ok().or(tags.disc_total),
+            "COMPOSER" => set(&mut tags.composer),
+            "ISRC" => set(&mut tags.isrc),
+            "BPM" | "TEMPO" => tags.bpm = tags.bpm.or_else(|| parse_bpm(&value)),
+            "INITIALKEY" | "KEY" => set(&mut tags.key),
+            "LYRICS" | "UNSYNCEDLYRICS" => set(&mut tags.lyrics),
+            "LABEL" | "ORGANIZATION" | "PUBLISHER" => set(&mut tags.label),
+            "COPYRIGHT" => set(&mut tags.copyright),
+            "COMMENT" | "DESCRIPTION" => set(&mut tags.comment),
+            "MUSICBRAINZ_TRACKID" => set(&mut tags.mbid),
+            "LICENSE" => set(&mut tags.license),
+            "WEBSITE" | "URL" | "CONTACT" => set(&mut tags.url),
+            _ => {
+                tags.custom.entry(key.to_ascii_lowercase()).or_insert(value);
+            }
+        }
+    }
+    tags
+}
+
+/// Commentaires à écrire, dans un ordre stable ; les pochettes sont à part
+pub fn build_comments(tags: &TrackTags) -> Vec<(String, String)> {
+    let mut comments = Vec::new();
+    let mut push = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            comments.push((key.to_string(), value));
+        }
+    };
+    push("TITLE", tags.title.clone());
+    for artist in &tags.artists {
+        push("ARTIST", Some(artist.clone()));
+    }
+    push("ALBUM", tags.album.clone());
+    push("ALBUMARTIST", tags.album_artist.clone());
+    push("GENRE", tags.genre.clone());
+    push("DATE", tags.year.map(|year| year.to_string()));
+    push("TRACKNUMBER", tags.track_number.map(|n| n.to_string()));
+    push("TRACKTOTAL", tags.track_total.map(|n| n.to_string()));
+    push("DISCNUMBER", tags.disc_number.map(|n| n.to_string()));
+    push("DISCTOTAL", tags.disc_total.map(|n| n.to_string()));
+    push("COMPOSER", tags.composer.clone());
+    push("ISRC", tags.isrc.clone());
+    push("BPM", tags.bpm.map(|bpm| bpm.to_string()));
+    push("INITIALKEY", tags.key.clone());
+    push("LYRICS", tags.lyrics.clone());
+    push("LABEL", tags.label.clone());
+    push("COPYRIGHT", tags.copyright.clone());
+    push("COMMENT", tags.comment.clone());
+    push("MUSICBRAINZ_TRACKID", tags.mbid.clone());
+    push("LICENSE", tags.license.clone());
+    push("WEBSITE", tags.url.clone());
+    let mut custom: Vec<_> = tags.custom.iter().collect();
+    custom.sort();
+    for (key, value) in custom {
+        // Les clés Vorbis sont en ASCII imprimable, sans `=`
+        if !key.is_empty() && key.bytes().all(|b| (0x20..=0x7D).contains(&b) && b != b'=') {
+            push(&key.to_ascii_uppercase(), Some(value.clone()));
+        }
+    }
+    comments
+}
+
+/// Bloc de commentaires (sans l'en-tête de paquet Vorbis ni le bit de trame)
+pub fn encode_comment_block(vendor: &str, comments: &[(String, String)]) -> Vec<u8> {
+    let mut block = Vec::new();
+    block.extend((vendor.len() as u32).to_le_bytes());
+    block.extend(vendor.as_bytes());
+    block.extend((comments.len() as u32).to_le_bytes());
+    for (key, value) in comments {
+        let field = format!("{}={}", key, value);
+        block.extend((field.len() as u32).to_le_bytes());
+        block.extend(field.as_bytes());
+    }
+    block
+}
+
+/// Corps d'un bloc PICTURE FLAC ; dimensions renseignées si l'image est lisible
+pub fn encode_picture(picture: &EmbeddedPicture) -> Vec<u8> {
+    let mut block = metaflac::block::Picture::new();
+    block.picture_type = flac_picture_type(picture.picture_type);
+    block.mime_type = picture.mime_type.clone();
+    block.description = picture.description.clone();
+    if let Ok(image) = crate::utils::image::RgbImage::decode(&picture.data) {
+        (block.width, block.height, block.depth) = (image.width, image.height, 24);
+    }
+    block.data = picture.data.clone();
+    block.to_bytes()
+}
+
+fn flac_picture_type(code: u8) -> metaflac::block::PictureType {
+    use metaflac::block::PictureType::*;
+    const TYPES: [metaflac::block::PictureType; 21] = [
+        Other, Icon, OtherIcon, CoverFront, CoverBack, Leaflet, Media, LeadArtist, Artist, Conductor, Band, Composer,
+        Lyricist, RecordingLocation, DuringRecording, DuringPerformance, ScreenCapture, BrightFish, Illustration,
+        BandLogo, PublisherLogo,
+    ];
+    TYPES.get(usize::from(code)).copied().unwrap_or(Other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment_block(fields: &[&str]) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend(6u32.to_le_bytes());
+        block.extend(b"vendor");
+        block.extend((fields.len() as u32).to_le_bytes());
+        for field in fields {
+            block.extend((field.len() as u32).to_le_bytes());
+            block.extend(field.as_bytes());
+        }
+        block
+    }
+
+    #[test]
+    fn test_maps_vorbis_comments_and_pictures() {
+        let mut picture = metaflac::block::Picture::new();
+        picture.picture_type = metaflac::block::PictureType::CoverFront;
+        picture.mime_type = "image/png".to_string();
+        picture.data = vec![0x89, b'P', b'N', b'G'];
+        let encoded = base64::engine::general_purpose::STANDARD.encode(picture.to_bytes());
+        let picture_field = format!("metadata_block_picture={}", encoded);
+
+        let block = comment_block(&[
+            "TITLE=Sunrise",
+            "artist=Alice",
+            "ARTIST=Bob",
+            "ALBUMARTIST=Various",
+            "DATE=2018-03-02",
+            "TRACKNUMBER=2",
+            "TRACKTOTAL=9",
+            "DISCNUMBER=1/2",
+            "COMPOSER=Dave",
+            "ISRC=USRC17607839",
+            "BPM=98.5",
+            "REPLAYGAIN_TRACK_GAIN=-6.2 dB",
+            &picture_field,
+        ]);
+        let tags = from_comments(parse_comment_block(&block).unwrap());
+
+        assert_eq!(tags.title.as_deref(), Some("Sunrise"));
+        assert_eq!(tags.artists, vec!["Alice", "Bob"]);
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!(tags.year, Some(2018));
+        assert_eq!((tags.track_number, tags.track_total), (Some(2), Some(9)));
+        assert_eq!((tags.disc_number, tags.disc_total), (Some(1), Some(2)));
+        assert_eq!(tags.bpm, Some(98.5));
+        assert_eq!(tags.custom.get("replaygain_track_gain").map(String::as_str), Some("-6.2 dB"));
+        let cover = tags.cover().unwrap();
+        assert_eq!((cover.picture_type, cover.mime_type.as_str()), (3, "image/png"));
+        assert_eq!(cover.data, vec![0x89, b'P', b'N', b'G']);
+
+        assert!(parse_comment_block(&block[..block.len() - 3]).is_err());
+    }
+}
@@ -0,0 +1,711 @@
+//! Réécriture des tags des fichiers livrés (rendus transcodés, téléchargements)
+//!
+//! Plutôt que de produire une copie complète du fichier, `plan` calcule une
+//! disposition : une suite de morceaux, soit des octets neufs (tags), soit des
+//! plages du fichier d'origine (flux audio). Un téléchargement peut ainsi être
+//! servi par plages directement depuis le stockage.
+//!
+//! - MP3/AAC ADTS : nouveau tag ID3v2.4 en tête, ancien ID3v2 et ID3v1 retirés ;
+//! - FLAC : blocs VORBIS_COMMENT et PICTURE remplacés, PADDING supprimé ;
+//! - MP4/M4A : `moov/udta/meta/ilst` remplacé, offsets de chunks recalés ;
+//! - Ogg Opus : paquet OpusTags remplacé sur ses pages d'origine ;
+//! - WAV : chunk `id3 ` (ID3v2.4) remplacé, en fin de fichier.
+//!
+//! Les autres conteneurs (Ogg Vorbis, AIFF) ne sont pas réécrits.
+
+use async_trait::async_trait;
+use base64::Engine as _;
+use sha2::{Digest, Sha256};
+
+use super::{id3v2, mp4, vorbis, TagFormat, TrackTags};
+use crate::error::AppError;
+
+/// Chaîne « vendor » des commentaires Vorbis écrits
+const VENDOR: &str = concat!("veza-stream-server ", env!("CARGO_PKG_VERSION"));
+
+/// Taille maximale d'un bloc de métadonnées FLAC (longueur sur 24 bits)
+const FLAC_MAX_BLOCK_SIZE: usize = 0xFF_FFFF;
+const FLAC_STREAMINFO: u8 = 0;
+const FLAC_APPLICATION: u8 = 2;
+const FLAC_SEEKTABLE: u8 = 3;
+const FLAC_VORBIS_COMMENT: u8 = 4;
+const FLAC_CUESHEET: u8 = 5;
+const FLAC_PICTURE: u8 = 6;
+
+/// Taille d'un tag ID3v1 en fin de fichier
+const ID3V1_SIZE: u64 = 128;
+
+/// En-tête d'une page Ogg, table des segments exclue
+const OGG_PAGE_HEADER_SIZE: u64 = 27;
+/// Nombre maximal de segments (255 octets au plus chacun) par page Ogg
+const OGG_MAX_SEGMENTS: usize = 255;
+
+/// En-tête RIFF : `RIFF`, taille, `WAVE`
+const RIFF_HEADER_SIZE: u64 = 12;
+
+/// Source des octets d'origine, lue par plages
+#[async_trait]
+pub trait ByteSource: Send + Sync {
+    fn size(&self) -> u64;
+    /// Lit au plus `length` octets à partir de `start`
+    async fn read_at(&self, start: u64, length: u64) -> Result<Vec<u8>, AppError>;
+}
+
+#[async_trait]
+impl ByteSource for Vec<u8> {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    async fn read_at(&self, start: u64, length: u64) -> Result<Vec<u8>, AppError> {
+        let start = (start as usize).min(self.len());
+        let end = start.saturating_add(length as usize).min(self.len());
+        Ok(self[start..end].to_vec())
+    }
+}
+
+/// Morceau du fichier réécrit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece {
+    /// Octets produits (tags, en-têtes)
+    Data(Vec<u8>),
+    /// Plage reprise telle quelle du fichier d'origine
+    Source { start: u64, length: u64 },
+}
+
+impl Piece {
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::Data(data) => data.len() as u64,
+            Self::Source { length, .. } => *length,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Disposition du fichier réécrit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaggedLayout {
+    pub format: TagFormat,
+    pub pieces: Vec<Piece>,
+}
+
+impl TaggedLayout {
+    fn new(format: TagFormat, pieces: Vec<Piece>) -> Self {
+        Self { format, pieces: pieces.into_iter().filter(|piece| !piece.is_empty()).collect() }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.pieces.iter().map(Piece::len).sum()
+    }
+
+    /// Morceaux couvrant la plage `[start, start + length)` du fichier réécrit
+    pub fn range(&self, start: u64, length: u64) -> Vec<Piece> {
+        let end = start.saturating_add(length);
+        let mut offset = 0;
+        let mut pieces = Vec::new();
+        for piece in &self.pieces {
+            let (piece_start, piece_end) = (offset, offset + piece.len());
+            offset = piece_end;
+            if piece_end <= start || piece_start >= end {
+                continue;
+            }
+            let (from, to) = (start.max(piece_start) - piece_start, end.min(piece_end) - piece_start);
+            pieces.push(match piece {
+                Piece::Data(data) => Piece::Data(data[from as usize..to as usize].to_vec()),
+                Piece::Source { start, .. } => Piece::Source { start: start + from, length: to - from },
+            });
+        }
+        pieces
+    }
+
+    /// Empreinte des octets produits et des plages reprises, pour l'ETag
+    pub fn digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        for piece in &self.pieces {
+            match piece {
+                Piece::Data(data) => hasher.update(data),
+                Piece::Source { start, length } => {
+                    hasher.update(start.to_be_bytes());
+                    hasher.update(length.to_be_bytes());
+                }
+            }
+        }
+        hex::encode(&hasher.finalize()[..8])
+    }
+
+    /// Assemble le fichier réécrit à partir de l'original en mémoire
+    pub fn apply(&self, original: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.size() as usize);
+        for piece in &self.pieces {
+            match piece {
+                Piece::Data(data) => output.extend_from_slice(data),
+                Piece::Source { start, length } => {
+                    output.extend_from_slice(&original[*start as usize..(*start + *length) as usize])
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Calcule la disposition du fichier `source` portant les tags `tags`
+pub async fn plan(source: &dyn ByteSource, tags: &TrackTags) -> Result<TaggedLayout, AppError> {
+    let size = source.size();
+    let head = source.read_at(0, 10).await?;
+    // Un tag ID3v2 peut précéder un flux MPEG comme un FLAC
+    let skip = id3v2::tag_size(&head).filter(|&tag_size| tag_size <= size).unwrap_or(0);
+    let header = source.read_at(skip, 12).await?;
+    let format = match TagFormat::detect(&header) {
+        None if skip > 0 => Some(TagFormat::Id3),
+        format => format,
+    };
+    match format {
+        Some(TagFormat::Id3) => plan_mpeg(source, skip, tags).await,
+        Some(TagFormat::Flac) => plan_flac(source, skip, tags).await,
+        Some(TagFormat::Mp4) if skip == 0 => plan_mp4(source, tags).await,
+        Some(TagFormat::Ogg) if skip == 0 => plan_ogg(source, tags).await,
+        Some(TagFormat::Wav) if skip == 0 => plan_wav(source, tags).await,
+        Some(format) => Err(AppError::UnsupportedCodec { codec: format!("{:?}", format).to_lowercase() }),
+        None => Err(AppError::UnsupportedCodec { codec: "unknown".to_string() }),
+    }
+}
+
+/// Réécrit un fichier en mémoire (rendus produits par l'encodeur)
+pub async fn tag_bytes(data: Vec<u8>, tags: &TrackTags) -> Result<Vec<u8>, AppError> {
+    let layout = plan(&data, tags).await?;
+    Ok(layout.apply(&data))
+}
+
+async fn plan_mpeg(source: &dyn ByteSource, skip: u64, tags: &TrackTags) -> Result<TaggedLayout, AppError> {
+    let mut end = source.size();
+    if end >= skip + ID3V1_SIZE {
+        let tail = source.read_at(end - ID3V1_SIZE, 3).await?;
+        if tail == b"TAG" {
+            end -= ID3V1_SIZE;
+        }
+    }
+    Ok(TaggedLayout::new(
+        TagFormat::Id3,
+        vec![Piece::Data(id3v2::encode(tags)?), Piece::Source { start: skip, length: end - skip }],
+    ))
+}
+
+async fn plan_flac(source: &dyn ByteSource, skip: u64, tags: &TrackTags) -> Result<TaggedLayout, AppError> {
+    let invalid = |message: &str| AppError::InvalidData { message: format!("FLAC: {}", message) };
+
+    // Blocs conservés (STREAMINFO en premier, comme l'exige le format)
+    let mut blocks: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut position = skip + 4;
+    loop {
+        let header = source.read_at(position, 4).await?;
+        if header.len() < 4 {
+            return Err(invalid("métadonnées tronquées"));
+        }
+        let (last, kind) = (header[0] & 0x80 != 0, header[0] & 0x7F);
+        let length = u64::from(u32::from_be_bytes([0, header[1], header[2], header[3]]));
+        position += 4;
+        if matches!(kind, FLAC_STREAMINFO | FLAC_APPLICATION | FLAC_SEEKTABLE | FLAC_CUESHEET) {
+            let body = source.read_at(position, length).await?;
+            if body.len() as u64 != length {
+                return Err(invalid("bloc tronqué"));
+            }
+            blocks.push((kind, body));
+        }
+        position += length;
+        if last {
+            break;
+        }
+        if position >= source.size() {
+            return Err(invalid("métadonnées tronquées"));
+        }
+    }
+    if blocks.first().map(|(kind, _)| *kind) != Some(FLAC_STREAMINFO) {
+        return Err(invalid("STREAMINFO absent"));
+    }
+
+    let comments = vorbis::encode_comment_block(VENDOR, &vorbis::build_comments(tags));
+    if comments.len() > FLAC_MAX_BLOCK_SIZE {
+        return Err(AppError::EncodingError { message: "Commentaires FLAC trop volumineux".to_string() });
+    }
+    blocks.push((FLAC_VORBIS_COMMENT, comments));
+    for picture in &tags.pictures {
+        let body = vorbis::encode_picture(picture);
+        if body.len() <= FLAC_MAX_BLOCK_SIZE {
+            blocks.push((FLAC_PICTURE, body));
+        } else {
+            tracing::warn!("Image de {} octets trop volumineuse pour un bloc FLAC, ignorée", picture.data.len());
+        }
+    }
+
+    let mut metadata = b"fLaC".to_vec();
+    let count = blocks.len();
+    for (index, (kind, body)) in blocks.into_iter().enumerate() {
+        let last = if index + 1 == count { 0x80 } else { 0 };
+        metadata.push(last | kind);
+        metadata.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        metadata.extend(body);
+    }
+    Ok(TaggedLayout::new(
+        TagFormat::Flac,
+        vec![Piece::Data(metadata), Piece::Source { start: position, length: source.size() - position }],
+    ))
+}
+
+async fn plan_mp4(source: &dyn ByteSource, tags: &TrackTags) -> Result<TaggedLayout, AppError> {
+    let (position, moov) = locate_moov(source).await?;
+    let rewritten = mp4::rewrite_moov(&moov, tags, position)?;
+    let moov_end = position + moov.len() as u64;
+    Ok(TaggedLayout::new(
+        TagFormat::Mp4,
+        vec![
+            Piece::Source { start: 0, length: position },
+            Piece::Data(rewritten),
+            Piece::Source { start: moov_end, length: source.size() - moov_end },
+        ],
+    ))
+}
+
+async fn plan_ogg(source: &dyn ByteSource, tags: &TrackTags) -> Result<TaggedLayout, AppError> {
+    let invalid = |message: &str| AppError::InvalidData { message: format!("Ogg: {}", message) };
+
+    let head = OggPage::read(source, 0).await?;
+    if source.read_at(head.body_start(), 8).await? != b"OpusHead" {
+        // En Vorbis, les commentaires partagent leur page avec l'en-tête de configuration
+        return Err(AppError::UnsupportedCodec { codec: "vorbis".to_string() });
+    }
+
+    // Pages du paquet OpusTags, qui termine toujours sa dernière page (RFC 7845)
+    let mut pages = Vec::new();
+    let mut position = head.end();
+    loop {
+        let page = OggPage::read(source, position).await?;
+        if page.serial() != head.serial() {
+            return Err(AppError::UnsupportedCodec { codec: "ogg multiplexé".to_string() });
+        }
+        position = page.end();
+        let packet_end = page.segments().iter().position(|&lacing| lacing < 255);
+        let segments = page.segments().len();
+        pages.push(page);
+        match packet_end {
+            Some(index) if index + 1 == segments => break,
+            Some(_) => return Err(invalid("paquet OpusTags partageant sa page")),
+            None => {}
+        }
+    }
+    if source.read_at(pages[0].body_start(), 8).await? != b"OpusTags" {
+        return Err(invalid("paquet OpusTags absent"));
+    }
+
+    // Les pages suivantes gardent leurs numéros de séquence : le paquet doit
+    // tenir sur le même nombre de pages
+    let capacity = pages.len() * OGG_MAX_SEGMENTS;
+    let mut pictures = tags.pictures.len();
+    let packet = loop {
+        let packet = opus_tags_packet(tags, &tags.pictures[..pictures]);
+        if packet.len() / 255 < capacity {
+            break packet;
+        }
+        if pictures == 0 {
+            return Err(AppError::EncodingError { message: "Commentaires Opus trop volumineux".to_string() });
+        }
+        pictures -= 1;
+        tracing::warn!(
+            "Image de {} octets trop volumineuse pour les pages OpusTags, ignorée",
+            tags.pictures[pictures].data.len()
+        );
+    };
+
+    Ok(TaggedLayout::new(
+        TagFormat::Ogg,
+        vec![
+            Piece::Source { start: 0, length: head.end() },
+            Piece::Data(paginate(packet, &pages)),
+            Piece::Source { start: position, length: source.size() - position },
+        ],
+    ))
+}
+
+fn opus_tags_packet(tags: &TrackTags, pictures: &[super::EmbeddedPicture]) -> Vec<u8> {
+    let mut comments = vorbis::build_comments(tags);
+    comments.extend(pictures.iter().map(|picture| {
+        let block = base64::engine::general_purpose::STANDARD.encode(vorbis::encode_picture(picture));
+        (vorbis::PICTURE_FIELD.to_string(), block)
+    }));
+    let mut packet = b"OpusTags".to_vec();
+    packet.extend(vorbis::encode_comment_block(VENDOR, &comments));
+    packet
+}
+
+/// Répartit `packet` sur des pages reprenant le flux et la séquence de `pages`
+fn paginate(mut packet: Vec<u8>, pages: &[OggPage]) -> Vec<u8> {
+    // Au moins un segment par page : bourrage nul après les commentaires,
+    // que les lecteurs ignorent (RFC 7845 §5.2)
+    packet.resize(packet.len().max((pages.len() - 1) * 255), 0);
+    let mut lacing = vec![255u8; packet.len() / 255];
+    lacing.push((packet.len() % 255) as u8);
+
+    let mut output = Vec::with_capacity(packet.len() + pages.len() * (OGG_PAGE_HEADER_SIZE as usize + OGG_MAX_SEGMENTS));
+    let (mut segment, mut offset) = (0, 0);
+    for (index, page) in pages.iter().enumerate() {
+        let remaining_pages = pages.len() - index - 1;
+        let count = (lacing.len() - segment - remaining_pages).min(OGG_MAX_SEGMENTS);
+        let segments = &lacing[segment..segment + count];
+        let length: usize = segments.iter().map(|&lacing| usize::from(lacing)).sum();
+
+        let start = output.len();
+        output.extend(b"OggS\0");
+        output.push(if index > 0 { 0x01 } else { 0 }); // suite d'un paquet
+        // Granule nul sur la page qui termine l'en-tête, -1 sur les autres
+        output.extend(if remaining_pages == 0 { 0u64 } else { u64::MAX }.to_le_bytes());
+        output.extend(page.serial().to_le_bytes());
+        output.extend(page.sequence().to_le_bytes());
+        output.extend([0; 4]);
+        output.push(count as u8);
+        output.extend(segments);
+        output.extend(&packet[offset..offset + length]);
+        let crc = ogg_crc(&output[start..]);
+        output[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+
+        segment += count;
+        offset += length;
+    }
+    output
+}
+
+/// Page Ogg : position et en-tête, table des segments comprise
+struct OggPage {
+    start: u64,
+    header: Vec<u8>,
+}
+
+impl OggPage {
+    async fn read(source: &dyn ByteSource, start: u64) -> Result<Self, AppError> {
+        let invalid = |message: &str| AppError::InvalidData { message: format!("Ogg: {}", message) };
+        let mut header = source.read_at(start, OGG_PAGE_HEADER_SIZE).await?;
+        if header.len() as u64 != OGG_PAGE_HEADER_SIZE || &header[..4] != b"OggS" {
+            return Err(invalid("page attendue"));
+        }
+        let count = u64::from(header[26]);
+        let segments = source.read_at(start + OGG_PAGE_HEADER_SIZE, count).await?;
+        if segments.len() as u64 != count {
+            return Err(invalid("page tronquée"));
+        }
+        header.extend(segments);
+        let page = Self { start, header };
+        if page.end() > source.size() {
+            return Err(invalid("page tronquée"));
+        }
+        Ok(page)
+    }
+
+    fn serial(&self) -> u32 {
+        u32::from_le_bytes(self.header[14..18].try_into().expect("4 octets"))
+    }
+
+    fn sequence(&self) -> u32 {
+        u32::from_le_bytes(self.header[18..22].try_into().expect("4 octets"))
+    }
+
+    fn segments(&self) -> &[u8] {
+        &self.header[OGG_PAGE_HEADER_SIZE as usize..]
+    }
+
+    fn body_start(&self) -> u64 {
+        self.start + self.header.len() as u64
+    }
+
+    fn end(&self) -> u64 {
+        self.body_start() + self.segments().iter().map(|&lacing| u64::from(lacing)).sum::<u64>()
+    }
+}
+
+const OGG_CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 d'une page Ogg (champ CRC à zéro)
+fn ogg_crc(page: &[u8]) -> u32 {
+    page.iter().fold(0u32, |crc, &byte| (crc << 8) ^ OGG_CRC_TABLE[usize::from((crc >> 24) as u8 ^ byte)])
+}
+
+async fn plan_wav(source: &dyn ByteSource, tags: &TrackTags) -> Result<TaggedLayout, AppError> {
+    let invalid = |message: &str| AppError::InvalidData { message: format!("WAV: {}", message) };
+
+    // Chunks conservés, l'ancien tag ID3 (`id3 ` ou `ID3 `) excepté
+    let mut pieces = Vec::new();
+    let mut riff_size = 4u64;
+    let mut position = RIFF_HEADER_SIZE;
+    while position + 8 <= source.size() {
+        let header = source.read_at(position, 8).await?;
+        let length = u64::from(u32::from_le_bytes(header[4..8].try_into().expect("4 octets")));
+        let end = position + 8 + length;
+        if end > source.size() {
+            return Err(invalid("chunk tronqué"));
+        }
+        // Octet de bourrage des chunks de taille impaire, parfois absent en fin de fichier
+        let padded_end = (end + (length & 1)).min(source.size());
+        if !header[..4].eq_ignore_ascii_case(b"id3 ") {
+            pieces.push(Piece::Source { start: position, length: padded_end - position });
+            riff_size += padded_end - position;
+            if padded_end < end + (length & 1) {
+                pieces.push(Piece::Data(vec![0]));
+                riff_size += 1;
+            }
+        }
+        position = padded_end;
+    }
+
+    let tag = id3v2::encode(tags)?;
+    let mut chunk = b"id3 ".to_vec();
+    chunk.extend((tag.len() as u32).to_le_bytes());
+    chunk.extend(&tag);
+    if tag.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    riff_size += chunk.len() as u64;
+    let riff_size = u32::try_from(riff_size).map_err(|_| invalid("fichier trop volumineux"))?;
+    pieces.push(Piece::Data(chunk));
+
+    let mut riff = b"RIFF".to_vec();
+    riff.extend(riff_size.to_le_bytes());
+    riff.extend(b"WAVE");
+    pieces.insert(0, Piece::Data(riff));
+    Ok(TaggedLayout::new(TagFormat::Wav, pieces))
+}
+
+/// Position et contenu (en-tête compris) de l'atome `moov`
+async fn locate_moov(source: &dyn ByteSource) -> Result<(u64, Vec<u8>), AppError> {
+    let invalid = |message: &str| AppError::InvalidData { message: format!("MP4: {}", message) };
+    let length = source.size();
+    let mut position = 0u64;
+    while position + 8 <= length {
+        let header = source.read_at(position, 16).await?;
+        let mut size = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+        if size == 1 {
+            let large = header.get(8..16).ok_or_else(|| invalid("atome tronqué"))?;
+            size = u64::from_be_bytes(large.try_into().expect("8 octets"));
+        } else if size == 0 {
+            size = length - position;
+        }
+        if size < 8 || position + size > length {
+            return Err(invalid("atome de taille invalide"));
+        }
+        if &header[4..8] == b"moov" {
+            if size > mp4::MAX_MOOV_SIZE {
+                return Err(invalid("atome moov trop volumineux"));
+            }
+            return Ok((position, source.read_at(position, size).await?));
+        }
+        position += size;
+    }
+    Err(invalid("atome moov absent"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::tags::{EmbeddedPicture, PICTURE_FRONT_COVER};
+
+    fn sample_tags() -> TrackTags {
+        TrackTags {
+            title: Some("Harbour Lights".into()),
+            artists: vec!["Eve".into(), "Mallory".into()],
+            album: Some("Docks".into()),
+            year: Some(2024),
+            track_number: Some(2),
+            track_total: Some(9),
+            isrc: Some("FRZ032400002".into()),
+            bpm: Some(96.0),
+            license: Some("https://creativecommons.org/licenses/by/4.0/".into()),
+            url: Some("https://veza.example/tracks/42".into()),
+            pictures: vec![EmbeddedPicture {
+                picture_type: PICTURE_FRONT_COVER,
+                mime_type: "image/png".into(),
+                description: String::new(),
+                data: b"\x89PNG\r\n\x1a\n".to_vec(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rewrites_mp3_tags() {
+        let mut old = Vec::new();
+        id3::Tag::new().write_to(&mut old, id3::Version::Id3v23).unwrap();
+        let mut original = old.clone();
+        original.extend([0xFF, 0xFB, 0x90, 0x00]);
+        original.extend([0xAA; 300]);
+        original.extend(b"TAG");
+        original.extend([0; 125]);
+
+        let layout = plan(&original, &sample_tags()).await.unwrap();
+        let output = layout.apply(&original);
+        assert_eq!(output.len() as u64, layout.size());
+        assert!(output.ends_with(&[0xAA; 300]));
+
+        let tags = id3v2::from_tag(&id3::Tag::read_from2(std::io::Cursor::new(&output)).unwrap());
+        assert_eq!(tags.title.as_deref(), Some("Harbour Lights"));
+        assert_eq!(tags.artists, vec!["Eve", "Mallory"]);
+        assert_eq!((tags.track_number, tags.track_total), (Some(2), Some(9)));
+        assert_eq!(tags.license, sample_tags().license);
+        assert_eq!(tags.url, sample_tags().url);
+        assert_eq!(tags.cover().unwrap().data, b"\x89PNG\r\n\x1a\n");
+
+        // Une plage à cheval sur le tag et l'audio
+        let split = output.len() as u64 - 310;
+        let range: Vec<u8> = layout.range(split, 20).iter().flat_map(|piece| match piece {
+            Piece::Data(data) => data.clone(),
+            Piece::Source { start, length } => original[*start as usize..(*start + *length) as usize].to_vec(),
+        }).collect();
+        assert_eq!(range, output[split as usize..split as usize + 20]);
+    }
+
+    #[tokio::test]
+    async fn test_rewrites_flac_blocks() {
+        let mut original = b"fLaC".to_vec();
+        original.extend([FLAC_STREAMINFO, 0, 0, 34]);
+        original.extend([0x11; 34]);
+        // Bloc PADDING (type 1)
+        original.extend([1, 0, 0, 16]);
+        original.extend([0; 16]);
+        original.extend([0x80 | FLAC_VORBIS_COMMENT]);
+        let old = vorbis::encode_comment_block("old", &[("TITLE".into(), "Old".into())]);
+        original.extend(&(old.len() as u32).to_be_bytes()[1..]);
+        original.extend(old);
+        original.extend([0xFF, 0xF8, 0x69, 0x08]);
+
+        let output = tag_bytes(original, &sample_tags()).await.unwrap();
+        let tag = metaflac::Tag::read_from(&mut std::io::Cursor::new(&output)).unwrap();
+        let comments = tag.vorbis_comments().unwrap();
+        assert_eq!(comments.title().unwrap(), &vec!["Harbour Lights".to_string()]);
+        assert_eq!(comments.get("ARTIST").unwrap(), &vec!["Eve".to_string(), "Mallory".to_string()]);
+        assert_eq!(comments.get("LICENSE").unwrap()[0], "https://creativecommons.org/licenses/by/4.0/");
+        assert_eq!(tag.pictures().count(), 1);
+        assert!(tag.get_blocks(metaflac::BlockType::Padding).next().is_none());
+        assert!(output.ends_with(&[0xFF, 0xF8, 0x69, 0x08]));
+    }
+
+    fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut atom = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend(kind);
+        atom.extend(payload);
+        atom
+    }
+
+    #[tokio::test]
+    async fn test_rewrites_faststart_mp4() {
+        // moov avant mdat : le chunk unique pointe sur le début de mdat
+        let ftyp = atom(b"ftyp", b"M4A \0\0\0\0");
+        let stco_for = |offset: u32| {
+            let mut stco = vec![0u8; 4];
+            stco.extend(1u32.to_be_bytes());
+            stco.extend(offset.to_be_bytes());
+            let stbl = atom(b"stbl", &atom(b"stco", &stco));
+            atom(b"moov", &atom(b"trak", &atom(b"mdia", &atom(b"minf", &stbl))))
+        };
+        let moov_len = stco_for(0).len();
+        let mdat_payload = ftyp.len() + moov_len + 8;
+        let original = [ftyp.clone(), stco_for(mdat_payload as u32), atom(b"mdat", b"audio!")].concat();
+
+        let output = tag_bytes(original, &sample_tags()).await.unwrap();
+        let chunk_offset = {
+            let moov = mp4::atoms(&output).find(|atom| &atom.kind == b"moov").unwrap().payload;
+            let stbl = ["trak", "mdia", "minf", "stbl"].iter().fold(moov, |data, kind| {
+                mp4::child(data, kind.as_bytes().try_into().unwrap()).unwrap().payload
+            });
+            let stco = mp4::child(stbl, b"stco").unwrap().payload;
+            u32::from_be_bytes(stco[8..12].try_into().unwrap()) as usize
+        };
+        assert_eq!(&output[chunk_offset..chunk_offset + 6], b"audio!");
+
+        let path = std::env::temp_dir().join(format!("tagged-{}.m4a", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &output).unwrap();
+        let tags = mp4::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Harbour Lights"));
+        assert_eq!(tags.artists, vec!["Eve, Mallory"]);
+        assert_eq!(tags.isrc.as_deref(), Some("FRZ032400002"));
+        assert_eq!(tags.url, sample_tags().url);
+        assert_eq!(tags.bpm, Some(96.0));
+    }
+
+    #[tokio::test]
+    async fn test_rewrites_opus_tags_in_place() {
+        use crate::codecs::{opus::OpusEncoderImpl, AudioEncoder, EncoderConfig};
+
+        let mut encoder = OpusEncoderImpl::new(EncoderConfig { sample_rate: 48000, ..EncoderConfig::default() }).unwrap();
+        let mut original = encoder.encode(&vec![0.0; 48000 * 2 / 5], 48000, 2).unwrap();
+        original.extend(encoder.finalize().unwrap());
+        let read_back = |data: &[u8]| {
+            let path = std::env::temp_dir().join(format!("tagged-{}.opus", uuid::Uuid::new_v4()));
+            std::fs::write(&path, data).unwrap();
+            let tags = vorbis::read_ogg(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            tags
+        };
+        let packets = |data: &[u8]| {
+            let mut reader = ogg::PacketReader::new(std::io::Cursor::new(data.to_vec()));
+            std::iter::from_fn(|| reader.read_packet().unwrap()).map(|packet| packet.data).collect::<Vec<_>>()
+        };
+
+        let output = tag_bytes(original.clone(), &sample_tags()).await.unwrap();
+        let tags = read_back(&output);
+        assert_eq!(tags.title.as_deref(), Some("Harbour Lights"));
+        assert_eq!(tags.artists, vec!["Eve", "Mallory"]);
+        assert_eq!(tags.cover().unwrap().data, b"\x89PNG\r\n\x1a\n");
+        // Pages audio reprises telles quelles, CRC des pages réécrites valides
+        let (original_packets, output_packets) = (packets(&original), packets(&output));
+        assert_eq!(original_packets.len(), output_packets.len());
+        assert_eq!(original_packets[2..], output_packets[2..]);
+
+        // Une pochette qui ne tient pas dans la page OpusTags d'origine est ignorée
+        let mut large = sample_tags();
+        large.pictures[0].data = vec![0x42; 70_000];
+        let output = tag_bytes(original, &large).await.unwrap();
+        let tags = read_back(&output);
+        assert_eq!(tags.title.as_deref(), Some("Harbour Lights"));
+        assert!(tags.pictures.is_empty());
+        assert_eq!(packets(&output).len(), original_packets.len());
+    }
+
+    #[tokio::test]
+    async fn test_rewrites_wav_id3_chunk() {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut original = std::io::Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut original, spec).unwrap();
+        for sample in 0..101i16 {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut first = sample_tags();
+        first.title = Some("Old".into());
+        let once = tag_bytes(original.into_inner(), &first).await.unwrap();
+        let output = tag_bytes(once, &sample_tags()).await.unwrap();
+
+        // Un seul chunk ID3, celui des nouveaux tags
+        assert_eq!(output.windows(4).filter(|window| window.eq_ignore_ascii_case(b"id3 ")).count(), 1);
+        let tags = id3v2::from_tag(&id3::Tag::read_from_wav(std::io::Cursor::new(&output)).unwrap());
+        assert_eq!(tags.title.as_deref(), Some("Harbour Lights"));
+        assert_eq!(u32::from_le_bytes(output[4..8].try_into().unwrap()) as usize, output.len() - 8);
+        let samples: Vec<i16> = hound::WavReader::new(std::io::Cursor::new(&output)).unwrap()
+            .into_samples().map(Result::unwrap).collect();
+        assert_eq!(samples, (0..101).collect::<Vec<_>>());
+    }
+}
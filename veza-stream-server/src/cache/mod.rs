@@ -1,6 +1,6 @@
 use std::{
     collections::HashMap,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
     time::{Duration, SystemTime},
 };
@@ -10,6 +10,8 @@ use serde::{Serialize, Deserialize};
 
 use sha2::Digest;
 
+use crate::error::{AppError, Result};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileMetadata {
     pub size: u64,
@@ -96,6 +98,30 @@ impl FileCache {
         debug!("Mise en cache pour: {:?}", path);
     }
 
+    /// Retourne les métadonnées du fichier depuis le cache, ou les lit sur le disque
+    /// et les met en cache en cas de miss
+    pub async fn get_or_load(&self, path: &Path) -> Result<FileMetadata> {
+        let path = path.to_path_buf();
+        if let Some(metadata) = self.get(&path).await {
+            return Ok(metadata);
+        }
+
+        let fs_metadata = tokio::fs::metadata(&path).await
+            .map_err(|_| AppError::FileNotFound)?;
+        if !fs_metadata.is_file() {
+            return Err(AppError::FileNotFound);
+        }
+        let modified = fs_metadata.modified()
+            .map_err(|e| AppError::FileError { message: e.to_string() })?;
+        let mime_type = mime_guess::from_path(&path)
+            .first_or_octet_stream()
+            .to_string();
+
+        let metadata = FileMetadata::new(fs_metadata.len(), modified, mime_type);
+        self.set(path, metadata.clone()).await;
+        Ok(metadata)
+    }
+
     pub async fn invalidate(&self, path: &PathBuf) {
         let mut cache = self.cache.write().await;
         cache.remove(path);
@@ -233,4 +259,22 @@ mod tests {
         // Devrait être expiré
         assert!(cache.get(&file_path).await.is_none());
     }
+
+    #[tokio::test]
+    async fn test_get_or_load() {
+        let cache = FileCache::default();
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("track.flac");
+
+        assert!(matches!(cache.get_or_load(&file_path).await, Err(AppError::FileNotFound)));
+
+        fs::write(&file_path, b"fLaC").await.unwrap();
+        let loaded = cache.get_or_load(&file_path).await.unwrap();
+        assert_eq!(loaded.size, 4);
+        assert_eq!(loaded.mime_type, "audio/flac");
+
+        // Le second appel est servi par le cache
+        let cached = cache.get(&file_path).await.unwrap();
+        assert_eq!(cached.etag, loaded.etag);
+    }
 } 
\ No newline at end of file
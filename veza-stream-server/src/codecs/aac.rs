@@ -1,42 +1,66 @@
 /// Codec AAC pour compatibilité universelle
-/// 
+///
 /// AAC est optimal pour :
 /// - Compatibilité iOS/Safari/mobile
 /// - Streaming adaptatif (HLS/DASH)
 /// - Qualité élevée à bitrates moyens
 /// - Support multi-canal
+///
+/// L'encodeur repose sur libfdk-aac, dont la licence n'est pas libre : il
+/// n'est compilé qu'avec la fonctionnalité `fdk-aac`. Il produit un flux ADTS,
+/// que le segmenteur CMAF réencapsule en MP4 fragmenté. Le décodage passe par
+/// symphonia (`CodecFactory::create_decoder("aac", ...)`) dans tous les cas :
+/// l'ancien décodeur libfdk-aac (`AacDecoderImpl`, `AacDecoderConfig`,
+/// `AacDrcMode`, `AacConcealMethod`) a été retiré de l'API.
 
-use std::sync::Arc;
-use std::time::Instant;
+#[cfg(feature = "fdk-aac")]
+use std::{sync::Arc, time::Instant};
+#[cfg(feature = "fdk-aac")]
 use parking_lot::Mutex;
 use serde::{Serialize, Deserialize};
+#[cfg(feature = "fdk-aac")]
 use tracing::debug;
+#[cfg(feature = "fdk-aac")]
+use fdk_aac::enc::{AudioObjectType, BitRate, ChannelMode, EncoderParams, Transport};
 
+#[cfg(feature = "fdk-aac")]
 use crate::error::AppError;
-use super::{
-    AudioEncoder, AudioDecoder, EncoderInfo, DecoderInfo, EncoderMetrics, 
-    DecodedAudio, AudioSampleFormat, EncoderConfig, DecoderConfig
-};
+#[cfg(feature = "fdk-aac")]
+use super::{AudioEncoder, EncoderInfo, EncoderMetrics, EncoderConfig};
+
+/// Fréquences indexées par `sampling_frequency_index` (ISO/IEC 14496-3 §1.6.3.4)
+pub const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// Échantillons par canal d'une frame AAC-LC
+pub const AAC_LC_FRAME_SAMPLES: u32 = 1024;
+
+/// Audio Object Type AAC-LC
+pub const AAC_LC_OBJECT_TYPE: u8 = 2;
 
 /// Implémentation de l'encodeur AAC
+#[cfg(feature = "fdk-aac")]
 #[derive(Debug)]
 pub struct AacEncoderImpl {
     config: AacEncoderConfig,
+    /// Encodeur libfdk-aac, sortie ADTS
+    encoder: fdk_aac::enc::Encoder,
     encoder_state: Arc<Mutex<AacEncoderState>>,
     metrics: EncoderMetrics,
-    sample_buffer: Vec<f32>,
+    /// Buffer de sortie dimensionné pour une frame
+    output_buffer: Vec<u8>,
+    /// Échantillons par canal consommés par frame (1024 en LC, 2048 en HE)
+    frame_length: u32,
+    /// Retard de l'encodeur en échantillons par canal
+    delay: u32,
     frame_size: usize,
 }
 
-/// Implémentation du décodeur AAC
-#[derive(Debug)]
-pub struct AacDecoderImpl {
-    config: AacDecoderConfig,
-    decoder_state: Arc<Mutex<AacDecoderState>>,
-    output_buffer: Vec<f32>,
-}
-
 /// Configuration spécifique à AAC pour l'encodeur
+///
+/// `afterburner` et `bandwidth_mode` sont laissés aux valeurs par défaut de
+/// libfdk-aac, que le binding n'expose pas.
 #[derive(Debug, Clone)]
 pub struct AacEncoderConfig {
     pub sample_rate: u32,
@@ -51,15 +75,6 @@ pub struct AacEncoderConfig {
     pub ps_enabled: bool,       // Parametric Stereo
 }
 
-/// Configuration spécifique à AAC pour le décodeur
-#[derive(Debug, Clone)]
-pub struct AacDecoderConfig {
-    pub sample_rate: u32,
-    pub channels: u8,
-    pub drc_mode: AacDrcMode,   // Dynamic Range Control
-    pub conceal_method: AacConcealMethod,
-}
-
 /// Profils AAC
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum AacProfile {
@@ -108,77 +123,27 @@ pub enum AacBandwidthMode {
     Custom(u32), // Hz
 }
 
-/// Mode de contrôle de dynamique
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum AacDrcMode {
-    Off,
-    Light,
-    Heavy,
-    Auto,
-}
-
-/// Méthode de dissimulation d'erreur
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum AacConcealMethod {
-    SpectralMuting,
-    NoiseSubstitution,
-    EnergyInterpolation,
-}
-
 /// État interne de l'encodeur AAC
+#[cfg(feature = "fdk-aac")]
 #[derive(Debug)]
 struct AacEncoderState {
-    initialized: bool,
-    samples_encoded: u64,
-    _psychoacoustic_model: PsychoacousticModel,
-    _bandwidth_extension: BandwidthExtension,
-}
-
-/// État interne du décodeur AAC
-#[derive(Debug)]
-struct AacDecoderState {
-    initialized: bool,
-    current_frame: u64,
-    _spectral_data: SpectralData,
-}
-
-/// Modèle psychoacoustique simplifié
-#[derive(Debug)]
-struct PsychoacousticModel {
-    _masking_threshold: Vec<f32>,
-    _perceptual_entropy: f32,
-    _tonal_components: Vec<TonalComponent>,
-}
-
-/// Extension de bande passante (SBR/PS)
-#[derive(Debug)]
-struct BandwidthExtension {
-    _sbr_active: bool,
-    _ps_active: bool,
-    _high_freq_reconstruction: Vec<f32>,
-}
-
-/// État de dissimulation d'erreur
-#[derive(Debug)]
-struct ErrorConcealmentState {
-    last_good_spectrum: Option<Vec<f32>>,
-    _concealment_method: AacConcealMethod,
-}
-
-/// Données spectrales
-#[derive(Debug)]
-struct SpectralData {
-    _mdct_coefficients: Vec<f32>,
-    _scale_factors: Vec<u8>,
-    _quantized_spectrum: Vec<i16>,
+    /// Échantillons par canal reçus en entrée
+    samples_in: u64,
+    /// Frames ADTS produites
+    frames_out: u64,
 }
 
-/// Composante tonale pour modèle psychoacoustique
-#[derive(Debug)]
-struct TonalComponent {
-    _frequency: f32,
-    _amplitude: f32,
-    _phase: f32,
+/// Informations extraites d'un header ADTS (ISO/IEC 13818-7 §6.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdtsFrameInfo {
+    /// Taille totale de la frame, header compris
+    pub frame_length: usize,
+    /// Taille du header (7 octets, 9 avec CRC)
+    pub header_length: usize,
+    /// Audio Object Type MPEG-4 (profil ADTS + 1)
+    pub object_type: u8,
+    pub sample_rate: u32,
+    pub channels: u8,
 }
 
 impl Default for AacEncoderConfig {
@@ -198,167 +163,304 @@ impl Default for AacEncoderConfig {
     }
 }
 
-impl Default for AacDecoderConfig {
-    fn default() -> Self {
-        Self {
-            sample_rate: 44100,
-            channels: 2,
-            drc_mode: AacDrcMode::Off,
-            conceal_method: AacConcealMethod::EnergyInterpolation,
+/// Index de fréquence AAC, `None` si la fréquence n'est pas normalisée
+pub fn sampling_frequency_index(sample_rate: u32) -> Option<u8> {
+    AAC_SAMPLE_RATES.iter().position(|&rate| rate == sample_rate).map(|index| index as u8)
+}
+
+/// AudioSpecificConfig minimale (2 octets) pour un flux sans signalisation SBR explicite
+pub fn audio_specific_config(object_type: u8, sample_rate: u32, channels: u8) -> Option<[u8; 2]> {
+    let index = sampling_frequency_index(sample_rate)?;
+    let config = (u16::from(object_type) << 11) | (u16::from(index) << 7) | (u16::from(channels & 0x0F) << 3);
+    Some(config.to_be_bytes())
+}
+
+/// Paramètres lus dans une AudioSpecificConfig (ISO/IEC 14496-3 §1.6.2.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioSpecificConfig {
+    /// Object type du cœur AAC (LC pour un flux HE-AAC)
+    pub object_type: u8,
+    /// Fréquence du cœur AAC, celle des headers ADTS
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+/// Parse une AudioSpecificConfig (séquence d'en-tête FLV/RTMP, esds MP4).
+/// Pour HE-AAC (SBR/PS explicites), renvoie le cœur AAC sous-jacent.
+pub fn parse_audio_specific_config(config: &[u8]) -> Option<AudioSpecificConfig> {
+    let bits = u64::from_be_bytes({
+        let mut padded = [0u8; 8];
+        let len = config.len().min(8);
+        padded[..len].copy_from_slice(&config[..len]);
+        padded
+    });
+    let mut position = 0u32;
+    // Au plus 28 bits lus : les 8 premiers octets suffisent
+    let mut read = |count: u32| {
+        let value = (bits << position) >> (64 - count);
+        position += count;
+        value as u32
+    };
+    if config.len() < 2 {
+        return None;
+    }
+    let mut object_type = read(5);
+    if object_type == 31 {
+        object_type = 32 + read(6);
+    }
+    let frequency_index = read(4);
+    if frequency_index == 15 {
+        // Fréquence explicite sur 24 bits : non représentable en ADTS
+        return None;
+    }
+    let sample_rate = *AAC_SAMPLE_RATES.get(frequency_index as usize)?;
+    let channels = read(4) as u8;
+
+    // SBR (5) / PS (29) : fréquence étendue puis object type du cœur
+    if object_type == 5 || object_type == 29 {
+        let extension_index = read(4);
+        if extension_index == 15 {
+            return None;
         }
+        object_type = read(5);
+    }
+    if !(1..=4).contains(&object_type) || position as usize > config.len() * 8 {
+        return None;
+    }
+
+    // La fréquence ADTS reste celle du cœur : le SBR est redétecté au décodage
+    Some(AudioSpecificConfig {
+        object_type: object_type as u8,
+        sample_rate,
+        channels,
+    })
+}
+
+/// Header ADTS (7 octets, sans CRC) pour une frame AAC brute de `payload_length` octets
+pub fn adts_header(config: &AudioSpecificConfig, payload_length: usize) -> Option<[u8; 7]> {
+    let frame_length = payload_length + 7;
+    if frame_length > 0x1FFF || !(1..=4).contains(&config.object_type) {
+        return None;
     }
+    let index = sampling_frequency_index(config.sample_rate)?;
+    let profile = config.object_type - 1;
+    Some([
+        0xFF,
+        0xF1,
+        (profile << 6) | (index << 2) | ((config.channels >> 2) & 0x01),
+        ((config.channels & 0x03) << 6) | ((frame_length >> 11) as u8 & 0x03),
+        (frame_length >> 3) as u8,
+        ((frame_length as u8 & 0x07) << 5) | 0x1F,
+        0xFC,
+    ])
 }
 
+/// Parse un header ADTS ; `None` si ce n'est pas un header valide
+pub fn parse_adts_header(header: &[u8]) -> Option<AdtsFrameInfo> {
+    if header.len() < 7 || header[0] != 0xFF || header[1] & 0xF6 != 0xF0 {
+        return None;
+    }
+
+    let protection_absent = header[1] & 0x01 == 1;
+    let object_type = (header[2] >> 6) + 1;
+    let sample_rate = *AAC_SAMPLE_RATES.get(usize::from((header[2] >> 2) & 0x0F))?;
+    let channels = ((header[2] & 0x01) << 2) | (header[3] >> 6);
+    let frame_length = (usize::from(header[3] & 0x03) << 11)
+        | (usize::from(header[4]) << 3)
+        | (usize::from(header[5]) >> 5);
+    let header_length = if protection_absent { 7 } else { 9 };
+
+    if frame_length <= header_length {
+        return None;
+    }
+
+    Some(AdtsFrameInfo {
+        frame_length,
+        header_length,
+        object_type,
+        sample_rate,
+        channels,
+    })
+}
+
+/// Découpe un flux ADTS en frames complètes, en ignorant les octets hors frame
+pub fn split_adts_frames(data: &[u8]) -> Vec<(AdtsFrameInfo, &[u8])> {
+    let mut frames = Vec::new();
+    let mut pos = 0;
+
+    while pos + 7 <= data.len() {
+        match parse_adts_header(&data[pos..]) {
+            Some(info) if pos + info.frame_length <= data.len() => {
+                frames.push((info, &data[pos..pos + info.frame_length]));
+                pos += info.frame_length;
+            }
+            Some(_) => break,
+            None => pos += 1,
+        }
+    }
+
+    frames
+}
+
+#[cfg(feature = "fdk-aac")]
 impl AacEncoderImpl {
     pub fn new(config: EncoderConfig) -> Result<Self, AppError> {
+        let profile = match config.bitrate {
+            0..=64_000 => AacProfile::HE,
+            _ => AacProfile::LC,
+        };
         let aac_config = AacEncoderConfig {
             sample_rate: config.sample_rate,
             channels: config.channels,
             bitrate: config.bitrate,
-            profile: match config.bitrate {
-                0..=64_000 => AacProfile::HE,
-                64_001..=128_000 => AacProfile::LC,
-                _ => AacProfile::LC,
+            object_type: match profile {
+                AacProfile::HE => AacObjectType::HE,
+                _ => AacObjectType::LC,
             },
-            object_type: AacObjectType::LC,
+            sbr_enabled: profile == AacProfile::HE,
+            profile,
             vbr_mode: if config.enable_vbr { AacVbrMode::VBR3 } else { AacVbrMode::CBR },
             bandwidth_mode: AacBandwidthMode::Auto,
             afterburner: true,
-            sbr_enabled: config.bitrate <= 128_000,
-            ps_enabled: config.bitrate <= 64_000 && config.channels == 2,
-        };
-        
-        // AAC utilise des frames de 1024 échantillons
-        let frame_size = 1024 * aac_config.channels as usize;
-        
-        let encoder_state = AacEncoderState {
-            initialized: false,
-            samples_encoded: 0,
-            _psychoacoustic_model: PsychoacousticModel {
-                _masking_threshold: vec![0.0; 512],
-                _perceptual_entropy: 0.0,
-                _tonal_components: Vec::new(),
-            },
-            _bandwidth_extension: BandwidthExtension {
-                _sbr_active: aac_config.sbr_enabled,
-                _ps_active: aac_config.ps_enabled,
-                _high_freq_reconstruction: Vec::new(),
-            },
+            ps_enabled: false,
         };
-        
-        let mut encoder = Self {
+
+        Self::with_aac_config(aac_config)
+    }
+
+    /// Crée un encodeur à partir d'une configuration AAC complète
+    pub fn with_aac_config(aac_config: AacEncoderConfig) -> Result<Self, AppError> {
+        Self::validate_config(&aac_config)?;
+
+        let encoder = Self::build_fdk_encoder(&aac_config)?;
+        let info = encoder.info().map_err(|e| AppError::EncodingError {
+            message: format!("Informations encodeur AAC indisponibles: {}", e),
+        })?;
+        let frame_length = info.frameLength;
+        let frame_size = frame_length as usize * aac_config.channels as usize;
+
+        debug!("Encodeur AAC initialisé: {}Hz, {} ch, profile {:?}, retard {} échantillons",
+               aac_config.sample_rate, aac_config.channels, aac_config.profile, info.nDelay);
+
+        Ok(Self {
             config: aac_config,
-            encoder_state: Arc::new(Mutex::new(encoder_state)),
+            encoder,
+            encoder_state: Arc::new(Mutex::new(AacEncoderState {
+                samples_in: 0,
+                frames_out: 0,
+            })),
             metrics: EncoderMetrics::default(),
-            sample_buffer: Vec::with_capacity(frame_size * 2),
+            output_buffer: vec![0u8; info.maxOutBufBytes.max(768 * 8) as usize],
+            frame_length,
+            delay: info.nDelay,
             frame_size,
-        };
-        
-        encoder.initialize()?;
-        Ok(encoder)
-    }
-    
-    fn initialize(&mut self) -> Result<(), AppError> {
-        self.validate_config()?;
-        
-        {
-            let mut state = self.encoder_state.lock();
-            state.initialized = true;
-        }
-        
-        debug!("Encodeur AAC initialisé: {}Hz, {} ch, profile {:?}", 
-               self.config.sample_rate, self.config.channels, self.config.profile);
-        Ok(())
+        })
     }
-    
-    fn validate_config(&self) -> Result<(), AppError> {
+
+    fn validate_config(config: &AacEncoderConfig) -> Result<(), AppError> {
         // AAC supporte de nombreux sample rates
         let valid_rates = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 88200, 96000];
-        if !valid_rates.contains(&self.config.sample_rate) {
+        if !valid_rates.contains(&config.sample_rate) {
             return Err(AppError::InvalidSampleRate {
-                rate: self.config.sample_rate,
+                rate: config.sample_rate,
             });
         }
-        
-        // Jusqu'à 8 canaux
-        if self.config.channels == 0 || self.config.channels > 8 {
+
+        // Mono ou stéréo : le binding libfdk-aac est limité à deux canaux
+        if config.channels == 0 || config.channels > 2 {
             return Err(AppError::InvalidChannelCount {
-                channels: self.config.channels,
+                channels: config.channels,
             });
         }
-        
+
         // Bitrate : 8kbps à 800kbps
-        if self.config.bitrate < 8_000 || self.config.bitrate > 800_000 {
+        if config.bitrate < 8_000 || config.bitrate > 800_000 {
             return Err(AppError::InvalidBitrate {
-                bitrate: self.config.bitrate,
+                bitrate: config.bitrate,
                 codec: "aac".to_string(),
             });
         }
-        
-        Ok(())
-    }
-    
-    fn encode_aac_frame(&mut self, samples: &[f32]) -> Result<Vec<u8>, AppError> {
-        let start_time = Instant::now();
-        
-        // Simulation d'encodage AAC avec modèle psychoacoustique
-        let estimated_size = self.estimate_aac_frame_size(samples);
-        let mut encoded_data = vec![0u8; estimated_size];
-        
-        // Simulation des étapes AAC
-        self.simulate_mdct_transform(samples)?;
-        self.simulate_psychoacoustic_modeling(samples)?;
-        self.simulate_quantization_and_coding(&mut encoded_data)?;
-        
-        let encoding_time = start_time.elapsed();
-        self.update_metrics(samples.len(), encoded_data.len(), encoding_time);
-        
-        Ok(encoded_data)
-    }
-    
-    fn estimate_aac_frame_size(&self, samples: &[f32]) -> usize {
-        let bits_per_sample = self.config.bitrate as f32 / self.config.sample_rate as f32;
-        let estimated_bits = samples.len() as f32 * bits_per_sample;
-        (estimated_bits / 8.0) as usize
-    }
-    
-    fn simulate_mdct_transform(&self, _samples: &[f32]) -> Result<(), AppError> {
-        // Simulation de la transformée MDCT
-        debug!("MDCT transform simulé");
+
+        // Les profils Error Resilient ne sont pas transportables en ADTS
+        if matches!(config.profile, AacProfile::LD | AacProfile::ELD) {
+            return Err(AppError::EncodingError {
+                message: format!("Profil AAC {:?} incompatible avec un flux ADTS", config.profile),
+            });
+        }
+
         Ok(())
     }
-    
-    fn simulate_psychoacoustic_modeling(&self, samples: &[f32]) -> Result<(), AppError> {
-        // Calcul simplifié du masquage
-        let _energy = samples.iter().map(|&s| s * s).sum::<f32>() / samples.len() as f32;
-        debug!("Modèle psychoacoustique appliqué");
-        Ok(())
+
+    fn build_fdk_encoder(config: &AacEncoderConfig) -> Result<fdk_aac::enc::Encoder, AppError> {
+        let audio_object_type = match config.profile {
+            AacProfile::HEv2 if config.channels == 2 => AudioObjectType::Mpeg4HeAacV2,
+            AacProfile::HE | AacProfile::HEv2 => AudioObjectType::Mpeg4HeAac,
+            _ => AudioObjectType::Mpeg4LowComplexity,
+        };
+        let bit_rate = match config.vbr_mode {
+            AacVbrMode::CBR => BitRate::Cbr(config.bitrate),
+            AacVbrMode::VBR1 => BitRate::VbrVeryLow,
+            AacVbrMode::VBR2 => BitRate::VbrLow,
+            AacVbrMode::VBR3 => BitRate::VbrMedium,
+            AacVbrMode::VBR4 => BitRate::VbrHigh,
+            AacVbrMode::VBR5 => BitRate::VbrVeryHigh,
+        };
+
+        fdk_aac::enc::Encoder::new(EncoderParams {
+            bit_rate,
+            sample_rate: config.sample_rate,
+            transport: Transport::Adts,
+            channels: if config.channels == 1 { ChannelMode::Mono } else { ChannelMode::Stereo },
+            audio_object_type,
+        })
+        .map_err(|e| AppError::EncodingError {
+            message: format!("Initialisation de l'encodeur AAC impossible: {}", e),
+        })
     }
-    
-    fn simulate_quantization_and_coding(&self, output: &mut [u8]) -> Result<(), AppError> {
-        // Simulation de quantification et codage entropique
-        for (i, byte) in output.iter_mut().enumerate() {
-            let _i = i;
-            *byte = (i % 256) as u8; // Données simulées
+
+    /// Passe des échantillons 16 bits à libfdk-aac et récupère les frames ADTS
+    /// produites. Un appel avec une entrée vide vide les frames déjà complètes.
+    fn run_encoder(&mut self, pcm: &[i16]) -> Result<Vec<u8>, AppError> {
+        let mut encoded = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let start_time = Instant::now();
+            let result = self
+                .encoder
+                .encode(&pcm[offset..], &mut self.output_buffer)
+                .map_err(|e| AppError::EncodingError {
+                    message: format!("Encodage AAC échoué: {}", e),
+                })?;
+            offset += result.input_consumed;
+
+            if result.output_size > 0 {
+                encoded.extend_from_slice(&self.output_buffer[..result.output_size]);
+                self.update_metrics(result.output_size, start_time.elapsed());
+            } else if offset >= pcm.len() || result.input_consumed == 0 {
+                break;
+            }
         }
-        Ok(())
+
+        Ok(encoded)
     }
-    
-    fn update_metrics(&mut self, input_samples: usize, output_bytes: usize, encoding_time: std::time::Duration) {
+
+    fn update_metrics(&mut self, output_bytes: usize, encoding_time: std::time::Duration) {
         self.metrics.frames_encoded += 1;
         self.metrics.bytes_output += output_bytes as u64;
         self.metrics.encoding_time_ms += encoding_time.as_millis() as u64;
-        
-        let input_bytes = input_samples * 4;
-        self.metrics.compression_ratio = input_bytes as f32 / output_bytes as f32;
-        
+
+        let input_bytes = self.metrics.frames_encoded * self.frame_size as u64 * 4;
+        self.metrics.compression_ratio = input_bytes as f32 / self.metrics.bytes_output.max(1) as f32;
+
         {
             let mut state = self.encoder_state.lock();
-            state.samples_encoded += 1;
+            state.frames_out += 1;
         }
     }
 }
 
+#[cfg(feature = "fdk-aac")]
 impl AudioEncoder for AacEncoderImpl {
     fn encode(&mut self, samples: &[f32], sample_rate: u32, channels: u8) -> Result<Vec<u8>, AppError> {
         if sample_rate != self.config.sample_rate || channels != self.config.channels {
@@ -367,168 +469,185 @@ impl AudioEncoder for AacEncoderImpl {
                 got: format!("{}Hz, {} ch", sample_rate, channels),
             });
         }
-        
-        self.sample_buffer.extend_from_slice(samples);
-        let mut encoded_frames = Vec::new();
-        
-        while self.sample_buffer.len() >= self.frame_size {
-            let frame_samples: Vec<f32> = self.sample_buffer.drain(..self.frame_size).collect();
-            let encoded_frame = self.encode_aac_frame(&frame_samples)?;
-            encoded_frames.extend(encoded_frame);
-        }
-        
-        Ok(encoded_frames)
+
+        let pcm: Vec<i16> = samples
+            .iter()
+            .map(|&s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        self.encoder_state.lock().samples_in += (samples.len() / channels as usize) as u64;
+
+        self.run_encoder(&pcm)
     }
-    
+
     fn finalize(&mut self) -> Result<Vec<u8>, AppError> {
-        if !self.sample_buffer.is_empty() {
-            while self.sample_buffer.len() < self.frame_size {
-                self.sample_buffer.push(0.0);
-            }
-            let final_frame: Vec<f32> = self.sample_buffer.drain(..).collect();
-            return self.encode_aac_frame(&final_frame);
+        // Silence jusqu'à ce que toute l'entrée, retard de l'encodeur compris,
+        // soit sortie en frames complètes
+        let (samples_in, mut frames_out) = {
+            let state = self.encoder_state.lock();
+            (state.samples_in, state.frames_out)
+        };
+        let needed = (samples_in + u64::from(self.delay)).div_ceil(u64::from(self.frame_length));
+        let silence = vec![0i16; self.frame_size];
+
+        let mut encoded = self.run_encoder(&[])?;
+        while frames_out < needed {
+            encoded.extend(self.run_encoder(&silence)?);
+            frames_out = self.encoder_state.lock().frames_out;
         }
-        Ok(Vec::new())
+
+        // Un nouveau flux repart d'un encodeur vierge
+        self.reset()?;
+        Ok(encoded)
     }
-    
+
     fn reset(&mut self) -> Result<(), AppError> {
-        self.sample_buffer.clear();
+        self.encoder = Self::build_fdk_encoder(&self.config)?;
         self.metrics = EncoderMetrics::default();
-        
+
         {
             let mut state = self.encoder_state.lock();
-            state.samples_encoded = 0;
+            state.samples_in = 0;
+            state.frames_out = 0;
         }
-        
+
         Ok(())
     }
-    
+
+    /// Le binding libfdk-aac ne permet pas de modifier le débit d'un encodeur
+    /// en cours : le nouveau bitrate s'applique au prochain flux (`reset`).
     fn set_bitrate(&mut self, bitrate: u32) -> Result<(), AppError> {
-        if bitrate < 8_000 || bitrate > 800_000 {
+        if !(8_000..=800_000).contains(&bitrate) {
             return Err(AppError::InvalidBitrate {
                 bitrate,
                 codec: "aac".to_string(),
             });
         }
-        
+
         self.config.bitrate = bitrate;
         Ok(())
     }
-    
+
     fn info(&self) -> EncoderInfo {
         EncoderInfo {
             codec_name: "AAC".to_string(),
-            version: "2.0".to_string(),
+            version: "libfdk-aac".to_string(),
             bitrate: self.config.bitrate,
             sample_rate: self.config.sample_rate,
             channels: self.config.channels,
             bit_depth: 16,
             frame_size: self.frame_size,
-            latency_ms: 23.2, // 1024 samples à 44.1kHz
+            latency_ms: (self.frame_length + self.delay) as f32 * 1000.0 / self.config.sample_rate as f32,
             quality_mode: format!("{:?}", self.config.profile),
         }
     }
-    
+
     fn metrics(&self) -> EncoderMetrics {
         self.metrics.clone()
     }
 }
 
-impl AacDecoderImpl {
-    pub fn new(config: DecoderConfig) -> Result<Self, AppError> {
-        let aac_config = AacDecoderConfig {
-            sample_rate: config.sample_rate,
-            channels: config.channels,
-            drc_mode: AacDrcMode::Off,
-            conceal_method: AacConcealMethod::EnergyInterpolation,
-        };
-        
-        let decoder_state = AacDecoderState {
-            initialized: false,
-            current_frame: 0,
-            _spectral_data: SpectralData {
-                _mdct_coefficients: Vec::new(),
-                _scale_factors: Vec::new(),
-                _quantized_spectrum: Vec::new(),
-            },
-        };
-        
-        let mut decoder = Self {
-            config: aac_config,
-            decoder_state: Arc::new(Mutex::new(decoder_state)),
-            output_buffer: Vec::new(),
-        };
-        
-        decoder.initialize()?;
-        Ok(decoder)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "fdk-aac")]
+    fn sine(sample_rate: u32, channels: u8, seconds: f32) -> Vec<f32> {
+        let frames = (sample_rate as f32 * seconds) as usize;
+        (0..frames)
+            .flat_map(|i| {
+                let s = (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin() * 0.5;
+                std::iter::repeat(s).take(channels as usize)
+            })
+            .collect()
     }
-    
-    fn initialize(&mut self) -> Result<(), AppError> {
-        {
-            let mut state = self.decoder_state.lock();
-            state.initialized = true;
+
+    #[cfg(feature = "fdk-aac")]
+    fn encode_adts(config: AacEncoderConfig, pcm: &[f32]) -> Vec<u8> {
+        let (sample_rate, channels) = (config.sample_rate, config.channels);
+        let mut encoder = AacEncoderImpl::with_aac_config(config).unwrap();
+        let mut adts = Vec::new();
+        for chunk in pcm.chunks(1000 * channels as usize) {
+            adts.extend(encoder.encode(chunk, sample_rate, channels).unwrap());
         }
-        Ok(())
+        adts.extend(encoder.finalize().unwrap());
+        adts
     }
-}
 
-impl AudioDecoder for AacDecoderImpl {
-    fn decode(&mut self, data: &[u8]) -> Result<DecodedAudio, AppError> {
-        let frame_size = 1024 * self.config.channels as usize;
-        let mut samples = Vec::with_capacity(frame_size);
-        
-        // Simulation simple de décodage AAC
-        for (_i, &byte) in data.iter().enumerate() {
-            if samples.len() < frame_size {
-                let sample = (byte as f32 - 128.0) / 128.0;
-                samples.push(sample);
-                
-                // Interpolation
-                for _ in 0..3 {
-                    if samples.len() < frame_size {
-                        samples.push(sample * 0.9);
-                    }
-                }
-            }
-        }
-        
-        while samples.len() < frame_size {
-            samples.push(0.0);
-        }
-        
-        {
-            let mut state = self.decoder_state.lock();
-            state.current_frame += 1;
+    #[cfg(feature = "fdk-aac")]
+    #[test]
+    fn test_adts_round_trip_with_symphonia() {
+        use std::io::Cursor;
+        use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_AAC};
+        use symphonia::core::formats::{FormatOptions, FormatReader};
+        use symphonia::core::io::MediaSourceStream;
+        use symphonia::default::formats::AdtsReader;
+
+        let config = AacEncoderConfig {
+            vbr_mode: AacVbrMode::CBR,
+            ..AacEncoderConfig::default()
+        };
+        let pcm = sine(44100, 2, 1.0);
+        let adts = encode_adts(config, &pcm);
+
+        let source = MediaSourceStream::new(Box::new(Cursor::new(adts)), Default::default());
+        let mut reader = AdtsReader::try_new(source, &FormatOptions::default()).unwrap();
+        let params = reader.default_track().unwrap().codec_params.clone();
+        assert_eq!(params.codec, CODEC_TYPE_AAC);
+        assert_eq!(params.sample_rate, Some(44100));
+
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .unwrap();
+        let mut decoded = Vec::new();
+        while let Ok(packet) = reader.next_packet() {
+            let audio = decoder.decode(&packet).unwrap();
+            let mut buffer = symphonia::core::audio::SampleBuffer::<f32>::new(audio.capacity() as u64, *audio.spec());
+            buffer.copy_interleaved_ref(audio);
+            decoded.extend_from_slice(buffer.samples());
         }
-        
-        Ok(DecodedAudio {
-            samples,
-            sample_rate: self.config.sample_rate,
-            channels: self.config.channels,
-            duration_ms: 23, // ~23ms pour 1024 samples à 44.1kHz
-            format: AudioSampleFormat::F32,
-        })
+        assert!(decoded.len() >= pcm.len());
+
+        // Le signal décodé, recalé sur le retard de l'encodeur, suit l'original
+        let window = 2 * 22050;
+        let correlation = |lag: usize| {
+            let (original, output) = (&pcm[8820..8820 + window], &decoded[8820 + lag..8820 + lag + window]);
+            let dot: f32 = original.iter().zip(output).map(|(a, b)| a * b).sum();
+            let energy = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>().sqrt();
+            dot / (energy(original) * energy(output))
+        };
+        let best = (0..4096).step_by(2).map(correlation).fold(f32::MIN, f32::max);
+        assert!(best > 0.95);
     }
-    
-    fn reset(&mut self) -> Result<(), AppError> {
-        self.output_buffer.clear();
-        
-        {
-            let mut state = self.decoder_state.lock();
-            state.current_frame = 0;
-        }
-        
-        Ok(())
+
+    #[cfg(feature = "fdk-aac")]
+    #[test]
+    fn test_adts_headers_match_audio_specific_config() {
+        let adts = encode_adts(AacEncoderConfig::default(), &sine(44100, 2, 0.2));
+        let frames = split_adts_frames(&adts);
+        assert!(!frames.is_empty());
+
+        let (info, _) = frames[0];
+        assert_eq!(info.object_type, AAC_LC_OBJECT_TYPE);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.channels, 2);
+        assert_eq!(audio_specific_config(AAC_LC_OBJECT_TYPE, 44100, 2), Some([0x12, 0x10]));
     }
-    
-    fn info(&self) -> DecoderInfo {
-        DecoderInfo {
-            codec_name: "AAC".to_string(),
-            version: "2.0".to_string(),
-            sample_rate: self.config.sample_rate,
-            channels: self.config.channels,
-            bit_depth: 16,
-            frame_size: 1024 * self.config.channels as usize,
-        }
+
+    #[test]
+    fn test_adts_header_from_audio_specific_config() {
+        let config = parse_audio_specific_config(&[0x12, 0x10]).unwrap();
+        assert_eq!(config, AudioSpecificConfig { object_type: AAC_LC_OBJECT_TYPE, sample_rate: 44100, channels: 2 });
+
+        let header = adts_header(&config, 300).unwrap();
+        let info = parse_adts_header(&header).unwrap();
+        assert_eq!(info.frame_length, 307);
+        assert_eq!(info.header_length, 7);
+        assert_eq!(info.object_type, AAC_LC_OBJECT_TYPE);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.channels, 2);
+
+        // HE-AAC v1 à 44,1 kHz : cœur LC à 22,05 kHz
+        let he_aac = parse_audio_specific_config(&[0x2B, 0x92, 0x08, 0x00]).unwrap();
+        assert_eq!(he_aac, AudioSpecificConfig { object_type: AAC_LC_OBJECT_TYPE, sample_rate: 22050, channels: 2 });
     }
-} 
\ No newline at end of file
+}
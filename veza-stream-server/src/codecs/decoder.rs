@@ -0,0 +1,644 @@
+//! Décodeur unifié basé sur symphonia
+//!
+//! Un seul chemin de décodage pour MP3, FLAC, WAV, OGG/Vorbis, Opus et
+//! MP4/AAC : démultiplexage et décodage par symphonia, Opus étant branché sur
+//! le décodeur opus-rs. Le décodeur fournit :
+//! - un seek précis à l'échantillon près
+//! - la suppression du délai et du padding encodeur (lecture gapless)
+//! - un flux asynchrone de `DecodedAudio` partagé par l'encodage, la
+//!   waveform et l'analyse
+
+use std::{fmt, fs, io::Cursor, path::Path, time::Duration};
+
+use futures::Stream;
+use once_cell::sync::Lazy;
+use symphonia::core::{
+    audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, SampleBuffer, Signal, SignalSpec},
+    codecs::{
+        CodecDescriptor, CodecParameters, CodecRegistry, Decoder, DecoderOptions, FinalizeResult,
+        CODEC_TYPE_NULL, CODEC_TYPE_OPUS,
+    },
+    errors::{Error as SymphoniaError, Result as SymphoniaResult},
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream, ReadOnlySource},
+    meta::MetadataOptions,
+    probe::Hint,
+    support_codec,
+    units::{Time, TimeBase},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, warn};
+
+use crate::codecs::{
+    AudioDecoder, AudioSampleFormat, DecodedAudio, DecoderConfig, DecoderInfo, OpusDecoderConfig, OpusDecoderImpl,
+};
+use crate::error::AppError;
+
+/// Nombre de chunks décodés d'avance par `into_stream`
+const STREAM_BUFFER_CHUNKS: usize = 8;
+/// Durée maximale d'un paquet Opus : 120 ms à 48 kHz
+const OPUS_MAX_PACKET_FRAMES: usize = 5760;
+/// Pré-roll avant la cible d'un seek Opus
+const OPUS_SEEK_PREROLL: Duration = Duration::from_millis(80);
+
+/// Registre symphonia complété par le décodeur Opus
+static CODEC_REGISTRY: Lazy<CodecRegistry> = Lazy::new(|| {
+    let mut registry = CodecRegistry::new();
+    symphonia::default::register_enabled_codecs(&mut registry);
+    registry.register_all::<OpusPacketDecoder>();
+    registry
+});
+
+/// Délai et padding encodeur d'un flux, en frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GaplessInfo {
+    /// Frames de démarrage ajoutées par l'encodeur (priming, pre-skip Opus)
+    pub encoder_delay: u32,
+    /// Frames de silence ajoutées en fin de flux pour compléter la dernière frame
+    pub encoder_padding: u32,
+    /// Nombre de frames utiles, délai et padding exclus, si le conteneur le déclare
+    pub total_frames: Option<u64>,
+}
+
+/// Décodeur audio générique au-dessus de symphonia
+pub struct SymphoniaDecoder {
+    config: DecoderConfig,
+    /// Extension utilisée comme indice de format par `decode`
+    extension: Option<String>,
+    stream: Option<MediaStream>,
+}
+
+/// Flux ouvert : démultiplexeur, décodeur et position de lecture
+struct MediaStream {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    codec_name: &'static str,
+    sample_rate: u32,
+    channels: usize,
+    bits_per_sample: u8,
+    gapless: GaplessInfo,
+    sample_buffer: Option<SampleBuffer<f32>>,
+    /// Délai que le démultiplexeur laisse dans les timestamps : symphonia
+    /// ne retire pas le pre-skip Opus, contrairement au délai MP3
+    ts_offset: u64,
+    /// Frames à écarter (délai non retiré, ou approche d'un seek) avant
+    /// l'échantillon demandé
+    skip_frames: u64,
+    /// Position courante en frames depuis le début du flux utile
+    position: u64,
+}
+
+impl fmt::Debug for SymphoniaDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SymphoniaDecoder")
+            .field("config", &self.config)
+            .field("extension", &self.extension)
+            .field("codec", &self.stream.as_ref().map(|s| s.codec_name))
+            .field("position", &self.stream.as_ref().map(|s| s.position))
+            .finish()
+    }
+}
+
+impl SymphoniaDecoder {
+    /// Crée un décodeur sans flux ouvert, alimenté par `AudioDecoder::decode`
+    pub fn new(config: DecoderConfig) -> Self {
+        Self {
+            config,
+            extension: None,
+            stream: None,
+        }
+    }
+
+    /// Indique l'extension (`mp3`, `flac`, `ogg`, `m4a`...) des données passées à `decode`
+    pub fn with_extension(mut self, extension: &str) -> Self {
+        self.extension = Some(extension.to_lowercase());
+        self
+    }
+
+    /// Ouvre un fichier audio ; le format est détecté par son contenu et son extension
+    pub fn open(path: &Path) -> Result<Self, AppError> {
+        let file = fs::File::open(path).map_err(|_| AppError::FileNotFound)?;
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+        Self::from_source(Box::new(file), extension)
+    }
+
+    /// Ouvre un média complet déjà en mémoire
+    pub fn from_bytes(data: Vec<u8>, extension: Option<&str>) -> Result<Self, AppError> {
+        Self::from_source(Box::new(Cursor::new(data)), extension.map(str::to_lowercase))
+    }
+
+    /// Ouvre un flux continu non seekable (source live) : `next_chunk` bloque
+    /// jusqu'à l'arrivée de données et renvoie `None` quand le lecteur se ferme
+    pub fn from_reader<R>(reader: R, extension: Option<&str>) -> Result<Self, AppError>
+    where
+        R: std::io::Read + Send + Sync + 'static,
+    {
+        Self::from_source(Box::new(ReadOnlySource::new(reader)), extension.map(str::to_lowercase))
+    }
+
+    fn from_source(source: Box<dyn MediaSource>, extension: Option<String>) -> Result<Self, AppError> {
+        let stream = MediaStream::open(source, extension.as_deref())?;
+        let config = DecoderConfig {
+            sample_rate: stream.sample_rate,
+            channels: stream.channels as u8,
+            output_format: AudioSampleFormat::F32,
+        };
+        Ok(Self {
+            config,
+            extension,
+            stream: Some(stream),
+        })
+    }
+
+    fn stream_mut(&mut self) -> Result<&mut MediaStream, AppError> {
+        self.stream.as_mut().ok_or_else(|| AppError::DecodingError {
+            message: "Aucun flux audio ouvert".to_string(),
+        })
+    }
+
+    /// Décode le prochain paquet non vide ; `None` en fin de flux
+    pub fn next_chunk(&mut self) -> Result<Option<DecodedAudio>, AppError> {
+        self.stream_mut()?.next_chunk()
+    }
+
+    /// Décode tout le reste du flux en un seul bloc
+    pub fn decode_all(&mut self) -> Result<DecodedAudio, AppError> {
+        let stream = self.stream_mut()?;
+        let mut samples = Vec::new();
+        while let Some(chunk) = stream.next_chunk()? {
+            samples.extend_from_slice(&chunk.samples);
+        }
+        Ok(stream.to_decoded(samples))
+    }
+
+    /// Se positionne sur `position` ; le prochain chunk commence exactement
+    /// à l'échantillon demandé. Renvoie la position effective.
+    pub fn seek(&mut self, position: Duration) -> Result<Duration, AppError> {
+        self.stream_mut()?.seek(position)
+    }
+
+    /// Position de lecture courante
+    pub fn position(&self) -> Duration {
+        self.stream
+            .as_ref()
+            .map(|s| frames_to_duration(s.position, s.sample_rate))
+            .unwrap_or_default()
+    }
+
+    /// Durée totale du flux utile, si le conteneur la déclare
+    pub fn duration(&self) -> Option<Duration> {
+        let stream = self.stream.as_ref()?;
+        stream
+            .gapless
+            .total_frames
+            .map(|frames| frames_to_duration(frames, stream.sample_rate))
+    }
+
+    /// Délai et padding encodeur du flux ouvert
+    pub fn gapless(&self) -> GaplessInfo {
+        self.stream.as_ref().map(|s| s.gapless).unwrap_or_default()
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.config.sample_rate
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.config.channels
+    }
+
+    /// Transforme le décodeur en flux asynchrone de chunks. Le décodage tourne
+    /// dans `spawn_blocking` et s'arrête dès que le flux est abandonné.
+    pub fn into_stream(mut self) -> impl Stream<Item = Result<DecodedAudio, AppError>> {
+        let (tx, rx) = tokio::sync::mpsc::channel(STREAM_BUFFER_CHUNKS);
+
+        tokio::task::spawn_blocking(move || loop {
+            match self.next_chunk() {
+                Ok(Some(chunk)) => {
+                    if tx.blocking_send(Ok(chunk)).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                    break;
+                }
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+}
+
+impl AudioDecoder for SymphoniaDecoder {
+    /// Décode un média autonome en mémoire (fichier complet, segment, suite
+    /// de frames MP3 ou ADTS) et renvoie tout son audio
+    fn decode(&mut self, data: &[u8]) -> Result<DecodedAudio, AppError> {
+        let stream = MediaStream::open(Box::new(Cursor::new(data.to_vec())), self.extension.as_deref())?;
+        self.config.sample_rate = stream.sample_rate;
+        self.config.channels = stream.channels as u8;
+        self.stream = Some(stream);
+        self.decode_all()
+    }
+
+    fn reset(&mut self) -> Result<(), AppError> {
+        self.stream = None;
+        Ok(())
+    }
+
+    fn info(&self) -> DecoderInfo {
+        DecoderInfo {
+            codec_name: self.stream.as_ref().map_or("symphonia", |s| s.codec_name).to_string(),
+            version: "symphonia 0.5".to_string(),
+            sample_rate: self.config.sample_rate,
+            channels: self.config.channels,
+            bit_depth: self.stream.as_ref().map_or(32, |s| s.bits_per_sample),
+            frame_size: 0,
+        }
+    }
+}
+
+impl MediaStream {
+    fn open(source: Box<dyn MediaSource>, extension: Option<&str>) -> Result<Self, AppError> {
+        let mut hint = Hint::new();
+        if let Some(extension) = extension {
+            hint.with_extension(extension);
+        }
+
+        let format_options = FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        };
+        let probed = symphonia::default::get_probe()
+            .format(&hint, MediaSourceStream::new(source, Default::default()), &format_options, &MetadataOptions::default())
+            .map_err(decoding_error)?;
+
+        let format = probed.format;
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| AppError::DecodingError {
+                message: "Aucune piste audio décodable".to_string(),
+            })?;
+        let track_id = track.id;
+        let params = track.codec_params.clone();
+
+        let decoder = CODEC_REGISTRY
+            .make(&params, &DecoderOptions::default())
+            .map_err(decoding_error)?;
+        let codec_name = CODEC_REGISTRY.get_codec(params.codec).map_or("unknown", |d| d.short_name);
+
+        let sample_rate = params.sample_rate.unwrap_or(44100);
+        let channels = params.channels.map_or(2, |c| c.count());
+        let ts_offset = untrimmed_delay(&params);
+        let gapless = GaplessInfo {
+            encoder_delay: params.delay.unwrap_or(0),
+            encoder_padding: params.padding.unwrap_or(0),
+            total_frames: params.n_frames.map(|frames| frames.saturating_sub(ts_offset)),
+        };
+
+        debug!("Flux {} ouvert: {} Hz, {} canaux, délai {} / padding {} frames",
+               codec_name, sample_rate, channels, gapless.encoder_delay, gapless.encoder_padding);
+
+        Ok(Self {
+            format,
+            decoder,
+            track_id,
+            time_base: params.time_base,
+            codec_name,
+            sample_rate,
+            channels,
+            bits_per_sample: params.bits_per_sample.map_or(32, |bits| bits.min(32) as u8),
+            gapless,
+            sample_buffer: None,
+            ts_offset,
+            skip_frames: ts_offset,
+            position: 0,
+        })
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<DecodedAudio>, AppError> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Ok(None);
+                }
+                Err(SymphoniaError::ResetRequired) => {
+                    // Nouveau flux chaîné (Ogg) : nouveaux paramètres de codec
+                    self.reopen_track()?;
+                    continue;
+                }
+                Err(e) => return Err(decoding_error(e)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(e)) => {
+                    warn!("Paquet {} corrompu ignoré: {}", self.codec_name, e);
+                    continue;
+                }
+                Err(e) => return Err(decoding_error(e)),
+            };
+
+            let spec = *decoded.spec();
+            let channels = spec.channels.count();
+            let buffer = match self.sample_buffer.as_mut() {
+                Some(buffer) if buffer.capacity() >= decoded.capacity() * channels => buffer,
+                _ => self.sample_buffer.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+            };
+            buffer.copy_interleaved_ref(decoded);
+
+            let frames = buffer.samples().len() / channels.max(1);
+            let skip = self.skip_frames.min(frames as u64) as usize;
+            self.skip_frames -= skip as u64;
+            if skip == frames {
+                continue;
+            }
+
+            self.sample_rate = spec.rate;
+            self.channels = channels;
+            self.position += (frames - skip) as u64;
+            let samples = buffer.samples()[skip * channels..].to_vec();
+            return Ok(Some(self.to_decoded(samples)));
+        }
+    }
+
+    fn reopen_track(&mut self) -> Result<(), AppError> {
+        let track = self
+            .format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| AppError::DecodingError {
+                message: "Aucune piste audio décodable".to_string(),
+            })?;
+        self.track_id = track.id;
+        self.time_base = track.codec_params.time_base;
+        self.ts_offset = untrimmed_delay(&track.codec_params);
+        self.skip_frames += self.ts_offset;
+        self.decoder = CODEC_REGISTRY
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(decoding_error)?;
+        Ok(())
+    }
+
+    fn seek(&mut self, position: Duration) -> Result<Duration, AppError> {
+        // Opus a besoin de 80 ms de pré-roll pour que le décodeur converge
+        // (RFC 7845, 4.6) : on se place avant la cible et on écarte le surplus
+        let target = position + frames_to_duration(self.ts_offset, self.sample_rate);
+        let preroll = if self.decoder.codec_params().codec == CODEC_TYPE_OPUS { OPUS_SEEK_PREROLL } else { Duration::ZERO };
+        let seeked = self
+            .format
+            .seek(SeekMode::Accurate, SeekTo::Time { time: Time::from(target.saturating_sub(preroll)), track_id: Some(self.track_id) })
+            .map_err(|e| AppError::DecodingError {
+                message: format!("Seek à {:?} impossible: {}", position, e),
+            })?;
+        self.decoder.reset();
+
+        // Le démultiplexeur s'arrête sur le paquet qui contient le point
+        // demandé : le décodage reprend là et tout ce qui précède la cible est écarté
+        let required = self.ts_to_frames(seeked.required_ts) + duration_to_frames(target, self.sample_rate)
+            - duration_to_frames(target.saturating_sub(preroll), self.sample_rate);
+        let actual = self.ts_to_frames(seeked.actual_ts);
+        self.skip_frames = required.saturating_sub(actual);
+        self.position = required.saturating_sub(self.ts_offset);
+
+        Ok(frames_to_duration(self.position, self.sample_rate))
+    }
+
+    fn ts_to_frames(&self, ts: u64) -> u64 {
+        match self.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(ts);
+                time.seconds * u64::from(self.sample_rate) + (time.frac * f64::from(self.sample_rate)).round() as u64
+            }
+            None => ts,
+        }
+    }
+
+    fn to_decoded(&self, samples: Vec<f32>) -> DecodedAudio {
+        let frames = samples.len() / self.channels.max(1);
+        DecodedAudio {
+            duration_ms: (frames as u64 * 1000 / u64::from(self.sample_rate.max(1))) as u32,
+            samples,
+            sample_rate: self.sample_rate,
+            channels: self.channels as u8,
+            format: AudioSampleFormat::F32,
+        }
+    }
+}
+
+/// Délai encodeur que le démultiplexeur n'a pas retiré des paquets
+fn untrimmed_delay(params: &CodecParameters) -> u64 {
+    if params.codec == CODEC_TYPE_OPUS {
+        u64::from(params.delay.unwrap_or(0))
+    } else {
+        0
+    }
+}
+
+fn decoding_error(e: SymphoniaError) -> AppError {
+    AppError::DecodingError { message: e.to_string() }
+}
+
+fn frames_to_duration(frames: u64, sample_rate: u32) -> Duration {
+    Duration::from_secs_f64(frames as f64 / f64::from(sample_rate.max(1)))
+}
+
+fn duration_to_frames(duration: Duration, sample_rate: u32) -> u64 {
+    (duration.as_secs_f64() * f64::from(sample_rate)).round() as u64
+}
+
+/// Décodeur Opus (opus-rs) exposé à symphonia, qui démultiplexe l'Ogg mais
+/// ne décode pas Opus. Applique le gain d'OpusHead et le trim de fin.
+struct OpusPacketDecoder {
+    params: CodecParameters,
+    decoder: OpusDecoderImpl,
+    buffer: AudioBuffer<f32>,
+}
+
+impl Decoder for OpusPacketDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> SymphoniaResult<Self> {
+        let spec_channels = params
+            .channels
+            .ok_or(SymphoniaError::Unsupported("opus: nombre de canaux inconnu"))?;
+        if spec_channels.count() > 2 {
+            return Err(SymphoniaError::Unsupported("opus: plus de deux canaux"));
+        }
+
+        // OpusHead : gain de sortie en Q7.8 dB aux octets 16-17
+        let gain_db = params
+            .extra_data
+            .as_deref()
+            .filter(|head| head.len() >= 18)
+            .map_or(0.0, |head| f32::from(i16::from_le_bytes([head[16], head[17]])) / 256.0);
+
+        let decoder = OpusDecoderImpl::with_opus_config(OpusDecoderConfig {
+            sample_rate: 48_000,
+            channels: spec_channels.count() as u8,
+            gain_db,
+            ..OpusDecoderConfig::default()
+        })
+        .map_err(|_| SymphoniaError::DecodeError("opus: initialisation impossible"))?;
+
+        Ok(Self {
+            params: params.clone(),
+            decoder,
+            buffer: AudioBuffer::new(OPUS_MAX_PACKET_FRAMES as u64, SignalSpec::new(48_000, spec_channels)),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus (opus-rs)")]
+    }
+
+    fn reset(&mut self) {
+        let _ = AudioDecoder::reset(&mut self.decoder);
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &symphonia::core::formats::Packet) -> SymphoniaResult<AudioBufferRef<'_>> {
+        if packet.data.is_empty() {
+            return Err(SymphoniaError::DecodeError("opus: paquet vide"));
+        }
+        let pcm = AudioDecoder::decode(&mut self.decoder, &packet.data)
+            .map_err(|_| SymphoniaError::DecodeError("opus: paquet invalide"))?
+            .samples;
+
+        let channels = self.buffer.spec().channels.count();
+        let frames = pcm.len() / channels;
+        self.buffer.clear();
+        self.buffer.render_reserved(Some(frames));
+        for channel in 0..channels {
+            let plane = self.buffer.chan_mut(channel);
+            for (frame, sample) in plane.iter_mut().enumerate() {
+                *sample = pcm[frame * channels + channel];
+            }
+        }
+        self.buffer.trim(packet.trim_start() as usize, packet.trim_end() as usize);
+
+        Ok(self.buffer.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buffer.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::{AudioEncoder, CodecFactory, DecoderConfig, EncoderConfig, LatencyMode, OpusEncoderImpl};
+    use futures::StreamExt;
+
+    fn sine(sample_rate: u32, channels: usize, seconds: f32) -> Vec<f32> {
+        let frames = (sample_rate as f32 * seconds) as usize;
+        (0..frames * channels)
+            .map(|i| ((i / channels) as f32 * 440.0 * 2.0 * std::f32::consts::PI / sample_rate as f32).sin() * 0.5)
+            .collect()
+    }
+
+    fn wav_bytes(samples: &[f32], sample_rate: u32, channels: u16) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample((sample * 32767.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_ogg_opus_is_gapless() {
+        let mut encoder = OpusEncoderImpl::new(EncoderConfig {
+            bitrate: 96_000,
+            sample_rate: 48000,
+            channels: 2,
+            latency_mode: LatencyMode::High,
+            ..Default::default()
+        })
+        .unwrap();
+        let input = sine(48000, 2, 1.0);
+        let mut ogg = encoder.encode(&input, 48000, 2).unwrap();
+        ogg.extend_from_slice(&encoder.finalize().unwrap());
+
+        let mut decoder = SymphoniaDecoder::from_bytes(ogg, Some("opus")).unwrap();
+        assert_eq!(decoder.info().codec_name, "opus");
+        assert!(decoder.gapless().encoder_delay > 0);
+
+        let decoded = decoder.decode_all().unwrap();
+        assert_eq!(decoded.sample_rate, 48000);
+        assert_eq!(decoded.samples.len(), input.len());
+
+        // Après seek + pré-roll, la sortie rejoint celle d'un décodage linéaire
+        let linear = decoded.samples;
+        let position = decoder.seek(Duration::from_millis(500)).unwrap();
+        assert_eq!(position, Duration::from_millis(500));
+        let chunk = decoder.next_chunk().unwrap().unwrap();
+        let offset = 24000 * 2;
+        for (i, sample) in chunk.samples.iter().enumerate() {
+            assert!((sample - linear[offset + i]).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn test_factory_decodes_mp3_frames() {
+        let config = EncoderConfig { bitrate: 128_000, sample_rate: 44100, channels: 2, ..Default::default() };
+        let mut encoder = CodecFactory::create_encoder("mp3", config).unwrap();
+        let input = sine(44100, 2, 1.0);
+        let mut mp3 = encoder.encode(&input, 44100, 2).unwrap();
+        mp3.extend_from_slice(&encoder.finalize().unwrap());
+
+        let decoded = CodecFactory::create_decoder("mp3", DecoderConfig::default()).unwrap().decode(&mp3).unwrap();
+        assert_eq!((decoded.sample_rate, decoded.channels), (44100, 2));
+        assert!(decoded.samples.len() >= input.len() * 9 / 10);
+        let rms = (decoded.samples.iter().map(|s| s * s).sum::<f32>() / decoded.samples.len() as f32).sqrt();
+        assert!((rms - 0.35).abs() < 0.05, "rms {}", rms);
+    }
+
+    #[test]
+    fn test_seek_is_sample_accurate() {
+        let input = sine(44100, 2, 2.0);
+        let mut decoder = SymphoniaDecoder::from_bytes(wav_bytes(&input, 44100, 2), Some("wav")).unwrap();
+
+        let position = decoder.seek(Duration::from_millis(1234)).unwrap();
+        assert_eq!(position, frames_to_duration(54419, 44100));
+
+        let chunk = decoder.next_chunk().unwrap().unwrap();
+        let expected = &input[54419 * 2..54419 * 2 + 16];
+        for (decoded, expected) in chunk.samples.iter().zip(expected) {
+            assert!((decoded - expected).abs() < 1e-3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_yields_whole_file() {
+        let input = sine(22050, 1, 1.5);
+        let decoder = SymphoniaDecoder::from_bytes(wav_bytes(&input, 22050, 1), Some("wav")).unwrap();
+
+        let chunks: Vec<DecodedAudio> = decoder.into_stream().map(|chunk| chunk.unwrap()).collect().await;
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.iter().map(|c| c.samples.len()).sum::<usize>(), input.len());
+    }
+}
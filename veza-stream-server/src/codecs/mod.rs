@@ -5,17 +5,22 @@
 /// - AAC : Compatibilité universelle iOS/Safari/mobile
 /// - MP3 : Compatibilité legacy et universelle  
 /// - FLAC : Qualité lossless pour premium/studio
+///
+/// Le décodage de fichiers complets (tous conteneurs) passe par
+/// `SymphoniaDecoder`, voir le module `decoder`.
 
 pub mod opus;
 pub mod aac;
 pub mod mp3;
 pub mod flac;
+pub mod decoder;
 
 // Re-exports pour faciliter l'usage
 pub use opus::*;
 pub use aac::*;
 pub use mp3::*;
 pub use flac::*;
+pub use decoder::*;
 
 use std::fmt;
 use serde::{Serialize, Deserialize};
@@ -118,6 +123,7 @@ impl CodecFactory {
     pub fn create_encoder(codec: &str, config: EncoderConfig) -> Result<Box<dyn AudioEncoder>, AppError> {
         match codec.to_lowercase().as_str() {
             "opus" => Ok(Box::new(opus::OpusEncoderImpl::new(config)?)),
+            #[cfg(feature = "fdk-aac")]
             "aac" => Ok(Box::new(aac::AacEncoderImpl::new(config)?)),
             "mp3" => {
                 let mp3_config = Self::convert_to_mp3_encoder_config(config);
@@ -132,12 +138,11 @@ impl CodecFactory {
     pub fn create_decoder(codec: &str, config: DecoderConfig) -> Result<Box<dyn AudioDecoder>, AppError> {
         match codec.to_lowercase().as_str() {
             "opus" => Ok(Box::new(opus::OpusDecoderImpl::new(config)?)),
-            "aac" => Ok(Box::new(aac::AacDecoderImpl::new(config)?)),
-            "mp3" => {
-                let mp3_config = Self::convert_to_mp3_decoder_config(config);
-                Ok(Box::new(mp3::Mp3DecoderImpl::new(mp3_config)))
+            // Flux de frames (MP3, ADTS) et conteneurs complets : démultiplexage
+            // et décodage symphonia
+            extension @ ("mp3" | "aac" | "flac" | "wav" | "ogg" | "oga" | "m4a" | "mp4") => {
+                Ok(Box::new(decoder::SymphoniaDecoder::new(config).with_extension(extension)))
             },
-            "flac" => Ok(Box::new(flac::FlacDecoderImpl::new(config)?)),
             _ => Err(AppError::UnsupportedCodec { codec: codec.to_string() }),
         }
     }
@@ -228,17 +233,6 @@ impl CodecFactory {
             original: true,
         }
     }
-    
-    /// Convertit DecoderConfig vers Mp3DecoderConfig
-    fn convert_to_mp3_decoder_config(_config: DecoderConfig) -> mp3::Mp3DecoderConfig {
-        mp3::Mp3DecoderConfig {
-            frame_buffer_size: 1024,
-            enable_seeking: true,
-            error_tolerance: mp3::Mp3ErrorTolerance::Tolerant,
-            gapless_playback: true,
-            auto_eq: false,
-        }
-    }
 }
 
 /// Configuration pour un encodeur
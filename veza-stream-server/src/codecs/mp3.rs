@@ -6,18 +6,19 @@
 /// - Compatibilité universelle (tous devices)
 /// - Streaming optimisé
 /// - ID3v2 metadata
+///
+/// Le décodage MP3 passe par `SymphoniaDecoder` (voir `CodecFactory::create_decoder`).
 
 use std::sync::Arc;
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use serde::{Serialize, Deserialize};
-use tokio::sync::RwLock;
 use parking_lot::Mutex;
 use tracing::debug;
 
 use crate::error::AppError as AppError;
-use crate::codecs::{AudioEncoder, AudioDecoder};
+use crate::codecs::AudioEncoder;
 
 /// Implémentation de l'encoder MP3 avec LAME
 #[derive(Debug)]
@@ -26,27 +27,24 @@ pub struct Mp3EncoderImpl {
     config: Mp3EncoderConfig,
     /// État de l'encoder
     encoder_state: Arc<Mutex<Mp3EncoderState>>,
-    /// Statistiques de performance
-    stats: Arc<RwLock<Mp3EncoderStats>>,
     /// Buffer d'entrée pour optimisation
     _input_buffer: Arc<Mutex<Vec<f32>>>,
     /// Quality preset utilisé
     _quality_preset: Mp3QualityPreset,
+    /// Encoder LAME, créé au premier appel à `encode`
+    lame: Option<LameEncoder>,
 }
 
-/// Implémentation du decoder MP3
-#[derive(Debug)]
-pub struct Mp3DecoderImpl {
-    /// Configuration du decoder
-    config: Mp3DecoderConfig,
-    /// État du decoder
-    decoder_state: Arc<Mutex<Mp3DecoderState>>,
-    /// Statistiques de performance
-    stats: Arc<RwLock<Mp3DecoderStats>>,
-    /// Buffer de sortie
-    _output_buffer: Arc<Mutex<Vec<f32>>>,
-    /// Cache des frames pour seeking
-    _frame_cache: Arc<RwLock<HashMap<u64, Mp3Frame>>>,
+/// Handle vers l'encoder LAME natif
+struct LameEncoder(mp3lame_encoder::Encoder);
+
+impl std::fmt::Debug for LameEncoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LameEncoder")
+            .field("sample_rate", &self.0.sample_rate())
+            .field("channels", &self.0.num_channels())
+            .finish()
+    }
 }
 
 /// Configuration de l'encoder MP3
@@ -76,21 +74,6 @@ pub struct Mp3EncoderConfig {
     pub original: bool,
 }
 
-/// Configuration du decoder MP3
-#[derive(Debug, Clone)]
-pub struct Mp3DecoderConfig {
-    /// Bufferisation en frames
-    pub frame_buffer_size: usize,
-    /// Activation du cache pour seeking
-    pub enable_seeking: bool,
-    /// Tolérance aux erreurs de stream
-    pub error_tolerance: Mp3ErrorTolerance,
-    /// Gapless playback
-    pub gapless_playback: bool,
-    /// EQ automatique
-    pub auto_eq: bool,
-}
-
 /// Mode d'encodage MP3
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Mp3EncodingMode {
@@ -121,127 +104,19 @@ pub enum Mp3QualityPreset {
     Portable,    // 64 kbps CBR
 }
 
-/// Tolérance aux erreurs
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Mp3ErrorTolerance {
-    /// Strict - arrêt sur toute erreur
-    Strict,
-    /// Tolérant - continuer malgré erreurs mineures
-    Tolerant,
-    /// Permissif - ignorer la plupart des erreurs
-    Permissive,
-}
-
 /// État de l'encoder MP3
 #[derive(Debug)]
 struct Mp3EncoderState {
-    /// Initialized flag
-    initialized: bool,
     /// Total samples encodés
     samples_encoded: u64,
     /// Total bytes générés
     bytes_generated: u64,
     /// Dernière frame encodée
     last_frame_timestamp: Option<Duration>,
-    /// Buffers internes LAME (simulation)
-    internal_buffers: Vec<u8>,
     /// Métadonnées ID3 courantes
     current_metadata: Option<Mp3Metadata>,
 }
 
-/// État du decoder MP3
-#[derive(Debug)]
-struct Mp3DecoderState {
-    /// Initialized flag
-    initialized: bool,
-    /// Position actuelle en samples
-    current_position: u64,
-    /// Total samples dans le stream
-    total_samples: Option<u64>,
-    /// Information du header
-    stream_info: Option<Mp3StreamInfo>,
-    /// Buffer de décodage
-    decode_buffer: Vec<f32>,
-    /// Frame courante
-    current_frame: Option<Mp3Frame>,
-}
-
-/// Frame MP3 décodée
-#[derive(Debug, Clone)]
-struct Mp3Frame {
-    /// Données audio
-    audio_data: Vec<f32>,
-    /// Timestamp de la frame
-    timestamp: Duration,
-    /// Bitrate de cette frame
-    bitrate: u32,
-    /// Information du header
-    header_info: Mp3FrameHeader,
-}
-
-/// Header d'une frame MP3
-#[derive(Debug, Clone)]
-struct Mp3FrameHeader {
-    /// Version MPEG
-    pub mpeg_version: MpegVersion,
-    /// Layer
-    pub layer: u8,
-    /// Protection CRC
-    pub crc_protection: bool,
-    /// Bitrate index
-    pub bitrate_index: u8,
-    /// Sample rate index
-    pub samplerate_index: u8,
-    /// Padding
-    pub padding: bool,
-    /// Mode (stereo, joint stereo, etc.)
-    pub channel_mode: ChannelMode,
-    /// Mode extension pour joint stereo
-    pub mode_extension: u8,
-    /// Copyright
-    pub copyright: bool,
-    /// Original
-    pub original: bool,
-    /// Emphasis
-    pub emphasis: u8,
-}
-
-/// Version MPEG
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum MpegVersion {
-    Mpeg1,
-    Mpeg2,
-    Mpeg25,
-}
-
-/// Mode de canal
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum ChannelMode {
-    Stereo,
-    JointStereo,
-    DualChannel,
-    Mono,
-}
-
-/// Information du stream MP3
-#[derive(Debug, Clone)]
-struct Mp3StreamInfo {
-    /// Bitrate moyen
-    pub average_bitrate: u32,
-    /// Sample rate
-    pub sample_rate: u32,
-    /// Nombre de canaux
-    pub channels: u8,
-    /// Durée totale
-    pub duration: Option<Duration>,
-    /// Mode d'encodage détecté
-    pub encoding_mode: Mp3EncodingMode,
-    /// Présence d'ID3 tags
-    pub has_id3: bool,
-    /// Version de l'encoder détectée
-    pub encoder_info: Option<String>,
-}
-
 /// Métadonnées ID3v2
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Mp3Metadata {
@@ -257,38 +132,6 @@ struct Mp3Metadata {
     pub custom_tags: HashMap<String, String>,
 }
 
-/// Statistiques de l'encoder MP3
-#[derive(Debug, Clone, Default)]
-struct Mp3EncoderStats {
-    /// Total frames encodées
-    pub frames_encoded: u64,
-    /// Temps total d'encodage
-    pub total_encoding_time: Duration,
-    /// Ratio de compression moyen
-    pub average_compression_ratio: f32,
-    /// Bitrate réel moyen
-    pub average_bitrate: f32,
-    /// Peak utilisation CPU
-    pub peak_cpu_usage: f32,
-    /// Erreurs d'encodage
-    pub encoding_errors: u32,
-}
-
-/// Statistiques du decoder MP3
-#[derive(Debug, Clone, Default)]
-struct Mp3DecoderStats {
-    /// Total frames décodées
-    pub frames_decoded: u64,
-    /// Temps total de décodage
-    pub total_decoding_time: Duration,
-    /// Erreurs de décodage récupérées
-    pub recovered_errors: u32,
-    /// Frames corrompues détectées
-    pub corrupted_frames: u32,
-    /// Peak utilisation CPU
-    pub peak_cpu_usage: f32,
-}
-
 impl Default for Mp3EncoderConfig {
     fn default() -> Self {
         Self {
@@ -307,18 +150,6 @@ impl Default for Mp3EncoderConfig {
     }
 }
 
-impl Default for Mp3DecoderConfig {
-    fn default() -> Self {
-        Self {
-            frame_buffer_size: 1024,
-            enable_seeking: true,
-            error_tolerance: Mp3ErrorTolerance::Tolerant,
-            gapless_playback: true,
-            auto_eq: false,
-        }
-    }
-}
-
 impl Mp3EncoderImpl {
     /// Crée un nouvel encoder MP3
     pub fn new(config: Mp3EncoderConfig) -> Self {
@@ -326,15 +157,13 @@ impl Mp3EncoderImpl {
             _quality_preset: config.quality_preset.clone(),
             config,
             encoder_state: Arc::new(Mutex::new(Mp3EncoderState {
-                initialized: false,
                 samples_encoded: 0,
                 bytes_generated: 0,
                 last_frame_timestamp: None,
-                internal_buffers: Vec::new(),
                 current_metadata: None,
             })),
-            stats: Arc::new(RwLock::new(Mp3EncoderStats::default())),
             _input_buffer: Arc::new(Mutex::new(Vec::new())),
+            lame: None,
         }
     }
     
@@ -346,11 +175,6 @@ impl Mp3EncoderImpl {
         Ok(())
     }
     
-    /// Obtient les statistiques de l'encoder
-    pub async fn get_stats(&self) -> Mp3EncoderStats {
-        self.stats.read().await.clone()
-    }
-    
     /// Optimise la configuration selon le preset
     #[allow(dead_code)]
     fn optimize_config_for_preset(&mut self) {
@@ -390,91 +214,59 @@ impl Mp3EncoderImpl {
         }
     }
     
-    /// Initialise l'encoder LAME (simulation)
-    #[allow(dead_code)]
-    async fn initialize_lame_encoder(&self) -> Result<(), AppError> {
-        let mut state = self.encoder_state.lock();
-        
-        if state.initialized {
-            return Ok(());
-        }
-        
-        // Simulation d'initialisation LAME
-        debug!("Initialisation encoder LAME MP3 - Preset: {:?}, Mode: {:?}, Bitrate: {}",
-              self._quality_preset, self.config.encoding_mode, self.config.bitrate);
-        
-        // Simuler allocation des buffers LAME
-        state.internal_buffers = vec![0u8; 4096];
-        state.initialized = true;
-        
-        debug!("Encoder LAME MP3 initialisé avec succès");
-        Ok(())
-    }
-    
-    /// Encode un chunk de données avec LAME
-    #[allow(dead_code)]
-    async fn encode_with_lame(&self, samples: &[f32]) -> Result<Vec<u8>, AppError> {
-        let start_time = Instant::now();
-        
-        // Simulation d'encodage LAME
-        let estimated_output_size = match self.config.encoding_mode {
+    /// Construit l'encoder LAME à partir de la configuration
+    fn build_lame_encoder(&self) -> Result<LameEncoder, AppError> {
+        use mp3lame_encoder::{Builder, Mode, VbrMode};
+
+        let lame_error = |e: mp3lame_encoder::BuildError| AppError::EncodingError {
+            message: format!("LAME: {:?}", e),
+        };
+
+        let mut builder = Builder::new().ok_or_else(|| AppError::EncodingError {
+            message: "LAME: allocation impossible".to_string(),
+        })?;
+
+        builder.set_num_channels(self.config.channels).map_err(lame_error)?;
+        builder.set_sample_rate(self.config.sample_rate).map_err(lame_error)?;
+        // Sans sortie explicite LAME rééchantillonne aux bas débits, ce qui
+        // fausserait la timescale des segments
+        builder.set_output_sample_rate(std::num::NonZeroU32::new(self.config.sample_rate)).map_err(lame_error)?;
+        builder.set_mode(match (self.config.channels, self.config.joint_stereo) {
+            (1, _) => Mode::Mono,
+            (_, true) => Mode::JointStereo,
+            (_, false) => Mode::Stereo,
+        }).map_err(lame_error)?;
+        builder.set_quality(lame_quality(match self.config.quality_preset {
+            Mp3QualityPreset::Insane | Mp3QualityPreset::Extreme => 0,
+            Mp3QualityPreset::Standard | Mp3QualityPreset::Medium => 2,
+            _ => 5,
+        })).map_err(lame_error)?;
+
+        match self.config.encoding_mode {
             Mp3EncodingMode::CBR => {
-                // CBR: taille prévisible
-                (samples.len() * self.config.bitrate as usize * 125) / (self.config.sample_rate as usize * 8)
-            }
-            Mp3EncodingMode::VBR => {
-                // VBR: estimation basée sur la qualité
-                let quality_factor = (9 - self.config.vbr_quality) as f32 / 9.0;
-                (samples.len() as f32 * quality_factor * 0.5) as usize
+                builder.set_vbr_mode(VbrMode::Off).map_err(lame_error)?;
+                builder.set_brate(lame_bitrate(self.config.bitrate)).map_err(lame_error)?;
             }
             Mp3EncodingMode::ABR => {
-                // ABR: similaire à CBR mais avec variations
-                let base_size = (samples.len() * self.config.bitrate as usize * 125) / (self.config.sample_rate as usize * 8);
-                base_size + (base_size / 10) // +10% de variation
+                builder.set_vbr_mode(VbrMode::Abr).map_err(lame_error)?;
+                builder.set_brate(lame_bitrate(self.config.bitrate)).map_err(lame_error)?;
+            }
+            Mp3EncodingMode::VBR => {
+                builder.set_vbr_mode(VbrMode::Mtrh).map_err(lame_error)?;
+                builder.set_vbr_quality(lame_quality(self.config.vbr_quality)).map_err(lame_error)?;
             }
-        };
-        
-        // Simulation des données encodées
-        let mut encoded_data = vec![0u8; estimated_output_size];
-        
-        // Simuler le travail d'encodage (pattern de données réaliste)
-        for (i, byte) in encoded_data.iter_mut().enumerate() {
-            *byte = ((i * 17 + samples.len()) % 256) as u8;
-        }
-        
-        // Mettre à jour les statistiques
-        {
-            let mut state = self.encoder_state.lock();
-            state.samples_encoded += samples.len() as u64;
-            state.bytes_generated += encoded_data.len() as u64;
-        }
-        
-        {
-            let mut stats = self.stats.write().await;
-            stats.frames_encoded += 1;
-            stats.total_encoding_time += start_time.elapsed();
-            
-            // Calculer le ratio de compression
-            let input_size = samples.len() * 4; // f32 = 4 bytes
-            let compression_ratio = input_size as f32 / encoded_data.len() as f32;
-            stats.average_compression_ratio = 
-                (stats.average_compression_ratio + compression_ratio) / 2.0;
-            
-            // Calculer le bitrate réel
-            let duration_ms = (samples.len() as f64 / self.config.sample_rate as f64) * 1000.0;
-            let bitrate = (encoded_data.len() as f64 * 8.0 * 1000.0) / duration_ms / 1000.0;
-            stats.average_bitrate = (stats.average_bitrate + bitrate as f32) / 2.0;
         }
-        
-        debug!("Chunk MP3 encodé: {} samples -> {} bytes (ratio: {:.2}x)",
-               samples.len(), encoded_data.len(), 
-               samples.len() * 4 / encoded_data.len().max(1));
-        
-        Ok(encoded_data)
+
+        // Pas de frame Xing/Info : la sortie est consommée en flux ou segmentée
+        builder.set_to_write_vbr_tag(false).map_err(lame_error)?;
+
+        debug!("Encoder LAME initialisé - Preset: {:?}, Mode: {:?}, Bitrate: {}",
+               self.config.quality_preset, self.config.encoding_mode, self.config.bitrate);
+
+        builder.build().map(LameEncoder).map_err(lame_error)
     }
     
-    /// Génère un tag ID3v1 (simulation)
-    #[allow(dead_code)]
+    /// Génère un tag ID3v1
     fn generate_id3v1_tag(&self, metadata: &Mp3Metadata) -> Vec<u8> {
         let mut tag = vec![0u8; 128];
         
@@ -518,279 +310,180 @@ impl Mp3EncoderImpl {
         
         tag
     }
-    
-    /// Simulation d'encodage MP3 pour une frame audio
-    fn simulate_mp3_encoding(&self, samples: &[f32], _channels: u8) -> Vec<u8> {
-        // Simulation d'un header MP3 + données encodées
-        let mut frame_data = Vec::new();
-        
-        // Header MP3 simulé (4 bytes)
-        frame_data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]); // MPEG-1 Layer 3
-        
-        // Simulation de données audio encodées basées sur les échantillons
-        let compressed_size = (samples.len() / 8).max(32); // Compression ~8:1
-        let mut audio_data = vec![0u8; compressed_size];
-        
-        // Simulation très basique d'encodage
-        for (i, sample) in samples.iter().enumerate() {
-            if i < compressed_size {
-                audio_data[i] = ((sample * 127.0) as i32 + 128) as u8;
-            }
-        }
-        
-        frame_data.extend_from_slice(&audio_data);
-        frame_data
-    }
 }
 
-impl Mp3DecoderImpl {
-    /// Crée un nouveau decoder MP3
-    pub fn new(config: Mp3DecoderConfig) -> Self {
-        Self {
-            config,
-            decoder_state: Arc::new(Mutex::new(Mp3DecoderState {
-                initialized: false,
-                current_position: 0,
-                total_samples: None,
-                stream_info: None,
-                decode_buffer: Vec::new(),
-                current_frame: None,
-            })),
-            stats: Arc::new(RwLock::new(Mp3DecoderStats::default())),
-            _output_buffer: Arc::new(Mutex::new(Vec::new())),
-            _frame_cache: Arc::new(RwLock::new(HashMap::new())),
-        }
-    }
-    
-    /// Obtient les statistiques du decoder
-    pub async fn get_stats(&self) -> Mp3DecoderStats {
-        self.stats.read().await.clone()
-    }
-    
-    /// Obtient les informations du stream
-    pub async fn get_stream_info(&self) -> Option<Mp3StreamInfo> {
-        let state = self.decoder_state.lock();
-        state.stream_info.clone()
-    }
-    
-    /// Analyse le header MP3 et extrait les informations
-    #[allow(dead_code)]
-    async fn analyze_mp3_header(&self, data: &[u8]) -> Result<Mp3StreamInfo, AppError> {
-        if data.len() < 4 {
-            return Err(AppError::DecodingError { message: "Insufficient data for MP3 header".to_string() });
-        }
-        
-        // Simulation d'analyse de header MP3
-        let first_frame_header = self.parse_frame_header(&data[0..4])?;
-        
-        let stream_info = Mp3StreamInfo {
-            average_bitrate: self.bitrate_from_index(first_frame_header.bitrate_index, 
-                                                   first_frame_header.mpeg_version.clone()),
-            sample_rate: self.samplerate_from_index(first_frame_header.samplerate_index, 
-                                                  first_frame_header.mpeg_version.clone()),
-            channels: match first_frame_header.channel_mode {
-                ChannelMode::Mono => 1,
-                _ => 2,
-            },
-            duration: None, // Sera calculé après scan complet
-            encoding_mode: Mp3EncodingMode::CBR, // Détection par défaut
-            has_id3: data.starts_with(b"ID3"),
-            encoder_info: None,
-        };
-        
-        debug!("Stream MP3 analysé: {}kbps, {}Hz, {} canaux",
-               stream_info.average_bitrate, stream_info.sample_rate, stream_info.channels);
-        
-        Ok(stream_info)
+/// Bitrate LAME supporté le plus proche (par défaut) du bitrate demandé en kbps
+fn lame_bitrate(kbps: u32) -> mp3lame_encoder::Bitrate {
+    use mp3lame_encoder::Bitrate::*;
+
+    match kbps {
+        0..=15 => Kbps8,
+        16..=23 => Kbps16,
+        24..=31 => Kbps24,
+        32..=39 => Kbps32,
+        40..=47 => Kbps40,
+        48..=63 => Kbps48,
+        64..=79 => Kbps64,
+        80..=95 => Kbps80,
+        96..=111 => Kbps96,
+        112..=127 => Kbps112,
+        128..=159 => Kbps128,
+        160..=191 => Kbps160,
+        192..=223 => Kbps192,
+        224..=255 => Kbps224,
+        256..=319 => Kbps256,
+        _ => Kbps320,
     }
-    
-    /// Parse un header de frame MP3
-    #[allow(dead_code)]
-    fn parse_frame_header(&self, header_bytes: &[u8]) -> Result<Mp3FrameHeader, AppError> {
-        if header_bytes.len() < 4 {
-            return Err(AppError::DecodingError { message: "Invalid MP3 frame header".to_string() });
-        }
-        
-        // Vérifier le sync word (11 bits à 1)
-        if (header_bytes[0] != 0xFF) || ((header_bytes[1] & 0xE0) != 0xE0) {
-            return Err(AppError::DecodingError { message: "Invalid MP3 sync word".to_string() });
-        }
-        
-        // Extraire les informations (simulation)
-        let mpeg_version = match (header_bytes[1] >> 3) & 0x03 {
-            0 => MpegVersion::Mpeg25,
-            2 => MpegVersion::Mpeg2,
-            3 => MpegVersion::Mpeg1,
-            _ => return Err(AppError::DecodingError { message: "Invalid MPEG version".to_string() }),
-        };
-        
-        let layer = 4 - ((header_bytes[1] >> 1) & 0x03);
-        let bitrate_index = (header_bytes[2] >> 4) & 0x0F;
-        let samplerate_index = (header_bytes[2] >> 2) & 0x03;
-        let channel_mode = match (header_bytes[3] >> 6) & 0x03 {
-            0 => ChannelMode::Stereo,
-            1 => ChannelMode::JointStereo,
-            2 => ChannelMode::DualChannel,
-            3 => ChannelMode::Mono,
-            _ => unreachable!(),
-        };
-        
-        Ok(Mp3FrameHeader {
-            mpeg_version,
-            layer,
-            crc_protection: (header_bytes[1] & 0x01) == 0,
-            bitrate_index,
-            samplerate_index,
-            padding: (header_bytes[2] & 0x02) != 0,
-            channel_mode,
-            mode_extension: (header_bytes[3] >> 4) & 0x03,
-            copyright: (header_bytes[3] & 0x08) != 0,
-            original: (header_bytes[3] & 0x04) != 0,
-            emphasis: header_bytes[3] & 0x03,
-        })
+}
+
+/// Qualité LAME 0 (meilleure) à 9
+fn lame_quality(level: u8) -> mp3lame_encoder::Quality {
+    use mp3lame_encoder::Quality::*;
+
+    match level {
+        0 => Best,
+        1 => SecondBest,
+        2 => NearBest,
+        3 => VeryNice,
+        4 => Nice,
+        5 => Good,
+        6 => Decent,
+        7 => Ok,
+        8 => SecondWorst,
+        _ => Worst,
     }
-    
-    /// Convertit l'index de bitrate en valeur
-    #[allow(dead_code)]
-    fn bitrate_from_index(&self, index: u8, version: MpegVersion) -> u32 {
-        // Table de bitrates MP3 (simulation simplifiée)
-        match version {
-            MpegVersion::Mpeg1 => match index {
-                1 => 32,
-                2 => 40,
-                3 => 48,
-                4 => 56,
-                5 => 64,
-                6 => 80,
-                7 => 96,
-                8 => 112,
-                9 => 128,
-                10 => 160,
-                11 => 192,
-                12 => 224,
-                13 => 256,
-                14 => 320,
-                _ => 128, // Défaut
-            },
-            _ => 128, // Simplification pour MPEG2/2.5
-        }
+}
+
+/// Informations extraites d'un header de frame MPEG audio Layer III
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mp3FrameInfo {
+    /// Taille totale de la frame, header compris
+    pub frame_length: usize,
+    /// Échantillons par canal contenus dans la frame
+    pub samples_per_frame: u32,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub bitrate_kbps: u32,
+}
+
+/// Parse un header de frame MP3 (4 octets) ; `None` si ce n'est pas un header Layer III valide
+pub fn parse_mp3_frame_header(header: &[u8]) -> Option<Mp3FrameInfo> {
+    const BITRATES_V1: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const BITRATES_V2: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    if header.len() < 4 || header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+        return None;
     }
-    
-    /// Convertit l'index de sample rate en valeur
-    #[allow(dead_code)]
-    fn samplerate_from_index(&self, index: u8, version: MpegVersion) -> u32 {
-        match version {
-            MpegVersion::Mpeg1 => match index {
-                0 => 44100,
-                1 => 48000,
-                2 => 32000,
-                _ => 44100,
-            },
-            MpegVersion::Mpeg2 => match index {
-                0 => 22050,
-                1 => 24000,
-                2 => 16000,
-                _ => 22050,
-            },
-            MpegVersion::Mpeg25 => match index {
-                0 => 11025,
-                1 => 12000,
-                2 => 8000,
-                _ => 11025,
-            },
-        }
+
+    let version_bits = (header[1] >> 3) & 0x03;
+    let layer_bits = (header[1] >> 1) & 0x03;
+    if version_bits == 0b01 || layer_bits != 0b01 {
+        return None; // version réservée ou pas Layer III
     }
-    
-    /// Décode une frame MP3
-    #[allow(dead_code)]
-    async fn decode_mp3_frame(&self, frame_data: &[u8]) -> Result<Mp3Frame, AppError> {
-        let start_time = Instant::now();
-        
-        // Parser le header de la frame
-        let header = self.parse_frame_header(&frame_data[0..4])?;
-        
-        // Calculer la taille de la frame
-        let bitrate = self.bitrate_from_index(header.bitrate_index, header.mpeg_version.clone());
-        let samples_per_frame = 1152; // MP3 Layer III
-        
-        // Simulation de décodage
-        let mut audio_data = Vec::with_capacity(samples_per_frame * 2); // Stereo
-        
-        // Générer des échantillons de test (bruit blanc faible)
-        for i in 0..samples_per_frame * 2 {
-            let sample = ((i as f32 * 0.001).sin() * 0.01) as f32;
-            audio_data.push(sample);
-        }
-        
-        let frame = Mp3Frame {
-            audio_data,
-            timestamp: Duration::from_millis(0), // Sera calculé par le caller
-            bitrate,
-            header_info: header,
-        };
-        
-        // Mettre à jour les statistiques
-        {
-            let mut stats = self.stats.write().await;
-            stats.frames_decoded += 1;
-            stats.total_decoding_time += start_time.elapsed();
-        }
-        
-        debug!("Frame MP3 décodée: {}kbps, {} échantillons",
-               bitrate, samples_per_frame);
-        
-        Ok(frame)
+
+    let bitrate_index = (header[2] >> 4) as usize;
+    let samplerate_index = ((header[2] >> 2) & 0x03) as usize;
+    if bitrate_index == 0 || bitrate_index == 15 || samplerate_index == 3 {
+        return None; // free format ou valeurs invalides
     }
-    
-    /// Seek vers une position spécifique
-    pub async fn seek_to_position(&self, position: Duration) -> Result<(), AppError> {
-        if !self.config.enable_seeking {
-            return Err(AppError::DecodingError { message: "Seeking not enabled".to_string() });
-        }
-        
-        let mut state = self.decoder_state.lock();
-        
-        if let Some(stream_info) = &state.stream_info {
-            // Calculer la position en samples
-            let target_sample = (position.as_secs_f64() * stream_info.sample_rate as f64) as u64;
-            state.current_position = target_sample;
-            
-            debug!("Seek MP3 vers position: {:?} (sample {})", position, target_sample);
+
+    let base_rate = [44100, 48000, 32000][samplerate_index];
+    let (sample_rate, bitrate_kbps, samples_per_frame) = match version_bits {
+        0b11 => (base_rate, BITRATES_V1[bitrate_index], 1152),
+        0b10 => (base_rate / 2, BITRATES_V2[bitrate_index], 576),
+        _ => (base_rate / 4, BITRATES_V2[bitrate_index], 576),
+    };
+
+    let padding = ((header[2] >> 1) & 0x01) as usize;
+    let frame_length = (samples_per_frame as usize / 8) * bitrate_kbps as usize * 1000 / sample_rate as usize + padding;
+    let channels = if header[3] >> 6 == 0b11 { 1 } else { 2 };
+
+    Some(Mp3FrameInfo {
+        frame_length,
+        samples_per_frame,
+        sample_rate,
+        channels,
+        bitrate_kbps,
+    })
+}
+
+/// Découpe un flux MP3 en frames complètes, en ignorant les octets hors frame (tags ID3, padding)
+pub fn split_mp3_frames(data: &[u8]) -> Vec<(Mp3FrameInfo, &[u8])> {
+    let mut frames = Vec::new();
+    let mut pos = 0;
+
+    while pos + 4 <= data.len() {
+        match parse_mp3_frame_header(&data[pos..]) {
+            Some(info) if pos + info.frame_length <= data.len() => {
+                frames.push((info, &data[pos..pos + info.frame_length]));
+                pos += info.frame_length;
+            }
+            Some(_) => break,
+            None => pos += 1,
         }
-        
-        Ok(())
     }
+
+    frames
 }
 
 impl AudioEncoder for Mp3EncoderImpl {
     fn encode(&mut self, samples: &[f32], _sample_rate: u32, channels: u8) -> Result<Vec<u8>, AppError> {
-        // Encoder les échantillons avec LAME (simulation)
-        let mut encoded_data = Vec::new();
-        
-        // Simulation d'encodage MP3
-        for chunk in samples.chunks(1152) { // Frame MP3 typique
-            let frame_data = self.simulate_mp3_encoding(chunk, channels);
-            encoded_data.extend_from_slice(&frame_data);
+        use mp3lame_encoder::{InterleavedPcm, MonoPcm};
+
+        if channels != self.config.channels {
+            return Err(AppError::ParameterMismatch {
+                expected: format!("{} channels", self.config.channels),
+                got: format!("{} channels", channels),
+            });
+        }
+
+        if self.lame.is_none() {
+            self.lame = Some(self.build_lame_encoder()?);
         }
+        let Some(LameEncoder(encoder)) = self.lame.as_mut() else {
+            unreachable!("encoder LAME initialisé ci-dessus");
+        };
+
+        let frames = samples.len() / channels as usize;
+        let mut encoded_data = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(frames));
+        let result = match channels {
+            1 => encoder.encode_to_vec(MonoPcm(samples), &mut encoded_data),
+            2 => encoder.encode_to_vec(InterleavedPcm(samples), &mut encoded_data),
+            _ => return Err(AppError::InvalidChannelCount { channels }),
+        };
+        result.map_err(|e| AppError::EncodingError { message: format!("LAME: {:?}", e) })?;
+
+        let mut state = self.encoder_state.lock();
+        state.samples_encoded += frames as u64;
+        state.bytes_generated += encoded_data.len() as u64;
         
         Ok(encoded_data)
     }
     
     fn finalize(&mut self) -> Result<Vec<u8>, AppError> {
-        // Flush des buffers LAME et génération des tags finaux
+        // Flush complet des buffers LAME (dernière frame complétée par du
+        // silence) et génération des tags finaux
         let mut final_data = Vec::new();
         
+        if let Some(LameEncoder(mut encoder)) = self.lame.take() {
+            final_data.reserve(7200);
+            encoder.flush_to_vec::<mp3lame_encoder::FlushGap>(&mut final_data)
+                .map_err(|e| AppError::EncodingError { message: format!("LAME: {:?}", e) })?;
+        }
+        
         // Ajouter les métadonnées ID3v1 en fin de fichier si configuré
         if self.config.include_id3 {
-            final_data.extend_from_slice(&[84, 65, 71]); // "TAG" signature
-            final_data.resize(128, 0); // ID3v1 tag size
+            if let Some(metadata) = self.encoder_state.lock().current_metadata.as_ref() {
+                final_data.extend_from_slice(&self.generate_id3v1_tag(metadata));
+            }
         }
         
         Ok(final_data)
     }
     
     fn reset(&mut self) -> Result<(), AppError> {
-        // Reset de l'état de l'encodeur
+        // L'encoder LAME sera recréé au prochain encode
+        self.lame = None;
         Ok(())
     }
     
@@ -814,9 +507,10 @@ impl AudioEncoder for Mp3EncoderImpl {
     }
     
     fn metrics(&self) -> crate::codecs::EncoderMetrics {
+        let state = self.encoder_state.lock();
         crate::codecs::EncoderMetrics {
-            frames_encoded: 0,
-            bytes_output: 0,
+            frames_encoded: state.samples_encoded / 1152,
+            bytes_output: state.bytes_generated,
             encoding_time_ms: 0,
             cpu_usage_percent: 0.0,
             memory_usage_mb: 0.0,
@@ -825,38 +519,3 @@ impl AudioEncoder for Mp3EncoderImpl {
         }
     }
 }
-
-impl AudioDecoder for Mp3DecoderImpl {
-    fn decode(&mut self, data: &[u8]) -> Result<crate::codecs::DecodedAudio, AppError> {
-        if data.len() < 4 {
-            return Err(AppError::DecodingError { message: "Insufficient MP3 frame data".to_string() });
-        }
-        
-        // Simulation de décodage MP3
-        let samples = vec![0.0f32; 1152 * 2]; // Frame stéréo typique
-        
-        Ok(crate::codecs::DecodedAudio {
-            samples,
-            sample_rate: 44100,
-            channels: 2,
-            duration_ms: 26,
-            format: crate::codecs::AudioSampleFormat::F32,
-        })
-    }
-    
-    fn reset(&mut self) -> Result<(), AppError> {
-        // Reset de l'état du décodeur
-        Ok(())
-    }
-    
-    fn info(&self) -> crate::codecs::DecoderInfo {
-        crate::codecs::DecoderInfo {
-            codec_name: "MP3".to_string(),
-            version: "1.0".to_string(),
-            sample_rate: 44100,
-            channels: 2,
-            bit_depth: 16,
-            frame_size: 1152,
-        }
-    }
-} 
\ No newline at end of file
@@ -1,49 +1,98 @@
 /// Codec Opus pour streaming ultra low latency
-/// 
+///
 /// Opus est le codec optimal pour streaming live :
 /// - Latence ultra-faible (<5ms)
 /// - Qualité excellente à tous les bitrates
 /// - Support VBR/CBR adaptatif
 /// - Optimisé pour voix et musique
+///
+/// L'encodage repose sur `mousiki` et le décodage sur `opus-rs`, deux portages
+/// Rust indépendants de libopus ; l'encodeur encapsule les paquets dans un flux
+/// Ogg-Opus (RFC 7845).
 
 use std::sync::Arc;
 use std::time::Instant;
 use parking_lot::Mutex;
 use serde::{Serialize, Deserialize};
 use tracing::debug;
+use mousiki::c_style_api::opus_encoder::{opus_encoder_ctl, OpusEncoderCtlRequest};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
 
 use crate::error::AppError;
 use super::{
-    AudioEncoder, AudioDecoder, EncoderInfo, DecoderInfo, EncoderMetrics, 
+    AudioEncoder, AudioDecoder, EncoderInfo, DecoderInfo, EncoderMetrics,
     DecodedAudio, AudioSampleFormat, EncoderConfig, DecoderConfig
 };
 
+/// Taille maximale d'un paquet Opus (recommandation libopus)
+const MAX_PACKET_SIZE: usize = 4000;
+
+/// Fréquence des granule positions Ogg-Opus, quelle que soit l'entrée (RFC 7845 §4)
+const OGG_OPUS_RATE: u32 = 48_000;
+
+/// Durée maximale d'un paquet Opus en millisecondes
+const MAX_PACKET_DURATION_MS: u32 = 120;
+
+/// Taux de perte annoncé à l'encodeur quand la résilience est active,
+/// nécessaire pour que la FEC in-band soit réellement émise
+const EXPECTED_PACKET_LOSS_PERC: i32 = 10;
+
+/// Complexité maximale d'un encodeur stéréo. Au-delà, mousiki active le theta
+/// RDO et re-synthétise les bandes CELT ; en mode hybride, les fenêtres de
+/// repliement lowband se chevauchent alors et mousiki 0.2.1 abandonne le
+/// repliement (debug_assert! en debug, bande mal reconstruite en release).
+const MAX_STEREO_COMPLEXITY: u8 = 7;
+
 /// Implémentation de l'encodeur Opus
 #[derive(Debug)]
 pub struct OpusEncoderImpl {
     /// Configuration de l'encodeur
     config: OpusEncoderConfig,
-    /// État interne de l'encodeur (opaque)
+    /// Encodeur Opus natif
+    encoder: mousiki::Encoder,
+    /// Multiplexeur Ogg du flux en cours
+    muxer: OggOpusMuxer,
+    /// Lookahead de l'encodeur, en échantillons par canal à la fréquence d'entrée
+    lookahead: u32,
+    /// Statistiques internes
     encoder_state: Arc<Mutex<OpusEncoderState>>,
     /// Métriques de performance
     metrics: EncoderMetrics,
     /// Buffer interne pour les échantillons
     sample_buffer: Vec<f32>,
-    /// Taille de frame configurée
+    /// Taille de frame configurée (échantillons entrelacés)
     frame_size: usize,
 }
 
 /// Implémentation du décodeur Opus
-#[derive(Debug)]
+///
+/// Un flux peut alterner paquets mono et stéréo (bit `s` du TOC) : libopus
+/// l'accepte, pas opus-rs, d'où un décodeur par nombre de canaux codés et un
+/// mixage vers le nombre de canaux demandé.
 pub struct OpusDecoderImpl {
     /// Configuration du décodeur
     config: OpusDecoderConfig,
-    /// État interne du décodeur (opaque)
+    /// Décodeurs natifs, indexés par nombre de canaux codés moins un
+    decoders: [Option<opus_rs::OpusDecoder>; 2],
+    /// Canaux codés et durée (échantillons par canal) du dernier paquet reçu
+    last_packet: Option<(usize, usize)>,
+    /// Gain linéaire déduit de `gain_db`
+    gain: f32,
+    /// Statistiques internes
     decoder_state: Arc<Mutex<OpusDecoderState>>,
-    /// Buffer de sortie
+    /// Buffer de sortie, dimensionné pour le plus long paquet stéréo possible
     output_buffer: Vec<f32>,
 }
 
+impl std::fmt::Debug for OpusDecoderImpl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpusDecoderImpl")
+            .field("config", &self.config)
+            .field("last_packet", &self.last_packet)
+            .finish()
+    }
+}
+
 /// Configuration spécifique à Opus pour l'encodeur
 #[derive(Debug, Clone)]
 pub struct OpusEncoderConfig {
@@ -119,10 +168,9 @@ pub enum OpusFrameDuration {
     Ms60,
 }
 
-/// État interne de l'encodeur (simulation)
+/// Statistiques internes de l'encodeur
 #[derive(Debug)]
 struct OpusEncoderState {
-    initialized: bool,
     last_frame_time: Option<Instant>,
     total_frames: u64,
     total_bytes: u64,
@@ -130,13 +178,12 @@ struct OpusEncoderState {
     bandwidth_adaptation: BandwidthAdaptation,
 }
 
-/// État interne du décodeur (simulation)
+/// Statistiques internes du décodeur
 #[derive(Debug)]
 struct OpusDecoderState {
-    initialized: bool,
     last_decode_time: Option<Instant>,
     total_frames: u64,
-    packet_loss_concealment: PacketLossConcealmentState,
+    consecutive_losses: u32,
 }
 
 /// Adaptation de bande passante intelligente
@@ -148,12 +195,120 @@ struct BandwidthAdaptation {
     _quality_history: Vec<f32>,
 }
 
-/// État de dissimulation de perte de paquets
-#[derive(Debug)]
-struct PacketLossConcealmentState {
-    last_good_frame: Option<Vec<f32>>,
-    consecutive_losses: u32,
-    concealment_energy: f32,
+/// Encapsulation Ogg-Opus d'un flux logique (RFC 7845)
+///
+/// Les granule positions sont exprimées à 48 kHz et incluent le pre-skip.
+struct OggOpusMuxer {
+    writer: PacketWriter<'static, Vec<u8>>,
+    serial: u32,
+    channels: u8,
+    input_sample_rate: u32,
+    pre_skip: u16,
+    headers_written: bool,
+    /// Granule position après le dernier paquet écrit
+    granule: u64,
+    /// Échantillons par canal reçus en entrée, à 48 kHz
+    input_samples: u64,
+}
+
+impl std::fmt::Debug for OggOpusMuxer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OggOpusMuxer")
+            .field("serial", &self.serial)
+            .field("pre_skip", &self.pre_skip)
+            .field("granule", &self.granule)
+            .finish()
+    }
+}
+
+impl OggOpusMuxer {
+    fn new(channels: u8, input_sample_rate: u32, lookahead: u32) -> Self {
+        let pre_skip = lookahead * (OGG_OPUS_RATE / input_sample_rate);
+        Self {
+            writer: PacketWriter::new(Vec::new()),
+            serial: rand::random(),
+            channels,
+            input_sample_rate,
+            pre_skip: pre_skip as u16,
+            headers_written: false,
+            granule: 0,
+            input_samples: 0,
+        }
+    }
+
+    /// Convertit un nombre d'échantillons par canal à la fréquence d'entrée en unités 48 kHz
+    fn to_granule(&self, samples: usize) -> u64 {
+        samples as u64 * u64::from(OGG_OPUS_RATE / self.input_sample_rate)
+    }
+
+    fn add_input(&mut self, samples_per_channel: usize) {
+        self.input_samples += self.to_granule(samples_per_channel);
+    }
+
+    /// Granule position finale : tout l'audio d'entrée, décalé du pre-skip
+    fn end_granule(&self) -> u64 {
+        u64::from(self.pre_skip) + self.input_samples
+    }
+
+    /// En-têtes `OpusHead` et `OpusTags`, chacun sur sa propre page
+    fn write_headers(&mut self) -> Result<(), AppError> {
+        let mut head = Vec::with_capacity(19);
+        head.extend_from_slice(b"OpusHead");
+        head.push(1); // version
+        head.push(self.channels);
+        head.extend_from_slice(&self.pre_skip.to_le_bytes());
+        head.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+        head.push(0); // channel mapping family 0 : mono/stéréo
+
+        let vendor = mousiki::opus_get_version_string().as_bytes();
+        let mut tags = Vec::with_capacity(16 + vendor.len());
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor);
+        tags.extend_from_slice(&0u32.to_le_bytes()); // aucun commentaire
+
+        self.write(head, PacketWriteEndInfo::EndPage, 0)?;
+        self.write(tags, PacketWriteEndInfo::EndPage, 0)?;
+        self.headers_written = true;
+        Ok(())
+    }
+
+    /// Écrit des paquets de `samples_per_channel` échantillons chacun et
+    /// retourne les pages terminées. Avec `end_of_stream`, la dernière page
+    /// porte le flag EOS et la granule position finale exacte.
+    fn write_packets(
+        &mut self,
+        packets: Vec<Vec<u8>>,
+        samples_per_channel: usize,
+        end_of_stream: bool,
+    ) -> Result<Vec<u8>, AppError> {
+        if !self.headers_written {
+            self.write_headers()?;
+        }
+
+        let step = self.to_granule(samples_per_channel);
+        let count = packets.len();
+        for (index, packet) in packets.into_iter().enumerate() {
+            self.granule += step;
+            let (end_info, granule) = match (index + 1 == count, end_of_stream) {
+                (true, true) => (PacketWriteEndInfo::EndStream, self.end_granule().min(self.granule)),
+                (true, false) => (PacketWriteEndInfo::EndPage, self.granule),
+                _ => (PacketWriteEndInfo::NormalPacket, self.granule),
+            };
+            self.write(packet, end_info, granule)?;
+        }
+
+        Ok(std::mem::take(self.writer.inner_mut()))
+    }
+
+    fn write(&mut self, packet: Vec<u8>, end_info: PacketWriteEndInfo, granule: u64) -> Result<(), AppError> {
+        self.writer
+            .write_packet(packet, self.serial, end_info, granule)
+            .map_err(|e| AppError::EncodingError {
+                message: format!("Écriture Ogg-Opus impossible: {}", e),
+            })
+    }
 }
 
 impl OpusFrameDuration {
@@ -168,11 +323,22 @@ impl OpusFrameDuration {
             Self::Ms60 => 60.0,
         }
     }
-    
+
     /// Calcule la taille de frame en échantillons
     pub fn frame_size(&self, sample_rate: u32) -> usize {
         (self.as_ms() * sample_rate as f32 / 1000.0) as usize
     }
+
+    fn to_native(&self) -> mousiki::FrameDuration {
+        match self {
+            Self::Ms2_5 => mousiki::FrameDuration::Ms2_5,
+            Self::Ms5 => mousiki::FrameDuration::Ms5,
+            Self::Ms10 => mousiki::FrameDuration::Ms10,
+            Self::Ms20 => mousiki::FrameDuration::Ms20,
+            Self::Ms40 => mousiki::FrameDuration::Ms40,
+            Self::Ms60 => mousiki::FrameDuration::Ms60,
+        }
+    }
 }
 
 impl Default for OpusEncoderConfig {
@@ -204,9 +370,29 @@ impl Default for OpusDecoderConfig {
     }
 }
 
+/// Vérifie les contraintes Opus sur la fréquence et le nombre de canaux
+fn validate_format(sample_rate: u32, channels: u8) -> Result<mousiki::Channels, AppError> {
+    // Opus supporte spécifiquement 8kHz, 12kHz, 16kHz, 24kHz, 48kHz
+    let valid_rates = [8000, 12000, 16000, 24000, 48000];
+    if !valid_rates.contains(&sample_rate) {
+        return Err(AppError::InvalidSampleRate { rate: sample_rate });
+    }
+
+    // Opus supporte 1 ou 2 channels
+    match channels {
+        1 => Ok(mousiki::Channels::Mono),
+        2 => Ok(mousiki::Channels::Stereo),
+        _ => Err(AppError::InvalidChannelCount { channels }),
+    }
+}
+
 impl OpusEncoderImpl {
     /// Crée un nouvel encodeur Opus
     pub fn new(config: EncoderConfig) -> Result<Self, AppError> {
+        let realtime = matches!(
+            config.latency_mode,
+            crate::codecs::LatencyMode::UltraLow | crate::codecs::LatencyMode::Low
+        );
         let opus_config = OpusEncoderConfig {
             sample_rate: config.sample_rate,
             channels: config.channels,
@@ -223,15 +409,32 @@ impl OpusEncoderImpl {
                 crate::codecs::LatencyMode::Low => OpusFrameDuration::Ms5,
                 _ => OpusFrameDuration::Ms10,
             },
-            packet_loss_resilience: true,
+            // La FEC in-band ne sert que sur un transport avec pertes (RTP,
+            // WebRTC) ; sur HTTP ou en fichier elle pousse l'encodeur vers les
+            // modes SILK/hybrides et consomme du débit pour rien.
+            packet_loss_resilience: realtime,
             dtx_enabled: false,
-            inband_fec: true,
+            inband_fec: realtime,
         };
-        
+
+        Self::with_opus_config(opus_config)
+    }
+
+    /// Crée un encodeur à partir d'une configuration Opus complète
+    pub fn with_opus_config(opus_config: OpusEncoderConfig) -> Result<Self, AppError> {
+        Self::validate_config(&opus_config)?;
+
+        let mut encoder = Self::build_native_encoder(&opus_config)?;
+        let mut lookahead = 0i32;
+        opus_encoder_ctl(encoder.as_raw_mut(), OpusEncoderCtlRequest::GetLookahead(&mut lookahead))
+            .map_err(|e| AppError::EncodingError {
+                message: format!("Lookahead Opus indisponible: {:?}", e),
+            })?;
+        let lookahead = lookahead.max(0) as u32;
+
         let frame_size = opus_config.frame_duration.frame_size(opus_config.sample_rate) * opus_config.channels as usize;
-        
+
         let encoder_state = OpusEncoderState {
-            initialized: false,
             last_frame_time: None,
             total_frames: 0,
             total_bytes: 0,
@@ -243,175 +446,147 @@ impl OpusEncoderImpl {
                 _quality_history: Vec::new(),
             },
         };
-        
-        let mut encoder = Self {
+
+        debug!("Encodeur Opus initialisé: {}Hz, {} ch, {} bps, frames de {} ms",
+               opus_config.sample_rate,
+               opus_config.channels,
+               opus_config.bitrate,
+               opus_config.frame_duration.as_ms());
+
+        Ok(Self {
+            muxer: OggOpusMuxer::new(opus_config.channels, opus_config.sample_rate, lookahead),
             config: opus_config,
+            encoder,
+            lookahead,
             encoder_state: Arc::new(Mutex::new(encoder_state)),
             metrics: EncoderMetrics::default(),
             sample_buffer: Vec::with_capacity(frame_size * 2),
             frame_size,
-        };
-        
-        encoder.initialize()?;
-        Ok(encoder)
-    }
-    
-    /// Initialise l'encodeur Opus
-    fn initialize(&mut self) -> Result<(), AppError> {
-        // Validation des paramètres
-        self.validate_config()?;
-        
-        // Simulation d'initialisation de l'encodeur Opus natif
-        // En production, utiliser opus_encoder_create() de libopus
-        {
-            let mut state = self.encoder_state.lock();
-            state.initialized = true;
-        }
-        
-        debug!("Encodeur Opus initialisé: {}Hz, {} ch, {} bps", 
-               self.config.sample_rate, 
-               self.config.channels, 
-               self.config.bitrate);
-        
-        Ok(())
+        })
     }
-    
+
     /// Valide la configuration
-    fn validate_config(&self) -> Result<(), AppError> {
-        // Opus supporte spécifiquement 8kHz, 12kHz, 16kHz, 24kHz, 48kHz
-        let valid_rates = [8000, 12000, 16000, 24000, 48000];
-        if !valid_rates.contains(&self.config.sample_rate) {
-            return Err(AppError::InvalidSampleRate {
-                rate: self.config.sample_rate,
-            });
-        }
-        
-        // Opus supporte 1 ou 2 channels
-        if self.config.channels == 0 || self.config.channels > 2 {
-            return Err(AppError::InvalidChannelCount {
-                channels: self.config.channels,
-            });
-        }
-        
+    fn validate_config(config: &OpusEncoderConfig) -> Result<(), AppError> {
+        validate_format(config.sample_rate, config.channels)?;
+
         // Bitrate valide : 6kbps à 512kbps
-        if self.config.bitrate < 6_000 || self.config.bitrate > 512_000 {
+        if config.bitrate < 6_000 || config.bitrate > 512_000 {
             return Err(AppError::InvalidBitrate {
-                bitrate: self.config.bitrate,
+                bitrate: config.bitrate,
                 codec: "opus".to_string(),
             });
         }
-        
+
         Ok(())
     }
-    
+
+    /// Configure l'encodeur natif selon l'application, le mode VBR et la résilience
+    fn build_native_encoder(config: &OpusEncoderConfig) -> Result<mousiki::Encoder, AppError> {
+        let channels = validate_format(config.sample_rate, config.channels)?;
+        let application = match config.application {
+            OpusApplication::Voip => mousiki::Application::Voip,
+            OpusApplication::Audio => mousiki::Application::Audio,
+            OpusApplication::RestrictedLowDelay => mousiki::Application::LowDelay,
+        };
+        let signal = match config.signal_type {
+            OpusSignalType::Auto => mousiki::Signal::Auto,
+            OpusSignalType::Voice => mousiki::Signal::Voice,
+            OpusSignalType::Music => mousiki::Signal::Music,
+        };
+        let (vbr, vbr_constraint) = match config.vbr_mode {
+            OpusVbrMode::CBR => (false, false),
+            OpusVbrMode::VBR => (true, false),
+            OpusVbrMode::CVBR => (true, true),
+        };
+        let packet_loss_perc = if config.packet_loss_resilience { EXPECTED_PACKET_LOSS_PERC } else { 0 };
+
+        mousiki::Encoder::builder(config.sample_rate, channels, application)
+            .bitrate(mousiki::Bitrate::Bits(config.bitrate as i32))
+            .complexity(i32::from(Self::effective_complexity(config)))
+            .signal(signal)
+            .vbr(vbr)
+            .vbr_constraint(vbr_constraint)
+            .frame_duration(config.frame_duration.to_native())
+            .inband_fec(config.inband_fec)
+            .packet_loss_perc(packet_loss_perc)
+            .dtx(config.dtx_enabled)
+            .build()
+            .map_err(|e| AppError::EncodingError {
+                message: format!("Initialisation de l'encodeur Opus impossible: {:?}", e),
+            })
+    }
+
+    /// Complexité transmise à mousiki, plafonnée en stéréo (cf. `MAX_STEREO_COMPLEXITY`)
+    fn effective_complexity(config: &OpusEncoderConfig) -> u8 {
+        let max = if config.channels == 2 { MAX_STEREO_COMPLEXITY } else { 10 };
+        config.complexity.min(max)
+    }
+
     /// Adapte automatiquement le bitrate selon les conditions
     #[allow(dead_code)]
     fn adapt_bitrate(&mut self, available_bandwidth: u32, packet_loss: f32) -> Result<(), AppError> {
-        let mut state = self.encoder_state.lock();
-        
-        // Calcul du bitrate optimal
-        let mut target_bitrate = available_bandwidth * 8 / 10; // 80% de la bande passante
-        
-        // Réduction si perte de paquets
-        if packet_loss > 0.01 { // > 1%
-            target_bitrate = (target_bitrate as f32 * (1.0 - packet_loss * 2.0)) as u32;
-        }
-        
-        // Limites du codec
-        target_bitrate = target_bitrate.clamp(6_000, 512_000);
-        
-        if target_bitrate != state.bandwidth_adaptation.current_bitrate {
+        let new_bitrate = {
+            let mut state = self.encoder_state.lock();
+
+            // Calcul du bitrate optimal
+            let mut target_bitrate = available_bandwidth * 8 / 10; // 80% de la bande passante
+
+            // Réduction si perte de paquets
+            if packet_loss > 0.01 { // > 1%
+                target_bitrate = (target_bitrate as f32 * (1.0 - packet_loss * 2.0)) as u32;
+            }
+
+            // Limites du codec
+            target_bitrate = target_bitrate.clamp(6_000, 512_000);
+
+            if target_bitrate == state.bandwidth_adaptation.current_bitrate {
+                return Ok(());
+            }
             state.bandwidth_adaptation.target_bitrate = target_bitrate;
-            
+
             // Adaptation progressive
             let diff = target_bitrate as f32 - state.bandwidth_adaptation.current_bitrate as f32;
             let adjustment = diff * state.bandwidth_adaptation._adaptation_rate;
-            state.bandwidth_adaptation.current_bitrate = 
-                (state.bandwidth_adaptation.current_bitrate as f32 + adjustment) as u32;
-            
-            debug!("Adaptation bitrate Opus: {} -> {} bps", 
-                   state.last_bitrate, state.bandwidth_adaptation.current_bitrate);
-        }
-        
-        Ok(())
+            (state.bandwidth_adaptation.current_bitrate as f32 + adjustment) as u32
+        };
+
+        debug!("Adaptation bitrate Opus: {} -> {} bps", self.config.bitrate, new_bitrate);
+        self.set_bitrate(new_bitrate)
     }
-    
-    /// Encode avec détection automatique du type de signal
-    fn encode_with_signal_detection(&mut self, samples: &[f32]) -> Result<Vec<u8>, AppError> {
-        // Analyse simple du signal pour optimiser l'encodage
-        let signal_type = self.detect_signal_type(samples);
-        
-        // Simulation d'encodage Opus
-        // En production, utiliser opus_encode() ou opus_encode_float()
-        let encoded_size = self.estimate_encoded_size(samples.len());
-        let mut encoded_data = vec![0u8; encoded_size];
-        
-        // Simulation de l'encodage avec compression réaliste
-        self.simulate_opus_encoding(samples, &mut encoded_data)?;
-        
-        // Mise à jour des métriques
-        self.update_encoding_metrics(samples.len(), encoded_data.len());
-        
-        debug!("Frame Opus encodée: {} samples -> {} bytes (type: {:?})", 
-               samples.len(), encoded_data.len(), signal_type);
-        
-        Ok(encoded_data)
-    }
-    
-    /// Détecte le type de signal pour optimiser l'encodage
-    fn detect_signal_type(&self, samples: &[f32]) -> OpusSignalType {
-        if samples.is_empty() {
-            return OpusSignalType::Auto;
-        }
-        
-        // Calcul de métriques simples
-        let energy = samples.iter().map(|&s| s * s).sum::<f32>() / samples.len() as f32;
-        let zero_crossings = samples.windows(2)
-            .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
-            .count();
-        
-        let zcr = zero_crossings as f32 / samples.len() as f32;
-        
-        // Heuristiques simples
-        if zcr > 0.1 && energy > 0.01 {
-            OpusSignalType::Voice
-        } else if energy > 0.001 {
-            OpusSignalType::Music
-        } else {
-            OpusSignalType::Auto
+
+    /// Encode toutes les frames complètes du buffer en paquets Opus bruts
+    fn encode_buffered_frames(&mut self) -> Result<Vec<Vec<u8>>, AppError> {
+        let mut packets = Vec::new();
+        while self.sample_buffer.len() >= self.frame_size {
+            let frame: Vec<f32> = self.sample_buffer.drain(..self.frame_size).collect();
+            packets.push(self.encode_frame(&frame)?);
         }
+        Ok(packets)
     }
-    
-    /// Estime la taille encodée
-    fn estimate_encoded_size(&self, sample_count: usize) -> usize {
-        let duration_ms = (sample_count as f32 / self.config.sample_rate as f32) * 1000.0;
-        let bits_per_ms = self.config.bitrate as f32 / 1000.0;
-        let estimated_bits = duration_ms * bits_per_ms;
-        (estimated_bits / 8.0) as usize
-    }
-    
-    /// Simulation d'encodage Opus
-    fn simulate_opus_encoding(&self, samples: &[f32], output: &mut [u8]) -> Result<(), AppError> {
-        // Simulation simple - en production, utiliser libopus
-        for (i, &sample) in samples.iter().enumerate() {
-            if i / 4 < output.len() {
-                // Quantification simulée
-                let quantized = (sample * 127.0) as i8;
-                output[i / 4] = quantized as u8;
-            }
-        }
-        Ok(())
+
+    /// Encode une frame complète en un paquet Opus
+    fn encode_frame(&mut self, frame: &[f32]) -> Result<Vec<u8>, AppError> {
+        let start = Instant::now();
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        let size = self.encoder.encode_float(frame, &mut packet).map_err(|e| AppError::EncodingError {
+            message: format!("Encodage Opus échoué: {:?}", e),
+        })?;
+        packet.truncate(size);
+
+        self.update_encoding_metrics(frame.len(), packet.len(), start);
+        Ok(packet)
     }
-    
+
     /// Met à jour les métriques d'encodage
-    fn update_encoding_metrics(&mut self, input_samples: usize, output_bytes: usize) {
+    fn update_encoding_metrics(&mut self, input_samples: usize, output_bytes: usize, start: Instant) {
         self.metrics.frames_encoded += 1;
         self.metrics.bytes_output += output_bytes as u64;
-        
+        self.metrics.encoding_time_ms += start.elapsed().as_millis() as u64;
+
         // Calcul du ratio de compression
-        let input_bytes = input_samples * 4; // f32 = 4 bytes
-        self.metrics.compression_ratio = input_bytes as f32 / output_bytes as f32;
-        
+        let input_bytes = self.metrics.frames_encoded * (input_samples as u64) * 4; // f32 = 4 bytes
+        self.metrics.compression_ratio = input_bytes as f32 / self.metrics.bytes_output.max(1) as f32;
+
         // Mise à jour de l'état
         {
             let mut state = self.encoder_state.lock();
@@ -420,6 +595,18 @@ impl OpusEncoderImpl {
             state.last_frame_time = Some(Instant::now());
         }
     }
+
+    fn samples_per_frame(&self) -> usize {
+        self.frame_size / self.config.channels as usize
+    }
+
+    /// Encode en paquets Opus bruts, sans encapsulation Ogg (RTP, WebRTC)
+    ///
+    /// Une frame incomplète reste en attente du prochain appel.
+    pub fn encode_packets(&mut self, samples: &[f32]) -> Result<Vec<Vec<u8>>, AppError> {
+        self.sample_buffer.extend_from_slice(samples);
+        self.encode_buffered_frames()
+    }
 }
 
 impl AudioEncoder for OpusEncoderImpl {
@@ -431,87 +618,106 @@ impl AudioEncoder for OpusEncoderImpl {
                 got: format!("{}Hz, {} ch", sample_rate, channels),
             });
         }
-        
+
         // Ajouter les échantillons au buffer
         self.sample_buffer.extend_from_slice(samples);
-        
-        let mut encoded_frames = Vec::new();
-        
+        self.muxer.add_input(samples.len() / channels as usize);
+
         // Traiter les frames complètes
-        while self.sample_buffer.len() >= self.frame_size {
-            let frame_samples: Vec<f32> = self.sample_buffer.drain(..self.frame_size).collect();
-            let encoded_frame = self.encode_with_signal_detection(&frame_samples)?;
-            encoded_frames.extend(encoded_frame);
-        }
-        
-        Ok(encoded_frames)
+        let packets = self.encode_buffered_frames()?;
+        let samples_per_frame = self.samples_per_frame();
+        self.muxer.write_packets(packets, samples_per_frame, false)
     }
-    
+
     fn finalize(&mut self) -> Result<Vec<u8>, AppError> {
-        // Encoder le buffer restant avec padding si nécessaire
-        if !self.sample_buffer.is_empty() {
-            // Padding avec du silence
-            while self.sample_buffer.len() < self.frame_size {
-                self.sample_buffer.push(0.0);
+        // Le décodeur retire `pre_skip` échantillons en tête : on complète avec
+        // du silence jusqu'à ce que tout l'audio d'entrée soit sorti du lookahead.
+        let end_granule = self.muxer.end_granule();
+        let step = self.muxer.to_granule(self.samples_per_frame());
+        let mut granule = self.muxer.granule;
+        let mut packets = Vec::new();
+        loop {
+            self.sample_buffer.resize(self.frame_size, 0.0);
+            let frame: Vec<f32> = std::mem::take(&mut self.sample_buffer);
+            packets.push(self.encode_frame(&frame)?);
+            granule += step;
+            if granule >= end_granule {
+                break;
             }
-            
-            let final_frame: Vec<f32> = self.sample_buffer.drain(..).collect();
-            return self.encode_with_signal_detection(&final_frame);
         }
-        
-        Ok(Vec::new())
+
+        let samples_per_frame = self.samples_per_frame();
+        let pages = self.muxer.write_packets(packets, samples_per_frame, true)?;
+
+        // Un nouvel appel à encode() démarre un flux logique chaîné
+        self.encoder.reset_state().map_err(|e| AppError::EncodingError {
+            message: format!("Remise à zéro Opus impossible: {:?}", e),
+        })?;
+        self.muxer = OggOpusMuxer::new(self.config.channels, self.config.sample_rate, self.lookahead);
+
+        Ok(pages)
     }
-    
+
     fn reset(&mut self) -> Result<(), AppError> {
         self.sample_buffer.clear();
         self.metrics = EncoderMetrics::default();
-        
+        self.encoder.reset_state().map_err(|e| AppError::EncodingError {
+            message: format!("Remise à zéro Opus impossible: {:?}", e),
+        })?;
+        self.muxer = OggOpusMuxer::new(self.config.channels, self.config.sample_rate, self.lookahead);
+
         {
             let mut state = self.encoder_state.lock();
             state.total_frames = 0;
             state.total_bytes = 0;
             state.last_frame_time = None;
         }
-        
+
         debug!("Encodeur Opus remis à zéro");
         Ok(())
     }
-    
+
     fn set_bitrate(&mut self, bitrate: u32) -> Result<(), AppError> {
-        if bitrate < 6_000 || bitrate > 512_000 {
+        if !(6_000..=512_000).contains(&bitrate) {
             return Err(AppError::InvalidBitrate {
                 bitrate,
                 codec: "opus".to_string(),
             });
         }
-        
+
+        self.encoder
+            .set_bitrate(mousiki::Bitrate::Bits(bitrate as i32))
+            .map_err(|e| AppError::EncodingError {
+                message: format!("Changement de bitrate Opus impossible: {:?}", e),
+            })?;
         self.config.bitrate = bitrate;
-        
+
         {
             let mut state = self.encoder_state.lock();
             state.last_bitrate = bitrate;
             state.bandwidth_adaptation.current_bitrate = bitrate;
             state.bandwidth_adaptation.target_bitrate = bitrate;
         }
-        
+
         debug!("Bitrate Opus mis à jour: {} bps", bitrate);
         Ok(())
     }
-    
+
     fn info(&self) -> EncoderInfo {
+        let lookahead_ms = self.lookahead as f32 * 1000.0 / self.config.sample_rate as f32;
         EncoderInfo {
             codec_name: "Opus".to_string(),
-            version: "1.3.1".to_string(),
+            version: mousiki::opus_get_version_string().to_string(),
             bitrate: self.config.bitrate,
             sample_rate: self.config.sample_rate,
             channels: self.config.channels,
             bit_depth: 16, // Opus utilise toujours 16-bit en interne
             frame_size: self.frame_size,
-            latency_ms: self.config.frame_duration.as_ms(),
+            latency_ms: self.config.frame_duration.as_ms() + lookahead_ms,
             quality_mode: format!("{:?}", self.config.vbr_mode),
         }
     }
-    
+
     fn metrics(&self) -> EncoderMetrics {
         self.metrics.clone()
     }
@@ -519,6 +725,9 @@ impl AudioEncoder for OpusEncoderImpl {
 
 impl OpusDecoderImpl {
     /// Crée un nouveau décodeur Opus
+    ///
+    /// Le décodeur consomme des paquets Opus bruts, tels qu'extraits d'un
+    /// conteneur Ogg, WebM ou RTP.
     pub fn new(config: DecoderConfig) -> Result<Self, AppError> {
         let opus_config = OpusDecoderConfig {
             sample_rate: config.sample_rate,
@@ -526,140 +735,115 @@ impl OpusDecoderImpl {
             frame_duration: OpusFrameDuration::Ms10,
             gain_db: 0.0,
         };
-        
-        let decoder_state = OpusDecoderState {
-            initialized: false,
-            last_decode_time: None,
-            total_frames: 0,
-            packet_loss_concealment: PacketLossConcealmentState {
-                last_good_frame: None,
-                consecutive_losses: 0,
-                concealment_energy: 0.0,
-            },
-        };
-        
-        let output_buffer_size = opus_config.frame_duration.frame_size(opus_config.sample_rate) * opus_config.channels as usize;
-        
-        let mut decoder = Self {
+        Self::with_opus_config(opus_config)
+    }
+
+    /// Crée un décodeur à partir d'une configuration Opus complète
+    pub fn with_opus_config(opus_config: OpusDecoderConfig) -> Result<Self, AppError> {
+        validate_format(opus_config.sample_rate, opus_config.channels)?;
+
+        let max_samples = (opus_config.sample_rate * MAX_PACKET_DURATION_MS / 1000) as usize;
+
+        debug!("Décodeur Opus initialisé: {}Hz, {} ch",
+               opus_config.sample_rate, opus_config.channels);
+
+        Ok(Self {
+            gain: 10f32.powf(opus_config.gain_db / 20.0),
             config: opus_config,
-            decoder_state: Arc::new(Mutex::new(decoder_state)),
-            output_buffer: Vec::with_capacity(output_buffer_size * 2),
-        };
-        
-        decoder.initialize()?;
-        Ok(decoder)
+            decoders: [None, None],
+            last_packet: None,
+            decoder_state: Arc::new(Mutex::new(OpusDecoderState {
+                last_decode_time: None,
+                total_frames: 0,
+                consecutive_losses: 0,
+            })),
+            output_buffer: vec![0.0; max_samples * 2],
+        })
     }
-    
-    /// Initialise le décodeur
-    fn initialize(&mut self) -> Result<(), AppError> {
-        {
-            let mut state = self.decoder_state.lock();
-            state.initialized = true;
+
+    /// Décodeur natif pour un nombre de canaux codés, créé au premier paquet
+    fn native_decoder(&mut self, coded_channels: usize) -> Result<&mut opus_rs::OpusDecoder, AppError> {
+        let slot = &mut self.decoders[coded_channels - 1];
+        if slot.is_none() {
+            let decoder = opus_rs::OpusDecoder::new(self.config.sample_rate as i32, coded_channels)
+                .map_err(|e| AppError::DecodingError {
+                    message: format!("Initialisation du décodeur Opus impossible: {}", e),
+                })?;
+            *slot = Some(decoder);
         }
-        
-        debug!("Décodeur Opus initialisé: {}Hz, {} ch", 
-               self.config.sample_rate, self.config.channels);
-        Ok(())
+        Ok(slot.as_mut().expect("décodeur initialisé"))
     }
-    
+
     /// Décode avec dissimulation de perte de paquets
     fn decode_with_plc(&mut self, data: Option<&[u8]>) -> Result<DecodedAudio, AppError> {
-        let mut state = self.decoder_state.lock();
-        
-        match data {
-            Some(encoded_data) => {
-                // Décodage normal
-                let decoded_samples = self.simulate_opus_decoding(encoded_data)?;
-                
-                // Sauvegarder pour PLC
-                state.packet_loss_concealment.last_good_frame = Some(decoded_samples.clone());
-                state.packet_loss_concealment.consecutive_losses = 0;
-                state.total_frames += 1;
-                state.last_decode_time = Some(Instant::now());
-                
-                Ok(DecodedAudio {
-                    samples: decoded_samples,
-                    sample_rate: self.config.sample_rate,
-                    channels: self.config.channels,
-                    duration_ms: self.config.frame_duration.as_ms() as u32,
-                    format: AudioSampleFormat::F32,
-                })
-            }
-            None => {
-                // Perte de paquet - générer du contenu de remplacement
-                state.packet_loss_concealment.consecutive_losses += 1;
-                
-                let concealed_samples = self.generate_packet_loss_concealment(&mut state)?;
-                
-                Ok(DecodedAudio {
-                    samples: concealed_samples,
-                    sample_rate: self.config.sample_rate,
-                    channels: self.config.channels,
-                    duration_ms: self.config.frame_duration.as_ms() as u32,
-                    format: AudioSampleFormat::F32,
-                })
+        let channels = self.config.channels as usize;
+        let max_samples = self.output_buffer.len() / 2;
+
+        let (coded_channels, samples_per_channel) = match (data, self.last_packet) {
+            (Some(packet), _) => {
+                let coded_channels = if packet[0] & 0x04 != 0 { 2 } else { 1 };
+                let mut output = std::mem::take(&mut self.output_buffer);
+                let decoded = self.native_decoder(coded_channels)
+                    .and_then(|decoder| decoder
+                        .decode(packet, max_samples, &mut output[..max_samples * coded_channels])
+                        .map_err(|e| AppError::DecodingError {
+                            message: format!("Décodage Opus échoué: {}", e),
+                        }));
+                self.output_buffer = output;
+                let samples = decoded?;
+                self.last_packet = Some((coded_channels, samples));
+                (coded_channels, samples)
             }
-        }
-    }
-    
-    /// Simulation de décodage Opus
-    fn simulate_opus_decoding(&self, data: &[u8]) -> Result<Vec<f32>, AppError> {
-        let frame_size = self.config.frame_duration.frame_size(self.config.sample_rate) * self.config.channels as usize;
-        let mut samples = Vec::with_capacity(frame_size);
-        
-        // Simulation simple - en production, utiliser opus_decode()
-        for (i, &byte) in data.iter().enumerate() {
-            if samples.len() < frame_size {
-                // Dé-quantification simulée
-                let sample = (byte as i8) as f32 / 127.0;
-                samples.push(sample);
-                
-                // Interpolation pour atteindre la taille de frame
-                if i < data.len() - 1 {
-                    for _ in 0..3 {
-                        if samples.len() < frame_size {
-                            samples.push(sample * 0.8); // Interpolation simple
-                        }
-                    }
-                }
+            (None, Some((coded_channels, last))) => {
+                // Perte de paquet : PLC natif sur la durée du dernier paquet reçu
+                let mut output = std::mem::take(&mut self.output_buffer);
+                let concealed = self.native_decoder(coded_channels)
+                    .and_then(|decoder| decoder
+                        .decode(&[], last, &mut output[..last * coded_channels])
+                        .map_err(|e| AppError::DecodingError {
+                            message: format!("Dissimulation Opus échouée: {}", e),
+                        }));
+                self.output_buffer = output;
+                (coded_channels, concealed?)
             }
-        }
-        
-        // Compléter avec du silence si nécessaire
-        while samples.len() < frame_size {
-            samples.push(0.0);
-        }
-        
-        Ok(samples)
-    }
-    
-    /// Génère une dissimulation de perte de paquet
-    fn generate_packet_loss_concealment(&self, state: &mut OpusDecoderState) -> Result<Vec<f32>, AppError> {
-        let frame_size = self.config.frame_duration.frame_size(self.config.sample_rate) * self.config.channels as usize;
-        
-        if let Some(ref last_frame) = state.packet_loss_concealment.last_good_frame {
-            // Atténuation progressive basée sur le nombre de pertes consécutives
-            let attenuation = 0.8_f32.powf(state.packet_loss_concealment.consecutive_losses as f32);
-            
-            let mut concealed = Vec::with_capacity(frame_size);
-            for (i, &sample) in last_frame.iter().enumerate() {
-                if concealed.len() < frame_size {
-                    // Atténuation avec légère variation pour éviter les artefacts
-                    let variation = (i as f32 * 0.1).sin() * 0.01;
-                    concealed.push(sample * attenuation + variation);
-                }
+            (None, None) => {
+                // Aucun paquet reçu : rien à prolonger, on rend du silence
+                let samples = self.config.frame_duration.frame_size(self.config.sample_rate);
+                self.output_buffer[..samples * channels].fill(0.0);
+                (channels, samples)
             }
-            
-            // Compléter si nécessaire
-            while concealed.len() < frame_size {
-                concealed.push(0.0);
+        };
+
+        {
+            let mut state = self.decoder_state.lock();
+            if data.is_some() {
+                state.consecutive_losses = 0;
+                state.total_frames += 1;
+            } else {
+                state.consecutive_losses += 1;
             }
-            
-            Ok(concealed)
-        } else {
-            // Pas de frame précédente, générer du silence
-            Ok(vec![0.0; frame_size])
+            state.last_decode_time = Some(Instant::now());
         }
+
+        let decoded = &self.output_buffer[..samples_per_channel * coded_channels];
+        let samples: Vec<f32> = match (coded_channels, channels) {
+            (1, 2) => decoded.iter().flat_map(|&s| [s, s]).collect(),
+            (2, 1) => decoded.chunks_exact(2).map(|pair| (pair[0] + pair[1]) * 0.5).collect(),
+            _ => decoded.to_vec(),
+        };
+        let samples = if self.gain != 1.0 {
+            samples.into_iter().map(|s| s * self.gain).collect()
+        } else {
+            samples
+        };
+
+        Ok(DecodedAudio {
+            samples,
+            sample_rate: self.config.sample_rate,
+            channels: self.config.channels,
+            duration_ms: (samples_per_channel as u64 * 1000 / u64::from(self.config.sample_rate)) as u32,
+            format: AudioSampleFormat::F32,
+        })
     }
 }
 
@@ -672,31 +856,313 @@ impl AudioDecoder for OpusDecoderImpl {
             self.decode_with_plc(Some(data))
         }
     }
-    
+
     fn reset(&mut self) -> Result<(), AppError> {
-        self.output_buffer.clear();
-        
+        self.decoders = [None, None];
+        self.last_packet = None;
+
         {
             let mut state = self.decoder_state.lock();
             state.total_frames = 0;
             state.last_decode_time = None;
-            state.packet_loss_concealment.last_good_frame = None;
-            state.packet_loss_concealment.consecutive_losses = 0;
-            state.packet_loss_concealment.concealment_energy = 0.0;
+            state.consecutive_losses = 0;
         }
-        
+
         debug!("Décodeur Opus remis à zéro");
         Ok(())
     }
-    
+
     fn info(&self) -> DecoderInfo {
         DecoderInfo {
             codec_name: "Opus".to_string(),
-            version: "1.3.1".to_string(),
+            version: "opus-rs".to_string(),
             sample_rate: self.config.sample_rate,
             channels: self.config.channels,
             bit_depth: 16,
             frame_size: self.config.frame_duration.frame_size(self.config.sample_rate) * self.config.channels as usize,
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use symphonia::core::codecs::CODEC_TYPE_OPUS;
+    use symphonia::core::formats::{FormatOptions, FormatReader};
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::default::formats::OggReader;
+
+    fn sine(sample_rate: u32, channels: u8, seconds: f32) -> Vec<f32> {
+        let frames = (sample_rate as f32 * seconds) as usize;
+        (0..frames)
+            .flat_map(|i| {
+                let s = (2.0 * std::f32::consts::PI * 440.0 * i as f32 / sample_rate as f32).sin() * 0.5;
+                std::iter::repeat(s).take(channels as usize)
+            })
+            .collect()
+    }
+
+    fn encode_ogg(mut encoder: OpusEncoderImpl, pcm: &[f32]) -> Vec<u8> {
+        let (sample_rate, channels) = (encoder.config.sample_rate, encoder.config.channels);
+        let mut ogg = Vec::new();
+        for chunk in pcm.chunks(1234 * channels as usize) {
+            ogg.extend(encoder.encode(chunk, sample_rate, channels).unwrap());
+        }
+        ogg.extend(encoder.finalize().unwrap());
+        ogg
+    }
+
+    fn encode_packets(config: OpusEncoderConfig, pcm: &[f32]) -> Vec<Vec<u8>> {
+        let mut encoder = OpusEncoderImpl::with_opus_config(config).unwrap();
+        encoder.sample_buffer.extend_from_slice(pcm);
+        encoder.encode_buffered_frames().unwrap()
+    }
+
+    #[test]
+    fn test_ogg_opus_round_trip() {
+        let pcm = sine(48000, 2, 1.0);
+        let encoder = OpusEncoderImpl::new(EncoderConfig {
+            sample_rate: 48000,
+            ..EncoderConfig::default()
+        })
+        .unwrap();
+        let ogg = encode_ogg(encoder, &pcm);
+
+        let source = MediaSourceStream::new(Box::new(Cursor::new(ogg)), Default::default());
+        let mut reader = OggReader::try_new(source, &FormatOptions::default()).unwrap();
+        let params = reader.default_track().unwrap().codec_params.clone();
+        assert_eq!(params.codec, CODEC_TYPE_OPUS);
+        assert_eq!(params.channels.unwrap().count(), 2);
+
+        // pre-skip + audio + padding de fin : la durée exacte est restituée
+        let pre_skip = params.delay.unwrap() as usize;
+        let padding = params.padding.unwrap_or(0) as usize;
+        assert!(pre_skip > 0);
+        assert_eq!(params.n_frames.unwrap() as usize - pre_skip - padding, 48000);
+
+        // Décodage par opus-rs, implémentation indépendante de l'encodeur mousiki
+        let mut decoder = OpusDecoderImpl::new(DecoderConfig {
+            sample_rate: 48000,
+            ..DecoderConfig::default()
+        })
+        .unwrap();
+        let mut decoded = Vec::new();
+        while let Ok(packet) = reader.next_packet() {
+            decoded.extend(decoder.decode(&packet.data).unwrap().samples);
+        }
+        let decoded = &decoded[pre_skip * 2..];
+        assert!(decoded.len() >= pcm.len());
+
+        // Corrélation normalisée entre l'original et le signal décodé, une fois le pre-skip retiré
+        let (original, output) = (&pcm[9600..], &decoded[9600..pcm.len()]);
+        let dot: f32 = original.iter().zip(output).map(|(a, b)| a * b).sum();
+        let energy = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!(dot / (energy(original) * energy(output)) > 0.95);
+    }
+
+    /// Lit la longueur d'une trame codée sur un ou deux octets (RFC 6716 §3.2.1)
+    fn frame_length(data: &[u8]) -> Option<(usize, usize)> {
+        match *data.first()? {
+            b @ 0..=251 => Some((b as usize, 1)),
+            b => Some((*data.get(1)? as usize * 4 + b as usize, 2)),
+        }
+    }
+
+    /// Valide le découpage d'un paquet Opus sans le décoder (RFC 6716 §3) et
+    /// renvoie sa durée en échantillons à 48 kHz
+    fn opus_packet_samples(packet: &[u8]) -> Option<usize> {
+        const MAX_FRAME_BYTES: usize = 1275;
+        let (&toc, mut data) = packet.split_first()?;
+        let config = toc >> 3;
+        let frame_samples = match config {
+            0..=11 => [480, 960, 1920, 2880][config as usize % 4],
+            12..=15 => [480, 960][config as usize % 2],
+            _ => [120, 240, 480, 960][config as usize % 4],
+        };
+
+        let frames = match toc & 0x03 {
+            0 => {
+                (data.len() <= MAX_FRAME_BYTES).then_some(())?;
+                1
+            }
+            1 => {
+                (data.len() % 2 == 0 && data.len() / 2 <= MAX_FRAME_BYTES).then_some(())?;
+                2
+            }
+            2 => {
+                let (first, used) = frame_length(data)?;
+                let second = data.len().checked_sub(used + first)?;
+                (first <= MAX_FRAME_BYTES && second <= MAX_FRAME_BYTES).then_some(())?;
+                2
+            }
+            _ => {
+                let (&count_byte, rest) = data.split_first()?;
+                data = rest;
+                let count = (count_byte & 0x3F) as usize;
+                (count > 0 && count * frame_samples <= 5760).then_some(())?;
+
+                let mut padding = 0;
+                if count_byte & 0x40 != 0 {
+                    loop {
+                        let (&byte, rest) = data.split_first()?;
+                        data = rest;
+                        padding += if byte == 255 { 254 } else { byte as usize };
+                        if byte != 255 {
+                            break;
+                        }
+                    }
+                }
+                let mut remaining = data.len().checked_sub(padding)?;
+
+                if count_byte & 0x80 != 0 {
+                    // VBR : toutes les longueurs sauf la dernière sont explicites
+                    for _ in 1..count {
+                        let (length, used) = frame_length(data)?;
+                        data = &data[used..];
+                        remaining = remaining.checked_sub(used + length)?;
+                        (length <= MAX_FRAME_BYTES).then_some(())?;
+                    }
+                    (remaining <= MAX_FRAME_BYTES).then_some(())?;
+                } else {
+                    (remaining % count == 0 && remaining / count <= MAX_FRAME_BYTES).then_some(())?;
+                }
+                count
+            }
+        };
+        Some(frames * frame_samples)
+    }
+
+    #[test]
+    fn test_ogg_packets_are_well_formed() {
+        // Validation indépendante de mousiki : démultiplexage symphonia et
+        // contrôle de la structure de chaque paquet selon la RFC 6716
+        let pcm = sine(48000, 2, 1.5);
+        let encoder = OpusEncoderImpl::new(EncoderConfig {
+            sample_rate: 48000,
+            ..EncoderConfig::default()
+        })
+        .unwrap();
+        let frame_samples = encoder.config.frame_duration.frame_size(OGG_OPUS_RATE);
+        let ogg = encode_ogg(encoder, &pcm);
+
+        let source = MediaSourceStream::new(Box::new(Cursor::new(ogg)), Default::default());
+        let mut reader = OggReader::try_new(source, &FormatOptions::default()).unwrap();
+        let params = reader.default_track().unwrap().codec_params.clone();
+        assert_eq!(params.codec, CODEC_TYPE_OPUS);
+        assert_eq!(params.sample_rate, Some(OGG_OPUS_RATE));
+
+        let (mut packets, mut total, mut next_ts) = (0, 0, 0);
+        while let Ok(packet) = reader.next_packet() {
+            let samples = opus_packet_samples(&packet.data)
+                .unwrap_or_else(|| panic!("paquet Opus mal formé : {:02x?}", &packet.data[..packet.data.len().min(8)]));
+            assert_eq!(samples, frame_samples);
+            assert_eq!(packet.ts, next_ts);
+            next_ts += samples as u64;
+            total += samples;
+            packets += 1;
+        }
+
+        // Les durées lues dans les TOC couvrent exactement le flux annoncé par les granules
+        let pre_skip = params.delay.unwrap() as usize;
+        let padding = params.padding.unwrap_or(0) as usize;
+        assert_eq!(total, params.n_frames.unwrap() as usize);
+        assert_eq!(total - pre_skip - padding, 72000);
+        assert_eq!(packets, total / frame_samples);
+    }
+
+    #[test]
+    fn test_frame_duration_is_honoured() {
+        for (duration, samples) in [
+            (OpusFrameDuration::Ms2_5, 120),
+            (OpusFrameDuration::Ms5, 240),
+            (OpusFrameDuration::Ms20, 960),
+            (OpusFrameDuration::Ms60, 2880),
+        ] {
+            let config = OpusEncoderConfig {
+                application: OpusApplication::RestrictedLowDelay,
+                frame_duration: duration,
+                ..OpusEncoderConfig::default()
+            };
+            let packets = encode_packets(config, &sine(48000, 2, 0.2));
+
+            assert!(!packets.is_empty());
+            for packet in &packets {
+                assert_eq!(opus_packet_samples(packet).unwrap(), samples);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cbr_produces_constant_packet_sizes() {
+        let config = OpusEncoderConfig {
+            vbr_mode: OpusVbrMode::CBR,
+            bitrate: 64_000,
+            frame_duration: OpusFrameDuration::Ms20,
+            packet_loss_resilience: false,
+            ..OpusEncoderConfig::default()
+        };
+        let packets = encode_packets(config, &sine(48000, 2, 0.5));
+
+        assert!(packets.iter().all(|p| p.len() == packets[0].len()));
+    }
+
+    #[test]
+    fn test_dtx_shrinks_silence() {
+        let config = OpusEncoderConfig {
+            application: OpusApplication::Voip,
+            frame_duration: OpusFrameDuration::Ms20,
+            dtx_enabled: true,
+            ..OpusEncoderConfig::default()
+        };
+        let packets = encode_packets(config, &vec![0.0; 48000 * 2]);
+
+        assert!(packets.iter().filter(|p| p.len() <= 2).count() > packets.len() / 2);
+    }
+
+    #[test]
+    fn test_packet_loss_is_concealed() {
+        let config = OpusEncoderConfig {
+            channels: 1,
+            ..OpusEncoderConfig::default()
+        };
+        let packets = encode_packets(config, &sine(48000, 1, 0.1));
+
+        let mut decoder = OpusDecoderImpl::new(DecoderConfig {
+            sample_rate: 48000,
+            channels: 1,
+            ..DecoderConfig::default()
+        })
+        .unwrap();
+        let frame = decoder.decode(&packets[0]).unwrap();
+        let concealed = decoder.decode(&[]).unwrap();
+        assert_eq!(concealed.samples.len(), frame.samples.len());
+        assert_eq!(concealed.duration_ms, 10);
+    }
+
+    #[test]
+    fn test_stereo_hybrid_encode_decodes_independently() {
+        // À bas débit l'encodeur passe en hybride SILK+CELT : c'est là que la
+        // re-synthèse stéréo de mousiki déclenchait le chevauchement lowband
+        let config = OpusEncoderConfig {
+            bitrate: 32_000,
+            complexity: 10,
+            frame_duration: OpusFrameDuration::Ms20,
+            packet_loss_resilience: false,
+            inband_fec: false,
+            ..OpusEncoderConfig::default()
+        };
+        let packets = encode_packets(config, &sine(48000, 2, 1.0));
+        assert!(packets.iter().any(|p| (12..=15).contains(&(p[0] >> 3))));
+
+        let mut decoder = OpusDecoderImpl::with_opus_config(OpusDecoderConfig::default()).unwrap();
+        let mut decoded = Vec::new();
+        for packet in &packets {
+            let frame = decoder.decode(packet).unwrap();
+            assert_eq!(frame.samples.len(), 960 * 2);
+            decoded.extend(frame.samples);
+        }
+        let rms = (decoded[9600..].iter().map(|s| s * s).sum::<f32>() / (decoded.len() - 9600) as f32).sqrt();
+        assert!(rms > 0.1, "rms {}", rms);
+    }
+}
@@ -34,6 +34,12 @@ pub struct Config {
     // Configuration de compression
     pub compression: CompressionConfig,
     
+    // Stockage des masters et des rendus
+    pub storage: StorageConfig,
+    
+    // Sources live (Icecast)
+    pub live: LiveConfig,
+    
     // Profil d'environnement
     pub environment: Environment,
 }
@@ -123,6 +129,78 @@ pub struct CompressionConfig {
     pub quality_profiles: Vec<String>,
 }
 
+/// Backend de stockage des fichiers uploadés
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StorageBackend {
+    /// Disque local du nœud (développement, nœud unique)
+    Local,
+    /// Stockage objet compatible S3 partagé entre les nœuds (AWS, MinIO, R2...)
+    S3,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    pub local_path: String,
+    pub public_url_base: Option<String>,
+    pub s3: S3StorageConfig,
+    /// Stockage adressé par contenu : un seul blob par SHA-256. Actif par
+    /// défaut en local ; avec S3, à activer explicitement, le service devant
+    /// gérer les écritures conditionnelles (`If-Match`) de l'index partagé
+    pub deduplicate: bool,
+    /// Index des références vers les blobs du backend local ; avec S3, l'index
+    /// est l'objet `dedup/index.json` du bucket, partagé par tous les nœuds
+    pub dedup_index_path: String,
+    /// Délai avant suppression d'un blob devenu sans référence
+    pub gc_grace: Duration,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct S3StorageConfig {
+    /// URL du service, ex. `https://s3.eu-west-3.amazonaws.com` ou `http://minio:9000`
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Adressage `endpoint/bucket/clé` (MinIO) plutôt que `bucket.endpoint/clé`
+    pub path_style: bool,
+    /// Préfixe ajouté à toutes les clés d'objets
+    pub prefix: String,
+    /// Durée de validité des URLs présignées
+    pub presign_expiry: Duration,
+    /// Taille des parts d'un upload multipart ; les fichiers plus petits partent en un seul PUT
+    pub multipart_part_size: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LiveConfig {
+    /// Port du serveur Icecast pour les sources live (0 pour le désactiver)
+    pub icecast_port: u16,
+    /// Port du serveur RTMP pour les sources live (0 pour le désactiver)
+    pub rtmp_port: u16,
+    /// Déconnexion d'une source qui n'envoie plus rien
+    pub source_idle_timeout: Duration,
+    /// Débits (kbps) des renditions HLS des streams créés par une source, en
+    /// AAC avec la fonctionnalité `fdk-aac`, en MP3 sinon
+    pub hls_bitrates: Vec<u32>,
+    /// Débit (kbps) des flux servis aux auditeurs Icecast (MP3, et AAC avec la
+    /// fonctionnalité `fdk-aac`)
+    pub icy_bitrate: u32,
+    /// Octets audio entre deux blocs de métadonnées ICY
+    pub icy_metaint: usize,
+    /// Port UDP partagé par les sessions WHIP/WHEP (0 pour un port aléatoire)
+    pub webrtc_udp_port: u16,
+    /// IP publiques annoncées dans les candidats ICE (serveur derrière un NAT 1:1)
+    pub webrtc_public_ips: Vec<String>,
+    /// Débit (kbps) de l'Opus envoyé aux lecteurs WHEP
+    pub webrtc_opus_bitrate: u32,
+    /// Débit (kbps) des flux fMP4 servis par le protocole WebSocket binaire
+    pub ws_audio_bitrate: u32,
+    /// Âge maximal d'un fragment WebSocket en attente avant abandon
+    pub ws_audio_max_latency: Duration,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Environment {
     Development,
@@ -179,6 +257,13 @@ impl Config {
             _ => Environment::Development,
         };
 
+        let audio_dir = env::var("AUDIO_DIR")
+            .unwrap_or_else(|_| "./audio".to_string());
+        let storage_backend = match env::var("STORAGE_BACKEND").unwrap_or_default().to_lowercase().as_str() {
+            "s3" => StorageBackend::S3,
+            _ => StorageBackend::Local,
+        };
+
         let config = Self {
             secret_key: env::var("SECRET_KEY")
                 .unwrap_or_else(|_| "your-secret-key-change-in-production".to_string()),
@@ -188,8 +273,7 @@ impl Config {
                 .unwrap_or_else(|_| "3002".to_string())
                 .parse()
                 .map_err(|_| ConfigError::InvalidPort)?,
-            audio_dir: env::var("AUDIO_DIR")
-                .unwrap_or_else(|_| "./audio".to_string()),
+            audio_dir: audio_dir.clone(),
             allowed_origins: env::var("ALLOWED_ORIGINS")
                 .unwrap_or_else(|_| "*".to_string())
                 .split(',')
@@ -444,6 +528,103 @@ impl Config {
                     .collect(),
             },
 
+            storage: StorageConfig {
+                backend: storage_backend.clone(),
+                local_path: env::var("STORAGE_LOCAL_PATH")
+                    .unwrap_or_else(|_| format!("{}/uploads", audio_dir)),
+                deduplicate: env::var("STORAGE_DEDUPLICATE")
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(storage_backend == StorageBackend::Local),
+                dedup_index_path: env::var("STORAGE_DEDUP_INDEX")
+                    .unwrap_or_else(|_| format!("{}/dedup-index.json", audio_dir)),
+                gc_grace: Duration::from_secs(
+                    env::var("STORAGE_GC_GRACE")
+                        .unwrap_or_else(|_| "86400".to_string())
+                        .parse()
+                        .unwrap_or(86400)
+                ),
+                public_url_base: env::var("STORAGE_PUBLIC_URL").ok(),
+                s3: S3StorageConfig {
+                    endpoint: env::var("S3_ENDPOINT")
+                        .unwrap_or_else(|_| "https://s3.amazonaws.com".to_string()),
+                    region: env::var("S3_REGION")
+                        .unwrap_or_else(|_| "us-east-1".to_string()),
+                    bucket: env::var("S3_BUCKET").unwrap_or_default(),
+                    access_key_id: env::var("S3_ACCESS_KEY_ID").unwrap_or_default(),
+                    secret_access_key: env::var("S3_SECRET_ACCESS_KEY").unwrap_or_default(),
+                    path_style: env::var("S3_PATH_STYLE")
+                        .unwrap_or_else(|_| "true".to_string())
+                        .parse()
+                        .unwrap_or(true),
+                    prefix: env::var("S3_PREFIX").unwrap_or_default(),
+                    presign_expiry: Duration::from_secs(
+                        env::var("S3_PRESIGN_EXPIRY")
+                            .unwrap_or_else(|_| "3600".to_string())
+                            .parse()
+                            .unwrap_or(3600)
+                    ),
+                    multipart_part_size: env::var("S3_MULTIPART_PART_SIZE")
+                        .unwrap_or_else(|_| "8388608".to_string())
+                        .parse()
+                        .unwrap_or(8 * 1024 * 1024),
+                },
+            },
+
+            live: LiveConfig {
+                icecast_port: env::var("ICECAST_PORT")
+                    .unwrap_or_else(|_| "8000".to_string())
+                    .parse()
+                    .unwrap_or(8000),
+                rtmp_port: env::var("RTMP_PORT")
+                    .unwrap_or_else(|_| "1935".to_string())
+                    .parse()
+                    .unwrap_or(1935),
+                source_idle_timeout: Duration::from_secs(
+                    env::var("LIVE_SOURCE_IDLE_TIMEOUT")
+                        .unwrap_or_else(|_| "10".to_string())
+                        .parse()
+                        .unwrap_or(10)
+                ),
+                hls_bitrates: env::var("LIVE_HLS_BITRATES")
+                    .unwrap_or_else(|_| "64,128".to_string())
+                    .split(',')
+                    .filter_map(|s| s.trim().parse().ok())
+                    .collect(),
+                icy_bitrate: env::var("ICY_BITRATE")
+                    .unwrap_or_else(|_| "128".to_string())
+                    .parse()
+                    .unwrap_or(128),
+                icy_metaint: env::var("ICY_METAINT")
+                    .unwrap_or_else(|_| "16000".to_string())
+                    .parse()
+                    .unwrap_or(16000),
+                webrtc_udp_port: env::var("WEBRTC_UDP_PORT")
+                    .unwrap_or_else(|_| "8189".to_string())
+                    .parse()
+                    .unwrap_or(8189),
+                webrtc_public_ips: env::var("WEBRTC_PUBLIC_IPS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|ip| ip.trim().to_string())
+                    .filter(|ip| !ip.is_empty())
+                    .collect(),
+                webrtc_opus_bitrate: env::var("WEBRTC_OPUS_BITRATE")
+                    .unwrap_or_else(|_| "128".to_string())
+                    .parse()
+                    .unwrap_or(128),
+                ws_audio_bitrate: env::var("WS_AUDIO_BITRATE")
+                    .unwrap_or_else(|_| "128".to_string())
+                    .parse()
+                    .unwrap_or(128),
+                ws_audio_max_latency: Duration::from_millis(
+                    env::var("WS_AUDIO_MAX_LATENCY_MS")
+                        .unwrap_or_else(|_| "2000".to_string())
+                        .parse()
+                        .unwrap_or(2000),
+                ),
+            },
+
             environment,
         };
 
@@ -489,6 +670,20 @@ impl Config {
             return Err(ConfigError::InvalidDatabaseConfig);
         }
 
+        // Validation du stockage objet (S3 impose des parts d'au moins 5 Mo)
+        if self.storage.backend == StorageBackend::S3
+            && (self.storage.s3.bucket.is_empty()
+                || self.storage.s3.access_key_id.is_empty()
+                || self.storage.s3.multipart_part_size < 5 * 1024 * 1024)
+        {
+            return Err(ConfigError::InvalidStorageConfig);
+        }
+
+        // Validation du live (un intervalle ICY nul n'insérerait que des métadonnées)
+        if self.live.icy_metaint == 0 {
+            return Err(ConfigError::InvalidLiveConfig);
+        }
+
         Ok(())
     }
 
@@ -546,4 +741,10 @@ pub enum ConfigError {
     
     #[error("Configuration de base de données invalide")]
     InvalidDatabaseConfig,
+    
+    #[error("Configuration de stockage invalide")]
+    InvalidStorageConfig,
+    
+    #[error("Configuration live invalide")]
+    InvalidLiveConfig,
 } 
\ No newline at end of file
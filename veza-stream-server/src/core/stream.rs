@@ -13,6 +13,10 @@ use tracing::{info, debug};
 
 use crate::core::AudioFormat;
 use crate::error::AppError;
+use crate::streaming::live_hls::{LiveHlsConfig, LiveHlsManager};
+
+/// Blocs de PCM live en attente par abonné avant qu'il ne décroche
+const LIVE_AUDIO_CHANNEL_SIZE: usize = 256;
 
 /// Gestionnaire principal des streams en production
 #[derive(Debug)]
@@ -27,6 +31,10 @@ pub struct StreamManager {
     _analytics: Arc<StreamAnalytics>,
     /// Événements globaux (nouveaux streams, fin, etc.)
     event_sender: broadcast::Sender<StreamEvent>,
+    /// Publication HLS live à fenêtre glissante
+    live_hls: Arc<LiveHlsManager>,
+    /// PCM poussé dans chaque stream live, pour les relais (Icecast...)
+    live_audio: Arc<DashMap<Uuid, broadcast::Sender<Arc<[f32]>>>>,
     /// Configuration globale
     config: Arc<RwLock<StreamConfig>>,
 }
@@ -183,6 +191,7 @@ pub struct StreamConfig {
     pub analytics_enabled: bool,
     pub recording_enabled: bool,
     pub transcoding_enabled: bool,
+    pub live_hls: LiveHlsConfig,
 }
 
 /// Analytics temps réel pour un stream
@@ -237,6 +246,7 @@ impl Default for StreamConfig {
             analytics_enabled: true,
             recording_enabled: false,
             transcoding_enabled: true,
+            live_hls: LiveHlsConfig::default(),
         }
     }
 }
@@ -245,6 +255,7 @@ impl StreamManager {
     /// Crée un nouveau gestionnaire de streams
     pub fn new(config: StreamConfig) -> Result<Self, AppError> {
         let (event_sender, _) = broadcast::channel(10_000);
+        let live_hls = Arc::new(LiveHlsManager::new(config.live_hls.clone()));
         
         Ok(Self {
             streams: Arc::new(DashMap::new()),
@@ -252,6 +263,8 @@ impl StreamManager {
             buffer_manager: Arc::new(crate::core::BufferManager::new()),
             _analytics: Arc::new(StreamAnalytics::default()),
             event_sender,
+            live_hls,
+            live_audio: Arc::new(DashMap::new()),
             config: Arc::new(RwLock::new(config)),
         })
     }
@@ -264,13 +277,12 @@ impl StreamManager {
         outputs: Vec<StreamOutput>,
         metadata: StreamMetadata,
     ) -> Result<Uuid, AppError> {
-        let config = self.config.read();
-        
-        // Vérifier les limites
-        if self.streams.len() >= config.max_concurrent_streams {
+        // Vérifier les limites (sans garder le verrou pendant les await)
+        let max_concurrent_streams = self.config.read().max_concurrent_streams;
+        if self.streams.len() >= max_concurrent_streams {
             return Err(AppError::LimitExceeded {
                 resource: "concurrent_streams".to_string(),
-                limit: config.max_concurrent_streams as u32,
+                limit: max_concurrent_streams as u32,
             });
         }
         
@@ -282,6 +294,12 @@ impl StreamManager {
         // Créer le buffer adaptatif
         let buffer = self.buffer_manager.create_buffer(stream_id).await?;
         
+        // Préparer les renditions HLS live (sources live avec sorties HLS)
+        self.live_hls.start(stream_id, &source, &outputs)?;
+        if matches!(source, StreamSource::Live { .. }) {
+            self.live_audio.insert(stream_id, broadcast::channel(LIVE_AUDIO_CHANNEL_SIZE).0);
+        }
+        
         let stream = LiveStream {
             id: stream_id,
             title: metadata.current_track.as_ref()
@@ -370,6 +388,12 @@ impl StreamManager {
         
         let duration = stream.started_at.elapsed();
         
+        if self.live_hls.is_live(stream_id) {
+            self.live_hls.finish(stream_id)?;
+        }
+        // Ferme les abonnements au PCM live
+        self.live_audio.remove(&stream_id);
+        
         // Émettre l'événement
         let _ = self.event_sender.send(StreamEvent::StreamEnded {
             stream_id,
@@ -380,6 +404,88 @@ impl StreamManager {
         Ok(())
     }
     
+    /// Pousse du PCM entrelacé f32, au format de la source, dans un stream live.
+    /// L'encodage HLS et l'écriture des segments tournent dans `spawn_blocking`.
+    pub async fn push_live_audio(&self, stream_id: Uuid, samples: &[f32]) -> Result<(), AppError> {
+        let mut stream = self.streams.get_mut(&stream_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("stream {}", stream_id) })?;
+        
+        if !matches!(stream.source, StreamSource::Live { .. }) {
+            return Err(AppError::StreamingError {
+                message: format!("Le stream {} n'a pas de source live", stream_id),
+            });
+        }
+        if stream.status == StreamStatus::Starting {
+            stream.status = StreamStatus::Live;
+        }
+        drop(stream);
+        
+        let samples: Arc<[f32]> = Arc::from(samples);
+        if self.live_hls.is_live(stream_id) {
+            let live_hls = self.live_hls.clone();
+            let pcm = samples.clone();
+            tokio::task::spawn_blocking(move || live_hls.push_pcm(stream_id, &pcm))
+                .await
+                .map_err(|e| AppError::InternalError { message: e.to_string() })??;
+        }
+        if let Some(sender) = self.live_audio.get(&stream_id) {
+            if sender.receiver_count() > 0 {
+                let _ = sender.send(samples);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Abonnement au PCM d'un stream live, au format `live_format` ; le canal
+    /// se ferme à la fin du stream
+    pub fn subscribe_live_audio(&self, stream_id: Uuid) -> Option<(AudioFormat, broadcast::Receiver<Arc<[f32]>>)> {
+        let receiver = self.live_audio.get(&stream_id)?.subscribe();
+        Some((self.live_format(stream_id)?, receiver))
+    }
+    
+    /// Listener connecté à un stream
+    pub fn listener(&self, stream_id: Uuid, listener_id: Uuid) -> Option<Listener> {
+        self.streams.get(&stream_id)?.listeners.get(&listener_id).map(|listener| listener.clone())
+    }
+    
+    /// Créateur d'un stream
+    pub fn creator_id(&self, stream_id: Uuid) -> Option<i64> {
+        self.streams.get(&stream_id).map(|stream| stream.creator_id)
+    }
+    
+    /// Titre d'un stream
+    pub fn stream_title(&self, stream_id: Uuid) -> Option<String> {
+        self.streams.get(&stream_id).map(|stream| stream.title.clone())
+    }
+    
+    /// Format du PCM attendu par `push_live_audio`, si le stream a une source live
+    pub fn live_format(&self, stream_id: Uuid) -> Option<AudioFormat> {
+        match &self.streams.get(&stream_id)?.source {
+            StreamSource::Live { format, .. } => Some(format.clone()),
+            _ => None,
+        }
+    }
+    
+    /// Piste en cours d'un stream
+    pub fn current_track(&self, stream_id: Uuid) -> Option<TrackInfo> {
+        self.streams.get(&stream_id)?.metadata.read().current_track.clone()
+    }
+    
+    /// Change la piste en cours (métadonnées envoyées par la source)
+    pub fn set_current_track(&self, stream_id: Uuid, track: TrackInfo) -> Result<(), AppError> {
+        let stream = self.streams.get(&stream_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("stream {}", stream_id) })?;
+        
+        debug!("Stream {} : piste « {} »", stream_id, track.title);
+        stream.metadata.write().current_track = Some(track);
+        Ok(())
+    }
+    
+    /// Gestionnaire HLS live, pour exposer les playlists
+    pub fn live_hls(&self) -> Arc<LiveHlsManager> {
+        self.live_hls.clone()
+    }
+    
     /// Obtient les statistiques globales
     pub fn get_global_stats(&self) -> HashMap<String, u64> {
         let mut stats = HashMap::new();
@@ -3,7 +3,7 @@
 /// Features :
 /// - Synchronisation précise <10ms entre clients
 /// - Compensation automatique du drift réseau
-/// - Support NTP pour horloge de référence
+/// - Protocole d'horloge intégré (échanges façon NTP sur WebSocket), sans NTP sortant
 /// - Synchronisation adaptative selon latence
 /// - Support paroles/sous-titres synchronisés
 
@@ -68,17 +68,12 @@ pub struct StreamSynchronizer {
     sync_buffer: Arc<RwLock<SyncBuffer>>,
 }
 
-/// Serveur de temps NTP-like
+/// Serveur de temps : horloge de référence des clients, qui s'y calent
+/// par échanges `ClockMessage::Ping` / `Pong`
 #[derive(Debug)]
 pub struct TimeServer {
-    /// Temps de référence (peut être NTP externe)
+    /// Temps de référence, avancé par l'horloge monotone
     reference_time: Arc<RwLock<ReferenceTime>>,
-    /// Clients NTP configurés
-    ntp_clients: Vec<String>,
-    /// Décalage mesuré avec les serveurs externes
-    time_offset: Arc<std::sync::atomic::AtomicI64>,
-    /// Qualité de la synchronisation
-    sync_quality: Arc<std::sync::atomic::AtomicU8>,
 }
 
 /// Temps de référence avec précision
@@ -86,7 +81,6 @@ pub struct TimeServer {
 pub struct ReferenceTime {
     pub system_time: SystemTime,
     pub monotonic_time: Instant,
-    pub ntp_offset: Duration,
     pub precision_microseconds: u32,
 }
 
@@ -97,20 +91,94 @@ pub struct DriftCompensator {
     drift_measurements: Arc<DashMap<Uuid, VecDeque<DriftMeasurement>>>,
     /// État des compensations par stream
     _compensations: Arc<DashMap<Uuid, DriftCompensation>>,
+    /// Dernière estimation d'horloge par client
+    estimates: Arc<DashMap<Uuid, ClockEstimate>>,
     /// Configuration
     config: DriftCompensatorConfig,
 }
 
-/// Mesure de drift pour un client
+/// Un échange est écarté si son aller-retour dépasse `RTT_OUTLIER_FACTOR`
+/// fois le meilleur de la fenêtre plus `RTT_OUTLIER_MARGIN_MS` : un
+/// aller-retour long est asymétrique et fausse le décalage
+const RTT_OUTLIER_FACTOR: f64 = 1.5;
+const RTT_OUTLIER_MARGIN_MS: f64 = 2.0;
+/// Écart au décalage médian, en écarts absolus médians, au-delà duquel un
+/// échange est écarté
+const OFFSET_OUTLIER_MADS: f64 = 3.0;
+const OFFSET_OUTLIER_MIN_MS: f64 = 1.0;
+
+/// Mesure de drift pour un client, issue d'un échange d'horloge
 #[derive(Debug, Clone)]
 pub struct DriftMeasurement {
     pub timestamp: Instant,
+    /// Heure client à la réception du ping (µs)
     pub client_reported_time: u64,
+    /// Heure serveur au milieu de l'échange (µs)
     pub server_time: u64,
     pub round_trip_time: Duration,
+    /// Décalage mesuré de l'horloge client sur l'horloge serveur
     pub drift_ms: f64,
 }
 
+/// Échange d'horloge complet, en µs : `t0` envoi du ping par le serveur,
+/// `t1` réception et `t2` envoi du pong par le client, `t3` réception du
+/// pong par le serveur
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ClockExchange {
+    pub t0: u64,
+    pub t1: u64,
+    pub t2: u64,
+    pub t3: u64,
+}
+
+impl ClockExchange {
+    /// Décalage de l'horloge client sur l'horloge serveur, en µs
+    pub fn offset_us(&self) -> i64 {
+        let (t0, t1, t2, t3) = (self.t0 as i128, self.t1 as i128, self.t2 as i128, self.t3 as i128);
+        (((t1 - t0) + (t2 - t3)) / 2) as i64
+    }
+
+    /// Aller-retour réseau, temps de traitement du client déduit.
+    /// `None` si les horodatages sont incohérents.
+    pub fn round_trip(&self) -> Option<Duration> {
+        let total = self.t3.checked_sub(self.t0)?;
+        let processing = self.t2.checked_sub(self.t1)?;
+        total.checked_sub(processing).map(Duration::from_micros)
+    }
+}
+
+/// Estimation de l'horloge d'un client sur ses derniers échanges
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ClockEstimate {
+    /// Décalage client - serveur à `measured_at_us`
+    pub offset_ms: f64,
+    pub round_trip_ms: f64,
+    /// Dérive de l'horloge client, en parties par million
+    pub drift_ppm: f64,
+    /// Écart-type des décalages retenus
+    pub jitter_ms: f64,
+    /// Échanges retenus après rejet des valeurs aberrantes
+    pub samples: usize,
+    /// Heure serveur du dernier échange retenu (µs)
+    pub measured_at_us: u64,
+}
+
+/// Messages JSON du protocole d'horloge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClockMessage {
+    /// Serveur → client, à l'ouverture
+    Hello { client_id: Uuid, server_time_us: u64 },
+    /// Serveur → client
+    Ping { sequence: u64, t0: u64 },
+    /// Client → serveur : heures locales de réception du ping et d'envoi
+    Pong { sequence: u64, t1: u64, t2: u64 },
+    /// Serveur → client après chaque échange accepté
+    Estimate { estimate: ClockEstimate },
+    /// Serveur → client : ajustement calculé à partir des estimations
+    Adjustment { adjustment: SyncAdjustment },
+}
+
 /// Compensation appliquée à un client
 #[derive(Debug, Clone)]
 pub struct DriftCompensation {
@@ -148,7 +216,12 @@ pub enum SyncState {
 /// Ajustement de synchronisation envoyé au client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncAdjustment {
+    /// Latence réseau estimée (aller simple)
     pub timestamp_offset: Duration,
+    /// Décalage de l'horloge client sur l'horloge serveur
+    pub clock_offset_ms: f64,
+    /// Dérive de l'horloge client, en parties par million
+    pub drift_ppm: f64,
     pub playback_rate: f64,
     pub buffer_target: usize,
     pub quality_switch: Option<String>,
@@ -263,8 +336,6 @@ pub struct TimedEvent {
 /// Configuration globale de synchronisation
 #[derive(Debug, Clone)]
 pub struct SyncConfig {
-    pub enable_ntp_sync: bool,
-    pub ntp_servers: Vec<String>,
     pub max_client_drift_ms: f64,
     pub sync_interval: Duration,
     pub drift_measurement_window: Duration,
@@ -311,7 +382,8 @@ pub struct SyncMetrics {
     pub average_sync_accuracy_ms: std::sync::atomic::AtomicU32,
     pub sync_failures_total: std::sync::atomic::AtomicU64,
     pub drift_corrections_total: std::sync::atomic::AtomicU64,
-    pub ntp_sync_errors: std::sync::atomic::AtomicU64,
+    /// Échanges d'horloge aux horodatages incohérents
+    pub clock_exchanges_rejected: std::sync::atomic::AtomicU64,
 }
 
 /// Événements de synchronisation
@@ -336,12 +408,6 @@ use std::collections::VecDeque;
 impl Default for SyncConfig {
     fn default() -> Self {
         Self {
-            enable_ntp_sync: true,
-            ntp_servers: vec![
-                "pool.ntp.org".to_string(),
-                "time.google.com".to_string(),
-                "time.cloudflare.com".to_string(),
-            ],
             max_client_drift_ms: 50.0,
             sync_interval: Duration::from_millis(1000),
             drift_measurement_window: Duration::from_secs(60),
@@ -356,7 +422,7 @@ impl SyncEngine {
     pub async fn new(config: SyncConfig) -> Result<Self, AppError> {
         let (event_sender, _) = broadcast::channel(10_000);
         
-        let time_server = Arc::new(TimeServer::new(config.ntp_servers.clone()).await?);
+        let time_server = Arc::new(TimeServer::new().await?);
         
         Ok(Self {
             time_server,
@@ -425,17 +491,25 @@ impl SyncEngine {
         listener: &Listener,
         master_time: MasterTime,
     ) -> Result<(), AppError> {
+        // Pas encore d'échange d'horloge : rien à ajuster
+        let Some(estimate) = self.drift_compensator.estimate(listener.id) else {
+            debug!("Listener {} sans échange d'horloge, synchronisation différée", listener.id);
+            return Ok(());
+        };
+
         // Mesurer la latence
         let latency = self.measure_latency(listener).await?;
         self.latency_map.insert(listener.id, latency);
         
-        // Calculer le drift
+        // Décalage d'horloge prédit à l'heure maître
         let drift = self.drift_compensator.calculate_drift(listener.id, master_time).await?;
         
         // Créer l'ajustement de synchronisation
         let adjustment = SyncAdjustment {
-            timestamp_offset: latency + Duration::from_millis(drift.abs() as u64),
-            playback_rate: self.calculate_playback_rate(drift),
+            timestamp_offset: latency,
+            clock_offset_ms: drift,
+            drift_ppm: estimate.drift_ppm,
+            playback_rate: self.calculate_playback_rate(estimate.drift_ppm),
             buffer_target: self.calculate_buffer_size(latency),
             quality_switch: self.determine_quality_switch(listener).await,
             sync_point: synchronizer.get_current_sync_point().await?,
@@ -444,13 +518,22 @@ impl SyncEngine {
         // Appliquer l'ajustement
         self.apply_sync_adjustment(listener.id, adjustment.clone()).await?;
         
+        // La précision est l'incertitude des mesures, pas le décalage (corrigé par le client)
+        let max_jitter_ms = self.config.read().max_client_drift_ms;
+        let sync_state = match estimate.jitter_ms <= max_jitter_ms {
+            true => SyncState::Synchronized { drift_ms: drift },
+            false => SyncState::Desynchronized {
+                reason: format!("gigue d'horloge de {:.1}ms", estimate.jitter_ms),
+            },
+        };
+
         // Mettre à jour le client synchronisé
         let sync_client = SynchronizedClient {
             listener_id: listener.id,
-            sync_state: SyncState::Synchronized { drift_ms: drift },
+            sync_state,
             last_sync_adjustment: Some(adjustment.clone()),
             sync_quality: SyncQuality {
-                accuracy_ms: drift.abs(),
+                accuracy_ms: estimate.jitter_ms,
                 stability_score: self.calculate_stability_score(listener.id).await,
                 last_drift: drift,
                 sync_loss_count: 0,
@@ -468,38 +551,26 @@ impl SyncEngine {
         // Émettre l'événement
         let _ = self.event_sender.send(SyncEvent::ClientSynchronized {
             client_id: listener.id,
-            accuracy_ms: drift.abs(),
+            accuracy_ms: estimate.jitter_ms,
         });
         
         debug!("Listener {} synchronisé avec drift: {:.2}ms", listener.id, drift);
         Ok(())
     }
     
-    /// Mesure la latence réseau avec un client
+    /// Latence réseau avec un client : moitié de l'aller-retour mesuré
     async fn measure_latency(&self, listener: &Listener) -> Result<Duration, AppError> {
-        // Simulation de mesure ping/pong
-        // En production, implémenter un vrai protocole de mesure
-        let base_latency = match listener.bandwidth_estimate {
-            0..=64_000 => Duration::from_millis(150),      // Mobile/slow
-            64_001..=256_000 => Duration::from_millis(50), // Standard
-            256_001..=1_000_000 => Duration::from_millis(20), // Fast
-            _ => Duration::from_millis(10),                // Fiber/local
-        };
-        
-        // Ajouter jitter simulé
-        let jitter = Duration::from_millis(rand::random::<u64>() % 10);
-        Ok(base_latency + jitter)
+        self.drift_compensator
+            .estimate(listener.id)
+            .map(|estimate| Duration::from_secs_f64(estimate.round_trip_ms / 2000.0))
+            .ok_or(AppError::NoSyncPoint)
     }
     
-    /// Calcule le taux de lecture pour corriger le drift
-    fn calculate_playback_rate(&self, drift_ms: f64) -> f64 {
-        const MAX_ADJUSTMENT: f64 = 0.005; // 0.5% max
-        
-        // Ajustement proportionnel au drift
-        let adjustment = (drift_ms / 1000.0) * 0.001; // 0.1% par seconde de drift
-        let clamped = adjustment.max(-MAX_ADJUSTMENT).min(MAX_ADJUSTMENT);
-        
-        1.0 + clamped
+    /// Calcule le taux de lecture compensant la dérive de l'horloge client :
+    /// un client dont l'horloge avance ralentit d'autant
+    fn calculate_playback_rate(&self, drift_ppm: f64) -> f64 {
+        let max_adjustment = self.drift_compensator.config.max_playback_rate_adjustment;
+        1.0 + (-drift_ppm / 1_000_000.0).clamp(-max_adjustment, max_adjustment)
     }
     
     /// Calcule la taille de buffer optimale selon la latence
@@ -527,10 +598,7 @@ impl SyncEngine {
     
     /// Applique un ajustement de synchronisation
     async fn apply_sync_adjustment(&self, client_id: Uuid, adjustment: SyncAdjustment) -> Result<(), AppError> {
-        // Envoyer l'ajustement au client via WebSocket
-        // TODO: Implémenter l'envoi réel via la connexion WebSocket
-        
-        // Émettre l'événement
+        // Émettre l'événement, relayé au client par sa WebSocket d'horloge
         let _ = self.event_sender.send(SyncEvent::SyncAdjustmentApplied {
             client_id,
             adjustment,
@@ -576,6 +644,51 @@ impl SyncEngine {
     pub fn subscribe_events(&self) -> broadcast::Receiver<SyncEvent> {
         self.event_sender.subscribe()
     }
+
+    /// Heure de référence (µs), pour horodater les échanges d'horloge
+    pub fn now_us(&self) -> Result<u64, AppError> {
+        self.time_server.now_us()
+    }
+
+    /// Intervalle entre deux échanges d'horloge
+    pub fn sync_interval(&self) -> Duration {
+        self.config.read().sync_interval
+    }
+
+    /// Enregistre un échange d'horloge et renvoie l'estimation à jour
+    pub fn record_clock_exchange(&self, client_id: Uuid, exchange: ClockExchange) -> Result<ClockEstimate, AppError> {
+        match self.drift_compensator.record_exchange(client_id, exchange) {
+            Ok(estimate) => {
+                self.latency_map.insert(client_id, Duration::from_secs_f64(estimate.round_trip_ms / 2000.0));
+                Ok(estimate)
+            }
+            Err(e) => {
+                self.metrics.clock_exchanges_rejected.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    /// Estimation d'horloge courante d'un client
+    pub fn clock_estimate(&self, client_id: Uuid) -> Option<ClockEstimate> {
+        self.drift_compensator.estimate(client_id)
+    }
+
+    /// Synchronise un seul listener d'un stream
+    pub async fn sync_listener(&self, stream_id: Uuid, listener: &Listener) -> Result<(), AppError> {
+        let synchronizer = self.get_or_create_synchronizer(stream_id).await?;
+        let master_time = self.time_server.get_master_time().await?;
+        self.sync_individual_listener(&synchronizer, listener, master_time).await
+    }
+
+    /// Oublie les mesures d'un client déconnecté
+    pub fn remove_client(&self, client_id: Uuid) {
+        self.drift_compensator.remove_client(client_id);
+        self.latency_map.remove(&client_id);
+        for synchronizer in self.stream_synchronizers.iter() {
+            synchronizer.synchronized_clients.remove(&client_id);
+        }
+    }
 }
 
 impl Clone for SyncEngine {
@@ -600,35 +713,33 @@ pub struct MasterTime {
 }
 
 impl TimeServer {
-    /// Crée un nouveau serveur de temps
-    pub async fn new(ntp_servers: Vec<String>) -> Result<Self, AppError> {
+    /// Crée un nouveau serveur de temps, référence pour tous les clients
+    pub async fn new() -> Result<Self, AppError> {
         let reference_time = ReferenceTime {
             system_time: SystemTime::now(),
             monotonic_time: Instant::now(),
-            ntp_offset: Duration::ZERO,
-            precision_microseconds: 1000, // 1ms precision par défaut
+            precision_microseconds: 1, // avancé par l'horloge monotone
         };
         
         Ok(Self {
             reference_time: Arc::new(RwLock::new(reference_time)),
-            ntp_clients: ntp_servers,
-            time_offset: Arc::new(std::sync::atomic::AtomicI64::new(0)),
-            sync_quality: Arc::new(std::sync::atomic::AtomicU8::new(50)),
         })
     }
     
-    /// Obtient le temps maître actuel
-    pub async fn get_master_time(&self) -> Result<MasterTime, AppError> {
+    /// Heure de référence en µs depuis l'epoch Unix, monotone
+    pub fn now_us(&self) -> Result<u64, AppError> {
         let ref_time = self.reference_time.read();
-        let elapsed = ref_time.monotonic_time.elapsed();
-        let timestamp = ref_time.system_time
+        let start = ref_time.system_time
             .duration_since(UNIX_EPOCH)
-            .map_err(|_| AppError::TimeSync)?
-            .as_micros() as u64 + elapsed.as_micros() as u64;
-        
+            .map_err(|_| AppError::TimeSync)?;
+        Ok((start + ref_time.monotonic_time.elapsed()).as_micros() as u64)
+    }
+    
+    /// Obtient le temps maître actuel
+    pub async fn get_master_time(&self) -> Result<MasterTime, AppError> {
         Ok(MasterTime {
-            timestamp,
-            precision_us: ref_time.precision_microseconds,
+            timestamp: self.now_us()?,
+            precision_us: self.reference_time.read().precision_microseconds,
         })
     }
 }
@@ -705,6 +816,7 @@ impl DriftCompensator {
         Self {
             drift_measurements: Arc::new(DashMap::new()),
             _compensations: Arc::new(DashMap::new()),
+            estimates: Arc::new(DashMap::new()),
             config: DriftCompensatorConfig {
                 measurement_window_size: 20,
                 min_measurements_for_compensation: 5,
@@ -714,10 +826,127 @@ impl DriftCompensator {
         }
     }
     
-    /// Calcule le drift pour un client
-    pub async fn calculate_drift(&self, _client_id: Uuid, _master_time: MasterTime) -> Result<f64, AppError> {
-        // Simulation de calcul de drift
-        // En production, utiliser les timestamps client/serveur
-        Ok(rand::random::<f64>() * 20.0 - 10.0) // -10 à +10ms
+    /// Décalage d'horloge prédit pour un client à l'heure maître, en ms
+    pub async fn calculate_drift(&self, client_id: Uuid, master_time: MasterTime) -> Result<f64, AppError> {
+        self.estimate(client_id)
+            .map(|estimate| estimate.offset_at(master_time.timestamp))
+            .ok_or(AppError::NoSyncPoint)
+    }
+
+    pub fn estimate(&self, client_id: Uuid) -> Option<ClockEstimate> {
+        self.estimates.get(&client_id).map(|estimate| estimate.clone())
+    }
+
+    pub fn remove_client(&self, client_id: Uuid) {
+        self.drift_measurements.remove(&client_id);
+        self.estimates.remove(&client_id);
+    }
+
+    /// Ajoute un échange à la fenêtre du client et recalcule son estimation
+    pub fn record_exchange(&self, client_id: Uuid, exchange: ClockExchange) -> Result<ClockEstimate, AppError> {
+        let round_trip = exchange.round_trip().ok_or_else(|| AppError::InvalidData {
+            message: "Horodatages d'échange d'horloge incohérents".to_string(),
+        })?;
+        let measurement = DriftMeasurement {
+            timestamp: Instant::now(),
+            client_reported_time: exchange.t1,
+            server_time: exchange.t0 + (exchange.t3 - exchange.t0) / 2,
+            round_trip_time: round_trip,
+            drift_ms: exchange.offset_us() as f64 / 1000.0,
+        };
+
+        let mut estimate = {
+            let mut measurements = self.drift_measurements.entry(client_id).or_default();
+            measurements.push_back(measurement);
+            while measurements.len() > self.config.measurement_window_size {
+                measurements.pop_front();
+            }
+            self.estimate_window(&measurements)
+        };
+
+        // Lissage de la dérive d'une estimation à l'autre
+        let min_samples = self.config.min_measurements_for_compensation;
+        if let Some(previous) = self.estimates.get(&client_id) {
+            if previous.samples >= min_samples && estimate.samples >= min_samples {
+                let smoothing = self.config.compensation_smoothing;
+                estimate.drift_ppm = smoothing * previous.drift_ppm + (1.0 - smoothing) * estimate.drift_ppm;
+            }
+        }
+        self.estimates.insert(client_id, estimate.clone());
+        Ok(estimate)
+    }
+
+    /// Estimation sur une fenêtre : rejet des échanges à aller-retour long
+    /// puis des décalages aberrants, et régression linéaire du décalage
+    /// dans le temps pour la dérive
+    fn estimate_window(&self, measurements: &VecDeque<DriftMeasurement>) -> ClockEstimate {
+        let rtt_ms = |m: &DriftMeasurement| m.round_trip_time.as_secs_f64() * 1000.0;
+        let best_rtt = measurements.iter().map(rtt_ms).fold(f64::INFINITY, f64::min);
+        let rtt_limit = best_rtt * RTT_OUTLIER_FACTOR + RTT_OUTLIER_MARGIN_MS;
+        let fast: Vec<&DriftMeasurement> = measurements.iter().filter(|m| rtt_ms(m) <= rtt_limit).collect();
+
+        let median_offset = median(fast.iter().map(|m| m.drift_ms).collect());
+        let mad = median(fast.iter().map(|m| (m.drift_ms - median_offset).abs()).collect());
+        let offset_limit = (mad * OFFSET_OUTLIER_MADS).max(OFFSET_OUTLIER_MIN_MS);
+        let retained: Vec<&DriftMeasurement> = fast
+            .into_iter()
+            .filter(|m| (m.drift_ms - median_offset).abs() <= offset_limit)
+            .collect();
+
+        let count = retained.len() as f64;
+        let measured_at_us = retained.iter().map(|m| m.server_time).max().unwrap_or_default();
+        // Temps en secondes relatif au dernier échange : décalage en ms, pente en ms/s
+        let times: Vec<f64> = retained
+            .iter()
+            .map(|m| (m.server_time as f64 - measured_at_us as f64) / 1_000_000.0)
+            .collect();
+        let mean_time = times.iter().sum::<f64>() / count;
+        let mean_offset = retained.iter().map(|m| m.drift_ms).sum::<f64>() / count;
+        let variance_time = times.iter().map(|t| (t - mean_time).powi(2)).sum::<f64>();
+        let slope = match retained.len() >= self.config.min_measurements_for_compensation && variance_time > 0.0 {
+            true => {
+                times.iter().zip(&retained).map(|(t, m)| (t - mean_time) * (m.drift_ms - mean_offset)).sum::<f64>()
+                    / variance_time
+            }
+            false => 0.0,
+        };
+        let offset_ms = mean_offset - slope * mean_time;
+        let jitter_ms = (times
+            .iter()
+            .zip(&retained)
+            .map(|(t, m)| (m.drift_ms - (offset_ms + slope * t)).powi(2))
+            .sum::<f64>()
+            / count)
+            .sqrt();
+
+        ClockEstimate {
+            offset_ms,
+            round_trip_ms: retained.iter().map(|m| rtt_ms(m)).sum::<f64>() / count,
+            // 1 ms/s = 1000 ppm
+            drift_ppm: slope * 1000.0,
+            jitter_ms,
+            samples: retained.len(),
+            measured_at_us,
+        }
+    }
+}
+
+impl ClockEstimate {
+    /// Décalage prédit à l'heure serveur `server_time_us`, dérive comprise
+    pub fn offset_at(&self, server_time_us: u64) -> f64 {
+        let elapsed_us = server_time_us as f64 - self.measured_at_us as f64;
+        self.offset_ms + self.drift_ppm * elapsed_us / 1_000_000_000.0
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    match values.len() % 2 {
+        0 => (values[middle - 1] + values[middle]) / 2.0,
+        _ => values[middle],
     }
 } 
\ No newline at end of file
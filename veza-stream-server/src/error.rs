@@ -50,6 +50,8 @@ pub enum AppError {
     ListenerLimitExceeded { current: u32, limit: u32 },
     UploadSessionNotFound { session_id: String },
     InvalidUploadState { current: String, expected: String },
+    UploadOffsetMismatch { expected: u64, got: u64 },
+    ChecksumMismatch { algorithm: String },
     TooManyActivePlayers { limit: u32 },
     PlayerNotFound { user_id: i64 },
     
@@ -116,6 +118,8 @@ impl fmt::Display for AppError {
             AppError::ListenerLimitExceeded { current, limit } => write!(f, "Listener limit exceeded: {} current, {} limit", current, limit),
             AppError::UploadSessionNotFound { session_id } => write!(f, "Upload session not found: {}", session_id),
             AppError::InvalidUploadState { current, expected } => write!(f, "Invalid upload state: current {} but expected {}", current, expected),
+            AppError::UploadOffsetMismatch { expected, got } => write!(f, "Upload offset mismatch: expected {} but got {}", expected, got),
+            AppError::ChecksumMismatch { algorithm } => write!(f, "Checksum mismatch ({})", algorithm),
             AppError::TooManyActivePlayers { limit } => write!(f, "Too many active players: limit {}", limit),
             AppError::PlayerNotFound { user_id } => write!(f, "Player not found: user_id {}", user_id),
             AppError::FileError { message } => write!(f, "File error: {}", message),
@@ -172,6 +176,9 @@ impl IntoResponse for AppError {
             AppError::ListenerLimitExceeded { current, limit } => (StatusCode::TOO_MANY_REQUESTS, format!("Listener limit exceeded: {} current, {} limit", current, limit)),
             AppError::UploadSessionNotFound { session_id } => (StatusCode::NOT_FOUND, format!("Upload session not found: {}", session_id)),
             AppError::InvalidUploadState { current, expected } => (StatusCode::BAD_REQUEST, format!("Invalid upload state: current {} but expected {}", current, expected)),
+            AppError::UploadOffsetMismatch { expected, got } => (StatusCode::CONFLICT, format!("Upload offset mismatch: expected {} but got {}", expected, got)),
+            // 460 Checksum Mismatch, défini par l'extension checksum de tus
+            AppError::ChecksumMismatch { algorithm } => (StatusCode::from_u16(460).unwrap_or(StatusCode::BAD_REQUEST), format!("Checksum mismatch ({})", algorithm)),
             AppError::TooManyActivePlayers { limit } => (StatusCode::BAD_REQUEST, format!("Too many active players: limit {}", limit)),
             AppError::PlayerNotFound { user_id } => (StatusCode::NOT_FOUND, format!("Player not found: user_id {}", user_id)),
             AppError::FileError { message } => (StatusCode::INTERNAL_SERVER_ERROR, message),
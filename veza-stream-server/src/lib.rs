@@ -36,9 +36,15 @@ use crate::{
     audio::{AudioProcessor, CompressionEngine},
     auth::AuthManager,
     cache::FileCache,
+    core::{StreamManager, SyncEngine},
     health::HealthMonitor,
     notifications::NotificationService,
-    streaming::{AdaptiveStreamingManager, WebSocketManager},
+    soundcloud::discovery::DiscoveryEngine,
+    soundcloud::upload::UploadManager,
+    streaming::{
+        ingest::LiveIngest, listening_party::ListeningPartyManager, webrtc_media::WebRtcMedia, ws_audio::WsAudioRelays,
+        AdaptiveStreamingManager, WebSocketManager,
+    },
     // utils::Metrics,
 };
 
@@ -60,6 +66,14 @@ pub struct AppState {
     pub compression_engine: Arc<CompressionEngine>,
     pub notification_service: Arc<NotificationService>,
     pub websocket_manager: Arc<WebSocketManager>,
+    pub stream_manager: Arc<StreamManager>,
+    pub upload_manager: Arc<UploadManager>,
+    pub discovery_engine: Arc<DiscoveryEngine>,
+    pub live_ingest: Arc<LiveIngest>,
+    pub webrtc_media: Arc<WebRtcMedia>,
+    pub ws_audio: Arc<WsAudioRelays>,
+    pub listening_parties: Arc<ListeningPartyManager>,
+    pub sync_engine: Arc<SyncEngine>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -845,8 +859,7 @@ mod integration_tests {
         let encoder_config = crate::codecs::mp3::Mp3EncoderConfig::default();
         let mut encoder = crate::codecs::mp3::Mp3EncoderImpl::new(encoder_config);
         
-        let decoder_config = crate::codecs::mp3::Mp3DecoderConfig::default();
-        let mut decoder = crate::codecs::mp3::Mp3DecoderImpl::new(decoder_config);
+        let mut decoder = crate::codecs::CodecFactory::create_decoder("mp3", crate::codecs::DecoderConfig::default()).unwrap();
         
         // Test encodage
         let test_samples = vec![0.1f32; 1152 * 2]; // Frame stéréo complète
@@ -7,15 +7,34 @@ use stream_server::{
         rate_limit::rate_limit_middleware,
         security::security_headers_middleware,
     },
+    soundcloud::discovery::{DiscoveryConfig, DiscoveryEngine},
+    soundcloud::social::{SocialConfig, SocialManager},
+    soundcloud::tus::tus_routes,
+    soundcloud::track_features::track_features_routes,
+    soundcloud::spectrogram::{spectrogram_routes, SpectrogramService},
+    streaming::{
+        adaptive::hls_routes,
+        clock_sync::clock_sync_routes,
+        icecast,
+        icy::{IcyConfig, IcyRelays},
+        ingest::live_ingest_routes,
+        listening_party::{listening_party_routes, ListeningPartyConfig, ListeningPartyManager},
+        live_hls::live_hls_routes,
+        rtmp,
+        sync_manager::{SyncConfig, SyncManager},
+        webrtc::{WebRTCConfig, WebRTCManager},
+        webrtc_media::{webrtc_media_routes, WebRtcMedia, WebRtcMediaConfig},
+        ws_audio::{ws_audio_routes, WsAudioConfig, WsAudioRelays},
+    },
     AppState,
 };
 use axum::{
-    http::{header, HeaderValue, Method},
+    http::{header, HeaderName, HeaderValue, Method},
     response::Json,
     routing::get,
     Router,
 };
-use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, path::Path, sync::Arc, time::Duration};
 use tokio::signal;
 use tower::ServiceBuilder;
 use tower_http::{
@@ -50,6 +69,28 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     // Démarrage des tâches de background
     start_background_tasks(&app_state).await;
     
+    // Serveur Icecast : sources live (BUTT, Mixxx, OBS...) et auditeurs ICY
+    if config.live.icecast_port != 0 {
+        let icecast_addr = SocketAddr::from(([0, 0, 0, 0], config.live.icecast_port));
+        let icecast_listener = tokio::net::TcpListener::bind(&icecast_addr).await
+            .map_err(|e| format!("Impossible de démarrer le serveur Icecast: {}", e))?;
+        let relays = Arc::new(IcyRelays::new(app_state.stream_manager.clone(), IcyConfig {
+            bitrate: config.live.icy_bitrate,
+            metaint: config.live.icy_metaint,
+        }));
+        info!("🎙️ Serveur Icecast sur {}", icecast_addr);
+        tokio::spawn(icecast::serve(icecast_listener, config.clone(), app_state.live_ingest.clone(), relays));
+    }
+    
+    // Serveur RTMP : encodeurs matériels, OBS, ffmpeg
+    if config.live.rtmp_port != 0 {
+        let rtmp_addr = SocketAddr::from(([0, 0, 0, 0], config.live.rtmp_port));
+        let rtmp_listener = tokio::net::TcpListener::bind(&rtmp_addr).await
+            .map_err(|e| format!("Impossible de démarrer le serveur RTMP: {}", e))?;
+        info!("📡 Serveur RTMP sur {}", rtmp_addr);
+        tokio::spawn(rtmp::serve(rtmp_listener, app_state.live_ingest.clone()));
+    }
+    
     // Création du routeur avec tous les middlewares
     let app = create_router(app_state);
     
@@ -94,9 +135,15 @@ async fn create_app_state(config: Arc<Config>) -> std::result::Result<AppState,
         audio::{compression::CompressionEngine, processing::AudioProcessor},
         auth::AuthManager,
         cache::FileCache,
+        core::{sync::SyncConfig as ClockSyncConfig, StreamConfig, StreamManager, SyncEngine},
         health::HealthMonitor,
         notifications::NotificationService,
-        streaming::{adaptive::AdaptiveStreamingManager, websocket::WebSocketManager},
+        soundcloud::{storage::create_storage, upload::{UploadConfig, UploadManager}},
+        streaming::{
+            adaptive::AdaptiveStreamingManager,
+            ingest::{LiveIngest, LiveIngestConfig},
+            websocket::WebSocketManager,
+        },
         utils::metrics::Metrics,
     };
     
@@ -140,6 +187,77 @@ async fn create_app_state(config: Arc<Config>) -> std::result::Result<AppState,
     // Création du gestionnaire WebSocket
     let websocket_manager = Arc::new(WebSocketManager::new());
     
+    // Création du gestionnaire de streams live
+    let mut stream_config = StreamConfig::default();
+    stream_config.live_hls.output_dir = Path::new(&config.compression.output_dir).join("live");
+    let stream_manager = Arc::new(
+        StreamManager::new(stream_config)
+            .map_err(|e| format!("Erreur streams live: {}", e))?,
+    );
+    
+    // Création du registre des sources live (clés de stream)
+    let live_ingest = Arc::new(LiveIngest::new(
+        stream_manager.clone(),
+        LiveIngestConfig::from_config(&config.live),
+    ));
+    
+    // Sessions WebRTC (WHIP/WHEP) sur un port UDP unique
+    let webrtc_media = Arc::new(
+        WebRtcMedia::new(
+            Arc::new(WebRTCManager::new(WebRTCConfig::default())),
+            live_ingest.clone(),
+            WebRtcMediaConfig::from_config(&config.live),
+        )
+        .await
+        .map_err(|e| format!("Erreur WebRTC: {}", e))?,
+    );
+    
+    // Relais fMP4 du protocole WebSocket binaire
+    let ws_audio = Arc::new(WsAudioRelays::new(
+        stream_manager.clone(),
+        WsAudioConfig::from_config(&config.live),
+    ));
+    
+    // Synchronisation d'horloge des clients, sans NTP sortant
+    let sync_engine = Arc::new(
+        SyncEngine::new(ClockSyncConfig::default())
+            .await
+            .map_err(|e| format!("Erreur synchronisation: {}", e))?,
+    );
+    
+    // Sessions d'écoute synchronisées sur l'horloge du SyncEngine
+    let listening_parties = Arc::new(ListeningPartyManager::new(
+        Arc::new(SyncManager::new(SyncConfig::default(), sync_engine.clone())),
+        ListeningPartyConfig::default(),
+    ));
+    listening_parties.clone().spawn_idle_cleanup();
+    
+    // Création du gestionnaire d'uploads (sessions tus persistées, stockage partagé)
+    let storage = create_storage(&config.storage)
+        .await
+        .map_err(|e| format!("Erreur stockage: {}", e))?;
+    let upload_manager = Arc::new(
+        UploadManager::with_storage(UploadConfig {
+            max_file_size: config.max_file_size,
+            upload_directory: Path::new(&config.audio_dir).join("uploads"),
+            temp_directory: Path::new(&config.compression.output_dir).join("uploads"),
+            ..UploadConfig::default()
+        }, storage)
+        .await
+        .map_err(|e| format!("Erreur uploads: {}", e))?,
+    );
+    
+    // Recommandations nourries des analyses musicales des uploads
+    let discovery_engine = Arc::new(
+        DiscoveryEngine::with_track_features(
+            DiscoveryConfig::default(),
+            Arc::new(SocialManager::new(SocialConfig::default())),
+            upload_manager.track_features(),
+        )
+        .await
+        .map_err(|e| format!("Erreur découverte: {}", e))?,
+    );
+    
     Ok(AppState {
         config,
         cache,
@@ -152,6 +270,14 @@ async fn create_app_state(config: Arc<Config>) -> std::result::Result<AppState,
         compression_engine,
         notification_service,
         websocket_manager,
+        stream_manager,
+        upload_manager,
+        discovery_engine,
+        live_ingest,
+        webrtc_media,
+        ws_audio,
+        listening_parties,
+        sync_engine,
     })
 }
 
@@ -176,19 +302,38 @@ async fn start_background_tasks(state: &AppState) {
     info!("✅ Tâches de background démarrées");
 }
 
+/// Méthodes autorisées en CORS (HEAD/PATCH/DELETE pour les uploads tus)
+fn cors_methods() -> [Method; 6] {
+    [Method::GET, Method::POST, Method::HEAD, Method::PATCH, Method::DELETE, Method::OPTIONS]
+}
+
+/// En-têtes lisibles par le navigateur : range pour le streaming, nom des
+/// téléchargements, tus pour les uploads
+fn cors_exposed_headers() -> [HeaderName; 11] {
+    [
+        header::CONTENT_RANGE,
+        header::CONTENT_LENGTH,
+        header::ACCEPT_RANGES,
+        header::CONTENT_DISPOSITION,
+        header::LOCATION,
+        HeaderName::from_static("upload-offset"),
+        HeaderName::from_static("upload-length"),
+        HeaderName::from_static("upload-metadata"),
+        HeaderName::from_static("tus-resumable"),
+        HeaderName::from_static("tus-version"),
+        HeaderName::from_static("tus-max-size"),
+    ]
+}
+
 fn create_router(state: AppState) -> Router {
     // Configuration CORS
     let cors = if state.config.allowed_origins.contains(&"*".to_string()) {
         warn!("⚠️  CORS configuré pour toutes les origines - non recommandé en production");
         CorsLayer::new()
             .allow_origin(Any)
-            .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+            .allow_methods(cors_methods())
             .allow_headers(Any)
-            .expose_headers([
-                header::CONTENT_RANGE,
-                header::CONTENT_LENGTH,
-                header::ACCEPT_RANGES,
-            ])
+            .expose_headers(cors_exposed_headers())
     } else {
         let origins: std::result::Result<Vec<_>, _> = state
             .config
@@ -200,13 +345,18 @@ fn create_router(state: AppState) -> Router {
         match origins {
             Ok(origins) => {
                 let mut cors_layer = CorsLayer::new()
-                    .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-                    .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, header::RANGE])
-                    .expose_headers([
-                        header::CONTENT_RANGE,
-                        header::CONTENT_LENGTH,
-                        header::ACCEPT_RANGES,
-                    ]);
+                    .allow_methods(cors_methods())
+                    .allow_headers([
+                        header::AUTHORIZATION,
+                        header::CONTENT_TYPE,
+                        header::RANGE,
+                        HeaderName::from_static("tus-resumable"),
+                        HeaderName::from_static("upload-length"),
+                        HeaderName::from_static("upload-offset"),
+                        HeaderName::from_static("upload-metadata"),
+                        HeaderName::from_static("upload-checksum"),
+                    ])
+                    .expose_headers(cors_exposed_headers());
                 
                 for origin in origins {
                     cors_layer = cors_layer.allow_origin(AllowOrigin::exact(origin));
@@ -246,6 +396,17 @@ fn create_router(state: AppState) -> Router {
         .route("/health/detailed", get(detailed_health_check))
         .route("/metrics", get(metrics_endpoint))
         .route("/stream/:filename", get(stream_audio))
+        .route("/files/:user_id/:filename", get(stream_stored_file))
+        .nest("/hls", hls_routes(state.adaptive_streaming.clone()))
+        .nest("/live", live_hls_routes(state.config.clone(), state.stream_manager.live_hls()))
+        .nest("/uploads/tus", tus_routes(state.upload_manager.clone(), state.auth_manager.clone()))
+        .nest("/ingest", live_ingest_routes(state.live_ingest.clone(), state.auth_manager.clone()))
+        .nest("/webrtc", webrtc_media_routes(state.config.clone(), state.webrtc_media.clone()))
+        .nest("/ws/audio", ws_audio_routes(state.config.clone(), state.ws_audio.clone()))
+        .nest("/parties", listening_party_routes(state.listening_parties.clone(), state.auth_manager.clone()))
+        .nest("/sync", clock_sync_routes(state.sync_engine.clone(), state.stream_manager.clone()))
+        .nest("/tracks/features", track_features_routes(state.upload_manager.track_features()))
+        .nest("/spectrogram", spectrogram_routes(Arc::new(SpectrogramService::new(state.config.clone()))))
         .layer(middleware_stack)
         .with_state(state)
 }
@@ -341,8 +502,12 @@ async fn stream_audio(
     let file_path = build_safe_path(&state.config, &format!("{}.mp3", validated_filename))
         .map_err(|_| (axum::http::StatusCode::NOT_FOUND, "File not found".to_string()))?;
     
+    let metadata = state.cache.get_or_load(&file_path)
+        .await
+        .map_err(|_| (axum::http::StatusCode::NOT_FOUND, "File not found".to_string()))?;
+    
     // Streaming du fichier
-    serve_partial_file(&state.config, file_path, headers)
+    serve_partial_file(&state.config, file_path, &metadata, headers)
         .await
         .map_err(|e| match e {
             AppError::FileNotFound => (axum::http::StatusCode::NOT_FOUND, "File not found".to_string()),
@@ -350,3 +515,77 @@ async fn stream_audio(
             _ => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Internal error".to_string()),
         })
 }
+
+/// Sert un fichier du backend de stockage (local ou S3), avec la même URL
+/// signée que `/stream` portant sur `<user_id>/<fichier>`
+async fn stream_stored_file(
+    axum::extract::Path((user_id, filename)): axum::extract::Path<(i64, String)>,
+    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
+    axum::extract::State(state): axum::extract::State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> std::result::Result<axum::response::Response, (axum::http::StatusCode, String)> {
+    use stream_server::{
+        error::AppError,
+        utils::{attachment_disposition, validate_filename, serve_stored_file, serve_tagged_file, validate_signature},
+    };
+    
+    let (Some(expires), Some(sig)) = (params.get("expires"), params.get("sig")) else {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "Missing expires or signature parameter".to_string(),
+        ));
+    };
+    
+    let validated_filename = validate_filename(&filename)
+        .map_err(|_| (axum::http::StatusCode::BAD_REQUEST, "Invalid filename".to_string()))?;
+    let file_id = format!("{}/{}", user_id, validated_filename);
+    
+    if !validate_signature(&state.config, &file_id, expires, sig) {
+        return Err((
+            axum::http::StatusCode::FORBIDDEN,
+            "Invalid signature".to_string(),
+        ));
+    }
+    
+    let storage = state.upload_manager.storage();
+    let file = storage.get_file(&file_id)
+        .await
+        .map_err(|_| (axum::http::StatusCode::NOT_FOUND, "File not found".to_string()))?;
+    
+    // `?download` : fichier en pièce jointe, avec les métadonnées courantes de
+    // la piste écrites dans ses tags (les fichiers sont nommés `<session>.<ext>`)
+    let download = params.contains_key("download");
+    let track_tags = match std::path::Path::new(&validated_filename).file_stem() {
+        Some(stem) if download => match uuid::Uuid::parse_str(&stem.to_string_lossy()) {
+            Ok(session_id) => state.upload_manager.track_tags(session_id).await,
+            Err(_) => None,
+        },
+        _ => None,
+    };
+    
+    let served = match &track_tags {
+        Some(tags) => serve_tagged_file(&state.config, storage.as_ref(), &file, tags, headers).await,
+        None => serve_stored_file(&state.config, storage.as_ref(), &file, headers).await,
+    };
+    let mut response = served.map_err(|e| match e {
+        AppError::FileNotFound | AppError::NotFound { .. } => (axum::http::StatusCode::NOT_FOUND, "File not found".to_string()),
+        AppError::InvalidRange => (axum::http::StatusCode::RANGE_NOT_SATISFIABLE, "Invalid range".to_string()),
+        _ => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Internal error".to_string()),
+    })?;
+    
+    if download {
+        let extension = std::path::Path::new(&validated_filename)
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        let name = match track_tags.as_ref().map(|tags| (tags.artist(), tags.title.clone())) {
+            Some((Some(artist), Some(title))) => format!("{} - {}{}", artist, title, extension),
+            Some((None, Some(title))) => format!("{}{}", title, extension),
+            _ => file.original_filename.clone(),
+        };
+        if let Ok(value) = attachment_disposition(&name).parse() {
+            response.headers_mut().insert(axum::http::header::CONTENT_DISPOSITION, value);
+        }
+    }
+    Ok(response)
+}
@@ -8,12 +8,20 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-use crate::{AppState, AppError, Result};
+use crate::{
+    cache::FileCache,
+    config::Config,
+    utils::{build_safe_path, serve_partial_file, validate_filename, validate_signature},
+    AppState, AppError, Result,
+};
 
+/// Paramètres d'un lien de streaming signé (`expires`/`sig` acceptés comme alias)
 #[derive(Deserialize)]
 pub struct StreamRequest {
     pub file: String,
+    #[serde(alias = "sig")]
     pub signature: String,
+    #[serde(alias = "expires")]
     pub timestamp: i64,
 }
 
@@ -40,21 +48,34 @@ async fn health_check() -> impl IntoResponse {
 
 async fn stream_file_handler(
     Query(params): Query<StreamRequest>,
-    _headers: HeaderMap,
-    State(_state): State<AppState>,
+    headers: HeaderMap,
+    State(state): State<AppState>,
+) -> Result<Response> {
+    stream_file(&state.config, &state.cache, params, headers).await
+}
+
+/// Sert un fichier de `audio_dir` désigné par un lien signé, par plages
+async fn stream_file(
+    config: &Config,
+    cache: &FileCache,
+    params: StreamRequest,
+    headers: HeaderMap,
 ) -> Result<Response> {
     // Validation basique
     if params.file.is_empty() {
         return Err(AppError::ValidationError("File parameter is required".to_string()));
     }
 
-    // Pour les tests, on retourne une réponse simple
-    let response = StreamResponse {
-        success: true,
-        message: format!("Stream request for file: {}", params.file),
-    };
+    let filename = validate_filename(&params.file)?;
+
+    if !validate_signature(config, &filename, &params.timestamp.to_string(), &params.signature) {
+        return Err(AppError::Forbidden);
+    }
 
-    Ok(Json(response).into_response())
+    let file_path = build_safe_path(config, &filename)?;
+    let metadata = cache.get_or_load(&file_path).await?;
+
+    serve_partial_file(config, file_path, &metadata, headers).await
 }
 
 async fn file_metadata_handler(
@@ -78,4 +99,72 @@ async fn file_metadata_handler(
 async fn websocket_handler() -> Result<Response> {
     // Handler WebSocket basique pour les tests
     Ok((StatusCode::SWITCHING_PROTOCOLS, "WebSocket upgrade").into_response())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::signed_query;
+    use axum::{body::Body, http::{header, Request}};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn stream_routes(config: Arc<Config>, cache: Arc<FileCache>) -> Router {
+        Router::new().route("/stream", get(move |Query(params): Query<StreamRequest>, headers: HeaderMap| async move {
+            stream_file(&config, &cache, params, headers).await
+        }))
+    }
+
+    async fn get_stream(app: &Router, query: &str, headers: &[(header::HeaderName, &str)]) -> Response {
+        let mut request = Request::get(format!("/stream?{}", query));
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn body_bytes(response: Response) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_stream_serves_ranges_and_honours_if_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.path().join("track.mp3"), &data).unwrap();
+        let mut config = Config::from_env().unwrap();
+        config.audio_dir = dir.path().to_string_lossy().to_string();
+        let config = Arc::new(config);
+        let app = stream_routes(config.clone(), Arc::new(FileCache::new(Duration::from_secs(60), 16)));
+        let query = format!("file=track.mp3&{}", signed_query(&config, "track.mp3"));
+
+        let unsigned = get_stream(&app, "file=track.mp3&expires=1&sig=00", &[]).await;
+        assert_eq!(unsigned.status(), StatusCode::FORBIDDEN);
+
+        let full = get_stream(&app, &query, &[]).await;
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(full.headers()[header::ACCEPT_RANGES], "bytes");
+        let etag = full.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert_eq!(body_bytes(full).await, data);
+
+        let partial = get_stream(&app, &query, &[(header::RANGE, "bytes=100-199")]).await;
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(partial.headers()[header::CONTENT_RANGE], "bytes 100-199/1000");
+        assert_eq!(body_bytes(partial).await, data[100..200]);
+
+        let unsatisfiable = get_stream(&app, &query, &[(header::RANGE, "bytes=5000-")]).await;
+        assert_eq!(unsatisfiable.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(unsatisfiable.headers()[header::CONTENT_RANGE], "bytes */1000");
+
+        // If-Range : la plage n'est servie que si l'ETag correspond encore
+        let matching = get_stream(&app, &query, &[(header::RANGE, "bytes=-100"), (header::IF_RANGE, &etag)]).await;
+        assert_eq!(matching.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body_bytes(matching).await, data[900..]);
+
+        let stale = get_stream(&app, &query, &[(header::RANGE, "bytes=-100"), (header::IF_RANGE, "\"stale\"")]).await;
+        assert_eq!(stale.status(), StatusCode::OK);
+        assert!(stale.headers().get(header::CONTENT_RANGE).is_none());
+        assert_eq!(body_bytes(stale).await, data);
+    }
+}
@@ -0,0 +1,254 @@
+//! Registre d'identification du contenu par empreinte acoustique
+//!
+//! Chaque piste uploadée est empreintée puis comparée au registre avant d'y
+//! être ajoutée. Les pistes retirées suite à une demande DMCA restent
+//! indexées avec l'identifiant du retrait : une nouvelle mise en ligne du même
+//! enregistrement, même partielle ou réencodée, est signalée comme telle.
+//! Le registre est un index partagé (`SharedIndex`) : objet du bucket quand le
+//! stockage est commun aux nœuds, fichier local sinon. Un retrait prononcé sur
+//! un nœud bloque donc les nouvelles mises en ligne sur tous les autres.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::fingerprint::{AudioFingerprint, FingerprintIndex, FingerprintMatch};
+use crate::error::AppError;
+use crate::soundcloud::storage::{IndexStore, SharedIndex};
+
+/// Similarité à partir de laquelle deux enregistrements sont considérés identiques
+pub const MATCH_SIMILARITY_THRESHOLD: f32 = 0.75;
+
+/// Nom du registre dans les messages d'erreur
+const REGISTRY_NAME: &str = "Registre d'empreintes";
+
+/// Correspondance trouvée dans le registre
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentMatch {
+    #[serde(flatten)]
+    pub fingerprint: FingerprintMatch,
+    /// Demande de retrait DMCA visant la piste correspondante
+    pub takedown_id: Option<u64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(from = "RegistryFile", into = "RegistryFile")]
+struct RegistryState {
+    index: FingerprintIndex,
+    /// Piste retirée → demande de retrait
+    takedowns: HashMap<String, u64>,
+}
+
+/// Forme persistée du registre, l'index inversé étant reconstruit au chargement
+#[derive(Serialize, Deserialize)]
+struct RegistryFile {
+    fingerprints: Vec<(String, AudioFingerprint)>,
+    takedowns: HashMap<String, u64>,
+}
+
+impl From<RegistryFile> for RegistryState {
+    fn from(file: RegistryFile) -> Self {
+        let mut index = FingerprintIndex::default();
+        for (track_id, fingerprint) in file.fingerprints {
+            index.insert(track_id, fingerprint);
+        }
+        Self { index, takedowns: file.takedowns }
+    }
+}
+
+impl From<RegistryState> for RegistryFile {
+    fn from(state: RegistryState) -> Self {
+        Self {
+            fingerprints: state.index.iter()
+                .map(|(track_id, fingerprint)| (track_id.to_string(), fingerprint.clone()))
+                .collect(),
+            takedowns: state.takedowns,
+        }
+    }
+}
+
+pub struct ContentIdRegistry {
+    state: SharedIndex<RegistryState>,
+}
+
+impl fmt::Debug for ContentIdRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.read(|state| {
+            f.debug_struct("ContentIdRegistry")
+                .field("tracks", &state.index.len())
+                .field("takedowns", &state.takedowns.len())
+                .finish()
+        })
+    }
+}
+
+impl ContentIdRegistry {
+    /// Registre non persisté
+    pub fn in_memory() -> Self {
+        Self { state: SharedIndex::in_memory(REGISTRY_NAME) }
+    }
+
+    /// Ouvre le registre stocké dans `store`, en le chargeant s'il existe
+    pub async fn open(store: Arc<dyn IndexStore>) -> Result<Self, AppError> {
+        Ok(Self { state: SharedIndex::open(store, REGISTRY_NAME).await? })
+    }
+
+    /// Recharge le registre s'il a été modifié par un autre nœud ; les
+    /// lectures qui suivent voient ses empreintes et ses retraits
+    pub async fn refresh(&self) -> Result<(), AppError> {
+        self.state.refresh().await
+    }
+
+    /// Nombre de pistes empreintées
+    pub fn len(&self) -> usize {
+        self.state.read(|state| state.index.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn fingerprint(&self, track_id: &str) -> Option<AudioFingerprint> {
+        self.state.read(|state| state.index.get(track_id).cloned())
+    }
+
+    /// Demande de retrait visant une piste
+    pub fn takedown_of(&self, track_id: &str) -> Option<u64> {
+        self.state.read(|state| state.takedowns.get(track_id).copied())
+    }
+
+    /// Pistes du registre contenant tout ou partie de `fingerprint`
+    pub fn find_matches(&self, fingerprint: &AudioFingerprint, min_similarity: f32) -> Vec<ContentMatch> {
+        self.state.read(|state| Self::annotate(state, state.index.search(fingerprint, min_similarity)))
+    }
+
+    /// Autres pistes du registre correspondant à une piste déjà empreintée
+    pub fn matches_for_track(&self, track_id: &str, min_similarity: f32) -> Vec<ContentMatch> {
+        self.state.read(|state| {
+            let Some(fingerprint) = state.index.get(track_id) else {
+                return Vec::new();
+            };
+            let matches = state.index.search(fingerprint, min_similarity)
+                .into_iter()
+                .filter(|m| m.track_id != track_id)
+                .collect();
+            Self::annotate(state, matches)
+        })
+    }
+
+    /// Recherche les correspondances d'une piste puis l'ajoute au registre
+    pub async fn register(
+        &self,
+        track_id: String,
+        fingerprint: AudioFingerprint,
+        min_similarity: f32,
+    ) -> Result<Vec<ContentMatch>, AppError> {
+        let matches = self.state.update(|state| {
+            let matches = state.index.search(&fingerprint, min_similarity)
+                .into_iter()
+                .filter(|m| m.track_id != track_id)
+                .collect();
+            state.index.insert(track_id.clone(), fingerprint.clone());
+            Some(Self::annotate(state, matches))
+        }).await?;
+        Ok(matches.unwrap_or_default())
+    }
+
+    /// Retire une piste du registre ; l'empreinte d'une piste retirée par
+    /// DMCA est conservée pour détecter ses nouvelles mises en ligne
+    pub async fn remove(&self, track_id: &str) -> Result<bool, AppError> {
+        let removed = self.state.update(|state| {
+            (!state.takedowns.contains_key(track_id) && state.index.remove(track_id).is_some()).then_some(())
+        }).await?;
+        Ok(removed.is_some())
+    }
+
+    /// Marque une piste comme retirée ; faux si elle n'a pas d'empreinte
+    pub async fn mark_taken_down(&self, track_id: &str, takedown_id: u64) -> Result<bool, AppError> {
+        let marked = self.state.update(|state| {
+            state.index.get(track_id)?;
+            state.takedowns.insert(track_id.to_string(), takedown_id);
+            Some(())
+        }).await?;
+        Ok(marked.is_some())
+    }
+
+    /// Lève le retrait d'une piste (contre-notification acceptée, appel gagné)
+    pub async fn lift_takedown(&self, track_id: &str) -> Result<(), AppError> {
+        self.state.update(|state| state.takedowns.remove(track_id).map(|_| ())).await?;
+        Ok(())
+    }
+
+    fn annotate(state: &RegistryState, matches: Vec<FingerprintMatch>) -> Vec<ContentMatch> {
+        matches
+            .into_iter()
+            .map(|fingerprint| ContentMatch {
+                takedown_id: state.takedowns.get(&fingerprint.track_id).copied(),
+                fingerprint,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soundcloud::storage::LocalIndexFile;
+
+    fn fingerprint(seed: u32, items: usize) -> AudioFingerprint {
+        let mut state = seed;
+        AudioFingerprint {
+            sub_fingerprints: (0..items)
+                .map(|_| {
+                    state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                    state
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reupload_of_taken_down_track_is_reported() {
+        let dir = std::env::temp_dir().join(format!("content_id_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("fingerprints.json");
+
+        let registry = ContentIdRegistry::open(Arc::new(LocalIndexFile::new(path.clone()))).await.unwrap();
+        assert!(registry.register("1".to_string(), fingerprint(1, 400), MATCH_SIMILARITY_THRESHOLD).await.unwrap().is_empty());
+        assert!(registry.mark_taken_down("1", 42).await.unwrap());
+        assert!(!registry.remove("1").await.unwrap());
+
+        // Le registre survit à un redémarrage
+        let registry = ContentIdRegistry::open(Arc::new(LocalIndexFile::new(path))).await.unwrap();
+        let excerpt = AudioFingerprint { sub_fingerprints: fingerprint(1, 400).sub_fingerprints[100..200].to_vec() };
+        let matches = registry.register("2".to_string(), excerpt, MATCH_SIMILARITY_THRESHOLD).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].fingerprint.track_id, "1");
+        assert_eq!(matches[0].takedown_id, Some(42));
+        assert_eq!(registry.matches_for_track("1", MATCH_SIMILARITY_THRESHOLD)[0].fingerprint.track_id, "2");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_takedown_on_one_node_is_seen_by_the_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn IndexStore> = Arc::new(LocalIndexFile::new(dir.path().join("fingerprints.json")));
+        let node_a = ContentIdRegistry::open(store.clone()).await.unwrap();
+        let node_b = ContentIdRegistry::open(store).await.unwrap();
+
+        node_a.register("1".to_string(), fingerprint(1, 400), MATCH_SIMILARITY_THRESHOLD).await.unwrap();
+        assert!(node_a.mark_taken_down("1", 42).await.unwrap());
+
+        // B n'a rien relu : sa mise à jour part de la version écrite par A
+        let matches = node_b.register("2".to_string(), fingerprint(1, 400), MATCH_SIMILARITY_THRESHOLD).await.unwrap();
+        assert_eq!(matches[0].takedown_id, Some(42));
+        assert_eq!(node_b.len(), 2);
+
+        node_b.lift_takedown("1").await.unwrap();
+        node_a.refresh().await.unwrap();
+        assert_eq!(node_a.takedown_of("1"), None);
+        assert_eq!(node_a.len(), 2);
+    }
+}
@@ -0,0 +1,481 @@
+//! Stockage adressé par contenu, dédupliqué par SHA-256
+//!
+//! `ContentAddressedStorage` enveloppe un autre `FileStorage` : chaque contenu
+//! n'y est écrit qu'une fois, sous `blobs/<aa>/<sha256>/<upload>`, et les
+//! fichiers des utilisateurs ne sont que des références comptées vers ces
+//! blobs. L'index des références vit à côté des blobs (`FileStorage::shared_index` :
+//! objet du bucket S3, ou fichier local pour un nœud unique) et n'est modifié
+//! que par compare-and-swap sur sa version : tous les nœuds qui partagent le stockage
+//! partagent aussi les comptes de références.
+//!
+//! Un blob sans référence n'est pas supprimé tout de suite : il devient
+//! orphelin et n'est collecté qu'après un délai de grâce. La collecte le retire
+//! d'abord de l'index, ce qui fait échouer tout rattachement concurrent basé
+//! sur l'ancienne version, puis efface l'objet. Chaque upload écrit son blob
+//! sous une clé qui lui est propre : un nouvel upload du même contenu ne peut
+//! donc jamais être effacé par la collecte d'un ancien blob.
+//!
+//! Le rattachement anticipé (`link_duplicate`, sur la foi d'un SHA-256 déclaré
+//! et du seul début du fichier) est réservé aux blobs déjà référencés par le
+//! même utilisateur : sans cela, connaître l'empreinte d'un fichier suffirait à
+//! en obtenir une copie. Entre utilisateurs, la déduplication n'a lieu qu'au
+//! stockage, une fois le contenu entier reçu et haché côté serveur.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::soundcloud::storage::{IndexStore, SharedIndex};
+use crate::soundcloud::upload::{
+    dedup_probe_len, file_head_sha256, file_sha256, FileStorage, StorageStream, StoredFile, UploadSession,
+};
+
+/// Blob partagé et les fichiers qui y font référence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Blob {
+    /// Fichier tel qu'enregistré par le backend sous-jacent
+    stored: StoredFile,
+    /// SHA-256 des `DEDUP_PROBE_BYTES` premiers octets
+    head_digest: String,
+    refs: BTreeSet<String>,
+    /// Date à laquelle la dernière référence a disparu
+    orphaned_at: Option<SystemTime>,
+}
+
+/// Fichier d'un utilisateur, référence vers un blob
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileRef {
+    checksum: String,
+    user_id: i64,
+    original_filename: String,
+    content_type: String,
+    created_at: SystemTime,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BlobIndex {
+    blobs: HashMap<String, Blob>,
+    files: HashMap<String, FileRef>,
+    /// Blobs collectés dont l'objet reste à effacer du backend
+    #[serde(default)]
+    garbage: BTreeSet<String>,
+}
+
+/// Statistiques de déduplication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupStats {
+    pub blobs: usize,
+    pub files: usize,
+    pub orphaned_blobs: usize,
+    /// Octets réellement stockés
+    pub stored_bytes: u64,
+    /// Octets qu'occuperaient les fichiers sans déduplication
+    pub logical_bytes: u64,
+}
+
+#[derive(Debug)]
+pub struct ContentAddressedStorage {
+    inner: Arc<dyn FileStorage + Send + Sync>,
+    /// Validité des URLs présignées renvoyées dans `StoredFile::cdn_url`
+    presign_expiry: Duration,
+    index: SharedIndex<BlobIndex>,
+}
+
+impl ContentAddressedStorage {
+    /// Ouvre le stockage dédupliqué et charge l'index partagé s'il existe
+    pub async fn open(
+        inner: Arc<dyn FileStorage + Send + Sync>,
+        index_store: Arc<dyn IndexStore>,
+        presign_expiry: Duration,
+    ) -> Result<Self, AppError> {
+        Ok(Self {
+            inner,
+            presign_expiry,
+            index: SharedIndex::open(index_store, "Index de déduplication").await?,
+        })
+    }
+
+    /// Collecte périodiquement les blobs orphelins depuis plus de `grace`
+    pub fn spawn_garbage_collector(self: Arc<Self>, grace: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(grace.clamp(Duration::from_secs(60), Duration::from_secs(3600)));
+            loop {
+                interval.tick().await;
+                match self.collect_garbage(grace).await {
+                    Ok(0) => {}
+                    Ok(collected) => info!("{} blobs orphelins supprimés", collected),
+                    Err(e) => error!("Erreur collecte des blobs orphelins: {:?}", e),
+                }
+            }
+        });
+    }
+
+    /// Supprime les blobs sans référence depuis plus de `grace` ; renvoie leur nombre
+    pub async fn collect_garbage(&self, grace: Duration) -> Result<usize, AppError> {
+        let now = SystemTime::now();
+        // Retirés de l'index avant d'être effacés : un rattachement concurrent
+        // échoue sur la version et ne peut plus viser ces blobs
+        let collected = self.index.update(|index| {
+            let expired: Vec<String> = index.blobs
+                .iter()
+                .filter(|(_, blob)| blob.refs.is_empty())
+                .filter(|(_, blob)| blob.orphaned_at.is_some_and(|at| now.duration_since(at).unwrap_or_default() >= grace))
+                .map(|(checksum, _)| checksum.clone())
+                .collect();
+            if expired.is_empty() && index.garbage.is_empty() {
+                return None;
+            }
+            for checksum in &expired {
+                if let Some(blob) = index.blobs.remove(checksum) {
+                    index.garbage.insert(blob.stored.id);
+                }
+            }
+            Some((expired.len(), index.garbage.clone()))
+        }).await?;
+        let Some((collected, garbage)) = collected else {
+            return Ok(0);
+        };
+
+        let mut deleted = BTreeSet::new();
+        for blob_id in garbage {
+            match self.inner.delete_file(&blob_id).await {
+                Ok(()) => {
+                    deleted.insert(blob_id);
+                }
+                Err(e) => warn!("Blob {} non supprimé, nouvel essai à la prochaine collecte: {:?}", blob_id, e),
+            }
+        }
+        if !deleted.is_empty() {
+            self.index.update(|index| {
+                index.garbage.retain(|blob_id| !deleted.contains(blob_id));
+                Some(())
+            }).await?;
+        }
+        Ok(collected)
+    }
+
+    /// Statistiques d'après la dernière version de l'index connue de ce nœud
+    pub fn stats(&self) -> DedupStats {
+        self.index.read(|index| DedupStats {
+            blobs: index.blobs.len(),
+            files: index.files.len(),
+            orphaned_blobs: index.blobs.values().filter(|blob| blob.refs.is_empty()).count(),
+            stored_bytes: index.blobs.values().map(|blob| blob.stored.size).sum(),
+            logical_bytes: index.blobs.values().map(|blob| blob.stored.size * blob.refs.len() as u64).sum(),
+        })
+    }
+
+    /// Identifiant d'un nouveau blob dans le backend sous-jacent, propre à l'upload
+    fn blob_id(checksum: &str) -> String {
+        format!("blobs/{}/{}/{}", &checksum[..2], checksum, Uuid::new_v4().simple())
+    }
+
+    /// Ajoute une référence ; le blob doit exister dans l'index
+    fn add_ref(index: &mut BlobIndex, checksum: &str, file_id: &str, session: &UploadSession) {
+        Self::remove_ref(index, file_id);
+        if let Some(blob) = index.blobs.get_mut(checksum) {
+            blob.refs.insert(file_id.to_string());
+            blob.orphaned_at = None;
+        }
+        index.files.insert(file_id.to_string(), FileRef {
+            checksum: checksum.to_string(),
+            user_id: session.user_id,
+            original_filename: session.filename.clone(),
+            content_type: session.content_type.clone(),
+            created_at: SystemTime::now(),
+        });
+    }
+
+    /// Retire une référence ; le blob devenu orphelin attend la collecte
+    fn remove_ref(index: &mut BlobIndex, file_id: &str) -> bool {
+        let Some(file) = index.files.remove(file_id) else {
+            return false;
+        };
+        if let Some(blob) = index.blobs.get_mut(&file.checksum) {
+            blob.refs.remove(file_id);
+            if blob.refs.is_empty() {
+                blob.orphaned_at = Some(SystemTime::now());
+            }
+        }
+        true
+    }
+
+    /// Vue `StoredFile` d'une référence
+    fn file_record(&self, index: &BlobIndex, file_id: &str) -> Option<StoredFile> {
+        let file = index.files.get(file_id)?;
+        let blob = index.blobs.get(&file.checksum)?;
+        Some(StoredFile {
+            id: file_id.to_string(),
+            original_filename: file.original_filename.clone(),
+            content_type: file.content_type.clone(),
+            size: blob.stored.size,
+            storage_path: blob.stored.storage_path.clone(),
+            public_url: blob.stored.public_url.clone(),
+            cdn_url: self.inner.presigned_url(&blob.stored.id, self.presign_expiry),
+            checksum: file.checksum.clone(),
+            created_at: file.created_at,
+        })
+    }
+
+    /// Blob d'une référence d'après l'index en cache
+    fn cached_blob_of(&self, file_id: &str) -> Option<String> {
+        self.index.read(|index| {
+            index.files.get(file_id)
+                .and_then(|file| index.blobs.get(&file.checksum))
+                .map(|blob| blob.stored.id.clone())
+        })
+    }
+
+    /// Blob d'une référence ; l'index n'est rechargé que si le cache l'ignore
+    /// (un blob reste lisible pendant le délai de grâce après son dernier retrait)
+    async fn blob_of(&self, file_id: &str) -> Result<String, AppError> {
+        if let Some(blob_id) = self.cached_blob_of(file_id) {
+            return Ok(blob_id);
+        }
+        self.index.refresh().await?;
+        self.cached_blob_of(file_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("File not found: {}", file_id) })
+    }
+}
+
+#[async_trait::async_trait]
+impl FileStorage for ContentAddressedStorage {
+    async fn store_file(&self, file_path: &Path, file_id: &str, session: &UploadSession) -> Result<StoredFile, AppError> {
+        let inconsistent = || AppError::StorageError { message: format!("Référence {} incohérente", file_id) };
+        let checksum = file_sha256(file_path).await?;
+
+        // Contenu déjà stocké (et pas encore collecté) : une simple référence
+        let linked = self.index.update(|index| {
+            index.blobs.contains_key(&checksum).then(|| {
+                Self::add_ref(index, &checksum, file_id, session);
+                self.file_record(index, file_id)
+            })
+        }).await?;
+        if let Some(record) = linked {
+            info!("Contenu {} déjà stocké, {} n'est qu'une référence", checksum, file_id);
+            return record.ok_or_else(inconsistent);
+        }
+
+        let size = fs::metadata(file_path).await?.len();
+        let head_digest = file_head_sha256(file_path, dedup_probe_len(size)).await?;
+        let stored = self.inner.store_file(file_path, &Self::blob_id(&checksum), session).await?;
+        let (inserted, record) = self.index.update(|index| {
+            let inserted = !index.blobs.contains_key(&checksum);
+            if inserted {
+                index.blobs.insert(checksum.clone(), Blob {
+                    stored: stored.clone(),
+                    head_digest: head_digest.clone(),
+                    refs: BTreeSet::new(),
+                    orphaned_at: None,
+                });
+            }
+            Self::add_ref(index, &checksum, file_id, session);
+            Some((inserted, self.file_record(index, file_id)))
+        }).await?.ok_or_else(inconsistent)?;
+
+        // Même contenu stocké par un autre upload entre-temps : notre copie est superflue
+        if !inserted {
+            if let Err(e) = self.inner.delete_file(&stored.id).await {
+                warn!("Copie superflue {} non supprimée: {:?}", stored.id, e);
+            }
+        }
+        record.ok_or_else(inconsistent)
+    }
+
+    async fn get_file(&self, file_id: &str) -> Result<StoredFile, AppError> {
+        self.index.refresh().await?;
+        self.index.read(|index| self.file_record(index, file_id))
+            .ok_or_else(|| AppError::NotFound { resource: format!("File not found: {}", file_id) })
+    }
+
+    /// Retire la référence ; le blob n'est effacé que par la collecte des orphelins
+    async fn delete_file(&self, file_id: &str) -> Result<(), AppError> {
+        self.index.update(|index| Self::remove_ref(index, file_id).then_some(())).await?;
+        Ok(())
+    }
+
+    async fn list_user_files(&self, user_id: i64) -> Result<Vec<StoredFile>, AppError> {
+        self.index.refresh().await?;
+        let mut files: Vec<StoredFile> = self.index.read(|index| {
+            index.files
+                .iter()
+                .filter(|(_, file)| file.user_id == user_id)
+                .filter_map(|(file_id, _)| self.file_record(index, file_id))
+                .collect()
+        });
+        files.sort_by_key(|f| f.created_at);
+        Ok(files)
+    }
+
+    async fn read_range(&self, file_id: &str, start: u64, length: u64) -> Result<StorageStream, AppError> {
+        let blob_id = self.blob_of(file_id).await?;
+        self.inner.read_range(&blob_id, start, length).await
+    }
+
+    fn presigned_url(&self, file_id: &str, expires_in: Duration) -> Option<String> {
+        let blob_id = self.cached_blob_of(file_id)?;
+        self.inner.presigned_url(&blob_id, expires_in)
+    }
+
+    fn shared_index(&self, name: &str) -> Option<Arc<dyn IndexStore>> {
+        self.inner.shared_index(name)
+    }
+
+    async fn link_duplicate(
+        &self,
+        checksum: &str,
+        size: u64,
+        head_digest: &str,
+        file_id: &str,
+        session: &UploadSession,
+    ) -> Result<Option<StoredFile>, AppError> {
+        let record = self.index.update(|index| {
+            let matches = index.blobs.get(checksum).is_some_and(|blob| {
+                blob.stored.size == size
+                    && blob.head_digest == head_digest
+                    && blob.refs.iter().any(|file| index.files.get(file).is_some_and(|file| file.user_id == session.user_id))
+            });
+            if !matches {
+                return None;
+            }
+            Self::add_ref(index, checksum, file_id, session);
+            Some(self.file_record(index, file_id))
+        }).await?;
+        Ok(record.flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soundcloud::storage::LocalIndexFile;
+    use crate::soundcloud::upload::{LocalFileStorage, UploadConfig, UploadManager, DECLARED_SHA256_KEY};
+    use futures::StreamExt;
+    use sha2::Digest;
+    use uuid::Uuid;
+
+    async fn open_storage(dir: &Path) -> Arc<ContentAddressedStorage> {
+        let inner = Arc::new(LocalFileStorage::new(dir.join("files"), "http://localhost/files".to_string()));
+        let index = Arc::new(LocalIndexFile::new(dir.join("index.json")));
+        Arc::new(ContentAddressedStorage::open(inner, index, Duration::from_secs(60)).await.unwrap())
+    }
+
+    async fn upload(manager: &UploadManager, user_id: i64, data: &[u8], declared: Option<&str>) -> (Uuid, u64) {
+        let metadata = declared
+            .map(|checksum| HashMap::from([(DECLARED_SHA256_KEY.to_string(), checksum.to_string())]))
+            .unwrap_or_default();
+        let session_id = manager
+            .create_upload(user_id, "master.wav".to_string(), data.len() as u64, "audio/wav".to_string(), metadata)
+            .await
+            .unwrap();
+        let first_chunk = futures::stream::iter(vec![Ok::<_, std::io::Error>(bytes::Bytes::copy_from_slice(&data[..1024 * 1024]))]);
+        (session_id, manager.append_upload(session_id, 0, first_chunk, None).await.unwrap())
+    }
+
+    async fn manager(dir: &Path, storage: Arc<ContentAddressedStorage>) -> UploadManager {
+        let config = UploadConfig {
+            upload_directory: dir.join("uploads"),
+            temp_directory: dir.join("temp"),
+            ..UploadConfig::default()
+        };
+        UploadManager::with_storage(config, storage).await.unwrap()
+    }
+
+    async fn session(manager: &UploadManager, user_id: i64, size: usize) -> UploadSession {
+        let session_id = manager
+            .create_upload(user_id, "master.wav".to_string(), size as u64, "audio/wav".to_string(), HashMap::new())
+            .await
+            .unwrap();
+        manager.get_upload_status(session_id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_identical_files_share_one_blob_until_collected() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_storage(dir.path()).await;
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let path = dir.path().join("master.wav");
+        std::fs::write(&path, &data).unwrap();
+
+        let manager = manager(dir.path(), storage.clone()).await;
+        let first = storage.store_file(&path, "1/a.wav", &session(&manager, 1, data.len()).await).await.unwrap();
+        let second = storage.store_file(&path, "2/b.wav", &session(&manager, 2, data.len()).await).await.unwrap();
+        assert_eq!(first.checksum, hex::encode(sha2::Sha256::digest(&data)));
+        assert_eq!(first.storage_path, second.storage_path);
+        let stats = storage.stats();
+        assert_eq!((stats.blobs, stats.files), (1, 2));
+        assert_eq!(stats.logical_bytes, 2 * stats.stored_bytes);
+
+        // Une référence supprimée ne touche pas au blob encore référencé
+        storage.delete_file("1/a.wav").await.unwrap();
+        assert_eq!(storage.collect_garbage(Duration::ZERO).await.unwrap(), 0);
+        let range = storage.read_range("2/b.wav", 1000, 10).await.unwrap()
+            .map(|chunk| chunk.unwrap().to_vec()).concat().await;
+        assert_eq!(range, data[1000..1010]);
+        assert!(storage.get_file("1/a.wav").await.is_err());
+
+        // L'index survit à un redémarrage
+        let reopened = open_storage(dir.path()).await;
+        assert_eq!(reopened.list_user_files(2).await.unwrap().len(), 1);
+
+        // Orphelin : conservé pendant le délai de grâce, puis collecté
+        reopened.delete_file("2/b.wav").await.unwrap();
+        assert_eq!(reopened.collect_garbage(Duration::from_secs(3600)).await.unwrap(), 0);
+        assert_eq!(reopened.collect_garbage(Duration::ZERO).await.unwrap(), 1);
+        assert_eq!(reopened.stats().blobs, 0);
+        assert!(!Path::new(&first.storage_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_upload_short_circuits_after_first_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_storage(dir.path()).await;
+        let manager = manager(dir.path(), storage.clone()).await;
+        let data: Vec<u8> = (0..3 * 1024 * 1024u32).map(|i| (i % 253) as u8).collect();
+        let checksum = hex::encode(sha2::Sha256::digest(&data));
+        let path = dir.path().join("existing.wav");
+        std::fs::write(&path, &data).unwrap();
+        storage.store_file(&path, "1/existing.wav", &session(&manager, 1, data.len()).await).await.unwrap();
+
+        // Même contenu déclaré par son propriétaire : terminé dès le premier chunk, sans nouveau blob
+        let (session_id, offset) = upload(&manager, 1, &data, Some(&checksum)).await;
+        assert_eq!(offset, data.len() as u64);
+        let linked = manager.get_upload_status(session_id).await.unwrap().stored_file.unwrap();
+        assert_eq!(linked.checksum, checksum);
+        assert_eq!(storage.stats().blobs, 1);
+        assert_eq!(storage.stats().files, 2);
+
+        // Empreinte déclarée mais début différent : l'upload continue normalement
+        let mut other = data.clone();
+        other[10] ^= 0xff;
+        let (_, offset) = upload(&manager, 1, &other, Some(&checksum)).await;
+        assert_eq!(offset, 1024 * 1024);
+        assert_eq!(storage.stats().files, 2);
+    }
+
+    #[tokio::test]
+    async fn test_other_user_cannot_claim_blob_from_declared_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open_storage(dir.path()).await;
+        let manager = manager(dir.path(), storage.clone()).await;
+        let data: Vec<u8> = (0..3 * 1024 * 1024u32).map(|i| (i % 241) as u8).collect();
+        let checksum = hex::encode(sha2::Sha256::digest(&data));
+        let path = dir.path().join("existing.wav");
+        std::fs::write(&path, &data).unwrap();
+        storage.store_file(&path, "1/existing.wav", &session(&manager, 1, data.len()).await).await.unwrap();
+
+        // L'utilisateur 2 connaît l'empreinte et le début du fichier de l'utilisateur 1 : refusé
+        let (session_id, offset) = upload(&manager, 2, &data, Some(&checksum)).await;
+        assert_eq!(offset, 1024 * 1024);
+        assert!(manager.get_upload_status(session_id).await.unwrap().stored_file.is_none());
+        assert_eq!(storage.stats().files, 1);
+        assert!(storage.list_user_files(2).await.unwrap().is_empty());
+    }
+}
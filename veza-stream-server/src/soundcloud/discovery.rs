@@ -8,18 +8,27 @@
 /// - Découverte personnalisée
 /// - Analytics d'engagement
 
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::collections::{HashMap, VecDeque};
 use std::time::{Duration, SystemTime};
 
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use parking_lot::Mutex;
-use tracing::{info};
+use tracing::{info, warn};
 
+use crate::audio::analysis::{KeyMode, TrackAnalysis};
 use crate::error::AppError;
 use crate::soundcloud::social::SocialManager;
+use crate::soundcloud::track_features::TrackFeatureStore;
+
+/// Écoutes conservées dans le profil d'un utilisateur
+const MAX_LISTENING_HISTORY: usize = 500;
+/// Poids d'une nouvelle écoute dans les préférences de tempo
+const TEMPO_LEARNING_RATE: f32 = 0.2;
+/// Part d'écoute à partir de laquelle une piste compte comme appréciée
+const MIN_LISTEN_COMPLETION: f32 = 0.5;
 
 /// Gestionnaire principal de la découverte
 #[derive(Debug)]
@@ -125,6 +134,45 @@ pub struct TempoPreferences {
     pub valence: f32,      // 0.0 - 1.0 (positivity)
 }
 
+impl TempoPreferences {
+    /// Préférences apprises d'au moins une écoute
+    pub fn is_learned(&self) -> bool {
+        self.preferred_bpm_range.1 > 0.0
+    }
+
+    /// Rapproche les préférences d'une piste écoutée (moyenne glissante)
+    pub fn learn(&mut self, features: &AudioFeatures) {
+        let rate = if self.is_learned() { TEMPO_LEARNING_RATE } else { 1.0 };
+        if features.tempo > 0.0 {
+            let (low, high) = self.preferred_bpm_range;
+            self.preferred_bpm_range = if self.is_learned() {
+                (low + (features.tempo * 0.9 - low) * rate, high + (features.tempo * 1.1 - high) * rate)
+            } else {
+                (features.tempo * 0.9, features.tempo * 1.1)
+            };
+        }
+        self.energy_level += (features.energy - self.energy_level) * rate;
+        self.danceability += (features.danceability - self.danceability) * rate;
+    }
+
+    /// Adéquation (0.0 - 1.0) d'une piste aux préférences : tempo dans la
+    /// plage (au demi ou double près), énergie et dansabilité proches
+    pub fn fit(&self, features: &AudioFeatures) -> f32 {
+        let (low, high) = self.preferred_bpm_range;
+        let tempo = if features.tempo > 0.0 {
+            [features.tempo, features.tempo * 2.0, features.tempo / 2.0]
+                .iter()
+                .map(|bpm| if *bpm < low { bpm / low } else if *bpm > high { high / bpm } else { 1.0 })
+                .fold(0.0f32, f32::max)
+        } else {
+            0.5
+        };
+        let energy = 1.0 - (features.energy - self.energy_level).abs();
+        let danceability = 1.0 - (features.danceability - self.danceability).abs();
+        (0.5 * tempo + 0.3 * energy + 0.2 * danceability).clamp(0.0, 1.0)
+    }
+}
+
 /// Préférences de découverte
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveryPreferences {
@@ -268,7 +316,11 @@ pub struct ContentBasedModel {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AudioFeatures {
     pub tempo: f32,
+    /// Classe de hauteur de la tonique (0 = Do), -1 si inconnue
     pub key: i8,
+    /// 1 majeur, 0 mineur
+    #[serde(default)]
+    pub mode: u8,
     pub energy: f32,
     pub danceability: f32,
     pub valence: f32,
@@ -280,6 +332,79 @@ pub struct AudioFeatures {
     pub time_signature: u8,
 }
 
+impl From<&TrackAnalysis> for AudioFeatures {
+    fn from(analysis: &TrackAnalysis) -> Self {
+        Self {
+            tempo: analysis.bpm.unwrap_or(0.0),
+            key: analysis.key.map_or(-1, |key| key.tonic as i8),
+            mode: analysis.key.map_or(0, |key| u8::from(key.mode == KeyMode::Major)),
+            energy: analysis.energy,
+            danceability: analysis.danceability,
+            loudness: analysis.loudness_db,
+            duration_ms: (analysis.duration_secs * 1000.0) as u32,
+            ..Self::default()
+        }
+    }
+}
+
+impl AudioFeatures {
+    /// Proximité musicale (0.0 - 1.0) : tempo au demi/double près, tonalité
+    /// sur le cycle des quintes, énergie et dansabilité
+    pub fn similarity(&self, other: &AudioFeatures) -> f32 {
+        let tempo = if self.tempo > 0.0 && other.tempo > 0.0 {
+            let octaves = (self.tempo / other.tempo).log2();
+            let closeness = (1.0 - (octaves - octaves.round()).abs() / 0.15).max(0.0);
+            if octaves.round() == 0.0 { closeness } else { closeness * 0.5 }
+        } else {
+            0.5
+        };
+        let key = if self.key >= 0 && other.key >= 0 {
+            // Relative mineure ramenée à sa majeure, puis distance en quintes
+            let major = |f: &AudioFeatures| (i32::from(f.key) + if f.mode == 1 { 0 } else { 3 }).rem_euclid(12);
+            let fifths = ((major(self) - major(other)) * 7).rem_euclid(12);
+            1.0 - fifths.min(12 - fifths) as f32 / 6.0
+        } else {
+            0.5
+        };
+        let energy = 1.0 - (self.energy - other.energy).abs();
+        let danceability = 1.0 - (self.danceability - other.danceability).abs();
+        (0.35 * tempo + 0.25 * key + 0.25 * energy + 0.15 * danceability).clamp(0.0, 1.0)
+    }
+}
+
+impl ContentBasedModel {
+    pub fn set_track_features(&mut self, track_id: Uuid, features: AudioFeatures) {
+        self.track_features.insert(track_id, features);
+    }
+
+    pub fn track_features(&self, track_id: &Uuid) -> Option<&AudioFeatures> {
+        self.track_features.get(track_id)
+    }
+
+    pub fn remove_track_features(&mut self, track_id: &Uuid) {
+        self.track_features.remove(track_id);
+    }
+
+    /// Pistes les plus proches en moyenne des pistes de référence
+    pub fn similar_tracks(&self, seeds: &[Uuid], count: usize) -> Vec<(Uuid, f32)> {
+        let references: Vec<&AudioFeatures> = seeds.iter().filter_map(|id| self.track_features.get(id)).collect();
+        if references.is_empty() {
+            return Vec::new();
+        }
+        let mut scored: Vec<(Uuid, f32)> = self.track_features
+            .iter()
+            .filter(|(id, _)| !seeds.contains(id))
+            .map(|(id, features)| {
+                let score = references.iter().map(|seed| seed.similarity(features)).sum::<f32>() / references.len() as f32;
+                (*id, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scored.truncate(count);
+        scored
+    }
+}
+
 /// Modèle hybride combinant collaborative et content-based
 #[derive(Debug, Default)]
 pub struct HybridModel {
@@ -719,6 +844,67 @@ impl DiscoveryEngine {
         })
     }
     
+    /// Crée un moteur de découverte alimenté par les analyses des uploads :
+    /// celles déjà stockées sont chargées, les suivantes reprises au fil de l'eau
+    pub async fn with_track_features(
+        config: DiscoveryConfig,
+        social_manager: Arc<SocialManager>,
+        track_features: Arc<TrackFeatureStore>,
+    ) -> Result<Self, AppError> {
+        let engine = Self::new(config, social_manager).await?;
+        // Abonnement avant le chargement : aucune analyse n'est perdue entre les deux
+        let mut updates = track_features.subscribe();
+        let content_model = engine.recommendation_engine.ml_models.content_model.clone();
+        Self::load_track_features(&content_model, &track_features);
+        
+        let store: Weak<TrackFeatureStore> = Arc::downgrade(&track_features);
+        tokio::spawn(async move {
+            loop {
+                match updates.recv().await {
+                    Ok((track_id, Some(analysis))) => {
+                        content_model.lock().set_track_features(track_id, AudioFeatures::from(&analysis));
+                    }
+                    Ok((track_id, None)) => content_model.lock().remove_track_features(&track_id),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Découverte en retard de {} analyses, rechargement complet", skipped);
+                        let Some(store) = store.upgrade() else { break };
+                        Self::load_track_features(&content_model, &store);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        
+        Ok(engine)
+    }
+    
+    fn load_track_features(content_model: &Mutex<ContentBasedModel>, track_features: &TrackFeatureStore) {
+        let mut model = content_model.lock();
+        for (track_id, analysis) in track_features.all() {
+            model.set_track_features(track_id, AudioFeatures::from(&analysis));
+        }
+        info!("{} analyses de pistes chargées pour la découverte", track_features.len());
+    }
+    
+    /// Enregistre une écoute ; une piste analysée écoutée jusqu'au bout (ou
+    /// presque) affine les préférences de tempo de l'utilisateur
+    pub async fn record_listening_event(&self, user_id: i64, event: ListeningEvent) {
+        let features = (!event.skipped && event.completion_percentage >= MIN_LISTEN_COMPLETION)
+            .then(|| self.recommendation_engine.ml_models.content_model.lock().track_features(&event.track_id).cloned())
+            .flatten();
+        
+        let mut profiles = self.recommendation_engine.user_listening_history.write().await;
+        let profile = profiles.entry(user_id).or_insert_with(|| UserListeningProfile::new(user_id));
+        if let Some(features) = features {
+            profile.tempo_preferences.learn(&features);
+        }
+        profile.listening_history.push_back(event);
+        while profile.listening_history.len() > MAX_LISTENING_HISTORY {
+            profile.listening_history.pop_front();
+        }
+        profile.last_updated = SystemTime::now();
+    }
+    
     /// Obtient des recommandations personnalisées pour un utilisateur
     pub async fn get_personalized_recommendations(
         &self,
@@ -789,6 +975,12 @@ impl DiscoveryEngine {
         self.charts_manager.get_chart(chart_type, period, limit).await
     }
     
+    /// Enregistre les caractéristiques audio mesurées d'une piste
+    pub fn update_track_features(&self, track_id: Uuid, analysis: &TrackAnalysis) {
+        self.recommendation_engine.ml_models.content_model.lock()
+            .set_track_features(track_id, AudioFeatures::from(analysis));
+    }
+    
     /// Crée une station radio personnalisée
     pub async fn create_radio_station(
         &self,
@@ -867,6 +1059,16 @@ impl DiscoveryEngine {
             _ => {}
         }
         
+        // Pistes analysées : rapprochées des préférences de tempo apprises
+        if user_profile.tempo_preferences.is_learned() {
+            let fit = self.recommendation_engine.ml_models.content_model.lock()
+                .track_features(&recommendation.track_id)
+                .map(|features| user_profile.tempo_preferences.fit(features));
+            if let Some(fit) = fit {
+                score *= 0.5 + 0.5 * fit;
+            }
+        }
+        
         score.clamp(0.0, 1.0)
     }
     
@@ -993,8 +1195,25 @@ impl RecommendationEngine {
         &self,
         _user_id: i64,
         count: usize,
-        _seed_tracks: Option<Vec<Uuid>>,
+        seed_tracks: Option<Vec<Uuid>>,
     ) -> Result<Vec<RecommendationResult>, AppError> {
+        // Pistes les plus proches des pistes de référence analysées
+        if let Some(seeds) = seed_tracks {
+            let similar = self.ml_models.content_model.lock().similar_tracks(&seeds, count);
+            if !similar.is_empty() {
+                return Ok(similar
+                    .into_iter()
+                    .map(|(track_id, score)| RecommendationResult {
+                        track_id,
+                        confidence_score: score,
+                        reason: RecommendationReason::SimilarToLiked,
+                        algorithm_used: "content_based".to_string(),
+                        metadata: None,
+                    })
+                    .collect());
+            }
+        }
+        
         // Simulation content-based filtering
         let mut recommendations = Vec::new();
         
@@ -1198,4 +1417,114 @@ impl EngagementTracker {
         let mut recommendation_metrics = self.recommendation_metrics.write().await;
         recommendation_metrics.insert(metrics.recommendation_id, metrics);
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis(bpm: f32, key: &str, energy: f32) -> TrackAnalysis {
+        TrackAnalysis {
+            bpm: Some(bpm),
+            bpm_confidence: 0.8,
+            key: Some(key.parse().unwrap()),
+            key_confidence: 0.7,
+            energy,
+            danceability: energy,
+            loudness_db: -12.0,
+            spectral_centroid_hz: 1500.0,
+            spectral_flatness: 0.1,
+            onset_rate: 2.0,
+            duration_secs: 180.0,
+        }
+    }
+
+    fn listen(track_id: Uuid, completion_percentage: f32, skipped: bool) -> ListeningEvent {
+        ListeningEvent {
+            track_id,
+            listened_at: SystemTime::now(),
+            duration_listened: Duration::from_secs(120),
+            completion_percentage,
+            source: ListeningSource::Recommendation,
+            skipped,
+            liked: false,
+            reposted: false,
+            shared: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recommendations_from_seed_tracks_use_upload_analyses() {
+        let directory = std::env::temp_dir().join(format!("discovery_{}", Uuid::new_v4()));
+        let store = Arc::new(TrackFeatureStore::open(directory.clone()).await.unwrap());
+        let (seed, near, close, mid, far) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        store.save(seed, analysis(124.0, "8A", 0.8)).await.unwrap();
+        store.save(near, analysis(125.0, "8A", 0.8)).await.unwrap();
+
+        // Analyses stockées chargées au démarrage, les suivantes reçues en continu
+        let social = Arc::new(SocialManager::new(crate::soundcloud::social::SocialConfig::default()));
+        let engine = DiscoveryEngine::with_track_features(DiscoveryConfig::default(), social, store.clone()).await.unwrap();
+        store.save(close, analysis(123.0, "9A", 0.75)).await.unwrap();
+        store.save(mid, analysis(100.0, "3B", 0.5)).await.unwrap();
+        store.save(far, analysis(87.0, "2B", 0.1)).await.unwrap();
+        let content_model = engine.recommendation_engine.ml_models.content_model.clone();
+        for _ in 0..100 {
+            if content_model.lock().track_features(&far).is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // 10 recommandations : 3 emplacements content-based, les plus proches de la référence
+        let recommendations = engine.get_personalized_recommendations(1, 10, Some(vec![seed])).await.unwrap();
+        let content: Vec<Uuid> = recommendations
+            .iter()
+            .filter(|r| r.algorithm_used == "content_based")
+            .map(|r| r.track_id)
+            .collect();
+        assert_eq!(content.len(), 3);
+        assert_eq!(&content[..2], &[near, close]);
+        assert!(!content.contains(&far) && !content.contains(&seed));
+
+        // Une piste retirée du stockage disparaît des recommandations
+        store.remove(near).await.unwrap();
+        for _ in 0..100 {
+            if content_model.lock().track_features(&near).is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let similar = content_model.lock().similar_tracks(&[seed], 3);
+        assert_eq!(similar.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![close, mid, far]);
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    #[tokio::test]
+    async fn test_listening_events_learn_tempo_preferences() {
+        let directory = std::env::temp_dir().join(format!("discovery_{}", Uuid::new_v4()));
+        let store = Arc::new(TrackFeatureStore::open(directory.clone()).await.unwrap());
+        let (slow, fast) = (Uuid::new_v4(), Uuid::new_v4());
+        store.save(slow, analysis(85.0, "5A", 0.3)).await.unwrap();
+        store.save(fast, analysis(128.0, "8A", 0.9)).await.unwrap();
+        let social = Arc::new(SocialManager::new(crate::soundcloud::social::SocialConfig::default()));
+        let engine = DiscoveryEngine::with_track_features(DiscoveryConfig::default(), social, store).await.unwrap();
+
+        // Une piste passée ne compte pas
+        engine.record_listening_event(7, listen(fast, 0.1, true)).await;
+        let profile = engine.recommendation_engine.get_user_profile(7).await.unwrap();
+        assert!(!profile.tempo_preferences.is_learned());
+        assert_eq!(profile.listening_history.len(), 1);
+
+        engine.record_listening_event(7, listen(slow, 1.0, false)).await;
+        engine.record_listening_event(7, listen(slow, 0.9, false)).await;
+        let preferences = engine.recommendation_engine.get_user_profile(7).await.unwrap().tempo_preferences;
+        let (low, high) = preferences.preferred_bpm_range;
+        assert!(low < 85.0 && 85.0 < high);
+        assert!((preferences.energy_level - 0.3).abs() < 1e-3);
+
+        let model = engine.recommendation_engine.ml_models.content_model.lock();
+        let (slow, fast) = (model.track_features(&slow).unwrap(), model.track_features(&fast).unwrap());
+        assert!(preferences.fit(slow) > 0.99);
+        assert!(preferences.fit(fast) < 0.7);
+    }
+}
@@ -1,16 +1,18 @@
 /// Module Management pour administration SoundCloud-like
 /// 
 /// Fonctionnalités :
-/// - Gestion de contenu (modération, DMCA)
+/// - Gestion de contenu (modération, DMCA, empreintes acoustiques)
 /// - Administration labels/distributeurs
 /// - Statistiques et analytics avancées
 /// - Monétisation et droits d'auteur
 /// - Gestion de communautés
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, Duration};
 use serde::{Serialize, Deserialize};
 use crate::error::AppError;
+use crate::soundcloud::content_id::{ContentIdRegistry, MATCH_SIMILARITY_THRESHOLD};
 
 /// Manager principal pour administration de contenu
 #[derive(Debug, Clone)]
@@ -20,6 +22,8 @@ pub struct ContentManager {
     pub community_manager: CommunityManager,
     pub analytics_engine: AnalyticsEngine,
     pub monetization_manager: MonetizationManager,
+    /// Empreintes acoustiques des pistes, partagées avec les uploads
+    pub content_id: Arc<ContentIdRegistry>,
 }
 
 /// Moteur de modération automatique
@@ -517,12 +521,18 @@ pub enum EventType {
 impl ContentManager {
     /// Crée un nouveau gestionnaire de contenu
     pub fn new() -> Self {
+        Self::with_content_id(Arc::new(ContentIdRegistry::in_memory()))
+    }
+    
+    /// Crée un gestionnaire de contenu sur un registre d'empreintes existant
+    pub fn with_content_id(content_id: Arc<ContentIdRegistry>) -> Self {
         Self {
             moderation_engine: ModerationEngine::new(),
             rights_manager: RightsManager::new(),
             community_manager: CommunityManager::new(),
             analytics_engine: AnalyticsEngine::new(),
             monetization_manager: MonetizationManager::new(),
+            content_id,
         }
     }
     
@@ -540,7 +550,7 @@ impl ContentManager {
     }
     
     /// Vérifie si une règle s'applique
-    async fn rule_matches(&self, rule: &PolicyRule, _flag: &ModerationFlag) -> Result<bool, AppError> {
+    async fn rule_matches(&self, rule: &PolicyRule, flag: &ModerationFlag) -> Result<bool, AppError> {
         for condition in &rule.conditions {
             match condition {
                 PolicyCondition::UserFlagCount { threshold } => {
@@ -552,6 +562,13 @@ impl ContentManager {
                     // Matcher le pattern contre le contenu
                     return Ok(pattern.contains("spam"));
                 },
+                PolicyCondition::AudioSignature { similarity_threshold } => {
+                    // La piste reprend un enregistrement retiré suite à une demande DMCA
+                    return Ok(self.content_id
+                        .matches_for_track(&flag.track_id.to_string(), *similarity_threshold)
+                        .iter()
+                        .any(|m| m.takedown_id.is_some()));
+                },
                 _ => continue,
             }
         }
@@ -581,6 +598,7 @@ impl ContentManager {
         // Vérification automatique de la base de droits
         if let Some(copyright_info) = self.rights_manager.copyright_db.get(&request.copyright_claim.work_title) {
             if self.verify_copyright_ownership(&request, copyright_info).await? {
+                self.enforce_takedown(&request).await?;
                 return Ok(DmcaResult::ValidClaim);
             }
         }
@@ -590,6 +608,42 @@ impl ContentManager {
         Ok(DmcaResult::PendingReview)
     }
     
+    /// Marque l'enregistrement retiré dans le registre d'empreintes et, si la
+    /// détection automatique est active, signale ses copies déjà en ligne
+    async fn enforce_takedown(&mut self, request: &TakedownRequest) -> Result<(), AppError> {
+        let track_id = request.track_id.to_string();
+        if !self.content_id.mark_taken_down(&track_id, request.id).await? {
+            return Ok(());
+        }
+        if !self.rights_manager.dmca_system.auto_detection {
+            return Ok(());
+        }
+        
+        for copy in self.content_id.matches_for_track(&track_id, MATCH_SIMILARITY_THRESHOLD) {
+            // Seules les pistes du catalogue (identifiants numériques) peuvent être signalées
+            let Ok(copy_track_id) = copy.fingerprint.track_id.parse::<u64>() else { continue };
+            if copy.takedown_id.is_some() {
+                continue;
+            }
+            let flag = ModerationFlag {
+                id: self.moderation_engine.auto_flags.len() as u64 + 1,
+                track_id: copy_track_id,
+                flag_type: FlagType::Copyright,
+                reason: format!(
+                    "Enregistrement de la piste {} retirée (DMCA #{}), similarité {:.2}",
+                    request.track_id, request.id, copy.fingerprint.similarity
+                ),
+                reporter_id: None,
+                severity: ModerationSeverity::High,
+                status: ModerationStatus::Pending,
+                created_at: SystemTime::now(),
+                reviewed_at: None,
+            };
+            self.moderation_engine.auto_flags.push(flag);
+        }
+        Ok(())
+    }
+    
     /// Valide une demande DMCA
     async fn validate_dmca_request(&self, request: &TakedownRequest) -> Result<bool, AppError> {
         // Vérifier les champs obligatoires
@@ -660,7 +714,17 @@ impl ModerationEngine {
                 ],
                 actions: vec![PolicyAction::AutoReject],
                 is_active: true,
-            }
+            },
+            PolicyRule {
+                id: 2,
+                name: "Block re-uploads of taken-down recordings".to_string(),
+                description: "Automatically reject tracks whose audio matches a recording removed after a DMCA takedown".to_string(),
+                conditions: vec![
+                    PolicyCondition::AudioSignature { similarity_threshold: MATCH_SIMILARITY_THRESHOLD },
+                ],
+                actions: vec![PolicyAction::AutoReject],
+                is_active: true,
+            },
         ]
     }
 }
@@ -831,6 +895,96 @@ impl PremiumFeatures {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::soundcloud::upload::{UploadConfig, UploadManager, UploadStatus, TRACK_ID_KEY};
+    
+    fn takedown_request(id: u64, track_id: u64) -> TakedownRequest {
+        TakedownRequest {
+            id,
+            track_id,
+            requestor_info: DmcaRequestorInfo {
+                name: "Test User".to_string(),
+                company: None,
+                email: "test@example.com".to_string(),
+                phone: None,
+                address: "123 Test St".to_string(),
+                is_rights_holder: true,
+                authorization_details: None,
+            },
+            copyright_claim: CopyrightClaim {
+                work_title: "Test Song".to_string(),
+                work_description: "Original composition".to_string(),
+                copyright_year: Some(2024),
+                registration_number: None,
+                infringement_description: "Unauthorized use".to_string(),
+                original_work_url: None,
+            },
+            good_faith_statement: "I believe in good faith...".to_string(),
+            penalty_acknowledgment: true,
+            status: TakedownStatus::Submitted,
+            submitted_at: SystemTime::now(),
+            processed_at: None,
+        }
+    }
+    
+    fn register_work(manager: &mut ContentManager) {
+        manager.rights_manager.copyright_db.insert("Test Song".to_string(), CopyrightInfo {
+            work_id: "work-1".to_string(),
+            title: "Test Song".to_string(),
+            authors: vec!["Test User".to_string()],
+            copyright_holders: vec!["Test User".to_string()],
+            license_type: LicenseType::AllRightsReserved,
+            usage_rights: UsageRights {
+                can_download: false,
+                can_remix: false,
+                can_commercial_use: false,
+                can_redistribute: false,
+                attribution_required: true,
+                share_alike_required: false,
+            },
+            expiration_date: None,
+        });
+    }
+    
+    /// WAV de 20 s : suite d'accords pseudo-aléatoires, un toutes les 0.5 s
+    fn music_wav(seed: u64) -> Vec<u8> {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 22050, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut cursor, spec).unwrap();
+        let mut state = seed;
+        let mut notes = [0.0f32; 3];
+        for i in 0..22050 * 20 {
+            if i % 11025 == 0 {
+                for note in notes.iter_mut() {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    *note = 110.0 * 2f32.powf(((state >> 33) % 36) as f32 / 12.0);
+                }
+            }
+            let t = i as f32 / 22050.0;
+            let tone: f32 = notes.iter().map(|f| (2.0 * std::f32::consts::PI * f * t).sin()).sum();
+            writer.write_sample((tone * 0.2 * i16::MAX as f32) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        cursor.into_inner()
+    }
+    
+    /// Envoie un fichier complet pour la piste `track_id` et attend la fin du traitement
+    async fn upload_track(uploads: &UploadManager, track_id: u64, data: &[u8]) -> UploadStatus {
+        let metadata = HashMap::from([(TRACK_ID_KEY.to_string(), track_id.to_string())]);
+        let session_id = uploads
+            .create_upload(7, "track.wav".to_string(), data.len() as u64, "audio/wav".to_string(), metadata)
+            .await
+            .unwrap();
+        let body = futures::stream::iter(vec![Ok::<_, std::io::Error>(bytes::Bytes::copy_from_slice(data))]);
+        uploads.append_upload(session_id, 0, body, None).await.unwrap();
+        for _ in 0..600 {
+            let status = uploads.get_upload_status(session_id).await.unwrap().status;
+            if matches!(status, UploadStatus::Completed | UploadStatus::Failed { .. }) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("traitement de l'upload {} non terminé", session_id);
+    }
     
     #[test]
     fn test_content_manager_creation() {
@@ -874,4 +1028,55 @@ mod tests {
                       request.penalty_acknowledgment;
         assert!(is_valid);
     }
-} 
\ No newline at end of file
+    
+    #[tokio::test]
+    async fn test_takedown_flags_copies_and_blocks_reuploads() {
+        use crate::audio::fingerprint::AudioFingerprint;
+        
+        let mut manager = ContentManager::new();
+        let recording = AudioFingerprint { sub_fingerprints: (0..300u32).map(|i| i.wrapping_mul(2654435761)).collect() };
+        let excerpt = AudioFingerprint { sub_fingerprints: recording.sub_fingerprints[50..150].to_vec() };
+        manager.content_id.register("123".to_string(), recording, MATCH_SIMILARITY_THRESHOLD).await.unwrap();
+        manager.content_id.register("456".to_string(), excerpt, MATCH_SIMILARITY_THRESHOLD).await.unwrap();
+        
+        register_work(&mut manager);
+        
+        assert!(matches!(manager.process_dmca_takedown(takedown_request(7, 123)).await.unwrap(), DmcaResult::ValidClaim));
+        assert_eq!(manager.content_id.takedown_of("123"), Some(7));
+        let flag = manager.moderation_engine.auto_flags[0].clone();
+        assert_eq!(flag.track_id, 456);
+        assert!(matches!(flag.flag_type, FlagType::Copyright));
+        
+        let action = manager.process_moderation_flag(flag).await.unwrap();
+        assert!(matches!(action, ModerationAction::AutoReject));
+    }
+    
+    #[tokio::test]
+    async fn test_reupload_of_taken_down_track_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = UploadConfig {
+            upload_directory: dir.path().join("uploads"),
+            temp_directory: dir.path().join("temp"),
+            ..UploadConfig::default()
+        };
+        let uploads = UploadManager::new(config).await.unwrap();
+        let mut manager = ContentManager::with_content_id(uploads.content_id());
+        register_work(&mut manager);
+        let recording = music_wav(1);
+        
+        // L'empreinte est rangée sous l'identifiant catalogue de la piste
+        assert!(matches!(upload_track(&uploads, 123, &recording).await, UploadStatus::Completed));
+        assert!(uploads.content_id().fingerprint("123").is_some());
+        assert!(matches!(upload_track(&uploads, 124, &music_wav(2)).await, UploadStatus::Completed));
+        
+        assert!(matches!(manager.process_dmca_takedown(takedown_request(7, 123)).await.unwrap(), DmcaResult::ValidClaim));
+        assert_eq!(uploads.content_id().takedown_of("123"), Some(7));
+        
+        // Remise en ligne sous un autre identifiant : refusée, rien n'est enregistré
+        let status = upload_track(&uploads, 456, &recording).await;
+        assert!(matches!(status, UploadStatus::Failed { reason } if reason.contains("#7")));
+        assert!(uploads.content_id().fingerprint("456").is_none());
+        assert_eq!(uploads.storage().list_user_files(7).await.unwrap().len(), 2);
+        assert!(matches!(upload_track(&uploads, 789, &music_wav(3)).await, UploadStatus::Completed));
+    }
+}
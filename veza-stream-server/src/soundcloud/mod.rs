@@ -2,18 +2,30 @@
 /// 
 /// Modules implémentés :
 /// - Upload & Management multi-format
+/// - Uploads reprenables (protocole tus 1.0)
+/// - Stockage local ou objet compatible S3, dédupliqué par contenu
+/// - Identification du contenu par empreinte acoustique
+/// - Analyse musicale (BPM, tonalité, énergie) et filtrage DJ
 /// - Playback Experience avancée
 /// - Social Features complètes 
 /// - Discovery & Algorithmes ML
 /// - Creator Tools & Analytics
+/// - Waveforms (peaks.js, `.dat`, images SVG/PNG) et spectrogrammes
 
 pub mod upload;
+pub mod tus;
+pub mod storage;
+pub mod dedup;
+pub mod content_id;
+pub mod track_features;
 pub mod management;
 pub mod playback;
 pub mod social;
 pub mod discovery;
 pub mod creator;
 pub mod waveform;
+pub mod waveform_render;
+pub mod spectrogram;
 
 // Re-exports pour faciliter l'usage
 pub use upload::*;
@@ -7,7 +7,13 @@
 /// - Shuffle/repeat algorithms
 /// - Timed comments sur waveform
 /// - Hotkeys et contrôles avancés
+/// - Rendu serveur gapless/crossfade des sessions radio (`renderer`)
 
+pub mod renderer;
+
+pub use renderer::*;
+
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use std::collections::{VecDeque, HashMap};
@@ -20,6 +26,7 @@ use tracing::info;
 
 use crate::error::AppError;
 use crate::core::StreamManager;
+use crate::audio::loudness::{LoudnessNormalization, TrackLoudness};
 
 /// Gestionnaire principal du playback
 #[derive(Debug)]
@@ -60,6 +67,9 @@ pub struct SoundCloudPlayer {
     
     /// Événements du player
     event_sender: mpsc::UnboundedSender<PlaybackEvent>,
+    
+    /// Session de rendu radio attachée au player
+    station: Mutex<Option<RenderHandle>>,
 }
 
 /// État de lecture du player
@@ -74,6 +84,9 @@ pub struct PlaybackState {
     pub shuffle_enabled: bool,
     pub crossfade_enabled: bool,
     pub gapless_enabled: bool,
+    /// Normalisation du volume (ReplayGain-like)
+    #[serde(default)]
+    pub normalization: LoudnessNormalization,
     pub last_updated: SystemTime,
 }
 
@@ -151,6 +164,9 @@ pub struct TrackInfo {
     pub plays_count: u64,
     pub likes_count: u64,
     pub created_at: SystemTime,
+    /// Mesure EBU R128 de la piste, si analysée
+    #[serde(default)]
+    pub loudness: Option<TrackLoudness>,
 }
 
 /// État du shuffle avec mémoire
@@ -315,7 +331,7 @@ pub enum PlaybackEvent {
     /// Piste suivante
     TrackChanged { 
         user_id: i64, 
-        previous_track: Option<TrackInfo>,
+        previous_track: Option<Box<TrackInfo>>,
         current_track: TrackInfo,
         change_reason: TrackChangeReason,
     },
@@ -464,6 +480,23 @@ impl PlaybackManager {
         }
     }
     
+    /// Démarre une session radio rendue côté serveur pour un utilisateur,
+    /// poussée dans le stream live `stream_id` s'il est fourni
+    pub async fn start_station<F>(
+        &self,
+        user_id: i64,
+        stream_id: Option<Uuid>,
+        config: RenderConfig,
+        resolve: F,
+    ) -> Result<RenderHandle, AppError>
+    where
+        F: Fn(&TrackInfo) -> Option<PathBuf>,
+    {
+        let player = self.get_or_create_player(user_id).await?;
+        let live = stream_id.map(|stream_id| (self.stream_manager.clone(), stream_id));
+        player.start_station(config, live, resolve).await
+    }
+    
     /// Abonnement aux événements de playback
     pub fn subscribe_events(&self) -> broadcast::Receiver<PlaybackEvent> {
         self.event_sender.subscribe()
@@ -491,6 +524,7 @@ impl SoundCloudPlayer {
             shuffle_enabled: false,
             crossfade_enabled: config.crossfade_duration > Duration::from_secs(0),
             gapless_enabled: true,
+            normalization: LoudnessNormalization::default(),
             last_updated: SystemTime::now(),
         }));
         
@@ -550,6 +584,7 @@ impl SoundCloudPlayer {
             timed_comments,
             session_analytics,
             event_sender: event_sender,
+            station: Mutex::new(None),
         })
     }
     
@@ -677,6 +712,12 @@ impl SoundCloudPlayer {
     
     /// Passe à la piste suivante
     pub async fn next_track(&self) -> Result<(), AppError> {
+        let station = self.station();
+        if let Some(station) = station {
+            station.skip();
+            self.handle_crossfade_transition().await?;
+        }
+        
         if let Some(next_track) = self.determine_next_track().await? {
             self.play_track(next_track, None).await
         } else {
@@ -710,8 +751,78 @@ impl SoundCloudPlayer {
         }
     }
     
+    /// Configure la normalisation du volume
+    pub async fn set_normalization(&self, normalization: LoudnessNormalization) {
+        let mut state = self.playback_state.write().await;
+        state.normalization = normalization;
+        state.last_updated = SystemTime::now();
+    }
+    
+    /// Gain de normalisation (dB) à appliquer à la piste en cours
+    pub async fn current_gain_db(&self) -> f32 {
+        let state = self.playback_state.read().await;
+        state.current_track
+            .as_ref()
+            .and_then(|track| track.loudness.as_ref())
+            .map(|loudness| state.normalization.gain_db(loudness))
+            .unwrap_or(0.0)
+    }
+    
+    /// Démarre le rendu serveur de la queue (piste courante puis suivantes).
+    /// Crossfade et courbe sont ceux du player ; `resolve` donne le fichier
+    /// local d'une piste, les pistes sans fichier sont ignorées.
+    pub async fn start_station<F>(
+        &self,
+        config: RenderConfig,
+        live: Option<(Arc<StreamManager>, Uuid)>,
+        resolve: F,
+    ) -> Result<RenderHandle, AppError>
+    where
+        F: Fn(&TrackInfo) -> Option<PathBuf>,
+    {
+        let config = {
+            let controller = self.crossfade_controller.lock();
+            RenderConfig {
+                crossfade: if controller.enabled { controller.duration } else { Duration::ZERO },
+                curve: controller.curve.clone(),
+                ..config
+            }
+        };
+        
+        let mut renderer = PlaybackRenderer::new(config);
+        {
+            let state = self.playback_state.read().await;
+            let queue = self.queue.read().await;
+            let upcoming = queue.tracks
+                .iter()
+                .skip(queue.current_index.map_or(0, |index| index + 1))
+                .map(|queued| &queued.track);
+            for track in state.current_track.iter().chain(upcoming) {
+                if let Some(path) = resolve(track) {
+                    renderer.enqueue(RenderTrack::from_track(track, path, &state.normalization));
+                }
+            }
+        }
+        
+        let handle = renderer.spawn(live)?;
+        if let Some(previous) = self.station.lock().replace(handle.clone()) {
+            previous.stop();
+        }
+        info!("Station started for user: {}", self.user_id);
+        Ok(handle)
+    }
+    
+    /// Session de rendu radio en cours
+    pub fn station(&self) -> Option<RenderHandle> {
+        self.station.lock().clone()
+    }
+    
     /// Arrête la lecture
     pub async fn stop(&self) -> Result<(), AppError> {
+        if let Some(station) = self.station.lock().take() {
+            station.stop();
+        }
+        
         let mut state = self.playback_state.write().await;
         state.status = PlaybackStatus::Stopped;
         state.current_track = None;
@@ -0,0 +1,633 @@
+//! Rendu serveur des sessions radio : enchaînements gapless et crossfades
+//!
+//! Chaque piste est décodée par morceaux, délai et padding encodeur retirés,
+//! convertie au format de sortie puis mixée avec la suivante selon la
+//! `CrossfadeCurve` du player. Le PCM rendu alimente un `LiveStream` et un
+//! flux encodé continu : un seul encodeur pour toute la session, donc pas de
+//! priming ni de padding entre les pistes.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::sync::{broadcast, mpsc, watch};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::audio::loudness::LoudnessNormalization;
+use crate::codecs::{CodecFactory, CodecQuality, EncoderConfig, GaplessInfo, LatencyMode, SymphoniaDecoder};
+use crate::core::StreamManager;
+use crate::error::AppError;
+use crate::streaming::segmenter::{remix_channels, StreamResampler};
+
+use super::{CrossfadeCurve, TrackInfo};
+
+/// Fondu appliqué lors d'un skip sans crossfade, pour éviter le clic
+const DECLICK_DURATION: Duration = Duration::from_millis(10);
+
+/// Capacité du canal de diffusion du flux encodé, en blocs
+const ENCODED_CHANNEL_CAPACITY: usize = 64;
+
+impl CrossfadeCurve {
+    /// Gains (piste sortante, piste entrante) à la position `t` ∈ [0, 1] du fondu
+    pub fn gains(&self, t: f32) -> (f32, f32) {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            // Amplitudes complémentaires : adapté aux pistes corrélées
+            CrossfadeCurve::Linear => (1.0 - t, t),
+            // Puissance constante (cos² + sin² = 1), en S
+            CrossfadeCurve::SCurve => {
+                let angle = t * std::f32::consts::FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            }
+            // Puissance constante, montée rapide de la piste entrante
+            CrossfadeCurve::Logarithmic => ((1.0 - t).sqrt(), t.sqrt()),
+            // Creux marqué au milieu du fondu
+            CrossfadeCurve::Exponential => ((1.0 - t).powi(2), t * t),
+        }
+    }
+}
+
+/// Configuration du rendu
+#[derive(Debug, Clone)]
+pub struct RenderConfig {
+    pub sample_rate: u32,
+    pub channels: u8,
+    /// Durée des crossfades ; zéro pour des enchaînements gapless
+    pub crossfade: Duration,
+    pub curve: CrossfadeCurve,
+    /// Durée d'un bloc rendu, qui cadence le flux
+    pub block_duration: Duration,
+    /// Codec du flux encodé continu (`mp3`, `aac`, `opus`)
+    pub codec: String,
+    pub bitrate_kbps: u32,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            channels: 2,
+            crossfade: Duration::ZERO,
+            curve: CrossfadeCurve::SCurve,
+            block_duration: Duration::from_millis(100),
+            codec: "mp3".to_string(),
+            bitrate_kbps: 128,
+        }
+    }
+}
+
+impl RenderConfig {
+    fn frames(&self, duration: Duration) -> usize {
+        (duration.as_secs_f64() * f64::from(self.sample_rate)).round() as usize
+    }
+
+    fn block_frames(&self) -> usize {
+        self.frames(self.block_duration).max(1)
+    }
+}
+
+/// Piste à rendre
+#[derive(Debug, Clone)]
+pub struct RenderTrack {
+    pub track_id: Uuid,
+    pub path: PathBuf,
+    /// Gain de normalisation (dB)
+    pub gain_db: f32,
+    /// Délai et padding encodeur à retirer quand le conteneur ne les déclare
+    /// pas (ADTS, MP4 sans edit list) ; le décodeur les retire déjà pour
+    /// MP3 (LAME/Xing) et Ogg
+    pub manual_trim: Option<GaplessInfo>,
+}
+
+impl RenderTrack {
+    pub fn new(track_id: Uuid, path: PathBuf) -> Self {
+        Self {
+            track_id,
+            path,
+            gain_db: 0.0,
+            manual_trim: None,
+        }
+    }
+
+    /// Piste du player, gain calculé depuis sa loudness mesurée
+    pub fn from_track(track: &TrackInfo, path: PathBuf, normalization: &LoudnessNormalization) -> Self {
+        Self {
+            gain_db: track.loudness.as_ref().map_or(0.0, |loudness| normalization.gain_db(loudness)),
+            ..Self::new(track.id, path)
+        }
+    }
+}
+
+/// Bloc de PCM rendu, entrelacé au format de sortie
+#[derive(Debug, Clone)]
+pub struct RenderedBlock {
+    pub samples: Vec<f32>,
+    /// Piste principale du bloc ; `None` si la file est vide (silence)
+    pub track_id: Option<Uuid>,
+}
+
+/// Piste en cours de décodage, convertie au format de sortie
+#[derive(Debug)]
+struct RenderSource {
+    track_id: Uuid,
+    decoder: Option<SymphoniaDecoder>,
+    resampler: StreamResampler,
+    source_channels: usize,
+    channels: usize,
+    gain: f32,
+    /// Frames de délai encodeur restant à écarter (trim manuel)
+    skip_frames: u64,
+    /// Dernières frames retenues tant que le flux n'est pas fini : ce sont
+    /// peut-être du padding encodeur (domaine source)
+    holdback: Vec<f32>,
+    padding_samples: usize,
+    /// PCM prêt, au format de sortie
+    buffer: VecDeque<f32>,
+}
+
+impl RenderSource {
+    fn open(track: &RenderTrack, config: &RenderConfig) -> Result<Self, AppError> {
+        let decoder = SymphoniaDecoder::open(&track.path)?;
+        let source_channels = usize::from(decoder.channels()).max(1);
+        let channels = usize::from(config.channels).max(1);
+        let trim = track.manual_trim.unwrap_or_default();
+        Ok(Self {
+            track_id: track.track_id,
+            resampler: StreamResampler::new(decoder.sample_rate(), config.sample_rate, channels)?,
+            decoder: Some(decoder),
+            source_channels,
+            channels,
+            gain: 10f32.powf(track.gain_db / 20.0),
+            skip_frames: u64::from(trim.encoder_delay),
+            holdback: Vec::new(),
+            padding_samples: trim.encoder_padding as usize * source_channels,
+            buffer: VecDeque::new(),
+        })
+    }
+
+    fn buffered_frames(&self) -> usize {
+        self.buffer.len() / self.channels
+    }
+
+    /// Décode jusqu'à disposer de `frames` frames ou atteindre la fin ; une
+    /// erreur de décodage termine la piste
+    fn fill(&mut self, frames: usize) {
+        while self.buffered_frames() < frames {
+            let Some(decoder) = self.decoder.as_mut() else {
+                break;
+            };
+            let result = match decoder.next_chunk() {
+                Ok(Some(chunk)) if usize::from(chunk.channels) != self.source_channels => Err(AppError::DecodingError {
+                    message: "Nombre de canaux variable en cours de piste".to_string(),
+                }),
+                Ok(Some(chunk)) => self.push_source(&chunk.samples),
+                Ok(None) => self.finish(),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                warn!("Rendu de la piste {} interrompu: {}", self.track_id, e);
+                let _ = self.finish();
+            }
+        }
+    }
+
+    fn push_source(&mut self, samples: &[f32]) -> Result<(), AppError> {
+        let skip = (self.skip_frames as usize * self.source_channels).min(samples.len());
+        self.skip_frames -= (skip / self.source_channels) as u64;
+        self.holdback.extend_from_slice(&samples[skip..]);
+
+        let release = self.holdback.len().saturating_sub(self.padding_samples);
+        if release == 0 {
+            return Ok(());
+        }
+        let released: Vec<f32> = self.holdback.drain(..release).collect();
+        let remixed = remix_channels(&released, self.source_channels, self.channels);
+        let converted = self.resampler.process(&remixed)?;
+        self.push_output(&converted);
+        Ok(())
+    }
+
+    /// Fin du flux : le padding retenu est abandonné et le filtre vidé
+    fn finish(&mut self) -> Result<(), AppError> {
+        self.decoder = None;
+        self.holdback.clear();
+        let tail = self.resampler.flush()?;
+        self.push_output(&tail);
+        Ok(())
+    }
+
+    fn push_output(&mut self, samples: &[f32]) {
+        self.buffer.extend(samples.iter().map(|sample| sample * self.gain));
+    }
+
+    /// Termine la piste après `frames` frames, avec un fondu de sortie si demandé
+    fn truncate(&mut self, frames: usize, fade_out: bool) {
+        self.fill(frames);
+        self.decoder = None;
+        self.buffer.truncate(frames * self.channels);
+
+        if fade_out {
+            let length = self.buffered_frames();
+            for (index, sample) in self.buffer.iter_mut().enumerate() {
+                let frame = index / self.channels;
+                *sample *= 1.0 - (frame as f32 + 0.5) / length as f32;
+            }
+        }
+    }
+
+    fn pop_frame(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.buffer.pop_front().unwrap_or(0.0);
+        }
+    }
+}
+
+/// Fondu en cours entre la piste courante et la suivante
+#[derive(Debug)]
+struct Crossfade {
+    incoming: RenderSource,
+    position: usize,
+    length: usize,
+}
+
+/// Moteur de rendu : file de pistes, piste courante et fondu éventuel.
+/// `render` est bloquant (décodage) et s'exécute dans `spawn_blocking`.
+#[derive(Debug)]
+pub struct PlaybackRenderer {
+    config: RenderConfig,
+    queue: VecDeque<RenderTrack>,
+    current: Option<RenderSource>,
+    fade: Option<Crossfade>,
+}
+
+impl PlaybackRenderer {
+    pub fn new(config: RenderConfig) -> Self {
+        Self {
+            config,
+            queue: VecDeque::new(),
+            current: None,
+            fade: None,
+        }
+    }
+
+    pub fn config(&self) -> &RenderConfig {
+        &self.config
+    }
+
+    pub fn enqueue(&mut self, track: RenderTrack) {
+        self.queue.push_back(track);
+    }
+
+    /// Piste principale en cours de rendu
+    pub fn current_track(&self) -> Option<Uuid> {
+        self.current.as_ref().map(|source| source.track_id)
+    }
+
+    /// Passe à la piste suivante : crossfade depuis la position courante,
+    /// ou court fondu de sortie si les fondus sont désactivés
+    pub fn skip(&mut self) {
+        if let Some(fade) = self.fade.take() {
+            self.current = Some(fade.incoming);
+        }
+        let crossfade = self.config.frames(self.config.crossfade);
+        let has_next = !self.queue.is_empty();
+        if let Some(current) = self.current.as_mut() {
+            if crossfade > 0 && has_next {
+                current.truncate(crossfade, false);
+            } else {
+                current.truncate(self.config.frames(DECLICK_DURATION).max(1), true);
+            }
+        }
+    }
+
+    /// Rend le bloc suivant ; complété par du silence si la file est vide
+    pub fn render(&mut self) -> RenderedBlock {
+        let channels = usize::from(self.config.channels).max(1);
+        let frames = self.config.block_frames();
+        let crossfade = self.config.frames(self.config.crossfade);
+        let mut output = vec![0.0f32; frames * channels];
+        let mut written = 0;
+        let mut track_id = self.current_track();
+
+        while written < frames {
+            let need = frames - written;
+
+            if self.current.is_none() {
+                self.current = self.open_next();
+                if self.current.is_none() {
+                    break;
+                }
+            }
+            track_id = track_id.or_else(|| self.current_track());
+
+            if let Some(fade) = self.fade.as_mut() {
+                let Some(current) = self.current.as_mut() else {
+                    break;
+                };
+                let count = need.min(fade.length - fade.position);
+                fade.incoming.fill(count);
+
+                let mut outgoing = vec![0.0f32; channels];
+                let mut incoming = vec![0.0f32; channels];
+                for frame in 0..count {
+                    let t = (fade.position + frame) as f32 + 0.5;
+                    let (gain_out, gain_in) = self.config.curve.gains(t / fade.length as f32);
+                    current.pop_frame(&mut outgoing);
+                    fade.incoming.pop_frame(&mut incoming);
+                    let start = (written + frame) * channels;
+                    for channel in 0..channels {
+                        output[start + channel] = outgoing[channel] * gain_out + incoming[channel] * gain_in;
+                    }
+                }
+                fade.position += count;
+                written += count;
+
+                if fade.position == fade.length {
+                    self.current = self.fade.take().map(|fade| fade.incoming);
+                }
+                continue;
+            }
+
+            let Some(current) = self.current.as_mut() else {
+                break;
+            };
+            // Les dernières frames de la piste sont réservées au fondu
+            let reserve = if crossfade > 0 && !self.queue.is_empty() { crossfade } else { 0 };
+            current.fill(need + reserve);
+            let count = need.min(current.buffered_frames().saturating_sub(reserve));
+            for frame in 0..count {
+                let start = (written + frame) * channels;
+                current.pop_frame(&mut output[start..start + channels]);
+            }
+            written += count;
+
+            if count < need {
+                // Fin de piste : enchaînement sample-exact, ou fondu sur les
+                // frames restantes si une piste suivante s'ouvre
+                let remaining = current.buffered_frames();
+                if remaining == 0 {
+                    self.current = None;
+                } else if let Some(incoming) = self.open_next() {
+                    self.fade = Some(Crossfade { incoming, position: 0, length: remaining });
+                }
+            }
+        }
+
+        RenderedBlock { samples: output, track_id }
+    }
+
+    /// Ouvre la prochaine piste lisible de la file
+    fn open_next(&mut self) -> Option<RenderSource> {
+        while let Some(track) = self.queue.pop_front() {
+            match RenderSource::open(&track, &self.config) {
+                Ok(source) => return Some(source),
+                Err(e) => warn!("Piste {} ignorée ({:?}): {}", track.track_id, track.path, e),
+            }
+        }
+        None
+    }
+
+    /// Lance le rendu temps réel : chaque bloc est poussé dans le `LiveStream`
+    /// `live` (source live au format du rendu) et encodé dans un flux continu.
+    /// La session s'arrête sur `RenderHandle::stop` ou quand tous les handles
+    /// sont abandonnés ; elle émet du silence tant que la file est vide.
+    pub fn spawn(self, live: Option<(Arc<StreamManager>, Uuid)>) -> Result<RenderHandle, AppError> {
+        let config = self.config.clone();
+        let mut encoder = CodecFactory::create_encoder(&config.codec, EncoderConfig {
+            bitrate: config.bitrate_kbps * 1000,
+            sample_rate: config.sample_rate,
+            channels: config.channels,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::Low,
+            enable_vbr: false,
+            complexity: 5,
+        })?;
+
+        let (command_sender, mut commands) = mpsc::unbounded_channel();
+        let (encoded_sender, _) = broadcast::channel(ENCODED_CHANNEL_CAPACITY);
+        let (track_sender, track_receiver) = watch::channel(self.current_track());
+        let encoded = encoded_sender.clone();
+
+        tokio::spawn(async move {
+            let mut renderer = Some(self);
+            let mut interval = tokio::time::interval(config.block_duration);
+
+            'session: loop {
+                interval.tick().await;
+                let Some(mut current) = renderer.take() else {
+                    break;
+                };
+
+                loop {
+                    match commands.try_recv() {
+                        Ok(RenderCommand::Enqueue(track)) => current.enqueue(track),
+                        Ok(RenderCommand::Skip) => current.skip(),
+                        Ok(RenderCommand::Stop) | Err(mpsc::error::TryRecvError::Disconnected) => break 'session,
+                        Err(mpsc::error::TryRecvError::Empty) => break,
+                    }
+                }
+
+                let (current, block) = match tokio::task::spawn_blocking(move || {
+                    let block = current.render();
+                    (current, block)
+                })
+                .await
+                {
+                    Ok(rendered) => rendered,
+                    Err(e) => {
+                        error!("Rendu interrompu: {}", e);
+                        break;
+                    }
+                };
+                renderer = Some(current);
+
+                if *track_sender.borrow() != block.track_id {
+                    info!("Rendu: piste courante {:?}", block.track_id);
+                    track_sender.send_replace(block.track_id);
+                }
+
+                if let Some((stream_manager, stream_id)) = &live {
+                    if let Err(e) = stream_manager.push_live_audio(*stream_id, &block.samples).await {
+                        error!("Rendu vers le stream {} interrompu: {}", stream_id, e);
+                        break;
+                    }
+                }
+
+                match encoder.encode(&block.samples, config.sample_rate, config.channels) {
+                    Ok(data) if !data.is_empty() => {
+                        let _ = encoded_sender.send(Bytes::from(data));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Encodage du rendu interrompu: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            if let Ok(data) = encoder.finalize() {
+                if !data.is_empty() {
+                    let _ = encoded_sender.send(Bytes::from(data));
+                }
+            }
+            info!("Session de rendu terminée");
+        });
+
+        Ok(RenderHandle {
+            commands: command_sender,
+            encoded,
+            current_track: track_receiver,
+        })
+    }
+}
+
+/// Commandes d'une session de rendu
+#[derive(Debug)]
+pub enum RenderCommand {
+    Enqueue(RenderTrack),
+    Skip,
+    Stop,
+}
+
+/// Contrôle d'une session de rendu lancée par `PlaybackRenderer::spawn`
+#[derive(Debug, Clone)]
+pub struct RenderHandle {
+    commands: mpsc::UnboundedSender<RenderCommand>,
+    encoded: broadcast::Sender<Bytes>,
+    current_track: watch::Receiver<Option<Uuid>>,
+}
+
+impl RenderHandle {
+    pub fn enqueue(&self, track: RenderTrack) {
+        let _ = self.commands.send(RenderCommand::Enqueue(track));
+    }
+
+    pub fn skip(&self) {
+        let _ = self.commands.send(RenderCommand::Skip);
+    }
+
+    pub fn stop(&self) {
+        let _ = self.commands.send(RenderCommand::Stop);
+    }
+
+    /// Vrai tant que la session de rendu tourne
+    pub fn is_running(&self) -> bool {
+        !self.commands.is_closed()
+    }
+
+    /// Abonnement au flux encodé continu, à partir du prochain bloc
+    pub fn subscribe(&self) -> broadcast::Receiver<Bytes> {
+        self.encoded.subscribe()
+    }
+
+    /// Piste en cours de rendu
+    pub fn current_track(&self) -> Option<Uuid> {
+        *self.current_track.borrow()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn write_constant(path: &Path, value: f32, frames: usize, sample_rate: u32) {
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..frames * 2 {
+            writer.write_sample(value).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn render_all(renderer: &mut PlaybackRenderer, blocks: usize) -> Vec<f32> {
+        (0..blocks).flat_map(|_| renderer.render().samples).collect()
+    }
+
+    #[test]
+    fn test_curve_gains() {
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            let (out, incoming) = CrossfadeCurve::SCurve.gains(t);
+            assert!((out * out + incoming * incoming - 1.0).abs() < 1e-5);
+            let (out, incoming) = CrossfadeCurve::Linear.gains(t);
+            assert!((out + incoming - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(CrossfadeCurve::SCurve.gains(0.0), (1.0, 0.0));
+    }
+
+    #[test]
+    fn test_gapless_transition_is_sample_accurate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("a.wav"), dir.path().join("b.wav"));
+        write_constant(&first, 0.25, 10_000, 44100);
+        write_constant(&second, 0.5, 7_000, 44100);
+
+        let mut renderer = PlaybackRenderer::new(RenderConfig::default());
+        renderer.enqueue(RenderTrack::new(Uuid::new_v4(), first));
+        renderer.enqueue(RenderTrack::new(Uuid::new_v4(), second));
+        let output = render_all(&mut renderer, 5);
+
+        let frames: Vec<f32> = output.chunks_exact(2).map(|frame| frame[0]).collect();
+        assert!(frames[..10_000].iter().all(|&s| s == 0.25));
+        assert!(frames[10_000..17_000].iter().all(|&s| s == 0.5));
+        assert!(frames[17_000..].iter().all(|&s| s == 0.0));
+        assert_eq!(renderer.current_track(), None);
+    }
+
+    #[test]
+    fn test_crossfade_overlaps_tracks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("a.wav"), dir.path().join("b.wav"));
+        write_constant(&first, 0.5, 44100, 44100);
+        write_constant(&second, 0.5, 44100, 48000);
+
+        let mut renderer = PlaybackRenderer::new(RenderConfig {
+            crossfade: Duration::from_millis(500),
+            curve: CrossfadeCurve::Linear,
+            ..RenderConfig::default()
+        });
+        renderer.enqueue(RenderTrack::new(Uuid::new_v4(), first));
+        renderer.enqueue(RenderTrack::new(Uuid::new_v4(), second));
+        let output = render_all(&mut renderer, 25);
+
+        // 1 s + 0,919 s rééchantillonnée - 0,5 s de recouvrement
+        let audible = output.chunks_exact(2).filter(|frame| frame[0].abs() > 1e-3).count();
+        let expected = 44100 + 40519 - 22050;
+        assert!(audible.abs_diff(expected) <= 2, "{} frames audibles", audible);
+
+        // Un fondu linéaire de deux signaux identiques reste constant
+        let fade = &output[(44100 - 22050) * 2..44100 * 2];
+        assert!(fade.iter().all(|s| (s - 0.5).abs() < 0.02));
+    }
+
+    #[test]
+    fn test_skip_crossfades_into_next_track() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, second) = (dir.path().join("a.wav"), dir.path().join("b.wav"));
+        write_constant(&first, 0.25, 441_000, 44100);
+        write_constant(&second, 0.5, 44100, 44100);
+
+        let mut renderer = PlaybackRenderer::new(RenderConfig {
+            crossfade: Duration::from_millis(200),
+            ..RenderConfig::default()
+        });
+        let second_id = Uuid::new_v4();
+        renderer.enqueue(RenderTrack::new(Uuid::new_v4(), first));
+        renderer.enqueue(RenderTrack::new(second_id, second));
+        render_all(&mut renderer, 2);
+
+        renderer.skip();
+        let output = render_all(&mut renderer, 3);
+        assert_eq!(renderer.current_track(), Some(second_id));
+        let last = output.len() - 2;
+        assert_eq!(output[last], 0.5);
+    }
+}
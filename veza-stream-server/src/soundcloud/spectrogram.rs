@@ -0,0 +1,319 @@
+//! Diffusion des spectrogrammes : image PNG ou tuiles JSON
+//!
+//! `GET /spectrogram/:filename` (URL signée comme `/stream`) calcule le
+//! spectrogramme de la piste avec la fenêtre, la taille de FFT, le pas et
+//! l'échelle de fréquences demandés. Sans paramètre `tile`, toute la piste
+//! est ramenée à `width` colonnes ; avec `tile`, les trames sont servies à
+//! pleine résolution par tranches de `TILE_FRAMES`, pour les outils de
+//! mastering qui zooment dans le temps. Les derniers spectrogrammes calculés
+//! restent en cache.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::spectrogram::{self, FrequencyScale, Spectrogram, SpectrogramConfig, WindowFunction, MIN_DB};
+use crate::config::Config;
+use crate::error::AppError;
+use crate::utils::{build_safe_path, png, validate_filename, validate_signature};
+
+/// Trames par tuile
+pub const TILE_FRAMES: usize = 512;
+/// Largeur par défaut d'une vue de toute la piste (colonnes)
+const DEFAULT_WIDTH: usize = 1200;
+const MAX_WIDTH: usize = 8192;
+/// Spectrogrammes gardés en mémoire
+const CACHE_ENTRIES: usize = 8;
+/// Palette « inferno » : du silence (noir) au niveau maximal (jaune pâle)
+const COLORMAP: [(f32, [f32; 3]); 5] = [
+    (0.0, [0.0, 0.0, 4.0]),
+    (0.25, [87.0, 16.0, 110.0]),
+    (0.5, [188.0, 55.0, 84.0]),
+    (0.75, [249.0, 142.0, 9.0]),
+    (1.0, [252.0, 255.0, 164.0]),
+];
+
+/// Format de sortie
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpectrogramFormat {
+    #[default]
+    Png,
+    Json,
+}
+
+/// Paramètres de `GET /spectrogram/:filename`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpectrogramQuery {
+    pub expires: String,
+    pub sig: String,
+    #[serde(default)]
+    pub format: SpectrogramFormat,
+    pub fft_size: Option<usize>,
+    pub hop_size: Option<usize>,
+    pub window: Option<WindowFunction>,
+    pub scale: Option<FrequencyScale>,
+    pub bands: Option<usize>,
+    pub min_freq: Option<f32>,
+    pub max_freq: Option<f32>,
+    /// Tuile à pleine résolution ; toute la piste si absent
+    pub tile: Option<usize>,
+    /// Nombre de colonnes d'une vue de toute la piste
+    pub width: Option<usize>,
+    /// Niveaux représentés par les couleurs (dBFS)
+    pub min_db: Option<f32>,
+    pub max_db: Option<f32>,
+}
+
+/// Tranche de spectrogramme au format JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct SpectrogramTile {
+    pub sample_rate: u32,
+    pub fft_size: usize,
+    pub hop_size: usize,
+    pub window: WindowFunction,
+    pub scale: FrequencyScale,
+    /// Fréquence centrale de chaque bande (Hz), de la plus grave à la plus aiguë
+    pub frequencies: Vec<f32>,
+    /// Tuile servie, absente pour une vue de toute la piste
+    pub tile: Option<usize>,
+    pub tile_count: usize,
+    pub start_ms: f32,
+    /// Durée couverte par chaque colonne
+    pub column_ms: f32,
+    pub duration_ms: f32,
+    pub min_db: f32,
+    pub max_db: f32,
+    /// Niveau de chaque bande par colonne, quantifié de 0 (`min_db`) à 255 (`max_db`)
+    pub levels: Vec<Vec<u8>>,
+    pub truncated: bool,
+}
+
+/// Calcul des spectrogrammes des fichiers du répertoire audio, avec cache
+#[derive(Debug)]
+pub struct SpectrogramService {
+    config: Arc<Config>,
+    cache: parking_lot::Mutex<VecDeque<(String, Arc<Spectrogram>)>>,
+}
+
+impl SpectrogramQuery {
+    fn spectrogram_config(&self) -> SpectrogramConfig {
+        let defaults = SpectrogramConfig::default();
+        let fft_size = self.fft_size.unwrap_or(defaults.fft_size);
+        SpectrogramConfig {
+            fft_size,
+            hop_size: self.hop_size.unwrap_or(fft_size / 2),
+            window: self.window.unwrap_or(defaults.window),
+            scale: self.scale.unwrap_or(defaults.scale),
+            bands: self.bands.unwrap_or(defaults.bands),
+            min_frequency: self.min_freq,
+            max_frequency: self.max_freq,
+        }
+    }
+
+    fn level_range(&self) -> Result<(f32, f32), AppError> {
+        let (min_db, max_db) = (self.min_db.unwrap_or(MIN_DB), self.max_db.unwrap_or(0.0));
+        if min_db.is_nan() || max_db.is_nan() || min_db >= max_db {
+            return Err(AppError::ValidationError(format!("Plage de niveaux invalide: {} - {} dB", min_db, max_db)));
+        }
+        Ok((min_db, max_db))
+    }
+}
+
+impl SpectrogramService {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            cache: parking_lot::Mutex::new(VecDeque::with_capacity(CACHE_ENTRIES)),
+        }
+    }
+
+    /// Spectrogramme d'un fichier du répertoire audio
+    pub async fn spectrogram(&self, filename: &str, config: SpectrogramConfig) -> Result<Arc<Spectrogram>, AppError> {
+        let path = build_safe_path(&self.config, &validate_filename(filename)?)?;
+        let key = format!("{}|{:?}", path.display(), config);
+        if let Some(spectrogram) = self.cached(&key) {
+            return Ok(spectrogram);
+        }
+
+        let spectrogram = Arc::new(Self::compute(path, config).await?);
+        let mut cache = self.cache.lock();
+        cache.retain(|(cached_key, _)| cached_key != &key);
+        if cache.len() >= CACHE_ENTRIES {
+            cache.pop_back();
+        }
+        cache.push_front((key, spectrogram.clone()));
+        Ok(spectrogram)
+    }
+
+    fn cached(&self, key: &str) -> Option<Arc<Spectrogram>> {
+        let mut cache = self.cache.lock();
+        let position = cache.iter().position(|(cached_key, _)| cached_key == key)?;
+        let entry = cache.remove(position)?;
+        let spectrogram = entry.1.clone();
+        cache.push_front(entry);
+        Some(spectrogram)
+    }
+
+    async fn compute(path: PathBuf, config: SpectrogramConfig) -> Result<Spectrogram, AppError> {
+        tokio::task::spawn_blocking(move || spectrogram::analyze_file(&path, config))
+            .await
+            .map_err(|e| AppError::InternalError { message: e.to_string() })?
+    }
+}
+
+/// Colonnes à servir : une tuile à pleine résolution ou toute la piste
+/// ramenée à `width` colonnes
+pub fn build_tile(
+    spectrogram: &Spectrogram,
+    tile: Option<usize>,
+    width: usize,
+    (min_db, max_db): (f32, f32),
+) -> Result<SpectrogramTile, AppError> {
+    let tile_count = spectrogram.len().div_ceil(TILE_FRAMES);
+    let (start, end, columns) = match tile {
+        Some(tile) if tile < tile_count.max(1) => (tile * TILE_FRAMES, (tile + 1) * TILE_FRAMES, TILE_FRAMES),
+        Some(tile) => {
+            return Err(AppError::NotFound { resource: format!("Tuile de spectrogramme {}", tile) });
+        }
+        None => (0, spectrogram.len(), width.clamp(1, MAX_WIDTH)),
+    };
+
+    let frames = spectrogram.columns(start, end, columns);
+    let covered_frames = end.min(spectrogram.len()).saturating_sub(start);
+    let column_ms = if frames.is_empty() {
+        spectrogram.time_resolution_ms
+    } else {
+        covered_frames as f32 * spectrogram.time_resolution_ms / frames.len() as f32
+    };
+    let levels = frames
+        .iter()
+        .map(|frame| {
+            frame
+                .iter()
+                .map(|level| ((level - min_db) / (max_db - min_db) * 255.0).round().clamp(0.0, 255.0) as u8)
+                .collect()
+        })
+        .collect();
+
+    Ok(SpectrogramTile {
+        sample_rate: spectrogram.sample_rate,
+        fft_size: spectrogram.fft_size,
+        hop_size: spectrogram.hop_size,
+        window: spectrogram.window,
+        scale: spectrogram.scale,
+        frequencies: spectrogram.frequencies.clone(),
+        tile,
+        tile_count,
+        start_ms: start as f32 * spectrogram.time_resolution_ms,
+        column_ms,
+        duration_ms: spectrogram.duration_secs() * 1000.0,
+        min_db,
+        max_db,
+        levels,
+        truncated: spectrogram.truncated,
+    })
+}
+
+/// Rend une tuile en PNG : une colonne par trame, graves en bas
+pub fn render_png(tile: &SpectrogramTile) -> Result<Vec<u8>, AppError> {
+    let (width, height) = (tile.levels.len(), tile.frequencies.len());
+    if width == 0 || height == 0 {
+        return Err(AppError::NotFound { resource: "Spectrogramme vide".to_string() });
+    }
+    let palette: Vec<[u8; 4]> = (0..=255u8).map(colormap).collect();
+    let mut rgba = vec![0u8; width * height * 4];
+    for (x, column) in tile.levels.iter().enumerate() {
+        for (band, level) in column.iter().enumerate() {
+            let y = height - 1 - band;
+            rgba[(y * width + x) * 4..][..4].copy_from_slice(&palette[usize::from(*level)]);
+        }
+    }
+    png::encode_rgba(width as u32, height as u32, &rgba)
+}
+
+fn colormap(level: u8) -> [u8; 4] {
+    let position = f32::from(level) / 255.0;
+    let upper = COLORMAP.iter().position(|(stop, _)| *stop >= position).unwrap_or(COLORMAP.len() - 1).max(1);
+    let (low_stop, low) = COLORMAP[upper - 1];
+    let (high_stop, high) = COLORMAP[upper];
+    let t = (position - low_stop) / (high_stop - low_stop);
+    let channel = |c: usize| (low[c] + (high[c] - low[c]) * t).round() as u8;
+    [channel(0), channel(1), channel(2), 0xff]
+}
+
+/// Route `GET /:filename` du spectrogramme
+pub fn spectrogram_routes<S>(service: Arc<SpectrogramService>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/:filename", get(get_spectrogram))
+        .with_state(service)
+}
+
+async fn get_spectrogram(
+    State(service): State<Arc<SpectrogramService>>,
+    Path(filename): Path<String>,
+    Query(query): Query<SpectrogramQuery>,
+) -> Result<Response, AppError> {
+    if !validate_signature(&service.config, &filename, &query.expires, &query.sig) {
+        return Err(AppError::Forbidden);
+    }
+    let level_range = query.level_range()?;
+    let spectrogram = service.spectrogram(&filename, query.spectrogram_config()).await?;
+    let tile = build_tile(&spectrogram, query.tile, query.width.unwrap_or(DEFAULT_WIDTH), level_range)?;
+
+    Ok(match query.format {
+        SpectrogramFormat::Json => Json(tile).into_response(),
+        SpectrogramFormat::Png => (
+            [(header::CONTENT_TYPE, "image/png"), (header::CACHE_CONTROL, "private, max-age=3600")],
+            render_png(&tile)?,
+        )
+            .into_response(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiles_and_png_rendering() {
+        let samples: Vec<f32> = (0..44100 * 15)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 44100.0).sin() * 0.5)
+            .collect();
+        let config = SpectrogramConfig { scale: FrequencyScale::Mel, bands: 32, ..SpectrogramConfig::default() };
+        let spectrogram = spectrogram::analyze_samples(&samples, 44100, 1, config).unwrap();
+        assert_eq!(spectrogram.len(), 645);
+
+        let range = (MIN_DB, 0.0);
+        let overview = build_tile(&spectrogram, None, 100, range).unwrap();
+        assert_eq!((overview.levels.len(), overview.tile_count), (100, 2));
+        let last = build_tile(&spectrogram, Some(1), 100, range).unwrap();
+        assert_eq!(last.levels.len(), 645 - TILE_FRAMES);
+        assert_eq!(last.start_ms, TILE_FRAMES as f32 * spectrogram.time_resolution_ms);
+        assert!(build_tile(&spectrogram, Some(2), 100, range).is_err());
+
+        // La bande du la 440 est la plus claire de chaque colonne
+        let loudest = overview.levels[50].iter().enumerate().max_by_key(|(_, level)| **level).unwrap().0;
+        assert!((overview.frequencies[loudest] / 440.0 - 1.0).abs() < 0.3);
+
+        let image = render_png(&overview).unwrap();
+        let (width, height, pixels) = png::decode_rgba(&image);
+        assert_eq!((width, height), (100, 32));
+        let row = height as usize - 1 - loudest;
+        assert_eq!(&pixels[(row * 100 + 50) * 4..][..4], &colormap(overview.levels[50][loudest]));
+        assert_eq!(colormap(0), [0, 0, 4, 0xff]);
+        assert_eq!(colormap(255), [252, 255, 164, 0xff]);
+    }
+}
@@ -0,0 +1,273 @@
+//! Caractéristiques musicales des pistes : tempo, tonalité, énergie
+//!
+//! L'analyse calculée après l'upload est persistée piste par piste dans
+//! `<répertoire>/<track_id>.json` et gardée en mémoire pour le filtrage des
+//! DJ : plage de BPM (éventuellement au demi ou double tempo), tonalité exacte
+//! ou compatible sur la roue de Camelot, plage d'énergie. Chaque changement
+//! est diffusé aux abonnés (moteur de découverte).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::sync::broadcast;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::audio::analysis::{MusicalKey, TrackAnalysis};
+use crate::error::AppError;
+
+/// Nombre de résultats renvoyés par défaut
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+const MAX_SEARCH_LIMIT: usize = 1000;
+/// Changements en attente par abonné avant qu'il ne décroche
+const UPDATES_CHANNEL_SIZE: usize = 256;
+
+/// Changement d'analyse diffusé aux abonnés ; `None` pour une piste retirée
+pub type TrackFeatureUpdate = (Uuid, Option<TrackAnalysis>);
+
+/// Analyses des pistes, indexées par identifiant de piste
+#[derive(Debug)]
+pub struct TrackFeatureStore {
+    directory: PathBuf,
+    tracks: parking_lot::RwLock<HashMap<Uuid, TrackAnalysis>>,
+    updates: broadcast::Sender<TrackFeatureUpdate>,
+}
+
+/// Critères de recherche (query string de `GET /tracks/features`)
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeatureQuery {
+    pub bpm_min: Option<f32>,
+    pub bpm_max: Option<f32>,
+    /// Accepte aussi les pistes au demi ou au double tempo
+    #[serde(default)]
+    pub half_double: bool,
+    /// Tonalité (`8A`, `A minor`, `Am`...)
+    pub key: Option<String>,
+    /// Accepte les tonalités compatibles avec `key` sur la roue de Camelot
+    #[serde(default)]
+    pub harmonic: bool,
+    pub energy_min: Option<f32>,
+    pub energy_max: Option<f32>,
+    pub limit: Option<usize>,
+}
+
+/// Analyse d'une piste telle que renvoyée par l'API
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackFeatures {
+    pub track_id: Uuid,
+    #[serde(flatten)]
+    pub analysis: TrackAnalysis,
+    /// Tonalité en notation Camelot
+    pub camelot: Option<String>,
+}
+
+impl TrackFeatureStore {
+    /// Ouvre le répertoire des analyses et recharge celles qui s'y trouvent
+    pub async fn open(directory: PathBuf) -> Result<Self, AppError> {
+        fs::create_dir_all(&directory).await?;
+        let mut tracks = HashMap::new();
+        let mut entries = fs::read_dir(&directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let Some(track_id) = path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .filter(|_| path.extension().is_some_and(|e| e == "json"))
+                .and_then(|stem| Uuid::parse_str(stem).ok())
+            else {
+                continue;
+            };
+            match fs::read(&path).await.map(|data| serde_json::from_slice::<TrackAnalysis>(&data)) {
+                Ok(Ok(analysis)) => {
+                    tracks.insert(track_id, analysis);
+                }
+                _ => warn!("Analyse illisible ignorée: {}", path.display()),
+            }
+        }
+
+        Ok(Self {
+            directory,
+            tracks: parking_lot::RwLock::new(tracks),
+            updates: broadcast::channel(UPDATES_CHANNEL_SIZE).0,
+        })
+    }
+
+    /// Abonnement aux analyses enregistrées ou retirées à partir de maintenant
+    pub fn subscribe(&self) -> broadcast::Receiver<TrackFeatureUpdate> {
+        self.updates.subscribe()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tracks.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, track_id: Uuid) -> Option<TrackAnalysis> {
+        self.tracks.read().get(&track_id).cloned()
+    }
+
+    /// Toutes les analyses, pour alimenter les modèles de recommandation
+    pub fn all(&self) -> Vec<(Uuid, TrackAnalysis)> {
+        self.tracks.read().iter().map(|(id, analysis)| (*id, analysis.clone())).collect()
+    }
+
+    /// Enregistre l'analyse d'une piste (écriture puis renommage atomique)
+    pub async fn save(&self, track_id: Uuid, analysis: TrackAnalysis) -> Result<(), AppError> {
+        let data = serde_json::to_vec(&analysis).map_err(|_| AppError::SerializationError)?;
+        let path = self.directory.join(format!("{}.json", track_id));
+        let part = path.with_extension("json.part");
+        fs::write(&part, data).await?;
+        fs::rename(&part, &path).await?;
+        self.tracks.write().insert(track_id, analysis.clone());
+        let _ = self.updates.send((track_id, Some(analysis)));
+        Ok(())
+    }
+
+    pub async fn remove(&self, track_id: Uuid) -> Result<(), AppError> {
+        if self.tracks.write().remove(&track_id).is_some() {
+            let _ = self.updates.send((track_id, None));
+        }
+        match fs::remove_file(self.directory.join(format!("{}.json", track_id))).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pistes répondant aux critères, triées par tempo
+    pub fn search(&self, query: &FeatureQuery) -> Result<Vec<TrackFeatures>, AppError> {
+        let key = query.key.as_deref().map(str::parse::<MusicalKey>).transpose()?;
+        let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).min(MAX_SEARCH_LIMIT);
+
+        let mut results: Vec<TrackFeatures> = self.tracks.read()
+            .iter()
+            .filter(|(_, analysis)| query.matches_bpm(analysis.bpm))
+            .filter(|(_, analysis)| match (key, analysis.key) {
+                (None, _) => true,
+                (Some(wanted), Some(found)) if query.harmonic => wanted.is_harmonic_with(&found),
+                (Some(wanted), Some(found)) => wanted == found,
+                (Some(_), None) => false,
+            })
+            .filter(|(_, analysis)| query.energy_min.is_none_or(|min| analysis.energy >= min))
+            .filter(|(_, analysis)| query.energy_max.is_none_or(|max| analysis.energy <= max))
+            .map(|(track_id, analysis)| TrackFeatures::new(*track_id, analysis.clone()))
+            .collect();
+
+        results.sort_by(|a, b| {
+            a.analysis.bpm.unwrap_or(f32::MAX).total_cmp(&b.analysis.bpm.unwrap_or(f32::MAX))
+                .then(a.track_id.cmp(&b.track_id))
+        });
+        results.truncate(limit);
+        Ok(results)
+    }
+}
+
+impl FeatureQuery {
+    fn matches_bpm(&self, bpm: Option<f32>) -> bool {
+        if self.bpm_min.is_none() && self.bpm_max.is_none() {
+            return true;
+        }
+        let Some(bpm) = bpm else {
+            return false;
+        };
+        let in_range = |bpm: f32| {
+            self.bpm_min.is_none_or(|min| bpm >= min) && self.bpm_max.is_none_or(|max| bpm <= max)
+        };
+        in_range(bpm) || (self.half_double && (in_range(bpm * 2.0) || in_range(bpm / 2.0)))
+    }
+}
+
+impl TrackFeatures {
+    fn new(track_id: Uuid, analysis: TrackAnalysis) -> Self {
+        Self {
+            track_id,
+            camelot: analysis.key.map(|key| key.camelot_code()),
+            analysis,
+        }
+    }
+}
+
+/// Routes de consultation : `GET /` filtre par BPM, tonalité et énergie,
+/// `GET /:track_id` renvoie l'analyse d'une piste
+pub fn track_features_routes<S>(store: Arc<TrackFeatureStore>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/", get(search_features))
+        .route("/:track_id", get(get_features))
+        .with_state(store)
+}
+
+async fn search_features(
+    State(store): State<Arc<TrackFeatureStore>>,
+    Query(query): Query<FeatureQuery>,
+) -> Result<Json<Vec<TrackFeatures>>, AppError> {
+    Ok(Json(store.search(&query)?))
+}
+
+async fn get_features(
+    State(store): State<Arc<TrackFeatureStore>>,
+    Path(track_id): Path<Uuid>,
+) -> Result<Json<TrackFeatures>, AppError> {
+    store.get(track_id)
+        .map(|analysis| Json(TrackFeatures::new(track_id, analysis)))
+        .ok_or_else(|| AppError::NotFound { resource: format!("Track features: {}", track_id) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis(bpm: f32, key: &str, energy: f32) -> TrackAnalysis {
+        TrackAnalysis {
+            bpm: Some(bpm),
+            bpm_confidence: 0.8,
+            key: Some(key.parse().unwrap()),
+            key_confidence: 0.7,
+            energy,
+            danceability: 0.7,
+            loudness_db: -12.0,
+            spectral_centroid_hz: 1500.0,
+            spectral_flatness: 0.1,
+            onset_rate: 2.0,
+            duration_secs: 180.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_by_bpm_and_harmonic_key() {
+        let directory = std::env::temp_dir().join(format!("track_features_{}", Uuid::new_v4()));
+        let store = TrackFeatureStore::open(directory.clone()).await.unwrap();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        store.save(a, analysis(124.0, "8A", 0.8)).await.unwrap();
+        store.save(b, analysis(64.0, "9A", 0.6)).await.unwrap();
+        store.save(c, analysis(126.0, "3B", 0.9)).await.unwrap();
+
+        // Rechargé depuis le disque
+        let store = TrackFeatureStore::open(directory.clone()).await.unwrap();
+        assert_eq!(store.len(), 3);
+
+        let query = FeatureQuery { bpm_min: Some(120.0), bpm_max: Some(130.0), ..Default::default() };
+        let ids: Vec<Uuid> = store.search(&query).unwrap().iter().map(|t| t.track_id).collect();
+        assert_eq!(ids, vec![a, c]);
+
+        let query = FeatureQuery { half_double: true, key: Some("Am".to_string()), harmonic: true, ..query };
+        let results = store.search(&query).unwrap();
+        let ids: Vec<Uuid> = results.iter().map(|t| t.track_id).collect();
+        assert_eq!(ids, vec![b, a]);
+        assert_eq!(results[1].camelot.as_deref(), Some("8A"));
+
+        assert!(store.search(&FeatureQuery { key: Some("H#".to_string()), ..Default::default() }).is_err());
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}
@@ -0,0 +1,454 @@
+//! Uploads reprenables suivant le protocole tus 1.0
+//!
+//! Extensions supportées : creation, termination et checksum (sha1, sha256,
+//! md5). Chaque upload tus est une `UploadSession` persistée sur disque : après
+//! une coupure ou un redémarrage du serveur, le client retrouve l'offset par
+//! `HEAD` puis reprend par `PATCH`.
+//!
+//! Un client qui déclare le SHA-256 du fichier (`Upload-Metadata: sha256 ...`)
+//! voit son upload terminé dès le premier Mo si ce contenu est déjà stocké :
+//! la réponse au `PATCH` porte alors `Upload-Offset` égal à `Upload-Length`.
+//!
+//! `Upload-Metadata: track_id ...` rattache l'upload à sa piste du catalogue :
+//! son empreinte acoustique est enregistrée sous cet identifiant, celui des
+//! retraits DMCA et de la modération.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Path as AxumPath, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{head, post},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use uuid::Uuid;
+
+use crate::auth::{AuthManager, Claims, Permission};
+use crate::error::AppError;
+use crate::soundcloud::upload::{ChecksumAlgorithm, UploadChecksum, UploadManager, UploadSession, UploadStatus};
+
+/// Version du protocole implémentée
+pub const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,termination,checksum";
+const TUS_CHECKSUM_ALGORITHMS: &str = "sha1,sha256,md5";
+const OFFSET_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+const TUS_RESUMABLE: &str = "tus-resumable";
+const TUS_VERSION_HEADER: &str = "tus-version";
+const UPLOAD_LENGTH: &str = "upload-length";
+const UPLOAD_OFFSET: &str = "upload-offset";
+const UPLOAD_METADATA: &str = "upload-metadata";
+const UPLOAD_CHECKSUM: &str = "upload-checksum";
+const UPLOAD_DEFER_LENGTH: &str = "upload-defer-length";
+
+#[derive(Clone)]
+struct TusState {
+    uploads: Arc<UploadManager>,
+    auth: Arc<AuthManager>,
+}
+
+/// Routes tus : `POST /` crée un upload, `HEAD|PATCH|DELETE /:upload_id`
+/// le reprend, l'alimente ou le supprime. Authentification par Bearer JWT
+/// avec la permission `UploadAudio` ; `OPTIONS` reste public (découverte).
+pub fn tus_routes<S>(uploads: Arc<UploadManager>, auth: Arc<AuthManager>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/", post(create_upload).options(tus_options))
+        .route(
+            "/:upload_id",
+            head(upload_offset)
+                .patch(append_upload)
+                .delete(terminate_upload)
+                .options(tus_options),
+        )
+        .with_state(TusState { uploads, auth })
+}
+
+/// Réponse portant l'en-tête `Tus-Resumable`, obligatoire hors `OPTIONS`
+fn tus_response(status: StatusCode) -> axum::http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header(TUS_RESUMABLE, TUS_VERSION)
+}
+
+/// Refus d'une requête tus ; la réponse porte toujours `Tus-Resumable`
+enum TusRejection {
+    /// Version du protocole non supportée (412 + `Tus-Version`)
+    Version,
+    Status(StatusCode),
+    App(AppError),
+}
+
+impl From<AppError> for TusRejection {
+    fn from(error: AppError) -> Self {
+        TusRejection::App(error)
+    }
+}
+
+impl IntoResponse for TusRejection {
+    fn into_response(self) -> Response {
+        let mut response = match self {
+            TusRejection::Version => {
+                let mut response = StatusCode::PRECONDITION_FAILED.into_response();
+                response.headers_mut().insert(TUS_VERSION_HEADER, HeaderValue::from_static(TUS_VERSION));
+                return response;
+            }
+            TusRejection::Status(status) => status.into_response(),
+            TusRejection::App(error) => error.into_response(),
+        };
+        response.headers_mut().insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+        response
+    }
+}
+
+fn build(builder: axum::http::response::Builder) -> Result<Response, TusRejection> {
+    builder
+        .body(Body::empty())
+        .map_err(|e| AppError::InternalError { message: e.to_string() }.into())
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Refuse les clients d'une autre version du protocole (412)
+fn check_version(headers: &HeaderMap) -> Result<(), TusRejection> {
+    match header_str(headers, TUS_RESUMABLE) {
+        Some(TUS_VERSION) => Ok(()),
+        _ => Err(TusRejection::Version),
+    }
+}
+
+async fn authenticate(state: &TusState, headers: &HeaderMap) -> Result<Claims, TusRejection> {
+    let token = header_str(headers, header::AUTHORIZATION.as_str())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| TusRejection::from(AppError::Unauthorized))?;
+
+    let validation = state.auth.validate_token(token).await;
+    let claims = validation.claims
+        .filter(|_| validation.valid)
+        .ok_or_else(|| TusRejection::from(AppError::Unauthorized))?;
+    if !state.auth.has_permission(&claims, Permission::UploadAudio) {
+        return Err(TusRejection::from(AppError::Forbidden));
+    }
+    Ok(claims)
+}
+
+/// Upload appartenant à l'utilisateur ; celui d'un autre est présenté comme absent
+async fn owned_upload(state: &TusState, claims: &Claims, upload_id: &str) -> Result<(Uuid, u64, UploadSession), TusRejection> {
+    let not_found = || TusRejection::from(AppError::UploadSessionNotFound { session_id: upload_id.to_string() });
+    let session_id = Uuid::parse_str(upload_id).map_err(|_| not_found())?;
+    let (offset, session) = state.uploads.upload_offset(session_id).await.map_err(TusRejection::from)?;
+    if session.user_id != claims.sub {
+        return Err(not_found());
+    }
+    Ok((session_id, offset, session))
+}
+
+async fn tus_options(State(state): State<TusState>) -> Result<Response, TusRejection> {
+    build(
+        tus_response(StatusCode::NO_CONTENT)
+            .header(TUS_VERSION_HEADER, TUS_VERSION)
+            .header("tus-extension", TUS_EXTENSIONS)
+            .header("tus-max-size", state.uploads.config().max_file_size)
+            .header("tus-checksum-algorithm", TUS_CHECKSUM_ALGORITHMS),
+    )
+}
+
+async fn create_upload(
+    State(state): State<TusState>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Result<Response, TusRejection> {
+    check_version(&headers)?;
+    let claims = authenticate(&state, &headers).await?;
+
+    if headers.contains_key(UPLOAD_DEFER_LENGTH) {
+        return Err(TusRejection::from(AppError::ValidationError("Upload-Defer-Length is not supported".to_string())));
+    }
+    let length: u64 = header_str(&headers, UPLOAD_LENGTH)
+        .and_then(|value| value.parse().ok())
+        .filter(|&length| length > 0)
+        .ok_or_else(|| TusRejection::from(AppError::ValidationError("Missing or invalid Upload-Length".to_string())))?;
+    if length > state.uploads.config().max_file_size {
+        return Err(TusRejection::Status(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    let metadata = match header_str(&headers, UPLOAD_METADATA) {
+        Some(value) => parse_metadata(value).map_err(TusRejection::from)?,
+        None => HashMap::new(),
+    };
+    let filename = metadata.get("filename")
+        .or_else(|| metadata.get("name"))
+        .cloned()
+        .ok_or_else(|| TusRejection::from(AppError::ValidationError("Upload-Metadata must include filename".to_string())))?;
+    let content_type = metadata.get("filetype")
+        .or_else(|| metadata.get("type"))
+        .cloned()
+        .unwrap_or_else(|| content_type_for(&filename).to_string());
+
+    let session_id = state.uploads
+        .create_upload(claims.sub, filename, length, content_type, metadata)
+        .await
+        .map_err(TusRejection::from)?;
+
+    let location = format!("{}/{}", uri.path().trim_end_matches('/'), session_id);
+    build(
+        tus_response(StatusCode::CREATED)
+            .header(header::LOCATION, location)
+            .header(UPLOAD_OFFSET, 0),
+    )
+}
+
+async fn upload_offset(
+    State(state): State<TusState>,
+    AxumPath(upload_id): AxumPath<String>,
+    headers: HeaderMap,
+) -> Result<Response, TusRejection> {
+    check_version(&headers)?;
+    let claims = authenticate(&state, &headers).await?;
+    let (_, offset, session) = owned_upload(&state, &claims, &upload_id).await?;
+
+    if session.status == UploadStatus::Cancelled {
+        return Err(TusRejection::Status(StatusCode::GONE));
+    }
+
+    let mut response = tus_response(StatusCode::OK)
+        .header(UPLOAD_OFFSET, offset)
+        .header(UPLOAD_LENGTH, session.file_size)
+        .header(header::CACHE_CONTROL, "no-store");
+    if !session.client_metadata.is_empty() {
+        response = response.header(UPLOAD_METADATA, encode_metadata(&session.client_metadata));
+    }
+    build(response)
+}
+
+async fn append_upload(
+    State(state): State<TusState>,
+    AxumPath(upload_id): AxumPath<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, TusRejection> {
+    check_version(&headers)?;
+    let claims = authenticate(&state, &headers).await?;
+
+    if header_str(&headers, header::CONTENT_TYPE.as_str()) != Some(OFFSET_CONTENT_TYPE) {
+        return Err(TusRejection::Status(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+    }
+    let offset: u64 = header_str(&headers, UPLOAD_OFFSET)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| TusRejection::from(AppError::ValidationError("Missing or invalid Upload-Offset".to_string())))?;
+    let checksum = header_str(&headers, UPLOAD_CHECKSUM)
+        .map(parse_checksum)
+        .transpose()
+        .map_err(TusRejection::from)?;
+
+    let (session_id, _, _) = owned_upload(&state, &claims, &upload_id).await?;
+    let new_offset = state.uploads
+        .append_upload(session_id, offset, body.into_data_stream(), checksum)
+        .await
+        .map_err(TusRejection::from)?;
+
+    build(tus_response(StatusCode::NO_CONTENT).header(UPLOAD_OFFSET, new_offset))
+}
+
+async fn terminate_upload(
+    State(state): State<TusState>,
+    AxumPath(upload_id): AxumPath<String>,
+    headers: HeaderMap,
+) -> Result<Response, TusRejection> {
+    check_version(&headers)?;
+    let claims = authenticate(&state, &headers).await?;
+    let (session_id, _, _) = owned_upload(&state, &claims, &upload_id).await?;
+
+    state.uploads.terminate_upload(session_id).await.map_err(TusRejection::from)?;
+    build(tus_response(StatusCode::NO_CONTENT))
+}
+
+/// Décode `Upload-Metadata` : paires `clé valeur_base64` séparées par des virgules
+pub fn parse_metadata(header: &str) -> Result<HashMap<String, String>, AppError> {
+    let invalid = || AppError::ValidationError(format!("Invalid Upload-Metadata: {}", header));
+
+    let mut metadata = HashMap::new();
+    for pair in header.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let mut parts = pair.splitn(2, ' ');
+        let key = parts.next().filter(|key| !key.is_empty()).ok_or_else(invalid)?;
+        let value = match parts.next() {
+            Some(encoded) => {
+                let decoded = STANDARD.decode(encoded.trim()).map_err(|_| invalid())?;
+                String::from_utf8(decoded).map_err(|_| invalid())?
+            }
+            None => String::new(),
+        };
+        metadata.insert(key.to_string(), value);
+    }
+    Ok(metadata)
+}
+
+/// Encode des métadonnées au format `Upload-Metadata`, clés triées
+pub fn encode_metadata(metadata: &HashMap<String, String>) -> String {
+    let mut keys: Vec<&String> = metadata.keys().collect();
+    keys.sort();
+    keys.into_iter()
+        .map(|key| match metadata[key].as_str() {
+            "" => key.clone(),
+            value => format!("{} {}", key, STANDARD.encode(value)),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Décode `Upload-Checksum` : `algorithme empreinte_base64`
+pub fn parse_checksum(header: &str) -> Result<UploadChecksum, AppError> {
+    let (name, digest) = header
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| AppError::ValidationError(format!("Invalid Upload-Checksum: {}", header)))?;
+    let algorithm = ChecksumAlgorithm::from_name(name)
+        .ok_or_else(|| AppError::ValidationError(format!("Unsupported checksum algorithm: {}", name)))?;
+    let digest = STANDARD
+        .decode(digest.trim())
+        .map_err(|_| AppError::ValidationError(format!("Invalid Upload-Checksum: {}", header)))?;
+    Ok(UploadChecksum { algorithm, digest })
+}
+
+/// Type MIME déduit de l'extension, quand le client ne fournit pas `filetype`
+fn content_type_for(filename: &str) -> &'static str {
+    let extension = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "aiff" => "audio/aiff",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/m4a",
+        "mp4" => "audio/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soundcloud::upload::UploadConfig;
+    use bytes::Bytes;
+    use sha2::Digest;
+
+    fn chunks(data: &[u8]) -> impl futures::Stream<Item = Result<Bytes, std::io::Error>> + Unpin {
+        futures::stream::iter(data.chunks(1000).map(|chunk| Ok(Bytes::copy_from_slice(chunk))).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let metadata = parse_metadata("filename bXlzb25nLndhdg==,filetype YXVkaW8vd2F2,is_private").unwrap();
+        assert_eq!(metadata["filename"], "mysong.wav");
+        assert_eq!(metadata["filetype"], "audio/wav");
+        assert_eq!(metadata["is_private"], "");
+        assert_eq!(parse_metadata(&encode_metadata(&metadata)).unwrap(), metadata);
+        assert!(parse_metadata("filename ###").is_err());
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let checksum = parse_checksum("sha1 Kq5sNclPz7QV2+lfQIuc6R7oRu0=").unwrap();
+        assert_eq!(checksum.algorithm, ChecksumAlgorithm::Sha1);
+        assert_eq!(checksum.digest.len(), 20);
+        assert!(parse_checksum("crc32 AAAA").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_resumes_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = UploadConfig {
+            upload_directory: dir.path().join("uploads"),
+            temp_directory: dir.path().join("temp"),
+            ..UploadConfig::default()
+        };
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+
+        let uploads = UploadManager::new(config.clone()).await.unwrap();
+        let session_id = uploads
+            .create_upload(7, "track.wav".to_string(), data.len() as u64, "audio/wav".to_string(), HashMap::new())
+            .await
+            .unwrap();
+        assert_eq!(uploads.append_upload(session_id, 0, chunks(&data[..4000]), None).await.unwrap(), 4000);
+
+        // Mauvais offset, puis somme de contrôle fausse : rien n'est écrit
+        assert!(matches!(
+            uploads.append_upload(session_id, 3000, chunks(&data[3000..5000]), None).await,
+            Err(AppError::UploadOffsetMismatch { expected: 4000, got: 3000 })
+        ));
+        let wrong = UploadChecksum { algorithm: ChecksumAlgorithm::Sha256, digest: vec![0; 32] };
+        assert!(matches!(
+            uploads.append_upload(session_id, 4000, chunks(&data[4000..6000]), Some(wrong)).await,
+            Err(AppError::ChecksumMismatch { .. })
+        ));
+
+        // Redémarrage : la session et son offset sont restaurés depuis le disque
+        drop(uploads);
+        let uploads = UploadManager::new(config).await.unwrap();
+        let (offset, session) = uploads.upload_offset(session_id).await.unwrap();
+        assert_eq!(offset, 4000);
+        assert_eq!(session.user_id, 7);
+
+        let checksum = UploadChecksum {
+            algorithm: ChecksumAlgorithm::Sha256,
+            digest: sha2::Sha256::digest(&data[4000..6000]).to_vec(),
+        };
+        assert_eq!(uploads.append_upload(session_id, 4000, chunks(&data[4000..6000]), Some(checksum)).await.unwrap(), 6000);
+        let written = std::fs::read(dir.path().join("temp").join(format!("{}.wav", session_id))).unwrap();
+        assert_eq!(written, data[..6000]);
+
+        uploads.terminate_upload(session_id).await.unwrap();
+        assert!(uploads.upload_offset(session_id).await.is_err());
+        assert!(!dir.path().join("temp").join(format!("{}.json", session_id)).exists());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_patch_is_rejected_then_sees_new_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = UploadConfig {
+            upload_directory: dir.path().join("uploads"),
+            temp_directory: dir.path().join("temp"),
+            ..UploadConfig::default()
+        };
+        let data: Vec<u8> = (0..4000u32).map(|i| (i % 251) as u8).collect();
+        let uploads = UploadManager::new(config).await.unwrap();
+        let session_id = uploads
+            .create_upload(7, "track.wav".to_string(), data.len() as u64, "audio/wav".to_string(), HashMap::new())
+            .await
+            .unwrap();
+
+        // Premier PATCH en cours : le corps n'arrive qu'après le second
+        let (tx, body) = futures::channel::mpsc::unbounded::<Result<Bytes, std::io::Error>>();
+        let first = uploads.append_upload(session_id, 0, body, None);
+        tokio::pin!(first);
+        assert!(futures::poll!(&mut first).is_pending());
+        assert!(matches!(
+            uploads.append_upload(session_id, 0, chunks(&data[..1000]), None).await,
+            Err(AppError::AlreadyProcessing)
+        ));
+
+        tx.unbounded_send(Ok(Bytes::copy_from_slice(&data[..2000]))).unwrap();
+        drop(tx);
+        assert_eq!(first.await.unwrap(), 2000);
+
+        // Le client retardataire rejoue son offset : refusé, rien n'est écrasé
+        assert!(matches!(
+            uploads.append_upload(session_id, 0, chunks(&data[..1000]), None).await,
+            Err(AppError::UploadOffsetMismatch { expected: 2000, got: 0 })
+        ));
+        let written = std::fs::read(dir.path().join("temp").join(format!("{}.wav", session_id))).unwrap();
+        assert_eq!(written, data[..2000]);
+    }
+}
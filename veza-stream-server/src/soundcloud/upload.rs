@@ -9,17 +9,31 @@
 
 use std::sync::Arc;
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, SystemTime};
 
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
 use serde::{Serialize, Deserialize};
+use sha2::Digest;
 use uuid::Uuid;
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::{mpsc, RwLock};
-use tracing::{info, error};
+use tracing::{info, error, warn};
 
+use crate::audio::analysis::{self, TrackAnalysis};
+use crate::audio::fingerprint::{self, AudioFingerprint};
+use crate::audio::loudness::{self, TrackLoudness};
+use crate::audio::tags::{self, EmbeddedPicture, TagFormat, TrackTags};
+use crate::codecs::decoder::SymphoniaDecoder;
+use crate::codecs::AudioDecoder;
 use crate::error::AppError;
+use crate::soundcloud::content_id::{ContentIdRegistry, ContentMatch, MATCH_SIMILARITY_THRESHOLD};
+use crate::soundcloud::storage::{IndexStore, LocalIndexFile};
+use crate::soundcloud::track_features::TrackFeatureStore;
 use crate::soundcloud::waveform::{WaveformGenerator, WaveformData};
+use crate::utils::image::{ImageFormat, RgbImage};
 
 /// Gestionnaire principal des uploads
 #[derive(Debug)]
@@ -34,12 +48,19 @@ pub struct UploadManager {
     metadata_extractor: Arc<MetadataExtractor>,
     /// Stockage des fichiers
     storage: Arc<dyn FileStorage + Send + Sync>,
+    /// Empreintes acoustiques des pistes uploadées
+    content_id: Arc<ContentIdRegistry>,
+    /// Analyses musicales des pistes uploadées
+    track_features: Arc<TrackFeatureStore>,
     /// Événements d'upload
     event_sender: mpsc::UnboundedSender<UploadEvent>,
+    /// Sessions recevant des données : un seul envoi à la fois par upload
+    uploads_in_flight: Arc<parking_lot::Mutex<HashSet<Uuid>>>,
 }
 
-/// Session d'upload d'un fichier
-#[derive(Debug, Clone)]
+/// Session d'upload d'un fichier, persistée dans `temp_directory/<id>.json`
+/// pour reprendre les uploads après un redémarrage
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UploadSession {
     pub id: Uuid,
     pub user_id: i64,
@@ -50,10 +71,38 @@ pub struct UploadSession {
     pub progress: UploadProgress,
     pub metadata: Option<TrackMetadata>,
     pub waveform: Option<WaveformData>,
+    /// Métadonnées fournies par le client à la création (`Upload-Metadata` tus)
+    #[serde(default)]
+    pub client_metadata: HashMap<String, String>,
+    /// Fichier final une fois le traitement terminé
+    #[serde(default)]
+    pub stored_file: Option<StoredFile>,
     pub created_at: SystemTime,
     pub updated_at: SystemTime,
 }
 
+/// Algorithmes de somme de contrôle acceptés pour les chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Sha1,
+    Sha256,
+    Md5,
+}
+
+/// Somme de contrôle attendue pour un chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadChecksum {
+    pub algorithm: ChecksumAlgorithm,
+    pub digest: Vec<u8>,
+}
+
+/// Calcul incrémental d'une somme de contrôle
+enum ChunkHasher {
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+    Md5(md5::Context),
+}
+
 /// Status de l'upload
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum UploadStatus {
@@ -75,6 +124,8 @@ pub enum ProcessingStage {
     ValidatingFile,
     ExtractingMetadata,
     GeneratingWaveform,
+    Fingerprinting,
+    AnalyzingAudio,
     ConvertingFormats,
     UploadingToStorage,
     CreatingThumbnails,
@@ -103,6 +154,17 @@ pub struct TrackMetadata {
     pub year: Option<u32>,
     pub track_number: Option<u32>,
     pub duration: Option<Duration>,
+    /// Tous les artistes crédités ; `artist` les joint pour l'affichage
+    #[serde(default)]
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub album_artist: Option<String>,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub disc_number: Option<u32>,
+    #[serde(default)]
+    pub lyrics: Option<String>,
     
     // Métadonnées techniques
     pub sample_rate: u32,
@@ -118,19 +180,52 @@ pub struct TrackMetadata {
     pub loudness_lufs: Option<f32>,
     pub peak_db: Option<f32>,
     pub dynamic_range: Option<f32>,
+    /// Tempo, tonalité et énergie mesurés sur le signal
+    #[serde(default)]
+    pub analysis: Option<TrackAnalysis>,
+    /// Mesure EBU R128 complète (LUFS, LRA, true peak, album)
+    #[serde(default)]
+    pub loudness: Option<TrackLoudness>,
     
     // Identifiants
     pub isrc: Option<String>,
     pub mbid: Option<String>, // MusicBrainz ID
+    /// Licence de diffusion (nom ou URL)
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Page publique de la piste, écrite dans les fichiers livrés
+    #[serde(default)]
+    pub track_url: Option<String>,
+    /// Pistes déjà en ligne contenant le même enregistrement
+    #[serde(default)]
+    pub content_matches: Vec<ContentMatch>,
     
     // Artwork
     pub has_artwork: bool,
     pub artwork_size: Option<(u32, u32)>,
+    /// Pochette extraite du fichier et ses vignettes
+    #[serde(default)]
+    pub artwork: Option<TrackArtwork>,
     
     // Métadonnées personnalisées
     pub custom_tags: HashMap<String, String>,
 }
 
+/// Pochette embarquée, enregistrée telle quelle avec des vignettes JPEG carrées
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackArtwork {
+    pub mime_type: String,
+    pub path: PathBuf,
+    pub thumbnails: Vec<ArtworkThumbnail>,
+}
+
+/// Vignette carrée de `size` pixels de côté
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtworkThumbnail {
+    pub size: u32,
+    pub path: PathBuf,
+}
+
 /// Configuration de l'upload
 #[derive(Debug, Clone)]
 pub struct UploadConfig {
@@ -151,8 +246,10 @@ pub enum UploadEvent {
     UploadStarted { session_id: Uuid, user_id: i64, filename: String },
     UploadProgress { session_id: Uuid, progress: UploadProgress },
     ProcessingStarted { session_id: Uuid, stage: ProcessingStage },
-    MetadataExtracted { session_id: Uuid, metadata: TrackMetadata },
+    MetadataExtracted { session_id: Uuid, metadata: Box<TrackMetadata> },
     WaveformGenerated { session_id: Uuid, waveform: WaveformData },
+    ContentMatched { session_id: Uuid, matches: Vec<ContentMatch> },
+    AudioAnalyzed { session_id: Uuid, analysis: TrackAnalysis },
     UploadCompleted { session_id: Uuid, track_id: Uuid },
     UploadFailed { session_id: Uuid, reason: String },
     UploadCancelled { session_id: Uuid },
@@ -172,15 +269,52 @@ pub struct MetadataExtractorConfig {
     pub enable_key_detection: bool,
     pub enable_loudness_analysis: bool,
     pub musicbrainz_lookup: bool,
+    pub extract_artwork: bool,
+    /// Côtés des vignettes générées depuis la pochette, en pixels
+    pub artwork_sizes: Vec<u32>,
+    /// Qualité JPEG des vignettes (1 à 100)
+    pub artwork_quality: u8,
 }
 
+/// Flux d'octets lu depuis un backend de stockage
+pub type StorageStream = std::pin::Pin<Box<dyn Stream<Item = Result<Bytes, std::io::Error>> + Send>>;
+
 /// Trait pour le stockage de fichiers
+///
+/// Les identifiants des fichiers uploadés sont de la forme
+/// `<user_id>/<session_id>.<ext>`, ce qui permet de lister les fichiers d'un
+/// utilisateur par préfixe.
 #[async_trait::async_trait]
 pub trait FileStorage: std::fmt::Debug {
-    async fn store_file(&self, file_path: &Path, metadata: &TrackMetadata) -> Result<StoredFile, AppError>;
+    async fn store_file(&self, file_path: &Path, file_id: &str, session: &UploadSession) -> Result<StoredFile, AppError>;
     async fn get_file(&self, file_id: &str) -> Result<StoredFile, AppError>;
     async fn delete_file(&self, file_id: &str) -> Result<(), AppError>;
     async fn list_user_files(&self, user_id: i64) -> Result<Vec<StoredFile>, AppError>;
+    /// Lit `length` octets à partir de `start`, sans charger le fichier en mémoire
+    async fn read_range(&self, file_id: &str, start: u64, length: u64) -> Result<StorageStream, AppError>;
+    /// URL temporaire de téléchargement direct, si le backend en fournit
+    fn presigned_url(&self, _file_id: &str, _expires_in: Duration) -> Option<String> {
+        None
+    }
+    /// Rattache `file_id` à un contenu déjà stocké par le même utilisateur, de
+    /// même SHA-256, même taille et même empreinte des `DEDUP_PROBE_BYTES`
+    /// premiers octets. `None` si le backend ne déduplique pas ou ne connaît pas
+    /// ce contenu pour cet utilisateur.
+    async fn link_duplicate(
+        &self,
+        _checksum: &str,
+        _size: u64,
+        _head_digest: &str,
+        _file_id: &str,
+        _session: &UploadSession,
+    ) -> Result<Option<StoredFile>, AppError> {
+        Ok(None)
+    }
+    /// Index JSON `name` partagé par tous les nœuds qui utilisent ce stockage ;
+    /// `None` si le backend est propre au nœud
+    fn shared_index(&self, _name: &str) -> Option<Arc<dyn IndexStore>> {
+        None
+    }
 }
 
 /// Fichier stocké
@@ -229,27 +363,45 @@ impl Default for UploadConfig {
 }
 
 impl UploadManager {
-    /// Crée un nouveau gestionnaire d'uploads
+    /// Crée un nouveau gestionnaire d'uploads stockant sur le disque local
     pub async fn new(config: UploadConfig) -> Result<Self, AppError> {
+        let storage = Arc::new(LocalFileStorage::new(
+            config.upload_directory.clone(),
+            "http://localhost:8080/uploads".to_string(),
+        ));
+        Self::with_storage(config, storage).await
+    }
+    
+    /// Crée un gestionnaire d'uploads sur un backend de stockage donné
+    pub async fn with_storage(
+        config: UploadConfig,
+        storage: Arc<dyn FileStorage + Send + Sync>,
+    ) -> Result<Self, AppError> {
         let (event_sender, _) = mpsc::unbounded_channel();
         
         // Créer les répertoires si nécessaire
         fs::create_dir_all(&config.upload_directory).await?;
         fs::create_dir_all(&config.temp_directory).await?;
+        fs::create_dir_all(config.upload_directory.join("metadata")).await?;
+        let content_id_index = storage
+            .shared_index(CONTENT_ID_INDEX_NAME)
+            .unwrap_or_else(|| Arc::new(LocalIndexFile::new(config.upload_directory.join("fingerprints.json"))));
+        let content_id = ContentIdRegistry::open(content_id_index).await?;
+        let track_features = TrackFeatureStore::open(config.upload_directory.join("features")).await?;
         
-        let storage = Arc::new(LocalFileStorage::new(
-            config.upload_directory.clone(),
-            "http://localhost:8080/uploads".to_string(),
-        ));
-        
-        Ok(Self {
+        let manager = Self {
             active_uploads: Arc::new(RwLock::new(HashMap::new())),
             waveform_generator: Arc::new(WaveformGenerator::new()),
             metadata_extractor: Arc::new(MetadataExtractor::new()),
             storage,
+            content_id: Arc::new(content_id),
+            track_features: Arc::new(track_features),
             config,
             event_sender,
-        })
+            uploads_in_flight: Arc::new(parking_lot::Mutex::new(HashSet::new())),
+        };
+        manager.restore_sessions().await?;
+        Ok(manager)
     }
     
     /// Démarre une session d'upload
@@ -259,6 +411,18 @@ impl UploadManager {
         filename: String,
         file_size: u64,
         content_type: String,
+    ) -> Result<Uuid, AppError> {
+        self.create_upload(user_id, filename, file_size, content_type, HashMap::new()).await
+    }
+    
+    /// Démarre une session d'upload en conservant les métadonnées du client
+    pub async fn create_upload(
+        &self,
+        user_id: i64,
+        filename: String,
+        file_size: u64,
+        content_type: String,
+        client_metadata: HashMap<String, String>,
     ) -> Result<Uuid, AppError> {
         // Validation de base
         self.validate_upload_request(&filename, file_size, &content_type)?;
@@ -287,11 +451,14 @@ impl UploadManager {
             },
             metadata: None,
             waveform: None,
+            client_metadata,
+            stored_file: None,
             created_at: SystemTime::now(),
             updated_at: SystemTime::now(),
         };
         
         // Enregistrer la session
+        self.persist_session(&session).await?;
         self.active_uploads.write().await.insert(session_id, session);
         
         // Émettre l'événement
@@ -312,8 +479,7 @@ impl UploadManager {
         chunk_data: &[u8],
         chunk_offset: u64,
     ) -> Result<(), AppError> {
-        let mut sessions = self.active_uploads.write().await;
-        let session = sessions.get_mut(&session_id)
+        let session = self.active_uploads.read().await.get(&session_id).cloned()
             .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
         
         // Vérifier le status
@@ -325,8 +491,162 @@ impl UploadManager {
             }),
         }
         
-        // Mettre à jour le progress
-        let new_uploaded = chunk_offset + chunk_data.len() as u64;
+        // Écrire le chunk à sa place dans le fichier temporaire
+        let temp_path = self.temp_file_path(&session);
+        let mut file = fs::OpenOptions::new().create(true).write(true).truncate(false).open(&temp_path).await?;
+        file.seek(std::io::SeekFrom::Start(chunk_offset)).await?;
+        file.write_all(chunk_data).await?;
+        file.flush().await?;
+        
+        let received = chunk_offset + chunk_data.len() as u64;
+        let duplicate = if chunk_offset == 0 && received >= dedup_probe_len(session.file_size) {
+            self.find_duplicate(&session).await?
+        } else {
+            None
+        };
+        self.finish_write(session_id, received, duplicate).await?;
+        Ok(())
+    }
+    
+    /// Ajoute des octets à un upload à partir de `offset`, qui doit être
+    /// l'offset courant (PATCH tus), et renvoie le nouvel offset. Sans somme de
+    /// contrôle, les octets reçus avant une coupure sont gardés pour la reprise ;
+    /// avec, le chunk entier est rejeté s'il est incomplet ou corrompu.
+    pub async fn append_upload<S, E>(
+        &self,
+        session_id: Uuid,
+        offset: u64,
+        mut body: S,
+        checksum: Option<UploadChecksum>,
+    ) -> Result<u64, AppError>
+    where
+        S: Stream<Item = Result<Bytes, E>> + Unpin,
+        E: std::fmt::Display,
+    {
+        // Réservation avant la vérification de l'offset : un PATCH concurrent qui
+        // vient de se terminer ne peut plus faire écrire celui-ci à un offset périmé
+        let _guard = InFlightGuard::acquire(&self.uploads_in_flight, session_id)?;
+        
+        let (temp_path, file_size) = {
+            let sessions = self.active_uploads.read().await;
+            let session = sessions.get(&session_id)
+                .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+            if !matches!(session.status, UploadStatus::Uploading { .. }) {
+                return Err(AppError::InvalidUploadState {
+                    current: format!("{:?}", session.status),
+                    expected: "Uploading".to_string(),
+                });
+            }
+            if offset != session.progress.uploaded_bytes {
+                return Err(AppError::UploadOffsetMismatch { expected: session.progress.uploaded_bytes, got: offset });
+            }
+            (self.temp_file_path(session), session.file_size)
+        };
+        
+        let mut file = fs::OpenOptions::new().create(true).write(true).truncate(false).open(&temp_path).await?;
+        file.set_len(offset).await?;
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        
+        let mut hasher = checksum.as_ref().map(|checksum| ChunkHasher::new(checksum.algorithm));
+        let mut written = 0u64;
+        let mut interrupted = None;
+        while let Some(chunk) = body.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    interrupted = Some(e.to_string());
+                    break;
+                }
+            };
+            if offset + written + chunk.len() as u64 > file_size {
+                file.set_len(offset).await?;
+                return Err(AppError::ValidationError(format!(
+                    "Chunk beyond declared upload length: {} bytes", file_size
+                )));
+            }
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&chunk);
+            }
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+        
+        if let (Some(expected), Some(hasher)) = (checksum, hasher) {
+            if interrupted.is_some() || hasher.finalize() != expected.digest {
+                file.set_len(offset).await?;
+                return match interrupted {
+                    Some(reason) => Err(AppError::NetworkError { message: reason }),
+                    None => Err(AppError::ChecksumMismatch { algorithm: expected.algorithm.name().to_string() }),
+                };
+            }
+        }
+        if let Some(reason) = interrupted {
+            warn!("Upload {} interrompu après {} octets: {}", session_id, written, reason);
+        }
+        
+        let new_offset = offset + written;
+        let probe = dedup_probe_len(file_size);
+        let duplicate = if offset < probe && new_offset >= probe {
+            let session = self.active_uploads.read().await.get(&session_id).cloned()
+                .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+            self.find_duplicate(&session).await?
+        } else {
+            None
+        };
+        self.finish_write(session_id, new_offset, duplicate).await
+    }
+    
+    /// Enregistre des octets écrits et persiste la session ; un doublon
+    /// reconnu termine l'upload sans attendre la suite. Renvoie l'offset retenu.
+    async fn finish_write(
+        &self,
+        session_id: Uuid,
+        received: u64,
+        duplicate: Option<StoredFile>,
+    ) -> Result<u64, AppError> {
+        let mut sessions = self.active_uploads.write().await;
+        let session = sessions.get_mut(&session_id)
+            .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+        let received = match duplicate {
+            Some(stored_file) => {
+                session.stored_file = Some(stored_file);
+                session.file_size
+            }
+            None => received,
+        };
+        self.record_progress(session, received);
+        let session = session.clone();
+        drop(sessions);
+        self.persist_session(&session).await?;
+        Ok(received)
+    }
+    
+    /// Compare le début d'un upload au contenu déjà stocké par le même
+    /// utilisateur dont le client a déclaré le SHA-256 (métadonnée `sha256`,
+    /// hexadécimal)
+    async fn find_duplicate(&self, session: &UploadSession) -> Result<Option<StoredFile>, AppError> {
+        let Some(checksum) = session.client_metadata.get(DECLARED_SHA256_KEY)
+            .map(|checksum| checksum.trim().to_lowercase())
+            .filter(|checksum| checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit()))
+        else {
+            return Ok(None);
+        };
+        
+        let probe = dedup_probe_len(session.file_size);
+        let head_digest = file_head_sha256(&self.temp_file_path(session), probe).await?;
+        let linked = self.storage
+            .link_duplicate(&checksum, session.file_size, &head_digest, &stored_file_id(session), session)
+            .await?;
+        if linked.is_some() {
+            info!("Upload {} dédupliqué: contenu {} déjà stocké", session.id, checksum);
+        }
+        Ok(linked)
+    }
+    
+    /// Enregistre l'avancement d'un upload et lance le processing une fois complet
+    fn record_progress(&self, session: &mut UploadSession, new_uploaded: u64) {
+        let session_id = session.id;
         session.progress.uploaded_bytes = new_uploaded;
         session.updated_at = SystemTime::now();
         
@@ -360,12 +680,118 @@ impl UploadManager {
                 bytes_received: new_uploaded 
             };
         }
+    }
+    
+    /// Fichier d'état d'une session
+    fn state_file_path(&self, session_id: Uuid) -> PathBuf {
+        self.config.temp_directory.join(format!("{}.json", session_id))
+    }
+    
+    /// Écrit l'état d'une session sur disque (écriture puis renommage atomique)
+    async fn persist_session(&self, session: &UploadSession) -> Result<(), AppError> {
+        let data = serde_json::to_vec(session).map_err(|_| AppError::SerializationError)?;
+        let path = self.state_file_path(session.id);
+        let partial = path.with_extension("json.part");
+        fs::write(&partial, data).await?;
+        fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+    
+    /// Recharge les sessions persistées : les uploads en cours reprennent à la
+    /// taille réelle du fichier temporaire, les processing interrompus sont relancés
+    async fn restore_sessions(&self) -> Result<(), AppError> {
+        let mut entries = fs::read_dir(&self.config.temp_directory).await?;
+        let mut to_process = Vec::new();
+        let mut restored = 0;
+        
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let mut session: UploadSession = match fs::read(&path).await.map(|data| serde_json::from_slice(&data)) {
+                Ok(Ok(session)) => session,
+                _ => {
+                    warn!("État d'upload illisible ignoré: {:?}", path);
+                    continue;
+                }
+            };
+            
+            match session.status {
+                UploadStatus::Uploading { .. } => {
+                    let received = fs::metadata(self.temp_file_path(&session)).await
+                        .map(|metadata| metadata.len().min(session.file_size))
+                        .unwrap_or(0);
+                    session.progress.uploaded_bytes = received;
+                    session.status = UploadStatus::Uploading { bytes_received: received };
+                }
+                UploadStatus::Processing { .. } => to_process.push(session.id),
+                _ => continue,
+            }
+            self.active_uploads.write().await.insert(session.id, session);
+            restored += 1;
+        }
+        
+        if restored > 0 {
+            info!("{} sessions d'upload restaurées", restored);
+        }
+        for session_id in to_process {
+            let manager = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = manager.process_uploaded_file(session_id).await {
+                    error!("Erreur processing fichier {}: {:?}", session_id, e);
+                }
+            });
+        }
+        Ok(())
+    }
+    
+    /// Offset courant d'un upload, taille déclarée et métadonnées client
+    pub async fn upload_offset(&self, session_id: Uuid) -> Result<(u64, UploadSession), AppError> {
+        let sessions = self.active_uploads.read().await;
+        let session = sessions.get(&session_id)
+            .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+        Ok((session.progress.uploaded_bytes, session.clone()))
+    }
+    
+    /// Supprime un upload, son fichier temporaire et son état (terminaison tus)
+    pub async fn terminate_upload(&self, session_id: Uuid) -> Result<(), AppError> {
+        let mut sessions = self.active_uploads.write().await;
+        let session = sessions.get(&session_id)
+            .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+        if matches!(session.status, UploadStatus::Processing { .. }) {
+            return Err(AppError::InvalidUploadState {
+                current: format!("{:?}", session.status),
+                expected: "Uploading".to_string(),
+            });
+        }
+        let _guard = InFlightGuard::acquire(&self.uploads_in_flight, session_id)?;
+        let session = sessions.remove(&session_id)
+            .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+        drop(sessions);
+        
+        let _ = fs::remove_file(self.temp_file_path(&session)).await;
+        let _ = fs::remove_file(self.state_file_path(session_id)).await;
+        let _ = self.event_sender.send(UploadEvent::UploadCancelled { session_id });
         
+        info!("Upload {} supprimé", session_id);
         Ok(())
     }
     
+    /// Configuration des uploads
+    pub fn config(&self) -> &UploadConfig {
+        &self.config
+    }
+    
     /// Traite un fichier uploadé
     async fn process_uploaded_file(&self, session_id: Uuid) -> Result<(), AppError> {
+        // Upload dédupliqué : seul le début a été reçu, le reste est relu depuis le stockage
+        let linked = self.active_uploads.read().await.get(&session_id)
+            .and_then(|session| session.stored_file.clone());
+        if let Some(linked) = &linked {
+            self.fetch_stored_copy(session_id, linked).await?;
+        }
+        
         // Étape 1: Extraction des métadonnées
         self.update_processing_stage(session_id, ProcessingStage::ExtractingMetadata).await?;
         let _metadata = self.extract_metadata(session_id).await?;
@@ -377,16 +803,65 @@ impl UploadManager {
             self.update_session_waveform(session_id, waveform).await?;
         }
         
-        // Étape 3: Stockage final
-        self.update_processing_stage(session_id, ProcessingStage::UploadingToStorage).await?;
-        let stored_file = self.store_file(session_id, &_metadata).await?;
+        // Étape 3: Empreinte acoustique et recherche des enregistrements déjà en ligne
+        if self.metadata_extractor.config.enable_fingerprinting {
+            self.update_processing_stage(session_id, ProcessingStage::Fingerprinting).await?;
+            if let Some(reason) = self.identify_content(session_id).await? {
+                return self.fail_upload(session_id, reason).await;
+            }
+        }
+        
+        // Étape 4: Tempo, tonalité et énergie
+        let extractor = &self.metadata_extractor.config;
+        if extractor.enable_bpm_detection || extractor.enable_key_detection {
+            self.update_processing_stage(session_id, ProcessingStage::AnalyzingAudio).await?;
+            self.analyze_audio(session_id).await?;
+        }
+        
+        // Étape 5: Stockage final
+        let stored_file = match linked {
+            Some(stored_file) => stored_file,
+            None => {
+                self.update_processing_stage(session_id, ProcessingStage::UploadingToStorage).await?;
+                self.store_file(session_id).await?
+            }
+        };
         
         // Marquer comme terminé
-        self.complete_upload(session_id, stored_file.id).await?;
+        self.complete_upload(session_id, stored_file).await?;
         
         Ok(())
     }
     
+    /// Recopie un fichier stocké dans le fichier temporaire de la session
+    async fn fetch_stored_copy(&self, session_id: Uuid, stored_file: &StoredFile) -> Result<(), AppError> {
+        let temp_path = self.session_temp_path(session_id).await?;
+        let mut stream = self.storage.read_range(&stored_file.id, 0, stored_file.size).await?;
+        let mut file = fs::File::create(&temp_path).await?;
+        while let Some(chunk) = stream.next().await {
+            file.write_all(&chunk?).await?;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    
+    /// Fichier temporaire où sont assemblés les chunks d'une session
+    fn temp_file_path(&self, session: &UploadSession) -> PathBuf {
+        let extension = Path::new(&session.filename)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        self.config.temp_directory.join(format!("{}.{}", session.id, extension))
+    }
+    
+    /// Chemin temporaire du fichier d'une session
+    async fn session_temp_path(&self, session_id: Uuid) -> Result<PathBuf, AppError> {
+        let sessions = self.active_uploads.read().await;
+        let session = sessions.get(&session_id)
+            .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+        Ok(self.temp_file_path(session))
+    }
+    
     /// Valide une demande d'upload
     fn validate_upload_request(
         &self,
@@ -450,44 +925,33 @@ impl UploadManager {
     
     /// Extrait les métadonnées d'un fichier
     async fn extract_metadata(&self, session_id: Uuid) -> Result<TrackMetadata, AppError> {
-        // Simulation d'extraction - en production, utiliser des libs comme `lofty` ou `mp3-metadata`
-        let metadata = TrackMetadata {
-            title: Some("Uploaded Track".to_string()),
-            artist: Some("Unknown Artist".to_string()),
-            album: None,
-            genre: Some("Electronic".to_string()),
-            year: Some(2024),
-            track_number: None,
-            duration: Some(Duration::from_secs(180)), // 3 minutes
-            
-            sample_rate: 44100,
-            bitrate: 320000,
-            channels: 2,
-            bit_depth: Some(16),
-            codec: "MP3".to_string(),
-            file_format: "MPEG".to_string(),
-            
-            bpm: Some(128.0),
-            key: Some("C major".to_string()),
-            loudness_lufs: Some(-14.0),
-            peak_db: Some(-1.0),
-            dynamic_range: Some(8.5),
-            
-            isrc: None,
-            mbid: None,
-            
-            has_artwork: false,
-            artwork_size: None,
-            
-            custom_tags: HashMap::new(),
-        };
+        let path = self.session_temp_path(session_id).await?;
+        let (mut metadata, tags) = self.metadata_extractor.read_file(path).await?;
+        
+        // Pochette embarquée : original et vignettes sous `artwork/<session>/`
+        if let Some(picture) = tags.cover().filter(|_| self.metadata_extractor.config.extract_artwork) {
+            let directory = self.config.upload_directory.join("artwork").join(session_id.to_string());
+            match self.metadata_extractor.save_artwork(picture.clone(), directory).await {
+                Ok((artwork, size)) => metadata.set_artwork(artwork, size),
+                Err(e) => warn!("Pochette illisible pour {}: {}", session_id, e),
+            }
+        }
+        
+        // Mesure EBU R128 sur le fichier reçu
+        if self.metadata_extractor.config.enable_loudness_analysis {
+            let path = self.session_temp_path(session_id).await?;
+            match self.metadata_extractor.analyze_loudness(path).await {
+                Ok(measured) => metadata.set_loudness(measured),
+                Err(e) => warn!("Analyse de loudness impossible pour {}: {}", session_id, e),
+            }
+        }
         
         // Mettre à jour la session
         self.update_session_metadata(session_id, metadata.clone()).await?;
         
         let _ = self.event_sender.send(UploadEvent::MetadataExtracted {
             session_id,
-            metadata: metadata.clone(),
+            metadata: Box::new(metadata.clone()),
         });
         
         Ok(metadata)
@@ -500,7 +964,8 @@ impl UploadManager {
         _metadata: &TrackMetadata,
     ) -> Result<WaveformData, AppError> {
         // Utiliser le générateur de waveform
-        let waveform = self.waveform_generator.generate_from_file("dummy_path").await?;
+        let path = self.session_temp_path(session_id).await?;
+        let waveform = self.waveform_generator.generate_from_file(&path).await?;
         
         let _ = self.event_sender.send(UploadEvent::WaveformGenerated {
             session_id,
@@ -510,6 +975,79 @@ impl UploadManager {
         Ok(waveform)
     }
     
+    /// Empreinte le fichier reçu, l'ajoute au registre sous l'identifiant de
+    /// la piste et signale les pistes correspondantes. Renvoie le motif du
+    /// refus quand le fichier reprend un enregistrement retiré par DMCA.
+    async fn identify_content(&self, session_id: Uuid) -> Result<Option<String>, AppError> {
+        let (path, content_key) = {
+            let sessions = self.active_uploads.read().await;
+            let session = sessions.get(&session_id)
+                .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+            (self.temp_file_path(session), content_key(session))
+        };
+        let fingerprint = match self.metadata_extractor.fingerprint(path).await {
+            Ok(fingerprint) if !fingerprint.is_empty() => fingerprint,
+            Ok(_) => return Ok(None),
+            Err(e) => {
+                warn!("Empreinte acoustique impossible pour {}: {}", session_id, e);
+                return Ok(None);
+            }
+        };
+        
+        // Un enregistrement retiré n'est pas remis en ligne, ni ajouté au registre,
+        // y compris quand le retrait a été prononcé sur un autre nœud
+        self.content_id.refresh().await?;
+        let matches = self.content_id.find_matches(&fingerprint, MATCH_SIMILARITY_THRESHOLD);
+        if let Some(taken_down) = matches.iter().find(|m| m.takedown_id.is_some() && m.fingerprint.track_id != content_key) {
+            warn!(
+                "Upload {} refusé : enregistrement de la piste {} retirée (DMCA #{:?}), similarité {:.2}",
+                session_id, taken_down.fingerprint.track_id, taken_down.takedown_id, taken_down.fingerprint.similarity
+            );
+            return Ok(Some(format!(
+                "Enregistrement retiré suite à une demande DMCA (#{})",
+                taken_down.takedown_id.unwrap_or_default()
+            )));
+        }
+        
+        let matches = self.content_id
+            .register(content_key, fingerprint, MATCH_SIMILARITY_THRESHOLD)
+            .await?;
+        if matches.is_empty() {
+            return Ok(None);
+        }
+        
+        let mut sessions = self.active_uploads.write().await;
+        if let Some(metadata) = sessions.get_mut(&session_id).and_then(|session| session.metadata.as_mut()) {
+            metadata.content_matches = matches.clone();
+        }
+        drop(sessions);
+        
+        let _ = self.event_sender.send(UploadEvent::ContentMatched { session_id, matches });
+        Ok(None)
+    }
+    
+    /// Mesure tempo, tonalité et énergie et les enregistre pour la piste
+    async fn analyze_audio(&self, session_id: Uuid) -> Result<(), AppError> {
+        let path = self.session_temp_path(session_id).await?;
+        let analysis = match self.metadata_extractor.analyze_music(path).await {
+            Ok(analysis) => analysis,
+            Err(e) => {
+                warn!("Analyse musicale impossible pour {}: {}", session_id, e);
+                return Ok(());
+            }
+        };
+        
+        let mut sessions = self.active_uploads.write().await;
+        if let Some(metadata) = sessions.get_mut(&session_id).and_then(|session| session.metadata.as_mut()) {
+            self.metadata_extractor.set_analysis(metadata, analysis.clone());
+        }
+        drop(sessions);
+        
+        self.track_features.save(session_id, analysis.clone()).await?;
+        let _ = self.event_sender.send(UploadEvent::AudioAnalyzed { session_id, analysis });
+        Ok(())
+    }
+    
     /// Met à jour les métadonnées d'une session
     async fn update_session_metadata(
         &self,
@@ -538,42 +1076,178 @@ impl UploadManager {
         Ok(())
     }
     
-    /// Stocke le fichier final
-    async fn store_file(
-        &self,
-        session_id: Uuid,
-        metadata: &TrackMetadata,
-    ) -> Result<StoredFile, AppError> {
-        // Simulation - en production, uploader vers S3/GCS/etc.
-        let file_path = self.config.upload_directory.join(format!("{}.mp3", session_id));
-        self.storage.store_file(&file_path, metadata).await
+    /// Envoie le fichier assemblé vers le backend de stockage
+    async fn store_file(&self, session_id: Uuid) -> Result<StoredFile, AppError> {
+        let session = self.active_uploads.read().await.get(&session_id).cloned()
+            .ok_or_else(|| AppError::UploadSessionNotFound { session_id: session_id.to_string() })?;
+        self.storage.store_file(&self.temp_file_path(&session), &stored_file_id(&session), &session).await
+    }
+    
+    /// Registre des empreintes acoustiques, partagé avec la modération
+    pub fn content_id(&self) -> Arc<ContentIdRegistry> {
+        self.content_id.clone()
+    }
+    
+    /// Analyses musicales des pistes (BPM, tonalité, énergie)
+    pub fn track_features(&self) -> Arc<TrackFeatureStore> {
+        self.track_features.clone()
+    }
+    
+    /// Backend de stockage des fichiers finaux
+    pub fn storage(&self) -> Arc<dyn FileStorage + Send + Sync> {
+        self.storage.clone()
     }
     
     /// Termine un upload avec succès
     async fn complete_upload(
         &self,
         session_id: Uuid,
-        track_id: String,
+        stored_file: StoredFile,
     ) -> Result<(), AppError> {
         let mut sessions = self.active_uploads.write().await;
+        let mut temp_file = None;
+        let mut metadata = None;
         if let Some(session) = sessions.get_mut(&session_id) {
+            metadata = session.metadata.clone();
             session.status = UploadStatus::Completed;
             session.progress.processing_progress = 1.0;
+            session.stored_file = Some(stored_file);
             session.updated_at = SystemTime::now();
+            temp_file = Some(self.temp_file_path(session));
             
             let _ = self.event_sender.send(UploadEvent::UploadCompleted {
                 session_id,
-                track_id: Uuid::parse_str(&track_id).unwrap_or_else(|_| Uuid::new_v4()),
+                track_id: session_id,
             });
         }
+        drop(sessions);
+        
+        // Les téléchargements relisent les métadonnées après redémarrage
+        if let Some(metadata) = metadata {
+            self.persist_metadata(session_id, &metadata).await?;
+        }
+        
+        // Plus rien à reprendre : le fichier vit désormais dans le stockage
+        if let Some(temp_file) = temp_file {
+            let _ = fs::remove_file(temp_file).await;
+        }
+        let _ = fs::remove_file(self.state_file_path(session_id)).await;
         Ok(())
     }
     
+    /// Refuse un upload pendant son traitement : rien n'est conservé
+    async fn fail_upload(&self, session_id: Uuid, reason: String) -> Result<(), AppError> {
+        let mut sessions = self.active_uploads.write().await;
+        let Some(session) = sessions.get_mut(&session_id) else {
+            return Ok(());
+        };
+        session.status = UploadStatus::Failed { reason: reason.clone() };
+        session.updated_at = SystemTime::now();
+        let temp_file = self.temp_file_path(session);
+        let linked = session.stored_file.take();
+        drop(sessions);
+        
+        // Upload dédupliqué : la référence vers le fichier existant est libérée
+        if let Some(linked) = linked {
+            if let Err(e) = self.storage.delete_file(&linked.id).await {
+                warn!("Impossible de libérer le fichier {} de l'upload {}: {}", linked.id, session_id, e);
+            }
+        }
+        let _ = fs::remove_file(temp_file).await;
+        let _ = fs::remove_file(self.state_file_path(session_id)).await;
+        let _ = self.event_sender.send(UploadEvent::UploadFailed { session_id, reason });
+        Ok(())
+    }
+    
+    /// Fichier des métadonnées d'une piste terminée
+    fn metadata_file_path(&self, session_id: Uuid) -> PathBuf {
+        self.config.upload_directory.join("metadata").join(format!("{}.json", session_id))
+    }
+    
+    async fn persist_metadata(&self, session_id: Uuid, metadata: &TrackMetadata) -> Result<(), AppError> {
+        let data = serde_json::to_vec(metadata).map_err(|_| AppError::SerializationError)?;
+        let path = self.metadata_file_path(session_id);
+        let partial = path.with_extension("json.part");
+        fs::write(&partial, data).await?;
+        fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+    
+    /// Métadonnées courantes d'une piste : session en mémoire, sinon disque
+    pub async fn track_metadata(&self, session_id: Uuid) -> Option<TrackMetadata> {
+        let in_memory = self.active_uploads.read().await.get(&session_id).and_then(|s| s.metadata.clone());
+        if in_memory.is_some() {
+            return in_memory;
+        }
+        let data = fs::read(self.metadata_file_path(session_id)).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+    
+    /// Remplace les métadonnées d'une piste (titre, artistes, licence…
+    /// modifiés sur la plateforme) ; les prochains fichiers livrés en tiennent compte
+    pub async fn update_track_metadata(&self, session_id: Uuid, metadata: TrackMetadata) -> Result<(), AppError> {
+        // `Some(terminée)` si la session est en mémoire
+        let in_memory = self.active_uploads.write().await.get_mut(&session_id).map(|session| {
+            session.metadata = Some(metadata.clone());
+            session.updated_at = SystemTime::now();
+            session.status == UploadStatus::Completed
+        });
+        match in_memory {
+            // Encore en traitement : persisté à la fin de l'upload
+            Some(false) => Ok(()),
+            Some(true) => self.persist_metadata(session_id, &metadata).await,
+            None if fs::try_exists(self.metadata_file_path(session_id)).await.unwrap_or(false) => {
+                self.persist_metadata(session_id, &metadata).await
+            }
+            None => Err(AppError::UploadSessionNotFound { session_id: session_id.to_string() }),
+        }
+    }
+    
+    /// Tags à écrire dans les fichiers livrés d'une piste, pochette comprise
+    pub async fn track_tags(&self, session_id: Uuid) -> Option<TrackTags> {
+        let metadata = self.track_metadata(session_id).await?;
+        let mut track_tags = metadata.to_tags();
+        if let Some(artwork) = &metadata.artwork {
+            match fs::read(&artwork.path).await {
+                Ok(data) => track_tags.pictures.push(EmbeddedPicture {
+                    picture_type: tags::PICTURE_FRONT_COVER,
+                    mime_type: artwork.mime_type.clone(),
+                    description: String::new(),
+                    data,
+                }),
+                Err(e) => warn!("Pochette {} illisible: {}", artwork.path.display(), e),
+            }
+        }
+        Some(track_tags)
+    }
+    
     /// Obtient le status d'un upload
     pub async fn get_upload_status(&self, session_id: Uuid) -> Option<UploadSession> {
         self.active_uploads.read().await.get(&session_id).cloned()
     }
     
+    /// Calcule le loudness commun d'un album à partir de ses sessions analysées
+    /// et le reporte dans leurs métadonnées. Renvoie `(LUFS, dBTP)` de l'album.
+    pub async fn apply_album_loudness(&self, session_ids: &[Uuid]) -> Option<(f32, f32)> {
+        let mut sessions = self.active_uploads.write().await;
+        let mut tracks: Vec<TrackLoudness> = session_ids
+            .iter()
+            .filter_map(|id| sessions.get(id)?.metadata.as_ref()?.loudness)
+            .collect();
+        let album = loudness::compute_album_loudness(&mut tracks)?;
+
+        for id in session_ids {
+            if let Some(measured) = sessions.get_mut(id)
+                .and_then(|session| session.metadata.as_mut())
+                .and_then(|metadata| metadata.loudness.as_mut())
+            {
+                measured.album_lufs = Some(album.0);
+                measured.album_true_peak_dbtp = Some(album.1);
+            }
+        }
+        Some(album)
+    }
+    
     /// Annule un upload
     pub async fn cancel_upload(&self, session_id: Uuid) -> Result<(), AppError> {
         let mut sessions = self.active_uploads.write().await;
@@ -581,8 +1255,12 @@ impl UploadManager {
             session.status = UploadStatus::Cancelled;
             session.updated_at = SystemTime::now();
             
+            let _ = fs::remove_file(self.temp_file_path(session)).await;
             let _ = self.event_sender.send(UploadEvent::UploadCancelled { session_id });
         }
+        drop(sessions);
+        
+        let _ = fs::remove_file(self.state_file_path(session_id)).await;
         Ok(())
     }
 }
@@ -595,7 +1273,77 @@ impl Clone for UploadManager {
             waveform_generator: self.waveform_generator.clone(),
             metadata_extractor: self.metadata_extractor.clone(),
             storage: self.storage.clone(),
+            content_id: self.content_id.clone(),
+            track_features: self.track_features.clone(),
             event_sender: self.event_sender.clone(),
+            uploads_in_flight: self.uploads_in_flight.clone(),
+        }
+    }
+}
+
+/// Réservation d'une session pendant la réception de données
+struct InFlightGuard {
+    uploads: Arc<parking_lot::Mutex<HashSet<Uuid>>>,
+    session_id: Uuid,
+}
+
+impl InFlightGuard {
+    fn acquire(uploads: &Arc<parking_lot::Mutex<HashSet<Uuid>>>, session_id: Uuid) -> Result<Self, AppError> {
+        if !uploads.lock().insert(session_id) {
+            return Err(AppError::AlreadyProcessing);
+        }
+        Ok(Self { uploads: uploads.clone(), session_id })
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.uploads.lock().remove(&self.session_id);
+    }
+}
+
+impl ChecksumAlgorithm {
+    /// Algorithme désigné par son nom tus (`sha1`, `sha256`, `md5`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            "md5" => Some(Self::Md5),
+            _ => None,
+        }
+    }
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Md5 => "md5",
+        }
+    }
+}
+
+impl ChunkHasher {
+    fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha1 => Self::Sha1(sha1::Sha1::new()),
+            ChecksumAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+            ChecksumAlgorithm::Md5 => Self::Md5(md5::Context::new()),
+        }
+    }
+    
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Md5(context) => context.consume(data),
+        }
+    }
+    
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Sha1(hasher) => hasher.finalize().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+            Self::Md5(context) => context.compute().0.to_vec(),
         }
     }
 }
@@ -609,11 +1357,258 @@ impl MetadataExtractor {
                 enable_key_detection: true,
                 enable_loudness_analysis: true,
                 musicbrainz_lookup: false, // Désactivé par défaut
+                extract_artwork: true,
+                artwork_sizes: vec![64, 300, 600],
+                artwork_quality: 85,
             },
         }
     }
 }
 
+impl MetadataExtractor {
+    /// Tags et caractéristiques techniques d'un fichier, lus dans un thread bloquant
+    pub async fn read_file(&self, path: PathBuf) -> Result<(TrackMetadata, TrackTags), AppError> {
+        tokio::task::spawn_blocking(move || TrackMetadata::read_file(&path))
+            .await
+            .map_err(|e| AppError::InternalError { message: e.to_string() })?
+    }
+    
+    /// Enregistre une pochette et ses vignettes dans `directory` ; renvoie
+    /// aussi ses dimensions quand l'image a pu être décodée
+    pub async fn save_artwork(
+        &self,
+        picture: EmbeddedPicture,
+        directory: PathBuf,
+    ) -> Result<(TrackArtwork, Option<(u32, u32)>), AppError> {
+        let sizes = self.config.artwork_sizes.clone();
+        let quality = self.config.artwork_quality;
+        tokio::task::spawn_blocking(move || write_artwork(&picture, &directory, &sizes, quality))
+            .await
+            .map_err(|e| AppError::InternalError { message: e.to_string() })?
+    }
+    
+    /// Mesure EBU R128 d'un fichier, décodé dans un thread bloquant
+    pub async fn analyze_loudness(&self, path: PathBuf) -> Result<TrackLoudness, AppError> {
+        tokio::task::spawn_blocking(move || loudness::analyze_file(&path))
+            .await
+            .map_err(|e| AppError::InternalError { message: e.to_string() })?
+    }
+    
+    /// Empreinte acoustique d'un fichier, calculée dans un thread bloquant
+    pub async fn fingerprint(&self, path: PathBuf) -> Result<AudioFingerprint, AppError> {
+        tokio::task::spawn_blocking(move || fingerprint::analyze_file(&path))
+            .await
+            .map_err(|e| AppError::InternalError { message: e.to_string() })?
+    }
+    
+    /// Tempo, tonalité et énergie d'un fichier, analysés dans un thread bloquant
+    pub async fn analyze_music(&self, path: PathBuf) -> Result<TrackAnalysis, AppError> {
+        tokio::task::spawn_blocking(move || analysis::analyze_file(&path))
+            .await
+            .map_err(|e| AppError::InternalError { message: e.to_string() })?
+    }
+    
+    /// Reporte une analyse dans les métadonnées, selon les détections activées
+    pub fn set_analysis(&self, metadata: &mut TrackMetadata, analysis: TrackAnalysis) {
+        // La mesure remplace la valeur des tags, sans l'effacer si elle échoue
+        if self.config.enable_bpm_detection {
+            metadata.bpm = analysis.bpm.or(metadata.bpm);
+        }
+        if self.config.enable_key_detection {
+            metadata.key = analysis.key.map(|key| key.to_string()).or(metadata.key.take());
+        }
+        metadata.analysis = Some(analysis);
+    }
+}
+
+/// Écrit l'original de la pochette puis ses vignettes ; une image dans un
+/// format non décodable (GIF, WebP…) est conservée sans vignettes
+fn write_artwork(
+    picture: &EmbeddedPicture,
+    directory: &Path,
+    sizes: &[u32],
+    quality: u8,
+) -> Result<(TrackArtwork, Option<(u32, u32)>), AppError> {
+    let format = ImageFormat::sniff(&picture.data)
+        .ok_or_else(|| AppError::InvalidData { message: format!("Pochette {} non reconnue", picture.mime_type) })?;
+    std::fs::create_dir_all(directory)?;
+    let path = directory.join(format!("cover.{}", format.extension()));
+    std::fs::write(&path, &picture.data)?;
+    
+    let mut artwork = TrackArtwork { mime_type: format.mime_type().to_string(), path, thumbnails: Vec::new() };
+    let image = match RgbImage::decode(&picture.data) {
+        Ok(image) => image,
+        Err(e) => {
+            warn!("Vignettes non générées pour {}: {}", artwork.path.display(), e);
+            return Ok((artwork, None));
+        }
+    };
+    for &size in sizes {
+        let path = directory.join(format!("cover_{}.jpg", size));
+        std::fs::write(&path, image.thumbnail(size).to_jpeg(quality)?)?;
+        artwork.thumbnails.push(ArtworkThumbnail { size, path });
+    }
+    Ok((artwork, Some((image.width, image.height))))
+}
+
+impl TrackMetadata {
+    /// Lit un fichier audio : flux décodé pour les caractéristiques techniques,
+    /// tags embarqués pour le reste. Des tags illisibles ne bloquent pas l'upload.
+    pub fn read_file(path: &Path) -> Result<(Self, TrackTags), AppError> {
+        let decoder = SymphoniaDecoder::open(path)?;
+        let info = decoder.info();
+        let duration = decoder.duration();
+        let file_size = std::fs::metadata(path)?.len();
+        let format = TagFormat::detect_file(path)?;
+        let tags = tags::read_tags(path).unwrap_or_else(|e| {
+            warn!("Tags illisibles dans {}: {}", path.display(), e);
+            TrackTags::default()
+        });
+        
+        let codec = info.codec_name.to_lowercase();
+        let lossless = codec.starts_with("pcm") || matches!(codec.as_str(), "flac" | "alac" | "wavpack");
+        // Débit moyen du flux, hors images embarquées
+        let audio_size = file_size.saturating_sub(tags.pictures.iter().map(|p| p.data.len() as u64).sum());
+        let bitrate = duration
+            .filter(|duration| !duration.is_zero())
+            .map_or(0, |duration| (audio_size as f64 * 8.0 / duration.as_secs_f64()) as u32);
+        let file_format = match format {
+            Some(TagFormat::Id3) => "MPEG",
+            Some(TagFormat::Flac) => "FLAC",
+            Some(TagFormat::Ogg) => "Ogg",
+            Some(TagFormat::Wav) => "WAVE",
+            Some(TagFormat::Aiff) => "AIFF",
+            Some(TagFormat::Mp4) => "MP4",
+            None => "unknown",
+        };
+        
+        let mut metadata = Self {
+            title: None,
+            artist: None,
+            album: None,
+            genre: None,
+            year: None,
+            track_number: None,
+            duration,
+            artists: Vec::new(),
+            album_artist: None,
+            composer: None,
+            disc_number: None,
+            lyrics: None,
+            
+            sample_rate: info.sample_rate,
+            bitrate,
+            channels: info.channels,
+            bit_depth: lossless.then_some(info.bit_depth),
+            codec: codec.to_uppercase(),
+            file_format: file_format.to_string(),
+            
+            bpm: None,
+            key: None,
+            loudness_lufs: None,
+            peak_db: None,
+            dynamic_range: None,
+            analysis: None,
+            loudness: None,
+            
+            isrc: None,
+            mbid: None,
+            license: None,
+            track_url: None,
+            content_matches: Vec::new(),
+            
+            has_artwork: false,
+            artwork_size: None,
+            artwork: None,
+            
+            custom_tags: HashMap::new(),
+        };
+        metadata.apply_tags(&tags);
+        Ok((metadata, tags))
+    }
+    
+    /// Reporte les tags lus dans le fichier
+    pub fn apply_tags(&mut self, tags: &TrackTags) {
+        self.title = tags.title.clone();
+        self.artist = tags.artist();
+        self.artists = tags.artists.clone();
+        self.album = tags.album.clone();
+        self.album_artist = tags.album_artist.clone();
+        self.genre = tags.genre.clone();
+        self.year = tags.year;
+        self.track_number = tags.track_number;
+        self.disc_number = tags.disc_number;
+        self.composer = tags.composer.clone();
+        self.lyrics = tags.lyrics.clone();
+        self.bpm = tags.bpm;
+        self.key = tags.key.clone();
+        self.isrc = tags.isrc.clone();
+        self.mbid = tags.mbid.clone();
+        self.license = tags.license.clone();
+        self.track_url = tags.url.clone();
+        self.has_artwork = !tags.pictures.is_empty();
+        
+        self.custom_tags = tags.custom.clone();
+        for (key, value) in [("label", &tags.label), ("copyright", &tags.copyright), ("comment", &tags.comment)] {
+            if let Some(value) = value {
+                self.custom_tags.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+    
+    /// Tags à écrire dans les fichiers livrés, sans la pochette (lue à part)
+    pub fn to_tags(&self) -> TrackTags {
+        let mut custom = self.custom_tags.clone();
+        let mut take = |key: &str| custom.remove(key);
+        let (label, copyright, comment) = (take("label"), take("copyright"), take("comment"));
+        let artists = if self.artists.is_empty() {
+            self.artist.iter().cloned().collect()
+        } else {
+            self.artists.clone()
+        };
+        TrackTags {
+            title: self.title.clone(),
+            artists,
+            album: self.album.clone(),
+            album_artist: self.album_artist.clone(),
+            genre: self.genre.clone(),
+            year: self.year,
+            track_number: self.track_number,
+            track_total: None,
+            disc_number: self.disc_number,
+            disc_total: None,
+            composer: self.composer.clone(),
+            isrc: self.isrc.clone(),
+            bpm: self.bpm,
+            key: self.key.clone(),
+            lyrics: self.lyrics.clone(),
+            label,
+            copyright,
+            comment,
+            mbid: self.mbid.clone(),
+            license: self.license.clone(),
+            url: self.track_url.clone(),
+            pictures: Vec::new(),
+            custom,
+        }
+    }
+    
+    /// Enregistre la pochette extraite et ses dimensions
+    pub fn set_artwork(&mut self, artwork: TrackArtwork, size: Option<(u32, u32)>) {
+        self.has_artwork = true;
+        self.artwork_size = size;
+        self.artwork = Some(artwork);
+    }
+    
+    /// Enregistre une mesure de loudness et les champs résumés correspondants
+    pub fn set_loudness(&mut self, measured: TrackLoudness) {
+        self.loudness_lufs = Some(measured.integrated_lufs);
+        self.peak_db = Some(measured.true_peak_dbtp);
+        self.dynamic_range = Some(measured.loudness_range_lu);
+        self.loudness = Some(measured);
+    }
+}
+
 impl LocalFileStorage {
     pub fn new(base_path: PathBuf, public_url_base: String) -> Self {
         Self {
@@ -621,45 +1616,160 @@ impl LocalFileStorage {
             public_url_base,
         }
     }
+
+    fn file_path(&self, file_id: &str) -> Result<PathBuf, AppError> {
+        Ok(self.base_path.join(validate_file_id(file_id)?))
+    }
+
+    /// Les infos d'un fichier sont conservées à côté de lui, dans `<fichier>.json`
+    fn info_path(path: &Path) -> PathBuf {
+        let mut info = path.as_os_str().to_owned();
+        info.push(".json");
+        PathBuf::from(info)
+    }
 }
 
 #[async_trait::async_trait]
 impl FileStorage for LocalFileStorage {
-    async fn store_file(&self, file_path: &Path, _metadata: &TrackMetadata) -> Result<StoredFile, AppError> {
-        let file_id = Uuid::new_v4().to_string();
-        let stored_path = self.base_path.join(&file_id);
-        
-        // Copier le fichier (simulation)
-        let file_size = 1024 * 1024; // 1MB simulé
-        
-        Ok(StoredFile {
-            id: file_id.clone(),
-            original_filename: file_path.file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
-            content_type: "audio/mpeg".to_string(),
-            size: file_size,
+    async fn store_file(&self, file_path: &Path, file_id: &str, session: &UploadSession) -> Result<StoredFile, AppError> {
+        let stored_path = self.file_path(file_id)?;
+        if let Some(parent) = stored_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let size = fs::copy(file_path, &stored_path).await?;
+        let stored = StoredFile {
+            id: file_id.to_string(),
+            original_filename: session.filename.clone(),
+            content_type: session.content_type.clone(),
+            size,
             storage_path: stored_path.to_string_lossy().to_string(),
             public_url: Some(format!("{}/{}", self.public_url_base, file_id)),
             cdn_url: None,
-            checksum: "abc123".to_string(),
+            checksum: file_sha256(&stored_path).await?,
             created_at: SystemTime::now(),
-        })
+        };
+
+        let info = serde_json::to_vec(&stored)
+            .map_err(|e| AppError::InternalError { message: e.to_string() })?;
+        fs::write(Self::info_path(&stored_path), info).await?;
+        Ok(stored)
     }
-    
+
     async fn get_file(&self, file_id: &str) -> Result<StoredFile, AppError> {
-        // Simulation de récupération
-        Err(AppError::NotFound { resource: format!("File not found: {}", file_id) })
+        let info_path = Self::info_path(&self.file_path(file_id)?);
+        let info = fs::read(&info_path).await
+            .map_err(|_| AppError::NotFound { resource: format!("File not found: {}", file_id) })?;
+        serde_json::from_slice(&info)
+            .map_err(|e| AppError::InternalError { message: e.to_string() })
     }
-    
-    async fn delete_file(&self, _file_id: &str) -> Result<(), AppError> {
-        // Simulation de suppression
-        Ok(())
+
+    async fn delete_file(&self, file_id: &str) -> Result<(), AppError> {
+        let path = self.file_path(file_id)?;
+        let _ = fs::remove_file(Self::info_path(&path)).await;
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
     }
-    
-    async fn list_user_files(&self, _user_id: i64) -> Result<Vec<StoredFile>, AppError> {
-        // Simulation de listing
-        Ok(Vec::new())
+
+    async fn list_user_files(&self, user_id: i64) -> Result<Vec<StoredFile>, AppError> {
+        let mut files = Vec::new();
+        let mut entries = match fs::read_dir(self.base_path.join(user_id.to_string())).await {
+            Ok(entries) => entries,
+            Err(_) => return Ok(files),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                if let Some(stored) = fs::read(&path).await.ok()
+                    .and_then(|info| serde_json::from_slice::<StoredFile>(&info).ok())
+                {
+                    files.push(stored);
+                }
+            }
+        }
+        files.sort_by_key(|f| f.created_at);
+        Ok(files)
+    }
+
+    async fn read_range(&self, file_id: &str, start: u64, length: u64) -> Result<StorageStream, AppError> {
+        let mut file = fs::File::open(self.file_path(file_id)?).await
+            .map_err(|_| AppError::FileNotFound)?;
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        Ok(Box::pin(tokio_util::io::ReaderStream::new(file.take(length))))
+    }
+}
+
+/// Identifiant de stockage d'une session : `<user_id>/<session_id>.<ext>`
+pub(crate) fn stored_file_id(session: &UploadSession) -> String {
+    let extension = Path::new(&session.filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "bin".to_string());
+    format!("{}/{}.{}", session.user_id, session.id, extension)
+}
+
+/// Métadonnée client portant l'identifiant de la piste dans le catalogue
+pub const TRACK_ID_KEY: &str = "track_id";
+
+/// Clé d'une session dans le registre d'empreintes : l'identifiant catalogue
+/// de la piste, que la modération et les retraits DMCA utilisent, ou à défaut
+/// l'identifiant de la session
+pub(crate) fn content_key(session: &UploadSession) -> String {
+    session.client_metadata.get(TRACK_ID_KEY)
+        .and_then(|track_id| track_id.parse::<u64>().ok())
+        .map(|track_id| track_id.to_string())
+        .unwrap_or_else(|| session.id.to_string())
+}
+
+/// Nombre d'octets du début d'un fichier comparés avant de le reconnaître comme doublon
+pub const DEDUP_PROBE_BYTES: u64 = 1024 * 1024;
+
+/// Métadonnée client déclarant le SHA-256 (hexadécimal) du fichier complet
+pub const DECLARED_SHA256_KEY: &str = "sha256";
+
+/// Nom du registre d'empreintes dans le stockage partagé
+const CONTENT_ID_INDEX_NAME: &str = "content-id/fingerprints.json";
+
+/// Taille de l'empreinte de début pour un fichier de `file_size` octets
+pub(crate) fn dedup_probe_len(file_size: u64) -> u64 {
+    DEDUP_PROBE_BYTES.min(file_size)
+}
+
+/// SHA-256 hexadécimal des `length` premiers octets d'un fichier
+pub(crate) async fn file_head_sha256(path: &Path, length: u64) -> Result<String, AppError> {
+    let mut head = Vec::with_capacity(length as usize);
+    fs::File::open(path).await?.take(length).read_to_end(&mut head).await?;
+    if (head.len() as u64) < length {
+        return Err(AppError::InsufficientData);
+    }
+    Ok(hex::encode(sha2::Sha256::digest(&head)))
+}
+
+/// Refuse les identifiants qui sortiraient de la racine du stockage
+pub(crate) fn validate_file_id(file_id: &str) -> Result<&str, AppError> {
+    let valid = !file_id.starts_with('/')
+        && !file_id.contains('\\')
+        && file_id.split('/').all(|part| !part.is_empty() && part != "." && part != "..");
+    if valid {
+        Ok(file_id)
+    } else {
+        Err(AppError::ValidationError(format!("Invalid file id: {}", file_id)))
     }
-} 
\ No newline at end of file
+}
+
+/// SHA-256 hexadécimal d'un fichier, lu par blocs
+pub(crate) async fn file_sha256(path: &Path) -> Result<String, AppError> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
@@ -5,7 +5,7 @@
 /// - Format peaks.js compatible
 /// - Analyse spectrale avancée
 /// - Support multi-résolution
-/// - Export JSON/binaire
+/// - Export JSON, `.dat` audiowaveform, SVG et PNG
 
 use std::sync::Arc;
 use std::path::Path;
@@ -13,9 +13,14 @@ use std::collections::HashMap;
 
 use serde::{Serialize, Deserialize};
 use tokio::sync::RwLock;
+use futures::StreamExt;
 use tracing::{debug, info};
 
+use crate::audio::loudness::LoudnessMeter;
+use crate::audio::spectrogram::{self, FrequencyScale, Spectrogram, SpectrogramConfig};
+use crate::codecs::SymphoniaDecoder;
 use crate::error::AppError;
+use crate::soundcloud::waveform_render::{self, WaveformStyle};
 
 /// Générateur de waveform principal
 #[derive(Debug)]
@@ -31,9 +36,13 @@ pub struct WaveformConfig {
     pub bit_depth: u8,
     pub amplitude_scale: f32,
     pub enable_spectral_analysis: bool,
+    /// Paramètres de l'analyse spectrale
+    pub spectral: SpectrogramConfig,
     pub peak_detection_threshold: f32,
     pub cache_enabled: bool,
     pub output_formats: Vec<WaveformFormat>,
+    /// Apparence des exports SVG et PNG
+    pub style: WaveformStyle,
 }
 
 /// Formats de sortie supportés
@@ -41,10 +50,10 @@ pub struct WaveformConfig {
 pub enum WaveformFormat {
     /// Format JSON compatible peaks.js
     PeaksJS,
-    /// Format binaire compact
+    /// Format `.dat` d'audiowaveform (version 2)
     Binary,
     /// Format SVG vectoriel
-    SVG,
+    SVG { width: u32, height: u32 },
     /// Format PNG image
     PNG { width: u32, height: u32 },
 }
@@ -97,7 +106,7 @@ pub struct SpectralData {
 /// Frame spectrale à un instant donné
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpectralFrame {
-    /// Magnitude par bande de fréquence
+    /// Niveau par bande de fréquence (dBFS)
     pub magnitudes: Vec<f32>,
     /// Timestamp en millisecondes
     pub timestamp_ms: f32,
@@ -163,12 +172,20 @@ impl Default for WaveformConfig {
             bit_depth: 16,
             amplitude_scale: 1.0,
             enable_spectral_analysis: true,
+            spectral: SpectrogramConfig {
+                fft_size: 2048,
+                hop_size: 2048,
+                scale: FrequencyScale::Mel,
+                bands: 64,
+                ..SpectrogramConfig::default()
+            },
             peak_detection_threshold: -20.0, // -20dB
             cache_enabled: true,
             output_formats: vec![
                 WaveformFormat::PeaksJS,
                 WaveformFormat::Binary,
             ],
+            style: WaveformStyle::default(),
         }
     }
 }
@@ -201,7 +218,6 @@ impl WaveformGenerator {
         
         info!("Génération de waveform pour: {}", path_str);
         
-        // Simulation de lecture du fichier audio
         let audio_data = self.load_audio_file(&path_str).await?;
         
         // Générer la waveform
@@ -232,29 +248,29 @@ impl WaveformGenerator {
         self.generate_waveform_data(&audio_data).await
     }
     
-    /// Charge un fichier audio (simulation)
-    async fn load_audio_file(&self, _file_path: &str) -> Result<AudioData, AppError> {
-        // Simulation de chargement - en production, utiliser symphonia ou similar
-        let sample_rate = 44100;
-        let channels = 2;
-        let duration_seconds = 180.0; // 3 minutes
-        let total_samples = (sample_rate as f64 * channels as f64 * duration_seconds) as usize;
-        
-        // Générer des échantillons de test (sinusoïde modulée)
-        let mut samples = Vec::with_capacity(total_samples);
-        for i in 0..total_samples {
-            let t = i as f64 / (sample_rate as f64 * channels as f64);
-            let frequency = 440.0 + 100.0 * (t * 0.1).sin(); // Fréquence modulée
-            let amplitude = 0.5 * (1.0 + (t * 0.05).sin()); // Amplitude modulée
-            let sample = (amplitude * (2.0 * std::f64::consts::PI * frequency * t).sin()) as f32;
-            samples.push(sample);
+    /// Charge un fichier audio via le flux de `SymphoniaDecoder`
+    async fn load_audio_file(&self, file_path: &str) -> Result<AudioData, AppError> {
+        let path = Path::new(file_path).to_path_buf();
+        let decoder = tokio::task::spawn_blocking(move || SymphoniaDecoder::open(&path))
+            .await
+            .map_err(|e| AppError::InternalError { message: e.to_string() })??;
+        let sample_rate = decoder.sample_rate();
+        let mut channels = decoder.channels();
+
+        let mut samples = Vec::new();
+        let mut chunks = decoder.into_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            channels = chunk.channels;
+            samples.extend_from_slice(&chunk.samples);
         }
-        
+
+        let duration = samples.len() as f64 / (f64::from(sample_rate) * f64::from(channels.max(1)));
         Ok(AudioData {
             samples,
             sample_rate,
             channels,
-            duration: duration_seconds,
+            duration,
         })
     }
     
@@ -282,7 +298,7 @@ impl WaveformGenerator {
         }
         
         // Calculer les statistiques audio
-        let audio_stats = self.calculate_audio_statistics(&audio_data.samples, audio_data.sample_rate);
+        let audio_stats = self.calculate_audio_statistics(&audio_data.samples, audio_data.sample_rate, audio_data.channels);
         
         // Générer les données spectrales si activé
         let spectral_data = if self.config.enable_spectral_analysis {
@@ -342,7 +358,7 @@ impl WaveformGenerator {
     }
     
     /// Calcule les statistiques audio globales
-    fn calculate_audio_statistics(&self, samples: &[f32], sample_rate: u32) -> AudioStatistics {
+    fn calculate_audio_statistics(&self, samples: &[f32], sample_rate: u32, channels: u8) -> AudioStatistics {
         if samples.is_empty() {
             return AudioStatistics::default();
         }
@@ -380,12 +396,10 @@ impl WaveformGenerator {
             0.0
         };
         
-        // Loudness intégré (approximation simple)
-        let integrated_loudness = if average_rms > 0.0 {
-            -0.691 + 10.0 * average_rms.log10()
-        } else {
-            -70.0 // Silence
-        };
+        // Loudness intégré BS.1770 (pondération K et gating)
+        let mut meter = LoudnessMeter::new(sample_rate, usize::from(channels));
+        meter.add_samples(samples);
+        let integrated_loudness = (meter.integrated_loudness() as f32).max(-70.0);
         
         // BPM et clé (simulation - en production, utiliser des algos dédiés)
         let estimated_bpm = self.estimate_bpm(samples, sample_rate);
@@ -415,67 +429,15 @@ impl WaveformGenerator {
         Some("C major".to_string())
     }
     
-    /// Génère les données spectrales
+    /// Génère les données spectrales (STFT regroupée en bandes)
     async fn generate_spectral_data(&self, audio_data: &AudioData) -> Result<SpectralData, AppError> {
-        let fft_size = 2048;
-        let hop_size = fft_size / 4; // 75% overlap
-        let window_count = (audio_data.samples.len() + hop_size - 1) / hop_size;
-        
-        let mut spectrogram = Vec::with_capacity(window_count);
-        let frequency_bins = self.generate_frequency_bins(fft_size, audio_data.sample_rate);
-        let time_resolution_ms = (hop_size as f32 / audio_data.sample_rate as f32) * 1000.0;
-        
-        // Simulation de FFT - en production, utiliser rustfft
-        for window_index in 0..window_count {
-            let start_sample = window_index * hop_size;
-            let end_sample = (start_sample + fft_size).min(audio_data.samples.len());
-            
-            if start_sample < audio_data.samples.len() {
-                let window_samples = &audio_data.samples[start_sample..end_sample];
-                let magnitudes = self.calculate_fft_magnitudes(window_samples, fft_size);
-                
-                let frame = SpectralFrame {
-                    magnitudes,
-                    timestamp_ms: window_index as f32 * time_resolution_ms,
-                };
-                
-                spectrogram.push(frame);
-            }
-        }
-        
-        Ok(SpectralData {
-            spectrogram,
-            frequency_bins,
-            time_resolution_ms,
-        })
-    }
-    
-    /// Génère les bins de fréquence
-    fn generate_frequency_bins(&self, fft_size: usize, sample_rate: u32) -> Vec<f32> {
-        let bin_count = fft_size / 2 + 1;
-        (0..bin_count)
-            .map(|i| i as f32 * sample_rate as f32 / fft_size as f32)
-            .collect()
-    }
-    
-    /// Calcule les magnitudes FFT (simulation)
-    fn calculate_fft_magnitudes(&self, samples: &[f32], fft_size: usize) -> Vec<f32> {
-        let bin_count = fft_size / 2 + 1;
-        let mut magnitudes = Vec::with_capacity(bin_count);
-        
-        // Simulation simple - en production, utiliser une vraie FFT
-        for i in 0..bin_count {
-            let frequency = i as f32 / bin_count as f32;
-            let magnitude = if !samples.is_empty() {
-                let avg_amplitude = samples.iter().map(|&s| s.abs()).sum::<f32>() / samples.len() as f32;
-                avg_amplitude * (1.0 - frequency) // Décroissance avec la fréquence
-            } else {
-                0.0
-            };
-            magnitudes.push(magnitude);
-        }
-        
-        magnitudes
+        let spectrogram = spectrogram::analyze_samples(
+            &audio_data.samples,
+            audio_data.sample_rate,
+            usize::from(audio_data.channels),
+            self.config.spectral.clone(),
+        )?;
+        Ok(SpectralData::from(&spectrogram))
     }
     
     /// Récupère depuis le cache
@@ -488,94 +450,40 @@ impl WaveformGenerator {
         self.cache.write().await.insert(key.to_string(), waveform.clone());
     }
     
-    /// Exporte la waveform dans un format spécifique
+    /// Exporte la waveform dans un format spécifique, avec le style configuré
     pub fn export_waveform(&self, waveform: &WaveformData, format: WaveformFormat) -> Result<Vec<u8>, AppError> {
+        self.export_waveform_with_style(waveform, format, &self.config.style)
+    }
+    
+    /// Exporte la waveform avec un style propre à la requête (couleurs,
+    /// position de lecture, densité de l'écran)
+    pub fn export_waveform_with_style(
+        &self,
+        waveform: &WaveformData,
+        format: WaveformFormat,
+        style: &WaveformStyle,
+    ) -> Result<Vec<u8>, AppError> {
         match format {
             WaveformFormat::PeaksJS => {
                 let json = serde_json::to_string_pretty(waveform)
                     .map_err(|_| AppError::SerializationError)?;
                 Ok(json.into_bytes())
             },
-            WaveformFormat::Binary => {
-                // Format binaire compact pour la performance
-                let mut data = Vec::new();
-                
-                // Header
-                data.extend(&(waveform.peaks.len() as u32).to_le_bytes());
-                data.extend(&waveform.sample_rate.to_le_bytes());
-                data.extend(&(waveform.channels as u32).to_le_bytes());
-                data.extend(&waveform.duration.to_le_bytes());
-                
-                // Peaks data
-                for peak in &waveform.peaks {
-                    data.extend(&peak.min.to_le_bytes());
-                    data.extend(&peak.max.to_le_bytes());
-                    data.extend(&peak.rms.to_le_bytes());
-                    data.extend(&peak.peak.to_le_bytes());
-                }
-                
-                Ok(data)
-            },
-            WaveformFormat::SVG { .. } => {
-                // Génération SVG simple
-                let svg = self.generate_svg_waveform(waveform)?;
+            WaveformFormat::Binary => Ok(waveform_render::encode_dat(
+                &waveform.peaks,
+                waveform.sample_rate,
+                self.config.samples_per_pixel,
+                self.config.bit_depth,
+            )),
+            WaveformFormat::SVG { width, height } => {
+                let svg = waveform_render::render_svg(&waveform.peaks, width, height, style)?;
                 Ok(svg.into_bytes())
             },
             WaveformFormat::PNG { width, height } => {
-                // Génération PNG (simulation)
-                let _png_data = self.generate_png_waveform(waveform, width, height)?;
-                Ok(Vec::new()) // Placeholder
+                waveform_render::render_png(&waveform.peaks, width, height, style)
             },
         }
     }
-    
-    /// Génère une représentation SVG de la waveform
-    fn generate_svg_waveform(&self, waveform: &WaveformData) -> Result<String, AppError> {
-        let width = 800;
-        let height = 200;
-        let center_y = height / 2;
-        
-        let mut svg = format!(
-            "<svg width=\"{}\" height=\"{}\" xmlns=\"http://www.w3.org/2000/svg\">\n<rect width=\"100%\" height=\"100%\" fill=\"#f0f0f0\"/>\n<g stroke=\"#007cba\" stroke-width=\"1\" fill=\"none\">",
-            width, height
-        );
-        
-        // Dessiner la waveform
-        let x_scale = width as f32 / waveform.peaks.len() as f32;
-        let y_scale = center_y as f32;
-        
-        let mut path = String::from("M");
-        for (i, peak) in waveform.peaks.iter().enumerate() {
-            let x = i as f32 * x_scale;
-            let y_top = center_y as f32 - (peak.max * y_scale);
-            let _y_bottom = center_y as f32 - (peak.min * y_scale);
-            
-            if i == 0 {
-                path.push_str(&format!("{},{}", x, y_top));
-            } else {
-                path.push_str(&format!(" L{},{}", x, y_top));
-            }
-        }
-        
-        // Fermer le chemin
-        for (i, peak) in waveform.peaks.iter().enumerate().rev() {
-            let x = i as f32 * x_scale;
-            let _y_bottom = center_y as f32 - (peak.min * y_scale);
-            path.push_str(&format!(" L{},{}", x, _y_bottom));
-        }
-        path.push('Z');
-        
-        svg.push_str(&format!("<path d=\"{}\" fill=\"#007cba\" opacity=\"0.6\"/>", path));
-        svg.push_str("</g></svg>");
-        
-        Ok(svg)
-    }
-    
-    /// Génère une image PNG de la waveform
-    fn generate_png_waveform(&self, _waveform: &WaveformData, _width: u32, _height: u32) -> Result<Vec<u8>, AppError> {
-        // Simulation - en production, utiliser une lib comme `image` ou `skia`
-        Ok(Vec::new())
-    }
 }
 
 /// Données audio brutes
@@ -587,6 +495,23 @@ struct AudioData {
     duration: f64,
 }
 
+impl From<&Spectrogram> for SpectralData {
+    fn from(spectrogram: &Spectrogram) -> Self {
+        Self {
+            spectrogram: spectrogram.frames
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| SpectralFrame {
+                    magnitudes: frame.clone(),
+                    timestamp_ms: i as f32 * spectrogram.time_resolution_ms,
+                })
+                .collect(),
+            frequency_bins: spectrogram.frequencies.clone(),
+            time_resolution_ms: spectrogram.time_resolution_ms,
+        }
+    }
+}
+
 impl Default for AudioStatistics {
     fn default() -> Self {
         Self {
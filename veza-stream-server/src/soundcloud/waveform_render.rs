@@ -0,0 +1,427 @@
+//! Rendu statique des waveforms : PNG, SVG et `.dat` audiowaveform
+//!
+//! Les aperçus de liens et les emails ne peuvent pas exécuter peaks.js : ils
+//! reçoivent une image calculée côté serveur. Les deux rendus partagent la
+//! même mise en page (barres espacées ou enveloppe continue), la coupure
+//! entre partie écoutée et non écoutée et le facteur d'échelle des écrans
+//! haute densité. Le format `.dat` (version 2) est celui lu par peaks.js et
+//! produit par l'outil `audiowaveform` de la BBC.
+
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::soundcloud::waveform::WaveformPeak;
+use crate::utils::png;
+
+/// Dimension logique maximale d'une image
+const MAX_DIMENSION: u32 = 8192;
+/// Facteur d'échelle maximal (écrans 4x)
+const MAX_SCALE: f32 = 4.0;
+/// Version du format `.dat` d'audiowaveform (avec nombre de canaux)
+const DAT_VERSION: i32 = 2;
+/// Drapeau `.dat` : échantillons sur 8 bits au lieu de 16
+const DAT_FLAG_8_BIT: u32 = 0x1;
+
+/// Couleur RGBA, notée `#rgb`, `#rrggbb` ou `#rrggbbaa`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct WaveformColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+/// Apparence d'une waveform rendue en image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaveformStyle {
+    /// Fond de l'image, transparent si absent
+    pub background: Option<WaveformColor>,
+    /// Couleur de la partie déjà écoutée
+    pub played_color: WaveformColor,
+    /// Couleur du reste de la piste
+    pub unplayed_color: WaveformColor,
+    /// Position de lecture (0.0 - 1.0) séparant les deux couleurs
+    pub progress: f32,
+    /// Densité de pixels (2.0 pour un écran retina) ; le PNG est rendu à
+    /// `largeur × échelle`, le SVG garde ses dimensions logiques
+    pub scale: f32,
+    /// Largeur des barres en pixels logiques, 0 pour une enveloppe continue
+    pub bar_width: u32,
+    /// Espace entre deux barres en pixels logiques
+    pub bar_gap: u32,
+    /// Ramène le pic le plus fort à pleine hauteur
+    pub normalize: bool,
+}
+
+/// Rectangle occupé par une barre, dans les coordonnées de sortie
+#[derive(Debug, Clone, Copy)]
+struct Bar {
+    left: f32,
+    right: f32,
+    top: f32,
+    bottom: f32,
+}
+
+impl Default for WaveformStyle {
+    fn default() -> Self {
+        Self {
+            background: None,
+            played_color: WaveformColor::rgb(0xff, 0x55, 0x00),
+            unplayed_color: WaveformColor::rgb(0x99, 0x99, 0x99),
+            progress: 0.0,
+            scale: 1.0,
+            bar_width: 2,
+            bar_gap: 1,
+            normalize: true,
+        }
+    }
+}
+
+impl WaveformColor {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 0xff }
+    }
+
+    /// Notation hexadécimale, sans canal alpha s'il est opaque
+    pub fn to_hex(&self) -> String {
+        if self.a == 0xff {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
+    }
+
+    fn channels(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a].map(|c| f32::from(c) / 255.0)
+    }
+}
+
+impl FromStr for WaveformColor {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AppError::ValidationError(format!("Couleur invalide: {}", s));
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let digits: Vec<u8> = match hex.len() {
+            3 | 4 => hex.chars()
+                .map(|c| c.to_digit(16).map(|d| d as u8 * 0x11))
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+            6 | 8 => (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            r: digits[0],
+            g: digits[1],
+            b: digits[2],
+            a: digits.get(3).copied().unwrap_or(0xff),
+        })
+    }
+}
+
+impl From<WaveformColor> for String {
+    fn from(color: WaveformColor) -> Self {
+        color.to_hex()
+    }
+}
+
+impl TryFrom<String> for WaveformColor {
+    type Error = AppError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl WaveformStyle {
+    fn validate(&self, width: u32, height: u32) -> Result<(), AppError> {
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(AppError::ValidationError(format!(
+                "Dimensions de waveform invalides: {}x{} (max {})",
+                width, height, MAX_DIMENSION
+            )));
+        }
+        if !(self.scale > 0.0 && self.scale <= MAX_SCALE) {
+            return Err(AppError::ValidationError(format!(
+                "Échelle de waveform invalide: {} (max {})",
+                self.scale, MAX_SCALE
+            )));
+        }
+        Ok(())
+    }
+
+    /// Barres à dessiner pour une image `width × height` (unités logiques)
+    /// exprimées dans un repère agrandi de `scale`
+    fn layout(&self, peaks: &[WaveformPeak], width: u32, height: u32, scale: f32) -> Vec<Bar> {
+        let full_width = width as f32 * scale;
+        let full_height = height as f32 * scale;
+        let (count, step, bar_width) = if self.bar_width == 0 {
+            // Enveloppe continue : une colonne par pixel de sortie
+            let count = full_width.round().max(1.0) as usize;
+            (count, full_width / count as f32, full_width / count as f32)
+        } else {
+            let step = self.bar_width + self.bar_gap;
+            let count = ((width + self.bar_gap) / step).max(1) as usize;
+            (count, step as f32 * scale, self.bar_width.min(width) as f32 * scale)
+        };
+
+        let envelope = envelope(peaks, count);
+        let gain = if self.normalize {
+            let loudest = envelope.iter().fold(0.0f32, |acc, &(min, max)| acc.max(-min).max(max));
+            if loudest > 1e-4 { 1.0 / loudest } else { 1.0 }
+        } else {
+            1.0
+        };
+
+        let center = full_height / 2.0;
+        // Même le silence reste visible sous forme d'un trait d'un pixel
+        let min_height = scale.min(full_height);
+        envelope
+            .into_iter()
+            .enumerate()
+            .map(|(i, (min, max))| {
+                let mut top = center - (max * gain).clamp(-1.0, 1.0) * center;
+                let mut bottom = center - (min * gain).clamp(-1.0, 1.0) * center;
+                if bottom - top < min_height {
+                    let middle = ((top + bottom) / 2.0).clamp(min_height / 2.0, full_height - min_height / 2.0);
+                    top = middle - min_height / 2.0;
+                    bottom = middle + min_height / 2.0;
+                }
+                let left = i as f32 * step;
+                Bar { left, right: left + bar_width, top, bottom }
+            })
+            .collect()
+    }
+}
+
+/// Min/max des pics regroupés en `count` colonnes
+fn envelope(peaks: &[WaveformPeak], count: usize) -> Vec<(f32, f32)> {
+    if peaks.is_empty() {
+        return vec![(0.0, 0.0); count];
+    }
+    (0..count)
+        .map(|i| {
+            let start = (i * peaks.len() / count).min(peaks.len() - 1);
+            let end = ((i + 1) * peaks.len() / count).clamp(start + 1, peaks.len());
+            peaks[start..end]
+                .iter()
+                .fold((0.0f32, 0.0f32), |(min, max), peak| (min.min(peak.min), max.max(peak.max)))
+        })
+        .collect()
+}
+
+/// Longueur du recouvrement entre `[a0, a1)` et `[b0, b1)`
+fn overlap(a0: f32, a1: f32, b0: f32, b1: f32) -> f32 {
+    (a1.min(b1) - a0.max(b0)).max(0.0)
+}
+
+/// Rend la waveform en PNG RGBA de `width × height` pixels logiques
+pub fn render_png(peaks: &[WaveformPeak], width: u32, height: u32, style: &WaveformStyle) -> Result<Vec<u8>, AppError> {
+    style.validate(width, height)?;
+    let pixel_width = (width as f32 * style.scale).round().max(1.0) as usize;
+    let pixel_height = (height as f32 * style.scale).round().max(1.0) as usize;
+
+    let background = style.background.map(|c| c.channels()).unwrap_or([0.0; 4]);
+    let mut canvas = vec![background; pixel_width * pixel_height];
+    let played = style.played_color.channels();
+    let unplayed = style.unplayed_color.channels();
+    let split = style.progress.clamp(0.0, 1.0) * pixel_width as f32;
+
+    for bar in style.layout(peaks, width, height, style.scale) {
+        let x_range = (bar.left.floor().max(0.0) as usize)..(bar.right.ceil() as usize).min(pixel_width);
+        let y_range = (bar.top.floor().max(0.0) as usize)..(bar.bottom.ceil() as usize).min(pixel_height);
+        for x in x_range {
+            let x0 = x as f32;
+            let horizontal = overlap(x0, x0 + 1.0, bar.left, bar.right);
+            // Part du pixel à gauche de la position de lecture
+            let played_part = (split - x0).clamp(0.0, 1.0);
+            let color: [f32; 4] = std::array::from_fn(|c| played[c] * played_part + unplayed[c] * (1.0 - played_part));
+            for y in y_range.clone() {
+                let y0 = y as f32;
+                let coverage = horizontal * overlap(y0, y0 + 1.0, bar.top, bar.bottom);
+                blend(&mut canvas[y * pixel_width + x], color, coverage);
+            }
+        }
+    }
+
+    let rgba: Vec<u8> = canvas
+        .iter()
+        .flat_map(|pixel| pixel.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        .collect();
+    png::encode_rgba(pixel_width as u32, pixel_height as u32, &rgba)
+}
+
+/// Composition « source over » d'une couleur couvrant `coverage` du pixel
+fn blend(pixel: &mut [f32; 4], color: [f32; 4], coverage: f32) {
+    let alpha = color[3] * coverage;
+    if alpha <= 0.0 {
+        return;
+    }
+    let out_alpha = alpha + pixel[3] * (1.0 - alpha);
+    for c in 0..3 {
+        pixel[c] = (color[c] * alpha + pixel[c] * pixel[3] * (1.0 - alpha)) / out_alpha;
+    }
+    pixel[3] = out_alpha;
+}
+
+/// Rend la waveform en SVG de `width × height` pixels logiques ; la partie
+/// écoutée est colorée par un dégradé à arrêt franc
+pub fn render_svg(peaks: &[WaveformPeak], width: u32, height: u32, style: &WaveformStyle) -> Result<String, AppError> {
+    style.validate(width, height)?;
+    let bars = style.layout(peaks, width, height, 1.0);
+
+    let mut path = String::new();
+    if style.bar_width == 0 {
+        // Enveloppe continue : crêtes de gauche à droite puis creux en retour
+        for (i, bar) in bars.iter().enumerate() {
+            let x = (bar.left + bar.right) / 2.0;
+            let _ = write!(path, "{}{:.2},{:.2}", if i == 0 { "M" } else { "L" }, x, bar.top);
+        }
+        for bar in bars.iter().rev() {
+            let _ = write!(path, "L{:.2},{:.2}", (bar.left + bar.right) / 2.0, bar.bottom);
+        }
+        path.push('Z');
+    } else {
+        for bar in &bars {
+            let _ = write!(
+                path,
+                "M{:.2},{:.2}h{:.2}v{:.2}h{:.2}Z",
+                bar.left, bar.top, bar.right - bar.left, bar.bottom - bar.top, bar.left - bar.right
+            );
+        }
+    }
+
+    let progress = style.progress.clamp(0.0, 1.0);
+    let stop = |color: WaveformColor| {
+        format!(
+            "<stop offset=\"{:.4}\" stop-color=\"#{:02x}{:02x}{:02x}\" stop-opacity=\"{:.3}\"/>",
+            progress, color.r, color.g, color.b, f32::from(color.a) / 255.0
+        )
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = width,
+        h = height
+    );
+    let _ = write!(
+        svg,
+        "<defs><linearGradient id=\"progress\" gradientUnits=\"userSpaceOnUse\" x1=\"0\" y1=\"0\" x2=\"{}\" y2=\"0\">{}{}</linearGradient></defs>",
+        width,
+        stop(style.played_color),
+        stop(style.unplayed_color)
+    );
+    if let Some(background) = style.background {
+        let _ = write!(
+            svg,
+            "<rect width=\"100%\" height=\"100%\" fill=\"#{:02x}{:02x}{:02x}\" fill-opacity=\"{:.3}\"/>",
+            background.r, background.g, background.b, f32::from(background.a) / 255.0
+        );
+    }
+    let _ = write!(svg, "<path d=\"{}\" fill=\"url(#progress)\"/></svg>", path);
+    Ok(svg)
+}
+
+/// Encode les pics au format `.dat` version 2 d'audiowaveform (mono, min/max
+/// par pixel sur 8 ou 16 bits, little-endian)
+pub fn encode_dat(peaks: &[WaveformPeak], sample_rate: u32, samples_per_pixel: u32, bit_depth: u8) -> Vec<u8> {
+    let eight_bit = bit_depth == 8;
+    let mut data = Vec::with_capacity(24 + peaks.len() * if eight_bit { 2 } else { 4 });
+    data.extend(DAT_VERSION.to_le_bytes());
+    data.extend((if eight_bit { DAT_FLAG_8_BIT } else { 0 }).to_le_bytes());
+    data.extend((sample_rate as i32).to_le_bytes());
+    data.extend((samples_per_pixel as i32).to_le_bytes());
+    data.extend((peaks.len() as u32).to_le_bytes());
+    // Les pics sont calculés sur tous les canaux entrelacés : un seul canal
+    data.extend(1i32.to_le_bytes());
+
+    for peak in peaks {
+        for value in [peak.min, peak.max] {
+            if eight_bit {
+                data.push((value * 128.0).round().clamp(-128.0, 127.0) as i8 as u8);
+            } else {
+                data.extend(((value * 32768.0).round().clamp(-32768.0, 32767.0) as i16).to_le_bytes());
+            }
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peaks(count: usize) -> Vec<WaveformPeak> {
+        (0..count)
+            .map(|i| {
+                let amplitude = 0.5 * (1.0 + (i as f32 * 0.1).sin());
+                WaveformPeak { min: -amplitude, max: amplitude, rms: amplitude * 0.7, peak: amplitude }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_png_retina_with_played_split() {
+        let style = WaveformStyle {
+            background: Some("#fff".parse().unwrap()),
+            played_color: "#ff5500".parse().unwrap(),
+            unplayed_color: "#333333".parse().unwrap(),
+            progress: 0.25,
+            scale: 2.0,
+            bar_width: 0,
+            ..WaveformStyle::default()
+        };
+        let image = render_png(&peaks(500), 200, 50, &style).unwrap();
+        let (width, height, pixels) = png::decode_rgba(&image);
+        assert_eq!((width, height), (400, 100));
+
+        let pixel = |x: usize, y: usize| &pixels[(y * width as usize + x) * 4..][..4];
+        // Ligne centrale : partie écoutée puis non écoutée, fond blanc aux bords
+        assert_eq!(pixel(50, 50), &[0xff, 0x55, 0x00, 0xff]);
+        assert_eq!(pixel(300, 50), &[0x33, 0x33, 0x33, 0xff]);
+        assert_eq!(pixel(10, 0), &[0xff, 0xff, 0xff, 0xff]);
+
+        assert!(render_png(&peaks(10), 0, 50, &style).is_err());
+        assert!(render_png(&peaks(10), 100, 50, &WaveformStyle { scale: 8.0, ..style }).is_err());
+    }
+
+    #[test]
+    fn test_svg_and_dat_output() {
+        let style = WaveformStyle { progress: 0.5, bar_width: 3, bar_gap: 1, ..WaveformStyle::default() };
+        let svg = render_svg(&peaks(100), 400, 80, &style).unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"400\" height=\"80\""));
+        assert_eq!(svg.matches('Z').count(), 100);
+        assert!(svg.contains("<stop offset=\"0.5000\" stop-color=\"#ff5500\""));
+        assert!(!svg.contains("<rect"));
+
+        let waveform = peaks(3);
+        let dat = encode_dat(&waveform, 44100, 256, 16);
+        assert_eq!(dat.len(), 24 + 3 * 4);
+        assert_eq!(i32::from_le_bytes(dat[0..4].try_into().unwrap()), 2);
+        assert_eq!(u32::from_le_bytes(dat[4..8].try_into().unwrap()), 0);
+        assert_eq!(i32::from_le_bytes(dat[8..12].try_into().unwrap()), 44100);
+        assert_eq!(i32::from_le_bytes(dat[12..16].try_into().unwrap()), 256);
+        assert_eq!(u32::from_le_bytes(dat[16..20].try_into().unwrap()), 3);
+        assert_eq!(i32::from_le_bytes(dat[20..24].try_into().unwrap()), 1);
+        assert_eq!(i16::from_le_bytes(dat[24..26].try_into().unwrap()), -16384);
+        assert_eq!(i16::from_le_bytes(dat[26..28].try_into().unwrap()), 16384);
+
+        let dat = encode_dat(&waveform, 44100, 256, 8);
+        assert_eq!(u32::from_le_bytes(dat[4..8].try_into().unwrap()), DAT_FLAG_8_BIT);
+        assert_eq!(dat[24..26], [(-64i8) as u8, 64]);
+
+        assert_eq!("#11223380".parse::<WaveformColor>().unwrap().a, 0x80);
+        assert!("#12345".parse::<WaveformColor>().is_err());
+    }
+}
@@ -1,17 +1,29 @@
 use std::{
     collections::HashMap,
+    path::{Path, PathBuf},
     sync::Arc,
     time::SystemTime,
 };
-use tokio::sync::RwLock;
+use dashmap::DashMap;
+use tokio::sync::{Mutex, RwLock};
 use axum::{
     extract::{Path as AxumPath, Query, State},
     response::Response,
+    routing::get,
     http::{StatusCode, header},
+    Router,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{config::Config, utils::validate_signature};
+use crate::{
+    config::Config,
+    error::AppError,
+    streaming::{
+        dash::{self, MpdRepresentation},
+        segmenter::{self, HlsSegmenter, SegmentedRendition, SegmenterConfig},
+    },
+    utils::{build_safe_path, validate_filename, validate_signature},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdaptiveProfile {
@@ -171,11 +183,18 @@ pub struct StreamInfo {
     pub url: String,
 }
 
+/// Clé d'une rendition : (track_id, quality_id)
+type RenditionKey = (String, String);
+
 #[derive(Clone)]
 pub struct AdaptiveStreamingManager {
     config: Arc<Config>,
     sessions: Arc<RwLock<HashMap<String, StreamingSession>>>,
     profiles: Vec<AdaptiveProfile>,
+    segmenter: Arc<HlsSegmenter>,
+    renditions: Arc<RwLock<HashMap<RenditionKey, Arc<SegmentedRendition>>>>,
+    /// Verrou par rendition pour ne segmenter qu'une fois en cas de requêtes concurrentes
+    segmenting: Arc<DashMap<RenditionKey, Arc<Mutex<()>>>>,
 }
 
 impl AdaptiveStreamingManager {
@@ -187,13 +206,25 @@ impl AdaptiveStreamingManager {
             AdaptiveProfile::mobile_quality(),
         ];
 
+        let segmenter = HlsSegmenter::new(SegmenterConfig {
+            cache_dir: Path::new(&config.compression.output_dir).join("hls"),
+            ..SegmenterConfig::default()
+        });
+
         Self {
             config,
             sessions: Arc::new(RwLock::new(HashMap::new())),
             profiles,
+            segmenter: Arc::new(segmenter),
+            renditions: Arc::new(RwLock::new(HashMap::new())),
+            segmenting: Arc::new(DashMap::new()),
         }
     }
 
+    pub fn profile(&self, quality_id: &str) -> Option<&AdaptiveProfile> {
+        self.profiles.iter().find(|profile| profile.quality_id == quality_id)
+    }
+
     pub async fn start_quality_monitor(&self) {
         let _sessions = self.sessions.clone();
         tokio::spawn(async move {
@@ -234,28 +265,116 @@ impl AdaptiveStreamingManager {
         }
     }
 
-    pub async fn generate_master_playlist(&self, track_id: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:6\n");
+    /// Les URIs des variantes sont relatives au master (`/hls/:track_id/master.m3u8`).
+    /// `FileNotFound` si la piste n'existe pas.
+    pub async fn generate_master_playlist(&self, track_id: &str, uri_query: Option<&str>) -> Result<String, AppError> {
+        self.track_source(&validate_filename(track_id)?).await?;
+
+        let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-INDEPENDENT-SEGMENTS\n");
         
         for profile in &self.profiles {
+            let codecs = segmenter::codecs_string(&profile.codec)
+                .ok_or_else(|| AppError::UnsupportedCodec { codec: profile.codec.clone() })?;
+            let uri = match uri_query {
+                Some(query) => format!("{}/playlist.m3u8?{}", profile.quality_id, query),
+                None => format!("{}/playlist.m3u8", profile.quality_id),
+            };
+
             playlist.push_str(&format!(
-                "#EXT-X-STREAM-INF:BANDWIDTH={},CODECS=\"{}\"\n{}/{}/playlist.m3u8\n",
+                "#EXT-X-STREAM-INF:BANDWIDTH={},AVERAGE-BANDWIDTH={},CODECS=\"{}\"\n{}\n",
                 profile.bandwidth_estimate_kbps * 1000,
-                profile.codec,
-                track_id,
-                profile.quality_id
+                profile.bitrate_kbps * 1000,
+                codecs,
+                uri
             ));
         }
         
         Ok(playlist)
     }
 
-    pub async fn generate_quality_playlist(&self, track_id: &str, quality: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let playlist = format!(
-            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:10\n#EXTINF:10.0,\n{}/{}/segment0.ts\n#EXT-X-ENDLIST\n",
-            track_id, quality
-        );
-        Ok(playlist)
+    pub async fn generate_quality_playlist(&self, track_id: &str, quality: &str, uri_query: Option<&str>) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let rendition = self.ensure_rendition(track_id, quality).await?;
+        Ok(rendition.media_playlist(uri_query))
+    }
+
+    /// MPD statique d'une piste : une `Representation` par profil, sur les
+    /// mêmes segments que les playlists HLS (`/hls/:track_id/manifest.mpd`)
+    pub async fn generate_dash_manifest(&self, track_id: &str, uri_query: Option<&str>) -> Result<String, AppError> {
+        // La timeline exacte de chaque représentation exige ses segments
+        let renditions = futures::future::try_join_all(
+            self.profiles.iter().map(|profile| self.ensure_rendition(track_id, &profile.quality_id)),
+        )
+        .await?;
+
+        let representations: Vec<MpdRepresentation> = self.profiles
+            .iter()
+            .zip(&renditions)
+            .map(|(profile, rendition)| MpdRepresentation::from_rendition(rendition, profile.bandwidth_estimate_kbps * 1000))
+            .collect();
+
+        Ok(dash::vod_manifest(&representations, uri_query))
+    }
+
+    /// Chemin et version (`segmenter::source_version`) de la source d'une
+    /// piste ; `FileNotFound` si la piste n'existe pas
+    async fn track_source(&self, track_id: &str) -> Result<(PathBuf, String), AppError> {
+        let source = build_safe_path(&self.config, track_id)?;
+        let metadata = tokio::fs::metadata(&source).await?;
+        Ok((source, segmenter::source_version(&metadata)))
+    }
+
+    /// Retourne la rendition segmentée d'une piste, en la produisant si besoin
+    /// (mémoire, puis cache disque, puis segmentation). Une rendition produite
+    /// depuis une version antérieure de la source est re-segmentée.
+    pub async fn ensure_rendition(&self, track_id: &str, quality: &str) -> Result<Arc<SegmentedRendition>, AppError> {
+        let profile = self.profile(quality)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound { resource: format!("quality {}", quality) })?;
+        let track_id = validate_filename(track_id)?;
+        let (source, version) = self.track_source(&track_id).await?;
+
+        let key = (track_id.clone(), quality.to_string());
+        let cached = |renditions: &HashMap<RenditionKey, Arc<SegmentedRendition>>| {
+            renditions.get(&key).filter(|rendition| rendition.source_version == version).cloned()
+        };
+        if let Some(rendition) = cached(&*self.renditions.read().await) {
+            return Ok(rendition);
+        }
+
+        let lock = self.segmenting.entry(key.clone()).or_default().clone();
+        let _guard = lock.lock().await;
+
+        // Une autre requête a pu terminer la segmentation pendant l'attente
+        if let Some(rendition) = cached(&*self.renditions.read().await) {
+            return Ok(rendition);
+        }
+
+        let rendition = match self.segmenter
+            .load_rendition(&track_id, quality)
+            .filter(|rendition| rendition.source_version == version)
+        {
+            Some(rendition) => rendition,
+            None => {
+                let segmenter = self.segmenter.clone();
+                tokio::task::spawn_blocking(move || segmenter.segment_track(&source, &track_id, &profile, &version))
+                    .await
+                    .map_err(|e| AppError::ThreadError { message: e.to_string() })??
+            }
+        };
+
+        let rendition = Arc::new(rendition);
+        self.renditions.write().await.insert(key.clone(), rendition.clone());
+        self.segmenting.remove(&key);
+        Ok(rendition)
+    }
+
+    /// Chemin sur disque d'un fichier (init ou segment) d'une rendition
+    pub async fn rendition_file(&self, track_id: &str, quality: &str, file: &str) -> Result<PathBuf, AppError> {
+        let rendition = self.ensure_rendition(track_id, quality).await?;
+        if !rendition.contains_file(file) {
+            return Err(AppError::NotFound { resource: file.to_string() });
+        }
+        Ok(self.segmenter.rendition_dir(&rendition.track_id, quality).join(file))
     }
 
     pub async fn get_streaming_stats(&self) -> serde_json::Value {
@@ -335,6 +454,51 @@ pub struct AdaptiveStreamQuery {
     pub device: Option<String>,
 }
 
+/// Routes HLS et DASH : `/:track_id/master.m3u8`, `/:track_id/manifest.mpd`
+/// et `/:track_id/:quality/:file`
+pub fn hls_routes<S>(streaming_manager: Arc<AdaptiveStreamingManager>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/:track_id/master.m3u8", get(hls_master_playlist))
+        .route("/:track_id/manifest.mpd", get(dash_manifest))
+        .route("/:track_id/:quality/:file", get(hls_quality_playlist))
+        .with_state(streaming_manager)
+}
+
+/// Query string signée à propager sur les URIs des playlists
+pub(crate) fn signed_query(params: &AdaptiveStreamQuery) -> String {
+    format!("expires={}&sig={}", params.expires, params.sig)
+}
+
+pub(crate) fn playlist_response(playlist: String) -> Result<Response, (StatusCode, String)> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(playlist.into())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+pub(crate) fn manifest_response(manifest: String) -> Result<Response, (StatusCode, String)> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/dash+xml")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(manifest.into())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+pub(crate) fn error_response(error: AppError) -> (StatusCode, String) {
+    let status = match error {
+        AppError::FileNotFound | AppError::NotFound { .. } => StatusCode::NOT_FOUND,
+        AppError::ValidationError(_) | AppError::UnsupportedCodec { .. } => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, error.to_string())
+}
+
 /// Handler pour le master playlist HLS
 pub async fn hls_master_playlist(
     AxumPath(track_id): AxumPath<String>,
@@ -346,26 +510,33 @@ pub async fn hls_master_playlist(
         return Err((StatusCode::FORBIDDEN, "Signature invalide".to_string()));
     }
 
-    let _base_url = format!("http://localhost:{}", streaming_manager.config.port);
-    
-    match streaming_manager.generate_master_playlist(&track_id).await {
-        Ok(playlist) => {
-            let response = Response::builder()
-                .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")
-                .header(header::CACHE_CONTROL, "no-cache")
-                .body(playlist.into())
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-            
-            Ok(response)
-        }
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    let playlist = streaming_manager
+        .generate_master_playlist(&track_id, Some(&signed_query(&params)))
+        .await
+        .map_err(error_response)?;
+    playlist_response(playlist)
+}
+
+/// Handler pour le manifest DASH d'une piste
+pub async fn dash_manifest(
+    AxumPath(track_id): AxumPath<String>,
+    Query(params): Query<AdaptiveStreamQuery>,
+    State(streaming_manager): State<Arc<AdaptiveStreamingManager>>,
+) -> Result<Response, (StatusCode, String)> {
+    if !validate_signature(&streaming_manager.config, &track_id, &params.expires, &params.sig) {
+        return Err((StatusCode::FORBIDDEN, "Signature invalide".to_string()));
     }
+
+    let manifest = streaming_manager
+        .generate_dash_manifest(&track_id, Some(&signed_query(&params)))
+        .await
+        .map_err(error_response)?;
+    manifest_response(manifest)
 }
 
-/// Handler pour les playlists de qualité spécifique
+/// Handler pour les playlists de qualité spécifique et leurs segments
 pub async fn hls_quality_playlist(
-    AxumPath((track_id, quality)): AxumPath<(String, String)>,
+    AxumPath((track_id, quality, file)): AxumPath<(String, String, String)>,
     Query(params): Query<AdaptiveStreamQuery>,
     State(streaming_manager): State<Arc<AdaptiveStreamingManager>>,
 ) -> Result<Response, (StatusCode, String)> {
@@ -374,19 +545,93 @@ pub async fn hls_quality_playlist(
         return Err((StatusCode::FORBIDDEN, "Signature invalide".to_string()));
     }
 
-    let _base_url = format!("http://localhost:{}", streaming_manager.config.port);
-    
-    match streaming_manager.generate_quality_playlist(&track_id, &quality).await {
-        Ok(playlist) => {
-            let response = Response::builder()
-                .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")
-                .header(header::CACHE_CONTROL, "no-cache")
-                .body(playlist.into())
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-            
-            Ok(response)
+    if file == "playlist.m3u8" {
+        return match streaming_manager.generate_quality_playlist(&track_id, &quality, Some(&signed_query(&params))).await {
+            Ok(playlist) => playlist_response(playlist),
+            Err(e) => match e.downcast::<AppError>() {
+                Ok(app_error) => Err(error_response(*app_error)),
+                Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+            },
+        };
+    }
+
+    let path = streaming_manager.rendition_file(&track_id, &quality, &file)
+        .await
+        .map_err(error_response)?;
+    let data = tokio::fs::read(&path)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, "Segment introuvable".to_string()))?;
+
+    // Les noms des fichiers portent la version de la source : un même URI
+    // désigne toujours les mêmes octets
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "audio/mp4")
+        .header(header::CONTENT_LENGTH, data.len())
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        .body(data.into())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_tone(path: &Path, seconds: u32) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..22050 * seconds {
+            let sample = ((i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 22050.0).sin() * 8000.0) as i16;
+            writer.write_sample(sample).unwrap();
         }
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        writer.finalize().unwrap();
+    }
+
+    fn manager(dir: &Path) -> AdaptiveStreamingManager {
+        let mut config = Config::from_env().unwrap();
+        config.audio_dir = dir.to_string_lossy().to_string();
+        config.compression.output_dir = dir.join("out").to_string_lossy().to_string();
+        AdaptiveStreamingManager::new(Arc::new(config))
+    }
+
+    #[tokio::test]
+    async fn test_master_playlist_of_unknown_track_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(dir.path());
+
+        let error = manager.generate_master_playlist("missing.wav", None).await.unwrap_err();
+        assert_eq!(error_response(error).0, StatusCode::NOT_FOUND);
+
+        write_tone(&dir.path().join("tone.wav"), 1);
+        let playlist = manager.generate_master_playlist("tone.wav", None).await.unwrap();
+        assert!(playlist.contains("\nlow/playlist.m3u8\n"));
+    }
+
+    #[tokio::test]
+    async fn test_replaced_source_is_resegmented_under_new_uris() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(dir.path());
+        let source = dir.path().join("tone.wav");
+
+        write_tone(&source, 2);
+        let first = manager.ensure_rendition("tone.wav", "low").await.unwrap();
+        assert!(Arc::ptr_eq(&first, &manager.ensure_rendition("tone.wav", "low").await.unwrap()));
+
+        write_tone(&source, 8);
+        let second = manager.ensure_rendition("tone.wav", "low").await.unwrap();
+        assert_ne!(first.source_version, second.source_version);
+        assert_ne!(first.init_uri, second.init_uri);
+        assert!(second.total_duration() > first.total_duration() + 5.0);
+
+        // Les URIs de l'ancienne version ne sont plus servies
+        let stale = manager.rendition_file("tone.wav", "low", &first.segments[0].uri).await;
+        assert!(matches!(stale, Err(AppError::NotFound { .. })));
+        let current = manager.rendition_file("tone.wav", "low", &second.segments[0].uri).await.unwrap();
+        assert!(current.exists());
     }
-} 
\ No newline at end of file
+}
@@ -11,6 +11,7 @@ use serde_json;
 
 use super::webrtc::{WebRTCManager, WebRTCConfig};
 use super::sync_manager::{SyncManager, SyncConfig};
+use crate::core::SyncEngine;
 use super::live_recording::{LiveRecordingManager, RecordingConfig, RecordingQuality};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -185,9 +186,10 @@ pub struct AdvancedStreamingEngine {
 }
 
 impl AdvancedStreamingEngine {
-    pub fn new(config: AdvancedStreamingConfig) -> Self {
+    /// `sync_engine` fournit l'horloge maître et les estimations d'horloge des clients
+    pub fn new(config: AdvancedStreamingConfig, sync_engine: Arc<SyncEngine>) -> Self {
         let webrtc_manager = Arc::new(WebRTCManager::new(config.webrtc.clone()));
-        let sync_manager = Arc::new(SyncManager::new(config.sync.clone()));
+        let sync_manager = Arc::new(SyncManager::new(config.sync.clone(), sync_engine));
         let recording_manager = Arc::new(LiveRecordingManager::new(config.recording.clone()));
         let (streaming_tx, _) = broadcast::channel(1000);
 
@@ -0,0 +1,227 @@
+//! WebSocket de synchronisation d'horloge
+//!
+//! Remplace NTP pour les clients : le serveur envoie un `ClockMessage::Ping`
+//! horodaté (`t0`) à chaque intervalle de synchronisation, le client répond
+//! par un `Pong` portant ses heures locales de réception (`t1`) et d'envoi
+//! (`t2`), et le serveur horodate la réception (`t3`). Chaque échange met à
+//! jour l'estimation du client (décalage, aller-retour, dérive), renvoyée en
+//! `Estimate`. Un client qui indique son listener (`listener_id` reçu sur la
+//! WebSocket audio) et son stream reçoit aussi les `Adjustment` calculés par
+//! le `SyncEngine`.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::Response,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::core::{ClockExchange, ClockMessage, StreamManager, SyncEngine, SyncEvent};
+use crate::error::AppError;
+
+/// Pings sans réponse conservés ; les plus anciens sont abandonnés
+const MAX_PENDING_PINGS: usize = 8;
+
+#[derive(Clone)]
+struct ClockSyncState {
+    engine: Arc<SyncEngine>,
+    streams: Arc<StreamManager>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClockQuery {
+    /// Identifiant du client, son listener s'il en a un
+    client_id: Option<Uuid>,
+    /// Stream écouté, pour recevoir les ajustements
+    stream_id: Option<Uuid>,
+}
+
+/// Route `GET /clock` (WebSocket), un ping par intervalle de synchronisation
+pub fn clock_sync_routes<S>(engine: Arc<SyncEngine>, streams: Arc<StreamManager>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/clock", get(clock_socket))
+        .with_state(ClockSyncState { engine, streams })
+}
+
+async fn clock_socket(
+    State(state): State<ClockSyncState>,
+    Query(query): Query<ClockQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let client_id = query.client_id.unwrap_or_else(Uuid::new_v4);
+    ws.on_upgrade(move |socket| async move {
+        if let Err(e) = serve_clock(socket, &state, client_id, query.stream_id).await {
+            debug!("WebSocket d'horloge du client {} fermée: {}", client_id, e);
+        }
+        state.engine.remove_client(client_id);
+    })
+}
+
+async fn send_message(socket: &mut WebSocket, message: &ClockMessage) -> Result<(), AppError> {
+    let text = serde_json::to_string(message).map_err(|_| AppError::SerializationError)?;
+    socket
+        .send(Message::Text(text))
+        .await
+        .map_err(|e| AppError::NetworkError { message: e.to_string() })
+}
+
+async fn serve_clock(
+    mut socket: WebSocket,
+    state: &ClockSyncState,
+    client_id: Uuid,
+    stream_id: Option<Uuid>,
+) -> Result<(), AppError> {
+    let engine = &state.engine;
+    let mut events = engine.subscribe_events();
+    send_message(&mut socket, &ClockMessage::Hello { client_id, server_time_us: engine.now_us()? }).await?;
+
+    let mut pending: VecDeque<(u64, u64)> = VecDeque::new();
+    let mut sequence = 0u64;
+    let mut ticker = tokio::time::interval(engine.sync_interval());
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                // Horodatage au plus près de la réception
+                let t3 = engine.now_us()?;
+                match message {
+                    Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClockMessage>(&text) {
+                        Ok(ClockMessage::Pong { sequence, t1, t2 }) => {
+                            // Seul le t0 mémorisé fait foi : le client ne peut pas le falsifier
+                            let Some((_, t0)) = pending
+                                .iter()
+                                .position(|(pending_sequence, _)| *pending_sequence == sequence)
+                                .and_then(|index| pending.remove(index))
+                            else {
+                                continue;
+                            };
+                            let estimate = match engine.record_clock_exchange(client_id, ClockExchange { t0, t1, t2, t3 }) {
+                                Ok(estimate) => estimate,
+                                Err(e) => {
+                                    debug!("Échange d'horloge du client {} rejeté: {}", client_id, e);
+                                    continue;
+                                }
+                            };
+                            send_message(&mut socket, &ClockMessage::Estimate { estimate }).await?;
+                            if let Some((stream_id, listener)) = stream_id
+                                .and_then(|stream_id| Some((stream_id, state.streams.listener(stream_id, client_id)?)))
+                            {
+                                engine.sync_listener(stream_id, &listener).await?;
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => debug!("Message d'horloge invalide: {}", e),
+                    },
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(AppError::NetworkError { message: e.to_string() }),
+                }
+            }
+            _ = ticker.tick() => {
+                sequence += 1;
+                let t0 = engine.now_us()?;
+                pending.push_back((sequence, t0));
+                if pending.len() > MAX_PENDING_PINGS {
+                    pending.pop_front();
+                }
+                send_message(&mut socket, &ClockMessage::Ping { sequence, t0 }).await?;
+            }
+            event = events.recv() => match event {
+                Ok(SyncEvent::SyncAdjustmentApplied { client_id: target, adjustment }) if target == client_id => {
+                    send_message(&mut socket, &ClockMessage::Adjustment { adjustment }).await?;
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sync::SyncConfig;
+    use futures::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+    #[tokio::test]
+    async fn test_estimate_rejects_delayed_exchanges() {
+        let engine = SyncEngine::new(SyncConfig::default()).await.unwrap();
+        let client_id = Uuid::new_v4();
+        // Horloge client en avance de 250 ms et plus rapide de 50 ppm, 10 ms d'aller-retour
+        let client_time = |server_us: u64| (server_us as f64 * (1.0 + 50e-6)) as u64 + 250_000;
+        let mut estimate = None;
+        for index in 0..20u64 {
+            let t0 = index * 1_000_000;
+            // Un échange sur quatre subit 80 ms de file d'attente à l'aller
+            let outbound = match index % 4 {
+                3 => 85_000,
+                _ => 5_000,
+            };
+            let t1 = client_time(t0 + outbound);
+            let t2 = t1 + 200;
+            let t3 = t0 + outbound + 200 + 5_000;
+            estimate = Some(engine.record_clock_exchange(client_id, ClockExchange { t0, t1, t2, t3 }).unwrap());
+        }
+        let estimate = estimate.unwrap();
+        assert_eq!(estimate.samples, 15);
+        assert!((estimate.round_trip_ms - 10.0).abs() < 0.01);
+        // Dernier échange retenu : le 19e (t0 = 18 s), mesuré à l'arrivée du ping
+        let expected_offset = 250.0 + 50e-6 * 18_005_000.0 / 1000.0;
+        assert!((estimate.offset_ms - expected_offset).abs() < 0.05, "{:?}", estimate);
+        assert!((estimate.drift_ppm - 50.0).abs() < 5.0, "{:?}", estimate);
+
+        // Horodatages incohérents : refusés
+        let invalid = ClockExchange { t0: 10, t1: 50, t2: 40, t3: 20 };
+        assert!(engine.record_clock_exchange(client_id, invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ping_pong_over_websocket() {
+        let config = SyncConfig { sync_interval: Duration::from_millis(20), ..SyncConfig::default() };
+        let engine = Arc::new(SyncEngine::new(config).await.unwrap());
+        let streams = Arc::new(StreamManager::new(crate::core::StreamConfig::default()).unwrap());
+        let app = clock_sync_routes::<()>(engine.clone(), streams);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client_id = Uuid::new_v4();
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/clock?client_id={}", address, client_id))
+            .await
+            .unwrap();
+        let mut estimates = 0;
+        while estimates < 3 {
+            let message = tokio::time::timeout(Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+            let ClientMessage::Text(text) = message else { continue };
+            match serde_json::from_str::<ClockMessage>(&text).unwrap() {
+                ClockMessage::Hello { client_id: id, .. } => assert_eq!(id, client_id),
+                ClockMessage::Ping { sequence, t0 } => {
+                    // Client en retard de 2 s sur le serveur, réponse immédiate
+                    let local = t0 - 2_000_000;
+                    let pong = ClockMessage::Pong { sequence, t1: local, t2: local };
+                    client.send(ClientMessage::Text(serde_json::to_string(&pong).unwrap())).await.unwrap();
+                }
+                ClockMessage::Estimate { estimate } => {
+                    assert!((estimate.offset_ms + 2000.0).abs() < 100.0, "{:?}", estimate);
+                    estimates += 1;
+                }
+                ClockMessage::Pong { .. } | ClockMessage::Adjustment { .. } => {}
+            }
+        }
+        assert!(engine.clock_estimate(client_id).is_some());
+        client.close(None).await.unwrap();
+    }
+}
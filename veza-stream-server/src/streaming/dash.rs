@@ -0,0 +1,319 @@
+//! Manifests MPEG-DASH (ISO/IEC 23009-1)
+//!
+//! Les MPD décrivent les mêmes segments CMAF que les playlists HLS : une
+//! `Representation` par rendition, avec un `SegmentTemplate` et une
+//! `SegmentTimeline` exacte, relatifs au répertoire de la rendition.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::time::Duration;
+
+use crate::streaming::segmenter::SegmentedRendition;
+
+/// Schéma de configuration des canaux audio (ISO/IEC 23003-3)
+const CHANNEL_CONFIGURATION_SCHEME: &str = "urn:mpeg:dash:23003:3:audio_channel_configuration:2011";
+
+/// Une `Representation` et sa timeline de segments
+#[derive(Debug, Clone)]
+pub struct MpdRepresentation {
+    pub id: String,
+    pub bandwidth: u32,
+    pub codecs: String,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub timescale: u32,
+    /// URI du segment d'initialisation, relative à la représentation
+    pub initialization: String,
+    /// Modèle des URIs de segments, avec `$Number$`
+    pub media: String,
+    /// Numéro du premier segment listé (`$Number$` de `media`)
+    pub start_number: u64,
+    /// `(début, durée)` de chaque segment, en unités de timescale
+    pub segments: Vec<(u64, u64)>,
+}
+
+impl MpdRepresentation {
+    /// Représentation d'une rendition VOD segmentée
+    pub fn from_rendition(rendition: &SegmentedRendition, bandwidth: u32) -> Self {
+        let timescale = rendition.sample_rate;
+        let mut start = 0;
+        let segments = rendition
+            .segments
+            .iter()
+            .map(|segment| {
+                let duration = (segment.duration * f64::from(timescale)).round() as u64;
+                let entry = (start, duration);
+                start += duration;
+                entry
+            })
+            .collect();
+
+        Self {
+            id: rendition.quality_id.clone(),
+            bandwidth,
+            codecs: rendition.codecs.clone(),
+            sample_rate: rendition.sample_rate,
+            channels: rendition.channels,
+            timescale,
+            initialization: rendition.init_uri.clone(),
+            media: rendition.media_template(),
+            start_number: 0,
+            segments,
+        }
+    }
+
+    fn duration_secs(&self) -> f64 {
+        let total: u64 = self.segments.iter().map(|(_, duration)| duration).sum();
+        total as f64 / f64::from(self.timescale.max(1))
+    }
+
+    fn max_segment_secs(&self) -> f64 {
+        let max = self.segments.iter().map(|(_, duration)| *duration).max().unwrap_or(0);
+        max as f64 / f64::from(self.timescale.max(1))
+    }
+}
+
+/// Paramètres d'un MPD dynamique (live)
+#[derive(Debug, Clone)]
+pub struct LiveMpdTiming {
+    /// Horloge murale correspondant au temps média zéro
+    pub availability_start_time: DateTime<Utc>,
+    pub publish_time: DateTime<Utc>,
+    pub segment_duration: Duration,
+    /// Profondeur de la fenêtre de timeshift (DVR)
+    pub time_shift_buffer_depth: Duration,
+    /// Vrai une fois le stream terminé
+    pub ended: bool,
+}
+
+/// Génère le MPD statique d'une piste VOD
+pub fn vod_manifest(representations: &[MpdRepresentation], uri_query: Option<&str>) -> String {
+    let duration = representations
+        .iter()
+        .map(MpdRepresentation::duration_secs)
+        .fold(0.0, f64::max);
+    let max_segment = representations
+        .iter()
+        .map(MpdRepresentation::max_segment_secs)
+        .fold(0.0, f64::max);
+
+    let mut mpd = xml_header();
+    mpd.push_str(&format!(
+        "<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"{}\" type=\"static\" \
+         mediaPresentationDuration=\"{}\" maxSegmentDuration=\"{}\" minBufferTime=\"{}\">\n",
+        PROFILES,
+        iso_duration(duration),
+        iso_duration(max_segment),
+        iso_duration(max_segment.max(2.0)),
+    ));
+    mpd.push_str("  <Period id=\"0\" start=\"PT0S\">\n");
+    write_adaptation_set(&mut mpd, representations, uri_query);
+    mpd.push_str("  </Period>\n</MPD>\n");
+    mpd
+}
+
+/// Génère le MPD dynamique d'un stream live
+pub fn live_manifest(representations: &[MpdRepresentation], timing: &LiveMpdTiming, uri_query: Option<&str>) -> String {
+    let segment_secs = timing.segment_duration.as_secs_f64();
+
+    // Un live terminé reste `dynamic` : la fin est annoncée par
+    // `mediaPresentationDuration` et la disparition de `minimumUpdatePeriod`
+    let update = if timing.ended {
+        let duration = representations
+            .iter()
+            .map(|representation| {
+                representation.segments.last().map_or(0.0, |(start, duration)| {
+                    (start + duration) as f64 / f64::from(representation.timescale.max(1))
+                })
+            })
+            .fold(0.0, f64::max);
+        format!("mediaPresentationDuration=\"{}\"", iso_duration(duration))
+    } else {
+        format!("minimumUpdatePeriod=\"{}\"", iso_duration(segment_secs))
+    };
+
+    let mut mpd = xml_header();
+    mpd.push_str(&format!(
+        "<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"{}\" type=\"dynamic\" \
+         availabilityStartTime=\"{}\" publishTime=\"{}\" {} \
+         timeShiftBufferDepth=\"{}\" suggestedPresentationDelay=\"{}\" \
+         maxSegmentDuration=\"{}\" minBufferTime=\"{}\">\n",
+        PROFILES,
+        xml_datetime(&timing.availability_start_time),
+        xml_datetime(&timing.publish_time),
+        update,
+        iso_duration(timing.time_shift_buffer_depth.as_secs_f64().max(segment_secs * 3.0)),
+        iso_duration(segment_secs * 3.0),
+        iso_duration(segment_secs + 1.0),
+        iso_duration(segment_secs.max(2.0)),
+    ));
+
+    mpd.push_str("  <Period id=\"0\" start=\"PT0S\">\n");
+    write_adaptation_set(&mut mpd, representations, uri_query);
+    mpd.push_str("  </Period>\n");
+
+    if !timing.ended {
+        // Horloge serveur pour que les clients calculent la live edge
+        mpd.push_str(&format!(
+            "  <UTCTiming schemeIdUri=\"urn:mpeg:dash:utc:direct:2014\" value=\"{}\"/>\n",
+            xml_datetime(&timing.publish_time)
+        ));
+    }
+    mpd.push_str("</MPD>\n");
+    mpd
+}
+
+const PROFILES: &str = "urn:mpeg:dash:profile:isoff-live:2011,urn:mpeg:dash:profile:cmaf:2019";
+
+fn xml_header() -> String {
+    String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n")
+}
+
+fn write_adaptation_set(mpd: &mut String, representations: &[MpdRepresentation], uri_query: Option<&str>) {
+    // Les URIs relatives n'héritent pas de la query string signée
+    let suffix = uri_query.map(|query| format!("?{}", xml_escape(query))).unwrap_or_default();
+
+    mpd.push_str(
+        "    <AdaptationSet id=\"0\" contentType=\"audio\" mimeType=\"audio/mp4\" lang=\"und\" \
+         segmentAlignment=\"true\" startWithSAP=\"1\">\n",
+    );
+    mpd.push_str("      <Role schemeIdUri=\"urn:mpeg:dash:role:2011\" value=\"main\"/>\n");
+
+    for representation in representations {
+        mpd.push_str(&format!(
+            "      <Representation id=\"{}\" bandwidth=\"{}\" codecs=\"{}\" audioSamplingRate=\"{}\">\n",
+            xml_escape(&representation.id),
+            representation.bandwidth,
+            representation.codecs,
+            representation.sample_rate,
+        ));
+        mpd.push_str(&format!(
+            "        <AudioChannelConfiguration schemeIdUri=\"{}\" value=\"{}\"/>\n",
+            CHANNEL_CONFIGURATION_SCHEME, representation.channels
+        ));
+        mpd.push_str(&format!(
+            "        <SegmentTemplate timescale=\"{}\" startNumber=\"{}\" \
+             initialization=\"{id}/{}{suffix}\" media=\"{id}/{}{suffix}\">\n",
+            representation.timescale,
+            representation.start_number,
+            xml_escape(&representation.initialization),
+            xml_escape(&representation.media),
+            id = xml_escape(&representation.id),
+            suffix = suffix,
+        ));
+        write_segment_timeline(mpd, &representation.segments);
+        mpd.push_str("        </SegmentTemplate>\n");
+        mpd.push_str("      </Representation>\n");
+    }
+
+    mpd.push_str("    </AdaptationSet>\n");
+}
+
+/// `SegmentTimeline` compactée : les durées identiques consécutives sont
+/// regroupées avec l'attribut `r`
+fn write_segment_timeline(mpd: &mut String, segments: &[(u64, u64)]) {
+    mpd.push_str("          <SegmentTimeline>\n");
+
+    let mut index = 0;
+    while index < segments.len() {
+        let (start, duration) = segments[index];
+        let mut repeat = 0;
+        while index + repeat + 1 < segments.len() {
+            let (next_start, next_duration) = segments[index + repeat + 1];
+            if next_duration != duration || next_start != start + duration * (repeat as u64 + 1) {
+                break;
+            }
+            repeat += 1;
+        }
+
+        if repeat > 0 {
+            mpd.push_str(&format!("            <S t=\"{}\" d=\"{}\" r=\"{}\"/>\n", start, duration, repeat));
+        } else {
+            mpd.push_str(&format!("            <S t=\"{}\" d=\"{}\"/>\n", start, duration));
+        }
+        index += repeat + 1;
+    }
+
+    mpd.push_str("          </SegmentTimeline>\n");
+}
+
+/// Durée ISO 8601 (`PT12.345S`)
+fn iso_duration(seconds: f64) -> String {
+    format!("PT{:.3}S", seconds)
+}
+
+fn xml_datetime(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn representation(segments: Vec<(u64, u64)>) -> MpdRepresentation {
+        MpdRepresentation {
+            id: "high".to_string(),
+            bandwidth: 320_000,
+            codecs: "mp4a.40.34".to_string(),
+            sample_rate: 44100,
+            channels: 2,
+            timescale: 44100,
+            initialization: "init.mp4".to_string(),
+            media: "segment$Number$.m4s".to_string(),
+            start_number: 0,
+            segments,
+        }
+    }
+
+    #[test]
+    fn test_segment_timeline_compaction() {
+        let mut mpd = String::new();
+        write_segment_timeline(&mut mpd, &[(0, 264960), (264960, 264960), (529920, 264960), (794880, 86400)]);
+
+        assert!(mpd.contains("<S t=\"0\" d=\"264960\" r=\"2\"/>"));
+        assert!(mpd.contains("<S t=\"794880\" d=\"86400\"/>"));
+    }
+
+    #[test]
+    fn test_vod_manifest() {
+        let mpd = vod_manifest(&[representation(vec![(0, 264960), (264960, 88200)])], Some("expires=1&sig=ab"));
+
+        assert!(mpd.contains("type=\"static\""));
+        assert!(mpd.contains("mediaPresentationDuration=\"PT8.008S\""));
+        assert!(mpd.contains("<Representation id=\"high\" bandwidth=\"320000\" codecs=\"mp4a.40.34\""));
+        assert!(mpd.contains("media=\"high/segment$Number$.m4s?expires=1&amp;sig=ab\""));
+        assert!(mpd.contains("initialization=\"high/init.mp4?expires=1&amp;sig=ab\""));
+    }
+
+    #[test]
+    fn test_live_manifest() {
+        let mut live = representation(vec![(441000, 88200), (529200, 88200)]);
+        live.start_number = 5;
+        let timing = LiveMpdTiming {
+            availability_start_time: DateTime::parse_from_rfc3339("2024-01-01T20:00:00Z").unwrap().with_timezone(&Utc),
+            publish_time: DateTime::parse_from_rfc3339("2024-01-01T20:00:14Z").unwrap().with_timezone(&Utc),
+            segment_duration: Duration::from_secs(2),
+            time_shift_buffer_depth: Duration::from_secs(3600),
+            ended: false,
+        };
+
+        let mpd = live_manifest(&[live.clone()], &timing, None);
+        assert!(mpd.contains("type=\"dynamic\""));
+        assert!(mpd.contains("availabilityStartTime=\"2024-01-01T20:00:00.000Z\""));
+        assert!(mpd.contains("timeShiftBufferDepth=\"PT3600.000S\""));
+        assert!(mpd.contains("startNumber=\"5\""));
+        assert!(mpd.contains("<S t=\"441000\" d=\"88200\" r=\"1\"/>"));
+        assert!(mpd.contains("<UTCTiming"));
+
+        let ended = live_manifest(&[live], &LiveMpdTiming { ended: true, ..timing }, None);
+        assert!(!ended.contains("minimumUpdatePeriod"));
+        assert!(ended.contains("mediaPresentationDuration=\"PT14.000S\""));
+    }
+}
@@ -0,0 +1,448 @@
+//! Écriture de segments CMAF / MP4 fragmenté (ISO/IEC 14496-12)
+//!
+//! Produit le segment d'initialisation (`ftyp` + `moov`) et les segments
+//! média (`styp` + `moof` + `mdat`) d'une piste audio unique, partagés par
+//! les playlists HLS et les manifests DASH, ainsi que les fichiers MP4 non
+//! fragmentés (rendus M4A téléchargeables).
+
+/// Codec audio porté par la piste, avec sa configuration décodeur
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fmp4AudioCodec {
+    /// MPEG-1/2 Layer III dans une sample entry `mp4a`
+    Mp3,
+    /// AAC dans une sample entry `mp4a`, l'AudioSpecificConfig servant de DSI
+    Aac { audio_specific_config: Vec<u8> },
+}
+
+impl Fmp4AudioCodec {
+    /// Chaîne RFC 6381 utilisée dans `CODECS=` (HLS) et `codecs=` (DASH)
+    pub fn codecs_string(&self) -> &'static str {
+        match self {
+            Fmp4AudioCodec::Mp3 => "mp4a.40.34",
+            Fmp4AudioCodec::Aac { audio_specific_config } => {
+                match audio_specific_config.first().map(|byte| byte >> 3) {
+                    Some(5) => "mp4a.40.5",
+                    Some(29) => "mp4a.40.29",
+                    _ => "mp4a.40.2",
+                }
+            }
+        }
+    }
+}
+
+/// Description de la piste audio d'un flux fragmenté
+#[derive(Debug, Clone)]
+pub struct Fmp4Track {
+    pub track_id: u32,
+    /// Timescale des timestamps média, en général le sample rate
+    pub timescale: u32,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub codec: Fmp4AudioCodec,
+    pub avg_bitrate: u32,
+    pub max_bitrate: u32,
+}
+
+/// Un échantillon (frame encodée) et sa durée en unités de timescale
+#[derive(Debug, Clone)]
+pub struct Fmp4Sample {
+    pub duration: u32,
+    pub data: Vec<u8>,
+}
+
+/// Construit le segment d'initialisation `ftyp` + `moov`
+pub fn init_segment(track: &Fmp4Track) -> Vec<u8> {
+    let mut w = BoxWriter::new();
+
+    w.begin(b"ftyp");
+    w.bytes(b"iso6");
+    w.u32(0);
+    for brand in [b"iso6", b"cmfc", b"mp41", b"dash"] {
+        w.bytes(brand);
+    }
+    w.end();
+
+    w.begin(b"moov");
+    write_mvhd(&mut w, track, 0);
+
+    w.begin(b"mvex");
+    w.begin_full(b"trex", 0, 0);
+    w.u32(track.track_id);
+    w.u32(1); // default_sample_description_index
+    w.u32(0); // default_sample_duration
+    w.u32(0); // default_sample_size
+    w.u32(0); // default_sample_flags
+    w.end();
+    w.end();
+
+    write_trak(&mut w, track, 0, |w| {
+        for empty_table in [b"stts", b"stsc", b"stco"] {
+            w.begin_full(empty_table, 0, 0);
+            w.u32(0);
+            w.end();
+        }
+        w.begin_full(b"stsz", 0, 0);
+        w.u32(0);
+        w.u32(0);
+        w.end();
+    });
+    w.end(); // moov
+
+    w.into_inner()
+}
+
+/// Construit un fichier MP4 non fragmenté `ftyp` + `moov` + `mdat`
+///
+/// `moov` précède les données pour permettre la lecture progressive ; tous les
+/// échantillons forment un seul chunk.
+pub fn progressive_file(track: &Fmp4Track, samples: &[Fmp4Sample]) -> Vec<u8> {
+    let mut w = BoxWriter::new();
+
+    w.begin(b"ftyp");
+    w.bytes(b"M4A ");
+    w.u32(0);
+    for brand in [b"M4A ", b"mp42", b"isom"] {
+        w.bytes(brand);
+    }
+    w.end();
+
+    let duration: u64 = samples.iter().map(|sample| u64::from(sample.duration)).sum();
+    let mut chunk_offset_pos = 0;
+    w.begin(b"moov");
+    write_mvhd(&mut w, track, duration * 1000 / u64::from(track.timescale.max(1)));
+    write_trak(&mut w, track, duration, |w| {
+        // Durées regroupées par séries identiques
+        w.begin_full(b"stts", 0, 0);
+        let runs = samples.chunk_by(|a, b| a.duration == b.duration).collect::<Vec<_>>();
+        w.u32(runs.len() as u32);
+        for run in runs {
+            w.u32(run.len() as u32);
+            w.u32(run[0].duration);
+        }
+        w.end();
+
+        w.begin_full(b"stsc", 0, 0);
+        if samples.is_empty() {
+            w.u32(0);
+        } else {
+            w.u32(1);
+            w.u32(1); // first_chunk
+            w.u32(samples.len() as u32);
+            w.u32(1); // sample_description_index
+        }
+        w.end();
+
+        w.begin_full(b"stsz", 0, 0);
+        w.u32(0); // tailles variables
+        w.u32(samples.len() as u32);
+        for sample in samples {
+            w.u32(sample.data.len() as u32);
+        }
+        w.end();
+
+        w.begin_full(b"stco", 0, 0);
+        w.u32(u32::from(!samples.is_empty()));
+        chunk_offset_pos = w.len();
+        if !samples.is_empty() {
+            w.u32(0); // patché une fois la taille du moov connue
+        }
+        w.end();
+    });
+    w.end(); // moov
+
+    if !samples.is_empty() {
+        let chunk_offset = (w.len() + 8) as u32;
+        w.patch_u32(chunk_offset_pos, chunk_offset);
+    }
+    w.begin(b"mdat");
+    for sample in samples {
+        w.bytes(&sample.data);
+    }
+    w.end();
+
+    w.into_inner()
+}
+
+/// Construit un segment média `styp` + `moof` + `mdat`
+///
+/// `base_media_decode_time` est le timestamp (en timescale de la piste) du
+/// premier échantillon du segment.
+pub fn media_segment(
+    track: &Fmp4Track,
+    sequence_number: u32,
+    base_media_decode_time: u64,
+    samples: &[Fmp4Sample],
+) -> Vec<u8> {
+    let mut w = BoxWriter::new();
+
+    w.begin(b"styp");
+    w.bytes(b"msdh");
+    w.u32(0);
+    for brand in [b"msdh", b"msix", b"cmfs"] {
+        w.bytes(brand);
+    }
+    w.end();
+
+    let moof_start = w.len();
+    w.begin(b"moof");
+    w.begin_full(b"mfhd", 0, 0);
+    w.u32(sequence_number);
+    w.end();
+
+    w.begin(b"traf");
+    w.begin_full(b"tfhd", 0, 0x02_0000); // default-base-is-moof
+    w.u32(track.track_id);
+    w.end();
+
+    w.begin_full(b"tfdt", 1, 0);
+    w.u64(base_media_decode_time);
+    w.end();
+
+    // data-offset-present | sample-duration-present | sample-size-present
+    w.begin_full(b"trun", 0, 0x00_0301);
+    w.u32(samples.len() as u32);
+    let data_offset_pos = w.len();
+    w.u32(0); // patché une fois la taille du moof connue
+    for sample in samples {
+        w.u32(sample.duration);
+        w.u32(sample.data.len() as u32);
+    }
+    w.end();
+    w.end(); // traf
+    w.end(); // moof
+
+    let moof_size = w.len() - moof_start;
+    w.patch_u32(data_offset_pos, (moof_size + 8) as u32);
+
+    w.begin(b"mdat");
+    for sample in samples {
+        w.bytes(&sample.data);
+    }
+    w.end();
+
+    w.into_inner()
+}
+
+/// `mvhd`, durée en millisecondes (0 pour un flux fragmenté)
+fn write_mvhd(w: &mut BoxWriter, track: &Fmp4Track, duration_ms: u64) {
+    w.begin_full(b"mvhd", 0, 0);
+    w.u32(0); // creation_time
+    w.u32(0); // modification_time
+    w.u32(1000); // timescale
+    w.u32(duration_ms.min(u64::from(u32::MAX)) as u32);
+    w.u32(0x0001_0000); // rate 1.0
+    w.u16(0x0100); // volume 1.0
+    w.zeros(10);
+    write_unity_matrix(w);
+    w.zeros(24); // pre_defined
+    w.u32(track.track_id + 1); // next_track_ID
+    w.end();
+}
+
+/// `trak` de la piste audio ; `write_tables` écrit les tables d'échantillons
+/// qui suivent le `stsd`. `duration` est en timescale de la piste.
+fn write_trak(w: &mut BoxWriter, track: &Fmp4Track, duration: u64, write_tables: impl FnOnce(&mut BoxWriter)) {
+    let duration_ms = duration * 1000 / u64::from(track.timescale.max(1));
+    w.begin(b"trak");
+    write_tkhd(w, track, duration_ms);
+    w.begin(b"mdia");
+    {
+        w.begin_full(b"mdhd", 0, 0);
+        w.u32(0);
+        w.u32(0);
+        w.u32(track.timescale);
+        w.u32(duration.min(u64::from(u32::MAX)) as u32);
+        w.u16(0x55c4); // langue 'und'
+        w.u16(0);
+        w.end();
+
+        w.begin_full(b"hdlr", 0, 0);
+        w.u32(0);
+        w.bytes(b"soun");
+        w.zeros(12);
+        w.bytes(b"SoundHandler\0");
+        w.end();
+
+        w.begin(b"minf");
+        w.begin_full(b"smhd", 0, 0);
+        w.u16(0); // balance
+        w.u16(0);
+        w.end();
+
+        w.begin(b"dinf");
+        w.begin_full(b"dref", 0, 0);
+        w.u32(1);
+        w.begin_full(b"url ", 0, 1); // données dans le même fichier
+        w.end();
+        w.end();
+        w.end();
+
+        w.begin(b"stbl");
+        write_stsd(w, track);
+        write_tables(w);
+        w.end();
+
+        w.end(); // minf
+    }
+    w.end(); // mdia
+    w.end(); // trak
+}
+
+fn write_tkhd(w: &mut BoxWriter, track: &Fmp4Track, duration_ms: u64) {
+    w.begin_full(b"tkhd", 0, 0x000003); // enabled | in_movie
+    w.u32(0);
+    w.u32(0);
+    w.u32(track.track_id);
+    w.u32(0);
+    w.u32(duration_ms.min(u64::from(u32::MAX)) as u32);
+    w.zeros(8);
+    w.u16(0); // layer
+    w.u16(1); // alternate_group
+    w.u16(0x0100); // volume
+    w.u16(0);
+    write_unity_matrix(w);
+    w.u32(0); // width
+    w.u32(0); // height
+    w.end();
+}
+
+fn write_stsd(w: &mut BoxWriter, track: &Fmp4Track) {
+    w.begin_full(b"stsd", 0, 0);
+    w.u32(1);
+
+    match &track.codec {
+        Fmp4AudioCodec::Mp3 => {
+            write_audio_sample_entry_header(w, b"mp4a", track);
+            // MPEG-1 audio pour 32/44.1/48 kHz, MPEG-2 audio (LSF) en dessous
+            let object_type = if track.sample_rate >= 32000 { 0x6B } else { 0x69 };
+            write_esds(w, track, object_type, None);
+            w.end();
+        }
+        Fmp4AudioCodec::Aac { audio_specific_config } => {
+            write_audio_sample_entry_header(w, b"mp4a", track);
+            // 0x40 : MPEG-4 Audio, le profil est porté par l'AudioSpecificConfig
+            write_esds(w, track, 0x40, Some(audio_specific_config));
+            w.end();
+        }
+    }
+
+    w.end();
+}
+
+fn write_audio_sample_entry_header(w: &mut BoxWriter, fourcc: &[u8; 4], track: &Fmp4Track) {
+    w.begin(fourcc);
+    w.zeros(6);
+    w.u16(1); // data_reference_index
+    w.zeros(8);
+    w.u16(track.channels);
+    w.u16(16); // samplesize
+    w.u16(0);
+    w.u16(0);
+    w.u32(track.sample_rate.min(0xFFFF) << 16);
+}
+
+fn write_esds(w: &mut BoxWriter, track: &Fmp4Track, object_type: u8, decoder_specific_info: Option<&[u8]>) {
+    w.begin_full(b"esds", 0, 0);
+
+    let dsi_len = decoder_specific_info.map_or(0, |dsi| 2 + dsi.len());
+    let decoder_config_len = 13 + dsi_len;
+    let es_len = 3 + 2 + decoder_config_len + 3;
+
+    w.u8(0x03); // ES_DescrTag
+    w.u8(es_len as u8);
+    w.u16(0); // ES_ID
+    w.u8(0);
+
+    w.u8(0x04); // DecoderConfigDescrTag
+    w.u8(decoder_config_len as u8);
+    w.u8(object_type);
+    w.u8(0x15); // streamType audio (0x05) << 2 | 1
+    w.u8(0);
+    w.u16(0); // bufferSizeDB
+    w.u32(track.max_bitrate);
+    w.u32(track.avg_bitrate);
+
+    if let Some(dsi) = decoder_specific_info {
+        w.u8(0x05); // DecSpecificInfoTag
+        w.u8(dsi.len() as u8);
+        w.bytes(dsi);
+    }
+
+    w.u8(0x06); // SLConfigDescrTag
+    w.u8(1);
+    w.u8(0x02);
+
+    w.end();
+}
+
+fn write_unity_matrix(w: &mut BoxWriter) {
+    for value in [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000] {
+        w.u32(value);
+    }
+}
+
+/// Écrivain de boxes ISO BMFF avec tailles calculées à la fermeture
+struct BoxWriter {
+    buf: Vec<u8>,
+    open: Vec<usize>,
+}
+
+impl BoxWriter {
+    fn new() -> Self {
+        Self { buf: Vec::new(), open: Vec::new() }
+    }
+
+    fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn begin(&mut self, fourcc: &[u8; 4]) {
+        self.open.push(self.buf.len());
+        self.u32(0);
+        self.bytes(fourcc);
+    }
+
+    fn begin_full(&mut self, fourcc: &[u8; 4], version: u8, flags: u32) {
+        self.begin(fourcc);
+        self.u32((u32::from(version) << 24) | (flags & 0x00FF_FFFF));
+    }
+
+    fn end(&mut self) {
+        let start = self.open.pop().expect("end() sans begin()");
+        let size = (self.buf.len() - start) as u32;
+        self.patch_u32(start, size);
+    }
+
+    fn patch_u32(&mut self, pos: usize, value: u32) {
+        self.buf[pos..pos + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn bytes(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    fn zeros(&mut self, count: usize) {
+        self.buf.resize(self.buf.len() + count, 0);
+    }
+
+    fn into_inner(self) -> Vec<u8> {
+        debug_assert!(self.open.is_empty());
+        self.buf
+    }
+}
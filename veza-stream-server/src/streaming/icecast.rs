@@ -0,0 +1,736 @@
+//! Serveur compatible Icecast2 pour les sources live
+//!
+//! BUTT, Mixxx, OBS ou liquidsoap se connectent en `SOURCE` (Icecast < 2.4)
+//! ou `PUT` (Icecast ≥ 2.4) avec la clé de stream comme mot de passe Basic,
+//! puis envoient un corps MP3 ou Ogg (Opus/Vorbis) sans fin ni
+//! `Content-Length`. hyper ne sait pas lire ce type de corps : la connexion
+//! est donc traitée directement sur TCP. Les mises à jour de titre passent
+//! par `GET /admin/metadata?mode=updinfo` (ou `/admin.cgi` façon SHOUTcast).
+//!
+//! Les auditeurs écoutent un point de montage (`GET /live`, `/live.aac`) ou
+//! un stream live par son identifiant (`GET /streams/<id>.mp3`), avec la même
+//! URL signée (`expires`, `sig` sur l'identifiant du stream) que le HLS live ;
+//! `Icy-MetaData: 1` active l'insertion du titre en cours dans le flux.
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::{Duration, Instant}};
+
+use base64::Engine;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::core::{Listener, TrackInfo};
+use crate::error::AppError;
+use crate::streaming::icy::{stream_title, IcyCodec, IcyMetadataWriter, IcyRelays, IcySubscription};
+use crate::streaming::ingest::{normalize_mount, LiveIngest, SourcePipeline};
+use crate::utils::validate_signature;
+
+/// Taille maximale de l'en-tête d'une requête
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+/// Délai pour recevoir l'en-tête complet
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+/// Auditeur déconnecté s'il ne lit plus rien pendant ce délai
+const LISTENER_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Requête HTTP/ICE lue sur la connexion
+#[derive(Debug)]
+pub(crate) struct IcecastRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    headers: Vec<(String, String)>,
+}
+
+impl IcecastRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let query = self.query.as_deref()?;
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Mot de passe Basic (la clé de stream) ; l'utilisateur, souvent
+    /// `source`, est ignoré
+    pub fn basic_password(&self) -> Option<String> {
+        let encoded = self.header("authorization")?.strip_prefix("Basic ")?;
+        let decoded = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+        let credentials = String::from_utf8(decoded).ok()?;
+        credentials.split_once(':').map(|(_, password)| password.to_string())
+    }
+}
+
+/// Accepte les connexions Icecast jusqu'à l'arrêt du serveur
+pub async fn serve(listener: TcpListener, config: Arc<Config>, ingest: Arc<LiveIngest>, relays: Arc<IcyRelays>) {
+    loop {
+        let (socket, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Connexion Icecast refusée: {}", e);
+                continue;
+            }
+        };
+        let config = config.clone();
+        let ingest = ingest.clone();
+        let relays = relays.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(socket, peer, config, ingest, relays).await {
+                debug!("Connexion Icecast {} fermée: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    socket: TcpStream,
+    peer: SocketAddr,
+    config: Arc<Config>,
+    ingest: Arc<LiveIngest>,
+    relays: Arc<IcyRelays>,
+) -> Result<(), AppError> {
+    let _ = socket.set_nodelay(true);
+    let mut reader = BufReader::new(socket);
+    let request = match timeout(HEAD_TIMEOUT, read_request(&mut reader)).await {
+        Ok(request) => request?,
+        Err(_) => return Err(AppError::ConnectionTimeout),
+    };
+    debug!("Icecast {} {} depuis {}", request.method, request.path, peer);
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("SOURCE" | "PUT", _) => handle_source(reader, request, ingest).await,
+        ("GET", "/admin/metadata" | "/admin.cgi") => handle_metadata(reader.get_mut(), &request, &ingest).await,
+        ("GET", _) => handle_listener(reader.into_inner(), peer, &request, &config, &ingest, &relays).await,
+        _ => write_status(reader.get_mut(), "404 File Not Found", "Unknown mountpoint").await,
+    }
+}
+
+/// Lit la ligne de requête et les en-têtes
+pub(crate) async fn read_request<R>(reader: &mut R) -> Result<IcecastRequest, AppError>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    let mut lines = Vec::new();
+    let mut total = 0;
+    loop {
+        let mut line = Vec::new();
+        let read = reader.read_until(b'\n', &mut line).await.map_err(io_error)?;
+        if read == 0 {
+            return Err(invalid_request("connexion fermée avant la fin de l'en-tête"));
+        }
+        total += read;
+        if total > MAX_HEAD_SIZE {
+            return Err(invalid_request("en-tête trop long"));
+        }
+        let line = String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() {
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(line);
+    }
+
+    let mut request_line = lines[0].split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_ascii_uppercase();
+    let target = request_line.next().ok_or_else(|| invalid_request("ligne de requête incomplète"))?;
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+    let headers = lines[1..]
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    Ok(IcecastRequest { method, path, query, headers })
+}
+
+/// Extension de conteneur d'après le `Content-Type` de la source
+fn source_extension(content_type: Option<&str>) -> Option<&'static str> {
+    let mime = content_type?.split(';').next()?.trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "audio/mpeg" | "audio/mp3" | "audio/mpeg3" => Some("mp3"),
+        "application/ogg" | "audio/ogg" | "audio/opus" | "audio/vorbis" => Some("ogg"),
+        _ => None,
+    }
+}
+
+async fn handle_source(mut reader: BufReader<TcpStream>, request: IcecastRequest, ingest: Arc<LiveIngest>) -> Result<(), AppError> {
+    let Some(extension) = source_extension(request.header("content-type")) else {
+        return write_status(reader.get_mut(), "415 Unsupported Media Type", "Content-type not supported").await;
+    };
+    let Some(key) = request.basic_password() else {
+        return write_unauthorized(reader.get_mut()).await;
+    };
+    let source = match ingest.connect(&key, "icecast", &request.path) {
+        Ok(source) => source,
+        Err(AppError::Unauthorized) => return write_unauthorized(reader.get_mut()).await,
+        Err(AppError::AlreadyRunning) => {
+            return write_status(reader.get_mut(), "403 Forbidden", "Mountpoint in use").await;
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some(title) = request.header("ice-name").filter(|title| !title.is_empty()) {
+        debug!("Source {} : ice-name « {} »", source.mount(), title);
+    }
+
+    if request.header("expect").is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue")) {
+        reader.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await.map_err(io_error)?;
+    }
+    reader
+        .get_mut()
+        .write_all(b"HTTP/1.0 200 OK\r\nServer: Icecast 2.4.4\r\n\r\n")
+        .await
+        .map_err(io_error)?;
+
+    let chunked = request
+        .header("transfer-encoding")
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+    let idle_timeout = ingest.config().idle_timeout;
+    let mount = source.mount().to_string();
+
+    let pipeline = SourcePipeline::start(source, extension);
+
+    let mut chunks = chunked.then(ChunkedDecoder::default);
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        let read = match timeout(idle_timeout, reader.read(&mut buffer)).await {
+            Ok(Ok(0)) => break,
+            Ok(Ok(read)) => read,
+            Ok(Err(e)) => {
+                debug!("Source {} : lecture interrompue: {}", mount, e);
+                break;
+            }
+            Err(_) => {
+                info!("Source {} inactive depuis {:?}, déconnexion", mount, idle_timeout);
+                break;
+            }
+        };
+        let data = match chunks.as_mut() {
+            Some(chunks) => chunks.feed(&buffer[..read])?,
+            None => buffer[..read].to_vec(),
+        };
+        if !data.is_empty() && !pipeline.send(data).await {
+            break;
+        }
+        if chunks.as_ref().is_some_and(ChunkedDecoder::is_done) {
+            break;
+        }
+    }
+    let result = pipeline.finish().await;
+    info!("Source {} terminée", mount);
+    result
+}
+
+/// Décodage incrémental d'un corps `Transfer-Encoding: chunked`
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    state: ChunkState,
+}
+
+#[derive(Debug)]
+enum ChunkState {
+    Size(Vec<u8>),
+    Data(u64),
+    /// CRLF restant après les données d'un bloc
+    DataEnd(u8),
+    Done,
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self { state: ChunkState::Size(Vec::new()) }
+    }
+}
+
+impl ChunkedDecoder {
+    pub fn feed(&mut self, mut input: &[u8]) -> Result<Vec<u8>, AppError> {
+        let mut output = Vec::with_capacity(input.len());
+        while !input.is_empty() {
+            match &mut self.state {
+                ChunkState::Size(line) => {
+                    let byte = input[0];
+                    input = &input[1..];
+                    if byte != b'\n' {
+                        line.push(byte);
+                        if line.len() > 64 {
+                            return Err(invalid_request("taille de bloc invalide"));
+                        }
+                        continue;
+                    }
+                    let line = String::from_utf8_lossy(line);
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    let size = u64::from_str_radix(size, 16).map_err(|_| invalid_request("taille de bloc invalide"))?;
+                    self.state = match size {
+                        0 => ChunkState::Done,
+                        size => ChunkState::Data(size),
+                    };
+                }
+                ChunkState::Data(remaining) => {
+                    let take = (*remaining).min(input.len() as u64) as usize;
+                    output.extend_from_slice(&input[..take]);
+                    input = &input[take..];
+                    *remaining -= take as u64;
+                    if *remaining == 0 {
+                        self.state = ChunkState::DataEnd(2);
+                    }
+                }
+                ChunkState::DataEnd(remaining) => {
+                    input = &input[1..];
+                    *remaining -= 1;
+                    if *remaining == 0 {
+                        self.state = ChunkState::Size(Vec::new());
+                    }
+                }
+                ChunkState::Done => break,
+            }
+        }
+        Ok(output)
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+}
+
+/// Stream et codec demandés : point de montage exact (`/live.mp3`), point de
+/// montage suivi d'une extension (`/live.aac`) ou `/streams/<id>[.mp3|.aac]`
+fn resolve_listener_path(ingest: &LiveIngest, path: &str) -> Option<(Uuid, IcyCodec)> {
+    let (base, codec) = match path.rsplit_once('.').and_then(|(base, extension)| Some((base, IcyCodec::from_extension(extension)?))) {
+        Some((base, codec)) => (base, codec),
+        None => (path, IcyCodec::Mp3),
+    };
+    if let Some(stream_id) = ingest.stream_for_mount(path) {
+        return Some((stream_id, codec));
+    }
+    match base.strip_prefix("/streams/") {
+        Some(stream_id) => Uuid::parse_str(stream_id).ok().map(|stream_id| (stream_id, codec)),
+        None => ingest.stream_for_mount(base).map(|stream_id| (stream_id, codec)),
+    }
+}
+
+async fn handle_listener(
+    mut socket: TcpStream,
+    peer: SocketAddr,
+    request: &IcecastRequest,
+    config: &Config,
+    ingest: &LiveIngest,
+    relays: &IcyRelays,
+) -> Result<(), AppError> {
+    let Some((stream_id, codec)) = resolve_listener_path(ingest, &request.path) else {
+        return write_status(&mut socket, "404 File Not Found", "Unknown mountpoint").await;
+    };
+    let expires = request.query_param("expires").unwrap_or_default();
+    let sig = request.query_param("sig").unwrap_or_default();
+    if !validate_signature(config, &stream_id.to_string(), &expires, &sig) {
+        return write_status(&mut socket, "403 Forbidden", "Invalid signature").await;
+    }
+    let streams = relays.streams();
+    let bitrate = relays.config().bitrate;
+    let listener = Listener {
+        id: Uuid::new_v4(),
+        user_id: None,
+        ip_address: peer.ip().to_string(),
+        user_agent: request.header("user-agent").map(str::to_string),
+        connected_at: Instant::now(),
+        current_quality: format!("{}k", bitrate),
+        bandwidth_estimate: bitrate * 1000,
+        buffer_health: 1.0,
+        session_data: HashMap::from([("protocol".to_string(), "icy".to_string())]),
+    };
+    let listener_id = listener.id;
+    match streams.add_listener(stream_id, listener).await {
+        Ok(()) => {}
+        Err(AppError::NotFound { .. }) => {
+            return write_status(&mut socket, "404 File Not Found", "Unknown mountpoint").await;
+        }
+        Err(AppError::ListenerLimitExceeded { .. }) => {
+            return write_status(&mut socket, "403 Forbidden", "Too many listeners").await;
+        }
+        Err(e) => return Err(e),
+    }
+
+    let result = match relays.subscribe(stream_id, codec) {
+        Ok(subscription) => {
+            let metaint = request
+                .header("icy-metadata")
+                .is_some_and(|value| value.trim() == "1")
+                .then_some(relays.config().metaint);
+            let name = streams.stream_title(stream_id).unwrap_or_default();
+            let mut head = format!(
+                "HTTP/1.0 200 OK\r\nContent-Type: {}\r\nicy-name: {}\r\nicy-br: {}\r\nicy-pub: 0\r\n",
+                codec.content_type(),
+                name.replace(['\r', '\n'], " "),
+                bitrate
+            );
+            if let Some(metaint) = metaint {
+                head.push_str(&format!("icy-metaint: {}\r\n", metaint));
+            }
+            head.push_str("Cache-Control: no-cache, no-store\r\nAccess-Control-Allow-Origin: *\r\nServer: Icecast 2.4.4\r\nConnection: close\r\n\r\n");
+            debug!("Auditeur {} ({}) sur le stream {}", listener_id, peer, stream_id);
+
+            match socket.write_all(head.as_bytes()).await {
+                Ok(()) => send_to_listener(&mut socket, subscription, metaint, stream_id, relays).await,
+                Err(e) => Err(io_error(e)),
+            }
+        }
+        Err(e) => {
+            warn!("Relais du stream {} indisponible: {}", stream_id, e);
+            write_status(&mut socket, "503 Service Unavailable", "Stream unavailable").await
+        }
+    };
+
+    // Le stream a pu se terminer entre-temps
+    let _ = streams.remove_listener(stream_id, listener_id).await;
+    debug!("Auditeur {} parti du stream {}", listener_id, stream_id);
+    result
+}
+
+/// Recopie le relais vers l'auditeur jusqu'à la fin du stream ou sa déconnexion
+async fn send_to_listener(
+    socket: &mut TcpStream,
+    subscription: IcySubscription,
+    metaint: Option<usize>,
+    stream_id: Uuid,
+    relays: &IcyRelays,
+) -> Result<(), AppError> {
+    let streams = relays.streams();
+    let IcySubscription { burst, mut receiver } = subscription;
+    let mut metadata = metaint.map(IcyMetadataWriter::new);
+    let mut output = Vec::new();
+    let mut pending = burst.into_iter();
+
+    loop {
+        let chunk = match pending.next() {
+            Some(chunk) => chunk,
+            None => match receiver.recv().await {
+                Ok(chunk) => chunk,
+                // Auditeur trop lent : il saute les blocs perdus
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        };
+
+        output.clear();
+        match metadata.as_mut() {
+            Some(metadata) => {
+                let title = streams.current_track(stream_id).map(|track| stream_title(&track)).unwrap_or_default();
+                metadata.write(&chunk, &title, &mut output);
+            }
+            None => output.extend_from_slice(&chunk),
+        }
+        match timeout(LISTENER_WRITE_TIMEOUT, socket.write_all(&output)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(io_error(e)),
+            Err(_) => return Err(AppError::ConnectionTimeout),
+        }
+    }
+}
+
+/// `mode=updinfo` : titre envoyé par le logiciel du DJ
+async fn handle_metadata<W>(writer: &mut W, request: &IcecastRequest, ingest: &LiveIngest) -> Result<(), AppError>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    // SHOUTcast v1 passe la clé en paramètre `pass`
+    let Some(key) = request.basic_password().or_else(|| request.query_param("pass")) else {
+        return write_unauthorized(writer).await;
+    };
+    let Some(source) = ingest.authenticate(&key).ok().and_then(|_| ingest.source(&key)) else {
+        return write_unauthorized(writer).await;
+    };
+    if request.query_param("mode").as_deref() != Some("updinfo") {
+        return write_admin_response(writer, "400 Bad Request", "Unknown mode", false).await;
+    }
+    if request.query_param("mount").is_some_and(|mount| normalize_mount(&mount) != source.mount) {
+        return write_admin_response(writer, "403 Forbidden", "Mountpoint not owned by this source", false).await;
+    }
+    let Some(track) = track_from_query(request) else {
+        return write_admin_response(writer, "400 Bad Request", "No metadata provided", false).await;
+    };
+
+    ingest.set_track(&source.mount, track)?;
+    write_admin_response(writer, "200 OK", "Metadata update successful", true).await
+}
+
+/// `song=Artiste - Titre`, ou `artist` et `title` séparés
+fn track_from_query(request: &IcecastRequest) -> Option<TrackInfo> {
+    let (artist, title) = match request.query_param("song").filter(|song| !song.trim().is_empty()) {
+        Some(song) => match song.split_once(" - ") {
+            Some((artist, title)) => (Some(artist.trim().to_string()), title.trim().to_string()),
+            None => (None, song.trim().to_string()),
+        },
+        None => {
+            let title = request.query_param("title").filter(|title| !title.trim().is_empty())?;
+            (request.query_param("artist"), title)
+        }
+    };
+    Some(TrackInfo {
+        title,
+        artist: artist.filter(|artist| !artist.is_empty()),
+        album: None,
+        duration: None,
+        isrc: None,
+        bpm: None,
+        key: None,
+        genre: None,
+    })
+}
+
+async fn write_status<W>(writer: &mut W, status: &str, message: &str) -> Result<(), AppError>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    let response = format!("HTTP/1.0 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}", status, message.len(), message);
+    writer.write_all(response.as_bytes()).await.map_err(io_error)
+}
+
+async fn write_unauthorized<W>(writer: &mut W) -> Result<(), AppError>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    let message = "Authentication Required";
+    let response = format!(
+        "HTTP/1.0 401 Authentication Required\r\nWWW-Authenticate: Basic realm=\"Icecast2 Server\"\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+        message.len(),
+        message
+    );
+    writer.write_all(response.as_bytes()).await.map_err(io_error)
+}
+
+async fn write_admin_response<W>(writer: &mut W, status: &str, message: &str, success: bool) -> Result<(), AppError>
+where
+    W: AsyncWriteExt + Unpin,
+{
+    let body = format!(
+        "<?xml version=\"1.0\"?>\n<iceresponse><message>{}</message><return>{}</return></iceresponse>\n",
+        message,
+        u8::from(success)
+    );
+    let response = format!("HTTP/1.0 {}\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body);
+    writer.write_all(response.as_bytes()).await.map_err(io_error)
+}
+
+fn invalid_request(message: &str) -> AppError {
+    AppError::InvalidData { message: format!("Requête Icecast invalide: {}", message) }
+}
+
+fn io_error(e: std::io::Error) -> AppError {
+    AppError::StreamingError { message: format!("Connexion Icecast: {}", e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::{CodecFactory, CodecQuality, EncoderConfig, LatencyMode};
+    use crate::core::{AudioFormat, StreamConfig, StreamEvent, StreamManager, StreamMetadata, StreamSource};
+    use crate::streaming::icy::IcyConfig;
+    use crate::streaming::ingest::LiveIngestConfig;
+    use crate::utils::signed_query;
+
+    async fn start_server(config: &Arc<Config>, streams: &Arc<StreamManager>, ingest: &Arc<LiveIngest>, metaint: usize) -> SocketAddr {
+        let relays = Arc::new(IcyRelays::new(streams.clone(), IcyConfig { bitrate: 128, metaint }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, config.clone(), ingest.clone(), relays));
+        address
+    }
+
+    fn track(title: &str, artist: Option<&str>) -> TrackInfo {
+        TrackInfo {
+            title: title.to_string(),
+            artist: artist.map(str::to_string),
+            album: None,
+            duration: None,
+            isrc: None,
+            bpm: None,
+            key: None,
+            genre: None,
+        }
+    }
+
+    fn tone_mp3(seconds: usize) -> Vec<u8> {
+        let mut encoder = CodecFactory::create_encoder("mp3", EncoderConfig {
+            bitrate: 128_000,
+            sample_rate: 44100,
+            channels: 2,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::Normal,
+            enable_vbr: false,
+            complexity: 5,
+        }).unwrap();
+        let samples: Vec<f32> = (0..44100 * seconds)
+            .flat_map(|i| {
+                let sample = (i as f32 * 440.0 * std::f32::consts::TAU / 44100.0).sin() * 0.5;
+                [sample, sample]
+            })
+            .collect();
+        let mut mp3 = encoder.encode(&samples, 44100, 2).unwrap();
+        mp3.extend(encoder.finalize().unwrap());
+        mp3
+    }
+
+    #[test]
+    fn test_chunked_decoder_across_reads() {
+        let body = b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\n\r\n";
+        let mut decoder = ChunkedDecoder::default();
+        let mut output = Vec::new();
+        for part in body.chunks(3) {
+            output.extend(decoder.feed(part).unwrap());
+        }
+        assert_eq!(output, b"Wikipedia");
+        assert!(decoder.is_done());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_source_put_creates_live_stream() {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+        let key = ingest.issue_key(5, "Night shift".to_string(), None).unwrap();
+        let address = start_server(&Arc::new(Config::from_env().unwrap()), &streams, &ingest, 16000).await;
+
+        let credentials = base64::engine::general_purpose::STANDARD.encode(format!("source:{}", key.key));
+        let mut refused = TcpStream::connect(address).await.unwrap();
+        refused.write_all(b"SOURCE /night HTTP/1.0\r\nAuthorization: Basic c291cmNlOm5vcGU=\r\nContent-Type: audio/mpeg\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        refused.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.0 401"));
+
+        let mut source = TcpStream::connect(address).await.unwrap();
+        let head = format!(
+            "PUT /night HTTP/1.1\r\nAuthorization: Basic {}\r\nContent-Type: audio/mpeg\r\nIce-Name: Night shift\r\nExpect: 100-continue\r\n\r\n",
+            credentials
+        );
+        source.write_all(head.as_bytes()).await.unwrap();
+        let mut status = [0u8; 25];
+        source.read_exact(&mut status).await.unwrap();
+        assert_eq!(&status, b"HTTP/1.1 100 Continue\r\n\r\n");
+        source.write_all(&tone_mp3(2)).await.unwrap();
+
+        let mut stream_id = None;
+        for _ in 0..100 {
+            stream_id = ingest.stream_for_mount("/night");
+            if stream_id.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let stream_id = stream_id.expect("stream créé par la source");
+        assert_eq!(streams.live_format(stream_id).unwrap().sample_rate, 44100);
+
+        let mut admin = TcpStream::connect(address).await.unwrap();
+        let update = format!(
+            "GET /admin/metadata?mode=updinfo&mount=%2Fnight&song=Artist+-+First+light HTTP/1.0\r\nAuthorization: Basic {}\r\n\r\n",
+            credentials
+        );
+        admin.write_all(update.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        admin.read_to_string(&mut response).await.unwrap();
+        assert!(response.contains("<return>1</return>"));
+        let track = streams.current_track(stream_id).unwrap();
+        assert_eq!(track.title, "First light");
+        assert_eq!(track.artist.as_deref(), Some("Artist"));
+
+        drop(source);
+        for _ in 0..100 {
+            if streams.live_format(stream_id).is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(streams.live_format(stream_id).is_none());
+        assert!(ingest.source(&key.key).is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_listener_receives_icy_metadata() {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+        let config = Arc::new(Config::from_env().unwrap());
+        let address = start_server(&config, &streams, &ingest, 1000).await;
+
+        let format = AudioFormat { codec: "pcm".to_string(), bitrate: 0, sample_rate: 48000, channels: 1, bit_depth: 32 };
+        let source = StreamSource::Live { input_device: "test".to_string(), format, bitrate: 0 };
+        let metadata = StreamMetadata {
+            current_position: Duration::ZERO,
+            total_duration: None,
+            current_track: Some(track("Opening", Some("Artist"))),
+            next_track: None,
+            volume: 1.0,
+            playback_speed: 1.0,
+            effects_enabled: Vec::new(),
+            tags: Vec::new(),
+            language: None,
+            artwork_url: None,
+        };
+        let stream_id = streams.create_stream(9, source, Vec::new(), metadata).await.unwrap();
+        let mut events = streams.subscribe_events();
+
+        let pusher = {
+            let streams = streams.clone();
+            tokio::spawn(async move {
+                let tone: Vec<f32> = (0..4800).map(|i| (i as f32 * 0.05).sin() * 0.3).collect();
+                loop {
+                    let _ = streams.push_live_audio(stream_id, &tone).await;
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+        };
+
+        // Sans signature valide, le stream n'est pas servi
+        let mut refused = TcpStream::connect(address).await.unwrap();
+        let request = format!("GET /streams/{}.mp3?expires=1&sig=00 HTTP/1.0\r\n\r\n", stream_id);
+        refused.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        refused.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.0 403"));
+
+        let mut listener = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "GET /streams/{}.mp3?{} HTTP/1.0\r\nIcy-MetaData: 1\r\n\r\n",
+            stream_id,
+            signed_query(&config, &stream_id.to_string())
+        );
+        listener.write_all(request.as_bytes()).await.unwrap();
+        let mut reader = BufReader::new(listener);
+        let request_head = read_request(&mut reader).await.unwrap();
+        assert_eq!(request_head.method, "HTTP/1.0");
+        assert_eq!(request_head.header("content-type"), Some("audio/mpeg"));
+        assert_eq!(request_head.header("icy-metaint"), Some("1000"));
+
+        let mut audio = vec![0u8; 1000];
+        reader.read_exact(&mut audio).await.unwrap();
+        let blocks = reader.read_u8().await.unwrap();
+        let mut block = vec![0u8; usize::from(blocks) * 16];
+        reader.read_exact(&mut block).await.unwrap();
+        assert!(block.starts_with(b"StreamTitle='Artist - Opening';"));
+        assert!(matches!(events.recv().await.unwrap(), StreamEvent::ListenerJoined { .. }));
+
+        drop(reader);
+        let left = timeout(Duration::from_secs(40), async {
+            loop {
+                if let Ok(StreamEvent::ListenerLeft { .. }) = events.recv().await {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(left.is_ok());
+        pusher.abort();
+    }
+}
@@ -0,0 +1,326 @@
+//! Relais Icecast/SHOUTcast des streams live pour les lecteurs « radio »
+//!
+//! Un relais par stream et par codec encode le PCM live (`subscribe_live_audio`)
+//! en MP3 (ou en AAC ADTS avec la fonctionnalité `fdk-aac`) et le diffuse à
+//! tous ses auditeurs ; il s'arrête quand le dernier auditeur part ou que le
+//! stream se termine. Les métadonnées ICY (`StreamTitle`) sont insérées par
+//! auditeur tous les `icy-metaint` octets.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::codecs::{CodecQuality, EncoderConfig, LatencyMode};
+use crate::core::{StreamManager, TrackInfo};
+use crate::error::AppError;
+use crate::streaming::segmenter::{create_segment_encoder, encode_blocking, remix_channels, StreamResampler};
+
+/// Format de sortie des relais
+const RELAY_SAMPLE_RATE: u32 = 44100;
+const RELAY_CHANNELS: u8 = 2;
+/// Blocs encodés en attente par auditeur
+const RELAY_CHANNEL_SIZE: usize = 128;
+/// Données envoyées d'un coup à la connexion pour remplir le tampon du lecteur
+const BURST_SIZE: usize = 64 * 1024;
+/// Taille maximale d'un bloc de métadonnées ICY (longueur sur un octet × 16)
+const MAX_METADATA_SIZE: usize = 255 * 16;
+
+/// Codec d'un point de montage d'écoute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IcyCodec {
+    Mp3,
+    /// Nécessite l'encodeur libfdk-aac (fonctionnalité `fdk-aac`)
+    #[cfg(feature = "fdk-aac")]
+    Aac,
+}
+
+impl IcyCodec {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "mp3" => Some(Self::Mp3),
+            #[cfg(feature = "fdk-aac")]
+            "aac" => Some(Self::Aac),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            #[cfg(feature = "fdk-aac")]
+            Self::Aac => "audio/aac",
+        }
+    }
+
+    fn codec(self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            #[cfg(feature = "fdk-aac")]
+            Self::Aac => "aac",
+        }
+    }
+}
+
+/// Paramètres des relais
+#[derive(Debug, Clone)]
+pub struct IcyConfig {
+    /// Débit des relais, en kbps
+    pub bitrate: u32,
+    /// Octets audio entre deux blocs de métadonnées
+    pub metaint: usize,
+}
+
+impl Default for IcyConfig {
+    fn default() -> Self {
+        Self { bitrate: 128, metaint: 16000 }
+    }
+}
+
+/// Relais encodé d'un stream
+#[derive(Debug)]
+struct IcyRelay {
+    sender: broadcast::Sender<Arc<Vec<u8>>>,
+    /// Derniers blocs encodés, envoyés aux nouveaux auditeurs
+    burst: Mutex<VecDeque<Arc<Vec<u8>>>>,
+}
+
+impl IcyRelay {
+    fn publish(&self, data: Vec<u8>) {
+        let data = Arc::new(data);
+        let mut burst = self.burst.lock();
+        burst.push_back(data.clone());
+        let mut buffered: usize = burst.iter().map(|chunk| chunk.len()).sum();
+        while buffered > BURST_SIZE && burst.len() > 1 {
+            buffered -= burst.pop_front().map_or(0, |chunk| chunk.len());
+        }
+        let _ = self.sender.send(data);
+    }
+
+    fn subscribe(&self) -> IcySubscription {
+        let burst = self.burst.lock();
+        IcySubscription {
+            burst: burst.iter().cloned().collect(),
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+/// Abonnement d'un auditeur à un relais
+#[derive(Debug)]
+pub struct IcySubscription {
+    /// Données récentes à envoyer avant le direct
+    pub burst: Vec<Arc<Vec<u8>>>,
+    pub receiver: broadcast::Receiver<Arc<Vec<u8>>>,
+}
+
+/// Relais actifs, partagés entre les auditeurs d'un même stream et codec
+#[derive(Debug)]
+pub struct IcyRelays {
+    config: IcyConfig,
+    streams: Arc<StreamManager>,
+    relays: Arc<DashMap<(Uuid, IcyCodec), Arc<IcyRelay>>>,
+}
+
+impl IcyRelays {
+    pub fn new(streams: Arc<StreamManager>, config: IcyConfig) -> Self {
+        Self {
+            config,
+            streams,
+            relays: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &IcyConfig {
+        &self.config
+    }
+
+    pub fn streams(&self) -> Arc<StreamManager> {
+        self.streams.clone()
+    }
+
+    /// Abonne un auditeur, en démarrant le relais s'il n'existe pas
+    pub fn subscribe(&self, stream_id: Uuid, codec: IcyCodec) -> Result<IcySubscription, AppError> {
+        // Abonnement sous le verrou de l'entrée : le relais ne peut pas s'arrêter entre-temps
+        match self.relays.entry((stream_id, codec)) {
+            dashmap::mapref::entry::Entry::Occupied(entry) => Ok(entry.get().subscribe()),
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                let relay = self.start_relay(stream_id, codec)?;
+                let subscription = relay.subscribe();
+                entry.insert(relay);
+                Ok(subscription)
+            }
+        }
+    }
+
+    fn start_relay(&self, stream_id: Uuid, codec: IcyCodec) -> Result<Arc<IcyRelay>, AppError> {
+        let (format, mut pcm) = self
+            .streams
+            .subscribe_live_audio(stream_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("stream live {}", stream_id) })?;
+        let (encoder, _) = create_segment_encoder(codec.codec(), EncoderConfig {
+            bitrate: self.config.bitrate * 1000,
+            sample_rate: RELAY_SAMPLE_RATE,
+            channels: RELAY_CHANNELS,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::Low,
+            enable_vbr: false,
+            complexity: 5,
+        })?;
+        let resampler = match format.sample_rate == RELAY_SAMPLE_RATE {
+            true => None,
+            false => Some(StreamResampler::new(format.sample_rate, RELAY_SAMPLE_RATE, usize::from(RELAY_CHANNELS))?),
+        };
+
+        let relay = Arc::new(IcyRelay {
+            sender: broadcast::channel(RELAY_CHANNEL_SIZE).0,
+            burst: Mutex::new(VecDeque::new()),
+        });
+        let relays = self.relays.clone();
+        let task_relay = relay.clone();
+        info!("Relais {} démarré pour le stream {}", codec.codec(), stream_id);
+
+        tokio::spawn(async move {
+            let mut state = (encoder, resampler);
+            loop {
+                let samples = match pcm.recv().await {
+                    Ok(samples) => samples,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Relais {} du stream {} en retard, {} blocs perdus", codec.codec(), stream_id, skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let channels = usize::from(format.channels.max(1));
+                let encoded = encode_blocking(state, move |(encoder, resampler)| {
+                    let remixed = remix_channels(&samples, channels, usize::from(RELAY_CHANNELS));
+                    let resampled = match resampler.as_mut() {
+                        Some(resampler) => resampler.process(&remixed)?,
+                        None => remixed,
+                    };
+                    encoder.encode(&resampled, RELAY_SAMPLE_RATE, RELAY_CHANNELS)
+                })
+                .await;
+                match encoded {
+                    Ok((returned, data)) => {
+                        state = returned;
+                        if !data.is_empty() {
+                            task_relay.publish(data);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Encodage du relais {} du stream {} impossible: {}", codec.codec(), stream_id, e);
+                        break;
+                    }
+                }
+
+                // Plus personne à l'écoute : le relais s'arrête sous le verrou de
+                // l'entrée, pour qu'aucun auditeur ne s'abonne entre-temps
+                if relays
+                    .remove_if(&(stream_id, codec), |_, relay| relay.sender.receiver_count() == 0)
+                    .is_some()
+                {
+                    debug!("Relais {} du stream {} arrêté : plus d'auditeur", codec.codec(), stream_id);
+                    return;
+                }
+            }
+            relays.remove_if(&(stream_id, codec), |_, relay| Arc::ptr_eq(relay, &task_relay));
+            info!("Relais {} du stream {} terminé", codec.codec(), stream_id);
+        });
+
+        Ok(relay)
+    }
+}
+
+/// `StreamTitle` d'une piste : « Artiste - Titre » ou le titre seul
+pub fn stream_title(track: &TrackInfo) -> String {
+    match track.artist.as_deref().filter(|artist| !artist.is_empty()) {
+        Some(artist) => format!("{} - {}", artist, track.title),
+        None => track.title.clone(),
+    }
+}
+
+/// Insère les blocs de métadonnées ICY dans le flux d'un auditeur
+#[derive(Debug)]
+pub struct IcyMetadataWriter {
+    metaint: usize,
+    /// Octets audio avant le prochain bloc
+    remaining: usize,
+    /// Titre déjà envoyé ; un bloc vide suffit tant qu'il ne change pas
+    sent_title: Option<String>,
+}
+
+impl IcyMetadataWriter {
+    pub fn new(metaint: usize) -> Self {
+        assert!(metaint > 0, "icy-metaint doit être strictement positif");
+        Self { metaint, remaining: metaint, sent_title: None }
+    }
+
+    /// Recopie `data` dans `output` en insérant un bloc tous les `metaint` octets
+    pub fn write(&mut self, mut data: &[u8], title: &str, output: &mut Vec<u8>) {
+        while !data.is_empty() {
+            let take = self.remaining.min(data.len());
+            output.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            self.remaining -= take;
+            if self.remaining == 0 {
+                self.write_block(title, output);
+                self.remaining = self.metaint;
+            }
+        }
+    }
+
+    fn write_block(&mut self, title: &str, output: &mut Vec<u8>) {
+        if self.sent_title.as_deref() == Some(title) {
+            output.push(0);
+            return;
+        }
+
+        // Les apostrophes délimitent la valeur : on les retire du titre
+        let mut text = format!("StreamTitle='{}';", title.replace('\'', "’"));
+        if text.len() > MAX_METADATA_SIZE {
+            let mut end = MAX_METADATA_SIZE - 2;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+            text.push_str("';");
+        }
+        let blocks = text.len().div_ceil(16);
+        output.push(blocks as u8);
+        output.extend_from_slice(text.as_bytes());
+        output.resize(output.len() + blocks * 16 - text.len(), 0);
+        self.sent_title = Some(title.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_blocks_at_metaint() {
+        let mut writer = IcyMetadataWriter::new(4);
+        let mut output = Vec::new();
+        writer.write(b"abcdef", "Artist - Song", &mut output);
+        writer.write(b"gh", "Artist - Song", &mut output);
+
+        let text = b"StreamTitle='Artist - Song';";
+        assert_eq!(&output[..4], b"abcd");
+        assert_eq!(output[4], 2);
+        assert_eq!(&output[5..5 + text.len()], text);
+        assert!(output[5 + text.len()..37].iter().all(|&byte| byte == 0));
+        // Titre inchangé : bloc vide
+        assert_eq!(&output[37..], b"efgh\0");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_metaint_is_rejected() {
+        IcyMetadataWriter::new(0);
+    }
+}
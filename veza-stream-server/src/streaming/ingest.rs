@@ -0,0 +1,655 @@
+//! Sources live poussées par le réseau (Icecast, RTMP, WHIP…)
+//!
+//! Un DJ s'authentifie avec une clé de stream émise par `issue_key`. À la
+//! première trame décodée, sa `LiveSource` crée un `LiveStream`
+//! (`StreamSource::Live`) au format du flux reçu, ou se rattache au stream
+//! associé à la clé. Le PCM est ensuite converti au format de la source du
+//! stream et poussé par `StreamManager::push_live_audio`. Chaque protocole se
+//! contente de décoder son flux et d'alimenter une `LiveSource`.
+
+use std::{
+    io::Read,
+    sync::{atomic::AtomicU64, Arc},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get},
+    Router,
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::auth::{AuthManager, Claims, Permission};
+use crate::codecs::{DecodedAudio, SymphoniaDecoder};
+use crate::config::LiveConfig;
+use crate::core::{AudioFormat, StreamManager, StreamMetadata, StreamOutput, StreamProtocol, StreamSource, TrackInfo};
+use crate::error::AppError;
+use crate::streaming::segmenter::{remix_channels, StreamResampler, DEFAULT_FMP4_CODEC};
+
+/// Blocs en attente entre la connexion, le décodeur et la `LiveSource`
+const PIPELINE_CHANNEL_SIZE: usize = 64;
+
+/// Paramètres des streams créés par une source live
+#[derive(Debug, Clone)]
+pub struct LiveIngestConfig {
+    /// Sorties des streams créés à la connexion d'une source
+    pub outputs: Vec<StreamOutput>,
+    /// Durée sans données au-delà de laquelle une source est déconnectée
+    pub idle_timeout: Duration,
+}
+
+impl Default for LiveIngestConfig {
+    fn default() -> Self {
+        Self {
+            outputs: Vec::new(),
+            idle_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl LiveIngestConfig {
+    /// Renditions HLS stéréo 44,1 kHz aux débits configurés, dans le codec
+    /// fMP4 par défaut (AAC avec la fonctionnalité `fdk-aac`, MP3 sinon)
+    pub fn from_config(config: &LiveConfig) -> Self {
+        let outputs = config
+            .hls_bitrates
+            .iter()
+            .map(|&bitrate| StreamOutput {
+                format: AudioFormat {
+                    codec: DEFAULT_FMP4_CODEC.to_string(),
+                    bitrate,
+                    sample_rate: 44100,
+                    channels: 2,
+                    bit_depth: 16,
+                },
+                bitrate,
+                protocol: StreamProtocol::HLS {
+                    segment_duration: Duration::from_secs(2),
+                    playlist_size: 6,
+                },
+                endpoint: format!("{}k", bitrate),
+                listeners_count: Arc::new(AtomicU64::new(0)),
+            })
+            .collect();
+        Self {
+            outputs,
+            idle_timeout: config.source_idle_timeout,
+        }
+    }
+}
+
+/// Clé de stream d'un créateur, utilisée comme mot de passe par son logiciel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamKey {
+    pub key: String,
+    pub creator_id: i64,
+    pub title: String,
+    /// Stream existant alimenté par cette clé ; sinon un stream est créé à
+    /// chaque connexion
+    pub stream_id: Option<Uuid>,
+    pub created_at: SystemTime,
+}
+
+/// Source actuellement connectée
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectedSource {
+    pub protocol: &'static str,
+    pub mount: String,
+    pub stream_id: Option<Uuid>,
+    pub connected_at: SystemTime,
+    /// Piste annoncée avant la création du stream
+    #[serde(skip)]
+    pending_track: Option<TrackInfo>,
+}
+
+/// Registre des clés et des sources live connectées
+#[derive(Debug)]
+pub struct LiveIngest {
+    config: LiveIngestConfig,
+    streams: Arc<StreamManager>,
+    keys: DashMap<String, StreamKey>,
+    /// Sources connectées, par clé : une seule connexion par clé
+    sources: DashMap<String, ConnectedSource>,
+    /// Point de montage → stream alimenté
+    mounts: DashMap<String, Uuid>,
+}
+
+impl LiveIngest {
+    pub fn new(streams: Arc<StreamManager>, config: LiveIngestConfig) -> Self {
+        Self {
+            config,
+            streams,
+            keys: DashMap::new(),
+            sources: DashMap::new(),
+            mounts: DashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &LiveIngestConfig {
+        &self.config
+    }
+
+    pub fn streams(&self) -> Arc<StreamManager> {
+        self.streams.clone()
+    }
+
+    /// Émet une clé de stream pour un créateur ; une clé liée à un stream
+    /// existant n'est émise que pour le créateur de ce stream
+    pub fn issue_key(&self, creator_id: i64, title: String, stream_id: Option<Uuid>) -> Result<StreamKey, AppError> {
+        if let Some(stream_id) = stream_id {
+            match self.streams.creator_id(stream_id) {
+                Some(owner) if owner == creator_id => {}
+                Some(_) => return Err(AppError::Forbidden),
+                None => return Err(AppError::NotFound { resource: format!("stream {}", stream_id) }),
+            }
+        }
+        let key = StreamKey {
+            key: Uuid::new_v4().simple().to_string(),
+            creator_id,
+            title,
+            stream_id,
+            created_at: SystemTime::now(),
+        };
+        self.keys.insert(key.key.clone(), key.clone());
+        Ok(key)
+    }
+
+    /// Révoque une clé ; une source connectée avec elle le reste jusqu'à sa déconnexion
+    pub fn revoke_key(&self, creator_id: i64, key: &str) -> Result<(), AppError> {
+        self.keys
+            .remove_if(key, |_, stream_key| stream_key.creator_id == creator_id)
+            .map(|_| ())
+            .ok_or_else(|| AppError::NotFound { resource: "clé de stream".to_string() })
+    }
+
+    pub fn keys_of(&self, creator_id: i64) -> Vec<StreamKey> {
+        self.keys
+            .iter()
+            .filter(|entry| entry.creator_id == creator_id)
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    pub fn authenticate(&self, key: &str) -> Result<StreamKey, AppError> {
+        self.keys.get(key).map(|entry| entry.value().clone()).ok_or(AppError::Unauthorized)
+    }
+
+    /// Stream alimenté par un point de montage
+    pub fn stream_for_mount(&self, mount: &str) -> Option<Uuid> {
+        self.mounts.get(&normalize_mount(mount)).map(|entry| *entry.value())
+    }
+
+    /// Source connectée avec une clé
+    pub fn source(&self, key: &str) -> Option<ConnectedSource> {
+        self.sources.get(key).map(|entry| entry.value().clone())
+    }
+
+    /// Connecte une source ; le stream n'est créé qu'à la première trame,
+    /// quand le format du flux est connu
+    pub fn connect(self: &Arc<Self>, key: &str, protocol: &'static str, mount: &str) -> Result<LiveSource, AppError> {
+        let stream_key = self.authenticate(key)?;
+        let mount = normalize_mount(mount);
+        if self.sources.iter().any(|source| source.mount == mount) {
+            return Err(AppError::AlreadyRunning);
+        }
+        match self.sources.entry(key.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => return Err(AppError::AlreadyRunning),
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(ConnectedSource {
+                    protocol,
+                    mount: mount.clone(),
+                    stream_id: None,
+                    connected_at: SystemTime::now(),
+                    pending_track: None,
+                });
+            }
+        }
+
+        info!("Source {} connectée sur {} (créateur {})", protocol, mount, stream_key.creator_id);
+        Ok(LiveSource {
+            ingest: self.clone(),
+            key: key.to_string(),
+            protocol,
+            mount,
+            attached: None,
+        })
+    }
+
+    /// Annonce la piste en cours sur un point de montage (métadonnées envoyées
+    /// par le logiciel du DJ, éventuellement sur une autre connexion)
+    pub fn set_track(&self, mount: &str, track: TrackInfo) -> Result<(), AppError> {
+        let mount = normalize_mount(mount);
+        let mut source = self
+            .sources
+            .iter_mut()
+            .find(|source| source.mount == mount)
+            .ok_or_else(|| AppError::NotFound { resource: format!("point de montage {}", mount) })?;
+        match source.stream_id {
+            Some(stream_id) => self.streams.set_current_track(stream_id, track),
+            None => {
+                source.pending_track = Some(track);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Point de montage avec `/` initial (`live` et `/live` sont équivalents)
+pub fn normalize_mount(mount: &str) -> String {
+    format!("/{}", mount.trim_start_matches('/'))
+}
+
+/// Point de montage des protocoles où la clé sert d'identifiant de
+/// publication (RTMP, WHIP) : dérivé de son empreinte pour ne pas l'exposer
+pub fn key_mount(namespace: &str, key: &str) -> String {
+    let digest = hex::encode(Sha256::digest(key.as_bytes()));
+    format!("/{}/{}", namespace.trim_matches('/'), &digest[..12])
+}
+
+/// Stream alimenté par une source et conversion vers son format
+#[derive(Debug)]
+struct AttachedStream {
+    stream_id: Uuid,
+    /// Stream créé par la source, terminé à sa déconnexion
+    owned: bool,
+    target: AudioFormat,
+    input: (u32, u8),
+    resampler: Option<StreamResampler>,
+}
+
+/// Connexion d'une source live ; la déconnexion libère la clé et termine le
+/// stream qu'elle a créé
+#[derive(Debug)]
+pub struct LiveSource {
+    ingest: Arc<LiveIngest>,
+    key: String,
+    protocol: &'static str,
+    mount: String,
+    attached: Option<AttachedStream>,
+}
+
+impl LiveSource {
+    pub fn stream_id(&self) -> Option<Uuid> {
+        self.attached.as_ref().map(|attached| attached.stream_id)
+    }
+
+    pub fn mount(&self) -> &str {
+        &self.mount
+    }
+
+    /// Pousse de l'audio décodé dans le stream
+    pub async fn push(&mut self, audio: &DecodedAudio) -> Result<(), AppError> {
+        if audio.samples.is_empty() {
+            return Ok(());
+        }
+        let attached = match self.attached.take() {
+            Some(attached) => attached,
+            None => self.attach(audio).await?,
+        };
+        let attached = self.attached.insert(attached);
+
+        // Un flux Ogg chaîné peut changer de fréquence en cours de route
+        let input = (audio.sample_rate, audio.channels);
+        if attached.input != input {
+            attached.input = input;
+            attached.resampler = match audio.sample_rate == attached.target.sample_rate {
+                true => None,
+                false => Some(StreamResampler::new(
+                    audio.sample_rate,
+                    attached.target.sample_rate,
+                    attached.target.channels.max(1) as usize,
+                )?),
+            };
+        }
+
+        let remixed = remix_channels(&audio.samples, usize::from(audio.channels), usize::from(attached.target.channels));
+        let samples = match attached.resampler.as_mut() {
+            Some(resampler) => resampler.process(&remixed)?,
+            None => remixed,
+        };
+        if samples.is_empty() {
+            return Ok(());
+        }
+        self.ingest.streams.push_live_audio(attached.stream_id, &samples).await
+    }
+
+    /// Annonce la piste en cours (métadonnées intégrées au flux)
+    pub fn set_track(&self, track: TrackInfo) -> Result<(), AppError> {
+        self.ingest.set_track(&self.mount, track)
+    }
+
+    /// Rattache la source au stream de sa clé, ou en crée un au format du flux
+    async fn attach(&mut self, audio: &DecodedAudio) -> Result<AttachedStream, AppError> {
+        let stream_key = self.ingest.authenticate(&self.key)?;
+        let streams = self.ingest.streams.clone();
+        let existing = stream_key
+            .stream_id
+            .filter(|stream_id| streams.creator_id(*stream_id) == Some(stream_key.creator_id))
+            .and_then(|stream_id| streams.live_format(stream_id).map(|format| (stream_id, format)));
+
+        let (stream_id, target, owned) = match existing {
+            Some((stream_id, format)) => (stream_id, format, false),
+            None => {
+                let format = AudioFormat {
+                    codec: "pcm".to_string(),
+                    bitrate: 0,
+                    sample_rate: audio.sample_rate,
+                    channels: audio.channels.max(1),
+                    bit_depth: 32,
+                };
+                let source = StreamSource::Live {
+                    input_device: format!("{}:{}", self.protocol, self.mount),
+                    format: format.clone(),
+                    bitrate: 0,
+                };
+                let metadata = StreamMetadata {
+                    current_position: Duration::ZERO,
+                    total_duration: None,
+                    current_track: Some(TrackInfo {
+                        title: stream_key.title.clone(),
+                        artist: None,
+                        album: None,
+                        duration: None,
+                        isrc: None,
+                        bpm: None,
+                        key: None,
+                        genre: None,
+                    }),
+                    next_track: None,
+                    volume: 1.0,
+                    playback_speed: 1.0,
+                    effects_enabled: Vec::new(),
+                    tags: Vec::new(),
+                    language: None,
+                    artwork_url: None,
+                };
+                let outputs = self.ingest.config.outputs.clone();
+                let stream_id = streams.create_stream(stream_key.creator_id, source, outputs, metadata).await?;
+                (stream_id, format, true)
+            }
+        };
+
+        self.ingest.mounts.insert(self.mount.clone(), stream_id);
+        let pending_track = self.ingest.sources.get_mut(&self.key).and_then(|mut source| {
+            source.stream_id = Some(stream_id);
+            source.pending_track.take()
+        });
+        if let Some(track) = pending_track {
+            streams.set_current_track(stream_id, track)?;
+        }
+        info!("Source {} rattachée au stream {} ({} Hz, {} canaux)", self.mount, stream_id, audio.sample_rate, audio.channels);
+
+        Ok(AttachedStream {
+            stream_id,
+            owned,
+            target,
+            input: (0, 0),
+            resampler: None,
+        })
+    }
+}
+
+impl Drop for LiveSource {
+    fn drop(&mut self) {
+        self.ingest.sources.remove(&self.key);
+        self.ingest.mounts.remove_if(&self.mount, |_, stream_id| Some(*stream_id) == self.stream_id());
+        info!("Source {} déconnectée", self.mount);
+
+        let Some(attached) = self.attached.take().filter(|attached| attached.owned) else {
+            return;
+        };
+        let streams = self.ingest.streams.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    if let Err(e) = streams.end_stream(attached.stream_id).await {
+                        warn!("Fin du stream {} impossible: {}", attached.stream_id, e);
+                    }
+                });
+            }
+            Err(_) => warn!("Stream {} laissé ouvert : pas de runtime à la déconnexion", attached.stream_id),
+        }
+    }
+}
+
+/// Décodage d'une source : octets du conteneur (MP3, Ogg, ADTS…) → décodeur
+/// symphonia sur un thread bloquant → `LiveSource`
+#[derive(Debug)]
+pub struct SourcePipeline {
+    body: mpsc::Sender<Vec<u8>>,
+    decoder: JoinHandle<Result<(), AppError>>,
+    pusher: JoinHandle<Result<(), AppError>>,
+}
+
+impl SourcePipeline {
+    /// `extension` sert d'indice au probe de symphonia
+    pub fn start(source: LiveSource, extension: &'static str) -> Self {
+        let (body, body_rx) = mpsc::channel::<Vec<u8>>(PIPELINE_CHANNEL_SIZE);
+        let (audio_tx, audio_rx) = mpsc::channel::<DecodedAudio>(PIPELINE_CHANNEL_SIZE);
+        let decoder = tokio::task::spawn_blocking(move || decode_source(body_rx, extension, audio_tx));
+        let pusher = tokio::spawn(push_source(source, audio_rx));
+        Self { body, decoder, pusher }
+    }
+
+    /// Transmet des octets au décodeur ; `false` si le pipeline s'est arrêté
+    pub async fn send(&self, data: Vec<u8>) -> bool {
+        self.body.send(data).await.is_ok()
+    }
+
+    /// Ferme le flux et attend que tout l'audio reçu soit poussé
+    pub async fn finish(self) -> Result<(), AppError> {
+        drop(self.body);
+        let decoded = self.decoder.await.map_err(|e| AppError::InternalError { message: e.to_string() })?;
+        let pushed = self.pusher.await.map_err(|e| AppError::InternalError { message: e.to_string() })?;
+        decoded.and(pushed)
+    }
+}
+
+/// Décode le flux reçu ; tourne sur un thread bloquant
+fn decode_source(body: mpsc::Receiver<Vec<u8>>, extension: &str, audio: mpsc::Sender<DecodedAudio>) -> Result<(), AppError> {
+    let reader = ChannelReader { receiver: body, pending: Vec::new(), position: 0 };
+    let mut decoder = SymphoniaDecoder::from_reader(reader, Some(extension))?;
+    while let Some(chunk) = decoder.next_chunk()? {
+        if audio.blocking_send(chunk).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn push_source(mut source: LiveSource, mut audio: mpsc::Receiver<DecodedAudio>) -> Result<(), AppError> {
+    while let Some(chunk) = audio.recv().await {
+        source.push(&chunk).await?;
+    }
+    Ok(())
+}
+
+/// `Read` bloquant sur les blocs reçus par le réseau
+struct ChannelReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position >= self.pending.len() {
+            match self.receiver.blocking_recv() {
+                Some(data) => {
+                    self.pending = data;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.pending.len() - self.position);
+        buf[..read].copy_from_slice(&self.pending[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+#[derive(Clone)]
+struct IngestState {
+    ingest: Arc<LiveIngest>,
+    auth: Arc<AuthManager>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueKeyRequest {
+    title: String,
+    #[serde(default)]
+    stream_id: Option<Uuid>,
+}
+
+/// Routes de gestion des clés : `GET|POST /keys`, `DELETE /keys/:key`.
+/// Authentification par Bearer JWT avec la permission `UploadAudio`.
+pub fn live_ingest_routes<S>(ingest: Arc<LiveIngest>, auth: Arc<AuthManager>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/keys", get(list_keys).post(issue_key))
+        .route("/keys/:key", delete(revoke_key))
+        .with_state(IngestState { ingest, auth })
+}
+
+async fn authenticate(state: &IngestState, headers: &HeaderMap) -> Result<Claims, AppError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized)?;
+
+    let validation = state.auth.validate_token(token).await;
+    let claims = validation.claims.filter(|_| validation.valid).ok_or(AppError::Unauthorized)?;
+    if !state.auth.has_permission(&claims, Permission::UploadAudio) {
+        return Err(AppError::Forbidden);
+    }
+    Ok(claims)
+}
+
+async fn list_keys(State(state): State<IngestState>, headers: HeaderMap) -> Result<Json<Vec<StreamKey>>, AppError> {
+    let claims = authenticate(&state, &headers).await?;
+    Ok(Json(state.ingest.keys_of(claims.sub)))
+}
+
+async fn issue_key(
+    State(state): State<IngestState>,
+    headers: HeaderMap,
+    Json(request): Json<IssueKeyRequest>,
+) -> Result<Response, AppError> {
+    let claims = authenticate(&state, &headers).await?;
+    if request.title.trim().is_empty() {
+        return Err(AppError::ValidationError("Titre du stream manquant".to_string()));
+    }
+    let key = state.ingest.issue_key(claims.sub, request.title.trim().to_string(), request.stream_id)?;
+    Ok((StatusCode::CREATED, Json(key)).into_response())
+}
+
+async fn revoke_key(
+    AxumPath(key): AxumPath<String>,
+    State(state): State<IngestState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    let claims = authenticate(&state, &headers).await?;
+    state.ingest.revoke_key(claims.sub, &key)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::AudioSampleFormat;
+    use crate::core::StreamConfig;
+
+    fn audio(sample_rate: u32, channels: u8, frames: usize) -> DecodedAudio {
+        DecodedAudio {
+            samples: vec![0.25; frames * usize::from(channels)],
+            sample_rate,
+            channels,
+            duration_ms: (frames as u64 * 1000 / u64::from(sample_rate)) as u32,
+            format: AudioSampleFormat::F32,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_source_creates_and_ends_stream() {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+        let key = ingest.issue_key(7, "Friday night".to_string(), None).unwrap();
+        assert!(matches!(ingest.connect("wrong", "icecast", "/live"), Err(AppError::Unauthorized)));
+
+        let mut source = ingest.connect(&key.key, "icecast", "live").unwrap();
+        assert!(matches!(ingest.connect(&key.key, "icecast", "/other"), Err(AppError::AlreadyRunning)));
+        source.set_track(TrackInfo {
+            title: "Artist - Opening".to_string(),
+            artist: None,
+            album: None,
+            duration: None,
+            isrc: None,
+            bpm: None,
+            key: None,
+            genre: None,
+        }).unwrap();
+
+        source.push(&audio(48000, 2, 4800)).await.unwrap();
+        let stream_id = source.stream_id().unwrap();
+        assert_eq!(ingest.stream_for_mount("/live"), Some(stream_id));
+        assert_eq!(streams.live_format(stream_id).unwrap().sample_rate, 48000);
+        assert_eq!(streams.current_track(stream_id).unwrap().title, "Artist - Opening");
+
+        drop(source);
+        assert!(ingest.source(&key.key).is_none());
+        assert!(ingest.stream_for_mount("/live").is_none());
+        tokio::task::yield_now().await;
+        assert!(streams.live_format(stream_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_source_attaches_to_existing_stream() {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+
+        // Premier passage : le stream est créé en 44,1 kHz stéréo par une autre source
+        let owner = ingest.issue_key(3, "Radio".to_string(), None).unwrap();
+        let mut first = ingest.connect(&owner.key, "icecast", "/radio").unwrap();
+        first.push(&audio(44100, 2, 4410)).await.unwrap();
+        let stream_id = first.stream_id().unwrap();
+
+        // Une clé liée à ce stream y pousse du 48 kHz mono, converti au format du stream
+        let guest = ingest.issue_key(3, "Guest".to_string(), Some(stream_id)).unwrap();
+        let mut second = ingest.connect(&guest.key, "icecast", "/guest").unwrap();
+        second.push(&audio(48000, 1, 48000)).await.unwrap();
+        assert_eq!(second.stream_id(), Some(stream_id));
+        drop(second);
+        tokio::task::yield_now().await;
+        assert!(streams.live_format(stream_id).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_key_for_foreign_stream_is_refused() {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+        let owner = ingest.issue_key(3, "Radio".to_string(), None).unwrap();
+        let mut source = ingest.connect(&owner.key, "icecast", "/radio").unwrap();
+        source.push(&audio(44100, 2, 4410)).await.unwrap();
+        let stream_id = source.stream_id().unwrap();
+
+        // Un autre créateur ne peut pas lier une clé au stream de l'utilisateur 3
+        assert!(matches!(ingest.issue_key(4, "Hijack".to_string(), Some(stream_id)), Err(AppError::Forbidden)));
+        assert!(matches!(
+            ingest.issue_key(4, "Unknown".to_string(), Some(Uuid::new_v4())),
+            Err(AppError::NotFound { .. })
+        ));
+        assert!(ingest.keys_of(4).is_empty());
+    }
+}
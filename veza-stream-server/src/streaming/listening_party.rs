@@ -0,0 +1,936 @@
+//! Sessions d'écoute synchronisée (listening parties)
+//!
+//! Un hôte crée une session sur un morceau ou un stream et y invite des
+//! utilisateurs. Lecture, pause et saut ne s'appliquent pas immédiatement :
+//! ils sont planifiés à une heure future de l'horloge maître du
+//! `SyncManager` (`SyncCommandType::Play`, `Pause`, `Seek`), avec une avance
+//! couvrant le RTT des membres, pour que tous les clients les exécutent au
+//! même instant.
+//!
+//! Chaque membre est connecté par WebSocket (messages JSON `SyncMessage`)
+//! et reçoit à son arrivée son `client_id`, avec lequel il cale son horloge
+//! sur la WebSocket `/sync/clock` du `SyncEngine` ; l'heure maître est celle
+//! du moteur. Le client envoie ensuite périodiquement sa position (`Drift`),
+//! ignorée tant que son horloge n'est pas calée. Un membre qui dérive au-delà
+//! de la tolérance reçoit un `Seek` correctif, et un retardataire reçoit à
+//! son arrivée un `Seek` puis un `Play` vers la position courante. Un
+//! `PauseSync` signale la fin de la session, y compris quand l'hôte reste
+//! déconnecté au-delà de `host_idle_timeout`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path as AxumPath, Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::auth::{AuthManager, Claims, Permission};
+use crate::error::AppError;
+use crate::streaming::sync_manager::{SyncCommandType, SyncManager, SyncMessage};
+
+/// Paramètres des sessions d'écoute
+#[derive(Debug, Clone)]
+pub struct ListeningPartyConfig {
+    /// Avance minimale entre une commande et son exécution
+    pub command_lead: Duration,
+    /// Écart toléré entre la position d'un membre et la position attendue
+    pub drift_tolerance_ms: u64,
+    pub max_members: usize,
+    /// Absence de l'hôte au-delà de laquelle la session est fermée
+    pub host_idle_timeout: Duration,
+}
+
+impl Default for ListeningPartyConfig {
+    fn default() -> Self {
+        Self {
+            command_lead: Duration::from_millis(500),
+            drift_tolerance_ms: 80,
+            max_members: 200,
+            host_idle_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Action de l'hôte
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PartyAction {
+    Play,
+    Pause,
+    Seek { position_ms: u64 },
+}
+
+/// État de lecture, ancré sur l'horloge maître
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PartyPlayback {
+    pub playing: bool,
+    /// Position à l'heure maître `anchor_ms`
+    pub position_ms: u64,
+    pub anchor_ms: u64,
+    /// Numéro de la dernière commande
+    pub sequence: u64,
+}
+
+impl PartyPlayback {
+    /// Position attendue à l'heure maître `master_ms`
+    pub fn position_at(&self, master_ms: u64) -> u64 {
+        match self.playing {
+            true => self.position_ms + master_ms.saturating_sub(self.anchor_ms),
+            false => self.position_ms,
+        }
+    }
+}
+
+/// Membre connecté
+#[derive(Debug, Clone, Serialize)]
+pub struct PartyMember {
+    pub user_id: i64,
+    pub joined_at: SystemTime,
+    /// Dernier écart mesuré entre la position du membre et la position attendue
+    pub last_drift_ms: Option<i64>,
+}
+
+/// Session d'écoute
+#[derive(Debug, Clone, Serialize)]
+pub struct ListeningParty {
+    pub id: Uuid,
+    pub host_id: i64,
+    pub title: String,
+    /// Morceau ou stream écouté
+    pub media: String,
+    pub invited: HashSet<i64>,
+    /// Membres connectés, par identifiant de client de synchronisation
+    pub members: HashMap<String, PartyMember>,
+    pub playback: PartyPlayback,
+    pub created_at: SystemTime,
+    /// Depuis quand aucune connexion de l'hôte n'est ouverte
+    #[serde(skip)]
+    host_absent_since: Option<SystemTime>,
+}
+
+impl ListeningParty {
+    fn can_join(&self, user_id: i64) -> bool {
+        user_id == self.host_id || self.invited.contains(&user_id)
+    }
+
+    fn host_connected(&self) -> bool {
+        self.members.values().any(|member| member.user_id == self.host_id)
+    }
+}
+
+/// Commande planifiée par l'hôte
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduledCommand {
+    pub party_id: Uuid,
+    pub sequence: u64,
+    pub action: PartyAction,
+    /// Position à l'exécution
+    pub position_ms: u64,
+    /// Heure maître d'exécution
+    pub execute_at_ms: u64,
+}
+
+/// Registre des sessions d'écoute
+pub struct ListeningPartyManager {
+    sync: Arc<SyncManager>,
+    config: ListeningPartyConfig,
+    parties: DashMap<Uuid, ListeningParty>,
+}
+
+impl std::fmt::Debug for ListeningPartyManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ListeningPartyManager")
+            .field("config", &self.config)
+            .field("parties", &self.parties.len())
+            .finish()
+    }
+}
+
+fn sync_error(error: Box<dyn std::error::Error + Send + Sync>) -> AppError {
+    AppError::StreamingError { message: error.to_string() }
+}
+
+impl ListeningPartyManager {
+    pub fn new(sync: Arc<SyncManager>, config: ListeningPartyConfig) -> Self {
+        Self {
+            sync,
+            config,
+            parties: DashMap::new(),
+        }
+    }
+
+    pub fn sync(&self) -> &Arc<SyncManager> {
+        &self.sync
+    }
+
+    pub fn create(&self, host_id: i64, title: String, media: String, invited: Vec<i64>) -> ListeningParty {
+        let party = ListeningParty {
+            id: Uuid::new_v4(),
+            host_id,
+            title,
+            media,
+            invited: invited.into_iter().collect(),
+            members: HashMap::new(),
+            playback: PartyPlayback { playing: false, position_ms: 0, anchor_ms: 0, sequence: 0 },
+            created_at: SystemTime::now(),
+            host_absent_since: Some(SystemTime::now()),
+        };
+        info!("Session d'écoute {} créée par {}", party.id, host_id);
+        self.parties.insert(party.id, party.clone());
+        party
+    }
+
+    /// Session visible par l'hôte et les invités
+    pub fn get(&self, party_id: Uuid, user_id: i64) -> Result<ListeningParty, AppError> {
+        let party = self.parties.get(&party_id).ok_or_else(|| AppError::NotFound { resource: format!("session {}", party_id) })?;
+        match party.can_join(user_id) {
+            true => Ok(party.clone()),
+            false => Err(AppError::Forbidden),
+        }
+    }
+
+    fn host_party(
+        &self,
+        party_id: Uuid,
+        host_id: i64,
+    ) -> Result<dashmap::mapref::one::RefMut<'_, Uuid, ListeningParty>, AppError> {
+        let party = self
+            .parties
+            .get_mut(&party_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("session {}", party_id) })?;
+        match party.host_id == host_id {
+            true => Ok(party),
+            false => Err(AppError::Forbidden),
+        }
+    }
+
+    pub fn invite(&self, party_id: Uuid, host_id: i64, user_ids: &[i64]) -> Result<ListeningParty, AppError> {
+        let mut party = self.host_party(party_id, host_id)?;
+        party.invited.extend(user_ids);
+        Ok(party.clone())
+    }
+
+    /// Avance d'une commande : au moins un aller-retour du membre le plus lent
+    async fn lead_ms(&self, client_ids: &[String]) -> u64 {
+        let mut lead = self.config.command_lead.as_millis() as u64;
+        for client_id in client_ids {
+            if let Some(client) = self.sync.get_client(client_id).await {
+                lead = lead.max(u64::from(client.connection_quality.rtt_ms) * 2);
+            }
+        }
+        lead
+    }
+
+    fn send(&self, client_id: &str, command: SyncCommandType, execute_at_ms: u64) {
+        if let Err(e) = self.sync.schedule_command(client_id, command, execute_at_ms) {
+            debug!("Commande non envoyée au client {}: {}", client_id, e);
+        }
+    }
+
+    /// Planifie une action de l'hôte pour tous les membres
+    pub async fn command(&self, party_id: Uuid, host_id: i64, action: PartyAction) -> Result<ScheduledCommand, AppError> {
+        let client_ids: Vec<String> = self.host_party(party_id, host_id)?.members.keys().cloned().collect();
+        let execute_at_ms = self.sync.master_time_ms()? + self.lead_ms(&client_ids).await;
+
+        let (playback, client_ids) = {
+            let mut party = self.host_party(party_id, host_id)?;
+            let position_ms = party.playback.position_at(execute_at_ms);
+            let playback = &mut party.playback;
+            match action {
+                PartyAction::Play => (playback.playing, playback.position_ms) = (true, position_ms),
+                PartyAction::Pause => (playback.playing, playback.position_ms) = (false, position_ms),
+                PartyAction::Seek { position_ms } => playback.position_ms = position_ms,
+            }
+            playback.anchor_ms = execute_at_ms;
+            playback.sequence += 1;
+            (party.playback.clone(), party.members.keys().cloned().collect::<Vec<_>>())
+        };
+
+        for client_id in &client_ids {
+            self.send(client_id, sync_command(action, playback.position_ms), execute_at_ms);
+        }
+        info!("Session {} : {:?} planifié à {} ms pour {} membres", party_id, action, execute_at_ms, client_ids.len());
+        Ok(ScheduledCommand {
+            party_id,
+            sequence: playback.sequence,
+            action,
+            position_ms: playback.position_ms,
+            execute_at_ms,
+        })
+    }
+
+    /// Enregistre un membre et le cale sur la lecture en cours
+    pub async fn join(&self, party_id: Uuid, user_id: i64, client_id: String) -> Result<ListeningParty, AppError> {
+        self.get(party_id, user_id)?;
+        self.sync.add_client(client_id.clone(), party_id.to_string()).await.map_err(sync_error)?;
+
+        // Limite vérifiée sous le verrou de l'entrée, avec l'inscription
+        let joined = match self.parties.get_mut(&party_id) {
+            Some(party) if party.members.len() >= self.config.max_members => Err(AppError::LimitExceeded {
+                resource: "membres de la session".to_string(),
+                limit: self.config.max_members as u32,
+            }),
+            Some(mut party) => {
+                party.members.insert(client_id.clone(), PartyMember {
+                    user_id,
+                    joined_at: SystemTime::now(),
+                    last_drift_ms: None,
+                });
+                if user_id == party.host_id {
+                    party.host_absent_since = None;
+                }
+                Ok(party.clone())
+            }
+            None => Err(AppError::NotFound { resource: format!("session {}", party_id) }),
+        };
+        let party = match joined {
+            Ok(party) => party,
+            Err(e) => {
+                self.sync.remove_client(&client_id).await;
+                return Err(e);
+            }
+        };
+        let playback = party.playback.clone();
+
+        // Retardataire : saut vers la position courante, au plus tôt à la dernière commande
+        if playback.sequence > 0 {
+            let now = self.sync.master_time_ms()?;
+            let execute_at_ms = (now + self.lead_ms(std::slice::from_ref(&client_id)).await).max(playback.anchor_ms);
+            let position_ms = playback.position_at(execute_at_ms);
+            self.send(&client_id, SyncCommandType::Seek { position_ms }, execute_at_ms);
+            let state = match playback.playing {
+                true => SyncCommandType::Play { position_ms },
+                false => SyncCommandType::Pause { position_ms },
+            };
+            self.send(&client_id, state, execute_at_ms);
+        }
+        info!("Client {} (utilisateur {}) a rejoint la session {}", client_id, user_id, party_id);
+        Ok(party)
+    }
+
+    pub async fn leave(&self, party_id: Uuid, client_id: &str) {
+        if let Some(mut party) = self.parties.get_mut(&party_id) {
+            party.members.remove(client_id);
+            if !party.host_connected() && party.host_absent_since.is_none() {
+                party.host_absent_since = Some(SystemTime::now());
+            }
+        }
+        self.sync.remove_client(client_id).await;
+    }
+
+    pub fn is_member(&self, party_id: Uuid, client_id: &str) -> bool {
+        self.parties.get(&party_id).is_some_and(|party| party.members.contains_key(client_id))
+    }
+
+    /// Mesure de drift d'un membre : position lue à son heure locale
+    /// `client_time_ms`. Renvoie l'écart en ms, `None` si une commande est
+    /// en attente d'exécution ou si l'horloge du membre n'est pas calée.
+    pub async fn report_drift(
+        &self,
+        party_id: Uuid,
+        client_id: &str,
+        position_ms: u64,
+        client_time_ms: u64,
+    ) -> Result<Option<i64>, AppError> {
+        if self.sync.get_client(client_id).await.is_none() {
+            return Err(AppError::NotFound { resource: format!("client {}", client_id) });
+        }
+        // Horloge pas encore calée : la position ne peut pas être datée
+        let Some(offset) = self.sync.clock_offset_ms(client_id) else {
+            return Ok(None);
+        };
+        let master_ms = (client_time_ms as f64 - offset).round().max(0.0) as u64;
+
+        let (drift, playback) = {
+            let mut party = self
+                .parties
+                .get_mut(&party_id)
+                .ok_or_else(|| AppError::NotFound { resource: format!("session {}", party_id) })?;
+            if master_ms < party.playback.anchor_ms {
+                return Ok(None);
+            }
+            let drift = position_ms as i64 - party.playback.position_at(master_ms) as i64;
+            let playback = party.playback.clone();
+            let member = party
+                .members
+                .get_mut(client_id)
+                .ok_or_else(|| AppError::NotFound { resource: format!("client {}", client_id) })?;
+            member.last_drift_ms = Some(drift);
+            (drift, playback)
+        };
+
+        if drift.unsigned_abs() > self.config.drift_tolerance_ms {
+            // Jamais avant l'ancrage de la lecture en cours
+            let execute_at_ms = (self.sync.master_time_ms()? + self.lead_ms(&[client_id.to_string()]).await)
+                .max(playback.anchor_ms);
+            debug!("Client {} de la session {} décalé de {} ms, correction", client_id, party_id, drift);
+            self.send(client_id, SyncCommandType::Seek { position_ms: playback.position_at(execute_at_ms) }, execute_at_ms);
+        }
+        Ok(Some(drift))
+    }
+
+    /// Fin de session : les membres reçoivent `PauseSync`
+    pub async fn close(&self, party_id: Uuid, host_id: i64) -> Result<(), AppError> {
+        drop(self.host_party(party_id, host_id)?);
+        let Some((_, party)) = self.parties.remove(&party_id) else {
+            return Err(AppError::NotFound { resource: format!("session {}", party_id) });
+        };
+        self.end(party).await
+    }
+
+    async fn end(&self, party: ListeningParty) -> Result<(), AppError> {
+        let now = self.sync.master_time_ms()?;
+        for client_id in party.members.keys() {
+            self.send(client_id, SyncCommandType::PauseSync, now);
+            self.sync.remove_client(client_id).await;
+        }
+        info!("Session d'écoute {} terminée", party.id);
+        Ok(())
+    }
+
+    /// Ferme périodiquement les sessions dont l'hôte est absent depuis plus
+    /// de `host_idle_timeout`
+    pub fn spawn_idle_cleanup(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let period = (self.config.host_idle_timeout / 4).clamp(Duration::from_secs(1), Duration::from_secs(60));
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                match self.close_idle().await {
+                    Ok(0) => {}
+                    Ok(closed) => info!("{} sessions d'écoute sans hôte fermées", closed),
+                    Err(e) => debug!("Fermeture des sessions d'écoute sans hôte: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Ferme les sessions dont l'hôte est absent depuis plus de
+    /// `host_idle_timeout` ; renvoie leur nombre
+    pub async fn close_idle(&self) -> Result<usize, AppError> {
+        let now = SystemTime::now();
+        let idle = |party: &ListeningParty| {
+            party
+                .host_absent_since
+                .is_some_and(|since| now.duration_since(since).unwrap_or_default() >= self.config.host_idle_timeout)
+        };
+        let candidates: Vec<Uuid> = self.parties.iter().filter(|party| idle(party)).map(|party| party.id).collect();
+
+        let mut closed = 0;
+        for party_id in candidates {
+            // Revérifié sous le verrou : l'hôte a pu revenir entre-temps
+            if let Some((_, party)) = self.parties.remove_if(&party_id, |_, party| idle(party)) {
+                self.end(party).await?;
+                closed += 1;
+            }
+        }
+        Ok(closed)
+    }
+}
+
+fn sync_command(action: PartyAction, position_ms: u64) -> SyncCommandType {
+    match action {
+        PartyAction::Play => SyncCommandType::Play { position_ms },
+        PartyAction::Pause => SyncCommandType::Pause { position_ms },
+        PartyAction::Seek { .. } => SyncCommandType::Seek { position_ms },
+    }
+}
+
+fn recipient(message: &SyncMessage) -> &str {
+    match message {
+        SyncMessage::BufferAdjustment { client_id, .. }
+        | SyncMessage::SyncCommand { client_id, .. }
+        | SyncMessage::QualityUpdate { client_id, .. }
+        | SyncMessage::SyncStatus { client_id, .. } => client_id,
+    }
+}
+
+/// Messages envoyés par un membre
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "data")]
+enum PartyClientMessage {
+    /// Position lue à l'heure locale `client_time_ms`
+    Drift { position_ms: u64, client_time_ms: u64 },
+}
+
+#[derive(Clone)]
+struct PartyState {
+    parties: Arc<ListeningPartyManager>,
+    auth: Arc<AuthManager>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatePartyRequest {
+    title: String,
+    media: String,
+    #[serde(default)]
+    invited: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InviteRequest {
+    user_ids: Vec<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SocketQuery {
+    /// Jeton JWT, les WebSocket des navigateurs ne pouvant pas porter d'en-tête
+    token: Option<String>,
+}
+
+/// Routes des sessions d'écoute : `POST /`, `GET|DELETE /:party_id`,
+/// `POST /:party_id/invitations`, `POST /:party_id/commands` et la
+/// WebSocket des membres `GET /:party_id/ws`. Authentification par JWT avec
+/// la permission `StreamAudio`.
+pub fn listening_party_routes<S>(parties: Arc<ListeningPartyManager>, auth: Arc<AuthManager>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/", post(create_party))
+        .route("/:party_id", get(get_party).delete(close_party))
+        .route("/:party_id/invitations", post(invite))
+        .route("/:party_id/commands", post(command))
+        .route("/:party_id/ws", get(party_socket))
+        .with_state(PartyState { parties, auth })
+}
+
+async fn authenticate(state: &PartyState, headers: &HeaderMap, token: Option<&str>) -> Result<Claims, AppError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or(token)
+        .ok_or(AppError::Unauthorized)?;
+
+    let validation = state.auth.validate_token(token).await;
+    let claims = validation.claims.filter(|_| validation.valid).ok_or(AppError::Unauthorized)?;
+    if !state.auth.has_permission(&claims, Permission::StreamAudio) {
+        return Err(AppError::Forbidden);
+    }
+    Ok(claims)
+}
+
+async fn create_party(
+    State(state): State<PartyState>,
+    headers: HeaderMap,
+    Json(request): Json<CreatePartyRequest>,
+) -> Result<Response, AppError> {
+    let claims = authenticate(&state, &headers, None).await?;
+    if request.title.trim().is_empty() || request.media.trim().is_empty() {
+        return Err(AppError::ValidationError("Titre ou média manquant".to_string()));
+    }
+    let party = state.parties.create(
+        claims.sub,
+        request.title.trim().to_string(),
+        request.media.trim().to_string(),
+        request.invited,
+    );
+    Ok((StatusCode::CREATED, Json(party)).into_response())
+}
+
+async fn get_party(
+    AxumPath(party_id): AxumPath<Uuid>,
+    State(state): State<PartyState>,
+    headers: HeaderMap,
+) -> Result<Json<ListeningParty>, AppError> {
+    let claims = authenticate(&state, &headers, None).await?;
+    Ok(Json(state.parties.get(party_id, claims.sub)?))
+}
+
+async fn close_party(
+    AxumPath(party_id): AxumPath<Uuid>,
+    State(state): State<PartyState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    let claims = authenticate(&state, &headers, None).await?;
+    state.parties.close(party_id, claims.sub).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn invite(
+    AxumPath(party_id): AxumPath<Uuid>,
+    State(state): State<PartyState>,
+    headers: HeaderMap,
+    Json(request): Json<InviteRequest>,
+) -> Result<Json<ListeningParty>, AppError> {
+    let claims = authenticate(&state, &headers, None).await?;
+    Ok(Json(state.parties.invite(party_id, claims.sub, &request.user_ids)?))
+}
+
+async fn command(
+    AxumPath(party_id): AxumPath<Uuid>,
+    State(state): State<PartyState>,
+    headers: HeaderMap,
+    Json(action): Json<PartyAction>,
+) -> Result<Response, AppError> {
+    let claims = authenticate(&state, &headers, None).await?;
+    let scheduled = state.parties.command(party_id, claims.sub, action).await?;
+    Ok((StatusCode::ACCEPTED, Json(scheduled)).into_response())
+}
+
+async fn party_socket(
+    AxumPath(party_id): AxumPath<Uuid>,
+    State(state): State<PartyState>,
+    Query(query): Query<SocketQuery>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let claims = authenticate(&state, &headers, query.token.as_deref()).await?;
+    state.parties.get(party_id, claims.sub)?;
+    let parties = state.parties.clone();
+    Ok(ws.on_upgrade(move |socket| async move {
+        if let Err(e) = serve_member(socket, &parties, party_id, claims.sub).await {
+            debug!("Membre {} de la session {} déconnecté: {}", claims.sub, party_id, e);
+        }
+    }))
+}
+
+async fn send_json(socket: &mut WebSocket, value: &impl Serialize) -> Result<(), AppError> {
+    let text = serde_json::to_string(value).map_err(|_| AppError::SerializationError)?;
+    socket
+        .send(Message::Text(text))
+        .await
+        .map_err(|e| AppError::NetworkError { message: e.to_string() })
+}
+
+async fn serve_member(
+    mut socket: WebSocket,
+    parties: &ListeningPartyManager,
+    party_id: Uuid,
+    user_id: i64,
+) -> Result<(), AppError> {
+    // UUID : le membre s'en sert pour caler son horloge sur `/sync/clock`
+    let client_id = Uuid::new_v4().to_string();
+    // Abonnement avant l'inscription : le calage d'un retardataire part dès `join`
+    let mut receiver = parties.sync.get_sync_receiver();
+    let party = parties.join(party_id, user_id, client_id.clone()).await?;
+    let result = member_loop(&mut socket, parties, &mut receiver, party, &client_id).await;
+    parties.leave(party_id, &client_id).await;
+    result
+}
+
+async fn member_loop(
+    socket: &mut WebSocket,
+    parties: &ListeningPartyManager,
+    receiver: &mut broadcast::Receiver<SyncMessage>,
+    party: ListeningParty,
+    client_id: &str,
+) -> Result<(), AppError> {
+    let party_id = party.id;
+    send_json(socket, &serde_json::json!({
+        "type": "Joined",
+        "data": { "client_id": client_id, "party": party },
+    }))
+    .await?;
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<PartyClientMessage>(&text) {
+                    Ok(PartyClientMessage::Drift { position_ms, client_time_ms }) => {
+                        parties.report_drift(party_id, client_id, position_ms, client_time_ms).await?;
+                    }
+                    Err(e) => debug!("Message de membre invalide: {}", e),
+                },
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(AppError::NetworkError { message: e.to_string() }),
+            },
+            event = receiver.recv() => match event {
+                Ok(message) if recipient(&message) == client_id => {
+                    send_json(socket, &message).await?;
+                    if matches!(message, SyncMessage::SyncCommand { command: SyncCommandType::PauseSync, .. }) {
+                        return Ok(());
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("Membre {} en retard, {} messages de synchronisation perdus", client_id, skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::config::Config;
+    use crate::core::{sync::SyncConfig as ClockSyncConfig, ClockExchange, SyncEngine};
+    use crate::streaming::sync_manager::SyncConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use futures::StreamExt;
+    use tower::ServiceExt;
+
+    /// Échange d'horloge immédiat : horloge client décalée de `offset_ms`,
+    /// aller-retour de `round_trip_ms`
+    fn exchange(engine: &SyncEngine, offset_ms: u64, round_trip_ms: u64) -> ClockExchange {
+        let t3 = engine.now_us().unwrap();
+        let t0 = t3 - round_trip_ms * 1000;
+        let t1 = t0 + round_trip_ms * 500 + offset_ms * 1000;
+        ClockExchange { t0, t1, t2: t1, t3 }
+    }
+
+    async fn next_command(receiver: &mut broadcast::Receiver<SyncMessage>, client: &str) -> (SyncCommandType, u64) {
+        loop {
+            match receiver.recv().await.unwrap() {
+                SyncMessage::SyncCommand { client_id, command, timestamp } if client_id == client => {
+                    return (command, timestamp)
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_commands_drift_and_late_joiner() {
+        let engine = Arc::new(SyncEngine::new(ClockSyncConfig::default()).await.unwrap());
+        let sync = Arc::new(SyncManager::new(SyncConfig::default(), engine.clone()));
+        let parties = ListeningPartyManager::new(sync.clone(), ListeningPartyConfig {
+            command_lead: Duration::from_millis(200),
+            ..ListeningPartyConfig::default()
+        });
+        // Un receiver par client : l'ordre d'envoi entre membres n'est pas défini
+        let (mut host_rx, mut guest_rx, mut late_rx) =
+            (sync.get_sync_receiver(), sync.get_sync_receiver(), sync.get_sync_receiver());
+        let (host, guest, late) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+        let party = parties.create(1, "Album premiere".to_string(), "album.flac".to_string(), vec![2]);
+        assert!(matches!(parties.join(party.id, 3, "intruder".to_string()).await, Err(AppError::Forbidden)));
+        parties.join(party.id, 1, host.clone()).await.unwrap();
+        parties.join(party.id, 2, guest.clone()).await.unwrap();
+        assert!(matches!(parties.command(party.id, 2, PartyAction::Play).await, Err(AppError::Forbidden)));
+
+        // Horloge non calée : position non datable, ignorée
+        assert_eq!(parties.report_drift(party.id, &guest, 0, 0).await.unwrap(), None);
+        // Calage par le protocole du SyncEngine : hôte en avance de 40 ms
+        // avec 300 ms d'aller-retour, invité en avance de 250 ms
+        engine.record_clock_exchange(Uuid::parse_str(&host).unwrap(), exchange(&engine, 40, 300)).unwrap();
+        engine.record_clock_exchange(Uuid::parse_str(&guest).unwrap(), exchange(&engine, 250, 4)).unwrap();
+        assert_eq!(sync.get_client(&host).await.unwrap().connection_quality.rtt_ms, 300);
+
+        // Avance : au moins deux RTT du membre le plus lent et la valeur configurée
+        let before = sync.master_time_ms().unwrap();
+        let play = parties.command(party.id, 1, PartyAction::Play).await.unwrap();
+        assert!(play.execute_at_ms >= before + 600);
+        for (receiver, client) in [(&mut host_rx, &host), (&mut guest_rx, &guest)] {
+            assert!(matches!(
+                next_command(receiver, client).await,
+                (SyncCommandType::Play { position_ms: 0 }, at) if at == play.execute_at_ms
+            ));
+        }
+
+        // Mesure pendant le délai d'exécution : ignorée
+        assert_eq!(parties.report_drift(party.id, &guest, 0, play.execute_at_ms + 249).await.unwrap(), None);
+        // En avance de 500 ms sur la position attendue : saut correctif
+        let drift = parties.report_drift(party.id, &guest, 1500, play.execute_at_ms + 1250).await.unwrap();
+        assert_eq!(drift, Some(500));
+        let (correction, at) = next_command(&mut guest_rx, &guest).await;
+        assert!(matches!(correction, SyncCommandType::Seek { position_ms } if position_ms == at - play.execute_at_ms));
+        assert_eq!(parties.report_drift(party.id, &host, 1020, play.execute_at_ms + 1040).await.unwrap(), Some(20));
+
+        let seek = parties.command(party.id, 1, PartyAction::Seek { position_ms: 60_000 }).await.unwrap();
+        assert_eq!((seek.sequence, seek.position_ms), (2, 60_000));
+        assert!(matches!(
+            next_command(&mut host_rx, &host).await,
+            (SyncCommandType::Seek { position_ms: 60_000 }, at) if at == seek.execute_at_ms
+        ));
+
+        // Retardataire : calé sur la position à l'heure d'exécution
+        parties.invite(party.id, 1, &[4]).unwrap();
+        parties.join(party.id, 4, late.clone()).await.unwrap();
+        let (seek_late, at) = next_command(&mut late_rx, &late).await;
+        assert!(at >= seek.execute_at_ms);
+        let expected = 60_000 + at - seek.execute_at_ms;
+        assert!(matches!(seek_late, SyncCommandType::Seek { position_ms } if position_ms == expected));
+        assert!(matches!(next_command(&mut late_rx, &late).await.0, SyncCommandType::Play { position_ms } if position_ms == expected));
+
+        parties.close(party.id, 1).await.unwrap();
+        assert!(matches!(next_command(&mut host_rx, &host).await.0, SyncCommandType::PauseSync));
+        assert!(sync.get_client(&guest).await.is_none());
+        assert!(matches!(parties.get(party.id, 1), Err(AppError::NotFound { .. })));
+    }
+
+    async fn manager(config: ListeningPartyConfig) -> (Arc<SyncManager>, ListeningPartyManager) {
+        let engine = Arc::new(SyncEngine::new(ClockSyncConfig::default()).await.unwrap());
+        let sync = Arc::new(SyncManager::new(SyncConfig::default(), engine));
+        (sync.clone(), ListeningPartyManager::new(sync, config))
+    }
+
+    #[tokio::test]
+    async fn test_only_host_and_invited_users_see_and_join() {
+        let (_, parties) = manager(ListeningPartyConfig::default()).await;
+        let party = parties.create(1, "Écoute".to_string(), "track.mp3".to_string(), vec![2]);
+        assert!(parties.get(party.id, 2).is_ok());
+        assert!(matches!(parties.get(party.id, 3), Err(AppError::Forbidden)));
+        assert!(matches!(parties.join(party.id, 3, Uuid::new_v4().to_string()).await, Err(AppError::Forbidden)));
+        assert!(matches!(parties.get(Uuid::new_v4(), 1), Err(AppError::NotFound { .. })));
+
+        // Seul l'hôte invite
+        assert!(matches!(parties.invite(party.id, 2, &[3]), Err(AppError::Forbidden)));
+        parties.invite(party.id, 1, &[3]).unwrap();
+        assert!(parties.get(party.id, 3).is_ok());
+        let joined = parties.join(party.id, 3, Uuid::new_v4().to_string()).await.unwrap();
+        assert_eq!(joined.members.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_member_limit_holds_under_concurrent_joins() {
+        let (sync, parties) = manager(ListeningPartyConfig { max_members: 3, ..ListeningPartyConfig::default() }).await;
+        let parties = Arc::new(parties);
+        let party = parties.create(1, "Écoute".to_string(), "track.mp3".to_string(), (2..=20).collect());
+
+        let joins: Vec<_> = (1..=20)
+            .map(|user_id| {
+                let parties = parties.clone();
+                tokio::spawn(async move { parties.join(party.id, user_id, Uuid::new_v4().to_string()).await })
+            })
+            .collect();
+        let results: Vec<_> = futures::future::join_all(joins).await.into_iter().map(Result::unwrap).collect();
+
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 3);
+        assert!(results
+            .iter()
+            .filter_map(|result| result.as_ref().err())
+            .all(|e| matches!(e, AppError::LimitExceeded { limit: 3, .. })));
+        assert_eq!(parties.get(party.id, 1).unwrap().members.len(), 3);
+        // Les clients refusés ne restent pas inscrits au SyncManager
+        assert_eq!(sync.get_sync_stats().await["sync_stats"]["total_clients"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_close_pauses_every_member() {
+        let (sync, parties) = manager(ListeningPartyConfig::default()).await;
+        let party = parties.create(1, "Écoute".to_string(), "track.mp3".to_string(), vec![2, 3]);
+        let members: Vec<String> = (1..=3).map(|_| Uuid::new_v4().to_string()).collect();
+        let mut receivers: Vec<_> = members.iter().map(|_| sync.get_sync_receiver()).collect();
+        for (user_id, client_id) in (1..).zip(&members) {
+            parties.join(party.id, user_id, client_id.clone()).await.unwrap();
+        }
+
+        assert!(matches!(parties.close(party.id, 2).await, Err(AppError::Forbidden)));
+        parties.close(party.id, 1).await.unwrap();
+        for (receiver, client_id) in receivers.iter_mut().zip(&members) {
+            assert!(matches!(next_command(receiver, client_id).await.0, SyncCommandType::PauseSync));
+            assert!(sync.get_client(client_id).await.is_none());
+        }
+        assert!(matches!(parties.close(party.id, 1).await, Err(AppError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_party_without_host_is_closed_after_idle_timeout() {
+        let (sync, parties) = manager(ListeningPartyConfig {
+            host_idle_timeout: Duration::from_millis(100),
+            ..ListeningPartyConfig::default()
+        })
+        .await;
+        let mut receiver = sync.get_sync_receiver();
+        let party = parties.create(1, "Écoute".to_string(), "track.mp3".to_string(), vec![2]);
+        parties.create(4, "Jamais ouverte".to_string(), "track.mp3".to_string(), vec![]);
+        let (host, guest) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+        parties.join(party.id, 1, host.clone()).await.unwrap();
+        parties.join(party.id, 2, guest.clone()).await.unwrap();
+
+        // Seule la session que son hôte n'a jamais rejointe est fermée
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(parties.close_idle().await.unwrap(), 1);
+        assert!(parties.get(party.id, 2).is_ok());
+
+        // Hôte déconnecté : l'invité est mis en pause une fois le délai écoulé
+        parties.leave(party.id, &host).await;
+        assert_eq!(parties.close_idle().await.unwrap(), 0);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(parties.close_idle().await.unwrap(), 1);
+        assert!(matches!(next_command(&mut receiver, &guest).await.0, SyncCommandType::PauseSync));
+        assert!(sync.get_client(&guest).await.is_none());
+        assert!(matches!(parties.get(party.id, 2), Err(AppError::NotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_routes_require_a_valid_jwt() {
+        let config = Config::from_env().unwrap();
+        // Jetons tels qu'émis par la plateforme
+        let secret = jsonwebtoken::EncodingKey::from_secret(config.security.jwt_secret.as_ref().unwrap().as_bytes());
+        let token = |user_id: i64| {
+            let now = SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+            let claims = Claims {
+                sub: user_id,
+                username: format!("user{}", user_id),
+                email: None,
+                roles: vec![Role::User],
+                permissions: vec![Permission::StreamAudio],
+                exp: now + 3600,
+                iat: now,
+                iss: "veza-platform".to_string(),
+                aud: "veza-services".to_string(),
+                session_id: Uuid::new_v4().to_string(),
+            };
+            jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, &secret).unwrap()
+        };
+        let (host_token, guest_token) = (token(1001), token(1002));
+        let auth = Arc::new(AuthManager::new(Arc::new(config)).unwrap());
+        let (_, parties) = manager(ListeningPartyConfig::default()).await;
+        let app = listening_party_routes::<()>(Arc::new(parties), auth.clone());
+        let request = |method: &str, uri: String, token: Option<&str>, body: &str| {
+            let mut request = Request::builder().method(method).uri(uri).header(header::CONTENT_TYPE, "application/json");
+            if let Some(token) = token {
+                request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+            }
+            request.body(Body::from(body.to_string())).unwrap()
+        };
+        let create = r#"{"title":"Soirée","media":"track.mp3"}"#;
+
+        for token in [None, Some("invalide")] {
+            let response = app.clone().oneshot(request("POST", "/".to_string(), token, create)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = app.clone().oneshot(request("POST", "/".to_string(), Some(&host_token), create)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let party: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let party_id = party["id"].as_str().unwrap().to_string();
+
+        // Invité seulement après l'invitation de l'hôte
+        let get = || request("GET", format!("/{}", party_id), Some(&guest_token), "");
+        assert_eq!(app.clone().oneshot(get()).await.unwrap().status(), StatusCode::FORBIDDEN);
+        let invite = request("POST", format!("/{}/invitations", party_id), Some(&host_token), r#"{"user_ids":[1002]}"#);
+        assert_eq!(app.clone().oneshot(invite).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(app.clone().oneshot(get()).await.unwrap().status(), StatusCode::OK);
+
+        // WebSocket : jeton passé en paramètre de requête
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = app.clone();
+        tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+        let socket_url = format!("ws://{}/{}/ws", address, party_id);
+        assert!(tokio_tungstenite::connect_async(socket_url.clone()).await.is_err());
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}?token={}", socket_url, guest_token)).await.unwrap();
+        let joined = socket.next().await.unwrap().unwrap();
+        assert!(joined.to_text().unwrap().contains("\"Joined\""));
+
+        // Seul l'hôte ferme la session ; le membre connecté reçoit `PauseSync`
+        let close = |token: &str| request("DELETE", format!("/{}", party_id), Some(token), "");
+        assert_eq!(app.clone().oneshot(close(&guest_token)).await.unwrap().status(), StatusCode::FORBIDDEN);
+        assert_eq!(app.clone().oneshot(close(&host_token)).await.unwrap().status(), StatusCode::NO_CONTENT);
+        let paused = socket.next().await.unwrap().unwrap();
+        assert!(paused.to_text().unwrap().contains("PauseSync"));
+    }
+}
@@ -0,0 +1,770 @@
+//! HLS live à fenêtre glissante
+//!
+//! Segmente en continu le PCM d'un stream `StreamSource::Live` en CMAF (fMP4),
+//! une rendition par sortie `StreamProtocol::HLS`. La playlist glisse
+//! (`#EXT-X-MEDIA-SEQUENCE`), chaque segment porte son
+//! `#EXT-X-PROGRAM-DATE-TIME` pour le seek dans la fenêtre DVR, et les
+//! segments sortis de la fenêtre sont supprimés du disque. Les sorties
+//! `StreamProtocol::DASH` partagent ces segments via un MPD dynamique.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, StatusCode},
+    response::Response,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::codecs::{AudioEncoder, CodecQuality, EncoderConfig, LatencyMode};
+use crate::config::Config;
+use crate::core::{AudioFormat, StreamOutput, StreamProtocol, StreamSource};
+use crate::error::AppError;
+use crate::streaming::adaptive::{error_response, manifest_response, playlist_response, signed_query, AdaptiveStreamQuery};
+use crate::streaming::dash::{self, LiveMpdTiming, MpdRepresentation};
+use crate::streaming::fmp4::{self, Fmp4Sample, Fmp4Track};
+use crate::streaming::segmenter::{self, remix_channels, StreamResampler, INIT_SEGMENT};
+use crate::utils::validate_signature;
+
+/// Configuration du HLS live
+#[derive(Debug, Clone)]
+pub struct LiveHlsConfig {
+    /// Répertoire racine (`<output_dir>/<stream_id>/<rendition_id>/`)
+    pub output_dir: PathBuf,
+    /// Profondeur de la fenêtre DVR annoncée dans la playlist ; zéro pour
+    /// ne garder que `playlist_size` segments
+    pub dvr_window: Duration,
+}
+
+impl Default for LiveHlsConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("./cache/live"),
+            dvr_window: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Nombre minimal de segments listés pour une sortie DASH
+const DASH_MIN_SEGMENTS: usize = 3;
+
+/// Paramètres d'une rendition live, issus d'une sortie HLS ou DASH du stream
+#[derive(Debug, Clone)]
+pub struct LiveRenditionConfig {
+    pub rendition_id: String,
+    pub codec: String,
+    pub bitrate_kbps: u32,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub segment_duration: Duration,
+    /// Nombre minimal de segments listés à la live edge
+    pub playlist_size: usize,
+}
+
+impl LiveRenditionConfig {
+    /// Rendition correspondant à une sortie, si elle utilise HLS ou DASH
+    pub fn from_output(output: &StreamOutput) -> Option<Self> {
+        let (segment_duration, playlist_size) = match &output.protocol {
+            StreamProtocol::HLS { segment_duration, playlist_size } => (*segment_duration, *playlist_size),
+            StreamProtocol::DASH { segment_duration, .. } => (*segment_duration, DASH_MIN_SEGMENTS),
+            _ => return None,
+        };
+
+        Some(Self {
+            rendition_id: format!("{}k", output.bitrate),
+            codec: output.format.codec.to_lowercase(),
+            bitrate_kbps: output.bitrate,
+            sample_rate: output.format.sample_rate,
+            channels: output.format.channels.max(1),
+            segment_duration,
+            playlist_size: playlist_size.max(1),
+        })
+    }
+}
+
+/// Un segment live écrit sur disque
+#[derive(Debug, Clone)]
+pub struct LiveSegment {
+    pub sequence: u64,
+    pub uri: String,
+    /// Temps média du premier échantillon, en unités de timescale
+    pub start_time: u64,
+    pub duration: f64,
+    /// Horloge murale du premier échantillon du segment
+    pub program_date_time: DateTime<Utc>,
+    pub byte_size: u64,
+}
+
+/// Rendition live : encodeur, fenêtre glissante et segments sur disque
+#[derive(Debug)]
+pub struct LiveHlsRendition {
+    config: LiveRenditionConfig,
+    dir: PathBuf,
+    track: Fmp4Track,
+    encoder: Box<dyn AudioEncoder>,
+    input_channels: usize,
+    resampler: Option<StreamResampler>,
+    /// Sortie de l'encodeur pas encore découpée en frames complètes
+    encoded: Vec<u8>,
+    pending: Vec<Fmp4Sample>,
+    pending_duration: u64,
+    decode_time: u64,
+    started_at: Option<DateTime<Utc>>,
+    next_sequence: u64,
+    /// Nombre de segments listés dans la playlist
+    window_len: usize,
+    /// Segments listés, précédés des segments encore servis après leur sortie
+    segments: VecDeque<LiveSegment>,
+    ended: bool,
+}
+
+impl LiveHlsRendition {
+    pub fn new(
+        dir: PathBuf,
+        config: LiveRenditionConfig,
+        input: &AudioFormat,
+        dvr_window: Duration,
+    ) -> Result<Self, AppError> {
+        let (encoder, codec) = segmenter::create_segment_encoder(&config.codec, EncoderConfig {
+            bitrate: config.bitrate_kbps * 1000,
+            sample_rate: config.sample_rate,
+            channels: config.channels,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::Low,
+            enable_vbr: false,
+            complexity: 5,
+        })?;
+
+        let resampler = if input.sample_rate != config.sample_rate {
+            Some(StreamResampler::new(input.sample_rate, config.sample_rate, config.channels as usize)?)
+        } else {
+            None
+        };
+
+        let bitrate = config.bitrate_kbps * 1000;
+        let track = Fmp4Track {
+            track_id: 1,
+            timescale: config.sample_rate,
+            sample_rate: config.sample_rate,
+            channels: u16::from(config.channels),
+            codec,
+            avg_bitrate: bitrate,
+            max_bitrate: bitrate,
+        };
+
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(INIT_SEGMENT), fmp4::init_segment(&track))?;
+
+        let segment_secs = config.segment_duration.as_secs_f64().max(0.1);
+        let window_len = config.playlist_size.max((dvr_window.as_secs_f64() / segment_secs).ceil() as usize);
+
+        Ok(Self {
+            dir,
+            track,
+            encoder,
+            input_channels: input.channels.max(1) as usize,
+            resampler,
+            encoded: Vec::new(),
+            pending: Vec::new(),
+            pending_duration: 0,
+            decode_time: 0,
+            started_at: None,
+            next_sequence: 0,
+            window_len,
+            segments: VecDeque::new(),
+            ended: false,
+            config,
+        })
+    }
+
+    pub fn rendition_id(&self) -> &str {
+        &self.config.rendition_id
+    }
+
+    pub fn config(&self) -> &LiveRenditionConfig {
+        &self.config
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn codecs(&self) -> &'static str {
+        self.track.codec.codecs_string()
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Profondeur de la fenêtre annoncée (`timeShiftBufferDepth` DASH)
+    pub fn window_depth(&self) -> Duration {
+        self.config.segment_duration * self.window_len as u32
+    }
+
+    /// Représentation DASH des segments de la fenêtre
+    pub fn mpd_representation(&self) -> Option<MpdRepresentation> {
+        let first = self.window().next()?;
+        let timescale = self.track.timescale;
+        Some(MpdRepresentation {
+            id: self.config.rendition_id.clone(),
+            bandwidth: self.config.bitrate_kbps * 1000,
+            codecs: self.codecs().to_string(),
+            sample_rate: self.config.sample_rate,
+            channels: self.config.channels,
+            timescale,
+            initialization: INIT_SEGMENT.to_string(),
+            media: "segment$Number$.m4s".to_string(),
+            start_number: first.sequence,
+            segments: self
+                .window()
+                .map(|segment| (segment.start_time, (segment.duration * f64::from(timescale)).round() as u64))
+                .collect(),
+        })
+    }
+
+    /// Segments actuellement annoncés dans la playlist
+    pub fn window(&self) -> impl Iterator<Item = &LiveSegment> {
+        let skip = self.segments.len().saturating_sub(self.window_len);
+        self.segments.iter().skip(skip)
+    }
+
+    /// Encode du PCM entrelacé au format de la source et publie les
+    /// segments complets
+    pub fn push(&mut self, samples: &[f32]) -> Result<(), AppError> {
+        if self.ended {
+            return Err(AppError::StreamingError {
+                message: format!("Rendition live {} terminée", self.config.rendition_id),
+            });
+        }
+        if samples.is_empty() {
+            return Ok(());
+        }
+        self.started_at.get_or_insert_with(Utc::now);
+
+        let channels = self.config.channels as usize;
+        let samples = remix_channels(samples, self.input_channels, channels);
+        let samples = match self.resampler.as_mut() {
+            Some(resampler) => resampler.process(&samples)?,
+            None => samples,
+        };
+        self.encode(&samples)
+    }
+
+    /// Vide l'encodeur, publie le dernier segment et ferme la playlist
+    pub fn finish(&mut self) -> Result<(), AppError> {
+        if self.ended {
+            return Ok(());
+        }
+        if self.started_at.is_some() {
+            if let Some(tail) = self.resampler.as_mut().map(|resampler| resampler.flush()).transpose()? {
+                self.encode(&tail)?;
+            }
+            let tail = self.encoder.finalize()?;
+            self.encoded.extend_from_slice(&tail);
+            self.take_frames()?;
+            if !self.pending.is_empty() {
+                self.cut_segment()?;
+            }
+        }
+        self.ended = true;
+        Ok(())
+    }
+
+    fn encode(&mut self, samples: &[f32]) -> Result<(), AppError> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        let encoded = self.encoder.encode(samples, self.config.sample_rate, self.config.channels)?;
+        self.encoded.extend_from_slice(&encoded);
+        self.take_frames()
+    }
+
+    /// Déplace les frames complètes de la sortie encodeur vers le segment
+    /// en cours, en coupant un segment à chaque durée cible atteinte
+    fn take_frames(&mut self) -> Result<(), AppError> {
+        let (frames, consumed) = segmenter::split_frames(&self.track.codec, &self.encoded);
+        self.encoded.drain(..consumed);
+
+        let target = (self.config.segment_duration.as_secs_f64() * f64::from(self.track.timescale)) as u64;
+        for frame in frames {
+            self.pending_duration += u64::from(frame.duration);
+            self.pending.push(frame);
+            if self.pending_duration >= target {
+                self.cut_segment()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cut_segment(&mut self) -> Result<(), AppError> {
+        let sequence = self.next_sequence;
+        let uri = format!("segment{}.m4s", sequence);
+        let data = fmp4::media_segment(&self.track, (sequence + 1) as u32, self.decode_time, &self.pending);
+
+        // Écriture puis renommage : un lecteur ne voit jamais de segment tronqué
+        let staging = self.dir.join(format!(".{}", uri));
+        fs::write(&staging, &data)?;
+        fs::rename(&staging, self.dir.join(&uri))?;
+
+        let timescale = f64::from(self.track.timescale);
+        let started_at = self.started_at.unwrap_or_else(Utc::now);
+        let offset = chrono::Duration::microseconds((self.decode_time as f64 / timescale * 1e6) as i64);
+        self.segments.push_back(LiveSegment {
+            sequence,
+            uri,
+            start_time: self.decode_time,
+            duration: self.pending_duration as f64 / timescale,
+            program_date_time: started_at + offset,
+            byte_size: data.len() as u64,
+        });
+
+        self.decode_time += self.pending_duration;
+        self.pending_duration = 0;
+        self.pending.clear();
+        self.next_sequence += 1;
+
+        self.expire_segments();
+        Ok(())
+    }
+
+    /// Supprime les segments sortis de la fenêtre depuis plus d'une
+    /// playlist : un client qui vient de la recharger peut encore les lire
+    fn expire_segments(&mut self) {
+        while self.segments.len() > self.window_len + self.config.playlist_size {
+            if let Some(segment) = self.segments.pop_front() {
+                if let Err(e) = fs::remove_file(self.dir.join(&segment.uri)) {
+                    warn!("Impossible de supprimer le segment live {:?}: {}", segment.uri, e);
+                }
+            }
+        }
+    }
+
+    /// Valeur constante de `#EXT-X-TARGETDURATION` : les segments dépassent
+    /// la durée cible d'au plus une frame
+    pub fn target_duration(&self) -> u32 {
+        self.config.segment_duration.as_secs() as u32 + 1
+    }
+
+    /// Génère la media playlist live ; `None` tant qu'aucun segment n'est prêt
+    pub fn media_playlist(&self, uri_query: Option<&str>) -> Option<String> {
+        let first = self.window().next()?;
+        let with_query = |uri: &str| match uri_query {
+            Some(query) => format!("{}?{}", uri, query),
+            None => uri.to_string(),
+        };
+
+        let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:7\n");
+        playlist.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", self.target_duration()));
+        playlist.push_str(&format!("#EXT-X-MEDIA-SEQUENCE:{}\n", first.sequence));
+        playlist.push_str("#EXT-X-INDEPENDENT-SEGMENTS\n");
+        playlist.push_str(&format!("#EXT-X-MAP:URI=\"{}\"\n", with_query(INIT_SEGMENT)));
+
+        for segment in self.window() {
+            playlist.push_str(&format!(
+                "#EXT-X-PROGRAM-DATE-TIME:{}\n#EXTINF:{:.5},\n{}\n",
+                segment.program_date_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                segment.duration,
+                with_query(&segment.uri)
+            ));
+        }
+
+        if self.ended {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+        Some(playlist)
+    }
+
+    /// Vrai si `file` est le segment d'init ou un segment encore servi
+    pub fn contains_file(&self, file: &str) -> bool {
+        file == INIT_SEGMENT || self.segments.iter().any(|segment| segment.uri == file)
+    }
+}
+
+/// Renditions HLS d'un stream live
+#[derive(Debug)]
+pub struct LiveHlsStream {
+    pub stream_id: Uuid,
+    pub dir: PathBuf,
+    /// Horloge murale du temps média zéro, commune à toutes les renditions
+    pub started_at: Option<DateTime<Utc>>,
+    pub renditions: Vec<LiveHlsRendition>,
+}
+
+impl LiveHlsStream {
+    fn rendition(&self, rendition_id: &str) -> Result<&LiveHlsRendition, AppError> {
+        self.renditions
+            .iter()
+            .find(|rendition| rendition.rendition_id() == rendition_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("rendition {}", rendition_id) })
+    }
+}
+
+/// Registre des streams live publiés en HLS
+#[derive(Debug)]
+pub struct LiveHlsManager {
+    config: LiveHlsConfig,
+    streams: DashMap<Uuid, Arc<Mutex<LiveHlsStream>>>,
+}
+
+impl LiveHlsManager {
+    pub fn new(config: LiveHlsConfig) -> Self {
+        Self {
+            config,
+            streams: DashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &LiveHlsConfig {
+        &self.config
+    }
+
+    /// Prépare les renditions d'un stream live ; retourne `false` si la
+    /// source n'est pas live ou si aucune sortie n'utilise HLS
+    pub fn start(&self, stream_id: Uuid, source: &StreamSource, outputs: &[StreamOutput]) -> Result<bool, AppError> {
+        let StreamSource::Live { format, .. } = source else {
+            return Ok(false);
+        };
+
+        let mut configs: Vec<LiveRenditionConfig> = Vec::new();
+        for config in outputs.iter().filter_map(LiveRenditionConfig::from_output) {
+            if !configs.iter().any(|existing| existing.rendition_id == config.rendition_id) {
+                configs.push(config);
+            }
+        }
+        if configs.is_empty() {
+            return Ok(false);
+        }
+
+        let dir = self.config.output_dir.join(stream_id.to_string());
+        let renditions = configs
+            .into_iter()
+            .map(|config| {
+                let rendition_dir = dir.join(&config.rendition_id);
+                LiveHlsRendition::new(rendition_dir, config, format, self.config.dvr_window)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        info!("HLS live démarré pour le stream {} ({} renditions)", stream_id, renditions.len());
+        self.streams.insert(stream_id, Arc::new(Mutex::new(LiveHlsStream { stream_id, dir, started_at: None, renditions })));
+        Ok(true)
+    }
+
+    fn stream(&self, stream_id: Uuid) -> Result<Arc<Mutex<LiveHlsStream>>, AppError> {
+        self.streams
+            .get(&stream_id)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| AppError::NotFound { resource: format!("live stream {}", stream_id) })
+    }
+
+    pub fn is_live(&self, stream_id: Uuid) -> bool {
+        self.streams.contains_key(&stream_id)
+    }
+
+    /// Pousse du PCM entrelacé (format de la source) vers toutes les renditions
+    pub fn push_pcm(&self, stream_id: Uuid, samples: &[f32]) -> Result<(), AppError> {
+        let stream = self.stream(stream_id)?;
+        let mut stream = stream.lock();
+        let started_at = *stream.started_at.get_or_insert_with(Utc::now);
+        for rendition in &mut stream.renditions {
+            rendition.started_at.get_or_insert(started_at);
+            rendition.push(samples)?;
+        }
+        Ok(())
+    }
+
+    /// Ferme les playlists d'un stream puis supprime ses segments une fois
+    /// la fenêtre DVR écoulée
+    pub fn finish(self: &Arc<Self>, stream_id: Uuid) -> Result<(), AppError> {
+        let stream = self.stream(stream_id)?;
+        for rendition in &mut stream.lock().renditions {
+            rendition.finish()?;
+        }
+
+        let manager = self.clone();
+        let retention = self.config.dvr_window;
+        tokio::spawn(async move {
+            tokio::time::sleep(retention).await;
+            manager.remove(stream_id);
+        });
+
+        info!("HLS live terminé pour le stream {}", stream_id);
+        Ok(())
+    }
+
+    /// Retire un stream et supprime ses fichiers
+    pub fn remove(&self, stream_id: Uuid) {
+        if let Some((_, stream)) = self.streams.remove(&stream_id) {
+            let dir = stream.lock().dir.clone();
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                warn!("Impossible de supprimer {:?}: {}", dir, e);
+            }
+            debug!("Segments live du stream {} supprimés", stream_id);
+        }
+    }
+
+    /// Master playlist ; les URIs des renditions sont relatives
+    pub fn master_playlist(&self, stream_id: Uuid, uri_query: Option<&str>) -> Result<String, AppError> {
+        let stream = self.stream(stream_id)?;
+        let stream = stream.lock();
+
+        let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-INDEPENDENT-SEGMENTS\n");
+        for rendition in &stream.renditions {
+            let config = rendition.config();
+            let uri = match uri_query {
+                Some(query) => format!("{}/playlist.m3u8?{}", config.rendition_id, query),
+                None => format!("{}/playlist.m3u8", config.rendition_id),
+            };
+            playlist.push_str(&format!(
+                "#EXT-X-STREAM-INF:BANDWIDTH={},CODECS=\"{}\"\n{}\n",
+                // Marge pour l'overhead des boxes fMP4
+                config.bitrate_kbps * 1100,
+                rendition.codecs(),
+                uri
+            ));
+        }
+        Ok(playlist)
+    }
+
+    pub fn media_playlist(&self, stream_id: Uuid, rendition_id: &str, uri_query: Option<&str>) -> Result<String, AppError> {
+        let stream = self.stream(stream_id)?;
+        let stream = stream.lock();
+        stream
+            .rendition(rendition_id)?
+            .media_playlist(uri_query)
+            .ok_or_else(|| AppError::NotFound { resource: format!("playlist {}", rendition_id) })
+    }
+
+    /// MPD dynamique partageant les segments des playlists HLS
+    pub fn dash_manifest(&self, stream_id: Uuid, uri_query: Option<&str>) -> Result<String, AppError> {
+        let stream = self.stream(stream_id)?;
+        let stream = stream.lock();
+
+        let representations: Vec<MpdRepresentation> = stream
+            .renditions
+            .iter()
+            .filter_map(LiveHlsRendition::mpd_representation)
+            .collect();
+        let (Some(started_at), Some(first)) = (stream.started_at, stream.renditions.first()) else {
+            return Err(AppError::NotFound { resource: format!("manifest {}", stream_id) });
+        };
+        if representations.is_empty() {
+            return Err(AppError::NotFound { resource: format!("manifest {}", stream_id) });
+        }
+
+        let timing = LiveMpdTiming {
+            availability_start_time: started_at,
+            publish_time: Utc::now(),
+            segment_duration: first.config().segment_duration,
+            time_shift_buffer_depth: first.window_depth(),
+            ended: stream.renditions.iter().all(LiveHlsRendition::is_ended),
+        };
+        Ok(dash::live_manifest(&representations, &timing, uri_query))
+    }
+
+    /// Chemin d'un fichier encore servi d'une rendition
+    pub fn segment_path(&self, stream_id: Uuid, rendition_id: &str, file: &str) -> Result<PathBuf, AppError> {
+        let stream = self.stream(stream_id)?;
+        let stream = stream.lock();
+        let rendition = stream.rendition(rendition_id)?;
+        if !rendition.contains_file(file) {
+            return Err(AppError::NotFound { resource: file.to_string() });
+        }
+        Ok(rendition.dir().join(file))
+    }
+}
+
+/// État partagé des routes HLS live
+#[derive(Clone)]
+struct LiveHlsState {
+    config: Arc<Config>,
+    live_hls: Arc<LiveHlsManager>,
+}
+
+/// Routes live : `/:stream_id/master.m3u8`, `/:stream_id/manifest.mpd` et
+/// `/:stream_id/:rendition/:file`
+pub fn live_hls_routes<S>(config: Arc<Config>, live_hls: Arc<LiveHlsManager>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/:stream_id/master.m3u8", get(live_master_playlist))
+        .route("/:stream_id/manifest.mpd", get(live_dash_manifest))
+        .route("/:stream_id/:rendition/:file", get(live_rendition_file))
+        .with_state(LiveHlsState { config, live_hls })
+}
+
+fn parse_stream_id(stream_id: &str) -> Result<Uuid, (StatusCode, String)> {
+    Uuid::parse_str(stream_id).map_err(|_| (StatusCode::BAD_REQUEST, "Identifiant de stream invalide".to_string()))
+}
+
+async fn live_master_playlist(
+    AxumPath(stream_id): AxumPath<String>,
+    Query(params): Query<AdaptiveStreamQuery>,
+    State(state): State<LiveHlsState>,
+) -> Result<Response, (StatusCode, String)> {
+    if !validate_signature(&state.config, &stream_id, &params.expires, &params.sig) {
+        return Err((StatusCode::FORBIDDEN, "Signature invalide".to_string()));
+    }
+
+    let playlist = state.live_hls
+        .master_playlist(parse_stream_id(&stream_id)?, Some(&signed_query(&params)))
+        .map_err(error_response)?;
+    playlist_response(playlist)
+}
+
+async fn live_dash_manifest(
+    AxumPath(stream_id): AxumPath<String>,
+    Query(params): Query<AdaptiveStreamQuery>,
+    State(state): State<LiveHlsState>,
+) -> Result<Response, (StatusCode, String)> {
+    if !validate_signature(&state.config, &stream_id, &params.expires, &params.sig) {
+        return Err((StatusCode::FORBIDDEN, "Signature invalide".to_string()));
+    }
+
+    let manifest = state.live_hls
+        .dash_manifest(parse_stream_id(&stream_id)?, Some(&signed_query(&params)))
+        .map_err(error_response)?;
+    manifest_response(manifest)
+}
+
+async fn live_rendition_file(
+    AxumPath((stream_id, rendition, file)): AxumPath<(String, String, String)>,
+    Query(params): Query<AdaptiveStreamQuery>,
+    State(state): State<LiveHlsState>,
+) -> Result<Response, (StatusCode, String)> {
+    if !validate_signature(&state.config, &stream_id, &params.expires, &params.sig) {
+        return Err((StatusCode::FORBIDDEN, "Signature invalide".to_string()));
+    }
+    let stream_id = parse_stream_id(&stream_id)?;
+
+    if file == "playlist.m3u8" {
+        let playlist = state.live_hls
+            .media_playlist(stream_id, &rendition, Some(&signed_query(&params)))
+            .map_err(error_response)?;
+        return playlist_response(playlist);
+    }
+
+    let path = state.live_hls
+        .segment_path(stream_id, &rendition, &file)
+        .map_err(error_response)?;
+    let data = tokio::fs::read(&path)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, "Segment introuvable".to_string()))?;
+
+    // Un segment publié n'est jamais réécrit
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "audio/mp4")
+        .header(header::CONTENT_LENGTH, data.len())
+        .header(header::CACHE_CONTROL, "public, max-age=3600, immutable")
+        .body(data.into())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_format(sample_rate: u32) -> AudioFormat {
+        AudioFormat {
+            codec: "pcm".to_string(),
+            bitrate: 0,
+            sample_rate,
+            channels: 2,
+            bit_depth: 32,
+        }
+    }
+
+    fn rendition_config(playlist_size: usize) -> LiveRenditionConfig {
+        LiveRenditionConfig {
+            rendition_id: "128k".to_string(),
+            codec: "mp3".to_string(),
+            bitrate_kbps: 128,
+            sample_rate: 44100,
+            channels: 2,
+            segment_duration: Duration::from_secs(1),
+            playlist_size,
+        }
+    }
+
+    fn tone(sample_rate: u32, seconds: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * seconds) as usize)
+            .flat_map(|i| {
+                let sample = (i as f32 * 440.0 * 2.0 * std::f32::consts::PI / sample_rate as f32).sin() * 0.3;
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_live_window_slides_and_expires() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rendition = LiveHlsRendition::new(
+            dir.path().join("128k"),
+            rendition_config(3),
+            &live_format(44100),
+            Duration::ZERO,
+        )
+        .unwrap();
+        assert!(rendition.media_playlist(None).is_none());
+
+        for chunk in tone(44100, 8.0).chunks(4410 * 2) {
+            rendition.push(chunk).unwrap();
+        }
+
+        let playlist = rendition.media_playlist(Some("expires=1&sig=ab")).unwrap();
+        let listed: Vec<_> = rendition.window().map(|segment| segment.sequence).collect();
+        assert_eq!(listed.len(), 3);
+        assert!(playlist.contains(&format!("#EXT-X-MEDIA-SEQUENCE:{}\n", listed[0])));
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:2\n"));
+        assert_eq!(playlist.matches("#EXT-X-PROGRAM-DATE-TIME:").count(), 3);
+        assert!(playlist.contains(&format!("segment{}.m4s?expires=1&sig=ab", listed[2])));
+        assert!(!playlist.contains("#EXT-X-ENDLIST"));
+
+        // Les segments sortis de la fenêtre depuis plus d'une playlist sont supprimés
+        assert!(!dir.path().join("128k/segment0.m4s").exists());
+        assert!(!rendition.contains_file("segment0.m4s"));
+        let retained = listed[0] - 1;
+        assert!(rendition.contains_file(&format!("segment{}.m4s", retained)));
+
+        rendition.finish().unwrap();
+        assert!(rendition.media_playlist(None).unwrap().ends_with("#EXT-X-ENDLIST\n"));
+    }
+
+    #[test]
+    fn test_program_date_time_follows_media_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rendition = LiveHlsRendition::new(
+            dir.path().join("128k"),
+            rendition_config(3),
+            &live_format(48000),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+
+        rendition.push(&tone(48000, 4.0)).unwrap();
+        rendition.finish().unwrap();
+
+        let segments: Vec<_> = rendition.window().cloned().collect();
+        assert!(segments.len() >= 4);
+        let total: f64 = segments.iter().map(|segment| segment.duration).sum();
+        assert!((total - 4.0).abs() < 0.1);
+        for pair in segments.windows(2) {
+            let elapsed = (pair[1].program_date_time - pair[0].program_date_time).num_milliseconds();
+            assert!((elapsed as f64 - pair[0].duration * 1000.0).abs() <= 1.0);
+        }
+    }
+}
@@ -4,6 +4,18 @@ pub mod webrtc;
 pub mod sync_manager;
 pub mod live_recording;
 pub mod advanced_streaming;
+pub mod fmp4;
+pub mod segmenter;
+pub mod dash;
+pub mod live_hls;
+pub mod ingest;
+pub mod icecast;
+pub mod icy;
+pub mod rtmp;
+pub mod webrtc_media;
+pub mod ws_audio;
+pub mod listening_party;
+pub mod clock_sync;
 
 pub use adaptive::*;
 pub use websocket::*;
@@ -0,0 +1,232 @@
+//! Encodage AMF0 des commandes RTMP (Adobe AMF0 File Format Specification)
+
+use crate::error::AppError;
+
+/// Valeur AMF0
+#[derive(Debug, Clone, PartialEq)]
+pub enum Amf0Value {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Object(Vec<(String, Amf0Value)>),
+    Null,
+    Undefined,
+    EcmaArray(Vec<(String, Amf0Value)>),
+    StrictArray(Vec<Amf0Value>),
+    Date(f64),
+}
+
+const NUMBER: u8 = 0x00;
+const BOOLEAN: u8 = 0x01;
+const STRING: u8 = 0x02;
+const OBJECT: u8 = 0x03;
+const NULL: u8 = 0x05;
+const UNDEFINED: u8 = 0x06;
+const ECMA_ARRAY: u8 = 0x08;
+const OBJECT_END: u8 = 0x09;
+const STRICT_ARRAY: u8 = 0x0A;
+const DATE: u8 = 0x0B;
+const LONG_STRING: u8 = 0x0C;
+
+/// Profondeur d'imbrication maximale acceptée
+const MAX_DEPTH: usize = 16;
+
+impl Amf0Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Propriété d'un objet ou tableau associatif
+    pub fn property(&self, name: &str) -> Option<&Amf0Value> {
+        match self {
+            Self::Object(properties) | Self::EcmaArray(properties) => {
+                properties.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn object(properties: &[(&str, Amf0Value)]) -> Self {
+        Self::Object(properties.iter().map(|(key, value)| (key.to_string(), value.clone())).collect())
+    }
+}
+
+/// Décode une suite de valeurs (corps d'une commande)
+pub fn decode_all(mut data: &[u8]) -> Result<Vec<Amf0Value>, AppError> {
+    let mut values = Vec::new();
+    while !data.is_empty() {
+        values.push(decode_value(&mut data, 0)?);
+    }
+    Ok(values)
+}
+
+fn take<'a>(data: &mut &'a [u8], count: usize) -> Result<&'a [u8], AppError> {
+    if data.len() < count {
+        return Err(AppError::InvalidData { message: "AMF0 tronqué".to_string() });
+    }
+    let (head, tail) = data.split_at(count);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_array<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], AppError> {
+    let mut array = [0u8; N];
+    array.copy_from_slice(take(data, N)?);
+    Ok(array)
+}
+
+fn decode_string(data: &mut &[u8], long: bool) -> Result<String, AppError> {
+    let length = match long {
+        true => u32::from_be_bytes(take_array(data)?) as usize,
+        false => usize::from(u16::from_be_bytes(take_array(data)?)),
+    };
+    Ok(String::from_utf8_lossy(take(data, length)?).into_owned())
+}
+
+fn decode_properties(data: &mut &[u8], depth: usize) -> Result<Vec<(String, Amf0Value)>, AppError> {
+    let mut properties = Vec::new();
+    loop {
+        let key = decode_string(data, false)?;
+        if key.is_empty() && data.first() == Some(&OBJECT_END) {
+            *data = &data[1..];
+            return Ok(properties);
+        }
+        properties.push((key, decode_value(data, depth + 1)?));
+    }
+}
+
+fn decode_value(data: &mut &[u8], depth: usize) -> Result<Amf0Value, AppError> {
+    if depth > MAX_DEPTH {
+        return Err(AppError::InvalidData { message: "AMF0 trop imbriqué".to_string() });
+    }
+    let marker = take(data, 1)?[0];
+    Ok(match marker {
+        NUMBER => Amf0Value::Number(f64::from_be_bytes(take_array(data)?)),
+        BOOLEAN => Amf0Value::Boolean(take(data, 1)?[0] != 0),
+        STRING => Amf0Value::String(decode_string(data, false)?),
+        LONG_STRING => Amf0Value::String(decode_string(data, true)?),
+        OBJECT => Amf0Value::Object(decode_properties(data, depth)?),
+        NULL => Amf0Value::Null,
+        UNDEFINED => Amf0Value::Undefined,
+        ECMA_ARRAY => {
+            // Le nombre d'entrées annoncé n'est qu'indicatif : le marqueur de fin fait foi
+            take(data, 4)?;
+            Amf0Value::EcmaArray(decode_properties(data, depth)?)
+        }
+        STRICT_ARRAY => {
+            let count = u32::from_be_bytes(take_array(data)?) as usize;
+            let mut values = Vec::with_capacity(count.min(256));
+            for _ in 0..count {
+                values.push(decode_value(data, depth + 1)?);
+            }
+            Amf0Value::StrictArray(values)
+        }
+        DATE => {
+            let time = f64::from_be_bytes(take_array(data)?);
+            take(data, 2)?;
+            Amf0Value::Date(time)
+        }
+        marker => {
+            return Err(AppError::InvalidData { message: format!("Marqueur AMF0 non géré: {:#04x}", marker) });
+        }
+    })
+}
+
+/// Encode une suite de valeurs
+pub fn encode_all(values: &[Amf0Value]) -> Vec<u8> {
+    let mut output = Vec::new();
+    for value in values {
+        encode_value(value, &mut output);
+    }
+    output
+}
+
+fn encode_string(value: &str, output: &mut Vec<u8>) {
+    output.extend_from_slice(&(value.len().min(u16::MAX as usize) as u16).to_be_bytes());
+    output.extend_from_slice(&value.as_bytes()[..value.len().min(u16::MAX as usize)]);
+}
+
+fn encode_properties(properties: &[(String, Amf0Value)], output: &mut Vec<u8>) {
+    for (key, value) in properties {
+        encode_string(key, output);
+        encode_value(value, output);
+    }
+    output.extend_from_slice(&[0, 0, OBJECT_END]);
+}
+
+fn encode_value(value: &Amf0Value, output: &mut Vec<u8>) {
+    match value {
+        Amf0Value::Number(number) => {
+            output.push(NUMBER);
+            output.extend_from_slice(&number.to_be_bytes());
+        }
+        Amf0Value::Boolean(boolean) => output.extend_from_slice(&[BOOLEAN, u8::from(*boolean)]),
+        Amf0Value::String(string) if string.len() > u16::MAX as usize => {
+            output.push(LONG_STRING);
+            output.extend_from_slice(&(string.len() as u32).to_be_bytes());
+            output.extend_from_slice(string.as_bytes());
+        }
+        Amf0Value::String(string) => {
+            output.push(STRING);
+            encode_string(string, output);
+        }
+        Amf0Value::Object(properties) => {
+            output.push(OBJECT);
+            encode_properties(properties, output);
+        }
+        Amf0Value::Null => output.push(NULL),
+        Amf0Value::Undefined => output.push(UNDEFINED),
+        Amf0Value::EcmaArray(properties) => {
+            output.push(ECMA_ARRAY);
+            output.extend_from_slice(&(properties.len() as u32).to_be_bytes());
+            encode_properties(properties, output);
+        }
+        Amf0Value::StrictArray(values) => {
+            output.push(STRICT_ARRAY);
+            output.extend_from_slice(&(values.len() as u32).to_be_bytes());
+            for value in values {
+                encode_value(value, output);
+            }
+        }
+        Amf0Value::Date(time) => {
+            output.push(DATE);
+            output.extend_from_slice(&time.to_be_bytes());
+            output.extend_from_slice(&[0, 0]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_round_trip() {
+        let command = vec![
+            Amf0Value::String("connect".into()),
+            Amf0Value::Number(1.0),
+            Amf0Value::object(&[
+                ("app", Amf0Value::String("live".into())),
+                ("fpad", Amf0Value::Boolean(false)),
+                ("audioCodecs", Amf0Value::Number(3575.0)),
+            ]),
+            Amf0Value::Null,
+            Amf0Value::EcmaArray(vec![("duration".into(), Amf0Value::Number(0.0))]),
+            Amf0Value::StrictArray(vec![Amf0Value::Undefined, Amf0Value::Date(1.5e12)]),
+        ];
+        let decoded = decode_all(&encode_all(&command)).unwrap();
+        assert_eq!(decoded, command);
+        assert_eq!(decoded[2].property("app").and_then(Amf0Value::as_str), Some("live"));
+        assert!(decode_all(&[STRING, 0, 5, b'a']).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Handshake et découpage en chunks RTMP (Adobe RTMP Specification 1.0, §5)
+
+use std::collections::HashMap;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+use crate::error::AppError;
+
+/// Version du protocole (C0/S0)
+const RTMP_VERSION: u8 = 3;
+/// Taille de C1/S1/C2/S2
+pub const HANDSHAKE_SIZE: usize = 1536;
+/// Taille de chunk par défaut, jusqu'au premier Set Chunk Size
+pub const DEFAULT_CHUNK_SIZE: usize = 128;
+/// Taille de chunk maximale acceptée du client
+const MAX_CHUNK_SIZE: usize = 1 << 24;
+/// Chunk streams ouverts par connexion (les encodeurs en utilisent moins de dix)
+const MAX_CHUNK_STREAMS: usize = 64;
+/// Octets de messages partiels en attente, tous chunk streams confondus
+const MAX_BUFFERED_BYTES: usize = 1 << 24;
+/// Timestamp au-delà duquel le champ étendu est utilisé
+const EXTENDED_TIMESTAMP: u32 = 0x00FF_FFFF;
+
+/// Types de messages de contrôle et de données utilisés par le serveur
+pub const SET_CHUNK_SIZE: u8 = 1;
+pub const ABORT: u8 = 2;
+pub const ACKNOWLEDGEMENT: u8 = 3;
+pub const USER_CONTROL: u8 = 4;
+pub const WINDOW_ACK_SIZE: u8 = 5;
+pub const SET_PEER_BANDWIDTH: u8 = 6;
+pub const AUDIO: u8 = 8;
+pub const VIDEO: u8 = 9;
+pub const DATA_AMF3: u8 = 15;
+pub const COMMAND_AMF3: u8 = 17;
+pub const DATA_AMF0: u8 = 18;
+pub const COMMAND_AMF0: u8 = 20;
+
+/// Message RTMP réassemblé
+#[derive(Debug, Clone)]
+pub struct RtmpMessage {
+    pub type_id: u8,
+    pub stream_id: u32,
+    pub timestamp: u32,
+    pub payload: Vec<u8>,
+}
+
+/// Handshake simple côté serveur : S1 sans signature numérique, que les
+/// encodeurs (OBS, ffmpeg, encodeurs matériels) acceptent
+pub async fn server_handshake<S>(stream: &mut S) -> Result<(), AppError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut c0c1 = vec![0u8; 1 + HANDSHAKE_SIZE];
+    stream.read_exact(&mut c0c1).await.map_err(io_error)?;
+    if c0c1[0] != RTMP_VERSION {
+        return Err(AppError::InvalidData { message: format!("Version RTMP non gérée: {}", c0c1[0]) });
+    }
+
+    let mut response = Vec::with_capacity(1 + 2 * HANDSHAKE_SIZE);
+    response.push(RTMP_VERSION);
+    // S1 : temps, zéros, puis octets arbitraires
+    response.extend_from_slice(&[0; 8]);
+    response.extend((0..HANDSHAKE_SIZE - 8).map(|i| (i * 31 % 251) as u8));
+    // S2 : écho de C1
+    response.extend_from_slice(&c0c1[1..]);
+    stream.write_all(&response).await.map_err(io_error)?;
+
+    let mut c2 = vec![0u8; HANDSHAKE_SIZE];
+    stream.read_exact(&mut c2).await.map_err(io_error)?;
+    Ok(())
+}
+
+/// État d'un chunk stream entrant
+#[derive(Debug, Default)]
+struct ChunkStream {
+    timestamp: u32,
+    delta: u32,
+    length: usize,
+    type_id: u8,
+    stream_id: u32,
+    extended: bool,
+    payload: Vec<u8>,
+}
+
+/// Réassemble les messages à partir des chunks reçus
+#[derive(Debug)]
+pub struct ChunkReader {
+    chunk_size: usize,
+    streams: HashMap<u32, ChunkStream>,
+    /// Octets lus depuis le début de la connexion, pour les acquittements
+    bytes_read: u64,
+}
+
+impl Default for ChunkReader {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            streams: HashMap::new(),
+            bytes_read: 0,
+        }
+    }
+}
+
+impl ChunkReader {
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    pub fn set_chunk_size(&mut self, size: usize) -> Result<(), AppError> {
+        if size == 0 || size > MAX_CHUNK_SIZE {
+            return Err(AppError::InvalidData { message: format!("Taille de chunk invalide: {}", size) });
+        }
+        self.chunk_size = size;
+        Ok(())
+    }
+
+    /// Abandonne le message en cours sur un chunk stream (message Abort)
+    pub fn abort(&mut self, chunk_stream_id: u32) {
+        if let Some(stream) = self.streams.get_mut(&chunk_stream_id) {
+            stream.payload = Vec::new();
+        }
+    }
+
+    async fn read_bytes<R: AsyncRead + Unpin>(&mut self, reader: &mut R, buffer: &mut [u8]) -> Result<(), AppError> {
+        reader.read_exact(buffer).await.map_err(io_error)?;
+        self.bytes_read += buffer.len() as u64;
+        Ok(())
+    }
+
+    async fn read_u24<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> Result<u32, AppError> {
+        let mut bytes = [0u8; 3];
+        self.read_bytes(reader, &mut bytes).await?;
+        Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+    }
+
+    /// Lit des chunks jusqu'à obtenir un message complet
+    pub async fn read_message<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> Result<RtmpMessage, AppError> {
+        loop {
+            if let Some(message) = self.read_chunk(reader).await? {
+                return Ok(message);
+            }
+        }
+    }
+
+    async fn read_chunk<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> Result<Option<RtmpMessage>, AppError> {
+        // Basic header : fmt sur 2 bits, identifiant sur 6, 14 ou 22 bits
+        let mut first = [0u8; 1];
+        self.read_bytes(reader, &mut first).await?;
+        let format = first[0] >> 6;
+        let chunk_stream_id = match u32::from(first[0] & 0x3F) {
+            0 => {
+                let mut byte = [0u8; 1];
+                self.read_bytes(reader, &mut byte).await?;
+                64 + u32::from(byte[0])
+            }
+            1 => {
+                let mut bytes = [0u8; 2];
+                self.read_bytes(reader, &mut bytes).await?;
+                64 + u32::from(bytes[0]) + 256 * u32::from(bytes[1])
+            }
+            id => id,
+        };
+
+        if !self.streams.contains_key(&chunk_stream_id) && self.streams.len() >= MAX_CHUNK_STREAMS {
+            return Err(AppError::InvalidData { message: format!("Trop de chunk streams RTMP: {}", MAX_CHUNK_STREAMS) });
+        }
+        let mut stream = self.streams.remove(&chunk_stream_id).unwrap_or_default();
+        let mut timestamp_field = None;
+        if format <= 2 {
+            timestamp_field = Some(self.read_u24(reader).await?);
+        }
+        if format <= 1 {
+            stream.length = self.read_u24(reader).await? as usize;
+            let mut type_id = [0u8; 1];
+            self.read_bytes(reader, &mut type_id).await?;
+            stream.type_id = type_id[0];
+            // Un header complet au milieu d'un message en commence un nouveau
+            if !stream.payload.is_empty() {
+                debug!("Chunk stream {} : message partiel abandonné ({} octets)", chunk_stream_id, stream.payload.len());
+                stream.payload = Vec::new();
+            }
+        }
+        let starting = stream.payload.is_empty();
+        if format == 0 {
+            let mut stream_id = [0u8; 4];
+            self.read_bytes(reader, &mut stream_id).await?;
+            stream.stream_id = u32::from_le_bytes(stream_id);
+        }
+
+        // Timestamp étendu : présent si le champ vaut 0xFFFFFF, y compris sur
+        // les chunks de type 3 qui suivent un tel header
+        let extended = match timestamp_field {
+            Some(field) => field == EXTENDED_TIMESTAMP,
+            None => stream.extended,
+        };
+        let mut timestamp = timestamp_field.unwrap_or(stream.delta);
+        if extended {
+            let mut bytes = [0u8; 4];
+            self.read_bytes(reader, &mut bytes).await?;
+            timestamp = u32::from_be_bytes(bytes);
+        }
+        stream.extended = extended;
+
+        if starting {
+            match format {
+                0 => {
+                    stream.timestamp = timestamp;
+                    stream.delta = 0;
+                }
+                1 | 2 => {
+                    stream.delta = timestamp;
+                    stream.timestamp = stream.timestamp.wrapping_add(timestamp);
+                }
+                _ => stream.timestamp = stream.timestamp.wrapping_add(stream.delta),
+            }
+        }
+
+        if stream.length > MAX_CHUNK_SIZE {
+            return Err(AppError::InvalidData { message: format!("Message RTMP trop long: {}", stream.length) });
+        }
+        let remaining = stream.length - stream.payload.len();
+        let start = stream.payload.len();
+        let chunk_length = remaining.min(self.chunk_size);
+        let buffered: usize = self.streams.values().map(|other| other.payload.len()).sum();
+        if buffered + start + chunk_length > MAX_BUFFERED_BYTES {
+            return Err(AppError::InvalidData { message: format!("Messages RTMP partiels trop volumineux: {} octets", buffered + start + chunk_length) });
+        }
+        stream.payload.resize(start + chunk_length, 0);
+        let mut payload = std::mem::take(&mut stream.payload);
+        let read = self.read_bytes(reader, &mut payload[start..]).await;
+        stream.payload = payload;
+        read?;
+
+        let message = (stream.payload.len() == stream.length).then(|| RtmpMessage {
+            type_id: stream.type_id,
+            stream_id: stream.stream_id,
+            timestamp: stream.timestamp,
+            payload: std::mem::take(&mut stream.payload),
+        });
+        self.streams.insert(chunk_stream_id, stream);
+        Ok(message)
+    }
+}
+
+/// Découpe les messages sortants en chunks de type 0 puis 3
+#[derive(Debug)]
+pub struct ChunkWriter {
+    chunk_size: usize,
+}
+
+impl Default for ChunkWriter {
+    fn default() -> Self {
+        Self { chunk_size: DEFAULT_CHUNK_SIZE }
+    }
+}
+
+impl ChunkWriter {
+    /// À appeler après l'envoi d'un Set Chunk Size
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.chunk_size = size.max(1);
+    }
+
+    pub fn encode(&self, chunk_stream_id: u8, message: &RtmpMessage) -> Vec<u8> {
+        let chunk_stream_id = chunk_stream_id & 0x3F;
+        let mut output = Vec::with_capacity(message.payload.len() + 16);
+        let length = message.payload.len() as u32;
+        let extended = message.timestamp >= EXTENDED_TIMESTAMP;
+
+        output.push(chunk_stream_id);
+        output.extend_from_slice(&message.timestamp.min(EXTENDED_TIMESTAMP).to_be_bytes()[1..]);
+        output.extend_from_slice(&length.to_be_bytes()[1..]);
+        output.push(message.type_id);
+        output.extend_from_slice(&message.stream_id.to_le_bytes());
+        if extended {
+            output.extend_from_slice(&message.timestamp.to_be_bytes());
+        }
+
+        for (index, chunk) in message.payload.chunks(self.chunk_size).enumerate() {
+            if index > 0 {
+                output.push(0xC0 | chunk_stream_id);
+                if extended {
+                    output.extend_from_slice(&message.timestamp.to_be_bytes());
+                }
+            }
+            output.extend_from_slice(chunk);
+        }
+        output
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin>(&self, writer: &mut W, chunk_stream_id: u8, message: &RtmpMessage) -> Result<(), AppError> {
+        writer.write_all(&self.encode(chunk_stream_id, message)).await.map_err(io_error)
+    }
+}
+
+pub(crate) fn io_error(e: std::io::Error) -> AppError {
+    AppError::StreamingError { message: format!("Connexion RTMP: {}", e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reassembles_interleaved_chunks() {
+        let writer = ChunkWriter::default();
+        let audio = RtmpMessage { type_id: AUDIO, stream_id: 1, timestamp: 0x0100_0000, payload: (0..300u32).map(|i| i as u8).collect() };
+        let command = RtmpMessage { type_id: COMMAND_AMF0, stream_id: 0, timestamp: 0, payload: vec![7; 10] };
+
+        // Le message audio (3 chunks) est interrompu par une commande sur un autre chunk stream
+        let audio_chunks = writer.encode(4, &audio);
+        let first_chunk = 1 + 11 + 4 + DEFAULT_CHUNK_SIZE;
+        let mut data = audio_chunks[..first_chunk].to_vec();
+        data.extend(writer.encode(3, &command));
+        data.extend_from_slice(&audio_chunks[first_chunk..]);
+        // Message suivant en type 2 : seul le delta de timestamp change
+        data.extend_from_slice(&[0x84, 0, 0, 20]);
+        data.extend_from_slice(&audio.payload[..DEFAULT_CHUNK_SIZE]);
+        data.push(0xC4);
+        data.extend_from_slice(&audio.payload[DEFAULT_CHUNK_SIZE..2 * DEFAULT_CHUNK_SIZE]);
+        data.push(0xC4);
+        data.extend_from_slice(&audio.payload[2 * DEFAULT_CHUNK_SIZE..]);
+
+        let mut reader = ChunkReader::default();
+        let mut input = data.as_slice();
+        let first = reader.read_message(&mut input).await.unwrap();
+        assert_eq!((first.type_id, first.payload.len()), (COMMAND_AMF0, 10));
+        let second = reader.read_message(&mut input).await.unwrap();
+        assert_eq!((second.type_id, second.timestamp), (AUDIO, 0x0100_0000));
+        assert_eq!(second.payload, audio.payload);
+        let third = reader.read_message(&mut input).await.unwrap();
+        assert_eq!((third.stream_id, third.timestamp), (1, 0x0100_0014));
+        assert_eq!(third.payload, audio.payload);
+        assert_eq!(reader.bytes_read(), data.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_new_header_mid_message_restarts_message() {
+        let writer = ChunkWriter::default();
+        let long = RtmpMessage { type_id: AUDIO, stream_id: 1, timestamp: 0, payload: vec![1; 300] };
+        let short = RtmpMessage { type_id: AUDIO, stream_id: 1, timestamp: 40, payload: vec![2; 50] };
+
+        // Premier chunk du long message, puis un header de type 0 plus court sur le même chunk stream
+        let mut data = writer.encode(4, &long)[..1 + 11 + DEFAULT_CHUNK_SIZE].to_vec();
+        data.extend(writer.encode(4, &short));
+
+        let mut reader = ChunkReader::default();
+        let mut input = data.as_slice();
+        let message = reader.read_message(&mut input).await.unwrap();
+        assert_eq!((message.timestamp, message.payload), (40, vec![2; 50]));
+    }
+
+    #[tokio::test]
+    async fn test_limits_open_chunk_streams() {
+        // Un premier chunk de 300 octets par chunk stream (identifiants 64 et plus), sans jamais les terminer
+        let mut data = Vec::new();
+        for index in 0..=MAX_CHUNK_STREAMS as u8 {
+            data.extend_from_slice(&[0x00, index, 0, 0, 0, 0, 1, 44, AUDIO, 1, 0, 0, 0]);
+            data.extend_from_slice(&[0; DEFAULT_CHUNK_SIZE]);
+        }
+
+        let mut reader = ChunkReader::default();
+        let mut input = data.as_slice();
+        let error = reader.read_message(&mut input).await.unwrap_err();
+        assert!(matches!(error, AppError::InvalidData { .. }), "{:?}", error);
+        assert_eq!(reader.streams.len(), MAX_CHUNK_STREAMS);
+    }
+}
@@ -0,0 +1,500 @@
+//! Serveur RTMP pour les sources live (`StreamProtocol::RTMP`)
+//!
+//! Les encodeurs matériels, OBS ou ffmpeg publient sur
+//! `rtmp://<hôte>/<app>` avec la clé de stream comme nom de publication. Seul
+//! l'audio est gardé : les tags FLV MP3 sont transmis tels quels au décodeur,
+//! les frames AAC brutes sont réencapsulées en ADTS à partir de la séquence
+//! d'en-tête. Les deux alimentent le même `SourcePipeline` que les sources
+//! Icecast ; la vidéo éventuelle est ignorée.
+
+pub mod amf;
+pub mod chunk;
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use tokio::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tracing::{debug, info, warn};
+
+use crate::codecs::aac::{adts_header, parse_audio_specific_config, AudioSpecificConfig};
+use crate::error::AppError;
+use crate::streaming::ingest::{key_mount, LiveIngest, LiveSource, SourcePipeline};
+use amf::Amf0Value;
+use chunk::{ChunkReader, ChunkWriter, RtmpMessage};
+
+/// Délai pour terminer le handshake et publier
+const SETUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Taille des chunks envoyés au client
+const SERVER_CHUNK_SIZE: usize = 4096;
+/// Fenêtre d'acquittement annoncée au client
+const WINDOW_ACK_SIZE: u32 = 2_500_000;
+/// Chunk streams des messages sortants
+const CONTROL_CHUNK_STREAM: u8 = 2;
+const COMMAND_CHUNK_STREAM: u8 = 3;
+/// Identifiant du stream de messages renvoyé par `createStream`
+const PUBLISH_STREAM_ID: u32 = 1;
+
+/// Formats audio FLV (E.4.2.1)
+const FLV_MP3: u8 = 2;
+const FLV_AAC: u8 = 10;
+const FLV_MP3_8KHZ: u8 = 14;
+const AAC_SEQUENCE_HEADER: u8 = 0;
+
+/// Accepte les connexions RTMP jusqu'à l'arrêt du serveur
+pub async fn serve(listener: TcpListener, ingest: Arc<LiveIngest>) {
+    loop {
+        let (socket, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                warn!("Connexion RTMP refusée: {}", e);
+                continue;
+            }
+        };
+        let ingest = ingest.clone();
+        tokio::spawn(async move {
+            if let Err(e) = RtmpSession::new(socket, peer, ingest).run().await {
+                debug!("Connexion RTMP {} fermée: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Audio reçu avant ou après le démarrage du décodeur
+#[derive(Debug)]
+enum AudioState {
+    /// Publication acceptée, codec pas encore connu
+    Waiting(Box<LiveSource>),
+    Mp3(SourcePipeline),
+    Aac {
+        config: Option<AudioSpecificConfig>,
+        pipeline: SourcePipeline,
+    },
+    Closed,
+}
+
+struct RtmpSession {
+    socket: BufReader<TcpStream>,
+    peer: SocketAddr,
+    ingest: Arc<LiveIngest>,
+    reader: ChunkReader,
+    writer: ChunkWriter,
+    app: String,
+    /// Fenêtre d'acquittement demandée par le client
+    peer_window: Option<u32>,
+    last_ack: u64,
+    audio: AudioState,
+}
+
+impl RtmpSession {
+    fn new(socket: TcpStream, peer: SocketAddr, ingest: Arc<LiveIngest>) -> Self {
+        let _ = socket.set_nodelay(true);
+        Self {
+            socket: BufReader::new(socket),
+            peer,
+            ingest,
+            reader: ChunkReader::default(),
+            writer: ChunkWriter::default(),
+            app: String::new(),
+            peer_window: None,
+            last_ack: 0,
+            audio: AudioState::Closed,
+        }
+    }
+
+    async fn run(mut self) -> Result<(), AppError> {
+        match timeout(SETUP_TIMEOUT, chunk::server_handshake(&mut self.socket)).await {
+            Ok(result) => result?,
+            Err(_) => return Err(AppError::ConnectionTimeout),
+        }
+
+        let idle_timeout = self.ingest.config().idle_timeout;
+        let result = loop {
+            // Avant la publication, le délai est celui de la mise en place
+            let limit = match self.audio {
+                AudioState::Closed => SETUP_TIMEOUT,
+                _ => idle_timeout,
+            };
+            let message = match timeout(limit, self.reader.read_message(&mut self.socket)).await {
+                Ok(Ok(message)) => message,
+                Ok(Err(e)) => break Err(e),
+                Err(_) => {
+                    info!("Connexion RTMP {} inactive depuis {:?}, déconnexion", self.peer, limit);
+                    break Ok(());
+                }
+            };
+            self.acknowledge().await?;
+            match self.handle_message(message).await {
+                Ok(true) => {}
+                Ok(false) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+
+        let finished = self.close().await;
+        result.and(finished)
+    }
+
+    /// Envoie un acquittement quand la fenêtre du client est atteinte
+    async fn acknowledge(&mut self) -> Result<(), AppError> {
+        let Some(window) = self.peer_window else {
+            return Ok(());
+        };
+        let received = self.reader.bytes_read();
+        if received - self.last_ack >= u64::from(window) {
+            self.last_ack = received;
+            self.send_control(chunk::ACKNOWLEDGEMENT, (received as u32).to_be_bytes().to_vec()).await?;
+        }
+        Ok(())
+    }
+
+    /// Traite un message ; `false` termine la session
+    async fn handle_message(&mut self, message: RtmpMessage) -> Result<bool, AppError> {
+        match message.type_id {
+            chunk::SET_CHUNK_SIZE => {
+                let size = read_u32(&message.payload)? & 0x7FFF_FFFF;
+                self.reader.set_chunk_size(size as usize)?;
+            }
+            chunk::ABORT => self.reader.abort(read_u32(&message.payload)?),
+            chunk::WINDOW_ACK_SIZE => self.peer_window = Some(read_u32(&message.payload)?.max(1)),
+            chunk::AUDIO => return self.handle_audio(&message.payload).await,
+            chunk::COMMAND_AMF0 => return self.handle_command(&message.payload).await,
+            // Commande AMF3 : un octet de format puis de l'AMF0
+            chunk::COMMAND_AMF3 if !message.payload.is_empty() => return self.handle_command(&message.payload[1..]).await,
+            chunk::DATA_AMF0 | chunk::DATA_AMF3 | chunk::VIDEO | chunk::ACKNOWLEDGEMENT | chunk::USER_CONTROL | chunk::SET_PEER_BANDWIDTH => {}
+            type_id => debug!("Message RTMP {} ignoré", type_id),
+        }
+        Ok(true)
+    }
+
+    async fn handle_command(&mut self, payload: &[u8]) -> Result<bool, AppError> {
+        let values = amf::decode_all(payload)?;
+        let name = values.first().and_then(Amf0Value::as_str).unwrap_or_default().to_string();
+        let transaction = values.get(1).and_then(Amf0Value::as_number).unwrap_or(0.0);
+        debug!("Commande RTMP {} depuis {}", name, self.peer);
+
+        match name.as_str() {
+            "connect" => {
+                self.app = values
+                    .get(2)
+                    .and_then(|object| object.property("app"))
+                    .and_then(Amf0Value::as_str)
+                    .unwrap_or("live")
+                    .to_string();
+                self.send_control(chunk::WINDOW_ACK_SIZE, WINDOW_ACK_SIZE.to_be_bytes().to_vec()).await?;
+                let mut bandwidth = WINDOW_ACK_SIZE.to_be_bytes().to_vec();
+                bandwidth.push(2);
+                self.send_control(chunk::SET_PEER_BANDWIDTH, bandwidth).await?;
+                self.send_control(chunk::SET_CHUNK_SIZE, (SERVER_CHUNK_SIZE as u32).to_be_bytes().to_vec()).await?;
+                self.writer.set_chunk_size(SERVER_CHUNK_SIZE);
+                self.send_command(0, &[
+                    Amf0Value::String("_result".into()),
+                    Amf0Value::Number(transaction),
+                    Amf0Value::object(&[
+                        ("fmsVer", Amf0Value::String("FMS/3,0,1,123".into())),
+                        ("capabilities", Amf0Value::Number(31.0)),
+                    ]),
+                    status("status", "NetConnection.Connect.Success", "Connection succeeded."),
+                ]).await?;
+            }
+            "releaseStream" | "FCPublish" => {
+                self.send_command(0, &[
+                    Amf0Value::String("_result".into()),
+                    Amf0Value::Number(transaction),
+                    Amf0Value::Null,
+                    Amf0Value::Undefined,
+                ]).await?;
+            }
+            "createStream" => {
+                self.send_command(0, &[
+                    Amf0Value::String("_result".into()),
+                    Amf0Value::Number(transaction),
+                    Amf0Value::Null,
+                    Amf0Value::Number(f64::from(PUBLISH_STREAM_ID)),
+                ]).await?;
+            }
+            "publish" => {
+                let name = values.get(3).and_then(Amf0Value::as_str).unwrap_or_default();
+                // Certains encodeurs ajoutent des paramètres après la clé
+                let key = name.split('?').next().unwrap_or_default().to_string();
+                return self.publish(&key).await;
+            }
+            "FCUnpublish" | "deleteStream" | "closeStream" => return Ok(false),
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    async fn publish(&mut self, key: &str) -> Result<bool, AppError> {
+        if !matches!(self.audio, AudioState::Closed) {
+            return Ok(true);
+        }
+        let mount = key_mount(&self.app, key);
+        let source = match self.ingest.connect(key, "rtmp", &mount) {
+            Ok(source) => source,
+            Err(e) => {
+                let (code, description) = match e {
+                    AppError::AlreadyRunning => ("NetStream.Publish.BadName", "Stream already publishing"),
+                    _ => ("NetStream.Publish.Unauthorized", "Invalid stream key"),
+                };
+                warn!("Publication RTMP refusée pour {}: {}", self.peer, description);
+                self.send_status("error", code, description).await?;
+                return Ok(false);
+            }
+        };
+
+        // User Control Stream Begin sur le stream de publication
+        let mut stream_begin = vec![0, 0];
+        stream_begin.extend_from_slice(&PUBLISH_STREAM_ID.to_be_bytes());
+        self.send_control(chunk::USER_CONTROL, stream_begin).await?;
+        self.send_status("status", "NetStream.Publish.Start", "Publishing started").await?;
+        info!("Publication RTMP de {} sur {}", self.peer, mount);
+        self.audio = AudioState::Waiting(Box::new(source));
+        Ok(true)
+    }
+
+    async fn handle_audio(&mut self, payload: &[u8]) -> Result<bool, AppError> {
+        let Some(&header) = payload.first() else {
+            return Ok(true);
+        };
+        let format = header >> 4;
+
+        if let AudioState::Waiting(_) = self.audio {
+            let extension = match format {
+                FLV_MP3 | FLV_MP3_8KHZ => "mp3",
+                FLV_AAC => "aac",
+                format => {
+                    warn!("Format audio FLV {} non géré (source {})", format, self.peer);
+                    return Err(AppError::UnsupportedCodec { codec: format!("flv:{}", format) });
+                }
+            };
+            let AudioState::Waiting(source) = std::mem::replace(&mut self.audio, AudioState::Closed) else {
+                return Ok(true);
+            };
+            let pipeline = SourcePipeline::start(*source, extension);
+            self.audio = match extension {
+                "aac" => AudioState::Aac { config: None, pipeline },
+                _ => AudioState::Mp3(pipeline),
+            };
+        }
+
+        let sent = match (&mut self.audio, format) {
+            (AudioState::Mp3(pipeline), FLV_MP3 | FLV_MP3_8KHZ) => pipeline.send(payload[1..].to_vec()).await,
+            (AudioState::Aac { config, pipeline }, FLV_AAC) if payload.len() > 2 => {
+                let data = &payload[2..];
+                if payload[1] == AAC_SEQUENCE_HEADER {
+                    *config = parse_audio_specific_config(data);
+                    if config.is_none() {
+                        return Err(AppError::UnsupportedCodec { codec: "aac (AudioSpecificConfig)".to_string() });
+                    }
+                    true
+                } else {
+                    match config.as_ref().and_then(|config| adts_header(config, data.len())) {
+                        Some(header) => {
+                            let mut frame = Vec::with_capacity(header.len() + data.len());
+                            frame.extend_from_slice(&header);
+                            frame.extend_from_slice(data);
+                            pipeline.send(frame).await
+                        }
+                        // Frames reçues avant la séquence d'en-tête
+                        None => true,
+                    }
+                }
+            }
+            (AudioState::Closed, _) => true,
+            _ => {
+                return Err(AppError::StreamingError { message: "Changement de codec audio en cours de publication".to_string() });
+            }
+        };
+        Ok(sent)
+    }
+
+    /// Ferme la publication et attend le décodage de l'audio reçu
+    async fn close(&mut self) -> Result<(), AppError> {
+        match std::mem::replace(&mut self.audio, AudioState::Closed) {
+            AudioState::Mp3(pipeline) | AudioState::Aac { pipeline, .. } => {
+                info!("Publication RTMP de {} terminée", self.peer);
+                pipeline.finish().await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn send_control(&mut self, type_id: u8, payload: Vec<u8>) -> Result<(), AppError> {
+        let message = RtmpMessage { type_id, stream_id: 0, timestamp: 0, payload };
+        self.writer.write(self.socket.get_mut(), CONTROL_CHUNK_STREAM, &message).await
+    }
+
+    async fn send_command(&mut self, stream_id: u32, values: &[Amf0Value]) -> Result<(), AppError> {
+        let message = RtmpMessage { type_id: chunk::COMMAND_AMF0, stream_id, timestamp: 0, payload: amf::encode_all(values) };
+        self.writer.write(self.socket.get_mut(), COMMAND_CHUNK_STREAM, &message).await
+    }
+
+    async fn send_status(&mut self, level: &str, code: &str, description: &str) -> Result<(), AppError> {
+        self.send_command(PUBLISH_STREAM_ID, &[
+            Amf0Value::String("onStatus".into()),
+            Amf0Value::Number(0.0),
+            Amf0Value::Null,
+            status(level, code, description),
+        ]).await
+    }
+}
+
+fn status(level: &str, code: &str, description: &str) -> Amf0Value {
+    Amf0Value::object(&[
+        ("level", Amf0Value::String(level.into())),
+        ("code", Amf0Value::String(code.into())),
+        ("description", Amf0Value::String(description.into())),
+        ("objectEncoding", Amf0Value::Number(0.0)),
+    ])
+}
+
+fn read_u32(payload: &[u8]) -> Result<u32, AppError> {
+    payload
+        .get(..4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| AppError::InvalidData { message: "Message de contrôle RTMP tronqué".to_string() })
+}
+
+// Les tests publient de l'AAC encodé par libfdk-aac
+#[cfg(all(test, feature = "fdk-aac"))]
+mod tests {
+    use super::*;
+    use crate::codecs::aac::split_adts_frames;
+    use crate::codecs::{CodecQuality, EncoderConfig, LatencyMode};
+    use crate::core::{StreamConfig, StreamManager};
+    use crate::streaming::ingest::LiveIngestConfig;
+    use crate::streaming::segmenter::create_segment_encoder;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn tone_adts(seconds: usize) -> Vec<u8> {
+        let (mut encoder, _) = create_segment_encoder("aac", EncoderConfig {
+            bitrate: 128_000,
+            sample_rate: 44100,
+            channels: 2,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::Normal,
+            enable_vbr: false,
+            complexity: 5,
+        }).unwrap();
+        let samples: Vec<f32> = (0..44100 * seconds)
+            .flat_map(|i| {
+                let sample = (i as f32 * 440.0 * std::f32::consts::TAU / 44100.0).sin() * 0.5;
+                [sample, sample]
+            })
+            .collect();
+        let mut adts = encoder.encode(&samples, 44100, 2).unwrap();
+        adts.extend(encoder.finalize().unwrap());
+        adts
+    }
+
+    async fn command(writer: &ChunkWriter, socket: &mut TcpStream, stream_id: u32, values: &[Amf0Value]) {
+        let message = RtmpMessage { type_id: chunk::COMMAND_AMF0, stream_id, timestamp: 0, payload: amf::encode_all(values) };
+        socket.write_all(&writer.encode(3, &message)).await.unwrap();
+    }
+
+    /// Lit les messages du serveur jusqu'à la commande attendue
+    async fn expect_command(reader: &mut ChunkReader, socket: &mut TcpStream, name: &str) -> Vec<Amf0Value> {
+        loop {
+            let message = reader.read_message(socket).await.unwrap();
+            if message.type_id == chunk::SET_CHUNK_SIZE {
+                reader.set_chunk_size(read_u32(&message.payload).unwrap() as usize).unwrap();
+            }
+            if message.type_id == chunk::COMMAND_AMF0 {
+                let values = amf::decode_all(&message.payload).unwrap();
+                if values[0].as_str() == Some(name) {
+                    return values;
+                }
+            }
+        }
+    }
+
+    async fn connect_and_publish(address: SocketAddr, key: &str) -> (TcpStream, ChunkReader, Vec<Amf0Value>) {
+        let mut socket = TcpStream::connect(address).await.unwrap();
+        let mut c0c1 = vec![3u8];
+        c0c1.extend(vec![1u8; chunk::HANDSHAKE_SIZE]);
+        socket.write_all(&c0c1).await.unwrap();
+        let mut s0s1s2 = vec![0u8; 1 + 2 * chunk::HANDSHAKE_SIZE];
+        socket.read_exact(&mut s0s1s2).await.unwrap();
+        assert_eq!(&s0s1s2[1 + chunk::HANDSHAKE_SIZE..], &c0c1[1..]);
+        socket.write_all(&s0s1s2[1..1 + chunk::HANDSHAKE_SIZE]).await.unwrap();
+
+        let writer = ChunkWriter::default();
+        let mut reader = ChunkReader::default();
+        command(&writer, &mut socket, 0, &[
+            Amf0Value::String("connect".into()),
+            Amf0Value::Number(1.0),
+            Amf0Value::object(&[("app", Amf0Value::String("live".into())), ("tcUrl", Amf0Value::String("rtmp://localhost/live".into()))]),
+        ]).await;
+        let result = expect_command(&mut reader, &mut socket, "_result").await;
+        assert_eq!(result[3].property("code").and_then(Amf0Value::as_str), Some("NetConnection.Connect.Success"));
+
+        command(&writer, &mut socket, 0, &[Amf0Value::String("createStream".into()), Amf0Value::Number(2.0), Amf0Value::Null]).await;
+        let result = expect_command(&mut reader, &mut socket, "_result").await;
+        assert_eq!(result[3], Amf0Value::Number(1.0));
+
+        command(&writer, &mut socket, 1, &[
+            Amf0Value::String("publish".into()),
+            Amf0Value::Number(3.0),
+            Amf0Value::Null,
+            Amf0Value::String(key.to_string()),
+            Amf0Value::String("live".into()),
+        ]).await;
+        let status = expect_command(&mut reader, &mut socket, "onStatus").await;
+        (socket, reader, status)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_publish_aac_creates_live_stream() {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+        let key = ingest.issue_key(11, "Hardware set".to_string(), None).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, ingest.clone()));
+
+        let (_, _, refused) = connect_and_publish(address, "not-a-key").await;
+        assert_eq!(refused[3].property("code").and_then(Amf0Value::as_str), Some("NetStream.Publish.Unauthorized"));
+
+        let (mut socket, _, status) = connect_and_publish(address, &format!("{}?bitrate=128", key.key)).await;
+        assert_eq!(status[3].property("code").and_then(Amf0Value::as_str), Some("NetStream.Publish.Start"));
+
+        let writer = ChunkWriter::default();
+        let adts = tone_adts(2);
+        let mut tags = vec![vec![0xAF, 0x00, 0x12, 0x10]];
+        tags.extend(split_adts_frames(&adts).into_iter().map(|(info, frame)| {
+            let mut tag = vec![0xAF, 0x01];
+            tag.extend_from_slice(&frame[info.header_length..]);
+            tag
+        }));
+        for (index, payload) in tags.into_iter().enumerate() {
+            let message = RtmpMessage { type_id: chunk::AUDIO, stream_id: 1, timestamp: index as u32 * 23, payload };
+            socket.write_all(&writer.encode(4, &message)).await.unwrap();
+        }
+
+        let mount = key_mount("live", &key.key);
+        assert!(!mount.contains(&key.key));
+        let mut stream_id = None;
+        for _ in 0..100 {
+            stream_id = ingest.stream_for_mount(&mount);
+            if stream_id.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let stream_id = stream_id.expect("stream créé par la publication RTMP");
+        let format = streams.live_format(stream_id).unwrap();
+        assert_eq!((format.sample_rate, format.channels), (44100, 2));
+
+        command(&writer, &mut socket, 1, &[Amf0Value::String("deleteStream".into()), Amf0Value::Number(4.0), Amf0Value::Null]).await;
+        for _ in 0..100 {
+            if streams.live_format(stream_id).is_none() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(streams.live_format(stream_id).is_none());
+    }
+}
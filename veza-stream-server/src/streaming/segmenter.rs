@@ -0,0 +1,866 @@
+//! Segmenteur HLS pour la VOD
+//!
+//! Décode une piste avec `SymphoniaDecoder` par morceaux, la ré-encode pour un
+//! `AdaptiveProfile` et écrit au fil de l'eau des segments CMAF (fMP4) de
+//! durée fixe, alignés sur les frames du codec, dans un répertoire de cache :
+//! la mémoire utilisée ne dépend pas de la durée de la piste. L'index de la
+//! rendition est écrit en dernier et sert de marqueur de complétude.
+//!
+//! Les noms des fichiers portent la version de la source (taille et date de
+//! modification) : un même URI désigne toujours les mêmes octets, ce qui
+//! permet de servir les segments comme immuables.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::codecs::{
+    aac, mp3, AudioEncoder, CodecFactory, CodecQuality, EncoderConfig, LatencyMode, SymphoniaDecoder,
+};
+use crate::audio::loudness::{self, LoudnessNormalization};
+use crate::error::AppError;
+use crate::streaming::adaptive::AdaptiveProfile;
+use crate::streaming::fmp4::{self, Fmp4AudioCodec, Fmp4Sample, Fmp4Track};
+
+/// Nom du fichier d'index d'une rendition segmentée
+const INDEX_FILE: &str = "index.json";
+/// Nom du segment d'initialisation des flux live (non versionné)
+pub const INIT_SEGMENT: &str = "init.mp4";
+/// Nombre de frames envoyées à l'encodeur par appel
+const ENCODE_CHUNK_FRAMES: usize = 1152 * 16;
+
+/// Configuration du segmenteur
+#[derive(Debug, Clone)]
+pub struct SegmenterConfig {
+    /// Répertoire racine des renditions (`<cache_dir>/<track_id>/<quality_id>/`)
+    pub cache_dir: PathBuf,
+    /// Durée cible d'un segment
+    pub segment_duration: Duration,
+    /// Normalisation appliquée avant encodage (gain piste mesuré au décodage)
+    pub normalization: Option<LoudnessNormalization>,
+}
+
+impl Default for SegmenterConfig {
+    fn default() -> Self {
+        Self {
+            cache_dir: PathBuf::from("./cache/hls"),
+            segment_duration: Duration::from_secs(6),
+            normalization: None,
+        }
+    }
+}
+
+/// Un segment média écrit sur disque
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentInfo {
+    pub uri: String,
+    /// Durée exacte en secondes, calculée à partir des frames
+    pub duration: f64,
+    pub byte_size: u64,
+}
+
+/// Rendition d'une piste pour un profil de qualité
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentedRendition {
+    pub track_id: String,
+    pub quality_id: String,
+    pub codecs: String,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub bitrate_kbps: u32,
+    pub init_uri: String,
+    pub segments: Vec<SegmentInfo>,
+    pub created_at: SystemTime,
+    /// Version de la source segmentée (`source_version`)
+    #[serde(default)]
+    pub source_version: String,
+}
+
+impl SegmentedRendition {
+    /// Valeur de `#EXT-X-TARGETDURATION` : durée max arrondie au supérieur
+    pub fn target_duration(&self) -> u32 {
+        self.segments
+            .iter()
+            .map(|segment| segment.duration.ceil() as u32)
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn total_duration(&self) -> f64 {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// Génère la media playlist VOD ; `uri_query` est ajouté à chaque URI
+    /// (les URIs relatives n'héritent pas de la query string signée)
+    pub fn media_playlist(&self, uri_query: Option<&str>) -> String {
+        let with_query = |uri: &str| match uri_query {
+            Some(query) => format!("{}?{}", uri, query),
+            None => uri.to_string(),
+        };
+
+        let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:7\n");
+        playlist.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", self.target_duration()));
+        playlist.push_str("#EXT-X-MEDIA-SEQUENCE:0\n");
+        playlist.push_str("#EXT-X-PLAYLIST-TYPE:VOD\n");
+        playlist.push_str("#EXT-X-INDEPENDENT-SEGMENTS\n");
+        playlist.push_str(&format!("#EXT-X-MAP:URI=\"{}\"\n", with_query(&self.init_uri)));
+
+        for segment in &self.segments {
+            playlist.push_str(&format!("#EXTINF:{:.5},\n{}\n", segment.duration, with_query(&segment.uri)));
+        }
+
+        playlist.push_str("#EXT-X-ENDLIST\n");
+        playlist
+    }
+
+    /// Vrai si `file` est le segment d'init ou un segment de la rendition
+    pub fn contains_file(&self, file: &str) -> bool {
+        self.init_uri == file || self.segments.iter().any(|segment| segment.uri == file)
+    }
+
+    /// Modèle `$Number$` des URIs de segments, pour les `SegmentTemplate` DASH
+    pub fn media_template(&self) -> String {
+        segment_uri("$Number$", &self.source_version)
+    }
+}
+
+/// Version d'une source d'après sa taille et sa date de modification : elle
+/// change quand la piste est remplacée
+pub fn source_version(metadata: &fs::Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(metadata.len().to_be_bytes());
+    hasher.update(modified.as_nanos().to_be_bytes());
+    hex::encode(&hasher.finalize()[..6])
+}
+
+fn init_uri(version: &str) -> String {
+    format!("init-{}.mp4", version)
+}
+
+fn segment_uri(number: impl std::fmt::Display, version: &str) -> String {
+    format!("segment{}-{}.m4s", number, version)
+}
+
+/// Chaîne `CODECS` annoncée pour un codec de profil, si segmentable
+/// (AAC seulement avec la fonctionnalité `fdk-aac`)
+pub fn codecs_string(codec: &str) -> Option<&'static str> {
+    match codec {
+        "mp3" => Some(Fmp4AudioCodec::Mp3.codecs_string()),
+        #[cfg(feature = "fdk-aac")]
+        "aac" => Some("mp4a.40.2"),
+        _ => None,
+    }
+}
+
+/// Segmenteur de pistes VOD
+#[derive(Debug, Clone)]
+pub struct HlsSegmenter {
+    config: SegmenterConfig,
+}
+
+impl HlsSegmenter {
+    pub fn new(config: SegmenterConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &SegmenterConfig {
+        &self.config
+    }
+
+    pub fn rendition_dir(&self, track_id: &str, quality_id: &str) -> PathBuf {
+        self.config.cache_dir.join(track_id).join(quality_id)
+    }
+
+    /// Charge une rendition déjà segmentée depuis le cache disque
+    pub fn load_rendition(&self, track_id: &str, quality_id: &str) -> Option<SegmentedRendition> {
+        let index_path = self.rendition_dir(track_id, quality_id).join(INDEX_FILE);
+        let data = fs::read(index_path).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// Segmente `source` (version `version`) pour le profil donné. Opération
+    /// bloquante (décodage et encodage), à exécuter dans `spawn_blocking`.
+    ///
+    /// Init et segments sont écrits dans un répertoire temporaire puis renommé,
+    /// pour qu'une rendition visible soit toujours complète.
+    pub fn segment_track(
+        &self,
+        source: &Path,
+        track_id: &str,
+        profile: &AdaptiveProfile,
+        version: &str,
+    ) -> Result<SegmentedRendition, AppError> {
+        let started = std::time::Instant::now();
+        let final_dir = self.rendition_dir(track_id, &profile.quality_id);
+        let parent = final_dir.parent().ok_or_else(|| AppError::StorageError {
+            message: "Répertoire de rendition invalide".to_string(),
+        })?;
+        fs::create_dir_all(parent)?;
+        let staging_dir = parent.join(format!(".{}.{}", profile.quality_id, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&staging_dir)?;
+
+        let rendition = match self.write_segments(&staging_dir, source, track_id, profile, version) {
+            Ok(rendition) => rendition,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging_dir);
+                return Err(e);
+            }
+        };
+
+        if final_dir.exists() {
+            fs::remove_dir_all(&final_dir)?;
+        }
+        fs::rename(&staging_dir, &final_dir)?;
+
+        info!("Piste {} segmentée en {} ({} segments, {:.1}s) en {:?}",
+              track_id, profile.quality_id, rendition.segments.len(),
+              rendition.total_duration(), started.elapsed());
+
+        Ok(rendition)
+    }
+
+    fn write_segments(
+        &self,
+        dir: &Path,
+        source: &Path,
+        track_id: &str,
+        profile: &AdaptiveProfile,
+        version: &str,
+    ) -> Result<SegmentedRendition, AppError> {
+        // Le gain de normalisation dépend de la piste entière : première passe de mesure
+        let gain_db = match &self.config.normalization {
+            Some(normalization) => Some(normalization.gain_db(&loudness::analyze_file(source)?)),
+            None => None,
+        };
+
+        let mut decoder = SymphoniaDecoder::open(source)?;
+        let (source_rate, source_channels) = (decoder.sample_rate(), usize::from(decoder.channels()));
+        debug!("Piste {} ouverte: {} Hz, {} canaux", track_id, source_rate, source_channels);
+
+        let channels = profile.channels.max(1);
+        let mut resampler = StreamResampler::new(source_rate, profile.sample_rate, channels as usize)?;
+        let (mut encoder, codec) = create_segment_encoder(&profile.codec, EncoderConfig {
+            bitrate: profile.bitrate_kbps * 1000,
+            sample_rate: profile.sample_rate,
+            channels,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::High,
+            enable_vbr: false,
+            complexity: 8,
+        })?;
+
+        let bitrate = profile.bitrate_kbps * 1000;
+        let track = Fmp4Track {
+            track_id: 1,
+            timescale: profile.sample_rate,
+            sample_rate: profile.sample_rate,
+            channels: u16::from(channels),
+            codec,
+            avg_bitrate: bitrate,
+            max_bitrate: bitrate,
+        };
+        let init_uri = init_uri(version);
+        fs::write(dir.join(&init_uri), fmp4::init_segment(&track))?;
+
+        let mut writer = SegmentWriter {
+            dir,
+            track: &track,
+            version,
+            target_samples: (self.config.segment_duration.as_secs_f64() * f64::from(track.timescale)) as u64,
+            segments: Vec::new(),
+            pending: Vec::new(),
+            pending_duration: 0,
+            decode_time: 0,
+        };
+        // Échantillons en attente d'encodage, octets encodés en attente d'une frame complète
+        let mut samples = Vec::new();
+        let mut encoded = Vec::new();
+        let mut encode = |samples: &mut Vec<f32>, last: bool, writer: &mut SegmentWriter| -> Result<(), AppError> {
+            if let Some(gain_db) = gain_db {
+                loudness::apply_gain(samples, gain_db);
+            }
+            encoded.extend_from_slice(&encoder.encode(samples, profile.sample_rate, channels)?);
+            if last {
+                encoded.extend_from_slice(&encoder.finalize()?);
+            }
+            samples.clear();
+            let (frames, consumed) = split_frames(&track.codec, &encoded);
+            encoded.drain(..consumed);
+            writer.push(frames)
+        };
+
+        while let Some(chunk) = decoder.next_chunk()? {
+            if chunk.sample_rate != source_rate || usize::from(chunk.channels) != source_channels {
+                return Err(AppError::DecodingError {
+                    message: "Format audio variable en cours de piste".to_string(),
+                });
+            }
+            let remixed = remix_channels(&chunk.samples, source_channels, channels as usize);
+            samples.extend(resampler.process(&remixed)?);
+            if samples.len() >= ENCODE_CHUNK_FRAMES * channels as usize {
+                encode(&mut samples, false, &mut writer)?;
+            }
+        }
+        samples.extend(resampler.flush()?);
+        encode(&mut samples, true, &mut writer)?;
+        let segments = writer.finish()?;
+
+        if segments.is_empty() {
+            return Err(AppError::EncodingError {
+                message: format!("Aucune frame produite pour {} ({})", track_id, profile.quality_id),
+            });
+        }
+
+        let rendition = SegmentedRendition {
+            track_id: track_id.to_string(),
+            quality_id: profile.quality_id.clone(),
+            codecs: track.codec.codecs_string().to_string(),
+            sample_rate: track.sample_rate,
+            channels: track.channels as u8,
+            bitrate_kbps: profile.bitrate_kbps,
+            init_uri,
+            segments,
+            created_at: SystemTime::now(),
+            source_version: version.to_string(),
+        };
+
+        let index = serde_json::to_vec_pretty(&rendition).map_err(|_| AppError::SerializationError)?;
+        fs::write(dir.join(INDEX_FILE), index)?;
+
+        Ok(rendition)
+    }
+}
+
+/// Regroupe les frames encodées en segments de durée cible, écrits dès qu'ils
+/// sont complets
+struct SegmentWriter<'a> {
+    dir: &'a Path,
+    track: &'a Fmp4Track,
+    version: &'a str,
+    target_samples: u64,
+    segments: Vec<SegmentInfo>,
+    pending: Vec<Fmp4Sample>,
+    pending_duration: u64,
+    decode_time: u64,
+}
+
+impl SegmentWriter<'_> {
+    fn push(&mut self, frames: Vec<Fmp4Sample>) -> Result<(), AppError> {
+        for frame in frames {
+            self.pending_duration += u64::from(frame.duration);
+            self.pending.push(frame);
+            if self.pending_duration >= self.target_samples {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), AppError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let sequence = self.segments.len() as u32;
+        let uri = segment_uri(sequence, self.version);
+        let data = fmp4::media_segment(self.track, sequence + 1, self.decode_time, &self.pending);
+        fs::write(self.dir.join(&uri), &data)?;
+        self.segments.push(SegmentInfo {
+            uri,
+            duration: self.pending_duration as f64 / f64::from(self.track.timescale),
+            byte_size: data.len() as u64,
+        });
+        self.decode_time += self.pending_duration;
+        self.pending_duration = 0;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Écrit le dernier segment, éventuellement plus court
+    fn finish(mut self) -> Result<Vec<SegmentInfo>, AppError> {
+        self.flush()?;
+        Ok(self.segments)
+    }
+}
+
+/// Codec des sorties fMP4 sans choix explicite : AAC si l'encodeur
+/// libfdk-aac est compilé (fonctionnalité `fdk-aac`), MP3 sinon
+#[cfg(feature = "fdk-aac")]
+pub(crate) const DEFAULT_FMP4_CODEC: &str = "aac";
+#[cfg(not(feature = "fdk-aac"))]
+pub(crate) const DEFAULT_FMP4_CODEC: &str = "mp3";
+
+/// Crée l'encodeur d'une rendition segmentée et le codec de sa piste fMP4.
+///
+/// AAC est toujours encodé en LC CBR : c'est le profil que tous les lecteurs
+/// HLS/DASH acceptent, et son AudioSpecificConfig ne dépend que du format.
+/// Sans la fonctionnalité `fdk-aac`, seul MP3 est disponible.
+pub(crate) fn create_segment_encoder(
+    codec: &str,
+    config: EncoderConfig,
+) -> Result<(Box<dyn AudioEncoder>, Fmp4AudioCodec), AppError> {
+    match codec {
+        "mp3" => Ok((CodecFactory::create_encoder(codec, config)?, Fmp4AudioCodec::Mp3)),
+        #[cfg(feature = "fdk-aac")]
+        "aac" => {
+            use crate::codecs::aac::{AacBandwidthMode, AacEncoderConfig, AacEncoderImpl, AacObjectType, AacProfile, AacVbrMode};
+
+            let audio_specific_config = aac::audio_specific_config(aac::AAC_LC_OBJECT_TYPE, config.sample_rate, config.channels)
+                .ok_or(AppError::InvalidSampleRate { rate: config.sample_rate })?;
+            let encoder = AacEncoderImpl::with_aac_config(AacEncoderConfig {
+                sample_rate: config.sample_rate,
+                channels: config.channels,
+                bitrate: config.bitrate,
+                profile: AacProfile::LC,
+                object_type: AacObjectType::LC,
+                vbr_mode: AacVbrMode::CBR,
+                bandwidth_mode: AacBandwidthMode::Auto,
+                afterburner: true,
+                sbr_enabled: false,
+                ps_enabled: false,
+            })?;
+            Ok((Box::new(encoder), Fmp4AudioCodec::Aac { audio_specific_config: audio_specific_config.to_vec() }))
+        }
+        _ => Err(AppError::UnsupportedCodec { codec: codec.to_string() }),
+    }
+}
+
+/// Découpe la sortie d'un encodeur en frames avec leur durée. Renvoie aussi
+/// le nombre d'octets consommés, une frame incomplète restant en attente.
+pub(crate) fn split_frames(codec: &Fmp4AudioCodec, data: &[u8]) -> (Vec<Fmp4Sample>, usize) {
+    let base = data.as_ptr() as usize;
+    let mut consumed = 0;
+    let mut frames = Vec::new();
+
+    match codec {
+        Fmp4AudioCodec::Mp3 => {
+            for (info, frame) in mp3::split_mp3_frames(data) {
+                consumed = frame.as_ptr() as usize - base + frame.len();
+                frames.push(Fmp4Sample {
+                    duration: info.samples_per_frame,
+                    data: frame.to_vec(),
+                });
+            }
+        }
+        Fmp4AudioCodec::Aac { .. } => {
+            // Le header ADTS est retiré : en MP4 la configuration est dans l'esds
+            for (info, frame) in aac::split_adts_frames(data) {
+                consumed = frame.as_ptr() as usize - base + frame.len();
+                frames.push(Fmp4Sample {
+                    duration: aac::AAC_LC_FRAME_SAMPLES,
+                    data: frame[info.header_length..].to_vec(),
+                });
+            }
+        }
+    }
+
+    (frames, consumed)
+}
+
+/// Audio PCM entrelacé en f32
+#[derive(Debug, Clone)]
+pub(crate) struct PcmAudio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+/// Décode intégralement un fichier audio, délai et padding encodeur retirés
+pub(crate) fn decode_file(path: &Path) -> Result<PcmAudio, AppError> {
+    let decoded = SymphoniaDecoder::open(path)?.decode_all()?;
+    Ok(PcmAudio {
+        samples: decoded.samples,
+        sample_rate: decoded.sample_rate,
+        channels: usize::from(decoded.channels),
+    })
+}
+
+/// Adapte le nombre de canaux : moyenne vers le mono, duplication du mono,
+/// conservation des deux premiers canaux pour les sources multicanal
+pub(crate) fn remix_channels(samples: &[f32], from: usize, to: usize) -> Vec<f32> {
+    if from == to || from == 0 {
+        return samples.to_vec();
+    }
+
+    let mut output = Vec::with_capacity(samples.len() / from * to);
+    for frame in samples.chunks_exact(from) {
+        if to == 1 {
+            output.push(frame.iter().sum::<f32>() / from as f32);
+        } else {
+            for channel in 0..to {
+                output.push(frame[channel.min(from - 1)]);
+            }
+        }
+    }
+    output
+}
+
+/// Encode un bloc hors des workers tokio : l'état de l'encodeur est déplacé
+/// dans `spawn_blocking` puis restitué avec le résultat. Les relais gardent
+/// leur boucle de réception asynchrone, annulée avec le runtime.
+pub(crate) async fn encode_blocking<S, T>(
+    mut state: S,
+    encode: impl FnOnce(&mut S) -> Result<T, AppError> + Send + 'static,
+) -> Result<(S, T), AppError>
+where
+    S: Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(move || encode(&mut state).map(|output| (state, output)))
+        .await
+        .map_err(|e| AppError::InternalError { message: e.to_string() })?
+}
+
+/// Rééchantillonne de l'audio entrelacé avec rubato, en compensant le délai du filtre
+pub(crate) fn resample(samples: &[f32], channels: usize, from: u32, to: u32) -> Result<Vec<f32>, AppError> {
+    use rubato::{FftFixedIn, Resampler};
+
+    if from == to || samples.is_empty() {
+        return Ok(samples.to_vec());
+    }
+
+    let resample_error = |e: &dyn std::fmt::Display| AppError::AudioError { message: format!("Rééchantillonnage: {}", e) };
+
+    let frames = samples.len() / channels;
+    let planar: Vec<Vec<f32>> = (0..channels)
+        .map(|channel| samples.iter().skip(channel).step_by(channels).copied().collect())
+        .collect();
+
+    let mut resampler = FftFixedIn::<f32>::new(from as usize, to as usize, 1024, 2, channels)
+        .map_err(|e| resample_error(&e))?;
+    let delay = resampler.output_delay();
+    let expected = (frames as u64 * u64::from(to)).div_ceil(u64::from(from)) as usize;
+
+    let mut output: Vec<Vec<f32>> = vec![Vec::with_capacity(expected + delay); channels];
+    let mut position = 0;
+
+    while position < frames {
+        let needed = resampler.input_frames_next();
+        let end = (position + needed).min(frames);
+        let input: Vec<&[f32]> = planar.iter().map(|channel| &channel[position..end]).collect();
+
+        let chunk = if end - position == needed {
+            resampler.process(&input, None)
+        } else {
+            resampler.process_partial(Some(&input), None)
+        }
+        .map_err(|e| resample_error(&e))?;
+
+        for (out, chunk) in output.iter_mut().zip(chunk) {
+            out.extend_from_slice(&chunk);
+        }
+        position = end;
+    }
+
+    // Vidange du filtre jusqu'à obtenir toutes les frames attendues
+    while output[0].len() < expected + delay {
+        let chunk = resampler
+            .process_partial::<&[f32]>(None, None)
+            .map_err(|e| resample_error(&e))?;
+        if chunk[0].is_empty() {
+            break;
+        }
+        for (out, chunk) in output.iter_mut().zip(chunk) {
+            out.extend_from_slice(&chunk);
+        }
+    }
+
+    let available = output[0].len().saturating_sub(delay).min(expected);
+    let mut interleaved = Vec::with_capacity(available * channels);
+    for frame in delay..delay + available {
+        for channel in &output {
+            interleaved.push(channel[frame]);
+        }
+    }
+    Ok(interleaved)
+}
+
+/// Rééchantillonneur incrémental, pour les flux décodés par morceaux :
+/// mêmes réglages que `resample`, délai du filtre retiré en tête
+pub(crate) struct StreamResampler {
+    resampler: Option<rubato::FftFixedIn<f32>>,
+    channels: usize,
+    from: u32,
+    to: u32,
+    /// Entrée planaire en attente d'un bloc complet
+    pending: Vec<Vec<f32>>,
+    /// Frames de sortie encore à écarter (délai du filtre)
+    delay: usize,
+    consumed: u64,
+    produced: u64,
+}
+
+impl std::fmt::Debug for StreamResampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamResampler")
+            .field("channels", &self.channels)
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish()
+    }
+}
+
+impl StreamResampler {
+    pub(crate) fn new(from: u32, to: u32, channels: usize) -> Result<Self, AppError> {
+        let resampler = if from == to {
+            None
+        } else {
+            Some(
+                rubato::FftFixedIn::<f32>::new(from as usize, to as usize, 1024, 2, channels)
+                    .map_err(|e| AppError::AudioError { message: format!("Rééchantillonnage: {}", e) })?,
+            )
+        };
+        let delay = resampler.as_ref().map_or(0, rubato::Resampler::output_delay);
+        Ok(Self {
+            resampler,
+            channels,
+            from,
+            to,
+            pending: vec![Vec::new(); channels],
+            delay,
+            consumed: 0,
+            produced: 0,
+        })
+    }
+
+    /// Rééchantillonne un morceau entrelacé ; la sortie peut être vide tant
+    /// qu'un bloc d'entrée n'est pas complet
+    pub(crate) fn process(&mut self, samples: &[f32]) -> Result<Vec<f32>, AppError> {
+        use rubato::Resampler;
+
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(samples.to_vec());
+        };
+        for frame in samples.chunks_exact(self.channels) {
+            for (channel, sample) in self.pending.iter_mut().zip(frame) {
+                channel.push(*sample);
+            }
+        }
+        self.consumed += (samples.len() / self.channels) as u64;
+
+        let mut output = vec![Vec::new(); self.channels];
+        while self.pending[0].len() >= resampler.input_frames_next() {
+            let needed = resampler.input_frames_next();
+            let chunk = resampler
+                .process(&self.pending.iter().map(|channel| &channel[..needed]).collect::<Vec<_>>(), None)
+                .map_err(|e| AppError::AudioError { message: format!("Rééchantillonnage: {}", e) })?;
+            for channel in &mut self.pending {
+                channel.drain(..needed);
+            }
+            for (out, chunk) in output.iter_mut().zip(chunk) {
+                out.extend_from_slice(&chunk);
+            }
+        }
+        Ok(self.emit(output, u64::MAX))
+    }
+
+    /// Vide le filtre en fin de flux, jusqu'au nombre de frames attendu
+    pub(crate) fn flush(&mut self) -> Result<Vec<f32>, AppError> {
+        use rubato::Resampler;
+
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(Vec::new());
+        };
+        let expected = (self.consumed * u64::from(self.to)).div_ceil(u64::from(self.from));
+        let resample_error = |e: &dyn std::fmt::Display| AppError::AudioError { message: format!("Rééchantillonnage: {}", e) };
+
+        let mut output = vec![Vec::new(); self.channels];
+        if !self.pending[0].is_empty() {
+            let chunk = resampler
+                .process_partial(Some(&self.pending.iter().map(Vec::as_slice).collect::<Vec<_>>()), None)
+                .map_err(|e| resample_error(&e))?;
+            for (out, chunk) in output.iter_mut().zip(chunk) {
+                out.extend_from_slice(&chunk);
+            }
+            self.pending.iter_mut().for_each(Vec::clear);
+        }
+        while self.produced + (output[0].len().saturating_sub(self.delay) as u64) < expected {
+            let chunk = resampler
+                .process_partial::<&[f32]>(None, None)
+                .map_err(|e| resample_error(&e))?;
+            if chunk[0].is_empty() {
+                break;
+            }
+            for (out, chunk) in output.iter_mut().zip(chunk) {
+                out.extend_from_slice(&chunk);
+            }
+        }
+        Ok(self.emit(output, expected))
+    }
+
+    /// Entrelace la sortie planaire en écartant le délai restant, sans
+    /// dépasser `limit` frames produites au total
+    fn emit(&mut self, output: Vec<Vec<f32>>, limit: u64) -> Vec<f32> {
+        let skip = self.delay.min(output[0].len());
+        self.delay -= skip;
+        let available = ((output[0].len() - skip) as u64).min(limit.saturating_sub(self.produced)) as usize;
+        self.produced += available as u64;
+
+        let mut interleaved = Vec::with_capacity(available * self.channels);
+        for frame in skip..skip + available {
+            for channel in &output {
+                interleaved.push(channel[frame]);
+            }
+        }
+        interleaved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendition(durations: &[f64]) -> SegmentedRendition {
+        SegmentedRendition {
+            track_id: "track.flac".to_string(),
+            quality_id: "high".to_string(),
+            codecs: "mp4a.40.34".to_string(),
+            sample_rate: 44100,
+            channels: 2,
+            bitrate_kbps: 320,
+            init_uri: init_uri("v1"),
+            segments: durations
+                .iter()
+                .enumerate()
+                .map(|(i, duration)| SegmentInfo {
+                    uri: segment_uri(i, "v1"),
+                    duration: *duration,
+                    byte_size: 0,
+                })
+                .collect(),
+            created_at: SystemTime::now(),
+            source_version: "v1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_media_playlist() {
+        let playlist = rendition(&[6.01088, 6.01088, 2.5]).media_playlist(Some("expires=1&sig=ab"));
+
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:7\n"));
+        assert!(playlist.contains("#EXT-X-MAP:URI=\"init-v1.mp4?expires=1&sig=ab\"\n"));
+        assert!(playlist.contains("#EXTINF:6.01088,\nsegment0-v1.m4s?expires=1&sig=ab\n"));
+        assert!(playlist.contains("#EXTINF:2.50000,\nsegment2-v1.m4s?expires=1&sig=ab\n"));
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+    }
+
+    #[test]
+    fn test_remix_channels() {
+        assert_eq!(remix_channels(&[1.0, 0.0, 0.5, 0.5], 2, 1), vec![0.5, 0.5]);
+        assert_eq!(remix_channels(&[0.25, -0.25], 1, 2), vec![0.25, 0.25, -0.25, -0.25]);
+    }
+
+    #[test]
+    fn test_resample_length() {
+        let samples: Vec<f32> = (0..44100 * 2).map(|i| ((i / 2) as f32 * 0.01).sin()).collect();
+        let resampled = resample(&samples, 2, 44100, 22050).unwrap();
+        assert_eq!(resampled.len(), 22050 * 2);
+    }
+
+    #[test]
+    fn test_segment_track_writes_rendition() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("tone.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&source, spec).unwrap();
+        for i in 0..44100 * 14 {
+            let sample = ((i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 44100.0).sin() * 8000.0) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let segmenter = HlsSegmenter::new(SegmenterConfig {
+            cache_dir: dir.path().join("hls"),
+            segment_duration: Duration::from_secs(6),
+            normalization: None,
+        });
+        let rendition = segmenter
+            .segment_track(&source, "tone.wav", &AdaptiveProfile::low_quality(), "v1")
+            .unwrap();
+
+        assert_eq!(rendition.segments.len(), 3);
+        assert!((rendition.total_duration() - 14.0).abs() < 0.2);
+        assert!(rendition.segments[..2].iter().all(|s| s.duration >= 6.0 && s.duration < 6.1));
+
+        let reloaded = segmenter.load_rendition("tone.wav", "low").unwrap();
+        assert_eq!(reloaded.segments.len(), 3);
+        assert_eq!(reloaded.source_version, "v1");
+        assert_eq!(reloaded.segments[2].uri, "segment2-v1.m4s");
+        let dir = segmenter.rendition_dir("tone.wav", "low");
+        let init = fs::read(dir.join("init-v1.mp4")).unwrap();
+        assert_eq!(&init[4..8], b"ftyp");
+        let segment = fs::read(dir.join(&reloaded.segments[0].uri)).unwrap();
+        assert_eq!(segment.len() as u64, reloaded.segments[0].byte_size);
+    }
+
+    #[test]
+    fn test_codecs_string_matches_available_encoders() {
+        for codec in ["mp3", "aac", "opus"] {
+            let encoder = create_segment_encoder(codec, EncoderConfig {
+                bitrate: 128_000,
+                sample_rate: 44100,
+                channels: 2,
+                quality: CodecQuality::High,
+                latency_mode: LatencyMode::High,
+                enable_vbr: false,
+                complexity: 5,
+            });
+            assert_eq!(codecs_string(codec).is_some(), encoder.is_ok(), "{}", codec);
+        }
+        assert!(codecs_string(DEFAULT_FMP4_CODEC).is_some());
+    }
+
+    #[cfg(feature = "fdk-aac")]
+    #[test]
+    fn test_aac_frames_are_raw_access_units() {
+        let (mut encoder, codec) = create_segment_encoder("aac", EncoderConfig {
+            bitrate: 128_000,
+            sample_rate: 44100,
+            channels: 2,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::High,
+            enable_vbr: false,
+            complexity: 8,
+        })
+        .unwrap();
+        assert_eq!(codec.codecs_string(), "mp4a.40.2");
+
+        let samples: Vec<f32> = (0..44100 * 2).map(|i| ((i / 2) as f32 * 0.05).sin() * 0.3).collect();
+        let mut encoded = encoder.encode(&samples, 44100, 2).unwrap();
+        encoded.extend_from_slice(&encoder.finalize().unwrap());
+
+        let (frames, consumed) = split_frames(&codec, &encoded);
+        assert_eq!(consumed, encoded.len());
+        assert!(frames.len() >= 44);
+        assert!(frames.iter().all(|frame| frame.duration == 1024 && frame.data[..2] != [0xFF, 0xF1]));
+
+        let track = Fmp4Track {
+            track_id: 1,
+            timescale: 44100,
+            sample_rate: 44100,
+            channels: 2,
+            codec,
+            avg_bitrate: 128_000,
+            max_bitrate: 128_000,
+        };
+        let init = fmp4::init_segment(&track);
+        // DecSpecificInfo : AudioSpecificConfig AAC-LC 44.1 kHz stéréo
+        assert!(init.windows(4).any(|w| w == [0x05, 0x02, 0x12, 0x10]));
+    }
+}
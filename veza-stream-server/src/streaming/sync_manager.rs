@@ -5,7 +5,11 @@ use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::{RwLock, broadcast};
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn, span, Level};
+use tracing::{info, warn, span, Instrument, Level};
+use uuid::Uuid;
+
+use crate::core::{ClockEstimate, SyncEngine};
+use crate::error::AppError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncConfig {
@@ -101,12 +105,6 @@ impl Default for SyncMetrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum SyncMessage {
-    ClockSync {
-        client_id: String,
-        server_timestamp: u64,
-        client_timestamp: u64,
-        round_trip_time: u32,
-    },
     BufferAdjustment {
         client_id: String,
         target_buffer_ms: u32,
@@ -135,12 +133,25 @@ pub enum SyncCommandType {
     ResetBuffer,
     ForceSync,
     AdjustClock,
+    /// Lecture à partir de `position_ms` à l'heure maître du message
+    Play { position_ms: u64 },
+    /// Pause à `position_ms` à l'heure maître du message
+    Pause { position_ms: u64 },
+    /// Saut à `position_ms` à l'heure maître du message
+    Seek { position_ms: u64 },
 }
 
 /// Gestionnaire de synchronisation multi-clients
+///
+/// L'horloge des clients est calée par le protocole du `SyncEngine`
+/// (WebSocket `/sync/clock`) : un client s'y connecte avec son `client_id`,
+/// qui doit alors être un UUID, et son décalage comme son aller-retour sont
+/// lus dans les estimations du moteur. L'heure maître des commandes
+/// planifiées est l'heure de référence du moteur, en ms.
 #[derive(Clone)]
 pub struct SyncManager {
     config: SyncConfig,
+    engine: Arc<SyncEngine>,
     clients: Arc<RwLock<HashMap<String, SynchronizedClient>>>,
     sync_tx: broadcast::Sender<SyncMessage>,
     master_clock: Arc<RwLock<MasterClock>>,
@@ -163,7 +174,7 @@ pub struct StreamPosition {
 }
 
 impl SyncManager {
-    pub fn new(config: SyncConfig) -> Self {
+    pub fn new(config: SyncConfig, engine: Arc<SyncEngine>) -> Self {
         let (sync_tx, _) = broadcast::channel(1000);
         
         let master_clock = MasterClock {
@@ -181,6 +192,7 @@ impl SyncManager {
 
         Self {
             config,
+            engine,
             clients: Arc::new(RwLock::new(HashMap::new())),
             sync_tx,
             master_clock: Arc::new(RwLock::new(master_clock)),
@@ -216,125 +228,88 @@ impl SyncManager {
         session_id: String,
     ) -> Result<SynchronizedClient, Box<dyn std::error::Error + Send + Sync>> {
         let span = span!(Level::INFO, "add_client", client_id = %client_id);
-        let _enter = span.enter();
 
-        let mut clients = self.clients.write().await;
-        
-        if clients.len() >= self.config.max_clients {
-            return Err("Maximum number of synchronized clients reached".into());
-        }
-
-        let client = SynchronizedClient {
-            client_id: client_id.clone(),
-            session_id: session_id.clone(),
-            sync_state: SyncState::Initializing,
-            clock_offset_ms: 0,
-            buffer_level_ms: self.config.buffer_target_ms,
-            target_buffer_ms: self.config.buffer_target_ms,
-            jitter_ms: 0,
-            last_sync: Instant::now(),
-            connection_quality: ConnectionQuality {
-                rtt_ms: 50,
-                jitter_ms: 10,
-                packet_loss_percent: 0.0,
-                bandwidth_kbps: 1000,
-                stability_score: 1.0,
-            },
-            sync_metrics: SyncMetrics::default(),
-            created_at: SystemTime::now(),
-        };
+        // Span attaché au futur : un garde `enter()` tenu à travers un `.await` le rendrait non-Send
+        async move {
+            let mut clients = self.clients.write().await;
+            
+            if clients.len() >= self.config.max_clients {
+                return Err("Maximum number of synchronized clients reached".into());
+            }
 
-        clients.insert(client_id.clone(), client.clone());
+            let client = SynchronizedClient {
+                client_id: client_id.clone(),
+                session_id: session_id.clone(),
+                sync_state: SyncState::Initializing,
+                clock_offset_ms: 0,
+                buffer_level_ms: self.config.buffer_target_ms,
+                target_buffer_ms: self.config.buffer_target_ms,
+                jitter_ms: 0,
+                last_sync: Instant::now(),
+                connection_quality: ConnectionQuality {
+                    rtt_ms: 50,
+                    jitter_ms: 10,
+                    packet_loss_percent: 0.0,
+                    bandwidth_kbps: 1000,
+                    stability_score: 1.0,
+                },
+                sync_metrics: SyncMetrics::default(),
+                created_at: SystemTime::now(),
+            };
 
-        info!("Added synchronized client: {} for session: {}", client_id, session_id);
+            clients.insert(client_id.clone(), client.clone());
 
-        // Initier la synchronisation initiale
-        self.initiate_client_sync(&client_id).await?;
+            info!("Added synchronized client: {} for session: {}", client_id, session_id);
 
-        Ok(client)
+            Ok(client)
+        }
+        .instrument(span)
+        .await
     }
 
-    /// Initier la synchronisation pour un client
-    async fn initiate_client_sync(&self, client_id: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let master_clock = self.master_clock.read().await;
-        let current_time = Instant::now();
-        let server_timestamp = current_time.duration_since(master_clock.start_time).as_millis() as u64;
-
-        let sync_msg = SyncMessage::ClockSync {
-            client_id: client_id.to_string(),
-            server_timestamp,
-            client_timestamp: 0, // Will be set by client
-            round_trip_time: 0,  // Will be calculated
-        };
-
-        self.sync_tx.send(sync_msg)?;
-        Ok(())
+    /// Heure maître en ms, référence des commandes planifiées : celle du
+    /// `SyncEngine`, sur laquelle les clients calent leur horloge
+    pub fn master_time_ms(&self) -> Result<u64, AppError> {
+        Ok(self.engine.now_us()? / 1000)
     }
 
-    /// Traiter une réponse de synchronisation d'horloge
-    pub async fn process_clock_sync_response(
-        &self,
-        client_id: &str,
-        client_timestamp: u64,
-        server_timestamp: u64,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// État de synchronisation d'un client, horloge à jour des estimations du `SyncEngine`
+    pub async fn get_client(&self, client_id: &str) -> Option<SynchronizedClient> {
         let mut clients = self.clients.write().await;
-        
-        if let Some(client) = clients.get_mut(client_id) {
-            let current_time = Instant::now();
-            let master_clock = self.master_clock.read().await;
-            let current_server_time = current_time.duration_since(master_clock.start_time).as_millis() as u64;
-            
-            // Calculer RTT et offset d'horloge
-            let rtt = (current_server_time - server_timestamp) as u32;
-            let clock_offset = (client_timestamp as i64) - (server_timestamp as i64) - (rtt as i64 / 2);
-            
-            client.clock_offset_ms = clock_offset;
-            client.connection_quality.rtt_ms = rtt;
-            client.last_sync = current_time;
-            client.sync_metrics.sync_events += 1;
-            client.sync_metrics.total_drift_ms += clock_offset.abs();
-            client.sync_metrics.avg_sync_accuracy_ms = 
-                client.sync_metrics.total_drift_ms as f32 / client.sync_metrics.sync_events as f32;
-
-            // Mettre à jour l'état de synchronisation
-            client.sync_state = if clock_offset.abs() <= self.config.sync_tolerance_ms as i64 {
-                SyncState::Synchronized
-            } else {
-                SyncState::Syncing
-            };
-
-            info!("Clock sync for client {}: offset={}ms, rtt={}ms, state={:?}", 
-                  client_id, clock_offset, rtt, client.sync_state);
-
-            // Envoyer les ajustements si nécessaire
-            if clock_offset.abs() > self.config.sync_tolerance_ms as i64 {
-                self.send_sync_adjustment(client_id, clock_offset).await?;
-            }
-        }
+        let client = clients.get_mut(client_id)?;
+        apply_clock_estimate(&self.engine, client, self.config.sync_tolerance_ms);
+        Some(client.clone())
+    }
 
-        Ok(())
+    /// Décalage courant de l'horloge d'un client sur l'heure maître (ms),
+    /// dérive comprise ; `None` tant qu'aucun échange d'horloge n'a abouti
+    pub fn clock_offset_ms(&self, client_id: &str) -> Option<f64> {
+        let estimate = clock_estimate(&self.engine, client_id)?;
+        let now_us = self.engine.now_us().ok()?;
+        Some(estimate.offset_at(now_us))
     }
 
-    /// Envoyer un ajustement de synchronisation
-    async fn send_sync_adjustment(
+    /// Planifier une commande pour un client à l'heure maître `execute_at_ms`
+    pub fn schedule_command(
         &self,
         client_id: &str,
-        clock_offset: i64,
+        command: SyncCommandType,
+        execute_at_ms: u64,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let adjustment_msg = SyncMessage::SyncCommand {
+        self.sync_tx.send(SyncMessage::SyncCommand {
             client_id: client_id.to_string(),
-            command: SyncCommandType::AdjustClock,
-            timestamp: clock_offset as u64,
-        };
-
-        self.sync_tx.send(adjustment_msg)?;
+            command,
+            timestamp: execute_at_ms,
+        })?;
         Ok(())
     }
 
     /// Obtenir les statistiques de synchronisation en temps réel
     pub async fn get_sync_stats(&self) -> serde_json::Value {
-        let clients = self.clients.read().await;
+        let mut clients = self.clients.write().await;
+        for client in clients.values_mut() {
+            apply_clock_estimate(&self.engine, client, self.config.sync_tolerance_ms);
+        }
         let master_clock = self.master_clock.read().await;
         
         let total_clients = clients.len();
@@ -429,6 +404,8 @@ impl SyncManager {
     /// Démarrer le moniteur de qualité
     async fn start_quality_monitor(&self) {
         let clients = self.clients.clone();
+        let engine = self.engine.clone();
+        let tolerance_ms = self.config.sync_tolerance_ms;
 
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(5));
@@ -436,8 +413,9 @@ impl SyncManager {
             loop {
                 interval.tick().await;
                 
-                let clients_guard = clients.read().await;
-                for (client_id, client) in clients_guard.iter() {
+                let mut clients_guard = clients.write().await;
+                for (client_id, client) in clients_guard.iter_mut() {
+                    apply_clock_estimate(&engine, client, tolerance_ms);
                     if client.last_sync.elapsed() > Duration::from_secs(60) {
                         warn!("Client {} sync timeout", client_id);
                     }
@@ -495,3 +473,31 @@ impl SyncManager {
         }
     }
 }
+
+fn clock_estimate(engine: &SyncEngine, client_id: &str) -> Option<ClockEstimate> {
+    engine.clock_estimate(Uuid::parse_str(client_id).ok()?)
+}
+
+/// Reporte la dernière estimation d'horloge du `SyncEngine` sur l'état d'un
+/// client ; sans échange abouti, le client reste à l'état initial
+fn apply_clock_estimate(engine: &SyncEngine, client: &mut SynchronizedClient, tolerance_ms: u32) {
+    let Some(estimate) = clock_estimate(engine, &client.client_id) else {
+        return;
+    };
+    let now_us = engine.now_us().unwrap_or(estimate.measured_at_us);
+    let jitter_ms = estimate.jitter_ms.round() as u32;
+
+    client.clock_offset_ms = estimate.offset_at(now_us).round() as i64;
+    client.connection_quality.rtt_ms = estimate.round_trip_ms.round() as u32;
+    client.connection_quality.jitter_ms = jitter_ms;
+    client.jitter_ms = jitter_ms;
+    client.last_sync = Instant::now()
+        .checked_sub(Duration::from_micros(now_us.saturating_sub(estimate.measured_at_us)))
+        .unwrap_or_else(Instant::now);
+    client.sync_metrics.sync_events = estimate.samples as u64;
+    client.sync_metrics.avg_sync_accuracy_ms = estimate.jitter_ms as f32;
+    client.sync_state = match estimate.jitter_ms <= f64::from(tolerance_ms) {
+        true => SyncState::Synchronized,
+        false => SyncState::Syncing,
+    };
+}
@@ -208,6 +208,10 @@ impl WebRTCManager {
         }
     }
 
+    pub fn config(&self) -> &WebRTCConfig {
+        &self.config
+    }
+
     /// Démarre le gestionnaire WebRTC
     pub async fn start(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         info!("Starting WebRTC Manager with max {} peers", self.config.max_peers);
@@ -478,6 +482,20 @@ impl WebRTCManager {
         });
     }
 
+    /// Mettre à jour l'état de connexion d'un peer
+    pub async fn update_connection_state(
+        &self,
+        peer_id: &str,
+        connection_state: ConnectionState,
+    ) {
+        let mut peers = self.peers.write().await;
+        if let Some(peer) = peers.get_mut(peer_id) {
+            debug!("WebRTC peer {} state: {:?}", peer_id, connection_state);
+            peer.connection_state = connection_state;
+            peer.last_activity = Instant::now();
+        }
+    }
+
     /// Supprimer un peer
     pub async fn remove_peer(&self, peer_id: &str) -> bool {
         let mut peers = self.peers.write().await;
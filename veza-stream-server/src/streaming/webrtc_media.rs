@@ -0,0 +1,932 @@
+//! WHIP (ingest) et WHEP (lecture) des streams live en WebRTC
+//!
+//! Chaque requête crée une vraie `RTCPeerConnection` (ICE, DTLS, SRTP)
+//! négociée en un seul échange offre/réponse : la réponse part une fois les
+//! candidats rassemblés, sans trickle ICE. Tout le trafic passe par un unique
+//! port UDP multiplexé. L'audio est en Opus dans les deux sens : une source
+//! WHIP alimente une `LiveSource` comme une source Icecast ou RTMP, et chaque
+//! stream live est encodé une seule fois pour tous ses lecteurs WHEP. Les
+//! sessions sont suivies comme peers du `WebRTCManager`.
+//!
+//! La lecture WHEP exige la même URL signée (`expires`, `sig` sur
+//! l'identifiant du stream) que le HLS live, et chaque route `DELETE` ne
+//! termine que les sessions de son sens (et de son stream pour WHEP).
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use axum::{
+    extract::{OriginalUri, Path as AxumPath, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+    Router,
+};
+use bytes::Bytes;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use tokio::{net::UdpSocket, sync::broadcast, time::timeout};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+use webrtc::{
+    api::{
+        interceptor_registry::register_default_interceptors,
+        media_engine::{MediaEngine, MIME_TYPE_OPUS},
+        setting_engine::SettingEngine,
+        APIBuilder, API,
+    },
+    ice::{
+        mdns::MulticastDnsMode,
+        network_type::NetworkType,
+        udp_mux::{UDPMuxDefault, UDPMuxParams},
+        udp_network::UDPNetwork,
+    },
+    ice_transport::{ice_candidate_type::RTCIceCandidateType, ice_server::RTCIceServer},
+    interceptor::registry::Registry,
+    media::Sample,
+    peer_connection::{
+        configuration::RTCConfiguration, peer_connection_state::RTCPeerConnectionState,
+        sdp::session_description::RTCSessionDescription, RTCPeerConnection,
+    },
+    rtp_transceiver::{
+        rtp_codec::{RTCRtpCodecCapability, RTCRtpCodecParameters, RTPCodecType},
+        rtp_transceiver_direction::RTCRtpTransceiverDirection,
+        RTCRtpTransceiverInit,
+    },
+    track::{track_local::track_local_static_sample::TrackLocalStaticSample, track_remote::TrackRemote},
+};
+
+use crate::codecs::{
+    AudioDecoder, OpusDecoderConfig, OpusDecoderImpl, OpusEncoderConfig, OpusEncoderImpl, OpusFrameDuration,
+};
+use crate::config::{Config, LiveConfig};
+use crate::core::{Listener, StreamManager};
+use crate::error::AppError;
+use crate::streaming::adaptive::AdaptiveStreamQuery;
+use crate::streaming::ingest::{key_mount, LiveIngest, LiveSource};
+use crate::streaming::segmenter::{encode_blocking, remix_channels, StreamResampler};
+use crate::streaming::webrtc::{ConnectionState, WebRTCManager};
+use crate::utils::validate_signature;
+
+/// Format Opus en RTP (RFC 7587) : toujours annoncé en 48 kHz stéréo
+const OPUS_SAMPLE_RATE: u32 = 48000;
+const OPUS_CHANNELS: u8 = 2;
+const OPUS_PAYLOAD_TYPE: u8 = 111;
+/// Durée des paquets envoyés aux lecteurs WHEP
+const OPUS_FRAME: Duration = Duration::from_millis(20);
+/// Délai maximal de collecte des candidats ICE avant la réponse
+const GATHER_TIMEOUT: Duration = Duration::from_secs(5);
+/// Paquets perdus au-delà desquels on ne dissimule plus la perte
+const MAX_CONCEALED_PACKETS: u16 = 5;
+
+/// Paramètres WHIP/WHEP
+#[derive(Debug, Clone)]
+pub struct WebRtcMediaConfig {
+    /// Adresse du socket UDP partagé par toutes les sessions
+    pub udp_addr: SocketAddr,
+    /// Adresses annoncées dans les candidats à la place de celles des
+    /// interfaces (serveur derrière un NAT 1:1)
+    pub public_ips: Vec<String>,
+    /// Débit Opus envoyé aux lecteurs, en kbps
+    pub opus_bitrate: u32,
+    /// Déconnexion d'une source WHIP qui n'envoie plus rien
+    pub idle_timeout: Duration,
+}
+
+impl WebRtcMediaConfig {
+    pub fn from_config(config: &LiveConfig) -> Self {
+        Self {
+            udp_addr: SocketAddr::from(([0, 0, 0, 0], config.webrtc_udp_port)),
+            public_ips: config.webrtc_public_ips.clone(),
+            opus_bitrate: config.webrtc_opus_bitrate,
+            idle_timeout: config.source_idle_timeout,
+        }
+    }
+}
+
+/// Sens d'une session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SessionKind {
+    /// Source WHIP
+    Publish,
+    /// Lecteur WHEP d'un stream
+    Play(Uuid),
+}
+
+struct MediaSession {
+    kind: SessionKind,
+    peer: Arc<RTCPeerConnection>,
+}
+
+/// Piste Opus d'un stream, partagée par ses lecteurs WHEP
+struct WhepRelay {
+    track: Arc<TrackLocalStaticSample>,
+    viewers: AtomicUsize,
+}
+
+/// Sessions WHIP/WHEP actives
+pub struct WebRtcMedia {
+    api: API,
+    config: WebRtcMediaConfig,
+    manager: Arc<WebRTCManager>,
+    ingest: Arc<LiveIngest>,
+    streams: Arc<StreamManager>,
+    sessions: DashMap<Uuid, MediaSession>,
+    relays: Arc<DashMap<Uuid, Arc<WhepRelay>>>,
+}
+
+impl std::fmt::Debug for WebRtcMedia {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebRtcMedia")
+            .field("config", &self.config)
+            .field("sessions", &self.sessions.len())
+            .field("relays", &self.relays.len())
+            .finish()
+    }
+}
+
+fn webrtc_error(e: webrtc::Error) -> AppError {
+    AppError::StreamingError { message: format!("WebRTC: {}", e) }
+}
+
+fn opus_capability() -> RTCRtpCodecCapability {
+    RTCRtpCodecCapability {
+        mime_type: MIME_TYPE_OPUS.to_string(),
+        clock_rate: OPUS_SAMPLE_RATE,
+        channels: u16::from(OPUS_CHANNELS),
+        sdp_fmtp_line: "minptime=10;useinbandfec=1".to_string(),
+        rtcp_feedback: vec![],
+    }
+}
+
+/// API WebRTC limitée à Opus, dont l'ICE passe par `socket`
+pub(crate) fn build_api(socket: UdpSocket, public_ips: &[String]) -> Result<API, AppError> {
+    let ipv4 = socket.local_addr().map_err(|e| AppError::NetworkError { message: e.to_string() })?.is_ipv4();
+
+    let mut media_engine = MediaEngine::default();
+    media_engine
+        .register_codec(
+            RTCRtpCodecParameters {
+                capability: opus_capability(),
+                payload_type: OPUS_PAYLOAD_TYPE,
+                ..Default::default()
+            },
+            RTPCodecType::Audio,
+        )
+        .map_err(webrtc_error)?;
+    let registry = register_default_interceptors(Registry::new(), &mut media_engine).map_err(webrtc_error)?;
+
+    let mut settings = SettingEngine::default();
+    settings.set_udp_network(UDPNetwork::Muxed(UDPMuxDefault::new(UDPMuxParams::new(socket))));
+    // Le candidat du socket partagé prend l'adresse de la première interface
+    // de la famille du socket
+    settings.set_network_types(vec![if ipv4 { NetworkType::Udp4 } else { NetworkType::Udp6 }]);
+    settings.set_ice_multicast_dns_mode(MulticastDnsMode::Disabled);
+    if !public_ips.is_empty() {
+        settings.set_nat_1to1_ips(public_ips.to_vec(), RTCIceCandidateType::Host);
+    }
+
+    Ok(APIBuilder::new()
+        .with_media_engine(media_engine)
+        .with_interceptor_registry(registry)
+        .with_setting_engine(settings)
+        .build())
+}
+
+/// Applique l'offre, attend les candidats et renvoie la réponse SDP complète
+async fn answer(peer: &RTCPeerConnection, offer: String) -> Result<String, AppError> {
+    let offer = RTCSessionDescription::offer(offer).map_err(|e| AppError::ValidationError(format!("Offre SDP invalide: {}", e)))?;
+    peer.set_remote_description(offer)
+        .await
+        .map_err(|e| AppError::ValidationError(format!("Offre SDP refusée: {}", e)))?;
+    let answer = peer.create_answer(None).await.map_err(webrtc_error)?;
+    let mut gathered = peer.gathering_complete_promise().await;
+    peer.set_local_description(answer).await.map_err(webrtc_error)?;
+    if timeout(GATHER_TIMEOUT, gathered.recv()).await.is_err() {
+        warn!("Collecte des candidats ICE incomplète après {:?}", GATHER_TIMEOUT);
+    }
+    peer.local_description()
+        .await
+        .map(|description| description.sdp)
+        .ok_or_else(|| AppError::StreamingError { message: "Réponse SDP indisponible".to_string() })
+}
+
+impl WebRtcMedia {
+    pub async fn new(
+        manager: Arc<WebRTCManager>,
+        ingest: Arc<LiveIngest>,
+        config: WebRtcMediaConfig,
+    ) -> Result<Self, AppError> {
+        let socket = UdpSocket::bind(config.udp_addr)
+            .await
+            .map_err(|e| AppError::NetworkError { message: format!("Socket WebRTC {}: {}", config.udp_addr, e) })?;
+        info!("WebRTC (WHIP/WHEP) sur udp://{}", socket.local_addr().map_err(|e| AppError::NetworkError { message: e.to_string() })?);
+        let api = build_api(socket, &config.public_ips)?;
+        Ok(Self {
+            api,
+            streams: ingest.streams(),
+            config,
+            manager,
+            ingest,
+            sessions: DashMap::new(),
+            relays: Arc::new(DashMap::new()),
+        })
+    }
+
+    pub fn config(&self) -> &WebRtcMediaConfig {
+        &self.config
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Crée une connexion enregistrée auprès du `WebRTCManager`
+    async fn new_peer(self: &Arc<Self>, session_id: Uuid) -> Result<Arc<RTCPeerConnection>, AppError> {
+        let peer_id = session_id.to_string();
+        self.manager
+            .create_peer_session(peer_id.clone(), peer_id.clone())
+            .await
+            .map_err(|_| AppError::LimitExceeded {
+                resource: "peers WebRTC".to_string(),
+                limit: self.manager.config().max_peers as u32,
+            })?;
+
+        let ice_servers = self
+            .manager
+            .config()
+            .ice_servers
+            .iter()
+            .map(|server| RTCIceServer {
+                urls: server.urls.clone(),
+                username: server.username.clone().unwrap_or_default(),
+                credential: server.credential.clone().unwrap_or_default(),
+                ..Default::default()
+            })
+            .collect();
+        let peer = match self.api.new_peer_connection(RTCConfiguration { ice_servers, ..Default::default() }).await {
+            Ok(peer) => Arc::new(peer),
+            Err(e) => {
+                self.manager.remove_peer(&peer_id).await;
+                return Err(webrtc_error(e));
+            }
+        };
+
+        let media = Arc::downgrade(self);
+        peer.on_peer_connection_state_change(Box::new(move |state: RTCPeerConnectionState| {
+            let media = media.clone();
+            Box::pin(async move {
+                let Some(media) = media.upgrade() else { return };
+                let connection_state = match state {
+                    RTCPeerConnectionState::Connecting => ConnectionState::Connecting,
+                    RTCPeerConnectionState::Connected => ConnectionState::Connected,
+                    RTCPeerConnectionState::Disconnected => ConnectionState::Disconnected,
+                    RTCPeerConnectionState::Failed => ConnectionState::Failed,
+                    RTCPeerConnectionState::Closed => ConnectionState::Closed,
+                    _ => ConnectionState::New,
+                };
+                media.manager.update_connection_state(&session_id.to_string(), connection_state).await;
+                if matches!(state, RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed) {
+                    // Fermeture hors du handler, qui appartient à la connexion
+                    tokio::spawn(async move {
+                        media.close(session_id).await;
+                    });
+                }
+            })
+        }));
+        Ok(peer)
+    }
+
+    /// Session WHIP : la clé de stream authentifie la source
+    pub async fn publish(self: &Arc<Self>, key: &str, offer: String) -> Result<(Uuid, String), AppError> {
+        let source = self.ingest.connect(key, "whip", &key_mount("whip", key))?;
+        let session_id = Uuid::new_v4();
+        let peer = self.new_peer(session_id).await?;
+
+        let source = Arc::new(Mutex::new(Some(source)));
+        let idle_timeout = self.config.idle_timeout;
+        let media = Arc::downgrade(self);
+        peer.on_track(Box::new(move |track, _receiver| {
+            let source = source.lock().take();
+            let media = media.clone();
+            Box::pin(async move {
+                match (track, source) {
+                    (Some(track), Some(source)) if track.kind() == RTPCodecType::Audio => {
+                        receive_opus(track, source, idle_timeout, session_id).await;
+                        // Source inactive ou en erreur : la connexion est fermée aussi
+                        if let Some(media) = media.upgrade() {
+                            media.close(session_id).await;
+                        }
+                    }
+                    _ => debug!("Piste WHIP ignorée (session {})", session_id),
+                }
+            })
+        }));
+
+        let negotiated = async {
+            peer.add_transceiver_from_kind(
+                RTPCodecType::Audio,
+                &[RTCRtpTransceiverInit { direction: RTCRtpTransceiverDirection::Recvonly, send_encodings: vec![] }],
+            )
+            .await
+            .map_err(webrtc_error)?;
+            answer(&peer, offer).await
+        }
+        .await;
+        self.sessions.insert(session_id, MediaSession { kind: SessionKind::Publish, peer });
+        match negotiated {
+            Ok(sdp) => {
+                info!("Source WHIP connectée (session {})", session_id);
+                Ok((session_id, sdp))
+            }
+            Err(e) => {
+                self.close(session_id).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Session WHEP : lecture d'un stream live
+    pub async fn play(self: &Arc<Self>, stream_id: Uuid, offer: String, ip_address: String) -> Result<(Uuid, String), AppError> {
+        let session_id = Uuid::new_v4();
+        let bitrate = self.config.opus_bitrate;
+        self.streams
+            .add_listener(stream_id, Listener {
+                id: session_id,
+                user_id: None,
+                ip_address,
+                user_agent: None,
+                connected_at: Instant::now(),
+                current_quality: format!("{}k", bitrate),
+                bandwidth_estimate: bitrate * 1000,
+                buffer_health: 1.0,
+                session_data: HashMap::from([("protocol".to_string(), "whep".to_string())]),
+            })
+            .await?;
+
+        let track = match self.join_relay(stream_id) {
+            Ok(track) => track,
+            Err(e) => {
+                let _ = self.streams.remove_listener(stream_id, session_id).await;
+                return Err(e);
+            }
+        };
+        let peer = match self.new_peer(session_id).await {
+            Ok(peer) => peer,
+            Err(e) => {
+                self.leave_relay(stream_id, session_id).await;
+                return Err(e);
+            }
+        };
+        self.sessions.insert(session_id, MediaSession { kind: SessionKind::Play(stream_id), peer: peer.clone() });
+
+        let negotiated = async {
+            let sender = peer.add_track(track).await.map_err(webrtc_error)?;
+            // Les rapports RTCP doivent être lus pour que les intercepteurs fonctionnent
+            tokio::spawn(async move {
+                let mut buffer = vec![0u8; 1500];
+                while sender.read(&mut buffer).await.is_ok() {}
+            });
+            answer(&peer, offer).await
+        }
+        .await;
+        match negotiated {
+            Ok(sdp) => {
+                info!("Lecteur WHEP connecté au stream {} (session {})", stream_id, session_id);
+                Ok((session_id, sdp))
+            }
+            Err(e) => {
+                self.close(session_id).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Termine une session ; `false` si elle n'existe pas
+    pub async fn close(&self, session_id: Uuid) -> bool {
+        self.close_if(session_id, |_| true).await
+    }
+
+    /// Termine une session si son sens convient ; `false` sinon
+    async fn close_if(&self, session_id: Uuid, accept: impl Fn(SessionKind) -> bool) -> bool {
+        let Some((_, session)) = self.sessions.remove_if(&session_id, |_, session| accept(session.kind)) else {
+            return false;
+        };
+        if let Err(e) = session.peer.close().await {
+            debug!("Fermeture de la session WebRTC {}: {}", session_id, e);
+        }
+        self.manager.remove_peer(&session_id.to_string()).await;
+        if let SessionKind::Play(stream_id) = session.kind {
+            self.leave_relay(stream_id, session_id).await;
+        }
+        info!("Session WebRTC {} terminée", session_id);
+        true
+    }
+
+    async fn leave_relay(&self, stream_id: Uuid, session_id: Uuid) {
+        if let Some(relay) = self.relays.get(&stream_id) {
+            relay.viewers.fetch_sub(1, Ordering::SeqCst);
+        }
+        let _ = self.streams.remove_listener(stream_id, session_id).await;
+    }
+
+    /// Rejoint la piste du stream, en démarrant son encodage si besoin
+    fn join_relay(self: &Arc<Self>, stream_id: Uuid) -> Result<Arc<TrackLocalStaticSample>, AppError> {
+        // Sous le verrou de l'entrée : le relais ne peut pas s'arrêter entre-temps
+        let relay = match self.relays.entry(stream_id) {
+            dashmap::mapref::entry::Entry::Occupied(entry) => entry.get().clone(),
+            dashmap::mapref::entry::Entry::Vacant(entry) => entry.insert(self.start_relay(stream_id)?).clone(),
+        };
+        relay.viewers.fetch_add(1, Ordering::SeqCst);
+        Ok(relay.track.clone())
+    }
+
+    fn start_relay(self: &Arc<Self>, stream_id: Uuid) -> Result<Arc<WhepRelay>, AppError> {
+        let (format, mut pcm) = self
+            .streams
+            .subscribe_live_audio(stream_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("stream live {}", stream_id) })?;
+        let encoder = OpusEncoderImpl::with_opus_config(OpusEncoderConfig {
+            sample_rate: OPUS_SAMPLE_RATE,
+            channels: OPUS_CHANNELS,
+            bitrate: self.config.opus_bitrate * 1000,
+            frame_duration: OpusFrameDuration::Ms20,
+            ..OpusEncoderConfig::default()
+        })?;
+        let resampler = match format.sample_rate == OPUS_SAMPLE_RATE {
+            true => None,
+            false => Some(StreamResampler::new(format.sample_rate, OPUS_SAMPLE_RATE, usize::from(OPUS_CHANNELS))?),
+        };
+
+        let relay = Arc::new(WhepRelay {
+            track: Arc::new(TrackLocalStaticSample::new(opus_capability(), "audio".to_string(), format!("veza-{}", stream_id))),
+            viewers: AtomicUsize::new(0),
+        });
+        let task_relay = relay.clone();
+        let media: Weak<Self> = Arc::downgrade(self);
+        info!("Relais WHEP démarré pour le stream {}", stream_id);
+
+        // Le canal PCM se ferme à la fin du stream
+        tokio::spawn(async move {
+            let mut state = (encoder, resampler);
+            loop {
+                let samples = match pcm.recv().await {
+                    Ok(samples) => samples,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Relais WHEP du stream {} en retard, {} blocs perdus", stream_id, skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let channels = usize::from(format.channels.max(1));
+                let encoded = encode_blocking(state, move |(encoder, resampler)| {
+                    let remixed = remix_channels(&samples, channels, usize::from(OPUS_CHANNELS));
+                    let resampled = match resampler.as_mut() {
+                        Some(resampler) => resampler.process(&remixed)?,
+                        None => remixed,
+                    };
+                    encoder.encode_packets(&resampled)
+                })
+                .await;
+                let packets = match encoded {
+                    Ok((returned, packets)) => {
+                        state = returned;
+                        packets
+                    }
+                    Err(e) => {
+                        warn!("Encodage Opus du stream {} impossible: {}", stream_id, e);
+                        break;
+                    }
+                };
+
+                for packet in packets {
+                    let sample = Sample {
+                        data: Bytes::from(packet),
+                        timestamp: SystemTime::now(),
+                        duration: OPUS_FRAME,
+                        packet_timestamp: 0,
+                        prev_dropped_packets: 0,
+                        prev_padding_packets: 0,
+                    };
+                    if let Err(e) = task_relay.track.write_sample(&sample).await {
+                        debug!("Envoi WHEP du stream {}: {}", stream_id, e);
+                    }
+                }
+
+                // Plus de lecteur : arrêt sous le verrou de l'entrée
+                let Some(media) = media.upgrade() else { return };
+                if media
+                    .relays
+                    .remove_if(&stream_id, |_, relay| relay.viewers.load(Ordering::SeqCst) == 0)
+                    .is_some()
+                {
+                    debug!("Relais WHEP du stream {} arrêté : plus de lecteur", stream_id);
+                    return;
+                }
+            }
+
+            // Fin du stream : les lecteurs restants sont déconnectés
+            let Some(media) = media.upgrade() else { return };
+            media.relays.remove_if(&stream_id, |_, relay| Arc::ptr_eq(relay, &task_relay));
+            let viewers: Vec<Uuid> = media
+                .sessions
+                .iter()
+                .filter(|session| session.kind == SessionKind::Play(stream_id))
+                .map(|session| *session.key())
+                .collect();
+            for session_id in viewers {
+                media.close(session_id).await;
+            }
+            info!("Relais WHEP du stream {} terminé", stream_id);
+        });
+
+        Ok(relay)
+    }
+}
+
+/// Décode l'Opus reçu d'une source WHIP et alimente sa `LiveSource`
+async fn receive_opus(track: Arc<TrackRemote>, mut source: LiveSource, idle_timeout: Duration, session_id: Uuid) {
+    let mut decoder = match OpusDecoderImpl::with_opus_config(OpusDecoderConfig {
+        sample_rate: OPUS_SAMPLE_RATE,
+        channels: OPUS_CHANNELS,
+        frame_duration: OpusFrameDuration::Ms20,
+        gain_db: 0.0,
+    }) {
+        Ok(decoder) => decoder,
+        Err(e) => {
+            warn!("Décodeur Opus WHIP indisponible: {}", e);
+            return;
+        }
+    };
+
+    let mut last_sequence: Option<u16> = None;
+    loop {
+        let packet = match timeout(idle_timeout, track.read_rtp()).await {
+            Ok(Ok((packet, _))) => packet,
+            Ok(Err(e)) => {
+                debug!("Piste WHIP de la session {} fermée: {}", session_id, e);
+                break;
+            }
+            Err(_) => {
+                info!("Source WHIP {} inactive depuis {:?}, déconnexion", session_id, idle_timeout);
+                break;
+            }
+        };
+
+        // Dissimulation des pertes courtes ; doublons et paquets en retard ignorés
+        let sequence = packet.header.sequence_number;
+        let lost = match last_sequence {
+            Some(last) => match sequence.wrapping_sub(last) {
+                0 | 0x8000.. => continue,
+                gap => gap - 1,
+            },
+            None => 0,
+        };
+        last_sequence = Some(sequence);
+        if packet.payload.is_empty() {
+            continue;
+        }
+
+        let mut frames = Vec::new();
+        if lost <= MAX_CONCEALED_PACKETS {
+            frames.extend((0..lost).map(|_| decoder.decode(&[])));
+        }
+        frames.push(decoder.decode(&packet.payload));
+        for frame in frames {
+            let pushed = match frame {
+                Ok(audio) => source.push(&audio).await,
+                Err(e) => {
+                    debug!("Paquet Opus WHIP illisible: {}", e);
+                    Ok(())
+                }
+            };
+            if let Err(e) = pushed {
+                warn!("Source WHIP {} interrompue: {}", session_id, e);
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct WebRtcMediaState {
+    config: Arc<Config>,
+    media: Arc<WebRtcMedia>,
+}
+
+pub fn webrtc_media_routes<S>(config: Arc<Config>, media: Arc<WebRtcMedia>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/whip", post(whip_publish))
+        .route("/whip/:session_id", delete(delete_session))
+        .route("/whep/:stream_id", post(whep_play))
+        .route("/whep/:stream_id/:session_id", delete(delete_whep_session))
+        .with_state(WebRtcMediaState { config, media })
+}
+
+/// Offre SDP du corps ; `None` si le type de contenu n'est pas `application/sdp`
+fn sdp_offer(headers: &HeaderMap, body: String) -> Option<String> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(';').next().is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/sdp")))
+        .then_some(body)
+}
+
+fn session_created(uri: &axum::http::Uri, session_id: Uuid, sdp: String) -> Response {
+    let location = format!("{}/{}", uri.path().trim_end_matches('/'), session_id);
+    (
+        StatusCode::CREATED,
+        [(header::CONTENT_TYPE, "application/sdp".to_string()), (header::LOCATION, location)],
+        sdp,
+    )
+        .into_response()
+}
+
+async fn whip_publish(
+    State(state): State<WebRtcMediaState>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, AppError> {
+    let key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized)?
+        .trim()
+        .to_string();
+    let Some(offer) = sdp_offer(&headers, body) else {
+        return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+    };
+    let (session_id, sdp) = state.media.publish(&key, offer).await?;
+    Ok(session_created(&uri, session_id, sdp))
+}
+
+async fn whep_play(
+    State(state): State<WebRtcMediaState>,
+    OriginalUri(uri): OriginalUri,
+    AxumPath(stream_id): AxumPath<Uuid>,
+    Query(params): Query<AdaptiveStreamQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, AppError> {
+    if !validate_signature(&state.config, &stream_id.to_string(), &params.expires, &params.sig) {
+        return Err(AppError::Forbidden);
+    }
+    let Some(offer) = sdp_offer(&headers, body) else {
+        return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response());
+    };
+    let ip_address = headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    let (session_id, sdp) = state.media.play(stream_id, offer, ip_address).await?;
+    Ok(session_created(&uri, session_id, sdp))
+}
+
+async fn delete_session(
+    State(state): State<WebRtcMediaState>,
+    AxumPath(session_id): AxumPath<Uuid>,
+) -> StatusCode {
+    match state.media.close_if(session_id, |kind| kind == SessionKind::Publish).await {
+        true => StatusCode::OK,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn delete_whep_session(
+    State(state): State<WebRtcMediaState>,
+    AxumPath((stream_id, session_id)): AxumPath<(Uuid, Uuid)>,
+) -> StatusCode {
+    match state.media.close_if(session_id, |kind| kind == SessionKind::Play(stream_id)).await {
+        true => StatusCode::OK,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{StreamConfig, StreamManager};
+    use crate::streaming::ingest::LiveIngestConfig;
+    use crate::streaming::webrtc::WebRTCConfig;
+    use crate::utils::signed_query;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tokio::sync::mpsc;
+    use tower::ServiceExt;
+
+    async fn loopback_api() -> API {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        build_api(socket, &["127.0.0.1".to_string()]).unwrap()
+    }
+
+    async fn setup() -> (Arc<StreamManager>, Arc<LiveIngest>, Arc<WebRtcMedia>) {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+        let manager = Arc::new(WebRTCManager::new(WebRTCConfig { ice_servers: vec![], ..WebRTCConfig::default() }));
+        let media = WebRtcMedia::new(manager, ingest.clone(), WebRtcMediaConfig {
+            udp_addr: "127.0.0.1:0".parse().unwrap(),
+            public_ips: vec!["127.0.0.1".to_string()],
+            opus_bitrate: 64,
+            idle_timeout: Duration::from_secs(10),
+        })
+        .await
+        .unwrap();
+        (streams, ingest, Arc::new(media))
+    }
+
+    /// Offre complète (candidats inclus) d'un client
+    async fn client_offer(peer: &RTCPeerConnection) -> String {
+        let offer = peer.create_offer(None).await.unwrap();
+        let mut gathered = peer.gathering_complete_promise().await;
+        peer.set_local_description(offer).await.unwrap();
+        let _ = gathered.recv().await;
+        peer.local_description().await.unwrap().sdp
+    }
+
+    async fn wait_for<T>(mut check: impl FnMut() -> Option<T>) -> Option<T> {
+        for _ in 0..250 {
+            if let Some(value) = check() {
+                return Some(value);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        None
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_whip_source_reaches_whep_viewer() {
+        let (streams, ingest, media) = setup().await;
+        let key = ingest.issue_key(5, "Live room".to_string(), None).unwrap();
+
+        // Source WHIP : une piste Opus envoyée en continu
+        let publisher = Arc::new(loopback_api().await.new_peer_connection(RTCConfiguration::default()).await.unwrap());
+        let track = Arc::new(TrackLocalStaticSample::new(opus_capability(), "audio".to_string(), "dj".to_string()));
+        publisher.add_track(track.clone()).await.unwrap();
+        let offer = client_offer(&publisher).await;
+        assert!(matches!(media.publish("wrong", offer.clone()).await, Err(AppError::Unauthorized)));
+        let (publish_session, answer_sdp) = media.publish(&key.key, offer).await.unwrap();
+        publisher.set_remote_description(RTCSessionDescription::answer(answer_sdp).unwrap()).await.unwrap();
+
+        tokio::spawn(async move {
+            let mut encoder = OpusEncoderImpl::with_opus_config(OpusEncoderConfig {
+                frame_duration: OpusFrameDuration::Ms20,
+                ..OpusEncoderConfig::default()
+            })
+            .unwrap();
+            let mut interval = tokio::time::interval(OPUS_FRAME);
+            for i in 0..500usize {
+                interval.tick().await;
+                let pcm: Vec<f32> = (0..960)
+                    .flat_map(|n| {
+                        let sample = ((i * 960 + n) as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin() * 0.5;
+                        [sample, sample]
+                    })
+                    .collect();
+                for packet in encoder.encode_packets(&pcm).unwrap() {
+                    let sample = Sample {
+                        data: Bytes::from(packet),
+                        timestamp: SystemTime::now(),
+                        duration: OPUS_FRAME,
+                        packet_timestamp: 0,
+                        prev_dropped_packets: 0,
+                        prev_padding_packets: 0,
+                    };
+                    if track.write_sample(&sample).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let mount = key_mount("whip", &key.key);
+        let stream_id = wait_for(|| ingest.stream_for_mount(&mount)).await.expect("stream créé par la source WHIP");
+        let format = streams.live_format(stream_id).unwrap();
+        assert_eq!((format.sample_rate, format.channels), (48000, 2));
+
+        // Lecteur WHEP : réception des paquets Opus du relais
+        let viewer = loopback_api().await.new_peer_connection(RTCConfiguration::default()).await.unwrap();
+        viewer
+            .add_transceiver_from_kind(
+                RTPCodecType::Audio,
+                &[RTCRtpTransceiverInit { direction: RTCRtpTransceiverDirection::Recvonly, send_encodings: vec![] }],
+            )
+            .await
+            .unwrap();
+        let (packets_tx, mut packets_rx) = mpsc::channel(64);
+        viewer.on_track(Box::new(move |track, _receiver| {
+            let packets_tx = packets_tx.clone();
+            Box::pin(async move {
+                let Some(track) = track else { return };
+                while let Ok((packet, _)) = track.read_rtp().await {
+                    if packets_tx.send(packet.payload.to_vec()).await.is_err() {
+                        break;
+                    }
+                }
+            })
+        }));
+        let offer = client_offer(&viewer).await;
+        let (play_session, answer_sdp) = media.play(stream_id, offer, "127.0.0.1".to_string()).await.unwrap();
+        viewer.set_remote_description(RTCSessionDescription::answer(answer_sdp).unwrap()).await.unwrap();
+
+        let mut decoder = OpusDecoderImpl::with_opus_config(OpusDecoderConfig::default()).unwrap();
+        for _ in 0..10 {
+            let payload = timeout(Duration::from_secs(10), packets_rx.recv()).await.unwrap().unwrap();
+            assert_eq!(decoder.decode(&payload).unwrap().duration_ms, 20);
+        }
+        assert_eq!(media.session_count(), 2);
+
+        // Chaque DELETE ne termine que les sessions de son sens et de son stream
+        let app = webrtc_media_routes::<()>(Arc::new(Config::from_env().unwrap()), media.clone());
+        for uri in [
+            format!("/whip/{}", play_session),
+            format!("/whep/{}/{}", stream_id, publish_session),
+            format!("/whep/{}/{}", Uuid::new_v4(), play_session),
+        ] {
+            let response = app.clone().oneshot(Request::delete(uri).body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(media.session_count(), 2);
+
+        // Fin de la source : le stream se termine et le lecteur est déconnecté
+        assert!(media.close(publish_session).await);
+        assert!(wait_for(|| streams.live_format(stream_id).is_none().then_some(())).await.is_some());
+        assert!(wait_for(|| (media.session_count() == 0).then_some(())).await.is_some());
+        assert!(!media.close(play_session).await);
+        let _ = publisher.close().await;
+        let _ = viewer.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_whip_requires_key_and_sdp() {
+        let (_, _, media) = setup().await;
+        let config = Arc::new(Config::from_env().unwrap());
+        let app = webrtc_media_routes::<()>(config.clone(), media);
+
+        // Lecture WHEP : URL signée obligatoire
+        let stream_id = Uuid::new_v4();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(format!("/whep/{}?expires=1&sig=00", stream_id))
+                    .header(header::CONTENT_TYPE, "application/sdp")
+                    .body(Body::from("v=0"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(format!("/whep/{}?{}", stream_id, signed_query(&config, &stream_id.to_string())))
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = app
+            .clone()
+            .oneshot(Request::post("/whip").header(header::CONTENT_TYPE, "application/sdp").body(Body::from("v=0")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/whip")
+                    .header(header::AUTHORIZATION, "Bearer key")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = app
+            .oneshot(Request::delete(format!("/whip/{}", Uuid::new_v4())).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,773 @@
+//! Protocole binaire de diffusion audio sur WebSocket
+//!
+//! Format des streams `StreamProtocol::WebSocket { binary_mode: true }`,
+//! négocié avec le sous-protocole `veza-audio.v1`. Chaque message binaire
+//! commence par un en-tête de 16 octets :
+//!
+//! - octet 0 : version du protocole ;
+//! - octet 1 : type de trame ;
+//! - octet 2 : drapeaux (`FLAG_DISCONTINUITY`) ;
+//! - octet 3 : réservé, à 0 ;
+//! - octets 4..8 : numéro de séquence de la trame (u32 big-endian) ;
+//! - octets 8..16 : timestamp (u64 big-endian), en unités de `timescale`.
+//!
+//! Le serveur envoie `CodecConfig` (JSON, dont le type MIME à passer à
+//! `addSourceBuffer`), puis `Init` (segment d'initialisation fMP4), puis des
+//! trames `Media` (fragments `moof` + `mdat` directement passés à
+//! `appendBuffer`, timestamp = decode time), des trames `Metadata` (JSON de la
+//! piste en cours) et `End` à la fin du stream. Le client accorde des crédits
+//! avec des trames `Credit` (u32 big-endian) : chaque trame `Media` en consomme
+//! un, les autres trames n'en consomment pas. Un fragment resté en attente plus
+//! de `max_latency` est abandonné, et la trame `Media` suivante porte
+//! `FLAG_DISCONTINUITY` pour que le lecteur se recale sur le direct.
+//!
+//! La connexion exige la même URL signée (`expires`, `sig` sur l'identifiant
+//! du stream) que le HLS live.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path as AxumPath, Query, State,
+    },
+    http::HeaderMap,
+    response::Response,
+    routing::get,
+    Router,
+};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::codecs::{CodecQuality, EncoderConfig, LatencyMode};
+use crate::config::{Config, LiveConfig};
+use crate::core::{Listener, StreamManager, TrackInfo};
+use crate::error::AppError;
+use crate::streaming::fmp4::{self, Fmp4Track};
+use crate::streaming::segmenter::{
+    create_segment_encoder, encode_blocking, remix_channels, split_frames, StreamResampler, DEFAULT_FMP4_CODEC,
+};
+use crate::utils::validate_signature;
+
+/// Version du format des trames
+pub const PROTOCOL_VERSION: u8 = 1;
+/// Sous-protocole WebSocket négocié à la connexion
+pub const SUBPROTOCOL: &str = "veza-audio.v1";
+/// Taille de l'en-tête de chaque trame
+pub const HEADER_SIZE: usize = 16;
+/// La trame suit des fragments abandonnés : le timeline n'est plus continu
+pub const FLAG_DISCONTINUITY: u8 = 0x01;
+
+/// Format de sortie des relais
+const RELAY_SAMPLE_RATE: u32 = 44100;
+const RELAY_CHANNELS: u8 = 2;
+/// Événements en attente par client
+const RELAY_CHANNEL_SIZE: usize = 256;
+
+/// Type d'une trame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    CodecConfig = 1,
+    Init = 2,
+    Media = 3,
+    Metadata = 4,
+    End = 5,
+    /// Client → serveur : crédits supplémentaires
+    Credit = 16,
+}
+
+impl FrameType {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::CodecConfig),
+            2 => Some(Self::Init),
+            3 => Some(Self::Media),
+            4 => Some(Self::Metadata),
+            5 => Some(Self::End),
+            16 => Some(Self::Credit),
+            _ => None,
+        }
+    }
+}
+
+/// Trame du protocole
+#[derive(Debug, Clone, PartialEq)]
+pub struct WsFrame {
+    pub frame_type: FrameType,
+    pub flags: u8,
+    pub sequence: u32,
+    pub timestamp: u64,
+    pub payload: Vec<u8>,
+}
+
+impl WsFrame {
+    pub fn new(frame_type: FrameType, payload: Vec<u8>) -> Self {
+        Self { frame_type, flags: 0, sequence: 0, timestamp: 0, payload }
+    }
+
+    /// Trame de crédit envoyée par le client
+    pub fn credit(credits: u32) -> Self {
+        Self::new(FrameType::Credit, credits.to_be_bytes().to_vec())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(HEADER_SIZE + self.payload.len());
+        output.extend_from_slice(&[PROTOCOL_VERSION, self.frame_type as u8, self.flags, 0]);
+        output.extend_from_slice(&self.sequence.to_be_bytes());
+        output.extend_from_slice(&self.timestamp.to_be_bytes());
+        output.extend_from_slice(&self.payload);
+        output
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, AppError> {
+        if data.len() < HEADER_SIZE {
+            return Err(AppError::InvalidData { message: "Trame WebSocket tronquée".to_string() });
+        }
+        if data[0] != PROTOCOL_VERSION {
+            return Err(AppError::InvalidData { message: format!("Version de protocole non gérée: {}", data[0]) });
+        }
+        let frame_type = FrameType::from_u8(data[1])
+            .ok_or_else(|| AppError::InvalidData { message: format!("Type de trame inconnu: {}", data[1]) })?;
+        Ok(Self {
+            frame_type,
+            flags: data[2],
+            sequence: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            timestamp: u64::from_be_bytes([data[8], data[9], data[10], data[11], data[12], data[13], data[14], data[15]]),
+            payload: data[HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    /// Crédits portés par une trame `Credit`
+    pub fn credits(&self) -> Option<u32> {
+        match (self.frame_type, self.payload.get(..4)) {
+            (FrameType::Credit, Some(bytes)) => Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            _ => None,
+        }
+    }
+}
+
+/// Contenu de la trame `CodecConfig`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CodecConfig {
+    pub version: u8,
+    pub codec: String,
+    /// Type MIME complet, pour `MediaSource.addSourceBuffer`
+    pub mime_type: String,
+    pub sample_rate: u32,
+    pub channels: u8,
+    /// Unités des timestamps des trames `Media`
+    pub timescale: u32,
+    pub bitrate: u32,
+    /// Listener du client, à passer à la WebSocket d'horloge (`/sync/clock`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listener_id: Option<Uuid>,
+}
+
+/// Paramètres des relais WebSocket
+#[derive(Debug, Clone)]
+pub struct WsAudioConfig {
+    /// Débit des relais, en kbps
+    pub bitrate: u32,
+    /// Âge maximal d'un fragment en attente d'envoi
+    pub max_latency: Duration,
+}
+
+impl Default for WsAudioConfig {
+    fn default() -> Self {
+        Self { bitrate: 128, max_latency: Duration::from_secs(2) }
+    }
+}
+
+impl WsAudioConfig {
+    pub fn from_config(config: &LiveConfig) -> Self {
+        Self { bitrate: config.ws_audio_bitrate, max_latency: config.ws_audio_max_latency }
+    }
+}
+
+/// Fragment fMP4 produit par un relais
+#[derive(Debug)]
+pub struct MediaFragment {
+    /// Decode time du premier échantillon, en unités de timescale
+    pub decode_time: u64,
+    pub data: Vec<u8>,
+    pub produced_at: Instant,
+}
+
+#[derive(Debug, Clone)]
+enum RelayEvent {
+    Fragment(Arc<MediaFragment>),
+    Metadata(Arc<Vec<u8>>),
+}
+
+/// Relais encodé d'un stream, partagé par ses clients
+#[derive(Debug)]
+struct WsAudioRelay {
+    codec_config: CodecConfig,
+    init_segment: Vec<u8>,
+    /// Dernières métadonnées, envoyées aux nouveaux clients
+    metadata: Mutex<Option<Arc<Vec<u8>>>>,
+    sender: broadcast::Sender<RelayEvent>,
+}
+
+/// Abonnement d'un client à un relais
+#[derive(Debug)]
+struct WsAudioSubscription {
+    codec_config: CodecConfig,
+    init_segment: Vec<u8>,
+    metadata: Option<Arc<Vec<u8>>>,
+    receiver: broadcast::Receiver<RelayEvent>,
+}
+
+/// Relais actifs, un par stream et par codec
+#[derive(Debug)]
+pub struct WsAudioRelays {
+    config: WsAudioConfig,
+    streams: Arc<StreamManager>,
+    relays: Arc<DashMap<(Uuid, &'static str), Arc<WsAudioRelay>>>,
+}
+
+fn metadata_payload(track: &TrackInfo) -> Option<Arc<Vec<u8>>> {
+    serde_json::to_vec(track).ok().map(Arc::new)
+}
+
+impl WsAudioRelays {
+    pub fn new(streams: Arc<StreamManager>, config: WsAudioConfig) -> Self {
+        Self {
+            config,
+            streams,
+            relays: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &WsAudioConfig {
+        &self.config
+    }
+
+    fn subscribe(&self, stream_id: Uuid, codec: &'static str) -> Result<WsAudioSubscription, AppError> {
+        // Abonnement sous le verrou de l'entrée : le relais ne peut pas s'arrêter entre-temps
+        let relay = match self.relays.entry((stream_id, codec)) {
+            dashmap::mapref::entry::Entry::Occupied(entry) => entry.get().clone(),
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                let relay = self.start_relay(stream_id, codec)?;
+                let subscription = relay.sender.subscribe();
+                entry.insert(relay.clone());
+                return Ok(WsAudioSubscription {
+                    codec_config: relay.codec_config.clone(),
+                    init_segment: relay.init_segment.clone(),
+                    metadata: relay.metadata.lock().clone(),
+                    receiver: subscription,
+                });
+            }
+        };
+        let metadata = relay.metadata.lock();
+        Ok(WsAudioSubscription {
+            codec_config: relay.codec_config.clone(),
+            init_segment: relay.init_segment.clone(),
+            metadata: metadata.clone(),
+            receiver: relay.sender.subscribe(),
+        })
+    }
+
+    fn start_relay(&self, stream_id: Uuid, codec: &'static str) -> Result<Arc<WsAudioRelay>, AppError> {
+        let (format, mut pcm) = self
+            .streams
+            .subscribe_live_audio(stream_id)
+            .ok_or_else(|| AppError::NotFound { resource: format!("stream live {}", stream_id) })?;
+        let bitrate = self.config.bitrate * 1000;
+        let (encoder, fmp4_codec) = create_segment_encoder(codec, EncoderConfig {
+            bitrate,
+            sample_rate: RELAY_SAMPLE_RATE,
+            channels: RELAY_CHANNELS,
+            quality: CodecQuality::High,
+            latency_mode: LatencyMode::Low,
+            enable_vbr: false,
+            complexity: 5,
+        })?;
+        let resampler = match format.sample_rate == RELAY_SAMPLE_RATE {
+            true => None,
+            false => Some(StreamResampler::new(format.sample_rate, RELAY_SAMPLE_RATE, usize::from(RELAY_CHANNELS))?),
+        };
+
+        let track = Fmp4Track {
+            track_id: 1,
+            timescale: RELAY_SAMPLE_RATE,
+            sample_rate: RELAY_SAMPLE_RATE,
+            channels: u16::from(RELAY_CHANNELS),
+            codec: fmp4_codec.clone(),
+            avg_bitrate: bitrate,
+            max_bitrate: bitrate,
+        };
+        let codec_config = CodecConfig {
+            version: PROTOCOL_VERSION,
+            codec: codec.to_string(),
+            mime_type: format!("audio/mp4; codecs=\"{}\"", fmp4_codec.codecs_string()),
+            sample_rate: RELAY_SAMPLE_RATE,
+            channels: RELAY_CHANNELS,
+            timescale: RELAY_SAMPLE_RATE,
+            bitrate,
+            listener_id: None,
+        };
+        let current_track = self.streams.current_track(stream_id);
+        let relay = Arc::new(WsAudioRelay {
+            codec_config,
+            init_segment: fmp4::init_segment(&track),
+            metadata: Mutex::new(current_track.as_ref().and_then(metadata_payload)),
+            sender: broadcast::channel(RELAY_CHANNEL_SIZE).0,
+        });
+
+        let streams = self.streams.clone();
+        let relays = self.relays.clone();
+        let task_relay = relay.clone();
+        info!("Relais WebSocket {} démarré pour le stream {}", codec, stream_id);
+
+        tokio::spawn(async move {
+            let mut state = (encoder, resampler);
+            let mut pending = Vec::new();
+            let mut decode_time = 0u64;
+            let mut sequence = 0u32;
+            let mut last_title = current_track.map(|track| track.title);
+            loop {
+                let samples = match pcm.recv().await {
+                    Ok(samples) => samples,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Relais WebSocket du stream {} en retard, {} blocs perdus", stream_id, skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+
+                let channels = usize::from(format.channels.max(1));
+                let encoded = encode_blocking(state, move |(encoder, resampler)| {
+                    let remixed = remix_channels(&samples, channels, usize::from(RELAY_CHANNELS));
+                    let resampled = match resampler.as_mut() {
+                        Some(resampler) => resampler.process(&remixed)?,
+                        None => remixed,
+                    };
+                    encoder.encode(&resampled, RELAY_SAMPLE_RATE, RELAY_CHANNELS)
+                })
+                .await;
+                match encoded {
+                    Ok((returned, data)) => {
+                        state = returned;
+                        pending.extend_from_slice(&data);
+                    }
+                    Err(e) => {
+                        warn!("Encodage du relais WebSocket du stream {} impossible: {}", stream_id, e);
+                        break;
+                    }
+                }
+
+                // Une frame incomplète reste en attente du prochain bloc
+                let (frames, consumed) = split_frames(&track.codec, &pending);
+                pending.drain(..consumed);
+                if !frames.is_empty() {
+                    sequence += 1;
+                    let duration: u64 = frames.iter().map(|frame| u64::from(frame.duration)).sum();
+                    let fragment = MediaFragment {
+                        decode_time,
+                        data: fmp4::media_segment(&track, sequence, decode_time, &frames),
+                        produced_at: Instant::now(),
+                    };
+                    decode_time += duration;
+                    let _ = task_relay.sender.send(RelayEvent::Fragment(Arc::new(fragment)));
+                }
+
+                let current = streams.current_track(stream_id);
+                if current.as_ref().map(|track| &track.title) != last_title.as_ref() {
+                    let metadata = current.as_ref().and_then(metadata_payload);
+                    *task_relay.metadata.lock() = metadata.clone();
+                    if let Some(metadata) = metadata {
+                        let _ = task_relay.sender.send(RelayEvent::Metadata(metadata));
+                    }
+                    last_title = current.map(|track| track.title);
+                }
+
+                // Plus de client : arrêt sous le verrou de l'entrée
+                if relays
+                    .remove_if(&(stream_id, codec), |_, relay| relay.sender.receiver_count() == 0)
+                    .is_some()
+                {
+                    debug!("Relais WebSocket {} du stream {} arrêté : plus de client", codec, stream_id);
+                    return;
+                }
+            }
+            relays.remove_if(&(stream_id, codec), |_, relay| Arc::ptr_eq(relay, &task_relay));
+            info!("Relais WebSocket {} du stream {} terminé", codec, stream_id);
+        });
+
+        Ok(relay)
+    }
+}
+
+/// Contrôle de flux d'un client : crédits accordés et abandon des
+/// fragments périmés
+#[derive(Debug)]
+pub struct CreditQueue {
+    credits: u32,
+    queue: VecDeque<Arc<MediaFragment>>,
+    max_latency: Duration,
+    /// Des fragments ont été perdus depuis le dernier envoi
+    discontinuity: bool,
+    dropped: u64,
+}
+
+impl CreditQueue {
+    pub fn new(credits: u32, max_latency: Duration) -> Self {
+        Self {
+            credits,
+            queue: VecDeque::new(),
+            max_latency,
+            discontinuity: false,
+            dropped: 0,
+        }
+    }
+
+    pub fn grant(&mut self, credits: u32) {
+        self.credits = self.credits.saturating_add(credits);
+    }
+
+    pub fn credits(&self) -> u32 {
+        self.credits
+    }
+
+    /// Fragments abandonnés depuis la connexion
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn push(&mut self, fragment: Arc<MediaFragment>, now: Instant) {
+        self.queue.push_back(fragment);
+        self.drop_stale(now);
+    }
+
+    /// Fragments perdus en amont (client trop lent pour le relais)
+    pub fn mark_discontinuity(&mut self) {
+        self.discontinuity = true;
+    }
+
+    /// Prochain fragment à envoyer s'il reste du crédit, avec l'indicateur de
+    /// discontinuité
+    pub fn next(&mut self, now: Instant) -> Option<(Arc<MediaFragment>, bool)> {
+        self.drop_stale(now);
+        if self.credits == 0 {
+            return None;
+        }
+        let fragment = self.queue.pop_front()?;
+        self.credits -= 1;
+        Some((fragment, std::mem::take(&mut self.discontinuity)))
+    }
+
+    fn drop_stale(&mut self, now: Instant) {
+        while self
+            .queue
+            .front()
+            .is_some_and(|fragment| now.saturating_duration_since(fragment.produced_at) > self.max_latency)
+        {
+            self.queue.pop_front();
+            self.dropped += 1;
+            self.discontinuity = true;
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WsAudioQuery {
+    expires: String,
+    sig: String,
+    /// `aac` ou `mp3` ; par défaut `DEFAULT_FMP4_CODEC`
+    codec: Option<String>,
+    /// Crédits accordés dès la connexion
+    credits: Option<u32>,
+}
+
+#[derive(Clone)]
+struct WsAudioState {
+    config: Arc<Config>,
+    relays: Arc<WsAudioRelays>,
+}
+
+pub fn ws_audio_routes<S>(config: Arc<Config>, relays: Arc<WsAudioRelays>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/streams/:stream_id", get(ws_audio_handler))
+        .with_state(WsAudioState { config, relays })
+}
+
+async fn ws_audio_handler(
+    State(state): State<WsAudioState>,
+    AxumPath(stream_id): AxumPath<Uuid>,
+    Query(query): Query<WsAudioQuery>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    if !validate_signature(&state.config, &stream_id.to_string(), &query.expires, &query.sig) {
+        return Err(AppError::Forbidden);
+    }
+    let codec = match query.codec.as_deref().unwrap_or(DEFAULT_FMP4_CODEC) {
+        #[cfg(feature = "fdk-aac")]
+        "aac" => "aac",
+        "mp3" => "mp3",
+        codec => return Err(AppError::UnsupportedCodec { codec: codec.to_string() }),
+    };
+    let streams = state.relays.streams.clone();
+    if streams.live_format(stream_id).is_none() {
+        return Err(AppError::NotFound { resource: format!("stream live {}", stream_id) });
+    }
+
+    let bitrate = state.relays.config.bitrate;
+    let listener = Listener {
+        id: Uuid::new_v4(),
+        user_id: None,
+        ip_address: headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        user_agent: headers.get("user-agent").and_then(|value| value.to_str().ok()).map(str::to_string),
+        connected_at: Instant::now(),
+        current_quality: format!("{}k", bitrate),
+        bandwidth_estimate: bitrate * 1000,
+        buffer_health: 1.0,
+        session_data: HashMap::from([("protocol".to_string(), "websocket".to_string())]),
+    };
+    let listener_id = listener.id;
+    streams.add_listener(stream_id, listener).await?;
+
+    let relays = state.relays.clone();
+    let credits = query.credits.unwrap_or(0);
+    Ok(ws.protocols([SUBPROTOCOL]).on_upgrade(move |socket| async move {
+        if let Err(e) = serve_client(socket, &relays, stream_id, codec, credits, listener_id).await {
+            debug!("Client WebSocket du stream {} déconnecté: {}", stream_id, e);
+        }
+        let _ = relays.streams.remove_listener(stream_id, listener_id).await;
+    }))
+}
+
+/// Numérote et envoie les trames d'un client
+struct FrameSender {
+    socket: WebSocket,
+    sequence: u32,
+}
+
+impl FrameSender {
+    async fn send(&mut self, mut frame: WsFrame) -> Result<(), AppError> {
+        frame.sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.socket
+            .send(Message::Binary(frame.encode()))
+            .await
+            .map_err(|e| AppError::NetworkError { message: e.to_string() })
+    }
+}
+
+async fn serve_client(
+    socket: WebSocket,
+    relays: &WsAudioRelays,
+    stream_id: Uuid,
+    codec: &'static str,
+    credits: u32,
+    listener_id: Uuid,
+) -> Result<(), AppError> {
+    let mut sender = FrameSender { socket, sequence: 0 };
+    let mut subscription = match relays.subscribe(stream_id, codec) {
+        Ok(subscription) => subscription,
+        Err(e) => {
+            sender.send(WsFrame::new(FrameType::End, Vec::new())).await?;
+            return Err(e);
+        }
+    };
+    let codec_config = CodecConfig { listener_id: Some(listener_id), ..subscription.codec_config };
+    let codec_config = serde_json::to_vec(&codec_config).map_err(|_| AppError::SerializationError)?;
+    sender.send(WsFrame::new(FrameType::CodecConfig, codec_config)).await?;
+    sender.send(WsFrame::new(FrameType::Init, subscription.init_segment)).await?;
+    if let Some(metadata) = subscription.metadata {
+        sender.send(WsFrame::new(FrameType::Metadata, metadata.to_vec())).await?;
+    }
+
+    let mut queue = CreditQueue::new(credits, relays.config.max_latency);
+    let mut last_decode_time = 0;
+    loop {
+        while let Some((fragment, discontinuity)) = queue.next(Instant::now()) {
+            let mut frame = WsFrame::new(FrameType::Media, fragment.data.clone());
+            frame.timestamp = fragment.decode_time;
+            if discontinuity {
+                frame.flags |= FLAG_DISCONTINUITY;
+            }
+            last_decode_time = fragment.decode_time;
+            sender.send(frame).await?;
+        }
+
+        tokio::select! {
+            message = sender.socket.recv() => match message {
+                Some(Ok(Message::Binary(data))) => match WsFrame::decode(&data) {
+                    Ok(frame) => match frame.credits() {
+                        Some(credits) => queue.grant(credits),
+                        None => debug!("Trame {:?} ignorée du client", frame.frame_type),
+                    },
+                    Err(e) => debug!("Trame client invalide: {}", e),
+                },
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(AppError::NetworkError { message: e.to_string() }),
+            },
+            event = subscription.receiver.recv() => match event {
+                Ok(RelayEvent::Fragment(fragment)) => queue.push(fragment, Instant::now()),
+                Ok(RelayEvent::Metadata(metadata)) => {
+                    let mut frame = WsFrame::new(FrameType::Metadata, metadata.to_vec());
+                    frame.timestamp = last_decode_time;
+                    sender.send(frame).await?;
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    debug!("Client WebSocket du stream {} en retard, {} événements perdus", stream_id, skipped);
+                    queue.mark_discontinuity();
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    sender.send(WsFrame::new(FrameType::End, Vec::new())).await?;
+                    break;
+                }
+            },
+        }
+    }
+
+    if queue.dropped() > 0 {
+        info!("Client WebSocket du stream {} : {} fragments abandonnés", stream_id, queue.dropped());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::{AudioSampleFormat, DecodedAudio};
+    use crate::core::StreamConfig;
+    use crate::streaming::ingest::{LiveIngest, LiveIngestConfig};
+    use crate::streaming::segmenter::codecs_string;
+    use crate::utils::signed_query;
+    use futures::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message as ClientMessage};
+
+    fn fragment(decode_time: u64, produced_at: Instant) -> Arc<MediaFragment> {
+        Arc::new(MediaFragment { decode_time, data: vec![0; 8], produced_at })
+    }
+
+    async fn next_frame(
+        client: &mut (impl futures::Stream<Item = Result<ClientMessage, tokio_tungstenite::tungstenite::Error>> + Unpin),
+    ) -> WsFrame {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(10), client.next()).await.unwrap().unwrap().unwrap();
+            if let ClientMessage::Binary(data) = message {
+                return WsFrame::decode(&data).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_credits_and_stale_fragments() {
+        let start = Instant::now();
+        let mut queue = CreditQueue::new(1, Duration::from_millis(500));
+        queue.push(fragment(0, start), start);
+        queue.push(fragment(1024, start + Duration::from_millis(100)), start);
+
+        let (first, discontinuity) = queue.next(start).unwrap();
+        assert_eq!((first.decode_time, discontinuity), (0, false));
+        // Crédit épuisé : le fragment suivant attend
+        assert!(queue.next(start).is_none());
+
+        // Client lent : le fragment est périmé quand le crédit arrive
+        queue.push(fragment(2048, start + Duration::from_millis(700)), start + Duration::from_millis(700));
+        queue.grant(2);
+        let (next, discontinuity) = queue.next(start + Duration::from_millis(700)).unwrap();
+        assert_eq!((next.decode_time, discontinuity), (2048, true));
+        assert_eq!((queue.dropped(), queue.credits()), (1, 1));
+
+        let frame = WsFrame { frame_type: FrameType::Media, flags: FLAG_DISCONTINUITY, sequence: 7, timestamp: 2048, payload: vec![1, 2] };
+        assert_eq!(WsFrame::decode(&frame.encode()).unwrap(), frame);
+        assert_eq!(WsFrame::decode(&WsFrame::credit(8).encode()).unwrap().credits(), Some(8));
+        assert!(WsFrame::decode(&[2; HEADER_SIZE]).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_client_receives_init_then_credited_fragments() {
+        let streams = Arc::new(StreamManager::new(StreamConfig::default()).unwrap());
+        let ingest = Arc::new(LiveIngest::new(streams.clone(), LiveIngestConfig::default()));
+        let key = ingest.issue_key(3, "WS room".to_string(), None).unwrap();
+        let mut source = ingest.connect(&key.key, "test", "/ws").unwrap();
+        let block = |index: usize| DecodedAudio {
+            samples: (0..4800)
+                .flat_map(|i| {
+                    let sample = ((index * 4800 + i) as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin() * 0.5;
+                    [sample, sample]
+                })
+                .collect(),
+            sample_rate: 48000,
+            channels: 2,
+            duration_ms: 100,
+            format: AudioSampleFormat::F32,
+        };
+        source.push(&block(0)).await.unwrap();
+        let stream_id = source.stream_id().unwrap();
+
+        let relays = Arc::new(WsAudioRelays::new(streams.clone(), WsAudioConfig::default()));
+        let app_config = Arc::new(Config::from_env().unwrap());
+        let app = ws_audio_routes::<()>(app_config.clone(), relays);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Sans signature valide, la connexion est refusée
+        let unsigned = format!("ws://{}/streams/{}?credits=2&expires=1&sig=00", address, stream_id);
+        assert!(tokio_tungstenite::connect_async(unsigned).await.is_err());
+
+        let signed = signed_query(&app_config, &stream_id.to_string());
+        let mut request = format!("ws://{}/streams/{}?credits=2&{}", address, stream_id, signed).into_client_request().unwrap();
+        request.headers_mut().insert("sec-websocket-protocol", SUBPROTOCOL.parse().unwrap());
+        let (mut client, response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        assert_eq!(response.headers()["sec-websocket-protocol"], SUBPROTOCOL);
+
+        let config = next_frame(&mut client).await;
+        assert_eq!((config.frame_type, config.sequence), (FrameType::CodecConfig, 0));
+        let config: CodecConfig = serde_json::from_slice(&config.payload).unwrap();
+        assert_eq!(config.mime_type, format!("audio/mp4; codecs=\"{}\"", codecs_string(DEFAULT_FMP4_CODEC).unwrap()));
+        assert!(config.listener_id.is_some());
+        let init = next_frame(&mut client).await;
+        assert_eq!(init.frame_type, FrameType::Init);
+        assert_eq!(&init.payload[4..8], b"ftyp");
+
+        let pusher = tokio::spawn(async move {
+            for index in 1..40 {
+                source.push(&block(index)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            source
+        });
+
+        let mut media = Vec::new();
+        while media.len() < 4 {
+            let frame = next_frame(&mut client).await;
+            if frame.frame_type == FrameType::Media {
+                assert!(frame.payload.windows(4).any(|window| window == b"moof"));
+                media.push(frame);
+                // Crédits accordés au fil de la lecture, après les deux premiers
+                if media.len() == 2 {
+                    client.send(ClientMessage::Binary(WsFrame::credit(2).encode())).await.unwrap();
+                }
+            }
+        }
+        assert!(media.windows(2).all(|pair| pair[1].timestamp > pair[0].timestamp && pair[1].sequence > pair[0].sequence));
+
+        drop(pusher.await.unwrap());
+        let _ = client.close(None).await;
+    }
+}
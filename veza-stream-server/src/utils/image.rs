@@ -0,0 +1,198 @@
+//! Manipulation des pochettes : détection du format, décodage, recadrage
+//! carré et réduction en vignettes JPEG
+//!
+//! Décodage et encodage passent par la crate `image` (JPEG et PNG).
+
+use std::io::Cursor;
+
+use ::image::codecs::jpeg::JpegEncoder;
+use ::image::{ExtendedColorType, ImageReader, Limits};
+
+use crate::error::AppError;
+use crate::utils::png;
+
+/// Nombre maximal de pixels d'une pochette décodée
+const MAX_PIXELS: u64 = 40_000_000;
+
+/// Formats d'image reconnus dans les pochettes embarquées
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Bmp,
+    WebP,
+}
+
+impl ImageFormat {
+    /// Détecte le format d'après les octets magiques
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(&png::PNG_SIGNATURE) {
+            Some(Self::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if data.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(Self::WebP)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Gif => "image/gif",
+            Self::Bmp => "image/bmp",
+            Self::WebP => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Gif => "gif",
+            Self::Bmp => "bmp",
+            Self::WebP => "webp",
+        }
+    }
+}
+
+/// Image RGB 8 bits (3 octets par pixel, ligne par ligne)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbImage {
+    /// Décode un JPEG ou un PNG ; la transparence est aplatie sur fond blanc
+    pub fn decode(data: &[u8]) -> Result<Self, AppError> {
+        let format = match ImageFormat::sniff(data) {
+            Some(ImageFormat::Jpeg) => ::image::ImageFormat::Jpeg,
+            Some(ImageFormat::Png) => ::image::ImageFormat::Png,
+            Some(format) => return Err(AppError::UnsupportedCodec { codec: format.mime_type().to_string() }),
+            None => return Err(AppError::InvalidData { message: "Format d'image inconnu".to_string() }),
+        };
+
+        let mut limits = Limits::default();
+        limits.max_alloc = Some(MAX_PIXELS * 4);
+        let mut reader = ImageReader::with_format(Cursor::new(data), format);
+        reader.limits(limits);
+        let decoded = reader.decode()
+            .map_err(|e| AppError::InvalidData { message: format!("Image illisible: {}", e) })?;
+
+        if !decoded.color().has_alpha() {
+            let rgb = decoded.to_rgb8();
+            return Ok(Self { width: rgb.width(), height: rgb.height(), pixels: rgb.into_raw() });
+        }
+        let rgba = decoded.to_rgba8();
+        let pixels = rgba
+            .as_raw()
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let alpha = u32::from(p[3]);
+                [0, 1, 2].map(|c| ((u32::from(p[c]) * alpha + 255 * (255 - alpha) + 127) / 255) as u8)
+            })
+            .collect();
+        Ok(Self { width: rgba.width(), height: rgba.height(), pixels })
+    }
+
+    /// Recadre au carré central
+    pub fn crop_square(&self) -> Self {
+        let side = self.width.min(self.height);
+        let (left, top) = ((self.width - side) / 2, (self.height - side) / 2);
+        let mut pixels = Vec::with_capacity(side as usize * side as usize * 3);
+        for y in top..top + side {
+            let start = (y as usize * self.width as usize + left as usize) * 3;
+            pixels.extend_from_slice(&self.pixels[start..start + side as usize * 3]);
+        }
+        Self { width: side, height: side, pixels }
+    }
+
+    /// Redimensionne par moyenne de surface ; adapté à la réduction, et
+    /// équivalent au plus proche voisin en agrandissement
+    pub fn resize(&self, width: u32, height: u32) -> Self {
+        let (src_w, src_h) = (self.width as usize, self.height as usize);
+        let (dst_w, dst_h) = (width.max(1) as usize, height.max(1) as usize);
+        let mut pixels = Vec::with_capacity(dst_w * dst_h * 3);
+        for y in 0..dst_h {
+            let y0 = y * src_h / dst_h;
+            let y1 = ((y + 1) * src_h / dst_h).max(y0 + 1);
+            for x in 0..dst_w {
+                let x0 = x * src_w / dst_w;
+                let x1 = ((x + 1) * src_w / dst_w).max(x0 + 1);
+                let mut sum = [0u64; 3];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let p = &self.pixels[(sy * src_w + sx) * 3..][..3];
+                        for c in 0..3 {
+                            sum[c] += u64::from(p[c]);
+                        }
+                    }
+                }
+                let count = ((y1 - y0) * (x1 - x0)) as u64;
+                pixels.extend(sum.map(|s| ((s + count / 2) / count) as u8));
+            }
+        }
+        Self { width: dst_w as u32, height: dst_h as u32, pixels }
+    }
+
+    /// Vignette carrée de `size` pixels de côté (sans agrandissement)
+    pub fn thumbnail(&self, size: u32) -> Self {
+        let square = self.crop_square();
+        let side = size.min(square.width);
+        if side == square.width {
+            square
+        } else {
+            square.resize(side, side)
+        }
+    }
+
+    pub fn to_jpeg(&self, quality: u8) -> Result<Vec<u8>, AppError> {
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, quality.clamp(1, 100))
+            .encode(&self.pixels, self.width, self.height, ExtendedColorType::Rgb8)
+            .map_err(|e| AppError::EncodingError { message: format!("Encodage JPEG: {}", e) })?;
+        Ok(jpeg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thumbnail_from_png_cover() {
+        // Bandeau 40x20 : moitié gauche rouge, moitié droite bleue semi-transparente
+        let mut rgba = Vec::new();
+        for _ in 0..20 {
+            for x in 0..40 {
+                rgba.extend(if x < 20 { [255, 0, 0, 255] } else { [0, 0, 255, 128] });
+            }
+        }
+        let png = png::encode_rgba(40, 20, &rgba).unwrap();
+        assert_eq!(ImageFormat::sniff(&png), Some(ImageFormat::Png));
+
+        let image = RgbImage::decode(&png).unwrap();
+        assert_eq!(&image.pixels[..3], &[255, 0, 0]);
+        assert_eq!(&image.pixels[39 * 3..40 * 3], &[127, 127, 255]);
+
+        // Carré central 20x20 (colonnes 10 à 29) réduit à 4x4
+        let thumbnail = image.thumbnail(4);
+        assert_eq!((thumbnail.width, thumbnail.height), (4, 4));
+        assert_eq!(&thumbnail.pixels[..3], &[255, 0, 0]);
+        assert_eq!(&thumbnail.pixels[3 * 3..4 * 3], &[127, 127, 255]);
+        assert_eq!(image.thumbnail(600).width, 20);
+
+        let jpeg = thumbnail.to_jpeg(85).unwrap();
+        assert_eq!(ImageFormat::sniff(&jpeg), Some(ImageFormat::Jpeg));
+        assert_eq!(RgbImage::decode(&jpeg).unwrap().width, 4);
+    }
+}
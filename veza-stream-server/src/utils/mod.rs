@@ -1,19 +1,29 @@
 // file: qstream_server/src/utils.rs
 
+pub mod image;
 pub mod metrics;
+pub mod png;
 pub mod signature;
 
 use crate::Config;
+use crate::audio::tags::TrackTags;
+use crate::audio::tags::write::{self, ByteSource, Piece};
+use crate::cache::FileMetadata;
 use crate::error::{AppError, Result};
+use crate::soundcloud::upload::{FileStorage, StorageStream, StoredFile};
 use axum::{
     body::Body,
-    http::{HeaderMap, HeaderValue, StatusCode},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::Response,
 };
+use headers::{ETag, HeaderMapExt, IfNoneMatch, IfRange, LastModified};
+use futures::{StreamExt, TryStreamExt};
+use std::future::Future;
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
 
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio_util::io::ReaderStream;
 
 pub use metrics::*;
@@ -60,16 +70,25 @@ pub fn parse_range(header: &str, file_size: u64) -> Option<(u64, u64)> {
     let range_str = header.strip_prefix("bytes=")?;
     
     if let Some((start_str, end_str)) = range_str.split_once('-') {
-        let start = if start_str.is_empty() {
-            0
-        } else {
-            start_str.parse::<u64>().ok()?
-        };
+        if file_size == 0 {
+            return None;
+        }
+
+        // Suffix range : les N derniers octets du fichier
+        if start_str.is_empty() {
+            let suffix_len = end_str.parse::<u64>().ok()?;
+            if suffix_len == 0 {
+                return None;
+            }
+            return Some((file_size.saturating_sub(suffix_len), file_size - 1));
+        }
+
+        let start = start_str.parse::<u64>().ok()?;
         
         let end = if end_str.is_empty() {
-            file_size.saturating_sub(1)
+            file_size - 1
         } else {
-            end_str.parse::<u64>().ok()?.min(file_size.saturating_sub(1))
+            end_str.parse::<u64>().ok()?.min(file_size - 1)
         };
         
         if start <= end && start < file_size {
@@ -82,59 +101,208 @@ pub fn parse_range(header: &str, file_size: u64) -> Option<(u64, u64)> {
     }
 }
 
+/// Sert un fichier audio en honorant `Range`, `If-Range` et `If-None-Match`.
+///
+/// Les plages plus grandes que `max_range_size` sont tronquées : le client
+/// reçoit un `206` partiel et redemande la suite, ce que font tous les lecteurs.
 pub async fn serve_partial_file(
     config: &Config,
     path: PathBuf,
+    metadata: &FileMetadata,
     headers: HeaderMap,
 ) -> Result<Response<Body>> {
-    let file = File::open(&path).await
-        .map_err(|_| AppError::FileNotFound)?;
-    
-    let metadata = file.metadata().await
-        .map_err(|_| AppError::InternalError { message: "Failed to read file metadata".to_string() })?;
-    
-    let file_size = metadata.len();
-    
-    if let Some(range_header) = headers.get("range") {
-        if let Ok(range_str) = range_header.to_str() {
-            if let Some((start, end)) = parse_range(range_str, file_size) {
-                let content_length = end - start + 1;
-                
-                if content_length > config.max_range_size {
-                    return Err(AppError::InvalidRange);
-                }
-                
-                let mut file = file;
-                let mut buffer = vec![0; content_length as usize];
-                file.read_exact(&mut buffer).await
-                    .map_err(|_| AppError::InternalError { message: "Failed to read file range".to_string() })?;
-                
-                let mut response = Response::builder()
-                    .status(StatusCode::PARTIAL_CONTENT)
-                    .header("Content-Length", content_length.to_string())
-                    .header("Content-Range", format!("bytes {}-{}/{}", start, end, file_size))
-                    .header("Accept-Ranges", "bytes");
-                
-                add_security_headers(&mut response);
-                
-                return Ok(response.body(Body::from(buffer))
-                    .map_err(|_| AppError::InternalError { message: "Failed to create response".to_string() })?);
+    serve_partial_body(config, metadata, headers, |start, length| async move {
+        let mut file = File::open(&path).await
+            .map_err(|_| AppError::FileNotFound)?;
+        file.seek(SeekFrom::Start(start)).await
+            .map_err(|_| AppError::InternalError { message: "Failed to read file range".to_string() })?;
+        Ok(Body::from_stream(ReaderStream::new(file.take(length))))
+    })
+    .await
+}
+
+/// Comme `serve_partial_file`, pour un fichier du backend de stockage :
+/// seule la plage demandée est lue, en streaming.
+pub async fn serve_stored_file(
+    config: &Config,
+    storage: &(dyn FileStorage + Send + Sync),
+    file: &StoredFile,
+    headers: HeaderMap,
+) -> Result<Response<Body>> {
+    let metadata = FileMetadata::new(file.size, file.created_at, file.content_type.clone());
+    serve_partial_body(config, &metadata, headers, |start, length| async move {
+        Ok(Body::from_stream(storage.read_range(&file.id, start, length).await?))
+    })
+    .await
+}
+
+/// En-tête `Content-Disposition` de téléchargement : nom ASCII de repli et
+/// nom UTF-8 complet (RFC 6266)
+pub fn attachment_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && !matches!(c, '"' | '\\' | '/') || c == ' ' { c } else { '_' })
+        .collect();
+    let encoded = percent_encoding::utf8_percent_encode(filename, percent_encoding::NON_ALPHANUMERIC);
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+/// Octets d'un fichier du backend de stockage, lus par plages
+struct StoredFileSource<'a> {
+    storage: &'a (dyn FileStorage + Send + Sync),
+    file: &'a StoredFile,
+}
+
+#[async_trait::async_trait]
+impl ByteSource for StoredFileSource<'_> {
+    fn size(&self) -> u64 {
+        self.file.size
+    }
+
+    async fn read_at(&self, start: u64, length: u64) -> Result<Vec<u8>> {
+        let length = length.min(self.file.size.saturating_sub(start));
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+        let chunks: Vec<_> = self.storage.read_range(&self.file.id, start, length).await?
+            .try_collect()
+            .await
+            .map_err(|e| AppError::InternalError { message: format!("Failed to read file range: {}", e) })?;
+        Ok(chunks.concat())
+    }
+}
+
+/// Comme `serve_stored_file`, avec les tags `tags` réécrits dans le fichier
+/// servi. Seuls les tags sont produits en mémoire ; le flux audio est lu par
+/// plages depuis le stockage. Un conteneur non réécrivable est servi tel quel.
+pub async fn serve_tagged_file(
+    config: &Config,
+    storage: &(dyn FileStorage + Send + Sync),
+    file: &StoredFile,
+    tags: &TrackTags,
+    headers: HeaderMap,
+) -> Result<Response<Body>> {
+    let layout = match write::plan(&StoredFileSource { storage, file }, tags).await {
+        Ok(layout) => layout,
+        Err(AppError::UnsupportedCodec { .. }) => return serve_stored_file(config, storage, file, headers).await,
+        Err(e) => return Err(e),
+    };
+    let mut metadata = FileMetadata::new(layout.size(), file.created_at, file.content_type.clone());
+    // L'ETag change avec les tags, sans quoi un If-Range mélangerait deux versions
+    metadata.etag = format!("\"{}-{}\"", metadata.etag.trim_matches('"'), layout.digest());
+    serve_partial_body(config, &metadata, headers, |start, length| async move {
+        let mut streams: Vec<StorageStream> = Vec::new();
+        for piece in layout.range(start, length) {
+            streams.push(match piece {
+                Piece::Data(data) => Box::pin(futures::stream::once(async move { Ok(bytes::Bytes::from(data)) })),
+                Piece::Source { start, length } => storage.read_range(&file.id, start, length).await?,
+            });
+        }
+        Ok(Body::from_stream(futures::stream::iter(streams).flatten()))
+    })
+    .await
+}
+
+/// Négociation commune des requêtes conditionnelles et partielles ;
+/// `open(début, longueur)` fournit le corps de la plage retenue.
+async fn serve_partial_body<F, Fut>(
+    config: &Config,
+    metadata: &FileMetadata,
+    headers: HeaderMap,
+    open: F,
+) -> Result<Response<Body>>
+where
+    F: FnOnce(u64, u64) -> Fut,
+    Fut: Future<Output = Result<Body>>,
+{
+    let file_size = metadata.size;
+    let etag = metadata.etag.parse::<ETag>().ok();
+    let last_modified = LastModified::from(metadata.modified);
+
+    // Le client possède déjà la version courante
+    if let (Some(if_none_match), Some(etag)) = (headers.typed_get::<IfNoneMatch>(), etag.as_ref()) {
+        if !if_none_match.precondition_passes(etag) {
+            let mut response = Response::builder().status(StatusCode::NOT_MODIFIED);
+            add_validator_headers(&mut response, etag.clone(), last_modified);
+            return response.body(Body::empty())
+                .map_err(|_| AppError::InternalError { message: "Failed to create response".to_string() });
+        }
+    }
+
+    // Un If-Range qui ne correspond plus invalide la plage : on renvoie le fichier complet
+    let range_still_valid = headers.typed_get::<IfRange>()
+        .is_none_or(|if_range| !if_range.is_modified(etag.as_ref(), Some(&last_modified)));
+
+    let requested_range = headers.get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| range_still_valid && value.starts_with("bytes=") && !value.contains(','));
+
+    let range = match requested_range {
+        Some(range_str) => match parse_range(range_str, file_size) {
+            Some((start, end)) => {
+                let max_len = config.max_range_size.max(1);
+                Some((start, end.min(start.saturating_add(max_len - 1))))
             }
+            None => return range_not_satisfiable(file_size),
+        },
+        None => None,
+    };
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, metadata.mime_type.as_str())
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let (start, content_length) = match range {
+        Some((start, end)) => {
+            response = response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, file_size));
+            (start, end - start + 1)
         }
+        None => {
+            response = response.status(StatusCode::OK);
+            (0, file_size)
+        }
+    };
+    let body = open(start, content_length).await?;
+
+    response = response.header(header::CONTENT_LENGTH, content_length.to_string());
+    if let Some(etag) = etag {
+        add_validator_headers(&mut response, etag, last_modified);
     }
-    
-    let stream = ReaderStream::new(file);
-    let body = Body::from_stream(stream);
-    
+    add_security_headers(&mut response);
+
+    response.body(body)
+        .map_err(|_| AppError::InternalError { message: "Failed to create response".to_string() })
+}
+
+fn range_not_satisfiable(file_size: u64) -> Result<Response<Body>> {
     let mut response = Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Length", file_size.to_string())
-        .header("Accept-Ranges", "bytes");
-    
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(header::CONTENT_RANGE, format!("bytes */{}", file_size))
+        .header(header::ACCEPT_RANGES, "bytes");
+
     add_security_headers(&mut response);
-    
-    Ok(response.body(body)
-        .map_err(|_| AppError::InternalError { message: "Failed to create response".to_string() })?)
+
+    response.body(Body::empty())
+        .map_err(|_| AppError::InternalError { message: "Failed to create response".to_string() })
+}
+
+fn add_validator_headers(response: &mut axum::http::response::Builder, etag: ETag, last_modified: LastModified) {
+    if let Some(headers) = response.headers_mut() {
+        headers.typed_insert(etag);
+        headers.typed_insert(last_modified);
+    }
+}
+
+/// Requête `expires=…&sig=…` valide cinq minutes, pour les tests des routes signées
+#[cfg(test)]
+pub(crate) fn signed_query(config: &Config, filename: &str) -> String {
+    let expires = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64 + 300;
+    format!("expires={}&sig={}", expires, generate_signature(filename, expires, &config.secret_key))
 }
 
 pub fn validate_signature(config: &Config, filename: &str, expires: &str, sig: &str) -> bool {
@@ -158,7 +326,7 @@ pub fn validate_signature(config: &Config, filename: &str, expires: &str, sig: &
     expected_sig.as_bytes().ct_eq(sig.as_bytes()).into()
 }
 
-fn generate_signature(filename: &str, expires: i64, secret: &str) -> String {
+pub(crate) fn generate_signature(filename: &str, expires: i64, secret: &str) -> String {
     use hmac::{Hmac, Mac};
     use sha2::Sha256;
     
@@ -211,6 +379,11 @@ mod tests {
         // Test range invalide
         assert_eq!(parse_range("bytes=2048-", 2048), None);
         assert_eq!(parse_range("invalid", 2048), None);
+
+        // Suffix plus grand que le fichier, suffix nul, fichier vide
+        assert_eq!(parse_range("bytes=-4096", 2048), Some((0, 2047)));
+        assert_eq!(parse_range("bytes=-0", 2048), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
     }
 
     #[test]
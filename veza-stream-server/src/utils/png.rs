@@ -0,0 +1,34 @@
+//! Encodage PNG des images calculées côté serveur
+//!
+//! Waveforms et spectrogrammes sont rendus dans un tampon RGBA 8 bits puis
+//! encodés par la crate `image`.
+
+use ::image::codecs::png::PngEncoder;
+use ::image::{ExtendedColorType, ImageEncoder};
+
+use crate::error::AppError;
+
+/// En-tête commun à tous les fichiers PNG
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Encode des pixels RGBA (4 octets par pixel, ligne par ligne) en PNG
+pub fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, AppError> {
+    if width == 0 || height == 0 || rgba.len() != width as usize * height as usize * 4 {
+        return Err(AppError::EncodingError {
+            message: format!("Tampon PNG incohérent: {} octets pour {}x{}", rgba.len(), width, height),
+        });
+    }
+
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .write_image(rgba, width, height, ExtendedColorType::Rgba8)
+        .map_err(|e| AppError::EncodingError { message: format!("Encodage PNG: {}", e) })?;
+    Ok(png)
+}
+
+/// Décode un PNG produit par `encode_rgba` : dimensions et pixels RGBA
+#[cfg(test)]
+pub(crate) fn decode_rgba(png: &[u8]) -> (u32, u32, Vec<u8>) {
+    let decoded = ::image::load_from_memory_with_format(png, ::image::ImageFormat::Png).unwrap().to_rgba8();
+    (decoded.width(), decoded.height(), decoded.into_raw())
+}
@@ -6,6 +6,7 @@ use std::{
     time::Instant,
 };
 use serde::{Serialize, Deserialize};
+use stream_server::audio::tags::{self, TrackTags};
 use tracing::{info, error, warn, debug};
 
 #[derive(Parser)]
@@ -43,6 +44,11 @@ struct Args {
     /// Préserver les métadonnées
     #[arg(long, default_value = "true")]
     preserve_metadata: bool,
+
+    /// Métadonnées de la plateforme (`<nom>.json` au format `TrackTags`),
+    /// prioritaires sur les tags de la source
+    #[arg(long)]
+    metadata_dir: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -176,10 +182,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let force = args.force;
             let preserve_metadata = args.preserve_metadata;
             let ffmpeg_cmd = ffmpeg_cmd.to_string();
+            let metadata_dir = args.metadata_dir.clone();
 
             let handle = tokio::spawn(async move {
                 let _permit = permit;
-                transcode_file(&audio_file, &quality, &output_dir, &ffmpeg_cmd, force, preserve_metadata).await
+                transcode_file(
+                    &audio_file,
+                    &quality,
+                    &output_dir,
+                    &ffmpeg_cmd,
+                    force,
+                    preserve_metadata,
+                    metadata_dir.as_deref(),
+                )
+                .await
             });
 
             handles.push(handle);
@@ -231,6 +247,7 @@ async fn transcode_file(
     ffmpeg_cmd: &str,
     force: bool,
     preserve_metadata: bool,
+    metadata_dir: Option<&str>,
 ) -> TranscodeResult {
     let file_stem = input_path.file_stem().unwrap().to_str().unwrap();
     let output_path = PathBuf::from(output_dir)
@@ -276,6 +293,12 @@ async fn transcode_file(
     match cmd.output() {
         Ok(output) => {
             if output.status.success() {
+                // ffmpeg ne reporte qu'une partie des champs : tags réécrits en ID3v2.4
+                if preserve_metadata {
+                    if let Err(e) = write_tags(input_path, &output_path, metadata_dir).await {
+                        warn!("⚠️  Tags non écrits pour {}: {}", file_stem, e);
+                    }
+                }
                 info!("✅ Transcodé: {} ({} kbps)", file_stem, quality.bitrate_kbps);
                 TranscodeResult::Success
             } else {
@@ -291,6 +314,31 @@ async fn transcode_file(
     }
 }
 
+/// Écrit dans le fichier produit les métadonnées de la plateforme ou, à
+/// défaut, celles de la source (pochette, ISRC, licence compris)
+async fn write_tags(
+    input_path: &Path,
+    output_path: &Path,
+    metadata_dir: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let file_stem = input_path.file_stem().unwrap().to_string_lossy();
+    let sidecar = metadata_dir
+        .map(|dir| PathBuf::from(dir).join(format!("{}.json", file_stem)))
+        .filter(|path| path.exists());
+    let tags: TrackTags = match sidecar {
+        Some(path) => serde_json::from_slice(&tokio::fs::read(&path).await?)?,
+        None => tags::read_tags(input_path)?,
+    };
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    let encoded = tokio::fs::read(output_path).await?;
+    let tagged = tags::write::tag_bytes(encoded, &tags).await?;
+    tokio::fs::write(output_path, tagged).await?;
+    Ok(())
+}
+
 fn check_ffmpeg_available(ffmpeg_cmd: &str) -> bool {
     Command::new(ffmpeg_cmd)
         .arg("-version")